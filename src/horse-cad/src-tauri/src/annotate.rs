@@ -0,0 +1,227 @@
+use fidget::render::{View3, VoxelRenderConfig, VoxelSize};
+use image::{Rgba, RgbaImage};
+use nalgebra::{Point3, Vector2};
+
+/// Color of dimension lines, arrows and angle arcs
+const LINE_COLOR: Rgba<u8> = Rgba([20, 80, 200, 255]);
+
+/// Color of callout text
+const TEXT_COLOR: Rgba<u8> = Rgba([20, 20, 20, 255]);
+
+/// Backing behind callout text, so it reads over the model
+const LABEL_BACKGROUND: Rgba<u8> = Rgba([255, 255, 255, 235]);
+
+/// Width and height of each `glyph`, in font pixels
+const GLYPH_SIZE: (u32, u32) = (5, 7);
+
+/// A dimension to draw, with its points already in model space
+pub struct Callout {
+    /// Two points for a length, or three for the angle at the second
+    pub points: Vec<[f32; 3]>,
+    pub label: String,
+}
+
+/// Draw `callouts` over `image`, a render of `view`
+///
+/// A length gets a line with arrows at both ends and its label beside the
+/// middle; an angle gets its two legs and an arc at the corner, labelled
+/// inside the arc.  Lines and text scale with the image, so callouts stay
+/// legible on large renders.
+pub fn draw_callouts(image: &mut RgbaImage, view: View3, callouts: &[Callout]) {
+    let (width, height) = image.dimensions();
+    let config = VoxelRenderConfig {
+        image_size: VoxelSize::new(width, height, width.max(height)),
+        view,
+        ..Default::default()
+    };
+    let Some(to_screen) = config.mat().try_inverse() else { return };
+    let project = |p: &[f32; 3]| {
+        let q = to_screen.transform_point(&Point3::from(*p));
+        Vector2::new(q.x, q.y)
+    };
+
+    let scale = (width.max(height) / 400).max(1);
+    let thickness = scale as f32;
+    for callout in callouts {
+        let points: Vec<Vector2<f32>> = callout.points.iter().map(project).collect();
+        match points.as_slice() {
+            [a, b] => {
+                draw_line(image, *a, *b, thickness);
+                draw_arrow(image, *a, *b, thickness);
+                draw_arrow(image, *b, *a, thickness);
+                // Beside the line rather than over it, on the side facing up the image
+                let along = (b - a).try_normalize(f32::EPSILON).unwrap_or(Vector2::x());
+                let mut side = Vector2::new(along.y, -along.x);
+                if side.y > 0.0 {
+                    side = -side;
+                }
+                let offset = (GLYPH_SIZE.1 * scale) as f32 * 1.5;
+                draw_label(image, (a + b) / 2.0 + side * offset, &callout.label, scale);
+            }
+            [a, b, c] => {
+                draw_line(image, *b, *a, thickness);
+                draw_line(image, *b, *c, thickness);
+                let radius = 12.0 * thickness;
+                let (start, end) = ((a - b).y.atan2((a - b).x), (c - b).y.atan2((c - b).x));
+                // The smaller of the two arcs between the legs
+                let mut sweep = end - start;
+                if sweep > std::f32::consts::PI {
+                    sweep -= std::f32::consts::TAU;
+                } else if sweep < -std::f32::consts::PI {
+                    sweep += std::f32::consts::TAU;
+                }
+                let arc = |t: f32| {
+                    let angle = start + sweep * t;
+                    b + Vector2::new(angle.cos(), angle.sin()) * radius
+                };
+                for i in 0..24 {
+                    draw_line(image, arc(i as f32 / 24.0), arc((i + 1) as f32 / 24.0), thickness);
+                }
+                let middle = start + sweep / 2.0;
+                let label_at = b + Vector2::new(middle.cos(), middle.sin()) * (radius * 2.2);
+                draw_label(image, label_at, &callout.label, scale);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Blend `color` over the pixel at `(x, y)`, if it's inside the image
+fn blend(image: &mut RgbaImage, x: i64, y: i64, color: Rgba<u8>) {
+    if x < 0 || y < 0 || x >= image.width() as i64 || y >= image.height() as i64 {
+        return;
+    }
+    let pixel = image.get_pixel_mut(x as u32, y as u32);
+    let alpha = color[3] as u32;
+    for i in 0..3 {
+        pixel[i] = ((color[i] as u32 * alpha + pixel[i] as u32 * (255 - alpha)) / 255) as u8;
+    }
+    // The background of a render is transparent, and callouts over it must show
+    pixel[3] = pixel[3].max(color[3]);
+}
+
+/// Draw a line `thickness` pixels wide from `a` to `b`
+fn draw_line(image: &mut RgbaImage, a: Vector2<f32>, b: Vector2<f32>, thickness: f32) {
+    let steps = (b - a).abs().max().ceil().max(1.0) as u32;
+    let half = (thickness / 2.0).floor() as i64;
+    let span = thickness as i64;
+    for i in 0..=steps {
+        let p = a + (b - a) * (i as f32 / steps as f32);
+        let (x, y) = (p.x.round() as i64 - half, p.y.round() as i64 - half);
+        for dy in 0..span {
+            for dx in 0..span {
+                blend(image, x + dx, y + dy, LINE_COLOR);
+            }
+        }
+    }
+}
+
+/// Draw an arrowhead at `tip`, pointing away from `from`
+fn draw_arrow(image: &mut RgbaImage, tip: Vector2<f32>, from: Vector2<f32>, thickness: f32) {
+    let Some(back) = (from - tip).try_normalize(f32::EPSILON) else { return };
+    let length = 6.0 * thickness;
+    for angle in [0.45f32, -0.45] {
+        let (sin, cos) = angle.sin_cos();
+        let barb = Vector2::new(back.x * cos - back.y * sin, back.x * sin + back.y * cos);
+        draw_line(image, tip, tip + barb * length, thickness);
+    }
+}
+
+/// Draw `text` centered on `center` on a light backing, `scale` image pixels per font pixel
+fn draw_label(image: &mut RgbaImage, center: Vector2<f32>, text: &str, scale: u32) {
+    let (glyph_width, glyph_height) = GLYPH_SIZE;
+    let count = text.chars().count() as u32;
+    if count == 0 {
+        return;
+    }
+    let width = (count * (glyph_width + 1) - 1) * scale;
+    let height = glyph_height * scale;
+    let left = center.x.round() as i64 - width as i64 / 2;
+    let top = center.y.round() as i64 - height as i64 / 2;
+    let pad = 2 * scale as i64;
+    for y in top - pad..top + height as i64 + pad {
+        for x in left - pad..left + width as i64 + pad {
+            blend(image, x, y, LABEL_BACKGROUND);
+        }
+    }
+    for (i, c) in text.chars().enumerate() {
+        let origin = left + (i as u32 * (glyph_width + 1) * scale) as i64;
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for col in 0..glyph_width {
+                if bits & (1 << (glyph_width - 1 - col)) == 0 {
+                    continue;
+                }
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        let x = origin + (col * scale + dx) as i64;
+                        let y = top + (row as u32 * scale + dy) as i64;
+                        blend(image, x, y, TEXT_COLOR);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Rows of the 5x7 glyph for `c`, top first, leftmost pixel in the highest bit
+///
+/// Covers what dimension labels need: digits, lowercase letters (capitals
+/// are drawn as lowercase), units like `µm` and `°`, and some punctuation.
+/// Anything else is drawn as an empty box.
+fn glyph(c: char) -> [u8; 7] {
+    match c.to_ascii_lowercase() {
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        'a' => [0x00, 0x00, 0x0E, 0x01, 0x0F, 0x11, 0x0F],
+        'b' => [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x1E],
+        'c' => [0x00, 0x00, 0x0E, 0x10, 0x10, 0x11, 0x0E],
+        'd' => [0x01, 0x01, 0x0D, 0x13, 0x11, 0x11, 0x0F],
+        'e' => [0x00, 0x00, 0x0E, 0x11, 0x1F, 0x10, 0x0E],
+        'f' => [0x06, 0x09, 0x08, 0x1C, 0x08, 0x08, 0x08],
+        'g' => [0x00, 0x0F, 0x11, 0x11, 0x0F, 0x01, 0x0E],
+        'h' => [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x11],
+        'i' => [0x04, 0x00, 0x0C, 0x04, 0x04, 0x04, 0x0E],
+        'j' => [0x02, 0x00, 0x06, 0x02, 0x02, 0x12, 0x0C],
+        'k' => [0x10, 0x10, 0x12, 0x14, 0x18, 0x14, 0x12],
+        'l' => [0x0C, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'm' => [0x00, 0x00, 0x1A, 0x15, 0x15, 0x11, 0x11],
+        'n' => [0x00, 0x00, 0x16, 0x19, 0x11, 0x11, 0x11],
+        'o' => [0x00, 0x00, 0x0E, 0x11, 0x11, 0x11, 0x0E],
+        'p' => [0x00, 0x00, 0x1E, 0x11, 0x1E, 0x10, 0x10],
+        'q' => [0x00, 0x00, 0x0D, 0x13, 0x0F, 0x01, 0x01],
+        'r' => [0x00, 0x00, 0x16, 0x19, 0x10, 0x10, 0x10],
+        's' => [0x00, 0x00, 0x0E, 0x10, 0x0E, 0x01, 0x1E],
+        't' => [0x08, 0x08, 0x1C, 0x08, 0x08, 0x09, 0x06],
+        'u' => [0x00, 0x00, 0x11, 0x11, 0x11, 0x13, 0x0D],
+        'v' => [0x00, 0x00, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'w' => [0x00, 0x00, 0x11, 0x11, 0x15, 0x15, 0x0A],
+        'x' | '×' => [0x00, 0x00, 0x11, 0x0A, 0x04, 0x0A, 0x11],
+        'y' => [0x00, 0x00, 0x11, 0x11, 0x0F, 0x01, 0x0E],
+        'z' => [0x00, 0x00, 0x1F, 0x02, 0x04, 0x08, 0x1F],
+        'µ' => [0x00, 0x00, 0x11, 0x11, 0x13, 0x1D, 0x10],
+        'ø' | 'Ø' | '⌀' => [0x01, 0x0E, 0x13, 0x15, 0x19, 0x0E, 0x10],
+        '°' => [0x0C, 0x12, 0x12, 0x0C, 0x00, 0x00, 0x00],
+        ' ' => [0x00; 7],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '+' => [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00],
+        '±' => [0x04, 0x04, 0x1F, 0x04, 0x04, 0x00, 0x1F],
+        '=' => [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00],
+        '/' => [0x01, 0x01, 0x02, 0x04, 0x08, 0x10, 0x10],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        '"' => [0x0A, 0x0A, 0x0A, 0x00, 0x00, 0x00, 0x00],
+        '\'' => [0x04, 0x04, 0x04, 0x00, 0x00, 0x00, 0x00],
+        _ => [0x1F, 0x11, 0x11, 0x11, 0x11, 0x11, 0x1F],
+    }
+}
@@ -0,0 +1,3 @@
+fn main() -> std::process::ExitCode {
+    horse_cad_lib::cli::main()
+}
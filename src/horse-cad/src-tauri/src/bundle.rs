@@ -0,0 +1,204 @@
+use std::fs::File;
+use std::io::{Cursor, Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::script::extensions::ExtensionModules;
+use crate::utils::file_utils::{sha256_hex, to_hex};
+
+/// Name of the manifest inside a bundle
+pub const BUNDLE_MANIFEST: &str = "bundle.json";
+
+/// Most files one bundle may hold, besides its manifest
+const MAX_BUNDLE_FILES: usize = 1024;
+
+/// Largest file a bundle may hold, in bytes
+const MAX_BUNDLE_FILE_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Most bytes all of a bundle's files may add up to once extracted
+const MAX_BUNDLE_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// A file packed into a bundle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleFile {
+    /// Path inside the bundle, relative to the document, with `/` separators
+    pub path: String,
+    /// SHA-256 of the file, in hex
+    pub sha256: String,
+    pub size: u64,
+}
+
+/// `bundle.json` of a shareable bundle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleManifest {
+    /// "horseCAD <version>"
+    pub generator: String,
+    /// Path of the `.horsi` document inside the bundle
+    pub document: String,
+    /// Every file in the bundle, the document first
+    pub files: Vec<BundleFile>,
+    /// Files the script loads from outside the document's folder, which can't be packed where it looks
+    #[serde(default)]
+    pub external: Vec<String>,
+}
+
+/// Pack the document at `document` and the files its script loaded into a ZIP
+///
+/// `loaded` is every file the script read, through `import` or functions
+/// loading assets such as SVGs, heightmaps and fonts.  Files in the
+/// document's folder keep their place next to it; extensions are packed
+/// next to the document under the name they're imported by, which a
+/// document's own files take over from.  Anything else, such as a file
+/// named by an absolute path, couldn't be put where the script looks for
+/// it, so it's listed in the manifest as `external` rather than packed.
+/// The script itself is packed unchanged.
+pub fn pack_bundle(
+    document: &Path,
+    loaded: &[PathBuf],
+    extensions: &ExtensionModules,
+    generator: &str,
+) -> Result<(Vec<u8>, BundleManifest)> {
+    let dir = document.parent().ok_or_else(|| anyhow!("{} isn't in a folder", document.display()))?;
+    let name = document.file_name().ok_or_else(|| anyhow!("{} isn't a file", document.display()))?;
+    let mut entries = vec![(name.to_string_lossy().into_owned(), document.to_path_buf())];
+    let mut external = Vec::new();
+    for file in loaded {
+        let inside = file.strip_prefix(dir).ok().filter(|relative| is_plain(relative));
+        let extension = extensions.iter().find(|(_, path)| *path == file).map(|(name, _)| format!("{}.rhai", name));
+        let path = match (inside, extension) {
+            (Some(relative), _) => {
+                relative.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/")
+            }
+            (None, Some(name)) => name,
+            (None, None) => {
+                external.push(file.display().to_string());
+                continue;
+            }
+        };
+        if !entries.iter().any(|(other, _)| *other == path) {
+            entries.push((path, file.clone()));
+        }
+    }
+    if entries.len() > MAX_BUNDLE_FILES {
+        bail!("The script loads more than {} files", MAX_BUNDLE_FILES);
+    }
+
+    let file_options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let mut files = Vec::with_capacity(entries.len());
+    for (path, source) in &entries {
+        let data = std::fs::read(source).with_context(|| format!("Failed to read {}", source.display()))?;
+        zip.start_file(path.as_str(), file_options)?;
+        zip.write_all(&data)?;
+        files.push(BundleFile { path: path.clone(), sha256: sha256_hex(&data), size: data.len() as u64 });
+    }
+    let manifest = BundleManifest { generator: generator.to_string(), document: entries[0].0.clone(), files, external };
+    zip.start_file(BUNDLE_MANIFEST, file_options)?;
+    serde_json::to_writer_pretty(&mut zip, &manifest).context("Failed to write bundle manifest")?;
+    let cursor = zip.finish().context("Failed to write bundle")?;
+    Ok((cursor.into_inner(), manifest))
+}
+
+/// Extract a bundle made by `pack_bundle` into `dir`, returning the path of its document
+///
+/// `dir` is created if needed and must be empty, so opening a bundle never
+/// overwrites anything.  Every file must be listed in the manifest, match
+/// its checksum, and stay inside `dir`, and together they may extract to
+/// at most `MAX_BUNDLE_BYTES`.  Files are hashed as they're written, so no
+/// file is held in memory whole; if any check fails, everything written
+/// is removed again.
+pub fn unpack_bundle(data: &[u8], dir: &Path) -> Result<(PathBuf, BundleManifest)> {
+    let mut archive = ZipArchive::new(Cursor::new(data)).context("The bundle isn't a valid zip")?;
+    let manifest: BundleManifest = {
+        let file = archive.by_name(BUNDLE_MANIFEST).context("The bundle has no manifest")?;
+        serde_json::from_reader(file.take(MAX_BUNDLE_FILE_BYTES)).context("The bundle's manifest isn't valid")?
+    };
+    if manifest.files.len() > MAX_BUNDLE_FILES {
+        bail!("The bundle has more than {} files", MAX_BUNDLE_FILES);
+    }
+    if !manifest.files.iter().any(|f| f.path == manifest.document) {
+        bail!("The bundle's document {} isn't in it", manifest.document);
+    }
+
+    // Sizes in the zip's headers can't be trusted, but catch honest oversized bundles early
+    let mut listed = 0u64;
+    for entry in &manifest.files {
+        if !is_plain(Path::new(&entry.path)) {
+            bail!("The bundle's file {} would be written outside the folder", entry.path);
+        }
+        let file = archive.by_name(&entry.path).with_context(|| format!("The bundle is missing {}", entry.path))?;
+        listed = listed.saturating_add(file.size());
+    }
+    if listed > MAX_BUNDLE_BYTES {
+        bail!("The bundle would extract to more than {} bytes", MAX_BUNDLE_BYTES);
+    }
+
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    if std::fs::read_dir(dir)?.next().is_some() {
+        bail!("{} isn't empty; open the bundle into a new folder", dir.display());
+    }
+    let mut budget = MAX_BUNDLE_BYTES;
+    let extracted = manifest.files.iter().try_for_each(|entry| extract_file(&mut archive, entry, dir, &mut budget));
+    if let Err(e) = extracted {
+        // The folder was empty, so everything in it now came from the bundle
+        if let Ok(entries) = std::fs::read_dir(dir) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let _ = if path.is_dir() { std::fs::remove_dir_all(&path) } else { std::fs::remove_file(&path) };
+            }
+        }
+        return Err(e);
+    }
+    Ok((dir.join(&manifest.document), manifest))
+}
+
+/// Copy one bundle file into `dir`, hashing it on the way, and check it against the manifest
+///
+/// `budget` is what the bundle may still extract to, and shrinks by the
+/// file's size.
+fn extract_file(
+    archive: &mut ZipArchive<Cursor<&[u8]>>,
+    entry: &BundleFile,
+    dir: &Path,
+    budget: &mut u64,
+) -> Result<()> {
+    let path = dir.join(&entry.path);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let file = archive.by_name(&entry.path).with_context(|| format!("The bundle is missing {}", entry.path))?;
+    let mut out = File::create(&path).with_context(|| format!("Failed to write {}", path.display()))?;
+    let limit = MAX_BUNDLE_FILE_BYTES.min(*budget);
+    let mut reader = file.take(limit + 1);
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+    let mut size = 0u64;
+    loop {
+        let read = reader.read(&mut buffer).with_context(|| format!("Failed to read {} from the bundle", entry.path))?;
+        if read == 0 {
+            break;
+        }
+        size += read as u64;
+        if size > limit && limit == MAX_BUNDLE_FILE_BYTES {
+            bail!("{} in the bundle is larger than {} bytes", entry.path, limit);
+        } else if size > limit {
+            bail!("The bundle would extract to more than {} bytes", MAX_BUNDLE_BYTES);
+        }
+        hasher.update(&buffer[..read]);
+        out.write_all(&buffer[..read]).with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    if !to_hex(&hasher.finalize()).eq_ignore_ascii_case(&entry.sha256) {
+        bail!("{} in the bundle doesn't match its checksum", entry.path);
+    }
+    *budget -= size;
+    Ok(())
+}
+
+/// A relative path made only of names, so joining it never leaves the folder
+fn is_plain(path: &Path) -> bool {
+    path.components().next().is_some() && path.components().all(|c| matches!(c, Component::Normal(_)))
+}
@@ -0,0 +1,367 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use serde::de::DeserializeOwned;
+
+use crate::export::{precision::PrecisionOptions, stl::StlFormat, ExportFormat, ExportOptions, ModelUnit};
+use crate::horsi::read_project_settings;
+use crate::host::ConsoleHost;
+use crate::mesh::normals::NormalOptions;
+use crate::state::CancelToken;
+use crate::{
+    batch_export_folder, build_model, write_export_file, BatchProgress, BuildSettings, CompileTimings, ScriptSource,
+};
+
+const USAGE: &str = "\
+Usage: horsecad build <script.horsi> [options]
+       horsecad batch <folder> -o <output folder> [options]
+       horsecad --serve
+
+Compile horseCAD scripts and export their meshes without opening the app.
+`build` exports one script; `batch` writes an STL for every .horsi script
+in a folder.  Build settings saved with a script are used unless `build` is
+given them as options; in a batch they take precedence over the defaults.
+`--serve` reads requests from stdin as one JSON object per line, such as
+{\"id\": 1, \"command\": \"compile\", \"path\": \"bracket.horsi\"}, and answers on stdout;
+commands are compile, export, analyze and cancel.
+
+Options for build:
+  -o, --output <path>         File to write; defaults to the script's name with the format's extension
+  -f, --format <format>       stl, stl_ascii, obj, ply, glb, 3mf, amf or off; defaults to the output's extension
+      --unit <unit>           Unit recorded in 3MF and AMF files: um, mm, cm, m, in or ft, or their names
+      --normals <mode>        Normals written by OBJ, PLY and GLB: smooth, flat or crease (default: smooth)
+      --crease-angle <deg>    Sharpest edge still shaded smooth by --normals crease (default: 30)
+      --precision <step>      Round every coordinate to a multiple of this, such as 0.001, for smaller files
+      --snap <distance>       Make coordinates this close across an edge equal, flattening near-axis-aligned faces
+
+Options for batch:
+  -o, --output <folder>       Folder to write the STLs into (required)
+  -s, --settings <file>       JSON object of per-script settings keyed by file name, such as
+                              {\"bracket.horsi\": {\"depth\": 8, \"params\": {\"width\": 12}}}
+      --ascii                 Write ASCII rather than binary STL
+
+Options for both, the defaults in a batch:
+  -d, --depth <n>             Octree depth
+  -q, --quality <preset>      draft, normal or fine; used when no depth is given (default: normal)
+  -p, --param <name=value>    Override a param() value; may be repeated
+      --backend <backend>     vm or jit (default: vm)
+      --target-triangles <n>  Simplify the mesh down to about this many triangles
+      --max-error <distance>  Simplify the mesh as long as it stays within this distance
+      --repair                Fix holes, flipped and duplicate triangles before exporting
+      --coarsen               After meshing, coarsen flat regions, keeping full detail where the surface curves
+      --up-axis <axis>        z for slicers or y for viewers that expect Y-up (default: z)
+  -v, --verbose               Print progress as well as warnings and errors
+  -h, --help                  Print this help";
+
+/// Arguments to `horsecad build`
+#[derive(Default)]
+struct BuildArgs {
+    script: PathBuf,
+    output: Option<PathBuf>,
+    format: Option<ExportFormat>,
+    unit: Option<ModelUnit>,
+    normals: NormalOptions,
+    precision: PrecisionOptions,
+    settings: BuildSettings,
+    verbose: bool,
+}
+
+/// Arguments to `horsecad batch`
+#[derive(Default)]
+struct BatchArgs {
+    folder: PathBuf,
+    output: PathBuf,
+    settings_file: Option<PathBuf>,
+    stl_format: StlFormat,
+    settings: BuildSettings,
+    verbose: bool,
+}
+
+/// Entry point of the `horsecad` binary
+pub fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("build") => match parse_build(&args[1..]) {
+            Ok(Some(build)) => run_build(build),
+            Ok(None) => {
+                println!("{}", USAGE);
+                ExitCode::SUCCESS
+            }
+            Err(e) => usage_error(&e),
+        },
+        Some("batch") => match parse_batch(&args[1..]) {
+            Ok(Some(batch)) => run_batch(batch),
+            Ok(None) => {
+                println!("{}", USAGE);
+                ExitCode::SUCCESS
+            }
+            Err(e) => usage_error(&e),
+        },
+        Some("serve" | "--serve") => crate::serve::run(),
+        None | Some("help" | "-h" | "--help") => {
+            println!("{}", USAGE);
+            ExitCode::SUCCESS
+        }
+        Some("-V" | "--version") => {
+            println!("horsecad {}", env!("CARGO_PKG_VERSION"));
+            ExitCode::SUCCESS
+        }
+        Some(other) => usage_error(&format!("unknown command '{}'", other)),
+    }
+}
+
+fn usage_error(message: &str) -> ExitCode {
+    eprintln!("error: {}\n\n{}", message, USAGE);
+    ExitCode::from(2)
+}
+
+/// Parse a value by the name it has in the app's settings, such as `3mf` or `fine`
+fn parse_named<T: DeserializeOwned>(flag: &str, value: &str) -> Result<T, String> {
+    serde_json::from_value(serde_json::Value::String(value.to_string()))
+        .map_err(|_| format!("invalid value '{}' for {}", value, flag))
+}
+
+/// Parse a unit by any name `set_units` accepts
+fn parse_unit(flag: &str, value: &str) -> Result<ModelUnit, String> {
+    ModelUnit::from_name(value).ok_or_else(|| {
+        format!("invalid value '{}' for {}; expected um, mm, cm, m, in or ft, or their names", value, flag)
+    })
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("invalid value '{}' for {}", value, flag))
+}
+
+/// Apply one of the options shared by `build` and `batch`, returning false if `flag` isn't one
+fn parse_setting(
+    settings: &mut BuildSettings,
+    flag: &str,
+    value: &mut dyn FnMut() -> Result<String, String>,
+) -> Result<bool, String> {
+    match flag {
+        "-d" | "--depth" => settings.depth = Some(parse_number(flag, &value()?)?),
+        "-q" | "--quality" => settings.quality = Some(parse_named(flag, &value()?)?),
+        "--backend" => settings.backend = Some(parse_named(flag, &value()?)?),
+        "-p" | "--param" => {
+            let param = value()?;
+            let (name, number) =
+                param.split_once('=').ok_or_else(|| format!("{} takes name=value, not '{}'", flag, param))?;
+            settings.params.insert(name.trim().to_string(), parse_number(flag, number.trim())?);
+        }
+        "--target-triangles" => settings.target_triangles = Some(parse_number(flag, &value()?)?),
+        "--max-error" => settings.max_error = Some(parse_number(flag, &value()?)?),
+        "--repair" => settings.repair = Some(true),
+        "--coarsen" => settings.coarsen = Some(true),
+        "--up-axis" => settings.up_axis = Some(parse_named(flag, &value()?)?),
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+/// Walk `args`, handing each option to `option` along with a way to take its
+/// value, and collecting the rest as positional arguments
+///
+/// Returns `None` if help was asked for.
+fn parse_args(
+    args: &[String],
+    mut option: impl FnMut(&str, &mut dyn FnMut() -> Result<String, String>) -> Result<bool, String>,
+) -> Result<Option<Vec<String>>, String> {
+    let mut positional = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let flag = arg.as_str();
+        let mut value = || args.next().cloned().ok_or_else(|| format!("{} needs a value", flag));
+        match flag {
+            "-h" | "--help" => return Ok(None),
+            _ if flag.starts_with('-') => {
+                if !option(flag, &mut value)? {
+                    return Err(format!("unknown option '{}'", flag));
+                }
+            }
+            _ => positional.push(arg.clone()),
+        }
+    }
+    Ok(Some(positional))
+}
+
+/// The one positional argument a subcommand takes
+fn single_positional(positional: Vec<String>, what: &str) -> Result<PathBuf, String> {
+    match <[String; 1]>::try_from(positional) {
+        Ok([path]) => Ok(PathBuf::from(path)),
+        Err(positional) if positional.is_empty() => Err(format!("no {} given", what)),
+        Err(positional) => Err(format!("unexpected argument '{}'", positional[1])),
+    }
+}
+
+/// Parse the arguments after `build`, or `None` if help was asked for
+fn parse_build(args: &[String]) -> Result<Option<BuildArgs>, String> {
+    let mut build = BuildArgs::default();
+    let positional = parse_args(args, |flag, value| {
+        match flag {
+            "-o" | "--output" => build.output = Some(PathBuf::from(value()?)),
+            "-f" | "--format" => build.format = Some(parse_named(flag, &value()?)?),
+            "--unit" => build.unit = Some(parse_unit(flag, &value()?)?),
+            "--normals" => build.normals.mode = parse_named(flag, &value()?)?,
+            "--crease-angle" => build.normals.crease_angle = parse_number(flag, &value()?)?,
+            "--precision" => build.precision.quantum = Some(parse_number(flag, &value()?)?),
+            "--snap" => build.precision.snap_tolerance = Some(parse_number(flag, &value()?)?),
+            "-v" | "--verbose" => build.verbose = true,
+            _ => return parse_setting(&mut build.settings, flag, value),
+        }
+        Ok(true)
+    })?;
+    let Some(positional) = positional else { return Ok(None) };
+    build.script = single_positional(positional, "script")?;
+    Ok(Some(build))
+}
+
+/// Parse the arguments after `batch`, or `None` if help was asked for
+fn parse_batch(args: &[String]) -> Result<Option<BatchArgs>, String> {
+    let mut batch = BatchArgs::default();
+    let mut output = None;
+    let positional = parse_args(args, |flag, value| {
+        match flag {
+            "-o" | "--output" => output = Some(PathBuf::from(value()?)),
+            "-s" | "--settings" => batch.settings_file = Some(PathBuf::from(value()?)),
+            "--ascii" => batch.stl_format = StlFormat::Ascii,
+            "-v" | "--verbose" => batch.verbose = true,
+            _ => return parse_setting(&mut batch.settings, flag, value),
+        }
+        Ok(true)
+    })?;
+    let Some(positional) = positional else { return Ok(None) };
+    batch.folder = single_positional(positional, "folder")?;
+    batch.output = output.ok_or("batch needs an output folder (-o)")?;
+    Ok(Some(batch))
+}
+
+fn run_build(args: BuildArgs) -> ExitCode {
+    let host = ConsoleHost::new(args.verbose);
+    let code = match std::fs::read_to_string(&args.script) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("error: can't read {}: {}", args.script.display(), e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let extension_format = |path: &PathBuf| {
+        path.extension().and_then(|e| e.to_str()).and_then(ExportFormat::from_extension)
+    };
+    let project = read_project_settings(&args.script).unwrap_or_default();
+    let settings = args.settings.or(&project.build);
+    let format = match (args.format, &args.output) {
+        (Some(format), _) => format,
+        (None, Some(output)) => match extension_format(output) {
+            Some(format) => format,
+            None => {
+                eprintln!("error: can't tell the format of {}; pass --format", output.display());
+                return ExitCode::from(2);
+            }
+        },
+        (None, None) => project.export_format.unwrap_or(ExportFormat::Stl),
+    };
+    let output = args.output.unwrap_or_else(|| args.script.with_extension(format.extension()));
+
+    let script_path = args.script.to_string_lossy().into_owned();
+    let source = ScriptSource::new(code, Some(&script_path));
+    let options = ExportOptions {
+        name: None,
+        unit: args.unit.or(project.unit),
+        script_name: args.script.file_name().map(|name| name.to_string_lossy().into_owned()),
+        normals: args.normals,
+        precision: args.precision,
+    };
+
+    let mut timings = CompileTimings::default();
+    let built = build_model(
+        &host,
+        &script_path,
+        &source,
+        &settings.params,
+        &settings.mesh_request(),
+        settings.repair.unwrap_or(false),
+        settings.up_axis.unwrap_or_default(),
+        format,
+        &options,
+        None,
+        &CancelToken::new(),
+        &mut timings,
+    );
+    // Failures have already been logged by the pipeline
+    let Ok(model) = built else { return ExitCode::FAILURE };
+    let output = output.to_string_lossy();
+    if write_export_file(&host, &output, &model.data, format.label()).is_err() {
+        return ExitCode::FAILURE;
+    }
+    println!("Wrote {} ({} triangles). {}", output, model.triangle_count, model.validation.summary());
+    let failed: Vec<_> = model.assertions.iter().filter(|a| !a.passed).collect();
+    if !failed.is_empty() {
+        for assertion in &failed {
+            let line = assertion.line.map(|line| format!("line {}: ", line)).unwrap_or_default();
+            eprintln!("assertion failed: {}{}: {}", line, assertion.assertion.function(), assertion.message);
+        }
+        eprintln!("{} of {} assertions failed", failed.len(), model.assertions.len());
+        return ExitCode::FAILURE;
+    }
+    if !model.assertions.is_empty() {
+        println!("All {} assertions passed", model.assertions.len());
+    }
+    ExitCode::SUCCESS
+}
+
+fn run_batch(args: BatchArgs) -> ExitCode {
+    let host = ConsoleHost::new(args.verbose);
+    let file_settings: HashMap<String, BuildSettings> = match &args.settings_file {
+        Some(path) => {
+            let parsed = std::fs::read_to_string(path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| serde_json::from_str(&text).map_err(|e| e.to_string()));
+            match parsed {
+                Ok(settings) => settings,
+                Err(e) => {
+                    eprintln!("error: can't read settings from {}: {}", path.display(), e);
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+        None => HashMap::new(),
+    };
+
+    let on_progress = |progress: &BatchProgress| {
+        println!("[{}/{}] {}", progress.index + 1, progress.total, progress.script);
+    };
+    let Ok(result) = batch_export_folder(
+        &host,
+        &args.folder,
+        &args.output,
+        &args.settings,
+        &file_settings,
+        args.stl_format,
+        &CancelToken::new(),
+        &on_progress,
+    ) else {
+        return ExitCode::FAILURE;
+    };
+
+    for file in &result.files {
+        match (&file.path, &file.error) {
+            (Some(path), _) => println!(
+                "  {} -> {} ({} triangles{}, {:.1} s)",
+                file.script,
+                path,
+                file.triangle_count,
+                if file.printable { "" } else { ", not printable" },
+                file.duration_ms / 1000.0
+            ),
+            (None, error) => println!("  {} failed: {}", file.script, error.as_deref().unwrap_or("unknown error")),
+        }
+    }
+    println!("Built {} of {} scripts", result.succeeded, result.files.len());
+    if result.failed > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
@@ -0,0 +1,152 @@
+use anyhow::{anyhow, bail, Context, Result};
+use percent_encoding::percent_decode_str;
+use tauri::AppHandle;
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+
+use crate::download::{download, MAX_DOWNLOAD_BYTES};
+use crate::emit_log;
+use crate::open::{open_untitled, SCRIPT_EXTENSION};
+use crate::templates::{template_script, templates};
+
+/// URL scheme of links that open scripts in horseCAD
+pub const DEEP_LINK_SCHEME: &str = "horsecad";
+
+/// Longest gist id accepted; GitHub's are 32 hex digits
+const MAX_GIST_ID_LENGTH: usize = 64;
+
+/// What a `horsecad://` link asks to open
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeepLink {
+    /// `horsecad://template/<id>`, a bundled template
+    Template(String),
+    /// `horsecad://open?gist=<id>`, a script shared as a GitHub gist
+    Gist(String),
+}
+
+impl DeepLink {
+    pub fn parse(url: &str) -> Result<DeepLink> {
+        let rest = url
+            .split_once("://")
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case(DEEP_LINK_SCHEME))
+            .map(|(_, rest)| rest)
+            .ok_or_else(|| anyhow!("{} isn't a {}:// link", url, DEEP_LINK_SCHEME))?;
+        let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let segments: Vec<String> = path
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .map(|s| percent_decode_str(s).decode_utf8_lossy().into_owned())
+            .collect();
+        match segments.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+            ["template", id] => {
+                if template_script(id).is_none() {
+                    let known: Vec<String> = templates().into_iter().map(|t| t.id).collect();
+                    bail!("There's no template called '{}'; templates are {}", id, known.join(", "));
+                }
+                Ok(DeepLink::Template(id.to_string()))
+            }
+            ["open"] => {
+                let gist = query
+                    .split('&')
+                    .filter_map(|pair| pair.split_once('='))
+                    .find(|(key, _)| *key == "gist")
+                    .map(|(_, value)| percent_decode_str(value).decode_utf8_lossy().into_owned())
+                    .ok_or_else(|| anyhow!("{} doesn't say what to open; expected ?gist=<id>", url))?;
+                // Links may give the gist as user/id, as its page URL does
+                let id = gist.rsplit('/').next().unwrap_or_default();
+                if id.is_empty() || id.len() > MAX_GIST_ID_LENGTH || !id.chars().all(|c| c.is_ascii_alphanumeric()) {
+                    bail!("'{}' isn't a gist id", gist);
+                }
+                Ok(DeepLink::Gist(id.to_string()))
+            }
+            _ => bail!("{} isn't a link horseCAD understands", url),
+        }
+    }
+
+    /// The question asked before opening it
+    fn confirmation(&self) -> String {
+        match self {
+            DeepLink::Template(id) => format!("A link asks to open the '{}' template in a new document.", id),
+            DeepLink::Gist(id) => format!(
+                "A link asks to download gist {} from GitHub and open it in a new document.\n\n\
+                 Scripts can read files they import, so only open ones from people you trust.",
+                id
+            ),
+        }
+    }
+
+    /// The script, and a name for where it came from
+    fn fetch(&self) -> Result<(String, String)> {
+        match self {
+            DeepLink::Template(id) => {
+                let script = template_script(id).ok_or_else(|| anyhow!("There's no template called '{}'", id))?;
+                Ok((script, format!("template {}", id)))
+            }
+            DeepLink::Gist(id) => Ok((gist_script(id)?, format!("gist {}", id))),
+        }
+    }
+}
+
+/// Links passed on the command line, as Windows and Linux do for a registered scheme
+pub fn links_in_args(args: impl IntoIterator<Item = String>) -> Vec<String> {
+    let prefix = format!("{}:", DEEP_LINK_SCHEME);
+    args.into_iter()
+        .skip(1)
+        .filter(|arg| arg.len() > prefix.len() && arg[..prefix.len()].eq_ignore_ascii_case(&prefix))
+        .collect()
+}
+
+/// Ask whether to open what `url` links to, and open it in a new document if so
+///
+/// Links can come from any web page, so nothing is fetched or opened
+/// until the user agrees.  Problems are reported in the log.
+pub fn handle_deep_link(app_handle: &AppHandle, url: &str) {
+    let link = match DeepLink::parse(url) {
+        Ok(link) => link,
+        Err(e) => {
+            emit_log(app_handle, "error", &format!("Can't open link: {}", e), Some("File"));
+            return;
+        }
+    };
+    let app = app_handle.clone();
+    app_handle
+        .dialog()
+        .message(link.confirmation())
+        .title("Open Link")
+        .kind(MessageDialogKind::Info)
+        .buttons(MessageDialogButtons::OkCancelCustom("Open".to_string(), "Cancel".to_string()))
+        .show(move |confirmed| {
+            if !confirmed {
+                return;
+            }
+            // Downloads block, and this runs on the main thread
+            std::thread::spawn(move || match link.fetch() {
+                Ok((script, source)) => {
+                    emit_log(&app, "info", &format!("Opened {} from a link", source), Some("File"));
+                    open_untitled(&app, &script);
+                }
+                Err(e) => emit_log(&app, "error", &format!("Failed to open link: {:#}", e), Some("File")),
+            });
+        });
+}
+
+/// The script in gist `id`: its `.horsi` file, else its `.rhai` file, else its only file
+fn gist_script(id: &str) -> Result<String> {
+    let url = format!("https://api.github.com/gists/{}", id);
+    let gist: serde_json::Value = serde_json::from_slice(&download(&url, MAX_DOWNLOAD_BYTES)?)
+        .with_context(|| format!("GitHub's reply for gist {} isn't JSON", id))?;
+    let files = gist["files"].as_object().ok_or_else(|| anyhow!("Gist {} has no files", id))?;
+    let with_extension = |extension: &str| {
+        files.iter().find(|(name, _)| name.to_ascii_lowercase().ends_with(&format!(".{}", extension)))
+    };
+    let (name, file) = with_extension(SCRIPT_EXTENSION)
+        .or_else(|| with_extension("rhai"))
+        .or_else(|| files.iter().next().filter(|_| files.len() == 1))
+        .ok_or_else(|| anyhow!("Gist {} has {} files and none is a .{} script", id, files.len(), SCRIPT_EXTENSION))?;
+    // GitHub leaves out the contents of large files, which have to be fetched separately
+    if file["truncated"].as_bool().unwrap_or(false) {
+        let raw = file["raw_url"].as_str().ok_or_else(|| anyhow!("Gist {} has no link to {}", id, name))?;
+        let bytes = download(raw, MAX_DOWNLOAD_BYTES)?;
+        return String::from_utf8(bytes).with_context(|| format!("{} in gist {} isn't text", name, id));
+    }
+    file["content"].as_str().map(str::to_string).ok_or_else(|| anyhow!("{} in gist {} has no contents", name, id))
+}
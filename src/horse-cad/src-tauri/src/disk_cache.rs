@@ -0,0 +1,138 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use fidget::mesh::Mesh;
+use nalgebra::{Matrix4, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::export::ModelUnit;
+use crate::mesh::color::Rgb;
+use crate::mesh::units::MeshUnits;
+use crate::script::imports::ImportStamp;
+use crate::state::{CachedCompile, LastMesh};
+use crate::utils::file_utils::write_atomically;
+
+/// Space the on-disk cache may use before it evicts, in bytes
+pub const DEFAULT_DISK_CACHE_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+
+/// Bumped whenever the entry layout or the cache key changes, so old entries are ignored
+const CACHE_FORMAT: u32 = 4;
+
+/// A cached compile as written to disk
+///
+/// The result header is kept as JSON, since some of its fields only
+/// round-trip through a self-describing format.
+#[derive(Serialize, Deserialize)]
+struct DiskEntry {
+    result: String,
+    stl_data: Vec<u8>,
+    vertices: Vec<[f32; 3]>,
+    triangles: Vec<[usize; 3]>,
+    triangle_colors: Option<Vec<Option<Rgb>>>,
+    unit: Option<ModelUnit>,
+    /// Column-major, as nalgebra stores it
+    to_model: Option<[f32; 16]>,
+    imports: Vec<(PathBuf, Option<SystemTime>)>,
+    /// `MeshOrigin` as JSON, like the result
+    origin: Option<String>,
+}
+
+/// Compiles kept in the app cache folder, so they survive a restart
+///
+/// Entries are files named after the compile cache key.  Once the folder
+/// holds more than its limit, the entries used least recently are deleted;
+/// reading an entry counts as using it.
+pub struct DiskCache {
+    dir: PathBuf,
+    limit_bytes: u64,
+}
+
+impl DiskCache {
+    pub fn new(dir: PathBuf, limit_bytes: u64) -> Self {
+        DiskCache { dir, limit_bytes }
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}-v{}.mesh", key, CACHE_FORMAT))
+    }
+
+    /// The compile kept under `key`, if there is one and the files it imported haven't changed
+    ///
+    /// Unreadable and stale entries are deleted.
+    pub fn load(&self, key: &str) -> Option<CachedCompile> {
+        let path = self.entry_path(key);
+        let data = fs::read(&path).ok()?;
+        let entry = bincode::deserialize::<DiskEntry>(&data).ok().and_then(|entry| {
+            let result = serde_json::from_str(&entry.result).ok()?;
+            let imports: Vec<ImportStamp> =
+                entry.imports.into_iter().map(|(path, modified)| ImportStamp { path, modified }).collect();
+            if !imports.iter().all(|i| i.is_current()) {
+                return None;
+            }
+            let mesh = Mesh {
+                vertices: entry.vertices.into_iter().map(Vector3::from).collect(),
+                triangles: entry.triangles.into_iter().map(Vector3::from).collect(),
+            };
+            let units = MeshUnits { unit: entry.unit, to_model: entry.to_model.map(|m| Matrix4::from_column_slice(&m)) };
+            let origin = entry.origin.and_then(|origin| serde_json::from_str(&origin).ok());
+            let mesh = Arc::new(LastMesh { mesh, triangle_colors: entry.triangle_colors, units, origin });
+            Some(CachedCompile { result, stl_data: entry.stl_data, mesh, imports })
+        });
+        match entry {
+            Some(_) => {
+                let _ = fs::File::options().write(true).open(&path).and_then(|f| f.set_modified(SystemTime::now()));
+            }
+            None => {
+                let _ = fs::remove_file(&path);
+            }
+        }
+        entry
+    }
+
+    /// Write `cached` under `key`, then trim the folder back under its limit
+    pub fn store(&self, key: &str, cached: &CachedCompile) -> Result<(), String> {
+        let entry = DiskEntry {
+            result: serde_json::to_string(&cached.result).map_err(|e| e.to_string())?,
+            stl_data: cached.stl_data.clone(),
+            vertices: cached.mesh.mesh.vertices.iter().map(|v| [v.x, v.y, v.z]).collect(),
+            triangles: cached.mesh.mesh.triangles.iter().map(|t| [t.x, t.y, t.z]).collect(),
+            triangle_colors: cached.mesh.triangle_colors.clone(),
+            unit: cached.mesh.units.unit,
+            to_model: cached.mesh.units.to_model.and_then(|m| m.as_slice().try_into().ok()),
+            imports: cached.imports.iter().map(|i| (i.path.clone(), i.modified)).collect(),
+            origin: cached.mesh.origin.as_ref().map(serde_json::to_string).transpose().map_err(|e| e.to_string())?,
+        };
+        let data = bincode::serialize(&entry).map_err(|e| e.to_string())?;
+        if data.len() as u64 > self.limit_bytes {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Failed to create mesh cache folder {}: {}", self.dir.display(), e))?;
+        let path = self.entry_path(key);
+        write_atomically(&path, &data).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        self.evict();
+        Ok(())
+    }
+
+    /// Delete the least recently used entries until the folder fits its limit
+    fn evict(&self) {
+        let Ok(files) = fs::read_dir(&self.dir) else { return };
+        let mut entries: Vec<(SystemTime, u64, PathBuf)> = files
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let metadata = entry.metadata().ok()?;
+                Some((metadata.modified().ok()?, metadata.len(), entry.path()))
+            })
+            .collect();
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.0));
+        let mut total = 0;
+        for (_, size, path) in entries {
+            total += size;
+            if total > self.limit_bytes {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+}
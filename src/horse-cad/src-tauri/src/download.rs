@@ -0,0 +1,39 @@
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use ureq::tls::{RootCerts, TlsConfig};
+use ureq::Agent;
+
+/// Largest response `download` accepts, which is far more than any script
+pub const MAX_DOWNLOAD_BYTES: u64 = 8 * 1024 * 1024;
+
+/// Seconds a download may take before it's abandoned
+const DOWNLOAD_TIMEOUT_SECS: u64 = 30;
+
+/// Fetch `url` over HTTPS
+///
+/// Redirects are followed but only to other HTTPS URLs, and responses
+/// over `max_bytes` or with an error status are refused.  Certificates
+/// are checked by the operating system, so roots it trusts, such as a
+/// company proxy's, are trusted here too.
+pub fn download(url: &str, max_bytes: u64) -> Result<Vec<u8>> {
+    if !url.starts_with("https://") {
+        bail!("Only https:// URLs can be downloaded, not {}", url);
+    }
+    let agent: Agent = Agent::config_builder()
+        .https_only(true)
+        .tls_config(TlsConfig::builder().root_certs(RootCerts::PlatformVerifier).build())
+        .timeout_global(Some(Duration::from_secs(DOWNLOAD_TIMEOUT_SECS)))
+        .user_agent(concat!("horseCAD/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .into();
+    let mut response = match agent.get(url).call() {
+        Ok(response) => response,
+        Err(e) => bail!("Failed to download {}: {}", url, e),
+    };
+    match response.body_mut().with_config().limit(max_bytes).read_to_vec() {
+        Ok(data) => Ok(data),
+        Err(ureq::Error::BodyExceedsLimit(_)) => bail!("{} is larger than {} bytes", url, max_bytes),
+        Err(e) => bail!("Failed to download {}: {}", url, e),
+    }
+}
@@ -0,0 +1,83 @@
+use std::io::Write;
+
+use anyhow::{Context as AnyhowContext, Result};
+use fidget::mesh::Mesh;
+
+use super::{xml_escape, ModelUnit};
+use crate::mesh::color::Rgb;
+
+/// Metadata embedded in the AMF object
+pub struct AmfOptions<'a> {
+    pub unit: ModelUnit,
+    pub script_name: Option<&'a str>,
+    pub part_name: &'a str,
+    /// Per-triangle colors, written on each colored triangle
+    pub colors: Option<&'a [Option<Rgb>]>,
+}
+
+/// Export mesh to an uncompressed AMF document
+pub fn export_mesh_to_amf(mesh: &Mesh, options: &AmfOptions) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    write_amf_xml(mesh, options, &mut out).context("Failed to write AMF document")?;
+    Ok(out)
+}
+
+/// AMF's name for `unit`, and the factor that converts lengths into it
+///
+/// AMF has no centimeters, so those models are written in millimeters.
+fn amf_unit(unit: ModelUnit) -> (&'static str, f32) {
+    match unit {
+        ModelUnit::Micron => ("micron", 1.0),
+        ModelUnit::Millimeter => ("millimeter", 1.0),
+        ModelUnit::Centimeter => ("millimeter", 10.0),
+        ModelUnit::Meter => ("meter", 1.0),
+        ModelUnit::Inch => ("inch", 1.0),
+        ModelUnit::Foot => ("feet", 1.0),
+    }
+}
+
+fn write_amf_xml<W: Write>(mesh: &Mesh, options: &AmfOptions, out: &mut W) -> std::io::Result<()> {
+    let (unit, scale) = amf_unit(options.unit);
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(out, r#"<amf unit="{}" version="1.1">"#, unit)?;
+    writeln!(out, r#"  <metadata type="cad">horseCAD {}</metadata>"#, env!("CARGO_PKG_VERSION"))?;
+    writeln!(out, r#"  <object id="0">"#)?;
+    writeln!(out, r#"    <metadata type="name">{}</metadata>"#, xml_escape(options.part_name))?;
+    if let Some(script_name) = options.script_name {
+        writeln!(out, r#"    <metadata type="description">Generated from {}</metadata>"#, xml_escape(script_name))?;
+    }
+    writeln!(out, r#"    <metadata type="revision">{}</metadata>"#, chrono::Utc::now().format("%Y-%m-%d"))?;
+    writeln!(out, "    <mesh>")?;
+    writeln!(out, "      <vertices>")?;
+    for v in &mesh.vertices {
+        writeln!(
+            out,
+            "        <vertex><coordinates><x>{}</x><y>{}</y><z>{}</z></coordinates></vertex>",
+            v.x * scale,
+            v.y * scale,
+            v.z * scale
+        )?;
+    }
+    writeln!(out, "      </vertices>")?;
+    writeln!(out, "      <volume>")?;
+    for (i, t) in mesh.triangles.iter().enumerate() {
+        let color = options.colors.and_then(|colors| colors.get(i).copied().flatten());
+        let vertices = format!("<v1>{}</v1><v2>{}</v2><v3>{}</v3>", t.x, t.y, t.z);
+        match color {
+            Some([r, g, b]) => writeln!(
+                out,
+                "        <triangle><color><r>{:.4}</r><g>{:.4}</g><b>{:.4}</b></color>{}</triangle>",
+                r as f32 / 255.0,
+                g as f32 / 255.0,
+                b as f32 / 255.0,
+                vertices
+            )?,
+            None => writeln!(out, "        <triangle>{}</triangle>", vertices)?,
+        }
+    }
+    writeln!(out, "      </volume>")?;
+    writeln!(out, "    </mesh>")?;
+    writeln!(out, "  </object>")?;
+    writeln!(out, "</amf>")?;
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use std::io::{Cursor, Write};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use super::{stl::StlFormat, ModelUnit, UpAxis};
+use crate::mesh::bounds::Bounds;
+
+/// Name of the manifest inside an archive
+pub const MANIFEST_NAME: &str = "manifest.json";
+
+/// One part's entry in an archive manifest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivePart {
+    pub name: String,
+    /// Path of the part's STL inside the archive
+    pub file: String,
+    pub triangle_count: usize,
+    pub vertex_count: usize,
+    /// Enclosed volume in `unit`; only meaningful for a watertight mesh
+    pub volume: f64,
+    pub surface_area: f64,
+    /// Box around the part, or `None` for an empty mesh
+    pub bounds: Option<Bounds>,
+    /// Display color as `#rrggbb`, if the script gave one
+    pub color: Option<String>,
+}
+
+/// Settings the parts in an archive were built with
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveSettings {
+    pub depth: u8,
+    pub stl_format: StlFormat,
+    pub up_axis: UpAxis,
+    pub repair: bool,
+    pub target_triangles: Option<usize>,
+    pub max_error: Option<f32>,
+    /// Script parameters the build used
+    pub params: std::collections::BTreeMap<String, f64>,
+}
+
+/// `manifest.json` of a parts archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    /// "horseCAD <version>"
+    pub generator: String,
+    /// File name of the generating script
+    pub script_name: Option<String>,
+    /// Unit the part files are in
+    pub unit: ModelUnit,
+    pub settings: ArchiveSettings,
+    pub parts: Vec<ArchivePart>,
+}
+
+/// Pack part files and their manifest into a ZIP
+///
+/// `files` pairs each path in `manifest.parts` with its encoded mesh; the
+/// manifest is written last, as `manifest.json` at the archive's root.
+pub fn export_parts_archive(files: &[(String, Vec<u8>)], manifest: &ArchiveManifest) -> Result<Vec<u8>> {
+    let file_options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for (path, data) in files {
+        zip.start_file(path.as_str(), file_options)?;
+        zip.write_all(data)?;
+    }
+    zip.start_file(MANIFEST_NAME, file_options)?;
+    serde_json::to_writer_pretty(&mut zip, manifest).context("Failed to write archive manifest")?;
+    let cursor = zip.finish().context("Failed to write parts archive")?;
+    Ok(cursor.into_inner())
+}
@@ -0,0 +1,53 @@
+use anyhow::{Context as AnyhowContext, Result};
+use dxf::{
+    entities::{Entity, EntityType, LwPolyline},
+    enums::{AcadVersion, Units},
+    tables::Layer,
+    Drawing, LwPolylineVertex,
+};
+
+use super::{sheet::Sheet, ModelUnit};
+use crate::mesh::slice::Slice;
+
+/// Export slices to DXF, laid out side by side on one sheet
+///
+/// Each outline is a closed LWPOLYLINE, and each slice gets a layer of its
+/// own, so CAM tools can pick profiles per slice.  The drawing is written
+/// as R2000, the oldest version that records units, set from `unit`.
+pub fn export_slices_to_dxf(slices: &[Slice], unit: ModelUnit) -> Result<Vec<u8>> {
+    let mut drawing = Drawing::new();
+    drawing.header.version = AcadVersion::R2000;
+    drawing.header.default_drawing_units = match unit {
+        ModelUnit::Micron => Units::Microns,
+        ModelUnit::Millimeter => Units::Millimeters,
+        ModelUnit::Centimeter => Units::Centimeters,
+        ModelUnit::Meter => Units::Meters,
+        ModelUnit::Inch => Units::Inches,
+        ModelUnit::Foot => Units::Feet,
+    };
+
+    let Sheet { offsets, .. } = Sheet::new(slices);
+    for (i, (slice, offset)) in slices.iter().zip(offsets).enumerate() {
+        let layer = format!("slice-{}", i);
+        drawing.add_layer(Layer { name: layer.clone(), ..Default::default() });
+        for contour in &slice.contours {
+            let mut polyline = LwPolyline::default();
+            polyline.set_is_closed(true);
+            polyline.vertices = contour
+                .iter()
+                .map(|p| LwPolylineVertex {
+                    x: f64::from(p[0] + offset[0]),
+                    y: f64::from(p[1] + offset[1]),
+                    ..Default::default()
+                })
+                .collect();
+            let mut entity = Entity::new(EntityType::LwPolyline(polyline));
+            entity.common.layer = layer.clone();
+            drawing.add_entity(entity);
+        }
+    }
+
+    let mut buffer = Vec::new();
+    drawing.save(&mut buffer).context("Failed to write DXF data")?;
+    Ok(buffer)
+}
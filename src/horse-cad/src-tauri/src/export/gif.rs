@@ -0,0 +1,54 @@
+use anyhow::{bail, Result};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, RgbaImage};
+
+/// Quantizer speed, from 1 for the best palette to 30 for the fastest
+///
+/// Renders are mostly shades of one material color, so a fast pass
+/// finds a good palette and a turntable of many frames stays quick.
+const QUANTIZE_SPEED: i32 = 10;
+
+/// Highest frame rate a GIF can show
+///
+/// Frame delays are whole hundredths of a second, and many viewers slow
+/// anything shorter than two of them down to ten.
+pub const MAX_GIF_FPS: u32 = 50;
+
+/// Time each frame is shown at `fps` frames per second, in hundredths of a second
+pub fn frame_delay(fps: u32) -> u16 {
+    (100.0 / fps.max(1) as f32).round().max(2.0) as u16
+}
+
+/// Encode frames as a looping animated GIF
+///
+/// Each frame gets its own palette, quantized from its colors, and each
+/// is cleared to the background before the next is drawn.  Pixels that
+/// aren't fully opaque become transparent.  `delay_cs` is the time each
+/// frame is shown, in hundredths of a second.
+pub fn export_frames_to_gif(frames: &[RgbaImage], delay_cs: u16) -> Result<Vec<u8>> {
+    let Some(first) = frames.first() else { bail!("no frames to encode") };
+    let (width, height) = first.dimensions();
+    if frames.iter().any(|f| f.dimensions() != (width, height)) {
+        bail!("frames differ in size");
+    }
+    if u16::try_from(width).is_err() || u16::try_from(height).is_err() {
+        bail!("frames are too large for a GIF");
+    }
+
+    let delay = Delay::from_numer_denom_ms(delay_cs as u32 * 10, 1);
+    let mut out = Vec::new();
+    {
+        let mut encoder = GifEncoder::new_with_speed(&mut out, QUANTIZE_SPEED);
+        encoder.set_repeat(Repeat::Infinite)?;
+        for frame in frames {
+            let mut buffer = frame.clone();
+            for pixel in buffer.pixels_mut() {
+                if pixel.0[3] < u8::MAX {
+                    pixel.0 = [0; 4];
+                }
+            }
+            encoder.encode_frame(Frame::from_parts(buffer, 0, 0, delay))?;
+        }
+    }
+    Ok(out)
+}
@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use anyhow::{bail, Result};
+use fidget::{
+    context::{BinaryOpcode, Context, Node, Op, UnaryOpcode},
+    var::Var,
+};
+
+use crate::mesh::bind::post_order;
+use crate::mesh::bounds::Bounds;
+
+/// Most sphere-tracing steps per pixel in a raymarching shader
+const RAYMARCH_STEPS: usize = 256;
+
+/// Share of each distance a ray advances, so fields that overestimate don't step through the surface
+const RAYMARCH_STEP_FACTOR: f32 = 0.8;
+
+/// Half the side of the box rays are clipped to when the shape has no known region
+const UNBOUNDED_HALF_EXTENT: f32 = 1.0e4;
+
+/// Write the shape at `root` as a self-contained GLSL function `float <name>(vec3 p)`
+///
+/// The function returns the distance field as horseCAD evaluates it:
+/// negative inside, in model units.  Each node of the graph becomes one
+/// local, so shared subexpressions are computed once.  The code needs GLSL
+/// 3.30 or GLSL ES 3.00, as ShaderToy and WebGL 2 provide.
+pub fn export_shape_to_glsl(ctx: &Context, root: Node, name: &str, source: Option<&str>) -> Result<String> {
+    let is_identifier = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !is_identifier {
+        bail!("'{}' isn't a valid GLSL function name", name);
+    }
+
+    let mut body = String::new();
+    let mut locals: HashMap<Node, String> = HashMap::new();
+    for (node, op) in post_order(ctx, &[root])? {
+        let expr = match op {
+            Op::Input(Var::X) => "p.x".to_string(),
+            Op::Input(Var::Y) => "p.y".to_string(),
+            Op::Input(Var::Z) => "p.z".to_string(),
+            Op::Input(Var::V(..)) => bail!("the shape still depends on an unbound parameter"),
+            Op::Const(c) => constant(c.0 as f32),
+            Op::Unary(op, a) => unary(op, &locals[&a]),
+            Op::Binary(op, a, b) => binary(op, &locals[&a], &locals[&b]),
+        };
+        let local = format!("v{}", locals.len());
+        writeln!(body, "    float {} = {};", local, expr)?;
+        locals.insert(node, local);
+    }
+
+    let mut out = String::new();
+    match source {
+        Some(source) => writeln!(out, "// Distance field of {}, exported by horseCAD", source)?,
+        None => writeln!(out, "// Distance field exported by horseCAD")?,
+    }
+    writeln!(out, "// Negative inside the shape, in model units")?;
+    writeln!(out, "float {}(vec3 p) {{", name)?;
+    out.push_str(&body);
+    writeln!(out, "    return {};", locals[&root])?;
+    writeln!(out, "}}")?;
+    Ok(out)
+}
+
+/// Write a GLSL ES 3.00 fragment shader that raymarches the shape at `root` directly on the GPU
+///
+/// The viewport draws it over a full-screen triangle, which shows a heavy
+/// shape at interactive rates long before a mesh at useful depth is done.
+/// Rays are clipped to `region`, and each pixel sphere-traces the distance
+/// function from `export_shape_to_glsl`, shaded by a headlight and writing
+/// its depth so the preview mixes with the rest of the scene.  Uniforms:
+///
+/// - `u_view_projection` and `u_inverse_view_projection`: the camera's
+///   model-to-clip matrix and its inverse
+/// - `u_resolution`: viewport size in pixels
+/// - `u_color`: surface color
+pub fn raymarch_shader(ctx: &Context, root: Node, region: Option<&Bounds>) -> Result<String> {
+    let (min, max) = match region {
+        Some(b) => (b.min, b.max),
+        None => ([-UNBOUNDED_HALF_EXTENT; 3], [UNBOUNDED_HALF_EXTENT; 3]),
+    };
+    // A ten-thousandth of the region, finer than an octree at any usual depth
+    let epsilon = region.map_or(1.0e-3, |b| b.half_extent() * 1.0e-4).max(f32::MIN_POSITIVE);
+    let vec3 = |v: [f32; 3]| format!("vec3({}, {}, {})", constant(v[0]), constant(v[1]), constant(v[2]));
+
+    let mut out = String::new();
+    writeln!(out, "#version 300 es")?;
+    writeln!(out, "precision highp float;")?;
+    writeln!(out)?;
+    out.push_str(&export_shape_to_glsl(ctx, root, "sdf", None)?);
+    writeln!(out)?;
+    writeln!(out, "uniform mat4 u_view_projection;")?;
+    writeln!(out, "uniform mat4 u_inverse_view_projection;")?;
+    writeln!(out, "uniform vec2 u_resolution;")?;
+    writeln!(out, "uniform vec3 u_color;")?;
+    writeln!(out, "out vec4 frag_color;")?;
+    writeln!(out)?;
+    writeln!(out, "const vec3 REGION_MIN = {};", vec3(min))?;
+    writeln!(out, "const vec3 REGION_MAX = {};", vec3(max))?;
+    writeln!(out, "const float EPSILON = {};", constant(epsilon))?;
+    writeln!(out, "const float STEP = {};", constant(RAYMARCH_STEP_FACTOR))?;
+    writeln!(out, "const int MAX_STEPS = {};", RAYMARCH_STEPS)?;
+    out.push_str(RAYMARCH_MAIN);
+    Ok(out)
+}
+
+/// Ray setup, sphere tracing and shading, after the constants `raymarch_shader` writes
+const RAYMARCH_MAIN: &str = r#"
+vec3 unproject(vec2 ndc, float z) {
+    vec4 p = u_inverse_view_projection * vec4(ndc, z, 1.0);
+    return p.xyz / p.w;
+}
+
+// Central differences on a tetrahedron, four samples rather than six
+vec3 normal_at(vec3 p) {
+    vec2 k = vec2(1.0, -1.0);
+    float h = EPSILON * 4.0;
+    return normalize(k.xyy * sdf(p + k.xyy * h) + k.yyx * sdf(p + k.yyx * h) +
+                     k.yxy * sdf(p + k.yxy * h) + k.xxx * sdf(p + k.xxx * h));
+}
+
+void main() {
+    vec2 ndc = gl_FragCoord.xy / u_resolution * 2.0 - 1.0;
+    vec3 near = unproject(ndc, -1.0);
+    vec3 far = unproject(ndc, 1.0);
+    vec3 dir = normalize(far - near);
+
+    // Only the part of the ray inside the region is traced
+    vec3 t0 = (REGION_MIN - near) / dir;
+    vec3 t1 = (REGION_MAX - near) / dir;
+    vec3 lo = min(t0, t1);
+    vec3 hi = max(t0, t1);
+    float t = max(max(lo.x, lo.y), max(lo.z, 0.0));
+    float end = min(min(hi.x, hi.y), min(hi.z, length(far - near)));
+
+    for (int i = 0; i < MAX_STEPS && t <= end; i++) {
+        vec3 p = near + dir * t;
+        float d = sdf(p);
+        if (d < EPSILON) {
+            vec3 n = normal_at(p);
+            float light = 0.25 + 0.75 * abs(dot(n, dir));
+            frag_color = vec4(u_color * light, 1.0);
+            vec4 clip = u_view_projection * vec4(p, 1.0);
+            gl_FragDepth = clip.z / clip.w * 0.5 + 0.5;
+            return;
+        }
+        t += max(d * STEP, EPSILON);
+    }
+    discard;
+}
+"#;
+
+/// A float literal GLSL reads back exactly
+fn constant(value: f32) -> String {
+    if value.is_nan() {
+        "uintBitsToFloat(0x7fc00000u)".to_string()
+    } else if value.is_infinite() {
+        let sign = if value < 0.0 { "-" } else { "" };
+        format!("{}uintBitsToFloat(0x7f800000u)", sign)
+    } else {
+        format!("{:e}", value)
+    }
+}
+
+fn unary(op: UnaryOpcode, a: &str) -> String {
+    match op {
+        UnaryOpcode::Neg => format!("-{}", a),
+        UnaryOpcode::Abs => format!("abs({})", a),
+        UnaryOpcode::Recip => format!("1.0 / {}", a),
+        UnaryOpcode::Sqrt => format!("sqrt({})", a),
+        UnaryOpcode::Square => format!("{} * {}", a, a),
+        UnaryOpcode::Floor => format!("floor({})", a),
+        UnaryOpcode::Ceil => format!("ceil({})", a),
+        UnaryOpcode::Round => format!("round({})", a),
+        UnaryOpcode::Sin => format!("sin({})", a),
+        UnaryOpcode::Cos => format!("cos({})", a),
+        UnaryOpcode::Tan => format!("tan({})", a),
+        UnaryOpcode::Asin => format!("asin({})", a),
+        UnaryOpcode::Acos => format!("acos({})", a),
+        UnaryOpcode::Atan => format!("atan({})", a),
+        UnaryOpcode::Exp => format!("exp({})", a),
+        UnaryOpcode::Ln => format!("log({})", a),
+        UnaryOpcode::Not => format!("float({} == 0.0)", a),
+    }
+}
+
+/// Operands are always locals, so no parentheses are needed
+fn binary(op: BinaryOpcode, a: &str, b: &str) -> String {
+    match op {
+        BinaryOpcode::Add => format!("{} + {}", a, b),
+        BinaryOpcode::Sub => format!("{} - {}", a, b),
+        BinaryOpcode::Mul => format!("{} * {}", a, b),
+        BinaryOpcode::Div => format!("{} / {}", a, b),
+        BinaryOpcode::Atan => format!("atan({}, {})", a, b),
+        BinaryOpcode::Min => format!("min({}, {})", a, b),
+        BinaryOpcode::Max => format!("max({}, {})", a, b),
+        BinaryOpcode::Compare => format!("sign({} - {})", a, b),
+        // GLSL's mod floors, which matches fidget's for positive divisors
+        BinaryOpcode::Mod => format!("mod({}, {})", a, b),
+        BinaryOpcode::And => format!("{} == 0.0 ? {} : {}", a, a, b),
+        BinaryOpcode::Or => format!("{} != 0.0 ? {} : {}", a, a, b),
+    }
+}
@@ -0,0 +1,137 @@
+use anyhow::{Context as AnyhowContext, Result};
+use fidget::mesh::Mesh;
+use serde_json::json;
+
+use crate::mesh::normals::{shade_normals, split_by_normals, NormalOptions};
+
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const GLB_VERSION: u32 = 2;
+const CHUNK_JSON: &[u8; 4] = b"JSON";
+const CHUNK_BIN: &[u8; 4] = b"BIN\0";
+
+const COMPONENT_FLOAT: u32 = 5126;
+const COMPONENT_UNSIGNED_INT: u32 = 5125;
+const TARGET_ARRAY_BUFFER: u32 = 34962;
+const TARGET_ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const MODE_TRIANGLES: u32 = 4;
+
+/// Export mesh to binary glTF (GLB)
+///
+/// Geometry is written indexed, with vertex normals shaded as `normals`
+/// asks and vertices duplicated along hard edges.  glTF is Y-up, so
+/// the root node carries a rotation instead of rewriting the Z-up vertices.
+pub fn export_mesh_to_glb(mesh: &Mesh, name: &str, normals: &NormalOptions) -> Result<Vec<u8>> {
+    let (mesh, normals) = split_by_normals(mesh, &shade_normals(mesh, normals));
+
+    let mut bin = Vec::with_capacity(mesh.vertices.len() * 24 + mesh.triangles.len() * 12);
+    for v in &mesh.vertices {
+        for p in v {
+            bin.extend_from_slice(&p.to_le_bytes());
+        }
+    }
+    let positions_len = bin.len();
+    for n in &normals {
+        for p in n {
+            bin.extend_from_slice(&p.to_le_bytes());
+        }
+    }
+    let normals_len = bin.len() - positions_len;
+    for t in &mesh.triangles {
+        for i in t {
+            let index = u32::try_from(*i).context("Mesh has too many vertices for GLB")?;
+            bin.extend_from_slice(&index.to_le_bytes());
+        }
+    }
+    let indices_len = bin.len() - positions_len - normals_len;
+    pad_to_four(&mut bin, 0);
+
+    let (min, max) = position_bounds(&mesh);
+    let document = json!({
+        "asset": {
+            "version": "2.0",
+            "generator": format!("horseCAD {}", env!("CARGO_PKG_VERSION")),
+        },
+        "scene": 0,
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [{
+            "mesh": 0,
+            "name": name,
+            // -90 degrees about X, mapping Z-up onto glTF's Y-up
+            "rotation": [-std::f32::consts::FRAC_1_SQRT_2, 0.0, 0.0, std::f32::consts::FRAC_1_SQRT_2],
+        }],
+        "meshes": [{
+            "name": name,
+            "primitives": [{
+                "attributes": { "POSITION": 0, "NORMAL": 1 },
+                "indices": 2,
+                "mode": MODE_TRIANGLES,
+            }],
+        }],
+        "buffers": [{ "byteLength": bin.len() }],
+        "bufferViews": [
+            { "buffer": 0, "byteOffset": 0, "byteLength": positions_len, "target": TARGET_ARRAY_BUFFER },
+            { "buffer": 0, "byteOffset": positions_len, "byteLength": normals_len, "target": TARGET_ARRAY_BUFFER },
+            {
+                "buffer": 0,
+                "byteOffset": positions_len + normals_len,
+                "byteLength": indices_len,
+                "target": TARGET_ELEMENT_ARRAY_BUFFER,
+            },
+        ],
+        "accessors": [
+            {
+                "bufferView": 0,
+                "componentType": COMPONENT_FLOAT,
+                "count": mesh.vertices.len(),
+                "type": "VEC3",
+                "min": min,
+                "max": max,
+            },
+            { "bufferView": 1, "componentType": COMPONENT_FLOAT, "count": normals.len(), "type": "VEC3" },
+            {
+                "bufferView": 2,
+                "componentType": COMPONENT_UNSIGNED_INT,
+                "count": mesh.triangles.len() * 3,
+                "type": "SCALAR",
+            },
+        ],
+    });
+
+    let mut json_bytes = serde_json::to_vec(&document).context("Failed to encode glTF JSON")?;
+    pad_to_four(&mut json_bytes, b' ');
+
+    let total_len = 12 + 8 + json_bytes.len() + 8 + bin.len();
+    let mut out = Vec::with_capacity(total_len);
+    out.extend_from_slice(GLB_MAGIC);
+    out.extend_from_slice(&GLB_VERSION.to_le_bytes());
+    out.extend_from_slice(&u32::try_from(total_len).context("Mesh is too large for GLB")?.to_le_bytes());
+    out.extend_from_slice(&(json_bytes.len() as u32).to_le_bytes());
+    out.extend_from_slice(CHUNK_JSON);
+    out.extend_from_slice(&json_bytes);
+    out.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+    out.extend_from_slice(CHUNK_BIN);
+    out.extend_from_slice(&bin);
+    Ok(out)
+}
+
+/// GLB chunks must be 4-byte aligned
+fn pad_to_four(bytes: &mut Vec<u8>, fill: u8) {
+    while !bytes.len().is_multiple_of(4) {
+        bytes.push(fill);
+    }
+}
+
+fn position_bounds(mesh: &Mesh) -> ([f32; 3], [f32; 3]) {
+    if mesh.vertices.is_empty() {
+        return ([0.0; 3], [0.0; 3]);
+    }
+    let mut min = [f32::INFINITY; 3];
+    let mut max = [f32::NEG_INFINITY; 3];
+    for v in &mesh.vertices {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(v[axis]);
+            max[axis] = max[axis].max(v[axis]);
+        }
+    }
+    (min, max)
+}
@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+
+use crate::mesh::grid::SdfGrid;
+
+/// File layout for an exported distance field grid
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GridFormat {
+    /// NumPy array, which records its own shape and type
+    #[default]
+    Npy,
+    /// Bare little-endian `f32` values, X varying fastest
+    Raw,
+}
+
+/// Encode a sampled grid in `format`
+pub fn export_grid(grid: &SdfGrid, format: GridFormat) -> Vec<u8> {
+    match format {
+        GridFormat::Npy => export_grid_to_npy(grid),
+        GridFormat::Raw => grid.values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+    }
+}
+
+/// Encode a grid as a version 1.0 `.npy` array of little-endian `f32`
+///
+/// The array's shape is `(z, y, x)`, so `array[k, j, i]` is the sample at
+/// `min + step * (i, j, k)`.
+fn export_grid_to_npy(grid: &SdfGrid) -> Vec<u8> {
+    let [nx, ny, nz] = grid.layout.dims;
+    let mut header = format!("{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, {}, {}), }}", nz, ny, nx);
+    // Magic, version and header length take 10 bytes; the data starts 64-byte aligned
+    let unpadded = 10 + header.len() + 1;
+    header.push_str(&" ".repeat(unpadded.next_multiple_of(64) - unpadded));
+    header.push('\n');
+
+    let mut out = Vec::with_capacity(10 + header.len() + grid.values.len() * 4);
+    out.extend_from_slice(b"\x93NUMPY\x01\x00");
+    out.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    out.extend_from_slice(header.as_bytes());
+    out.extend(grid.values.iter().flat_map(|v| v.to_le_bytes()));
+    out
+}
@@ -0,0 +1,331 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use fidget::mesh::Mesh;
+use serde::{Deserialize, Serialize};
+
+pub mod amf;
+pub mod archive;
+pub mod dxf;
+pub mod gif;
+pub mod glsl;
+pub mod gltf;
+pub mod grid;
+pub mod obj;
+pub mod off;
+pub mod ply;
+pub mod points;
+pub mod precision;
+pub mod report;
+pub mod sheet;
+pub mod stl;
+pub mod svg;
+pub mod threemf;
+pub mod vm;
+
+use crate::mesh::color::Rgb;
+use crate::mesh::normals::NormalOptions;
+use amf::AmfOptions;
+use precision::{apply_precision, PrecisionOptions};
+use stl::StlFormat;
+use threemf::ThreeMfOptions;
+
+/// Mesh file formats supported by the exporters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExportFormat {
+    #[serde(rename = "stl")]
+    Stl,
+    #[serde(rename = "stl_ascii")]
+    StlAscii,
+    #[serde(rename = "obj")]
+    Obj,
+    #[serde(rename = "ply")]
+    Ply,
+    #[serde(rename = "glb")]
+    Glb,
+    #[serde(rename = "3mf")]
+    ThreeMf,
+    #[serde(rename = "amf")]
+    Amf,
+    #[serde(rename = "off")]
+    Off,
+}
+
+impl ExportFormat {
+    /// Human-readable format name for logs and dialogs
+    pub fn label(&self) -> &'static str {
+        match self {
+            ExportFormat::Stl => "STL",
+            ExportFormat::StlAscii => "ASCII STL",
+            ExportFormat::Obj => "OBJ",
+            ExportFormat::Ply => "PLY",
+            ExportFormat::Glb => "GLB",
+            ExportFormat::ThreeMf => "3MF",
+            ExportFormat::Amf => "AMF",
+            ExportFormat::Off => "OFF",
+        }
+    }
+
+    /// File extension, without the dot
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Stl | ExportFormat::StlAscii => "stl",
+            ExportFormat::Obj => "obj",
+            ExportFormat::Ply => "ply",
+            ExportFormat::Glb => "glb",
+            ExportFormat::ThreeMf => "3mf",
+            ExportFormat::Amf => "amf",
+            ExportFormat::Off => "off",
+        }
+    }
+
+    /// Format for a file extension, ignoring case; STL files are binary
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        [Self::Stl, Self::Obj, Self::Ply, Self::Glb, Self::ThreeMf, Self::Amf, Self::Off]
+            .into_iter()
+            .find(|format| format.extension().eq_ignore_ascii_case(extension))
+    }
+}
+
+/// Format-specific metadata shared by all exporters
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ExportOptions {
+    /// Object name written into formats that support one
+    pub name: Option<String>,
+    /// Units recorded by formats that support them
+    pub unit: Option<ModelUnit>,
+    /// File name of the generating script
+    pub script_name: Option<String>,
+    /// Shading of the normals written by OBJ, PLY and GLB
+    #[serde(default)]
+    pub normals: NormalOptions,
+    /// Rounding and snapping of the written coordinates
+    #[serde(default)]
+    pub precision: PrecisionOptions,
+}
+
+impl ExportOptions {
+    /// Object name, falling back to the script's file stem
+    pub fn part_name(&self) -> String {
+        self.name
+            .clone()
+            .or_else(|| {
+                self.script_name.as_deref().and_then(|name| {
+                    std::path::Path::new(name)
+                        .file_stem()
+                        .map(|stem| stem.to_string_lossy().into_owned())
+                })
+            })
+            .unwrap_or_else(|| "horsecad_model".to_string())
+    }
+}
+
+/// Encode a mesh in the requested format
+///
+/// Per-triangle `colors` are kept by binary STL, PLY, 3MF, AMF and OFF, and
+/// dropped by the other formats.  Coordinates are rounded first if the
+/// options' `precision` asks for it.
+pub fn export_mesh(
+    mesh: &Mesh,
+    colors: Option<&[Option<Rgb>]>,
+    format: ExportFormat,
+    options: &ExportOptions,
+) -> Result<Vec<u8>> {
+    options.precision.validate()?;
+    let rounded;
+    let (mesh, colors) = if options.precision.is_enabled() {
+        rounded = apply_precision(mesh, colors, &options.precision);
+        (&rounded.0, rounded.1.as_deref())
+    } else {
+        (mesh, colors)
+    };
+    match format {
+        ExportFormat::Stl => {
+            let mut data =
+                stl::export_mesh_to_stl(mesh, StlFormat::Binary, &options.part_name(), options.script_name.as_deref())?;
+            if let Some(colors) = colors {
+                stl::apply_stl_colors(&mut data, colors);
+            }
+            Ok(data)
+        }
+        ExportFormat::StlAscii => {
+            stl::export_mesh_to_stl(mesh, StlFormat::Ascii, &options.part_name(), options.script_name.as_deref())
+        }
+        ExportFormat::Obj => obj::export_mesh_to_obj(mesh, &options.normals),
+        ExportFormat::Ply => ply::export_mesh_to_ply(mesh, colors, &options.normals),
+        ExportFormat::Off => off::export_mesh_to_off(mesh, colors),
+        ExportFormat::Glb => gltf::export_mesh_to_glb(mesh, &options.part_name(), &options.normals),
+        ExportFormat::ThreeMf => {
+            let part_name = options.part_name();
+            threemf::export_mesh_to_3mf(
+                mesh,
+                &ThreeMfOptions {
+                    unit: options.unit.unwrap_or_default(),
+                    script_name: options.script_name.as_deref(),
+                    part_name: &part_name,
+                    colors,
+                },
+            )
+        }
+        ExportFormat::Amf => {
+            let part_name = options.part_name();
+            amf::export_mesh_to_amf(
+                mesh,
+                &AmfOptions {
+                    unit: options.unit.unwrap_or_default(),
+                    script_name: options.script_name.as_deref(),
+                    part_name: &part_name,
+                    colors,
+                },
+            )
+        }
+    }
+}
+
+/// Physical unit that mesh coordinates are expressed in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModelUnit {
+    Micron,
+    #[default]
+    Millimeter,
+    Centimeter,
+    Meter,
+    Inch,
+    Foot,
+}
+
+impl ModelUnit {
+    /// Unit named by a script, by its symbol or its name in either number
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "um" | "micron" | "microns" => Some(ModelUnit::Micron),
+            "mm" | "millimeter" | "millimeters" => Some(ModelUnit::Millimeter),
+            "cm" | "centimeter" | "centimeters" => Some(ModelUnit::Centimeter),
+            "m" | "meter" | "meters" => Some(ModelUnit::Meter),
+            "in" | "inch" | "inches" => Some(ModelUnit::Inch),
+            "ft" | "foot" | "feet" => Some(ModelUnit::Foot),
+            _ => None,
+        }
+    }
+
+    /// Unit name as spelled by the 3MF core specification
+    pub fn as_3mf_str(&self) -> &'static str {
+        match self {
+            ModelUnit::Micron => "micron",
+            ModelUnit::Millimeter => "millimeter",
+            ModelUnit::Centimeter => "centimeter",
+            ModelUnit::Meter => "meter",
+            ModelUnit::Inch => "inch",
+            ModelUnit::Foot => "foot",
+        }
+    }
+
+    /// Abbreviation shown after a length
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            ModelUnit::Micron => "µm",
+            ModelUnit::Millimeter => "mm",
+            ModelUnit::Centimeter => "cm",
+            ModelUnit::Meter => "m",
+            ModelUnit::Inch => "in",
+            ModelUnit::Foot => "ft",
+        }
+    }
+
+    /// Length of one unit in millimeters
+    pub fn millimeters(&self) -> f32 {
+        match self {
+            ModelUnit::Micron => 0.001,
+            ModelUnit::Millimeter => 1.0,
+            ModelUnit::Centimeter => 10.0,
+            ModelUnit::Meter => 1000.0,
+            ModelUnit::Inch => 25.4,
+            ModelUnit::Foot => 304.8,
+        }
+    }
+}
+
+/// Axis pointing up in an exported mesh
+///
+/// Models are built Z-up, as slicers expect; many viewers and game engines
+/// expect Y-up instead.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpAxis {
+    #[default]
+    Z,
+    Y,
+}
+
+impl UpAxis {
+    /// The axis to bake into a `format` export
+    ///
+    /// GLB is always Y-up, and its exporter already rotates the model into
+    /// place, so it keeps the vertices Z-up.
+    pub fn for_format(self, format: ExportFormat) -> UpAxis {
+        match format {
+            ExportFormat::Glb => UpAxis::Z,
+            _ => self,
+        }
+    }
+
+    /// Turn a Z-up `mesh` so this axis points up
+    ///
+    /// The turn is about X, so the model isn't mirrored and its triangles
+    /// keep their winding.
+    pub fn orient(&self, mut mesh: Mesh) -> Mesh {
+        if *self == UpAxis::Y {
+            for v in &mut mesh.vertices {
+                *v = nalgebra::Vector3::new(v.x, v.z, -v.y);
+            }
+        }
+        mesh
+    }
+}
+
+/// Turn a part name into a file stem that is safe on every platform
+pub fn sanitize_file_stem(name: &str) -> String {
+    let stem: String = name
+        .trim()
+        .chars()
+        .map(|c| if c.is_alphanumeric() || matches!(c, '-' | '_' | ' ' | '.') { c } else { '_' })
+        .collect();
+    let stem = stem.trim_matches(['.', ' ']);
+    if stem.is_empty() {
+        "part".to_string()
+    } else {
+        stem.to_string()
+    }
+}
+
+/// `sanitize_file_stem` of `name`, with `-2`, `-3` and so on added until it isn't in `taken`
+///
+/// Stems are compared ignoring case, as they are on Windows and macOS, and
+/// the one returned is added to `taken`.
+pub fn unique_file_stem(name: &str, taken: &mut HashSet<String>) -> String {
+    let stem = sanitize_file_stem(name);
+    let mut unique = stem.clone();
+    let mut n = 1;
+    while !taken.insert(unique.to_lowercase()) {
+        n += 1;
+        unique = format!("{}-{}", stem, n);
+    }
+    unique
+}
+
+/// Escape text for use in XML attributes and element bodies
+pub fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,38 @@
+use std::io::Write;
+
+use anyhow::{Context as AnyhowContext, Result};
+use fidget::mesh::Mesh;
+
+use crate::mesh::normals::{shade_normals, NormalOptions};
+
+/// Export mesh to Wavefront OBJ format
+///
+/// Vertices are written once and shared between faces, so the topology of
+/// the dual-contoured mesh is preserved (unlike STL's triangle soup).
+/// Normals are indexed separately, so hard edges don't split vertices.
+pub fn export_mesh_to_obj(mesh: &Mesh, normals: &NormalOptions) -> Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    write_obj(mesh, normals, &mut buffer).context("Failed to write OBJ data")?;
+    Ok(buffer)
+}
+
+fn write_obj<W: Write>(mesh: &Mesh, normals: &NormalOptions, out: &mut W) -> std::io::Result<()> {
+    let shaded = shade_normals(mesh, normals);
+    writeln!(out, "# Exported by horseCAD")?;
+    writeln!(out, "# {} vertices, {} triangles", mesh.vertices.len(), mesh.triangles.len())?;
+    writeln!(out, "o horsecad_model")?;
+
+    for v in &mesh.vertices {
+        writeln!(out, "v {} {} {}", v.x, v.y, v.z)?;
+    }
+    for n in &shaded.normals {
+        writeln!(out, "vn {} {} {}", n.x, n.y, n.z)?;
+    }
+
+    // OBJ indices are 1-based
+    for (t, [na, nb, nc]) in mesh.triangles.iter().zip(&shaded.corners) {
+        writeln!(out, "f {}//{} {}//{} {}//{}", t.x + 1, na + 1, t.y + 1, nb + 1, t.z + 1, nc + 1)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,36 @@
+use std::io::Write;
+
+use anyhow::{Context as AnyhowContext, Result};
+use fidget::mesh::Mesh;
+
+use crate::mesh::color::Rgb;
+
+/// Export mesh to Object File Format (OFF), as read by CGAL and Geomview
+///
+/// Like OBJ, vertices are written once and shared between faces.  If
+/// `colors` is given, colored faces carry an RGB color after their indices.
+pub fn export_mesh_to_off(mesh: &Mesh, colors: Option<&[Option<Rgb>]>) -> Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    write_off(mesh, colors, &mut buffer).context("Failed to write OFF data")?;
+    Ok(buffer)
+}
+
+fn write_off<W: Write>(mesh: &Mesh, colors: Option<&[Option<Rgb>]>, out: &mut W) -> std::io::Result<()> {
+    writeln!(out, "OFF")?;
+    writeln!(out, "# Exported by horseCAD")?;
+    // Vertex, face and edge counts; the edge count is not required to be correct
+    writeln!(out, "{} {} 0", mesh.vertices.len(), mesh.triangles.len())?;
+
+    for v in &mesh.vertices {
+        writeln!(out, "{} {} {}", v.x, v.y, v.z)?;
+    }
+
+    for (f, t) in mesh.triangles.iter().enumerate() {
+        match colors.and_then(|colors| colors.get(f).copied().flatten()) {
+            Some([r, g, b]) => writeln!(out, "3 {} {} {} {} {} {}", t.x, t.y, t.z, r, g, b)?,
+            None => writeln!(out, "3 {} {} {}", t.x, t.y, t.z)?,
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use std::io::Write;
+
+use anyhow::{Context as AnyhowContext, Result};
+use fidget::mesh::Mesh;
+use nalgebra::Vector3;
+
+use crate::mesh::color::{Rgb, DEFAULT_COLOR};
+use crate::mesh::normals::{shade_normals, split_by_normals, NormalOptions};
+
+/// Export mesh to binary little-endian PLY format
+///
+/// Each vertex carries `nx`/`ny`/`nz` normals shaded as `normals` asks,
+/// duplicated along hard edges.  If `colors` is given, each face gets
+/// `red`/`green`/`blue` properties.
+pub fn export_mesh_to_ply(mesh: &Mesh, colors: Option<&[Option<Rgb>]>, normals: &NormalOptions) -> Result<Vec<u8>> {
+    let (mesh, normals) = split_by_normals(mesh, &shade_normals(mesh, normals));
+    let mut buffer = Vec::new();
+    write_ply(&mesh, &normals, colors, &mut buffer).context("Failed to write PLY data")?;
+    Ok(buffer)
+}
+
+fn write_ply<W: Write>(
+    mesh: &Mesh,
+    normals: &[Vector3<f32>],
+    colors: Option<&[Option<Rgb>]>,
+    out: &mut W,
+) -> std::io::Result<()> {
+    writeln!(out, "ply")?;
+    writeln!(out, "format binary_little_endian 1.0")?;
+    writeln!(out, "comment Exported by horseCAD")?;
+    writeln!(out, "element vertex {}", mesh.vertices.len())?;
+    writeln!(out, "property float x")?;
+    writeln!(out, "property float y")?;
+    writeln!(out, "property float z")?;
+    writeln!(out, "property float nx")?;
+    writeln!(out, "property float ny")?;
+    writeln!(out, "property float nz")?;
+    writeln!(out, "element face {}", mesh.triangles.len())?;
+    writeln!(out, "property list uchar uint vertex_indices")?;
+    if colors.is_some() {
+        writeln!(out, "property uchar red")?;
+        writeln!(out, "property uchar green")?;
+        writeln!(out, "property uchar blue")?;
+    }
+    writeln!(out, "end_header")?;
+
+    for (v, n) in mesh.vertices.iter().zip(normals) {
+        for p in v.iter().chain(n) {
+            out.write_all(&p.to_le_bytes())?;
+        }
+    }
+
+    for (f, t) in mesh.triangles.iter().enumerate() {
+        out.write_all(&[3u8])?;
+        for i in t {
+            out.write_all(&(*i as u32).to_le_bytes())?;
+        }
+        if let Some(colors) = colors {
+            out.write_all(&colors.get(f).copied().flatten().unwrap_or(DEFAULT_COLOR))?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,42 @@
+use std::fmt::Write;
+
+use serde::{Deserialize, Serialize};
+
+use crate::mesh::points::PointCloud;
+
+/// File layout for an exported point cloud
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PointCloudFormat {
+    /// One `x y z nx ny nz` line per point, as CloudCompare and MeshLab read it
+    #[default]
+    Xyz,
+    /// ASCII Point Cloud Library file, with normals
+    Pcd,
+}
+
+impl PointCloudFormat {
+    /// File extension, without the dot
+    pub fn extension(&self) -> &'static str {
+        match self {
+            PointCloudFormat::Xyz => "xyz",
+            PointCloudFormat::Pcd => "pcd",
+        }
+    }
+}
+
+/// Encode a point cloud in `format`
+pub fn export_point_cloud(cloud: &PointCloud, format: PointCloudFormat) -> Vec<u8> {
+    let mut out = String::with_capacity(cloud.points.len() * 64);
+    if format == PointCloudFormat::Pcd {
+        let count = cloud.points.len();
+        out.push_str("# .PCD v0.7 - Point Cloud Data file format\nVERSION 0.7\n");
+        out.push_str("FIELDS x y z normal_x normal_y normal_z\nSIZE 4 4 4 4 4 4\n");
+        out.push_str("TYPE F F F F F F\nCOUNT 1 1 1 1 1 1\n");
+        let _ = write!(out, "WIDTH {}\nHEIGHT 1\nVIEWPOINT 0 0 0 1 0 0 0\nPOINTS {}\nDATA ascii\n", count, count);
+    }
+    for (p, n) in cloud.points.iter().zip(&cloud.normals) {
+        let _ = writeln!(out, "{} {} {} {} {} {}", p.x, p.y, p.z, n.x, n.y, n.z);
+    }
+    out.into_bytes()
+}
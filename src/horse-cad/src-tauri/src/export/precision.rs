@@ -0,0 +1,116 @@
+use anyhow::{bail, Result};
+use fidget::mesh::Mesh;
+use serde::{Deserialize, Serialize};
+
+use crate::mesh::color::Rgb;
+
+/// Coordinate rounding applied to a mesh as it's written
+///
+/// Both are off by default, writing coordinates at full precision.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PrecisionOptions {
+    /// Round every coordinate to a multiple of this, in the exported unit, such as 0.001 in millimeters
+    pub quantum: Option<f32>,
+    /// Make coordinates this close across an edge exactly equal, so faces meant to be axis-aligned are flat
+    pub snap_tolerance: Option<f32>,
+}
+
+impl PrecisionOptions {
+    pub fn is_enabled(&self) -> bool {
+        self.quantum.is_some() || self.snap_tolerance.is_some()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.quantum.is_some_and(|q| !(q.is_finite() && q > 0.0)) {
+            bail!("The coordinate precision must be greater than zero");
+        }
+        if self.snap_tolerance.is_some_and(|t| !(t.is_finite() && t > 0.0)) {
+            bail!("The snapping tolerance must be greater than zero");
+        }
+        Ok(())
+    }
+}
+
+/// Snap and round the coordinates of `mesh`, dropping triangles that collapse
+///
+/// Snapping comes first.  On each axis, vertices joined by edges that move
+/// no more than `snap_tolerance` along it are grouped, and a group that
+/// spans at most twice the tolerance is set to its mean; faces that are
+/// nearly axis-aligned come out exactly flat, while gentle slopes, whose
+/// groups reach further, are left alone.  Each coordinate is then rounded
+/// to the nearest multiple of `quantum`.  Files get smaller, since text
+/// formats write fewer digits and repeated values compress, and a model
+/// exported twice diffs cleanly.  Triangles left with two corners at the
+/// same point are dropped, along with their `colors`.
+pub fn apply_precision(
+    mesh: &Mesh,
+    colors: Option<&[Option<Rgb>]>,
+    options: &PrecisionOptions,
+) -> (Mesh, Option<Vec<Option<Rgb>>>) {
+    let mut vertices = mesh.vertices.clone();
+    if let Some(tolerance) = options.snap_tolerance {
+        for axis in 0..3 {
+            snap_axis(&mut vertices, mesh, axis, tolerance);
+        }
+    }
+    if let Some(quantum) = options.quantum {
+        let quantum = quantum as f64;
+        for v in &mut vertices {
+            for x in v.iter_mut() {
+                // Adding zero turns -0 into 0, so both round the same way in text
+                *x = ((*x as f64 / quantum).round() * quantum) as f32 + 0.0;
+            }
+        }
+    }
+
+    let mut triangles = Vec::with_capacity(mesh.triangles.len());
+    let mut kept_colors = colors.map(|_| Vec::with_capacity(mesh.triangles.len()));
+    for (i, t) in mesh.triangles.iter().enumerate() {
+        let [a, b, c] = [t.x, t.y, t.z].map(|i| vertices[i]);
+        if a == b || b == c || c == a {
+            continue;
+        }
+        triangles.push(*t);
+        if let (Some(kept), Some(colors)) = (kept_colors.as_mut(), colors) {
+            kept.push(colors.get(i).copied().flatten());
+        }
+    }
+    (Mesh { vertices, triangles }, kept_colors)
+}
+
+/// Set `axis` of each group of vertices joined by edges flat along it to the group's mean
+fn snap_axis(vertices: &mut [nalgebra::Vector3<f32>], mesh: &Mesh, axis: usize, tolerance: f32) {
+    let mut parent: Vec<usize> = (0..vertices.len()).collect();
+    for t in &mesh.triangles {
+        for (a, b) in [(t.x, t.y), (t.y, t.z), (t.z, t.x)] {
+            if (vertices[a][axis] - vertices[b][axis]).abs() <= tolerance {
+                let (ra, rb) = (find_root(&mut parent, a), find_root(&mut parent, b));
+                parent[ra.max(rb)] = ra.min(rb);
+            }
+        }
+    }
+
+    // Lowest value, highest value, sum and count of each group
+    let mut groups = vec![(f32::INFINITY, f32::NEG_INFINITY, 0.0f64, 0usize); vertices.len()];
+    for i in 0..vertices.len() {
+        let group = &mut groups[find_root(&mut parent, i)];
+        let x = vertices[i][axis];
+        *group = (group.0.min(x), group.1.max(x), group.2 + x as f64, group.3 + 1);
+    }
+    for i in 0..vertices.len() {
+        let (low, high, sum, count) = groups[find_root(&mut parent, i)];
+        if count > 1 && high - low <= 2.0 * tolerance {
+            vertices[i][axis] = (sum / count as f64) as f32;
+        }
+    }
+}
+
+/// The group `i` is in, halving the path to it on the way
+fn find_root(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
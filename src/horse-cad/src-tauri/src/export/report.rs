@@ -0,0 +1,63 @@
+use std::path::{Path, PathBuf};
+
+use fidget::mesh::Mesh;
+use serde::{Deserialize, Serialize};
+
+use super::{ExportFormat, ModelUnit};
+use crate::mesh::measure::{measure_mesh, MeshMeasurements};
+use crate::state::MeshOrigin;
+use crate::{CompileTimings, ScriptParam};
+
+/// What an exported file was made from, written next to it
+///
+/// Kept with a printed or machined part, it ties the part back to the
+/// exact script and parameters that produced it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportReport {
+    pub app_version: String,
+    /// When the file was exported, as RFC 3339
+    pub exported_at: String,
+    pub file: String,
+    pub format: ExportFormat,
+    /// Unit the file's coordinates are in, if known
+    pub unit: Option<ModelUnit>,
+    /// SHA-256 of the script's code, in hex; `None` if the mesh didn't come from a script compiled here
+    pub script_hash: Option<String>,
+    pub parameters: Vec<ScriptParam>,
+    pub triangle_count: usize,
+    pub vertex_count: usize,
+    /// Of the mesh as written, after any simplifying, unit conversion and transform
+    pub measurements: MeshMeasurements,
+    /// Time the compile spent in each stage
+    pub timings: Option<CompileTimings>,
+}
+
+impl ExportReport {
+    /// Report on `mesh`, the mesh written to `file`
+    pub fn new(
+        file: &Path,
+        format: ExportFormat,
+        unit: Option<ModelUnit>,
+        mesh: &Mesh,
+        origin: Option<&MeshOrigin>,
+    ) -> Self {
+        ExportReport {
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            exported_at: chrono::Utc::now().to_rfc3339(),
+            file: file.to_string_lossy().into_owned(),
+            format,
+            unit,
+            script_hash: origin.map(|o| o.script_hash.clone()),
+            parameters: origin.map(|o| o.parameters.clone()).unwrap_or_default(),
+            triangle_count: mesh.triangles.len(),
+            vertex_count: mesh.vertices.len(),
+            measurements: measure_mesh(mesh),
+            timings: origin.map(|o| o.timings),
+        }
+    }
+}
+
+/// Where the report on an export to `path` goes: `part.stl` gets `part.report.json`
+pub fn report_path(path: &Path) -> PathBuf {
+    path.with_extension("report.json")
+}
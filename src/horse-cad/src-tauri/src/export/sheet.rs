@@ -0,0 +1,50 @@
+use serde::{Deserialize, Serialize};
+
+use crate::mesh::slice::Slice;
+
+/// Space left between slices laid out on one sheet, as a fraction of the slice size
+const SHEET_GAP: f32 = 0.05;
+
+/// How slices are split between exported files
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SliceLayout {
+    /// One file per slice
+    #[default]
+    PerLayer,
+    /// Every slice in one file, side by side
+    Stacked,
+}
+
+/// Slices arranged left to right and top to bottom in a square-ish grid, so none overlap
+pub struct Sheet {
+    pub width: f32,
+    pub height: f32,
+    /// Offset that moves each slice into place, with the sheet's origin at
+    /// its bottom left and Y pointing up
+    pub offsets: Vec<[f32; 2]>,
+}
+
+impl Sheet {
+    pub fn new(slices: &[Slice]) -> Self {
+        let size = |s: &Slice| [s.max[0] - s.min[0], s.max[1] - s.min[1]];
+        let cell = slices.iter().map(size).fold([0.0f32; 2], |a, b| [a[0].max(b[0]), a[1].max(b[1])]);
+        let gap = cell[0].max(cell[1]) * SHEET_GAP;
+        let columns = (slices.len() as f32).sqrt().ceil().max(1.0) as usize;
+        let rows = slices.len().div_ceil(columns).max(1);
+        let offsets = slices
+            .iter()
+            .enumerate()
+            .map(|(i, slice)| {
+                let left = (i % columns) as f32 * (cell[0] + gap);
+                let bottom = (rows - 1 - i / columns) as f32 * (cell[1] + gap);
+                [left - slice.min[0], bottom - slice.min[1]]
+            })
+            .collect();
+        Sheet {
+            width: columns as f32 * cell[0] + (columns - 1) as f32 * gap,
+            height: rows as f32 * cell[1] + (rows - 1) as f32 * gap,
+            offsets,
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use anyhow::{Context as AnyhowContext, Result};
+use fidget::mesh::Mesh;
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+
+use crate::mesh::color::{from_rgb555, to_rgb555, Rgb};
+
+const STL_HEADER_LEN: usize = 80;
+const STL_TRIANGLE_LEN: usize = 50;
+
+/// STL encoding variants
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StlFormat {
+    #[default]
+    Binary,
+    Ascii,
+}
+
+/// Export mesh to STL format
+///
+/// `name` names the solid in ASCII STL.  Binary STL has no name, so its
+/// header says instead which app wrote it, then `name` and the `document`
+/// it came from, cut off at 80 bytes.  It has no timestamp, so exporting
+/// the same mesh twice gives identical files.
+pub fn export_mesh_to_stl(mesh: &Mesh, format: StlFormat, name: &str, document: Option<&str>) -> Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    match format {
+        StlFormat::Binary => {
+            mesh.write_stl(&mut buffer).context("Failed to write STL data")?;
+            buffer[..STL_HEADER_LEN].copy_from_slice(&binary_header(name, document));
+        }
+        StlFormat::Ascii => {
+            // The solid name is a single word to most readers
+            let name: String = name.chars().map(|c| if c.is_whitespace() { '_' } else { c }).collect();
+            write_ascii_stl(mesh, &name, &mut buffer).context("Failed to write ASCII STL data")?
+        }
+    }
+    Ok(buffer)
+}
+
+/// Header of a binary STL, padded with zeros
+///
+/// It never starts with `solid`, which would make readers take the file
+/// for ASCII STL.
+fn binary_header(name: &str, document: Option<&str>) -> [u8; STL_HEADER_LEN] {
+    let mut text = format!("horseCAD {}", name);
+    if let Some(document) = document {
+        text.push_str(&format!(" from {}", document));
+    }
+    let mut end = text.len().min(STL_HEADER_LEN);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    let mut header = [0; STL_HEADER_LEN];
+    header[..end].copy_from_slice(&text.as_bytes()[..end]);
+    header
+}
+
+/// Store per-triangle colors in the attribute words of binary STL data
+///
+/// Triangles without a color keep a zero attribute word.  `data` must have
+/// been written by `export_mesh_to_stl` with `StlFormat::Binary`.
+pub fn apply_stl_colors(data: &mut [u8], colors: &[Option<Rgb>]) {
+    for (i, color) in colors.iter().enumerate() {
+        let offset = STL_HEADER_LEN + 4 + i * STL_TRIANGLE_LEN + STL_TRIANGLE_LEN - 2;
+        if let (Some(color), Some(word)) = (color, data.get_mut(offset..offset + 2)) {
+            word.copy_from_slice(&to_rgb555(*color).to_le_bytes());
+        }
+    }
+}
+
+/// Read per-triangle colors from the attribute words of binary STL data
+///
+/// Returns `None` for ASCII data, or if no triangle carries a color.
+pub fn parse_stl_colors(data: &[u8]) -> Option<Vec<Option<Rgb>>> {
+    if is_ascii_stl(data) || data.len() < STL_HEADER_LEN + 4 {
+        return None;
+    }
+    let count_bytes: [u8; 4] = data[STL_HEADER_LEN..STL_HEADER_LEN + 4].try_into().ok()?;
+    let triangle_count = u32::from_le_bytes(count_bytes) as usize;
+    let colors: Vec<_> = (0..triangle_count)
+        .map(|i| {
+            let offset = STL_HEADER_LEN + 4 + i * STL_TRIANGLE_LEN + STL_TRIANGLE_LEN - 2;
+            let word = data.get(offset..offset + 2)?;
+            from_rgb555(u16::from_le_bytes([word[0], word[1]]))
+        })
+        .collect();
+    colors.iter().any(Option::is_some).then_some(colors)
+}
+
+fn write_ascii_stl<W: Write>(mesh: &Mesh, name: &str, out: &mut W) -> std::io::Result<()> {
+    writeln!(out, "solid {}", name)?;
+    for t in &mesh.triangles {
+        let a = mesh.vertices[t.x];
+        let b = mesh.vertices[t.y];
+        let c = mesh.vertices[t.z];
+        let normal = (b - a).cross(&(c - a)).try_normalize(f32::EPSILON).unwrap_or_else(Vector3::zeros);
+        writeln!(out, "  facet normal {:e} {:e} {:e}", normal.x, normal.y, normal.z)?;
+        writeln!(out, "    outer loop")?;
+        for v in [a, b, c] {
+            writeln!(out, "      vertex {:e} {:e} {:e}", v.x, v.y, v.z)?;
+        }
+        writeln!(out, "    endloop")?;
+        writeln!(out, "  endfacet")?;
+    }
+    writeln!(out, "endsolid {}", name)?;
+    Ok(())
+}
+
+/// Returns true if the data looks like an ASCII STL
+///
+/// Binary headers are free-form and may also start with `solid`, so the
+/// presence of a `facet` keyword is checked as well.
+pub fn is_ascii_stl(data: &[u8]) -> bool {
+    let prefix = &data[..data.len().min(512)];
+    data.starts_with(b"solid")
+        && std::str::from_utf8(prefix).map(|s| s.contains("facet") || s.contains("endsolid")).unwrap_or(false)
+}
+
+/// Rebuild an indexed mesh from binary or ASCII STL data
+pub fn parse_stl(data: &[u8]) -> Result<Mesh> {
+    if is_ascii_stl(data) {
+        parse_ascii_stl(data)
+    } else {
+        parse_binary_stl(data)
+    }
+}
+
+fn parse_ascii_stl(data: &[u8]) -> Result<Mesh> {
+    let text = std::str::from_utf8(data).context("ASCII STL is not valid UTF-8")?;
+
+    let mut mesh = Mesh::new();
+    let mut vertex_indices: HashMap<[u32; 3], usize> = HashMap::new();
+    let mut corners = Vec::with_capacity(3);
+
+    for line in text.lines() {
+        let mut words = line.split_whitespace();
+        if words.next() != Some("vertex") {
+            continue;
+        }
+        let mut bits = [0u32; 3];
+        for b in &mut bits {
+            let word = words.next().context("ASCII STL vertex is missing a coordinate")?;
+            let value: f32 = word.parse().with_context(|| format!("Invalid STL coordinate '{}'", word))?;
+            *b = value.to_bits();
+        }
+        let index = *vertex_indices.entry(bits).or_insert_with(|| {
+            mesh.vertices.push(Vector3::new(
+                f32::from_bits(bits[0]),
+                f32::from_bits(bits[1]),
+                f32::from_bits(bits[2]),
+            ));
+            mesh.vertices.len() - 1
+        });
+        corners.push(index);
+        if corners.len() == 3 {
+            mesh.triangles.push(Vector3::new(corners[0], corners[1], corners[2]));
+            corners.clear();
+        }
+    }
+
+    Ok(mesh)
+}
+
+/// Rebuild an indexed mesh from binary STL data
+///
+/// Vertices with bit-identical positions are merged, which recovers the
+/// shared vertices of meshes that were written by `export_mesh_to_stl`.
+fn parse_binary_stl(data: &[u8]) -> Result<Mesh> {
+    if data.len() < STL_HEADER_LEN + 4 {
+        return Err(anyhow::anyhow!("STL data is too short ({} bytes)", data.len()));
+    }
+
+    let count_bytes: [u8; 4] = data[STL_HEADER_LEN..STL_HEADER_LEN + 4].try_into()?;
+    let triangle_count = u32::from_le_bytes(count_bytes) as usize;
+    let expected_len = STL_HEADER_LEN + 4 + triangle_count * STL_TRIANGLE_LEN;
+    if data.len() < expected_len {
+        return Err(anyhow::anyhow!(
+            "STL data is truncated (expected {} bytes, got {})",
+            expected_len,
+            data.len()
+        ));
+    }
+
+    let mut mesh = Mesh::new();
+    let mut vertex_indices: HashMap<[u32; 3], usize> = HashMap::new();
+
+    for i in 0..triangle_count {
+        // Skip the 12-byte facet normal, it's recomputed by every consumer
+        let offset = STL_HEADER_LEN + 4 + i * STL_TRIANGLE_LEN + 12;
+        let mut corners = [0usize; 3];
+        for (corner, index) in corners.iter_mut().enumerate() {
+            let start = offset + corner * 12;
+            let mut bits = [0u32; 3];
+            for (axis, b) in bits.iter_mut().enumerate() {
+                let s = start + axis * 4;
+                *b = u32::from_le_bytes(data[s..s + 4].try_into()?);
+            }
+            *index = *vertex_indices.entry(bits).or_insert_with(|| {
+                mesh.vertices.push(Vector3::new(
+                    f32::from_bits(bits[0]),
+                    f32::from_bits(bits[1]),
+                    f32::from_bits(bits[2]),
+                ));
+                mesh.vertices.len() - 1
+            });
+        }
+        mesh.triangles.push(Vector3::new(corners[0], corners[1], corners[2]));
+    }
+
+    Ok(mesh)
+}
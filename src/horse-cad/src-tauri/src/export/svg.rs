@@ -0,0 +1,61 @@
+use std::io::Write;
+
+use anyhow::{Context as AnyhowContext, Result};
+
+use super::{sheet::Sheet, ModelUnit};
+use crate::mesh::slice::Slice;
+
+/// Stroke width of the outlines, in millimeters, thin enough for laser cutters to treat as a cut line
+const STROKE_MM: f32 = 0.1;
+
+/// Export slices to SVG, laid out side by side on one sheet
+///
+/// Each slice is an Inkscape layer holding a single unfilled path, so the
+/// file can go straight to a laser cutter.  The document is sized in real
+/// units, taking model coordinates to be in `unit`; Y is flipped so the
+/// outlines appear as seen from above.
+pub fn export_slices_to_svg(slices: &[Slice], unit: ModelUnit) -> Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    write_svg(slices, unit, &mut buffer).context("Failed to write SVG data")?;
+    Ok(buffer)
+}
+
+fn write_svg<W: Write>(slices: &[Slice], unit: ModelUnit, out: &mut W) -> std::io::Result<()> {
+    let Sheet { width, height, offsets } = Sheet::new(slices);
+    let mm = unit.millimeters();
+
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape" width="{}mm" height="{}mm" viewBox="0 0 {} {}">"#,
+        width * mm,
+        height * mm,
+        width,
+        height
+    )?;
+    writeln!(out, "  <!-- Exported by horseCAD -->")?;
+    for (i, (slice, offset)) in slices.iter().zip(offsets).enumerate() {
+        writeln!(
+            out,
+            r#"  <g id="slice-{}" inkscape:groupmode="layer" inkscape:label="z = {}">"#,
+            i, slice.z
+        )?;
+        write!(
+            out,
+            r#"    <path fill="none" stroke="black" stroke-width="{}" d=""#,
+            STROKE_MM / mm
+        )?;
+        for contour in &slice.contours {
+            for (k, p) in contour.iter().enumerate() {
+                let x = p[0] + offset[0];
+                let y = height - (p[1] + offset[1]);
+                write!(out, "{}{} {} ", if k == 0 { "M" } else { "L" }, x, y)?;
+            }
+            write!(out, "Z ")?;
+        }
+        writeln!(out, r#""/>"#)?;
+        writeln!(out, "  </g>")?;
+    }
+    writeln!(out, "</svg>")?;
+    Ok(())
+}
@@ -0,0 +1,190 @@
+use std::io::{Cursor, Write};
+
+use anyhow::{Context as AnyhowContext, Result};
+use fidget::mesh::Mesh;
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use super::{xml_escape, ModelUnit};
+use crate::mesh::color::{to_hex, Rgb, DEFAULT_COLOR};
+
+/// Resource id of the color palette, when one is written
+const MATERIALS_ID: u32 = 2;
+
+const CONTENT_TYPES: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">
+  <Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/>
+  <Default Extension="model" ContentType="application/vnd.ms-package.3dmanufacturing-3dmodel+xml"/>
+</Types>
+"#;
+
+const RELS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+  <Relationship Target="/3D/3dmodel.model" Id="rel0" Type="http://schemas.microsoft.com/3dmanufacturing/2013/01/3dmodel"/>
+</Relationships>
+"#;
+
+/// Metadata embedded in the 3MF model part
+pub struct ThreeMfOptions<'a> {
+    pub unit: ModelUnit,
+    pub script_name: Option<&'a str>,
+    pub part_name: &'a str,
+    /// Per-triangle colors, written as a base materials palette
+    pub colors: Option<&'a [Option<Rgb>]>,
+}
+
+/// One object of a multi-material package, printed in its own material
+pub struct MaterialObject<'a> {
+    pub material: &'a str,
+    pub mesh: &'a Mesh,
+    pub color: Rgb,
+}
+
+/// Export mesh to a 3MF package
+pub fn export_mesh_to_3mf(mesh: &Mesh, options: &ThreeMfOptions) -> Result<Vec<u8>> {
+    write_package(|out| write_model_xml(mesh, options, out))
+}
+
+/// Export one object per material to a 3MF package
+///
+/// The objects share one coordinate frame and are placed where they were
+/// modeled, so a slicer can load them as parts of one multi-material
+/// print.  Each references its own entry in a base materials group named
+/// after the material.
+pub fn export_materials_to_3mf(
+    objects: &[MaterialObject],
+    unit: ModelUnit,
+    script_name: Option<&str>,
+) -> Result<Vec<u8>> {
+    write_package(|out| {
+        write_model_header(out, unit, script_name)?;
+        writeln!(out, r#"    <basematerials id="{}">"#, MATERIALS_ID)?;
+        for object in objects {
+            writeln!(
+                out,
+                r#"      <base name="{}" displaycolor="{}FF"/>"#,
+                xml_escape(object.material),
+                to_hex(object.color)
+            )?;
+        }
+        writeln!(out, "    </basematerials>")?;
+        for (i, object) in objects.iter().enumerate() {
+            writeln!(
+                out,
+                r#"    <object id="{}" type="model" name="{}" pid="{}" pindex="{}">"#,
+                MATERIALS_ID as usize + 1 + i,
+                xml_escape(object.material),
+                MATERIALS_ID,
+                i
+            )?;
+            write_mesh_xml(object.mesh, None, out)?;
+            writeln!(out, "    </object>")?;
+        }
+        writeln!(out, "  </resources>")?;
+        writeln!(out, "  <build>")?;
+        for i in 0..objects.len() {
+            writeln!(out, r#"    <item objectid="{}"/>"#, MATERIALS_ID as usize + 1 + i)?;
+        }
+        writeln!(out, "  </build>")?;
+        writeln!(out, "</model>")
+    })
+}
+
+/// Zip the package parts around a model written by `write_model`
+fn write_package(
+    write_model: impl FnOnce(&mut ZipWriter<Cursor<Vec<u8>>>) -> std::io::Result<()>,
+) -> Result<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let file_options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    zip.start_file("[Content_Types].xml", file_options)?;
+    zip.write_all(CONTENT_TYPES.as_bytes())?;
+    zip.start_file("_rels/.rels", file_options)?;
+    zip.write_all(RELS.as_bytes())?;
+    zip.start_file("3D/3dmodel.model", file_options)?;
+    write_model(&mut zip).context("Failed to write 3MF model")?;
+
+    let cursor = zip.finish().context("Failed to write 3MF package")?;
+    Ok(cursor.into_inner())
+}
+
+/// The model element's opening, its metadata, and the opening of its resources
+fn write_model_header<W: Write>(out: &mut W, unit: ModelUnit, script_name: Option<&str>) -> std::io::Result<()> {
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<model unit="{}" xml:lang="en-US" xmlns="http://schemas.microsoft.com/3dmanufacturing/core/2015/02">"#,
+        unit.as_3mf_str()
+    )?;
+    writeln!(out, r#"  <metadata name="Application">horseCAD {}</metadata>"#, env!("CARGO_PKG_VERSION"))?;
+    writeln!(out, r#"  <metadata name="CreationDate">{}</metadata>"#, chrono::Utc::now().format("%Y-%m-%d"))?;
+    if let Some(script_name) = script_name {
+        writeln!(out, r#"  <metadata name="Title">{}</metadata>"#, xml_escape(script_name))?;
+    }
+    writeln!(out, "  <resources>")
+}
+
+/// An object's mesh element, with each triangle's palette index if there are any
+fn write_mesh_xml<W: Write>(mesh: &Mesh, material_indices: Option<&[usize]>, out: &mut W) -> std::io::Result<()> {
+    writeln!(out, "      <mesh>")?;
+    writeln!(out, "        <vertices>")?;
+    for v in &mesh.vertices {
+        writeln!(out, r#"          <vertex x="{}" y="{}" z="{}"/>"#, v.x, v.y, v.z)?;
+    }
+    writeln!(out, "        </vertices>")?;
+    writeln!(out, "        <triangles>")?;
+    for (i, t) in mesh.triangles.iter().enumerate() {
+        match material_indices.and_then(|m| m.get(i)) {
+            Some(p) => writeln!(
+                out,
+                r#"          <triangle v1="{}" v2="{}" v3="{}" pid="{}" p1="{}"/>"#,
+                t.x, t.y, t.z, MATERIALS_ID, p
+            )?,
+            None => writeln!(out, r#"          <triangle v1="{}" v2="{}" v3="{}"/>"#, t.x, t.y, t.z)?,
+        }
+    }
+    writeln!(out, "        </triangles>")?;
+    writeln!(out, "      </mesh>")
+}
+
+fn write_model_xml<W: Write>(mesh: &Mesh, options: &ThreeMfOptions, out: &mut W) -> std::io::Result<()> {
+    write_model_header(out, options.unit, options.script_name)?;
+
+    // Palette index for each triangle; uncolored triangles use entry 0
+    let mut palette = vec![DEFAULT_COLOR];
+    let material_indices: Option<Vec<usize>> = options.colors.map(|colors| {
+        colors
+            .iter()
+            .map(|color| match color {
+                Some(c) => palette.iter().position(|p| p == c).unwrap_or_else(|| {
+                    palette.push(*c);
+                    palette.len() - 1
+                }),
+                None => 0,
+            })
+            .collect()
+    });
+    if material_indices.is_some() {
+        writeln!(out, r#"    <basematerials id="{}">"#, MATERIALS_ID)?;
+        for (i, color) in palette.iter().enumerate() {
+            let name = if i == 0 { "Default".to_string() } else { format!("Color {}", i) };
+            writeln!(out, r#"      <base name="{}" displaycolor="{}FF"/>"#, name, to_hex(*color))?;
+        }
+        writeln!(out, "    </basematerials>")?;
+        writeln!(
+            out,
+            r#"    <object id="1" type="model" name="{}" pid="{}" pindex="0">"#,
+            xml_escape(options.part_name),
+            MATERIALS_ID
+        )?;
+    } else {
+        writeln!(out, r#"    <object id="1" type="model" name="{}">"#, xml_escape(options.part_name))?;
+    }
+    write_mesh_xml(mesh, material_indices.as_deref(), out)?;
+    writeln!(out, "    </object>")?;
+    writeln!(out, "  </resources>")?;
+    writeln!(out, "  <build>")?;
+    writeln!(out, r#"    <item objectid="1"/>"#)?;
+    writeln!(out, "  </build>")?;
+    writeln!(out, "</model>")?;
+    Ok(())
+}
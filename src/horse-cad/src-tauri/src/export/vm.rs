@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, Context as AnyhowContext, Result};
+use fidget::{
+    context::{BinaryOpcode, Context, Node, Op, Tree, UnaryOpcode},
+    var::Var,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::mesh::bind::post_order;
+
+/// Identifies a compiled shape file
+const VM_MAGIC: &[u8; 8] = b"horseVM\0";
+
+/// Bumped whenever the layout of [`VmFile`] changes
+const VM_FORMAT: u32 = 1;
+
+/// Opcodes in the order they're numbered in a file; only ever append to these
+const UNARY: [UnaryOpcode; 17] = [
+    UnaryOpcode::Neg,
+    UnaryOpcode::Abs,
+    UnaryOpcode::Recip,
+    UnaryOpcode::Sqrt,
+    UnaryOpcode::Square,
+    UnaryOpcode::Floor,
+    UnaryOpcode::Ceil,
+    UnaryOpcode::Round,
+    UnaryOpcode::Sin,
+    UnaryOpcode::Cos,
+    UnaryOpcode::Tan,
+    UnaryOpcode::Asin,
+    UnaryOpcode::Acos,
+    UnaryOpcode::Atan,
+    UnaryOpcode::Exp,
+    UnaryOpcode::Ln,
+    UnaryOpcode::Not,
+];
+const BINARY: [BinaryOpcode; 11] = [
+    BinaryOpcode::Add,
+    BinaryOpcode::Sub,
+    BinaryOpcode::Mul,
+    BinaryOpcode::Div,
+    BinaryOpcode::Atan,
+    BinaryOpcode::Min,
+    BinaryOpcode::Max,
+    BinaryOpcode::Compare,
+    BinaryOpcode::Mod,
+    BinaryOpcode::And,
+    BinaryOpcode::Or,
+];
+
+/// One node of the graph; operands index earlier entries
+#[derive(Serialize, Deserialize)]
+enum VmOp {
+    X,
+    Y,
+    Z,
+    Const(f64),
+    Unary(u8, u32),
+    Binary(u8, u32, u32),
+}
+
+#[derive(Serialize, Deserialize)]
+struct VmFile {
+    ops: Vec<VmOp>,
+    root: u32,
+    /// Named shapes, so a single part can be loaded back
+    parts: Vec<(String, u32)>,
+}
+
+/// A compiled shape read back from a file
+pub struct VmShapes {
+    /// Union of every drawn shape
+    pub root: Tree,
+    pub parts: Vec<(String, Tree)>,
+}
+
+/// Write the graph at `root`, and the named `parts` within it, as a compiled shape file
+///
+/// The file holds the evaluated expression rather than the script, so
+/// loading it skips running the script entirely.  Parameters must already
+/// be bound, since the file has no way to supply them.
+pub fn export_shape_to_vm(ctx: &Context, root: Node, parts: &[(&str, Node)]) -> Result<Vec<u8>> {
+    let mut ops = Vec::new();
+    let mut index: HashMap<Node, u32> = HashMap::new();
+    let roots: Vec<Node> = std::iter::once(root).chain(parts.iter().map(|&(_, node)| node)).collect();
+    for (node, op) in post_order(ctx, &roots)? {
+        ops.push(match op {
+            Op::Input(Var::X) => VmOp::X,
+            Op::Input(Var::Y) => VmOp::Y,
+            Op::Input(Var::Z) => VmOp::Z,
+            Op::Input(Var::V(..)) => bail!("the shape still depends on an unbound parameter"),
+            Op::Const(c) => VmOp::Const(c.0),
+            Op::Unary(op, a) => VmOp::Unary(UNARY.iter().position(|&o| o == op).unwrap() as u8, index[&a]),
+            Op::Binary(op, a, b) => {
+                VmOp::Binary(BINARY.iter().position(|&o| o == op).unwrap() as u8, index[&a], index[&b])
+            }
+        });
+        index.insert(node, ops.len() as u32 - 1);
+    }
+
+    let file = VmFile {
+        ops,
+        root: index[&root],
+        parts: parts.iter().map(|(name, node)| (name.to_string(), index[node])).collect(),
+    };
+    let mut out = VM_MAGIC.to_vec();
+    out.extend_from_slice(&VM_FORMAT.to_le_bytes());
+    bincode::serialize_into(&mut out, &file).context("Failed to encode the shape")?;
+    Ok(out)
+}
+
+/// Rebuild the shapes in a compiled shape file
+pub fn parse_vm(data: &[u8]) -> Result<VmShapes> {
+    let body = data.strip_prefix(VM_MAGIC.as_slice()).ok_or_else(|| anyhow!("not a horseCAD compiled shape"))?;
+    let (version, body) = body.split_first_chunk::<4>().ok_or_else(|| anyhow!("file is truncated"))?;
+    let version = u32::from_le_bytes(*version);
+    if version != VM_FORMAT {
+        bail!("compiled shape format {} isn't supported; recompile the script", version);
+    }
+    let file: VmFile = bincode::deserialize(body).context("file is corrupt")?;
+
+    let mut trees: Vec<Tree> = Vec::with_capacity(file.ops.len());
+    for op in file.ops {
+        // Operands always come first, so an out-of-order index means a damaged file
+        let get = |i: u32| trees.get(i as usize).cloned().ok_or_else(|| anyhow!("file is corrupt"));
+        let tree = match op {
+            VmOp::X => Tree::x(),
+            VmOp::Y => Tree::y(),
+            VmOp::Z => Tree::z(),
+            VmOp::Const(c) => Tree::constant(c),
+            VmOp::Unary(op, a) => unary(*UNARY.get(op as usize).ok_or_else(|| anyhow!("unknown opcode"))?, &get(a)?),
+            VmOp::Binary(op, a, b) => {
+                binary(*BINARY.get(op as usize).ok_or_else(|| anyhow!("unknown opcode"))?, get(a)?, get(b)?)
+            }
+        };
+        trees.push(tree);
+    }
+    let get = |i: u32| trees.get(i as usize).cloned().ok_or_else(|| anyhow!("file is corrupt"));
+    let parts = file.parts.into_iter().map(|(name, i)| Ok((name, get(i)?))).collect::<Result<_>>()?;
+    Ok(VmShapes { root: get(file.root)?, parts })
+}
+
+/// Apply the unary opcode numbered `op` in files to `a`, or `None` if there's no such opcode
+pub fn unary_opcode(op: u8, a: &Tree) -> Option<Tree> {
+    UNARY.get(op as usize).map(|&op| unary(op, a))
+}
+
+/// Apply the binary opcode numbered `op` in files to `a` and `b`, or `None` if there's no such opcode
+pub fn binary_opcode(op: u8, a: Tree, b: Tree) -> Option<Tree> {
+    BINARY.get(op as usize).map(|&op| binary(op, a, b))
+}
+
+fn unary(op: UnaryOpcode, a: &Tree) -> Tree {
+    match op {
+        UnaryOpcode::Neg => a.neg(),
+        UnaryOpcode::Abs => a.abs(),
+        UnaryOpcode::Recip => a.recip(),
+        UnaryOpcode::Sqrt => a.sqrt(),
+        UnaryOpcode::Square => a.square(),
+        UnaryOpcode::Floor => a.floor(),
+        UnaryOpcode::Ceil => a.ceil(),
+        UnaryOpcode::Round => a.round(),
+        UnaryOpcode::Sin => a.sin(),
+        UnaryOpcode::Cos => a.cos(),
+        UnaryOpcode::Tan => a.tan(),
+        UnaryOpcode::Asin => a.asin(),
+        UnaryOpcode::Acos => a.acos(),
+        UnaryOpcode::Atan => a.atan(),
+        UnaryOpcode::Exp => a.exp(),
+        UnaryOpcode::Ln => a.ln(),
+        UnaryOpcode::Not => a.not(),
+    }
+}
+
+fn binary(op: BinaryOpcode, a: Tree, b: Tree) -> Tree {
+    match op {
+        BinaryOpcode::Add => a + b,
+        BinaryOpcode::Sub => a - b,
+        BinaryOpcode::Mul => a * b,
+        BinaryOpcode::Div => a / b,
+        BinaryOpcode::Atan => a.atan2(b),
+        BinaryOpcode::Min => a.min(b),
+        BinaryOpcode::Max => a.max(b),
+        BinaryOpcode::Compare => a.compare(b),
+        BinaryOpcode::Mod => a.modulo(b),
+        BinaryOpcode::And => a.and(b),
+        BinaryOpcode::Or => a.or(b),
+    }
+}
@@ -0,0 +1,147 @@
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::export::sanitize_file_stem;
+
+/// Versions kept for each script; the oldest are deleted past this
+pub const MAX_VERSIONS: usize = 100;
+
+/// What stored a version
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VersionReason {
+    Compile,
+    Save,
+}
+
+/// A stored copy of a script
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptVersion {
+    /// File name of the version in its script's history folder, without the extension
+    pub id: String,
+    /// File the script was saved to, or the document id of an unsaved one
+    pub script: String,
+    pub reason: VersionReason,
+    /// RFC 3339 time the version was stored
+    pub saved_at: String,
+    pub content: String,
+}
+
+/// A version as listed by `list_versions`, without its content
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersionInfo {
+    pub id: String,
+    pub reason: VersionReason,
+    pub saved_at: String,
+    pub lines: usize,
+    pub bytes: usize,
+}
+
+/// Timestamped copies of scripts, stored on every successful compile and save
+///
+/// Each script gets a folder under the history folder, named after it and
+/// a hash of its path, holding one JSON file per version.  A version the
+/// same as the script's newest isn't stored again.  This is a safety net
+/// apart from git and the save backups, so it is kept across sessions.
+pub struct VersionHistory {
+    root: Option<PathBuf>,
+    /// Serializes writes, so two compiles can't both prune one folder
+    lock: Mutex<()>,
+}
+
+impl VersionHistory {
+    pub fn new(root: Option<PathBuf>) -> Self {
+        VersionHistory { root, lock: Mutex::new(()) }
+    }
+
+    /// Store `content` as the newest version of `script`, returning whether it was new
+    pub fn record(&self, script: &str, reason: VersionReason, content: &str) -> Result<bool, String> {
+        let Some(folder) = self.folder(script) else { return Ok(false) };
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut versions = version_files(&folder);
+        if let Some(newest) = versions.last().and_then(|file| read_version(file)) {
+            if newest.content == content {
+                return Ok(false);
+            }
+        }
+
+        std::fs::create_dir_all(&folder)
+            .map_err(|e| format!("Failed to create history folder {}: {}", folder.display(), e))?;
+        let now = chrono::Utc::now();
+        // Sortable by name, and unique even for two versions within a millisecond
+        let mut id = now.format("%Y%m%dT%H%M%S%3f").to_string();
+        if versions.iter().any(|file| file.file_stem().is_some_and(|stem| stem.to_string_lossy() == id)) {
+            id.push_str(&format!("_{}", versions.len()));
+        }
+        let version = ScriptVersion {
+            id: id.clone(),
+            script: script.to_string(),
+            reason,
+            saved_at: now.to_rfc3339(),
+            content: content.to_string(),
+        };
+        let file = folder.join(format!("{}.json", id));
+        let text = serde_json::to_string(&version).map_err(|e| e.to_string())?;
+        std::fs::write(&file, text).map_err(|e| format!("Failed to store version {}: {}", file.display(), e))?;
+        versions.push(file);
+
+        let excess = versions.len().saturating_sub(MAX_VERSIONS);
+        for old in versions.drain(..excess) {
+            let _ = std::fs::remove_file(old);
+        }
+        Ok(true)
+    }
+
+    /// The stored versions of `script`, newest first
+    pub fn list(&self, script: &str) -> Vec<VersionInfo> {
+        let Some(folder) = self.folder(script) else { return Vec::new() };
+        version_files(&folder)
+            .iter()
+            .rev()
+            .filter_map(|file| read_version(file))
+            .map(|version| VersionInfo {
+                lines: version.content.lines().count(),
+                bytes: version.content.len(),
+                id: version.id,
+                reason: version.reason,
+                saved_at: version.saved_at,
+            })
+            .collect()
+    }
+
+    /// Version `id` of `script`
+    pub fn get(&self, script: &str, id: &str) -> Option<ScriptVersion> {
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return None;
+        }
+        read_version(&self.folder(script)?.join(format!("{}.json", id)))
+    }
+
+    fn folder(&self, script: &str) -> Option<PathBuf> {
+        let name = Path::new(script).file_stem().map_or_else(|| script.into(), |stem| stem.to_string_lossy());
+        let folder = format!("{}-{:016x}", sanitize_file_stem(&name), fnv1a(script.as_bytes()));
+        self.root.as_ref().map(|root| root.join(folder))
+    }
+}
+
+/// Version files in `folder`, oldest first
+fn version_files(folder: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(folder) else { return Vec::new() };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    files.sort();
+    files
+}
+
+fn read_version(file: &Path) -> Option<ScriptVersion> {
+    serde_json::from_str(&std::fs::read_to_string(file).ok()?).ok()
+}
+
+/// 64-bit FNV-1a, which unlike `DefaultHasher` gives the same folder names in every build
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3))
+}
@@ -0,0 +1,154 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::export::{ExportFormat, ModelUnit};
+use crate::mesh::quality::{EvalBackend, QualityPreset};
+use crate::render::RenderCamera;
+use crate::BuildSettings;
+
+/// Opens the metadata section kept at the end of a `.horsi` file
+const METADATA_START: &str = "/* horsecad-metadata\n";
+
+/// Closes the metadata section
+const METADATA_END: &str = "*/\n";
+
+/// Settings and a preview saved alongside a script in its `.horsi` file
+///
+/// Kept as a block comment after the script, so the file still runs as a
+/// plain script, keeps its line numbers, and opens in older versions.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DocumentMetadata {
+    /// Octree depth the script was last compiled at
+    pub depth: Option<u8>,
+    /// Quality preset of the last compile, which beats `depth` if set
+    pub quality: Option<QualityPreset>,
+    pub scale: Option<f32>,
+    pub center: Option<[f32; 3]>,
+    /// Whether the last compile fitted the meshing region to the shape
+    pub auto_bounds: Option<bool>,
+    /// Evaluator the last compile used
+    pub backend: Option<EvalBackend>,
+    /// Parameter values set in the editor, by name
+    pub params: HashMap<String, f64>,
+    /// Base64-encoded PNG preview of the model
+    pub thumbnail: Option<String>,
+    /// Settings pinned to the script, which compiles and exports use over the editor's
+    pub project: Option<ProjectSettings>,
+    /// Saved camera views, by name
+    pub views: BTreeMap<String, RenderCamera>,
+}
+
+/// How a script was last compiled, for compiling it the same way after it's reopened
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompileDefaults {
+    pub depth: Option<u8>,
+    pub quality: Option<QualityPreset>,
+    pub scale: Option<f32>,
+    pub center: Option<[f32; 3]>,
+    pub auto_bounds: Option<bool>,
+    pub backend: Option<EvalBackend>,
+}
+
+/// Build settings that travel with a script
+///
+/// Compiling or exporting the script uses these wherever they are set,
+/// rather than whatever the editor is showing, so it builds the same way
+/// on every machine.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProjectSettings {
+    #[serde(flatten)]
+    pub build: BuildSettings,
+    /// Unit model coordinates are in, for formats that record one
+    pub unit: Option<ModelUnit>,
+    /// Format exports default to
+    pub export_format: Option<ExportFormat>,
+}
+
+/// Settings pinned to the script at `path`, if it has any
+pub fn read_project_settings(path: &Path) -> Option<ProjectSettings> {
+    HorsiFile::parse(&std::fs::read_to_string(path).ok()?).metadata?.project
+}
+
+/// Camera views saved in the file at `path`, empty if it has none or can't be read
+pub fn read_views(path: &Path) -> BTreeMap<String, RenderCamera> {
+    let metadata = std::fs::read_to_string(path).ok().and_then(|text| HorsiFile::parse(&text).metadata);
+    metadata.map(|metadata| metadata.views).unwrap_or_default()
+}
+
+impl DocumentMetadata {
+    pub fn compile_defaults(&self) -> CompileDefaults {
+        CompileDefaults {
+            depth: self.depth,
+            quality: self.quality,
+            scale: self.scale,
+            center: self.center,
+            auto_bounds: self.auto_bounds,
+            backend: self.backend,
+        }
+    }
+
+    pub fn set_compile_defaults(&mut self, defaults: CompileDefaults) {
+        self.depth = defaults.depth;
+        self.quality = defaults.quality;
+        self.scale = defaults.scale;
+        self.center = defaults.center;
+        self.auto_bounds = defaults.auto_bounds;
+        self.backend = defaults.backend;
+    }
+
+    /// Check the fields that are written into the file as they are
+    pub fn validate(&self) -> Result<(), String> {
+        let Some(thumbnail) = &self.thumbnail else { return Ok(()) };
+        let is_base64 = thumbnail.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'/' | b'='));
+        if !is_base64 {
+            return Err("The thumbnail must be base64-encoded PNG data".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// A `.horsi` file split into its script and metadata
+#[derive(Debug, Clone, PartialEq)]
+pub struct HorsiFile {
+    pub script: String,
+    /// `None` for plain scripts, as written before metadata was kept
+    pub metadata: Option<DocumentMetadata>,
+}
+
+impl HorsiFile {
+    /// Split the contents of a `.horsi` file
+    ///
+    /// Files without a metadata section, or with one that doesn't parse,
+    /// are taken as a plain script.
+    pub fn parse(text: &str) -> Self {
+        let plain = || HorsiFile { script: text.to_string(), metadata: None };
+        let start = match text.rfind(METADATA_START) {
+            Some(0) => 0,
+            Some(i) if text[..i].ends_with('\n') => i,
+            _ => return plain(),
+        };
+        let Some(json) = text[start + METADATA_START.len()..].strip_suffix(METADATA_END) else { return plain() };
+        match serde_json::from_str(json) {
+            Ok(metadata) => {
+                // `to_text` separates the section from the script with a newline
+                let script = text[..start].strip_suffix('\n').unwrap_or(&text[..start]);
+                HorsiFile { script: script.to_string(), metadata: Some(metadata) }
+            }
+            Err(_) => plain(),
+        }
+    }
+
+    /// The file contents, with the metadata section after the script
+    pub fn to_text(&self) -> Result<String, String> {
+        let Some(metadata) = &self.metadata else { return Ok(self.script.clone()) };
+        metadata.validate()?;
+        // `*/` can only appear inside JSON strings, where `\/` reads back the same
+        let json = serde_json::to_string_pretty(metadata).map_err(|e| e.to_string())?.replace("*/", "*\\/");
+        Ok(format!("{}\n{}{}\n{}", self.script, METADATA_START, json, METADATA_END))
+    }
+}
@@ -0,0 +1,276 @@
+use std::sync::Arc;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, Window};
+
+use crate::log_file::LogFile;
+use crate::log_history::LogHistory;
+use crate::plugins::{PluginFunction, Plugins};
+use crate::script::extensions::{ExtensionModules, Extensions};
+use crate::state::{MeshOptionsState, ScriptCache, ScriptLimits, ScriptLimitsState, DEFAULT_DOCUMENT_ID};
+use crate::{CompileProgress, LogEntry};
+
+/// Shareable sink for log entries, for callbacks that outlive a borrow of the host
+pub type LogSink = Arc<dyn Fn(&LogEntry) + Send + Sync>;
+
+/// Whatever is running the compile pipeline: the app, or the command line
+///
+/// The pipeline reports progress and reads shared state through this, so
+/// the same code serves the Tauri commands and the headless CLI.
+pub trait Host: Send + Sync {
+    fn log(&self, entry: &LogEntry);
+    /// Report that a compile stage has started
+    fn progress(&self, progress: &CompileProgress);
+    /// Handle on `log` that script callbacks can keep
+    fn log_sink(&self) -> LogSink;
+    fn script_cache(&self) -> &ScriptCache;
+    fn mesh_options(&self) -> &MeshOptionsState;
+    fn script_limits(&self) -> ScriptLimits;
+    /// Extension modules scripts can import
+    fn extensions(&self) -> Arc<ExtensionModules>;
+    /// Script functions added by native plugins
+    fn plugin_functions(&self) -> Arc<Vec<PluginFunction>>;
+}
+
+/// Write `entry` to the log file and history, returning whether it's at the level sent to the frontend
+fn keep_log(app: &AppHandle, entry: &LogEntry) -> bool {
+    if let Some(log_file) = app.try_state::<LogFile>() {
+        log_file.write(entry);
+    }
+    app.try_state::<LogHistory>().is_none_or(|history| history.record(entry))
+}
+
+/// The app forwards everything to the frontend as events
+impl Host for AppHandle {
+    fn log(&self, entry: &LogEntry) {
+        if !keep_log(self, entry) {
+            return;
+        }
+        if let Err(e) = self.emit("log_entry", entry) {
+            eprintln!("Failed to emit log entry: {}", e);
+        }
+    }
+
+    fn progress(&self, progress: &CompileProgress) {
+        if let Err(e) = self.emit("compile_progress", progress) {
+            eprintln!("Failed to emit compile_progress event: {}", e);
+        }
+    }
+
+    fn log_sink(&self) -> LogSink {
+        let handle = self.clone();
+        Arc::new(move |entry| handle.log(entry))
+    }
+
+    fn script_cache(&self) -> &ScriptCache {
+        self.state::<ScriptCache>().inner()
+    }
+
+    fn mesh_options(&self) -> &MeshOptionsState {
+        self.state::<MeshOptionsState>().inner()
+    }
+
+    fn script_limits(&self) -> ScriptLimits {
+        self.state::<ScriptLimitsState>().get()
+    }
+
+    fn extensions(&self) -> Arc<ExtensionModules> {
+        self.try_state::<Extensions>().map(|extensions| extensions.modules()).unwrap_or_default()
+    }
+
+    fn plugin_functions(&self) -> Arc<Vec<PluginFunction>> {
+        self.try_state::<Plugins>().map(|plugins| plugins.functions()).unwrap_or_default()
+    }
+}
+
+/// Host for one of the app's windows, sending events to that window alone
+///
+/// Each window edits its own documents, so compiles started from it keep
+/// their logs and progress to it, and their queue entries and caches apart
+/// from other windows' documents of the same id.
+#[derive(Clone)]
+pub struct WindowHost {
+    app: AppHandle,
+    label: String,
+}
+
+impl WindowHost {
+    pub fn new(window: &Window) -> Self {
+        WindowHost { app: window.app_handle().clone(), label: window.label().to_string() }
+    }
+
+    pub fn app(&self) -> &AppHandle {
+        &self.app
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Identifies `document_id` of this window in state shared by all windows
+    pub fn document_key(&self, document_id: Option<&str>) -> String {
+        format!("{}/{}", self.label, document_id.unwrap_or(DEFAULT_DOCUMENT_ID))
+    }
+
+    /// Prefix of the keys of this window's documents
+    pub fn document_prefix(&self) -> String {
+        format!("{}/", self.label)
+    }
+
+    /// Send an event to this window
+    pub fn emit<S: Serialize + Clone>(&self, event: &str, payload: S) {
+        if let Err(e) = self.app.emit_to(self.label.as_str(), event, payload) {
+            eprintln!("Failed to emit {} event: {}", event, e);
+        }
+    }
+}
+
+impl Host for WindowHost {
+    fn log(&self, entry: &LogEntry) {
+        if keep_log(&self.app, entry) {
+            self.emit("log_entry", entry);
+        }
+    }
+
+    fn progress(&self, progress: &CompileProgress) {
+        self.emit("compile_progress", progress);
+    }
+
+    fn log_sink(&self) -> LogSink {
+        let host = self.clone();
+        Arc::new(move |entry| host.log(entry))
+    }
+
+    fn script_cache(&self) -> &ScriptCache {
+        self.app.script_cache()
+    }
+
+    fn mesh_options(&self) -> &MeshOptionsState {
+        self.app.mesh_options()
+    }
+
+    fn script_limits(&self) -> ScriptLimits {
+        self.app.script_limits()
+    }
+
+    fn extensions(&self) -> Arc<ExtensionModules> {
+        self.app.extensions()
+    }
+
+    fn plugin_functions(&self) -> Arc<Vec<PluginFunction>> {
+        self.app.plugin_functions()
+    }
+}
+
+/// Host for requests to the HTTP API, logging to the app without reporting progress
+///
+/// API compiles aren't any window's, so they leave the editors' progress
+/// bars alone.
+#[derive(Clone)]
+pub struct ApiHost {
+    app: AppHandle,
+}
+
+impl ApiHost {
+    pub fn new(app: AppHandle) -> Self {
+        ApiHost { app }
+    }
+
+    pub fn app(&self) -> &AppHandle {
+        &self.app
+    }
+}
+
+impl Host for ApiHost {
+    fn log(&self, entry: &LogEntry) {
+        self.app.log(entry);
+    }
+
+    fn progress(&self, _progress: &CompileProgress) {}
+
+    fn log_sink(&self) -> LogSink {
+        self.app.log_sink()
+    }
+
+    fn script_cache(&self) -> &ScriptCache {
+        self.app.script_cache()
+    }
+
+    fn mesh_options(&self) -> &MeshOptionsState {
+        self.app.mesh_options()
+    }
+
+    fn script_limits(&self) -> ScriptLimits {
+        self.app.script_limits()
+    }
+
+    fn extensions(&self) -> Arc<ExtensionModules> {
+        self.app.extensions()
+    }
+
+    fn plugin_functions(&self) -> Arc<Vec<PluginFunction>> {
+        self.app.plugin_functions()
+    }
+}
+
+/// Host for the command line, printing log entries to stderr
+///
+/// Warnings, errors and script `print()` output are always shown; progress
+/// messages only when `verbose` is set.
+#[derive(Default)]
+pub struct ConsoleHost {
+    pub verbose: bool,
+    cache: ScriptCache,
+    mesh_options: MeshOptionsState,
+    limits: ScriptLimits,
+}
+
+impl ConsoleHost {
+    pub fn new(verbose: bool) -> Self {
+        ConsoleHost { verbose, ..Default::default() }
+    }
+}
+
+fn print_entry(entry: &LogEntry, verbose: bool) {
+    match entry.level.as_str() {
+        "error" | "warn" => eprintln!("{}: {}", entry.level, entry.message),
+        _ if verbose => eprintln!("{}", entry.message),
+        "info" if entry.source.as_deref() == Some("Script") => eprintln!("{}", entry.message),
+        _ => {}
+    }
+}
+
+impl Host for ConsoleHost {
+    fn log(&self, entry: &LogEntry) {
+        print_entry(entry, self.verbose);
+    }
+
+    fn progress(&self, _progress: &CompileProgress) {}
+
+    fn log_sink(&self) -> LogSink {
+        let verbose = self.verbose;
+        Arc::new(move |entry| print_entry(entry, verbose))
+    }
+
+    fn script_cache(&self) -> &ScriptCache {
+        &self.cache
+    }
+
+    fn mesh_options(&self) -> &MeshOptionsState {
+        &self.mesh_options
+    }
+
+    fn script_limits(&self) -> ScriptLimits {
+        self.limits
+    }
+
+    /// The command line has no config folder to keep extensions in
+    fn extensions(&self) -> Arc<ExtensionModules> {
+        Arc::default()
+    }
+
+    /// Nor any plugins
+    fn plugin_functions(&self) -> Arc<Vec<PluginFunction>> {
+        Arc::default()
+    }
+}
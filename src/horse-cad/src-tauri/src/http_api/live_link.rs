@@ -0,0 +1,341 @@
+use std::io::{ErrorKind, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use tungstenite::error::ProtocolError;
+use tungstenite::handshake::derive_accept_key;
+use tungstenite::protocol::{Role, WebSocketConfig};
+use tungstenite::{Message, WebSocket};
+
+use super::{with_app_defaults, Request, Response, MAX_BODY_BYTES};
+use crate::host::{ApiHost, Host, LogSink};
+use crate::mesh::buffers::{MeshBuffers, DEFAULT_CHUNK_TRIANGLES};
+use crate::mesh::color::to_hex;
+use crate::plugins::PluginFunction;
+use crate::script::extensions::ExtensionModules;
+use crate::state::{CancelToken, LastMesh, MeshCache, MeshOrigin, MeshOptionsState, ScriptCache, ScriptLimits};
+use crate::utils::file_utils::sha256_hex;
+use crate::utils::ipc_utils::encode_binary_frame;
+use crate::{
+    compile_progressive, emit_log, BuildSettings, CompileProgress, CompileTimings, LogEntry, MeshChunkHeader,
+    PreviewResult, ScriptSource,
+};
+
+/// Document id pushed scripts compile as, in the main window
+const LIVE_LINK_DOCUMENT_ID: &str = "live-link";
+
+/// Window that previews pushed scripts
+const LIVE_LINK_WINDOW: &str = "main";
+
+/// Longest the connection waits for the editor before sending what the compile has queued
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A message from the editor
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    /// The script's new contents; `path` is where it's saved, for imports and pinned settings
+    Update {
+        code: String,
+        path: Option<String>,
+        #[serde(default)]
+        settings: BuildSettings,
+    },
+}
+
+/// A message to the editor, as a JSON text frame
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage<'a> {
+    Log(&'a LogEntry),
+    Progress(&'a CompileProgress),
+    /// Coarse mesh of a deep compile still under way, held in the result's `buffers`
+    Preview { result: &'a PreviewResult },
+    /// Outcome of a compile; on success, binary frames with the mesh follow
+    Result { result: &'a PreviewResult, chunk_count: usize },
+    /// A message that couldn't be handled
+    Error { message: String },
+}
+
+/// Payload of the `live_link_update` event, sent to the main window before each compile
+#[derive(Clone, Serialize)]
+struct LiveLinkUpdate<'a> {
+    code: &'a str,
+    path: Option<&'a str>,
+}
+
+/// Messages waiting to go to the editor, sent by the connection's thread between reads
+///
+/// Only that thread touches the WebSocket, so messages from the compile
+/// worker never interleave with the replies to pings it sends.
+#[derive(Clone)]
+struct Outbox(Sender<Message>);
+
+impl Outbox {
+    /// Queue `message`, ignoring failures; a closed connection is noticed by the reader
+    fn send_message(&self, message: &ServerMessage) {
+        if let Ok(json) = serde_json::to_string(message) {
+            let _ = self.0.send(Message::text(json));
+        }
+    }
+
+    fn send_binary(&self, data: Vec<u8>) {
+        let _ = self.0.send(Message::binary(data));
+    }
+}
+
+/// Host for a pushed script: everything goes to the editor, and logs to the app as well
+#[derive(Clone)]
+struct LiveHost {
+    api: ApiHost,
+    outbox: Outbox,
+}
+
+impl Host for LiveHost {
+    fn log(&self, entry: &LogEntry) {
+        self.api.log(entry);
+        self.outbox.send_message(&ServerMessage::Log(entry));
+    }
+
+    fn progress(&self, progress: &CompileProgress) {
+        self.outbox.send_message(&ServerMessage::Progress(progress));
+    }
+
+    fn log_sink(&self) -> LogSink {
+        let host = self.clone();
+        Arc::new(move |entry| host.log(entry))
+    }
+
+    fn script_cache(&self) -> &ScriptCache {
+        self.api.script_cache()
+    }
+
+    fn mesh_options(&self) -> &MeshOptionsState {
+        self.api.mesh_options()
+    }
+
+    fn script_limits(&self) -> ScriptLimits {
+        self.api.script_limits()
+    }
+
+    fn extensions(&self) -> Arc<ExtensionModules> {
+        self.api.extensions()
+    }
+
+    fn plugin_functions(&self) -> Arc<Vec<PluginFunction>> {
+        self.api.plugin_functions()
+    }
+}
+
+/// The client's `Sec-WebSocket-Key`, if `request` is a WebSocket handshake the live link accepts
+///
+/// Browsers let any page open a WebSocket to localhost, but always say
+/// which page it is, so requests with an `Origin` are refused.
+pub fn upgrade_key(request: &Request) -> Result<String, Response> {
+    if request.method != "GET" {
+        return Err(Response::error(405, "Method not allowed"));
+    }
+    if request.header("origin").is_some() {
+        return Err(Response::error(403, "The live link doesn't accept connections from web pages"));
+    }
+    let upgrade = request.header("upgrade").is_some_and(|value| value.eq_ignore_ascii_case("websocket"));
+    if !upgrade || request.header("sec-websocket-version") != Some("13") {
+        return Err(Response::error(400, "Connect to /live with a version 13 WebSocket"));
+    }
+    request
+        .header("sec-websocket-key")
+        .map(str::to_string)
+        .ok_or_else(|| Response::error(400, "Missing Sec-WebSocket-Key"))
+}
+
+/// Live link to an external editor, over a WebSocket at `/live` on the HTTP API
+///
+/// The editor sends `{"type": "update", "code": "...", "path": "...",
+/// "settings": {...}}` as it's edited.  Each update is shown in the main
+/// window as a `live_link_update` event and compiled, cancelling any
+/// compile of an older update, with logs and progress streamed back as
+/// `log` and `progress` messages.  Deep compiles first send a `preview`
+/// message with a coarse mesh inline in its `PreviewResult`, as the
+/// viewport's progressive compiles do, so the editor sees the change within
+/// moments.  A `result` message holding a `PreviewResult` ends each
+/// compile; if it succeeded, the full mesh follows in `chunk_count` binary
+/// messages framed as by `stream_mesh`.  The main window gets both meshes
+/// as `live_link_preview` events.  The mesh is kept as the main window's
+/// `live-link` document, so it can be exported.
+pub fn serve(app: &AppHandle, mut stream: TcpStream, key: &str) {
+    let handshake = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {}\r\n\r\n",
+        derive_accept_key(key.as_bytes())
+    );
+    // Reads time out so queued messages still go out while the editor is idle
+    let opened = stream.write_all(handshake.as_bytes()).and_then(|_| stream.set_read_timeout(Some(POLL_INTERVAL)));
+    if let Err(e) = opened {
+        eprintln!("Failed to open the live link: {}", e);
+        return;
+    }
+    let config = WebSocketConfig::default().max_message_size(Some(MAX_BODY_BYTES)).max_frame_size(Some(MAX_BODY_BYTES));
+    let mut socket = WebSocket::from_raw_socket(stream, Role::Server, Some(config));
+    emit_log(app, "info", "Editor connected to the live link", Some("System"));
+
+    let (outgoing, queued) = mpsc::channel();
+    let outbox = Outbox(outgoing);
+    let (updates, queue) = mpsc::channel();
+    let current = Arc::new(Mutex::new(CancelToken::new()));
+    let host = LiveHost { api: ApiHost::new(app.clone()), outbox: outbox.clone() };
+    let worker = {
+        let current = current.clone();
+        std::thread::spawn(move || compile_updates(&host, queue, &current))
+    };
+
+    loop {
+        // Pings are answered and closes completed by tungstenite as it reads
+        let read = queued
+            .try_iter()
+            .try_for_each(|message| socket.write(message))
+            .and_then(|_| socket.flush())
+            .and_then(|_| socket.read());
+        match read {
+            Ok(Message::Text(text)) => match serde_json::from_str::<ClientMessage>(&text) {
+                Ok(update) => {
+                    current.lock().unwrap_or_else(|e| e.into_inner()).cancel();
+                    let _ = updates.send(update);
+                }
+                Err(e) => outbox.send_message(&ServerMessage::Error { message: format!("Invalid message: {}", e) }),
+            },
+            Ok(Message::Binary(_)) => {
+                outbox.send_message(&ServerMessage::Error { message: "Send messages as JSON text".to_string() });
+            }
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(e)) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(
+                tungstenite::Error::ConnectionClosed
+                | tungstenite::Error::Protocol(ProtocolError::ResetWithoutClosingHandshake),
+            ) => break,
+            Err(tungstenite::Error::Capacity(_)) => {
+                let error = ServerMessage::Error { message: "Message is too large".to_string() };
+                if let Ok(json) = serde_json::to_string(&error) {
+                    let _ = socket.send(Message::text(json));
+                }
+                break;
+            }
+            Err(e) => {
+                eprintln!("Live link connection failed: {}", e);
+                break;
+            }
+        }
+    }
+
+    current.lock().unwrap_or_else(|e| e.into_inner()).cancel();
+    drop(updates);
+    let _ = worker.join();
+    emit_log(app, "info", "Editor disconnected from the live link", Some("System"));
+}
+
+/// Compile each update from `queue` in turn, skipping any overtaken while the last one compiled
+fn compile_updates(host: &LiveHost, queue: Receiver<ClientMessage>, current: &Mutex<CancelToken>) {
+    let mut generation = 0;
+    while let Ok(mut update) = queue.recv() {
+        while let Ok(newer) = queue.try_recv() {
+            update = newer;
+        }
+        let ClientMessage::Update { code, path, settings } = update;
+        generation += 1;
+        let cancel = CancelToken::new();
+        *current.lock().unwrap_or_else(|e| e.into_inner()) = cancel.clone();
+
+        let app = host.api.app();
+        let payload = LiveLinkUpdate { code: &code, path: path.as_deref() };
+        if let Err(e) = app.emit_to(LIVE_LINK_WINDOW, "live_link_update", payload) {
+            eprintln!("Failed to emit live_link_update event: {}", e);
+        }
+        let settings = with_app_defaults(app, &settings);
+        let source = ScriptSource::new(code, path.as_deref());
+        let mut timings = CompileTimings::default();
+        let compiled = compile_progressive(
+            host,
+            LIVE_LINK_DOCUMENT_ID,
+            &source,
+            &settings.params,
+            &settings.mesh_request(),
+            &cancel,
+            &mut timings,
+            &|preview| {
+                let preview = PreviewResult { generation, ..preview };
+                host.outbox.send_message(&ServerMessage::Preview { result: &preview });
+                if let Err(e) = app.emit_to(LIVE_LINK_WINDOW, "live_link_preview", &preview) {
+                    eprintln!("Failed to emit live_link_preview event: {}", e);
+                }
+            },
+        );
+        if cancel.is_cancelled() {
+            continue;
+        }
+        let compiled = match compiled {
+            Ok(compiled) => compiled,
+            Err(e) => {
+                let result = PreviewResult {
+                    generation,
+                    timings,
+                    crashed: e.crashed,
+                    error: Some(e.message),
+                    diagnostics: e.diagnostics,
+                    ..Default::default()
+                };
+                host.outbox.send_message(&ServerMessage::Result { result: &result, chunk_count: 0 });
+                continue;
+            }
+        };
+
+        let triangle_count = compiled.mesh.triangles.len();
+        let mut result = PreviewResult {
+            success: true,
+            generation,
+            triangle_count: Some(triangle_count),
+            bounds: compiled.bounds,
+            shape_triangle_counts: compiled.shape_triangle_counts,
+            shape_colors: compiled.shape_colors.iter().map(|c| c.map(to_hex)).collect(),
+            parameters: compiled.params,
+            timings,
+            outline: compiled.outline.clone(),
+            ..Default::default()
+        };
+        let chunk_count = triangle_count.div_ceil(DEFAULT_CHUNK_TRIANGLES);
+        host.outbox.send_message(&ServerMessage::Result { result: &result, chunk_count });
+        let chunks = MeshBuffers::chunks(&compiled.mesh, DEFAULT_CHUNK_TRIANGLES);
+        for (index, chunk) in chunks.enumerate() {
+            let header = MeshChunkHeader {
+                index,
+                chunk_count,
+                first_triangle: index * DEFAULT_CHUNK_TRIANGLES,
+                vertex_count: chunk.positions.len() / 3,
+                triangle_count: chunk.indices.len() / 3,
+            };
+            if let Ok(frame) = encode_binary_frame(&header, &chunk.to_bytes()) {
+                host.outbox.send_binary(frame);
+            }
+        }
+
+        result.buffers = Some(MeshBuffers::from_mesh(&compiled.mesh));
+        if let Err(e) = app.emit_to(LIVE_LINK_WINDOW, "live_link_preview", &result) {
+            eprintln!("Failed to emit live_link_preview event: {}", e);
+        }
+        let origin = MeshOrigin {
+            script_hash: sha256_hex(source.code.as_bytes()),
+            parameters: result.parameters.clone(),
+            timings: result.timings,
+        };
+        let last = LastMesh {
+            mesh: compiled.mesh,
+            triangle_colors: compiled.triangle_colors,
+            units: compiled.units,
+            origin: Some(origin),
+        };
+        let key = format!("{}/{}", LIVE_LINK_WINDOW, LIVE_LINK_DOCUMENT_ID);
+        app.state::<MeshCache>().insert(&key, Arc::new(last));
+    }
+}
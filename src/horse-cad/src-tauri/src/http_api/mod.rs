@@ -0,0 +1,420 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::export::{precision::PrecisionOptions, ExportFormat, ExportOptions, ModelUnit};
+use crate::host::ApiHost;
+use crate::mesh::measure::MeshMeasurements;
+use crate::mesh::normals::NormalOptions;
+use crate::mesh::validate::MeshReport;
+use crate::script::diagnostics::Diagnostic;
+use crate::settings::SettingsStore;
+use crate::state::CancelToken;
+use crate::{build_model, compile_mesh, emit_log, BuildSettings, CompileTimings, ScriptParam, ScriptSource};
+
+mod live_link;
+
+/// Port the API listens on unless the settings pick another
+pub const DEFAULT_HTTP_API_PORT: u16 = 7878;
+
+/// Largest request body accepted
+const MAX_BODY_BYTES: usize = 16 * 1024 * 1024;
+
+/// Largest request line and headers accepted, together
+const MAX_HEADER_BYTES: usize = 64 * 1024;
+
+/// How long a client may take to send its request
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often the listener checks whether it's been stopped
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Key of API compiles in the script cache, apart from every window's documents
+const API_DOCUMENT_ID: &str = "http-api";
+
+/// Whether and where the HTTP API listens
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpApiOptions {
+    pub enabled: bool,
+    pub port: u16,
+}
+
+impl Default for HttpApiOptions {
+    fn default() -> Self {
+        HttpApiOptions { enabled: false, port: DEFAULT_HTTP_API_PORT }
+    }
+}
+
+struct Server {
+    port: u16,
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+/// Managed state for the optional HTTP API, letting other tools compile and export scripts
+///
+/// The server listens on 127.0.0.1 only, while the app is open and the
+/// API is enabled in the settings.  Endpoints:
+///
+/// - `GET /status`: the app's name and version
+/// - `POST /compile`: mesh a script, returning its measurements and checks as JSON
+/// - `POST /export`: mesh a script, returning the file in the body
+/// - `GET /live`: a WebSocket for editors to push scripts to; see `live_link`
+///
+/// Both POSTs take a JSON body of `{"script": "...", "settings": {...}}`,
+/// settings being those saved with a `.horsi` file, and `/export` also
+/// `format` and `unit`.  Errors come back as `{"error": "..."}`.  Requests
+/// must say they're JSON and be addressed to `localhost` or `127.0.0.1`,
+/// so web pages can't reach the API.  Scripts have no document folder, so
+/// they can only import extensions.
+#[derive(Default)]
+pub struct HttpApi {
+    server: Mutex<Option<Server>>,
+}
+
+impl HttpApi {
+    /// Start, stop or move the server to match `options`
+    pub fn apply(&self, app_handle: &AppHandle, options: HttpApiOptions) {
+        let mut server = self.server.lock().unwrap_or_else(|e| e.into_inner());
+        let port = options.enabled.then_some(options.port);
+        if server.as_ref().map(|server| server.port) == port {
+            return;
+        }
+        if let Some(old) = server.take() {
+            old.stop.store(true, Ordering::Relaxed);
+            let _ = old.thread.join();
+            emit_log(app_handle, "info", "Stopped the HTTP API", Some("System"));
+        }
+        let Some(port) = port else { return };
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port)).and_then(|listener| {
+            listener.set_nonblocking(true)?;
+            Ok(listener)
+        });
+        match listener {
+            Ok(listener) => {
+                let stop = Arc::new(AtomicBool::new(false));
+                let (app, flag) = (app_handle.clone(), stop.clone());
+                let thread = std::thread::spawn(move || serve(app, listener, flag));
+                *server = Some(Server { port, stop, thread });
+                let message = format!("HTTP API listening on http://127.0.0.1:{}", port);
+                emit_log(app_handle, "info", &message, Some("System"));
+            }
+            Err(e) => {
+                let message = format!("Failed to start the HTTP API on port {}: {}", port, e);
+                emit_log(app_handle, "warn", &message, Some("System"));
+            }
+        }
+    }
+}
+
+/// Accept connections until `stop` is set, handling each on its own thread
+fn serve(app: AppHandle, listener: TcpListener, stop: Arc<AtomicBool>) {
+    while !stop.load(Ordering::Relaxed) {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let app = app.clone();
+                std::thread::spawn(move || handle_connection(&app, stream));
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => std::thread::sleep(POLL_INTERVAL),
+            Err(e) => {
+                eprintln!("HTTP API failed to accept a connection: {}", e);
+                std::thread::sleep(POLL_INTERVAL);
+            }
+        }
+    }
+}
+
+fn handle_connection(app: &AppHandle, mut stream: TcpStream) {
+    let setup = stream.set_nonblocking(false).and_then(|_| stream.set_read_timeout(Some(READ_TIMEOUT)));
+    if let Err(e) = setup {
+        eprintln!("HTTP API failed to set up a connection: {}", e);
+        return;
+    }
+    let response = match read_request(&mut stream) {
+        Ok(request) if !request.header("host").is_some_and(is_local_host) => {
+            Response::error(403, "Requests must be addressed to localhost or 127.0.0.1")
+        }
+        Ok(request) if request.path == "/live" => match live_link::upgrade_key(&request) {
+            Ok(key) => return live_link::serve(app, stream, &key),
+            Err(response) => response,
+        },
+        Ok(request) => route(app, &request),
+        Err(response) => response,
+    };
+    if let Err(e) = response.write_to(&mut stream) {
+        eprintln!("HTTP API failed to send a response: {}", e);
+    }
+}
+
+struct Request {
+    method: String,
+    /// Without any query string
+    path: String,
+    /// Names in lower case
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n == name).map(|(_, value)| value.as_str())
+    }
+}
+
+/// Read one HTTP/1.1 request, or the error response to send instead
+fn read_request(stream: &mut TcpStream) -> Result<Request, Response> {
+    let mut reader = BufReader::new(stream);
+    let mut header_bytes = 0;
+    let mut read_line = |reader: &mut BufReader<&mut TcpStream>| {
+        let mut line = String::new();
+        let read = reader
+            .by_ref()
+            .take((MAX_HEADER_BYTES - header_bytes) as u64)
+            .read_line(&mut line)
+            .map_err(|e| Response::error(400, &format!("Failed to read the request: {}", e)))?;
+        header_bytes += read;
+        if read == 0 && header_bytes < MAX_HEADER_BYTES {
+            return Err(Response::error(400, "The connection closed before the request ended"));
+        }
+        if !line.ends_with('\n') {
+            return Err(Response::error(431, "Request headers are too large"));
+        }
+        Ok(line.trim_end().to_string())
+    };
+
+    let request_line = read_line(&mut reader)?;
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target), Some(_version)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(Response::error(400, "Malformed request line"));
+    };
+    let path = target.split('?').next().unwrap_or(target).to_string();
+    let mut headers = Vec::new();
+    loop {
+        let line = read_line(&mut reader)?;
+        if line.is_empty() {
+            break;
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            return Err(Response::error(400, "Malformed header"));
+        };
+        headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+    }
+    let mut request = Request { method: method.to_string(), path, headers, body: Vec::new() };
+
+    if request.header("transfer-encoding").is_some() {
+        return Err(Response::error(411, "Send the body with a Content-Length"));
+    }
+    let length = match request.header("content-length") {
+        Some(length) => length.parse::<usize>().map_err(|_| Response::error(400, "Invalid Content-Length"))?,
+        None => 0,
+    };
+    if length > MAX_BODY_BYTES {
+        return Err(Response::error(413, &format!("Request bodies are limited to {} MB", MAX_BODY_BYTES >> 20)));
+    }
+    request.body = vec![0; length];
+    reader
+        .read_exact(&mut request.body)
+        .map_err(|e| Response::error(400, &format!("Failed to read the request body: {}", e)))?;
+    Ok(request)
+}
+
+/// Whether `host`, from a Host header, names this machine rather than some other site
+///
+/// Checked so a page that rebinds its own domain to 127.0.0.1 still can't call the API.
+fn is_local_host(host: &str) -> bool {
+    let name = match host.rsplit_once(':') {
+        Some((name, port)) if port.chars().all(|c| c.is_ascii_digit()) => name,
+        _ => host,
+    };
+    name.eq_ignore_ascii_case("localhost") || name == "127.0.0.1"
+}
+
+fn route(app: &AppHandle, request: &Request) -> Response {
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/status") => {
+            Response::json(200, &serde_json::json!({ "name": "horseCAD", "version": env!("CARGO_PKG_VERSION") }))
+        }
+        ("POST", "/compile" | "/export") => {
+            // Browsers can't send JSON across origins without asking first, which this server never allows
+            if !request.header("content-type").is_some_and(|kind| kind.starts_with("application/json")) {
+                return Response::error(415, "Send the request as application/json");
+            }
+            let body = match serde_json::from_slice::<ApiRequest>(&request.body) {
+                Ok(body) => body,
+                Err(e) => return Response::error(400, &format!("Invalid request: {}", e)),
+            };
+            let host = ApiHost::new(app.clone());
+            emit_log(&host, "info", &format!("HTTP API request: {}", request.path), Some("System"));
+            match request.path.as_str() {
+                "/compile" => compile(&host, body),
+                _ => export(&host, body),
+            }
+        }
+        (_, "/status" | "/compile" | "/export") => Response::error(405, "Method not allowed"),
+        _ => Response::error(404, "Not found"),
+    }
+}
+
+/// Body of `POST /compile` and `POST /export`
+#[derive(Deserialize)]
+struct ApiRequest {
+    script: String,
+    #[serde(default)]
+    settings: BuildSettings,
+    /// Format to export; defaults to STL
+    format: Option<ExportFormat>,
+    /// Unit recorded in the formats that keep one
+    unit: Option<ModelUnit>,
+    /// Shading of the normals in OBJ, PLY and GLB
+    #[serde(default)]
+    normals: NormalOptions,
+    /// Rounding and snapping of the exported coordinates
+    #[serde(default)]
+    precision: PrecisionOptions,
+}
+
+impl ApiRequest {
+    /// The request's settings, with anything left unset taken from the app's
+    fn settings(&self, app_handle: &AppHandle) -> BuildSettings {
+        with_app_defaults(app_handle, &self.settings)
+    }
+}
+
+/// `settings`, with anything left unset taken from the app's default depth and up axis
+fn with_app_defaults(app_handle: &AppHandle, settings: &BuildSettings) -> BuildSettings {
+    let preferences = app_handle.state::<SettingsStore>().get();
+    let defaults = BuildSettings {
+        depth: Some(preferences.default_depth),
+        up_axis: Some(preferences.export_up_axis),
+        ..Default::default()
+    };
+    settings.or(&defaults)
+}
+
+/// Response to `POST /compile`
+#[derive(Serialize)]
+struct CompileResponse {
+    triangle_count: usize,
+    validation: MeshReport,
+    measurements: MeshMeasurements,
+    params: Vec<ScriptParam>,
+    timings: CompileTimings,
+}
+
+/// Response to a script that failed to build
+#[derive(Serialize)]
+struct BuildFailure {
+    error: String,
+    diagnostics: Vec<Diagnostic>,
+}
+
+fn compile(host: &ApiHost, request: ApiRequest) -> Response {
+    let settings = request.settings(host.app());
+    let source = ScriptSource::new(request.script, None);
+    let mut timings = CompileTimings::default();
+    let compiled = compile_mesh(
+        host,
+        API_DOCUMENT_ID,
+        &source,
+        &settings.params,
+        &settings.mesh_request(),
+        &CancelToken::new(),
+        &mut timings,
+    );
+    match compiled {
+        Ok(compiled) => Response::json(
+            200,
+            &CompileResponse {
+                triangle_count: compiled.mesh.triangles.len(),
+                validation: compiled.validation,
+                measurements: compiled.measurements,
+                params: compiled.params,
+                timings,
+            },
+        ),
+        Err(e) => Response::json(422, &BuildFailure { error: e.message, diagnostics: e.diagnostics }),
+    }
+}
+
+fn export(host: &ApiHost, request: ApiRequest) -> Response {
+    let settings = request.settings(host.app());
+    let format = request.format.unwrap_or(ExportFormat::Stl);
+    let options = ExportOptions {
+        unit: request.unit,
+        normals: request.normals,
+        precision: request.precision,
+        ..Default::default()
+    };
+    let source = ScriptSource::new(request.script, None);
+    let built = build_model(
+        host,
+        API_DOCUMENT_ID,
+        &source,
+        &settings.params,
+        &settings.mesh_request(),
+        settings.repair.unwrap_or(false),
+        settings.up_axis.unwrap_or_default(),
+        format,
+        &options,
+        None,
+        &CancelToken::new(),
+        &mut CompileTimings::default(),
+    );
+    match built {
+        Ok(model) => Response { status: 200, content_type: "application/octet-stream", body: model.data },
+        Err(e) => Response::json(422, &BuildFailure { error: e.message, diagnostics: e.diagnostics }),
+    }
+}
+
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn json(status: u16, value: &impl Serialize) -> Self {
+        match serde_json::to_vec(value) {
+            Ok(body) => Response { status, content_type: "application/json", body },
+            Err(e) => Response::error(500, &format!("Failed to encode the response: {}", e)),
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        let body = serde_json::json!({ "error": message }).to_string().into_bytes();
+        Response { status, content_type: "application/json", body }
+    }
+
+    fn write_to(&self, stream: &mut TcpStream) -> std::io::Result<()> {
+        let reason = match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            411 => "Length Required",
+            413 => "Payload Too Large",
+            415 => "Unsupported Media Type",
+            422 => "Unprocessable Entity",
+            431 => "Request Header Fields Too Large",
+            _ => "Internal Server Error",
+        };
+        let head = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            self.status,
+            reason,
+            self.content_type,
+            self.body.len()
+        );
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(&self.body)?;
+        stream.flush()
+    }
+}
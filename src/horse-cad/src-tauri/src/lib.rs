@@ -1,25 +1,177 @@
-use std::sync::{Arc, Mutex};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+use std::time::Instant;
 
-use anyhow::{Context as AnyhowContext, Result};
+use anyhow::Result;
 use fidget::{
     context::{Context, Tree},
+    eval::MathFunction,
     mesh::{Mesh, Octree, Settings as MeshSettings},
-    render::ThreadPool,
+    render::{RenderHints, ThreadPool, View3},
     rhai::FromDynamic,
+    shape::Shape,
+    var::Var,
     vm::VmShape,
 };
-use nalgebra::{Scale3, Translation3};
+use image::RgbaImage;
+use nalgebra::{Matrix4, Scale3, Translation3};
 use rhai::{Dynamic, EvalAltResult, NativeCallContext};
 use serde::{Deserialize, Serialize};
 use std::fs;
-use tauri::{AppHandle, Emitter};
+use tauri::{
+    ipc::{Channel, InvokeBody, Request, Response},
+    AppHandle, Emitter, Manager, State, WebviewWindow, Window,
+};
 use tauri::menu::{MenuBuilder, MenuItemBuilder, PredefinedMenuItem, SubmenuBuilder};
 use tauri_plugin_dialog::{DialogExt};
+use tauri_plugin_opener::OpenerExt;
 
+mod annotate;
+mod bundle;
+pub mod cli;
+mod deep_link;
+mod disk_cache;
+mod download;
+mod export;
+mod history;
+mod horsi;
+mod host;
+mod http_api;
+mod log_file;
+mod log_history;
+mod mesh;
+mod open;
+mod plugins;
+mod recent;
+mod recovery;
+mod render;
+mod script;
+mod serve;
+mod session;
+mod settings;
+mod state;
+mod templates;
 mod utils;
+mod watch;
+mod window_state;
+use bundle::BundleManifest;
+use deep_link::{handle_deep_link, links_in_args};
+use disk_cache::{DiskCache, DEFAULT_DISK_CACHE_BYTES};
+use export::{
+    archive::{export_parts_archive, ArchiveManifest, ArchivePart, ArchiveSettings},
+    dxf::export_slices_to_dxf,
+    gif::{export_frames_to_gif, frame_delay, MAX_GIF_FPS},
+    glsl::export_shape_to_glsl,
+    gltf::export_mesh_to_glb,
+    grid::{export_grid, GridFormat},
+    obj::export_mesh_to_obj,
+    off::export_mesh_to_off,
+    ply::export_mesh_to_ply,
+    points::{export_point_cloud, PointCloudFormat},
+    precision::PrecisionOptions,
+    report::{report_path, ExportReport},
+    sheet::SliceLayout,
+    stl::{apply_stl_colors, export_mesh_to_stl, is_ascii_stl, parse_stl, parse_stl_colors, StlFormat},
+    svg::export_slices_to_svg,
+    threemf::{export_materials_to_3mf, MaterialObject},
+    vm::export_shape_to_vm,
+    export_mesh, sanitize_file_stem, unique_file_stem, ExportFormat, ExportOptions, ModelUnit, UpAxis,
+};
+use history::{ScriptVersion, VersionHistory, VersionInfo, VersionReason};
+use horsi::{read_project_settings, read_views, CompileDefaults, DocumentMetadata, HorsiFile, ProjectSettings};
+use host::{Host, WindowHost};
+use http_api::HttpApi;
+use mesh::bed::{bed_fit, BedFit};
+use mesh::benchmark::{BenchmarkReport, MAX_BENCHMARK_DEPTH};
+use mesh::bind::bind_vars;
+use mesh::bounds::{detect_bounds, Bounds};
+use mesh::buffers::{MeshBuffers, DEFAULT_CHUNK_TRIANGLES};
+use mesh::color::{parse_hex_color, to_hex, Rgb, DEFAULT_COLOR, MATERIAL_COLORS};
+use mesh::complexity::{shape_complexity, ShapeComplexity};
+use mesh::cull::cull_region;
+use mesh::empty::diagnose_empty_mesh;
+use mesh::estimate::{estimate_print, Material, MaterialPreset, PrintEstimate};
+use mesh::feature_size::{feature_size, FeatureSizeReport};
+use mesh::features::sharpen_features;
+use mesh::grid::{sample_grid, GridLayout, MAX_GRID_RESOLUTION};
+use mesh::hollow::HollowOptions;
+use mesh::limits::{MeshLimits, MIN_CHECKED_DEPTH, PROBE_LEVELS};
+use mesh::diff::{diff_meshes, MeshDiff, DEFAULT_DIFF_RESOLUTION, MAX_DIFF_RESOLUTION};
+use mesh::measure::{mass_properties, MassProperties, MeshMeasurements};
+use mesh::normals::NormalOptions;
+use mesh::overhang::{overhang_report, OverhangReport};
+use mesh::supports::{generate_supports, SupportOptions, SupportReport};
+use mesh::parts::{owner_counts, triangle_owners};
+use mesh::points::{
+    mesh_vertices, project_to_surface, sample_surface, surface_point_count, PointSource, MAX_POINT_CLOUD_POINTS,
+};
+use mesh::probe::{draft_angle, measure_points, probe_point, trace_ray, PointMeasurement, Probe, RayHit};
+use mesh::region::{clipped_faces, meshing_region, RegionFace};
+use mesh::quality::{AdvancedMeshOptions, CoarsenOptions, EvalBackend, MeshQuality, QualityPreset};
+use mesh::repair::repair_mesh;
+use mesh::simplify::{simplify, SimplifyOptions};
+use mesh::slice::{slice, Slice};
+use mesh::thickness::{thin_regions, ThicknessReport};
+use mesh::transform::{transform_mesh, ExportTransform};
+use mesh::units::MeshUnits;
+use mesh::validate::MeshReport;
+use mesh::weld::{weld, weld_tolerance};
+use open::{
+    check_external_change, focused_window, open_document, open_dropped, scripts_in_args, OpenedDocument,
+    OpenedDocuments,
+};
+use plugins::{PluginInfo, Plugins};
+use log_file::LogFile;
+use log_history::{LogExportFormat, LogHistory, LogLevel, LogReport, MAX_LOG_HISTORY};
+use recent::{remember_file, RecentFiles, CLEAR_RECENT_MENU_ID, RECENT_MENU_PREFIX};
+use recovery::{spawn_autosave, RecoveredDocument, Recovery};
+use render::{
+    check_render_size, encode_png, render_shape, RenderCamera, TurntableFormat, MAX_TURNTABLE_FRAMES,
+};
+use script::api::{api_reference, shape_functions, ApiFunction};
+use script::assertions::{check_assertions, AssertionResult, DeclaredAssertion};
+use script::assembly::Assembly;
+use session::{SessionDocument, SessionStore};
+use settings::{apply_settings, select_profile, Settings, SettingsStore, NO_PROFILE_MENU_ID, PROFILE_MENU_PREFIX};
+use script::console::{eval_snippet, ConsoleOutput, ConsoleState};
+use script::diagnostics::{diagnostic_from_error, Diagnostic};
+use script::extensions::{ExtensionInfo, Extensions};
+use script::format::format_rhai;
+use script::packages::{library_index, LibraryEntry};
+use script::openscad::{translate_openscad, OpenScadImport};
+use script::imports::{DocumentFiles, DocumentResolver, ImportStamp};
+use script::materials::{material_regions, DEFAULT_MATERIAL};
+use script::lint::lint_script;
+use script::symbols::{script_symbols, ScriptSymbol};
+use state::{
+    CachedCompile, CancelToken, CompileCache, CompileHistory, CompileRecord, CompileState, CompileStats, ExportJobInfo,
+    ExportJobStatus, ExportQueue, LastMesh, MeshCache, MeshOptions, MeshOptionsState, MeshOrigin, SaveOptions,
+    SaveOptionsState, ScriptCache, ScriptLimits, ScriptLimitsState, SnapshotInfo, SnapshotStore,
+    DEFAULT_COMPILE_CACHE_BYTES,
+};
+use templates::{template_menu, template_script, templates, TemplateInfo, TEMPLATE_MENU_PREFIX};
+use utils::file_utils::{rotate_backups, sha256_hex, write_atomically, write_json};
+use utils::ipc_utils::{decode_json_header, encode_binary_frame};
 use utils::log_utils::prettify_byte_count;
+use watch::{WatchRequest, WatchState};
+use window_state::WindowState;
 
-#[derive(Debug, Serialize, Deserialize)]
+const EXPORT_OPTIONS_HEADER: &str = "x-export-options";
+
+/// Grid cells along the longer side of a cross-section, unless asked otherwise
+const DEFAULT_SLICE_RESOLUTION: usize = 256;
+/// Finest cross-section grid accepted, in cells per side
+const MAX_SLICE_RESOLUTION: usize = 4096;
+/// Most slices a single export may take
+const MAX_SLICE_LAYERS: usize = 10_000;
+/// Triangles past which a compiled mesh is flagged as slow to display and export
+const LARGE_MESH_TRIANGLES: usize = 2_000_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LogEntry {
     pub timestamp: String,
     pub level: String,
@@ -27,248 +179,6597 @@ pub struct LogEntry {
     pub source: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl LogEntry {
+    /// Entry stamped with the current time
+    fn new(level: &str, message: &str, source: Option<&str>) -> Self {
+        LogEntry {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            level: level.to_string(),
+            message: message.to_string(),
+            source: source.map(|s| s.to_string()),
+        }
+    }
+}
+
+/// Header describing a compile, sent ahead of the binary STL payload
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct MeshResult {
     pub success: bool,
-    pub stl_data: Option<Vec<u8>>,
+    pub cancelled: bool,
+    /// True if a newer request for the same document replaced this one
+    pub superseded: bool,
+    /// Per-document request counter, used to discard stale results
+    pub generation: u64,
+    pub stl_format: StlFormat,
     pub triangle_count: Option<usize>,
+    /// Meshing region from `set_bounds` or the auto-bounds pass, if either ran
+    pub bounds: Option<Bounds>,
+    /// Triangles attributed to each `draw()` call, in call order
+    pub shape_triangle_counts: Vec<usize>,
+    /// `#RRGGBB` color of each drawn shape, in call order
+    pub shape_colors: Vec<Option<String>>,
+    /// Parameters declared by the script, with the values used
+    pub parameters: Vec<ScriptParam>,
+    /// Watertightness and orientation checks of the compiled mesh
+    pub validation: Option<MeshReport>,
+    /// Volume, surface area and extent of the compiled mesh
+    pub measurements: Option<MeshMeasurements>,
+    /// Unit passed to `set_units`, if the script called it
+    pub unit: Option<ModelUnit>,
+    /// Rough print time and material, for the print profile in the settings
+    pub print_estimate: Option<PrintEstimate>,
+    pub timings: CompileTimings,
+    /// Inputs matched an earlier compile, whose mesh was returned without meshing again
+    pub cached: bool,
+    /// The STL bytes were left out of the response; fetch the mesh with `stream_mesh`
+    #[serde(default)]
+    pub streamed: bool,
+    /// Id of the snapshot this result was restored from by `restore_snapshot`
+    #[serde(default)]
+    pub snapshot: Option<u64>,
     pub error: Option<String>,
+    /// The compile panicked; `error` says so, and the app carried on
+    #[serde(default)]
+    pub crashed: bool,
+    /// Located script errors and static analysis warnings, for editor squiggles
+    pub diagnostics: Vec<Diagnostic>,
+    /// Problems with a successful compile's mesh
+    #[serde(default)]
+    pub warnings: Vec<MeshWarning>,
+    /// Outcome of each `assert_*` call; failures are also in `diagnostics`
+    #[serde(default)]
+    pub assertions: Vec<AssertionResult>,
+    /// Size of the shape's expression and how far its tape prunes
+    #[serde(default)]
+    pub complexity: Option<ShapeComplexity>,
+    /// Contours of a script in 2D mode, which has no mesh
+    #[serde(default)]
+    pub outline: Option<Slice>,
 }
 
-/// Emit a log entry to the frontend
-fn emit_log(app_handle: &AppHandle, level: &str, message: &str, source: Option<&str>) {
-    let log_entry = LogEntry {
-        timestamp: chrono::Utc::now().to_rfc3339(),
-        level: level.to_string(),
-        message: message.to_string(),
-        source: source.map(|s| s.to_string()),
-    };
-    
-    if let Err(e) = app_handle.emit("log_entry", &log_entry) {
-        eprintln!("Failed to emit log entry: {}", e);
+/// Something wrong with a mesh that still compiled
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MeshWarning {
+    /// The surface reaches these faces of the meshing region, and the mesh is cut off flat there
+    Clipped { faces: Vec<RegionFace>, message: String },
+    /// The mesh has holes or edges shared by more than two triangles, so it may not slice cleanly
+    NotWatertight { open_edges: usize, non_manifold_edges: usize, message: String },
+    /// Walls are thinner than the `min_wall` in the advanced mesh options
+    ThinWalls { report: ThicknessReport, message: String },
+    /// The mesh has more than `LARGE_MESH_TRIANGLES` triangles
+    LargeMesh { triangles: usize, message: String },
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PreviewResult {
+    pub success: bool,
+    pub cancelled: bool,
+    pub superseded: bool,
+    pub generation: u64,
+    pub buffers: Option<MeshBuffers>,
+    pub triangle_count: Option<usize>,
+    /// Meshing region from `set_bounds` or the auto-bounds pass, if either ran
+    pub bounds: Option<Bounds>,
+    /// Triangles attributed to each `draw()` call, in call order
+    pub shape_triangle_counts: Vec<usize>,
+    /// `#RRGGBB` color of each drawn shape, in call order
+    pub shape_colors: Vec<Option<String>>,
+    /// Parameters declared by the script, with the values used
+    pub parameters: Vec<ScriptParam>,
+    pub timings: CompileTimings,
+    pub error: Option<String>,
+    /// The compile panicked; `error` says so, and the app carried on
+    #[serde(default)]
+    pub crashed: bool,
+    /// Located script errors, for editor squiggles
+    pub diagnostics: Vec<Diagnostic>,
+    /// Contours of a script in 2D mode, which has no mesh
+    #[serde(default)]
+    pub outline: Option<Slice>,
+}
+
+/// Outcome of `analyze_mesh`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MeshAnalysis {
+    pub measurements: MeshMeasurements,
+    pub mass: MassProperties,
+    /// Price of the mass in the chosen material, if `analyze_mesh` was given one
+    pub cost: Option<f64>,
+    /// Mass properties are only trustworthy if this reports a watertight mesh
+    pub validation: MeshReport,
+}
+
+/// Outcome of `check_script`
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CheckResult {
+    pub valid: bool,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// A file written by `export_parts`, or packed by `export_archive`
+///
+/// For an archive, `path` is the part's file name inside it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PartExport {
+    pub name: String,
+    pub path: String,
+    pub triangle_count: usize,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PartsExportResult {
+    pub success: bool,
+    pub cancelled: bool,
+    pub parts: Vec<PartExport>,
+    pub error: Option<String>,
+}
+
+/// Options for `export_mesh_binary`, sent alongside the raw mesh body
+#[derive(Debug, Deserialize)]
+pub struct BinaryExportOptions {
+    pub path: String,
+    /// Document within the window the mesh was compiled for, whose units it's written in
+    #[serde(default)]
+    pub document_id: Option<String>,
+    pub format: ExportFormat,
+    #[serde(flatten)]
+    pub export: ExportOptions,
+    #[serde(flatten)]
+    pub simplify: SimplifyOptions,
+    /// Run `repair_mesh` before simplifying and encoding
+    #[serde(default)]
+    pub repair: bool,
+}
+
+/// Pipeline stages reported through `compile_progress` events
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompileStage {
+    ScriptEval,
+    ShapeBuild,
+    Octree,
+    DualWalk,
+    Simplify,
+    Export,
+    Done,
+}
+
+impl CompileStage {
+    /// Share of the overall progress bar at which this stage starts
+    ///
+    /// Octree construction dominates compile time at useful depths, so it is
+    /// given the largest slice.
+    fn start_percent(&self) -> f32 {
+        match self {
+            CompileStage::ScriptEval => 0.0,
+            CompileStage::ShapeBuild => 10.0,
+            CompileStage::Octree => 15.0,
+            CompileStage::DualWalk => 75.0,
+            CompileStage::Simplify => 88.0,
+            CompileStage::Export => 95.0,
+            CompileStage::Done => 100.0,
+        }
     }
 }
 
-/// Compile Rhai script and generate STL mesh
-#[tauri::command]
-async fn compile_script(
-    app_handle: AppHandle,
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CompileProgress {
+    pub stage: CompileStage,
+    pub percent: f32,
+}
+
+/// Wall-clock time spent in each compile stage, in milliseconds
+///
+/// Stages that didn't run (because an earlier one failed) are left at zero.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct CompileTimings {
+    pub script_eval_ms: f64,
+    pub shape_build_ms: f64,
+    pub bounds_ms: f64,
+    pub octree_ms: f64,
+    pub dual_walk_ms: f64,
+    pub weld_ms: f64,
+    pub features_ms: f64,
+    pub simplify_ms: f64,
+    pub validate_ms: f64,
+    pub export_ms: f64,
+}
+
+impl CompileTimings {
+    pub fn total_ms(&self) -> f64 {
+        self.script_eval_ms
+            + self.shape_build_ms
+            + self.bounds_ms
+            + self.octree_ms
+            + self.dual_walk_ms
+            + self.weld_ms
+            + self.features_ms
+            + self.simplify_ms
+            + self.validate_ms
+            + self.export_ms
+    }
+}
+
+fn elapsed_ms(start: Instant) -> f64 {
+    start.elapsed().as_secs_f64() * 1000.0
+}
+
+/// Emit a `compile_progress` event marking the start of a stage
+fn emit_progress(host: &dyn Host, stage: CompileStage) {
+    let progress = CompileProgress {
+        stage,
+        percent: stage.start_percent(),
+    };
+    host.progress(&progress);
+}
+
+/// Emit a log entry to the frontend, or wherever the host sends them
+fn emit_log(host: &dyn Host, level: &str, message: &str, source: Option<&str>) {
+    host.log(&LogEntry::new(level, message, source));
+}
+
+/// Depth from which compiles send a coarse preview before meshing at full detail
+const PROGRESSIVE_MIN_DEPTH: u8 = 8;
+
+/// Depth of that coarse preview, which meshes in well under a second for most scripts
+const COARSE_PREVIEW_DEPTH: u8 = 5;
+
+/// Meshing parameters for a single compile
+#[derive(Debug, Clone, Copy)]
+struct MeshRequest {
+    quality: MeshQuality,
+    center: [f32; 3],
+    /// Fit the meshing region to the shape, unless the script sets a scale
+    auto_bounds: bool,
+    /// Evaluator for the octree; falls back to the VM if unavailable
+    backend: EvalBackend,
+    /// Decimation applied to the mesh after meshing
+    simplify: SimplifyOptions,
+    advanced: AdvancedMeshOptions,
+}
+
+/// Script text and the document it belongs to
+struct ScriptSource {
     code: String,
-    depth: u8,
-    scale: Option<f32>,
-    center: Option<[f32; 3]>,
-) -> Result<MeshResult, String> {
-    let center = center.unwrap_or([0.0, 0.0, 0.0]);
-    
-    emit_log(&app_handle, "info", "Starting script compilation", Some("Compiler"));
+    /// Directory `import` paths are resolved against
+    base_dir: Option<PathBuf>,
+}
+
+impl ScriptSource {
+    /// Imports resolve next to `document_path`; unsaved documents can't import
+    fn new(code: String, document_path: Option<&str>) -> Self {
+        let base_dir = document_path.and_then(|p| Path::new(p).parent()).map(Path::to_path_buf);
+        ScriptSource { code, base_dir }
+    }
+}
+
+/// A meshed shape and the bounds detected while meshing it
+struct CompiledMesh {
+    mesh: Mesh,
+    bounds: Option<Bounds>,
+    /// Triangles attributed to each `draw()` call, in call order
+    shape_triangle_counts: Vec<usize>,
+    /// Per-triangle colors, if any drawn shape has one
+    triangle_colors: Option<Vec<Option<Rgb>>>,
+    /// Color of each drawn shape, in call order
+    shape_colors: Vec<Option<Rgb>>,
+    /// Parameters declared by the script
+    params: Vec<ScriptParam>,
+    validation: MeshReport,
+    measurements: MeshMeasurements,
+    /// Files the script read, with their modification times
+    imports: Vec<ImportStamp>,
+    units: MeshUnits,
+    warnings: Vec<MeshWarning>,
+    /// Outcome of each `assert_*` call, in call order
+    assertions: Vec<AssertionResult>,
+    complexity: Option<ShapeComplexity>,
+    /// Contours traced instead of meshing, for a script in 2D mode
+    outline: Option<Slice>,
+}
+
+/// A failed compile, with script diagnostics if the script itself failed
+struct CompileError {
+    message: String,
+    diagnostics: Vec<Diagnostic>,
+    /// The compile panicked, which is a bug rather than a problem with the script
+    crashed: bool,
+}
+
+impl From<String> for CompileError {
+    fn from(message: String) -> Self {
+        CompileError { message, diagnostics: Vec::new(), crashed: false }
+    }
+}
+
+/// Run one compile, turning a panic inside it into a `CompileError`
+///
+/// A bug in the evaluator or the mesher then fails only this compile, with
+/// a logged error, and the app keeps running.  Running out of memory still
+/// aborts the process, since Rust doesn't unwind from a failed allocation.
+/// A panic leaves any lock the compile held poisoned, so shared state is
+/// locked with `unwrap_or_else(|e| e.into_inner())` to keep it usable after.
+fn catch_crash<T>(host: &dyn Host, compile: impl FnOnce() -> Result<T, CompileError>) -> Result<T, CompileError> {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(compile)).unwrap_or_else(|payload| {
+        let cause = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        let message = format!("The compiler crashed ({}); this is a bug, please report it with the script", cause);
+        emit_log(host, "error", &message, Some("Compiler"));
+        Err(CompileError { message, diagnostics: Vec::new(), crashed: true })
+    })
+}
+
+/// Log and return an error if `cancel` has been triggered
+fn check_cancelled(host: &dyn Host, cancel: &CancelToken) -> Result<(), String> {
+    if cancel.is_cancelled() {
+        emit_log(host, "warn", "Compilation cancelled", Some("Compiler"));
+        Err("Compilation cancelled".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+/// Run the script, logging progress
+///
+/// If `document_id` last ran the same code, its imports haven't changed, and
+/// only `param_tree()` values differ, the cached graph is re-bound to the new
+/// values instead; the script doesn't run, so its `print()` output isn't
+/// repeated.
+fn run_script(
+    host: &dyn Host,
+    document_id: &str,
+    source: &ScriptSource,
+    params: &HashMap<String, f64>,
+    cancel: &CancelToken,
+    timings: &mut CompileTimings,
+) -> Result<ScriptOutput, CompileError> {
+    emit_progress(host, CompileStage::ScriptEval);
+    let cache = host.script_cache();
+    if let Some(cached) = cache.get(document_id, source).filter(|c| c.can_rebind(params)) {
+        let start = Instant::now();
+        let bound = cached.bind(params);
+        timings.script_eval_ms = elapsed_ms(start);
+        match bound {
+            Ok(result) => {
+                emit_log(host, "info", "Script unchanged; re-binding parameter values", Some("Compiler"));
+                return Ok(result);
+            }
+            Err(e) => {
+                emit_log(host, "warn", &format!("Parameter re-binding failed: {}", e), Some("Compiler"));
+            }
+        }
+    }
+
+    emit_log(host, "info", "Starting script compilation", Some("Compiler"));
     
     // Compile the Rhai script
-    let (ctx, root, scale) = match compile_rhai_script(&code) {
+    let start = Instant::now();
+    let compiled = compile_rhai_script(host, source, params, cancel).and_then(|symbolic| {
+        let symbolic = Arc::new(symbolic);
+        let bound = symbolic.bind(params)?;
+        cache.insert(document_id, source, symbolic);
+        Ok(bound)
+    });
+    timings.script_eval_ms = elapsed_ms(start);
+    match compiled {
         Ok(result) => {
-            emit_log(&app_handle, "info", "Script compiled successfully", Some("Compiler"));
-            result
+            emit_log(host, "info", "Script compiled successfully", Some("Compiler"));
+            Ok(result)
         }
         Err(e) => {
+            // A cancelled script aborts with a progress error; report it as such
+            check_cancelled(host, cancel)?;
             let error_msg = format!("Script compilation failed: {}", e);
-            emit_log(&app_handle, "error", &error_msg, Some("Compiler"));
-            return Ok(MeshResult {
-                success: false,
-                stl_data: None,
-                triangle_count: None,
-                error: Some(error_msg),
-            });
+            emit_log(host, "error", &error_msg, Some("Compiler"));
+            Err(CompileError {
+                message: error_msg,
+                diagnostics: vec![diagnostic_from_error(&e, &source.code)],
+                crashed: false,
+            })
         }
-    };
-    
+    }
+}
+
+/// A single shape meshed out of a script's context
+struct MeshedShape {
+    mesh: Mesh,
+    bounds: Option<Bounds>,
+    /// Shape-space transform applied before meshing, if the mesh isn't in model units
+    transform: Option<nalgebra::Matrix4<f32>>,
+    warnings: Vec<MeshWarning>,
+}
+
+/// `node` as a shape in the space its mesh's vertices are in, given the mesh's `transform`
+///
+/// Octree vertices are in model space with a view, shape space otherwise,
+/// so checks against a mesh evaluate the shape through the same transform.
+fn mesh_space_shape(
+    ctx: &Context,
+    node: fidget::context::Node,
+    transform: Option<nalgebra::Matrix4<f32>>,
+) -> Result<VmShape, fidget::Error> {
+    let shape = VmShape::new(ctx, node)?;
+    Ok(match transform {
+        Some(t) => shape.apply_transform(t),
+        None => shape,
+    })
+}
+
+/// Mesh one node of a script's context
+///
+/// If the script calls `set_bounds`, or bounds are requested, the octree is
+/// fitted to that box and vertices stay in model units; otherwise the shape
+/// is scaled by the script's `set_scale` and centered on `center`.  Without
+/// auto-bounds it's then meshed in the unit cube; with it, the detected box
+/// is moved into that same space and the octree fitted to it, so turning
+/// auto-bounds on stops the shape being clipped without moving it.
+fn mesh_node(
+    host: &dyn Host,
+    script: &ScriptOutput,
+    node: fidget::context::Node,
+    request: &MeshRequest,
+    cancel: &CancelToken,
+    timings: &mut CompileTimings,
+) -> Result<MeshedShape, String> {
     // Create VmShape
-    let shape = match VmShape::new(&ctx, root) {
+    emit_progress(host, CompileStage::ShapeBuild);
+    let start = Instant::now();
+    let shape = match VmShape::new(&script.ctx, node) {
         Ok(shape) => {
-            emit_log(&app_handle, "info", "Shape created successfully", Some("Compiler"));
+            emit_log(host, "info", "Shape created successfully", Some("Compiler"));
             shape
         }
         Err(e) => {
             let error_msg = format!("Shape creation failed: {}", e);
-            emit_log(&app_handle, "error", &error_msg, Some("Compiler"));
-            return Ok(MeshResult {
-                success: false,
-                stl_data: None,
-                triangle_count: None,
-                error: Some(error_msg),
-            });
+            emit_log(host, "error", &error_msg, Some("Compiler"));
+            return Err(error_msg);
         }
     };
     
-    // Apply transformations
-    emit_log(&app_handle, "info", &format!("Applying transformations (scale: {}, center: {:?})", scale, center), Some("Transform"));
-    let s = 1.0 / scale;
-    let scale_transform = Scale3::new(s, s, s);
-    let center_transform = Translation3::new(-center[0], -center[1], -center[2]);
-    let t = center_transform.to_homogeneous() * scale_transform.to_homogeneous();
-    let shape = shape.apply_transform(t);
+    timings.shape_build_ms = elapsed_ms(start);
+    check_cancelled(host, cancel)?;
+
+    let start = Instant::now();
+    let bounds = if let Some(bounds) = request.advanced.bounds {
+        emit_log(
+            host,
+            "info",
+            &format!("Using requested bounds {:?} to {:?}", bounds.min, bounds.max),
+            Some("Transform"),
+        );
+        Some(bounds)
+    } else if let Some(bounds) = script.bounds {
+        emit_log(
+            host,
+            "info",
+            &format!("Using script bounds {:?} to {:?}", bounds.min, bounds.max),
+            Some("Transform"),
+        );
+        Some(bounds)
+    } else if request.auto_bounds && script.scale.is_none() {
+        emit_log(host, "info", "Detecting shape bounds", Some("Transform"));
+        match detect_bounds(&shape) {
+            Ok(Some(bounds)) if bounds.half_extent() > 0.0 => {
+                emit_log(
+                    host,
+                    "info",
+                    &format!("Detected bounds {:?} to {:?}", bounds.min, bounds.max),
+                    Some("Transform"),
+                );
+                Some(bounds)
+            }
+            Ok(_) => {
+                emit_log(host, "warn", "Shape is empty or unbounded; using the default meshing region", Some("Transform"));
+                None
+            }
+            Err(e) => {
+                emit_log(host, "warn", &format!("Bounds detection failed: {}", e), Some("Transform"));
+                None
+            }
+        }
+    } else {
+        None
+    };
+    timings.bounds_ms = elapsed_ms(start);
+    check_cancelled(host, cancel)?;
+
+    let detected = bounds.is_some() && request.advanced.bounds.is_none() && script.bounds.is_none();
+    let fit = |bounds: &Bounds| {
+        let center = bounds.center();
+        View3::from_center_and_scale(
+            nalgebra::Vector3::new(center[0], center[1], center[2]),
+            bounds.half_extent() * (1.0 + request.advanced.margin),
+        )
+    };
+    let (view, transform) = match bounds {
+        Some(bounds) if !detected => (fit(&bounds), None),
+        _ => {
+            // Apply transformations
+            let scale = script.scale.unwrap_or(1.0);
+            let center = request.center;
+            emit_log(host, "info", &format!("Applying transformations (scale: {}, center: {:?})", scale, center), Some("Transform"));
+            let s = 1.0 / scale;
+            let scale_transform = Scale3::new(s, s, s);
+            let center_transform = Translation3::new(-center[0], -center[1], -center[2]);
+            let t = center_transform.to_homogeneous() * scale_transform.to_homogeneous();
+            let view = match (bounds, t.try_inverse()) {
+                (Some(bounds), Some(to_view)) => {
+                    let [a, b] =
+                        [bounds.min, bounds.max].map(|p| to_view.transform_point(&nalgebra::Point3::from(p)));
+                    let min = std::array::from_fn(|i| a[i].min(b[i]));
+                    let max = std::array::from_fn(|i| a[i].max(b[i]));
+                    fit(&Bounds { min, max })
+                }
+                _ => View3::default(),
+            };
+            (view, Some(t))
+        }
+    };
     
     // Generate mesh
-    emit_log(&app_handle, "info", &format!("Building octree at depth {}", depth), Some("Mesh"));
+    emit_log(host, "info", &format!("Building octree at depth {}", request.quality.depth), Some("Mesh"));
     
+    let pool = host.mesh_options().thread_pool()?;
     let mesh_settings = MeshSettings {
-        depth,
-        threads: Some(&ThreadPool::Global),
-        ..Default::default()
+        depth: request.quality.depth,
+        view,
+        threads: request.advanced.threaded.unwrap_or(request.quality.threaded).then_some(pool.as_ref()),
     };
-    
+    let limits = request.advanced.check_limits.then(|| host.mesh_options().get().limits);
+
+    let backend = if request.backend.is_available() {
+        request.backend
+    } else {
+        emit_log(
+            host,
+            "warn",
+            &format!("The {} backend isn't available on this platform; using the VM", request.backend.label()),
+            Some("Mesh"),
+        );
+        EvalBackend::Vm
+    };
+    let thread_count = mesh_settings.threads.map_or(1, ThreadPool::thread_count);
+    emit_log(
+        host,
+        "info",
+        &format!("Meshing with the {} backend on {} threads", backend.label(), thread_count),
+        Some("Mesh"),
+    );
+    let mesh = match backend {
+        #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+        EvalBackend::Jit => {
+            let start = Instant::now();
+            let jit = fidget::jit::JitShape::new(&script.ctx, node).map_err(|e| {
+                let error_msg = format!("JIT shape creation failed: {}", e);
+                emit_log(host, "error", &error_msg, Some("Compiler"));
+                error_msg
+            })?;
+            timings.shape_build_ms += elapsed_ms(start);
+            octree_mesh(host, jit, transform, mesh_settings, request.advanced.cull, limits, cancel, timings)?
+        }
+        _ => {
+            octree_mesh(host, shape.clone(), transform, mesh_settings, request.advanced.cull, limits, cancel, timings)?
+        }
+    };
+
+    let region = meshing_region(bounds, transform, request.advanced.margin);
+    if mesh.triangles.is_empty() {
+        let cell = (region.max[0] - region.min[0]) / (1u64 << request.quality.depth) as f32;
+        let reason = diagnose_empty_mesh(&shape, &region, cell, request.quality.depth)
+            .unwrap_or_else(|e| format!("Checking why failed: {}", e));
+        let error_msg = format!("Meshing produced no triangles. {}", reason);
+        emit_log(host, "error", &error_msg, Some("Mesh"));
+        return Err(error_msg);
+    }
+    let mut warnings = Vec::new();
+    match clipped_faces(&shape, &region) {
+        Ok(faces) if !faces.is_empty() => {
+            let labels: Vec<_> = faces.iter().map(RegionFace::label).collect();
+            let message = format!(
+                "The shape runs past the {} side{} of the meshing region, so it's cut off flat there; \
+                 widen set_bounds() or lower set_scale() to mesh all of it",
+                labels.join(", "),
+                if faces.len() == 1 { "" } else { "s" }
+            );
+            emit_log(host, "warn", &message, Some("Mesh"));
+            warnings.push(MeshWarning::Clipped { faces, message });
+        }
+        Ok(_) => {}
+        Err(e) => emit_log(host, "warn", &format!("Checking for clipping failed: {}", e), Some("Mesh")),
+    }
+
+    // Sharp features leave coincident vertices joined by zero-area triangles
+    let mesh = if request.advanced.weld {
+        let start = Instant::now();
+        let welded = weld(&mesh, weld_tolerance(&mesh));
+        timings.weld_ms += elapsed_ms(start);
+        if welded.merged > 0 {
+            emit_log(
+                host,
+                "info",
+                &format!(
+                    "Welded {} duplicate vertices, dropping {} degenerate triangles",
+                    welded.merged,
+                    mesh.triangles.len() - welded.mesh.triangles.len()
+                ),
+                Some("Mesh"),
+            );
+        }
+        welded.mesh
+    } else {
+        mesh
+    };
+
+    let mut mesh = mesh;
+    if request.advanced.features.enabled {
+        let start = Instant::now();
+        let report = mesh_space_shape(&script.ctx, node, transform)
+            .map_err(anyhow::Error::from)
+            .and_then(|field| sharpen_features(&field, &mut mesh, &request.advanced.features, cancel))
+            .map_err(|e| format!("Sharp-feature pass failed: {}", e))
+            .inspect_err(|e| emit_log(host, "error", e, Some("Mesh")))?;
+        timings.features_ms += elapsed_ms(start);
+        check_cancelled(host, cancel)?;
+        if let Some(report) = report {
+            emit_log(
+                host,
+                "info",
+                &format!(
+                    "Found {} feature vertices: {} snapped to edges and corners, {} fell back to the mass point",
+                    report.feature_vertices, report.sharpened, report.mass_point_fallbacks
+                ),
+                Some("Mesh"),
+            );
+        }
+    }
+
+    if request.advanced.coarsen.enabled {
+        emit_progress(host, CompileStage::Simplify);
+        let start = Instant::now();
+        let cell = (region.max[0] - region.min[0]) / (1u64 << request.quality.depth) as f32;
+        let tolerance = cell * request.advanced.coarsen.tolerance;
+        let coarsened = simplify(&mesh, &SimplifyOptions { target_triangles: None, max_error: Some(tolerance) }).mesh;
+        timings.simplify_ms += elapsed_ms(start);
+        check_cancelled(host, cancel)?;
+        emit_log(
+            host,
+            "info",
+            &format!(
+                "Coarsening kept {} of {} triangles, within {} of the depth {} surface",
+                coarsened.triangles.len(),
+                mesh.triangles.len(),
+                tolerance,
+                request.quality.depth
+            ),
+            Some("Mesh"),
+        );
+        mesh = coarsened;
+    }
+
+    let mesh = if request.simplify.is_enabled() {
+        emit_progress(host, CompileStage::Simplify);
+        let start = Instant::now();
+        let mesh = simplify_mesh(host, &mesh, &request.simplify).mesh;
+        timings.simplify_ms += elapsed_ms(start);
+        check_cancelled(host, cancel)?;
+        mesh
+    } else {
+        mesh
+    };
+    Ok(MeshedShape { mesh, bounds, transform, warnings })
+}
+
+/// Decimate a mesh, logging how far it was reduced
+fn simplify_mesh(host: &dyn Host, mesh: &Mesh, options: &SimplifyOptions) -> mesh::simplify::Simplified {
+    let simplified = simplify(mesh, options);
+    emit_log(
+        host,
+        "info",
+        &format!(
+            "Simplified mesh from {} to {} triangles",
+            mesh.triangles.len(),
+            simplified.mesh.triangles.len()
+        ),
+        Some("Mesh"),
+    );
+    simplified
+}
+
+/// Build the octree for a shape and walk it into triangles
+///
+/// `transform` is applied to the shape first, as chosen by `mesh_node`.
+/// With `limits`, deep octrees are first built a few levels shallower to
+/// estimate the triangle count, and the compile fails with a suggested
+/// depth if that breaks them; the real count is checked again after the
+/// walk, before the copies made by later stages.
+///
+/// With `cull`, the octree is built over the smaller cube `cull_region`
+/// finds around the surface, at the depth that keeps its finest cells.
+#[allow(clippy::too_many_arguments)]
+fn octree_mesh<F: MathFunction + RenderHints + Clone>(
+    host: &dyn Host,
+    shape: Shape<F>,
+    transform: Option<nalgebra::Matrix4<f32>>,
+    mesh_settings: MeshSettings,
+    cull: bool,
+    limits: Option<MeshLimits>,
+    cancel: &CancelToken,
+    timings: &mut CompileTimings,
+) -> Result<Mesh, String> {
+    let shape = match transform {
+        Some(t) => shape.apply_transform(t),
+        None => shape,
+    };
+    emit_progress(host, CompileStage::Octree);
+    // Limits are named by the requested depth, whatever depth culling leaves
+    let requested_depth = mesh_settings.depth;
+    let check_limits = |triangles: u64| match limits {
+        Some(limits) => {
+            limits.check(triangles, requested_depth).inspect_err(|e| emit_log(host, "error", e, Some("Mesh")))
+        }
+        None => Ok(()),
+    };
+    let start = Instant::now();
+    let mesh_settings = if cull {
+        match cull_region(&shape, mesh_settings.view, mesh_settings.depth) {
+            Ok(Some(culled)) => {
+                emit_log(
+                    host,
+                    "info",
+                    &format!(
+                        "Culled the octree to 1/{} of the region ({:.1}% of coarse cells hold surface), \
+                         meshing at depth {}",
+                        1u64 << (3 * culled.levels as u32),
+                        culled.occupied * 100.0,
+                        culled.depth
+                    ),
+                    Some("Mesh"),
+                );
+                MeshSettings { depth: culled.depth, view: culled.view, ..mesh_settings }
+            }
+            Ok(None) => {
+                emit_log(host, "info", "No smaller cube holds the surface, so meshing the whole region", Some("Mesh"));
+                mesh_settings
+            }
+            Err(e) => {
+                emit_log(host, "warn", &format!("Region culling failed: {}", e), Some("Mesh"));
+                mesh_settings
+            }
+        }
+    } else {
+        mesh_settings
+    };
+    if limits.is_some() && requested_depth >= MIN_CHECKED_DEPTH && mesh_settings.depth > PROBE_LEVELS {
+        let probe = MeshSettings { depth: mesh_settings.depth - PROBE_LEVELS, ..mesh_settings };
+        let triangles = Octree::build(&shape, probe).walk_dual(probe).triangles.len() as u64;
+        check_limits(triangles << (2 * PROBE_LEVELS))?;
+        check_cancelled(host, cancel)?;
+    }
     let octree = Octree::build(&shape, mesh_settings);
-    emit_log(&app_handle, "info", "Octree construction complete", Some("Mesh"));
+    timings.octree_ms = elapsed_ms(start);
+    emit_log(host, "info", "Octree construction complete", Some("Mesh"));
+    check_cancelled(host, cancel)?;
     
-    emit_log(&app_handle, "info", "Generating mesh triangles", Some("Mesh"));
+    emit_log(host, "info", "Generating mesh triangles", Some("Mesh"));
+    emit_progress(host, CompileStage::DualWalk);
+    let start = Instant::now();
     let mesh = octree.walk_dual(mesh_settings);
-    let triangle_count = mesh.triangles.len();
-    
-    emit_log(&app_handle, "info", &format!("Mesh generation complete ({} triangles)", triangle_count), Some("Mesh"));
+    timings.dual_walk_ms = elapsed_ms(start);
+    check_cancelled(host, cancel)?;
+    drop(octree);
+    check_limits(mesh.triangles.len() as u64)?;
     
-    // Export to STL
-    emit_log(&app_handle, "info", "Exporting STL data", Some("Export"));
-    let stl_data = match export_mesh_to_stl(&mesh) {
-        Ok(data) => {
-            emit_log(&app_handle, "info", &format!("STL export complete ({})", prettify_byte_count(data.len() as u64)), Some("Export"));
-            data
+    emit_log(host, "info", &format!("Mesh generation complete ({} triangles)", mesh.triangles.len()), Some("Mesh"));
+    Ok(mesh)
+}
+
+/// Run the script and mesh the resulting shape
+///
+/// Progress is logged as each stage completes; on failure the logged,
+/// user-facing error message is returned.
+///
+/// `cancel` is checked between stages (and periodically during script
+/// evaluation); a cancelled compile stops at the next check.  Stage durations
+/// are recorded into `timings` as they complete.
+fn compile_mesh(
+    host: &dyn Host,
+    document_id: &str,
+    source: &ScriptSource,
+    params: &HashMap<String, f64>,
+    request: &MeshRequest,
+    cancel: &CancelToken,
+    timings: &mut CompileTimings,
+) -> Result<CompiledMesh, CompileError> {
+    catch_crash(host, || {
+        let script = run_script(host, document_id, source, params, cancel, timings)?;
+        mesh_script(host, script, request, cancel, timings)
+    })
+}
+
+/// Compile as `compile_mesh` does, first handing `on_coarse` a coarse preview if the request is deep
+///
+/// At `PROGRESSIVE_MIN_DEPTH` and beyond, the shape is meshed at
+/// `COARSE_PREVIEW_DEPTH` and passed to `on_coarse` as a `PreviewResult`
+/// with its buffers, so the viewport updates within moments while the full
+/// mesh is built.  The script runs once for both meshes.
+#[allow(clippy::too_many_arguments)]
+fn compile_progressive(
+    host: &dyn Host,
+    document_id: &str,
+    source: &ScriptSource,
+    params: &HashMap<String, f64>,
+    request: &MeshRequest,
+    cancel: &CancelToken,
+    timings: &mut CompileTimings,
+    on_coarse: &dyn Fn(PreviewResult),
+) -> Result<CompiledMesh, CompileError> {
+    catch_crash(host, || {
+        let script = run_script(host, document_id, source, params, cancel, timings)?;
+        if request.quality.depth >= PROGRESSIVE_MIN_DEPTH && !script.flat {
+            let coarse = MeshRequest {
+                quality: MeshQuality { depth: COARSE_PREVIEW_DEPTH, ..request.quality },
+                simplify: SimplifyOptions::default(),
+                ..*request
+            };
+            let mut coarse_timings = CompileTimings::default();
+            // The full compile reports any meshing error, so only cancellation stops here
+            let meshed = mesh_node(host, &script, script.root, &coarse, cancel, &mut coarse_timings);
+            check_cancelled(host, cancel)?;
+            if let Ok(meshed) = meshed {
+                let preview = PreviewResult {
+                    success: true,
+                    triangle_count: Some(meshed.mesh.triangles.len()),
+                    buffers: Some(MeshBuffers::from_mesh(&meshed.mesh)),
+                    bounds: meshed.bounds,
+                    parameters: script.params.clone(),
+                    timings: coarse_timings,
+                    ..Default::default()
+                };
+                emit_log(
+                    host,
+                    "info",
+                    &format!("Coarse preview ready ({} triangles); refining", meshed.mesh.triangles.len()),
+                    Some("Mesh"),
+                );
+                on_coarse(preview);
+            }
+        }
+        mesh_script(host, script, request, cancel, timings)
+    })
+}
+
+/// Mesh a script's output, attribute triangles to its shapes, and check the result
+fn mesh_script(
+    host: &dyn Host,
+    script: ScriptOutput,
+    request: &MeshRequest,
+    cancel: &CancelToken,
+    timings: &mut CompileTimings,
+) -> Result<CompiledMesh, CompileError> {
+    if script.flat {
+        return outline_script(host, script, request, timings);
+    }
+    let MeshedShape { mesh, bounds, transform, mut warnings } =
+        mesh_node(host, &script, script.root, request, cancel, timings)?;
+
+    let shape_colors: Vec<_> = script.shapes.iter().map(|s| s.color).collect();
+    let has_colors = shape_colors.iter().any(Option::is_some);
+    let (shape_triangle_counts, triangle_colors) = if script.shapes.len() > 1 || has_colors {
+        let attributed = script
+            .shapes
+            .iter()
+            .map(|shape| mesh_space_shape(&script.ctx, shape.node, transform))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(anyhow::Error::from)
+            .and_then(|part_shapes| triangle_owners(&mesh, &part_shapes));
+        match attributed {
+            Ok(owners) => {
+                let counts = owner_counts(&owners, script.shapes.len());
+                emit_log(host, "info", &format!("Triangles per shape: {:?}", counts), Some("Mesh"));
+                let colors = has_colors.then(|| owners.iter().map(|&o| shape_colors[o]).collect());
+                (counts, colors)
+            }
+            Err(e) => {
+                let error_msg = format!("Per-shape triangle counting failed: {}", e);
+                emit_log(host, "error", &error_msg, Some("Mesh"));
+                return Err(error_msg.into());
+            }
+        }
+    } else {
+        (vec![mesh.triangles.len()], None)
+    };
+
+    let start = Instant::now();
+    let validation = mesh::validate::validate_mesh(&mesh);
+    timings.validate_ms = elapsed_ms(start);
+    let level = if validation.printable { "info" } else { "warn" };
+    emit_log(host, level, &validation.summary(), Some("Mesh"));
+    if !validation.watertight || validation.non_manifold_edges > 0 {
+        warnings.push(MeshWarning::NotWatertight {
+            open_edges: validation.open_edges,
+            non_manifold_edges: validation.non_manifold_edges,
+            message: validation.summary(),
+        });
+    }
+    if mesh.triangles.len() > LARGE_MESH_TRIANGLES {
+        let message = format!(
+            "The mesh has {} triangles, which is slow to display and export; \
+             lower the depth or set a target triangle count",
+            mesh.triangles.len()
+        );
+        emit_log(host, "warn", &message, Some("Mesh"));
+        warnings.push(MeshWarning::LargeMesh { triangles: mesh.triangles.len(), message });
+    }
+    if let Some(min_wall) = request.advanced.min_wall {
+        let thin = mesh_space_shape(&script.ctx, script.root, transform)
+            .map_err(anyhow::Error::from)
+            .and_then(|shape| thin_regions(&mesh, &shape, min_wall));
+        match thin {
+            Ok(report) if !report.regions.is_empty() => {
+                let message = format!(
+                    "Walls are thinner than {} in {} places, down to {:.3}",
+                    min_wall,
+                    report.regions.len() + report.omitted_regions,
+                    report.regions.iter().map(|r| r.min_thickness).fold(f32::INFINITY, f32::min)
+                );
+                emit_log(host, "warn", &message, Some("Analysis"));
+                warnings.push(MeshWarning::ThinWalls { report, message });
+            }
+            Ok(_) => {}
+            Err(e) => emit_log(host, "warn", &format!("Checking wall thickness failed: {}", e), Some("Analysis")),
+        }
+    }
+    // A mesh left in the unit cube is measured after moving it back, so sizes are in model units
+    let units = MeshUnits { unit: script.unit, to_model: transform };
+    let measurements = match units.model_mesh(&mesh) {
+        Some(model) => mesh::measure::measure_mesh(&model),
+        None => mesh::measure::measure_mesh(&mesh),
+    };
+    emit_log(
+        host,
+        "info",
+        &format!("Volume {:.3}, surface area {:.3}", measurements.volume, measurements.surface_area),
+        Some("Mesh"),
+    );
+    let assertions = check_script_assertions(host, &script, &measurements)?;
+    let region = meshing_region(bounds, transform, request.advanced.margin);
+    let complexity = match shape_complexity(&script.ctx, script.root, &region) {
+        Ok(complexity) => {
+            emit_log(
+                host,
+                "info",
+                &format!(
+                    "Shape has {} nodes and a tape of {} instructions, pruned to about {:.0} near the surface",
+                    complexity.node_count, complexity.tape_length, complexity.cell_tape_length
+                ),
+                Some("Analysis"),
+            );
+            Some(complexity)
         }
         Err(e) => {
-            let error_msg = format!("STL export failed: {}", e);
-            emit_log(&app_handle, "error", &error_msg, Some("Export"));
-            return Ok(MeshResult {
-                success: false,
-                stl_data: None,
-                triangle_count: Some(triangle_count),
-                error: Some(error_msg),
-            });
+            emit_log(host, "warn", &format!("Measuring shape complexity failed: {}", e), Some("Analysis"));
+            None
         }
     };
-    
-    emit_log(&app_handle, "info", "Mesh compilation completed successfully", Some("System"));
-    
-    Ok(MeshResult {
-        success: true,
-        stl_data: Some(stl_data),
-        triangle_count: Some(triangle_count),
-        error: None,
+
+    Ok(CompiledMesh {
+        mesh,
+        bounds,
+        shape_triangle_counts,
+        triangle_colors,
+        shape_colors,
+        params: script.params,
+        validation,
+        measurements,
+        imports: script.imports,
+        units,
+        warnings,
+        assertions,
+        complexity,
+        outline: None,
+    })
+}
+
+/// Trace the contours of a script in 2D mode where it crosses the XY plane, instead of meshing it
+///
+/// The grid has `2^depth` cells along the longer side, up to
+/// `MAX_SLICE_RESOLUTION`, so quality presets still trade detail for
+/// speed.  The compiled mesh is left empty.
+fn outline_script(
+    host: &dyn Host,
+    script: ScriptOutput,
+    request: &MeshRequest,
+    timings: &mut CompileTimings,
+) -> Result<CompiledMesh, CompileError> {
+    let resolution = (1usize << request.quality.depth.min(16)).clamp(2, MAX_SLICE_RESOLUTION);
+    emit_log(host, "info", &format!("2D mode: tracing outlines on a {} cell grid", resolution), Some("Mesh"));
+    let start = Instant::now();
+    let bounds = request.advanced.bounds.or(script.bounds);
+    let outline = match mesh::outline::outline(&script.ctx, script.root, bounds, resolution) {
+        Ok(Some(outline)) if !outline.contours.is_empty() => outline,
+        Ok(_) => {
+            let error_msg = "The 2D shape is empty or unbounded, so there's nothing to outline".to_string();
+            emit_log(host, "error", &error_msg, Some("Mesh"));
+            return Err(error_msg.into());
+        }
+        Err(e) => {
+            let error_msg = format!("Tracing outlines failed: {}", e);
+            emit_log(host, "error", &error_msg, Some("Mesh"));
+            return Err(error_msg.into());
+        }
+    };
+    timings.dual_walk_ms = elapsed_ms(start);
+    emit_log(host, "info", &format!("Traced {} outlines", outline.contours.len()), Some("Mesh"));
+    let bounds = Bounds { min: [outline.min[0], outline.min[1], 0.0], max: [outline.max[0], outline.max[1], 0.0] };
+    Ok(CompiledMesh {
+        mesh: Mesh { vertices: Vec::new(), triangles: Vec::new() },
+        bounds: Some(bounds),
+        shape_triangle_counts: vec![0; script.shapes.len()],
+        triangle_colors: None,
+        shape_colors: script.shapes.iter().map(|s| s.color).collect(),
+        params: script.params,
+        validation: MeshReport::default(),
+        measurements: MeshMeasurements::default(),
+        imports: script.imports,
+        units: MeshUnits { unit: script.unit, to_model: None },
+        warnings: Vec::new(),
+        assertions: Vec::new(),
+        complexity: None,
+        outline: Some(outline),
+    })
+}
+
+/// Run the script's `assert_*` calls against its compiled mesh's `measurements`, logging each outcome
+///
+/// Assertions are in model coordinates, as the measurements are.
+fn check_script_assertions(
+    host: &dyn Host,
+    script: &ScriptOutput,
+    measurements: &MeshMeasurements,
+) -> Result<Vec<AssertionResult>, String> {
+    if script.assertions.is_empty() {
+        return Ok(Vec::new());
+    }
+    let checked = VmShape::new(&script.ctx, script.root)
+        .map_err(anyhow::Error::from)
+        .and_then(|shape| check_assertions(&script.assertions, measurements, &shape));
+    let results = checked.map_err(|e| {
+        let error_msg = format!("Checking assertions failed: {}", e);
+        emit_log(host, "error", &error_msg, Some("Compiler"));
+        error_msg
+    })?;
+    for result in &results {
+        let line = result.line.map(|line| format!(" (line {})", line)).unwrap_or_default();
+        let (level, outcome) = if result.passed { ("info", "passed") } else { ("error", "failed") };
+        let message = format!("{} {}{}: {}", result.assertion.function(), outcome, line, result.message);
+        emit_log(host, level, &message, Some("Compiler"));
+    }
+    Ok(results)
+}
+
+/// Meshing settings for building a script outside the editor
+///
+/// Used by the CLI and batch exports, where per-file settings override the
+/// batch defaults field by field.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BuildSettings {
+    pub depth: Option<u8>,
+    pub quality: Option<QualityPreset>,
+    pub backend: Option<EvalBackend>,
+    /// `param()` overrides
+    pub params: HashMap<String, f64>,
+    pub target_triangles: Option<usize>,
+    pub max_error: Option<f32>,
+    pub repair: Option<bool>,
+    pub up_axis: Option<UpAxis>,
+    /// Coarsen flat regions after meshing, as `CoarsenOptions` describes
+    #[serde(alias = "adaptive")]
+    pub coarsen: Option<bool>,
+}
+
+impl BuildSettings {
+    /// These settings, with anything left unset taken from `defaults`
+    ///
+    /// Depth and preset are taken together, since a preset beats a depth;
+    /// otherwise a depth set here would lose to a preset in `defaults`.
+    fn or(&self, defaults: &BuildSettings) -> BuildSettings {
+        let mut params = defaults.params.clone();
+        params.extend(self.params.iter().map(|(name, &value)| (name.clone(), value)));
+        let (depth, quality) = if self.depth.is_some() || self.quality.is_some() {
+            (self.depth, self.quality)
+        } else {
+            (defaults.depth, defaults.quality)
+        };
+        BuildSettings {
+            depth,
+            quality,
+            backend: self.backend.or(defaults.backend),
+            params,
+            target_triangles: self.target_triangles.or(defaults.target_triangles),
+            max_error: self.max_error.or(defaults.max_error),
+            repair: self.repair.or(defaults.repair),
+            up_axis: self.up_axis.or(defaults.up_axis),
+            coarsen: self.coarsen.or(defaults.coarsen),
+        }
+    }
+
+    /// Meshing request for these settings; without a depth or preset, the default preset is used
+    fn mesh_request(&self) -> MeshRequest {
+        let quality = match (self.quality, self.depth) {
+            (None, Some(depth)) => MeshQuality::resolve(depth, None),
+            (preset, _) => preset.unwrap_or_default().settings(),
+        };
+        MeshRequest {
+            quality,
+            center: [0.0, 0.0, 0.0],
+            auto_bounds: true,
+            backend: self.backend.unwrap_or_default(),
+            simplify: SimplifyOptions { target_triangles: self.target_triangles, max_error: self.max_error },
+            advanced: AdvancedMeshOptions {
+                coarsen: CoarsenOptions { enabled: self.coarsen.unwrap_or(false), ..Default::default() },
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// The editor's settings, overridden by those pinned in the document's file if it has any
+fn with_project_settings(
+    host: &dyn Host,
+    document_path: Option<&str>,
+    editor: BuildSettings,
+) -> (BuildSettings, Option<ProjectSettings>) {
+    let Some(project) = document_path.and_then(|path| read_project_settings(Path::new(path))) else {
+        return (editor, None);
+    };
+    if project.build != BuildSettings::default() {
+        emit_log(host, "debug", "Using the build settings saved with the script", Some("Compiler"));
+    }
+    (project.build.or(&editor), Some(project))
+}
+
+/// A script compiled and encoded in an export format
+struct BuiltModel {
+    data: Vec<u8>,
+    triangle_count: usize,
+    /// Checks of the mesh as written, after any repair
+    validation: MeshReport,
+    /// Outcome of each `assert_*` call in the script
+    assertions: Vec<AssertionResult>,
+}
+
+/// Compile a script and encode its mesh as `format`, for exports that skip the preview
+///
+/// Used by the CLI, batch exports and the export queue; `repair` runs
+/// `repair_mesh` before encoding, keeping triangle colors.  With `part`,
+/// only the `draw_named` shape of that name is meshed, in its own region.
+/// A script that calls `set_units` is written in real units, as
+/// `MeshUnits::export_unit` picks, then turned so `up_axis` points up.
+#[allow(clippy::too_many_arguments)]
+fn build_model(
+    host: &dyn Host,
+    document_id: &str,
+    source: &ScriptSource,
+    params: &HashMap<String, f64>,
+    request: &MeshRequest,
+    repair: bool,
+    up_axis: UpAxis,
+    format: ExportFormat,
+    options: &ExportOptions,
+    part: Option<&str>,
+    cancel: &CancelToken,
+    timings: &mut CompileTimings,
+) -> Result<BuiltModel, CompileError> {
+    let compiled = match part {
+        None => compile_mesh(host, document_id, source, params, request, cancel, timings)?,
+        Some(part) => catch_crash(host, || {
+            let mut script = run_script(host, document_id, source, params, cancel, timings)?;
+            let index = script.shapes.iter().position(|s| s.name.as_deref() == Some(part)).ok_or_else(|| {
+                let error_msg = format!("Script has no part named '{}'", part);
+                emit_log(host, "error", &error_msg, Some("Export"));
+                error_msg
+            })?;
+            let shape = script.shapes.swap_remove(index);
+            script.root = shape.node;
+            script.shapes = vec![shape];
+            script.materials.clear();
+            mesh_script(host, script, request, cancel, timings)
+        })?,
+    };
+    let (mut mesh, mut colors, mut validation) = (compiled.mesh, compiled.triangle_colors, compiled.validation);
+    let mut options = options.clone();
+    if let Some(unit) = compiled.units.export_unit(format, options.unit) {
+        mesh = compiled.units.convert(&mesh, unit);
+        options.unit = Some(unit);
+    }
+    if repair {
+        let repaired = repair_mesh(&mesh);
+        emit_log(host, "info", &repaired.report.summary(), Some("Export"));
+        colors = colors.map(|colors| repaired.sources.iter().map(|&t| colors[t]).collect());
+        mesh = repaired.mesh;
+        validation = mesh::validate::validate_mesh(&mesh);
+    }
+    let mesh = up_axis.for_format(format).orient(mesh);
+
+    emit_progress(host, CompileStage::Export);
+    let start = Instant::now();
+    let data = export_mesh(&mesh, colors.as_deref(), format, &options).map_err(|e| {
+        let error_msg = format!("{} export failed: {}", format.label(), e);
+        emit_log(host, "error", &error_msg, Some("Export"));
+        error_msg
+    })?;
+    timings.export_ms = elapsed_ms(start);
+    Ok(BuiltModel { data, triangle_count: mesh.triangles.len(), validation, assertions: compiled.assertions })
+}
+
+/// Outcome of one script in a batch export
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BatchFileResult {
+    /// Script file name, relative to the batch folder
+    pub script: String,
+    /// STL written, if the script built
+    pub path: Option<String>,
+    pub triangle_count: usize,
+    /// Whether the mesh passed `validate_mesh`
+    pub printable: bool,
+    pub error: Option<String>,
+    pub duration_ms: f64,
+}
+
+/// Summary of a batch export
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BatchExportResult {
+    pub succeeded: usize,
+    pub failed: usize,
+    /// The batch was cancelled before every script ran
+    pub cancelled: bool,
+    /// Scripts that ran, in file name order
+    pub files: Vec<BatchFileResult>,
+}
+
+/// Sent as each script of a batch export starts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchProgress {
+    /// Zero-based position of the script in the batch
+    pub index: usize,
+    pub total: usize,
+    pub script: String,
+}
+
+/// Build every `.horsi` script directly inside `folder` and write `<stem>.stl` into `output`
+///
+/// Settings for each script are its entry in `file_settings`, keyed by file
+/// name, over `defaults`.  A script that fails is recorded and the batch
+/// moves on; only an unreadable folder fails the whole batch.
+#[allow(clippy::too_many_arguments)]
+fn batch_export_folder(
+    host: &dyn Host,
+    folder: &Path,
+    output: &Path,
+    defaults: &BuildSettings,
+    file_settings: &HashMap<String, BuildSettings>,
+    stl_format: StlFormat,
+    cancel: &CancelToken,
+    on_progress: &dyn Fn(&BatchProgress),
+) -> Result<BatchExportResult, String> {
+    let fail = |error_msg: String| {
+        emit_log(host, "error", &error_msg, Some("Export"));
+        error_msg
+    };
+    let mut scripts: Vec<PathBuf> = fs::read_dir(folder)
+        .map_err(|e| fail(format!("Failed to read folder {}: {}", folder.display(), e)))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && path.extension().is_some_and(|e| e.eq_ignore_ascii_case("horsi")))
+        .collect();
+    scripts.sort();
+    if scripts.is_empty() {
+        return Err(fail(format!("No .horsi scripts in {}", folder.display())));
+    }
+    fs::create_dir_all(output).map_err(|e| fail(format!("Failed to create folder {}: {}", output.display(), e)))?;
+
+    let format = match stl_format {
+        StlFormat::Binary => ExportFormat::Stl,
+        StlFormat::Ascii => ExportFormat::StlAscii,
+    };
+    let mut result = BatchExportResult::default();
+    for (index, script_path) in scripts.iter().enumerate() {
+        if cancel.is_cancelled() {
+            emit_log(host, "warn", "Batch export cancelled", Some("Export"));
+            result.cancelled = true;
+            break;
+        }
+        let script = script_path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        on_progress(&BatchProgress { index, total: scripts.len(), script: script.clone() });
+        emit_log(host, "info", &format!("Building {} ({} of {})", script, index + 1, scripts.len()), Some("Export"));
+
+        let start = Instant::now();
+        // The batch's per-file entry, then settings saved with the script, then the batch defaults
+        let pinned = read_project_settings(script_path).map(|project| project.build.or(defaults));
+        let defaults = pinned.as_ref().unwrap_or(defaults);
+        let settings = file_settings.get(&script).map_or_else(|| defaults.clone(), |s| s.or(defaults));
+        let mut file = BatchFileResult { script, ..Default::default() };
+        let built = fs::read_to_string(script_path)
+            .map_err(|e| fail(format!("Failed to read {}: {}", script_path.display(), e)))
+            .and_then(|code| {
+                let path = script_path.to_string_lossy();
+                let source = ScriptSource::new(code, Some(&path));
+                let options = ExportOptions { script_name: Some(file.script.clone()), ..Default::default() };
+                let mut timings = CompileTimings::default();
+                // One cache slot for the whole batch, replaced by each script
+                build_model(
+                    host,
+                    "batch_export",
+                    &source,
+                    &settings.params,
+                    &settings.mesh_request(),
+                    settings.repair.unwrap_or(false),
+                    settings.up_axis.unwrap_or_default(),
+                    format,
+                    &options,
+                    None,
+                    cancel,
+                    &mut timings,
+                )
+                .map_err(|e| e.message)
+            })
+            .and_then(|model| {
+                let stem = script_path.file_stem().unwrap_or_default().to_string_lossy();
+                let path = output.join(format!("{}.stl", sanitize_file_stem(&stem))).to_string_lossy().into_owned();
+                write_export_file(host, &path, &model.data, format.label())?;
+                // A part library failing its own checks fails the batch, though the file is still written
+                match model.assertions.iter().filter(|a| !a.passed).count() {
+                    0 => Ok((path, model)),
+                    failed => Err(format!("{} of {} assertions failed", failed, model.assertions.len())),
+                }
+            });
+        match built {
+            Ok((path, model)) => {
+                file.path = Some(path);
+                file.triangle_count = model.triangle_count;
+                file.printable = model.validation.printable;
+                result.succeeded += 1;
+            }
+            Err(error_msg) => {
+                file.error = Some(error_msg);
+                result.failed += 1;
+            }
+        }
+        file.duration_ms = elapsed_ms(start);
+        result.files.push(file);
+    }
+
+    let level = if result.failed > 0 { "warn" } else { "info" };
+    emit_log(
+        host,
+        level,
+        &format!(
+            "Batch export finished: {} built, {} failed{}",
+            result.succeeded,
+            result.failed,
+            if result.cancelled { ", cancelled" } else { "" }
+        ),
+        Some("Export"),
+    );
+    Ok(result)
+}
+
+/// Compile Rhai script and generate STL mesh
+///
+/// The response is a single binary frame (see `encode_binary_frame`) holding
+/// the `MeshResult` header and the raw STL bytes, so large meshes cross the
+/// IPC bridge without being expanded into a JSON number array.  The header is
+/// also emitted as a `compile_result` event for listeners outside the caller.
+///
+/// Requests are coalesced per `document_id`: a new request cancels the one in
+/// flight, and requests superseded while queued return without meshing.
+/// Deep compiles send a coarse preview first; see `compile_progressive`.
+/// Document ids are scoped to the calling window, whose logs and progress
+/// events go to it alone.
+///
+/// If `quality` names a preset, it takes precedence over `depth`.  Bounds are
+/// detected automatically unless `auto_bounds` is false, in which case the
+/// script's `set_scale` and `center` are used.  `scale` is only remembered
+/// with the document.  `backend` overrides the evaluator chosen in the
+/// mesh options.
+///
+/// `target_triangles` and `max_error` decimate the mesh after meshing; see
+/// `simplify` for how they interact.
+///
+/// `advanced` exposes further meshing knobs; see `AdvancedMeshOptions`.
+///
+/// With `stream` set, a successful compile's response carries the header
+/// alone once the whole mesh is built, and the mesh is then fetched in
+/// pieces with `stream_mesh`.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn compile_script(
+    window: Window,
+    compile_state: State<'_, CompileState>,
+    mesh_options: State<'_, MeshOptionsState>,
+    document_id: Option<String>,
+    document_path: Option<String>,
+    code: String,
+    depth: u8,
+    scale: Option<f32>,
+    center: Option<[f32; 3]>,
+    stl_format: Option<StlFormat>,
+    quality: Option<QualityPreset>,
+    auto_bounds: Option<bool>,
+    params: Option<HashMap<String, f64>>,
+    backend: Option<EvalBackend>,
+    target_triangles: Option<usize>,
+    max_error: Option<f32>,
+    stream: Option<bool>,
+    advanced: Option<AdvancedMeshOptions>,
+) -> Result<Response, String> {
+    let advanced = advanced.unwrap_or_default();
+    advanced.validate()?;
+    let host = WindowHost::new(&window);
+    let editor = BuildSettings {
+        depth: Some(depth),
+        quality,
+        backend: Some(backend.unwrap_or(mesh_options.get().backend)),
+        params: params.unwrap_or_default(),
+        target_triangles,
+        max_error,
+        repair: None,
+        up_axis: None,
+        coarsen: Some(advanced.coarsen.enabled),
+    };
+    let (settings, _) = with_project_settings(&host, document_path.as_deref(), editor);
+    let code_copy = code.clone();
+    let source = ScriptSource::new(code, document_path.as_deref());
+    let coarsen = CoarsenOptions { enabled: settings.coarsen.unwrap_or(false), ..advanced.coarsen };
+    let request = MeshRequest {
+        center: center.unwrap_or([0.0, 0.0, 0.0]),
+        auto_bounds: auto_bounds.unwrap_or(true),
+        advanced: AdvancedMeshOptions { coarsen, ..advanced },
+        ..settings.mesh_request()
+    };
+    let params = settings.params;
+    let stl_format = stl_format.unwrap_or_default();
+    let document_key = host.document_key(document_id.as_deref());
+    host.app().state::<SessionStore>().track(&document_key, window.label(), document_id.as_deref(), |document| {
+        document.path = document_path.clone();
+        document.compile = Some(CompileDefaults { depth: Some(depth), quality, scale, center, auto_bounds, backend });
+        document.params = params.clone();
+    });
+    let (mut result, stl_data) =
+        compile_queued(&host, &compile_state, &document_key, source, params, request, stl_format).await?;
+    if result.success {
+        let script = document_path.as_deref().unwrap_or(&document_key);
+        record_version(host.app(), script, VersionReason::Compile, &code_copy);
+    }
+    if stream.unwrap_or(false) && result.success {
+        result.streamed = true;
+        return encode_binary_frame(&result, &[]).map(Response::new);
+    }
+    encode_binary_frame(&result, &stl_data).map(Response::new)
+}
+
+/// The document's kept snapshots of successful compiles, newest first
+#[tauri::command]
+fn list_snapshots(
+    window: Window,
+    snapshots: State<'_, SnapshotStore>,
+    document_id: Option<String>,
+) -> Vec<SnapshotInfo> {
+    snapshots.list(&WindowHost::new(&window).document_key(document_id.as_deref()))
+}
+
+/// Make a kept snapshot the document's current mesh again, without recompiling
+///
+/// Returns the snapshot's result header and mesh in the frame
+/// `compile_script` sends, with `snapshot` set to its id; with `stream`
+/// set, the mesh is left out to be fetched with `stream_mesh`.  The
+/// editor's code is left alone, so the next compile picks up from it.
+#[tauri::command]
+fn restore_snapshot(
+    window: Window,
+    mesh_cache: State<'_, MeshCache>,
+    snapshots: State<'_, SnapshotStore>,
+    document_id: Option<String>,
+    id: u64,
+    stream: Option<bool>,
+) -> Result<Response, String> {
+    let host = WindowHost::new(&window);
+    let document_key = host.document_key(document_id.as_deref());
+    let Some(snapshot) = snapshots.get(&document_key, id) else {
+        return Err(format!("No snapshot {} is kept for this document", id));
+    };
+    mesh_cache.insert(&document_key, snapshot.mesh.clone());
+    emit_log(&host, "info", &format!("Restored the compile from {}", snapshot.timestamp), Some("Compiler"));
+    let streamed = stream.unwrap_or(false);
+    let result = MeshResult {
+        snapshot: Some(snapshot.id),
+        streamed,
+        // The print profile may have changed since
+        print_estimate: Some(estimate_last_print(host.app(), &snapshot.mesh)),
+        timings: CompileTimings::default(),
+        ..snapshot.result.clone()
+    };
+    let body: &[u8] = if streamed { &[] } else { &snapshot.stl_data };
+    encode_binary_frame(&result, body).map(Response::new)
+}
+
+/// Where a chunk sent by `stream_mesh` sits in the whole mesh
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct MeshChunkHeader {
+    index: usize,
+    chunk_count: usize,
+    /// Index of the chunk's first triangle in the whole mesh
+    first_triangle: usize,
+    vertex_count: usize,
+    triangle_count: usize,
+}
+
+/// Send the document's last mesh to `on_chunk` in pieces of up to `max_triangles`
+///
+/// Each message is a binary frame (see `encode_binary_frame`) holding a
+/// `MeshChunkHeader` and the chunk's `MeshBuffers` as packed little-endian
+/// positions, normals and indices.  Chunking spares any single IPC message
+/// a huge mesh, and lets the frontend upload each chunk to the GPU while
+/// the rest are still being encoded and sent.  Returns the number of chunks.
+///
+/// Chunks are only cut once the whole mesh is finished; sending each
+/// region as the dual walk completes it, before meshing is done, isn't
+/// supported yet.  fidget walks the octree in a single pass, and welding,
+/// sharpening and decimation all work on the whole mesh after it, so
+/// regions meshed apart would be open along their seams.
+#[tauri::command]
+async fn stream_mesh(
+    window: Window,
+    mesh_cache: State<'_, MeshCache>,
+    document_id: Option<String>,
+    max_triangles: Option<usize>,
+    on_chunk: Channel<Response>,
+) -> Result<usize, String> {
+    let host = WindowHost::new(&window);
+    let Some(last) = mesh_cache.get(&host.document_key(document_id.as_deref())) else {
+        return Err("Nothing has been compiled yet to stream".to_string());
+    };
+    let max_triangles = max_triangles.unwrap_or(DEFAULT_CHUNK_TRIANGLES).max(1);
+
+    let sent = tauri::async_runtime::spawn_blocking(move || -> Result<usize, String> {
+        let chunk_count = last.mesh.triangles.len().div_ceil(max_triangles);
+        for (index, chunk) in MeshBuffers::chunks(&last.mesh, max_triangles).enumerate() {
+            let header = MeshChunkHeader {
+                index,
+                chunk_count,
+                first_triangle: index * max_triangles,
+                vertex_count: chunk.positions.len() / 3,
+                triangle_count: chunk.indices.len() / 3,
+            };
+            let frame = encode_binary_frame(&header, &chunk.to_bytes())?;
+            on_chunk.send(Response::new(frame)).map_err(|e| format!("Failed to send mesh chunk: {}", e))?;
+        }
+        Ok(chunk_count)
+    })
+    .await;
+    sent.map_err(|e| format!("Mesh worker failed: {}", e))?.inspect_err(|e| {
+        emit_log(&host, "error", e, Some("Mesh"));
+    })
+}
+
+/// SHA-256 of everything that decides a compile's output, as hex, keying the compile cache
+///
+/// The hash has to stay the same across releases, since entries are kept
+/// on disk, so each input is written out length-prefixed and hashed with
+/// `sha256_hex` rather than `DefaultHasher`.  `plugins` is
+/// `Plugins::fingerprint`, as scripts can call plugin functions.
+fn compile_cache_key(
+    source: &ScriptSource,
+    params: &HashMap<String, f64>,
+    request: &MeshRequest,
+    stl_format: StlFormat,
+    plugins: &str,
+) -> String {
+    let mut input = Vec::new();
+    let mut field = |bytes: &[u8]| {
+        input.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+        input.extend_from_slice(bytes);
+    };
+    field(source.code.as_bytes());
+    field(source.base_dir.as_deref().map(|dir| dir.to_string_lossy()).unwrap_or_default().as_bytes());
+    let mut params: Vec<_> = params.iter().collect();
+    params.sort_by(|a, b| a.0.cmp(b.0));
+    for (name, value) in params {
+        field(name.as_bytes());
+        field(&value.to_bits().to_le_bytes());
+    }
+    // The request's Debug form covers every field
+    field(format!("{:?} {:?}", request, stl_format).as_bytes());
+    field(plugins.as_bytes());
+    sha256_hex(&input)
+}
+
+/// Compile a script in turn with other requests for its document, as `compile_script` does
+///
+/// Returns the result header, also emitted as a `compile_result` event, and
+/// the STL bytes.
+async fn compile_queued(
+    host: &WindowHost,
+    compile_state: &CompileState,
+    document_key: &str,
+    source: ScriptSource,
+    params: HashMap<String, f64>,
+    request: MeshRequest,
+    stl_format: StlFormat,
+) -> Result<(MeshResult, Vec<u8>), String> {
+    let ticket = compile_state.enqueue(document_key);
+    let turn = ticket.wait_turn().await;
+    if !compile_state.is_current(&ticket) {
+        let result = MeshResult {
+            cancelled: true,
+            superseded: true,
+            generation: ticket.generation,
+            stl_format,
+            error: Some("Superseded by a newer compile request".to_string()),
+            ..Default::default()
+        };
+        return Ok((result, Vec::new()));
+    }
+
+    let plugins = host.app().state::<Plugins>().fingerprint().to_string();
+    let cache_key = compile_cache_key(&source, &params, &request, stl_format, &plugins);
+    if let Some(cached) = host.app().state::<CompileCache>().get(&cache_key) {
+        drop(turn);
+        compile_state.finish(&ticket);
+        emit_log(host, "info", "Inputs unchanged; using the cached mesh", Some("Compiler"));
+        host.app().state::<MeshCache>().insert(document_key, cached.mesh.clone());
+        host.app().state::<SnapshotStore>().record(
+            document_key,
+            cached.result.clone(),
+            cached.stl_data.clone(),
+            cached.mesh.clone(),
+        );
+        let result = MeshResult {
+            generation: ticket.generation,
+            superseded: !compile_state.is_current(&ticket),
+            cached: true,
+            // The print profile may have changed since
+            print_estimate: Some(estimate_last_print(host.app(), &cached.mesh)),
+            timings: CompileTimings::default(),
+            ..cached.result.clone()
+        };
+        host.emit("compile_result", &result);
+        return Ok((result, cached.stl_data.clone()));
+    }
+
+    // Meshing is CPU-bound, so keep it off the async runtime's worker threads
+    let worker_handle = host.clone();
+    let worker_cancel = ticket.cancel.clone();
+    let worker_document = ticket.document_id.clone();
+    let worker_key = cache_key.clone();
+    let generation = ticket.generation;
+    let depth = request.quality.depth;
+    let compiled = tauri::async_runtime::spawn_blocking(move || {
+        let mut timings = CompileTimings::default();
+        let (mut result, stl_data) =
+            match compile_progressive(
+                &worker_handle,
+                &worker_document,
+                &source,
+                &params,
+                &request,
+                &worker_cancel,
+                &mut timings,
+                &|preview| worker_handle.emit("compile_coarse_preview", &PreviewResult { generation, ..preview }),
+            ) {
+                Ok(compiled) => {
+                    let start = Instant::now();
+                    let (mut result, stl_data) = export_compiled_mesh(&worker_handle, &compiled, stl_format);
+                    timings.export_ms = elapsed_ms(start);
+                    result.bounds = compiled.bounds;
+                    result.shape_triangle_counts = compiled.shape_triangle_counts;
+                    result.shape_colors = compiled.shape_colors.iter().map(|c| c.map(to_hex)).collect();
+                    result.parameters = compiled.params;
+                    result.validation = Some(compiled.validation);
+                    result.measurements = Some(compiled.measurements);
+                    result.unit = compiled.units.unit;
+                    result.warnings = compiled.warnings;
+                    result.diagnostics =
+                        compiled.assertions.iter().filter(|a| !a.passed).map(AssertionResult::diagnostic).collect();
+                    result.diagnostics.extend(script_warnings(&source.code));
+                    result.assertions = compiled.assertions;
+                    result.complexity = compiled.complexity;
+                    result.outline = compiled.outline;
+                    let origin = MeshOrigin {
+                        script_hash: sha256_hex(source.code.as_bytes()),
+                        parameters: result.parameters.clone(),
+                        timings,
+                    };
+                    let last = Arc::new(LastMesh {
+                        mesh: compiled.mesh,
+                        triangle_colors: compiled.triangle_colors,
+                        units: compiled.units,
+                        origin: Some(origin),
+                    });
+                    result.print_estimate = Some(estimate_last_print(worker_handle.app(), &last));
+                    worker_handle.app().state::<MeshCache>().insert(&worker_document, last.clone());
+                    worker_handle.app().state::<SnapshotStore>().record(
+                        &worker_document,
+                        result.clone(),
+                        stl_data.clone(),
+                        last.clone(),
+                    );
+                    let cached = CachedCompile {
+                        result: result.clone(),
+                        stl_data: stl_data.clone(),
+                        mesh: last,
+                        imports: compiled.imports,
+                    };
+                    if let Err(e) = worker_handle.app().state::<CompileCache>().insert(&worker_key, cached) {
+                        let message = format!("Failed to cache the mesh on disk: {}", e);
+                        emit_log(&worker_handle, "warn", &message, Some("Compiler"));
+                    }
+                    (result, stl_data)
+                }
+                Err(error) => (
+                    MeshResult {
+                        cancelled: worker_cancel.is_cancelled(),
+                        stl_format,
+                        crashed: error.crashed,
+                        error: Some(error.message),
+                        diagnostics: error.diagnostics,
+                        ..Default::default()
+                    },
+                    Vec::new(),
+                ),
+            };
+        result.timings = timings;
+        (result, stl_data)
+    })
+    .await;
+    drop(turn);
+    compile_state.finish(&ticket);
+    let (mut result, stl_data) = compiled.map_err(|e| format!("Compile worker failed: {}", e))?;
+    result.generation = ticket.generation;
+    result.superseded = !compile_state.is_current(&ticket);
+    host.app().state::<CompileHistory>().record(CompileRecord {
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        document_id: ticket.document_id.clone(),
+        hash: cache_key,
+        depth,
+        triangle_count: result.triangle_count,
+        success: result.success,
+        timings: result.timings,
+    });
+
+    host.emit("compile_result", &result);
+    Ok((result, stl_data))
+}
+
+/// Watch a script on disk and recompile it whenever it changes
+///
+/// This lets the script be edited in another editor with horseCAD as a live
+/// viewer.  Each recompile is queued under `document_id` like a
+/// `compile_script` call, and is followed by a `watched_file_compiled`
+/// event carrying the new code and result header; the mesh itself is
+/// fetched with `get_watched_mesh`.  Each window watches one file at a time.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn watch_file(
+    window: Window,
+    watch_state: State<'_, WatchState>,
+    mesh_options: State<'_, MeshOptionsState>,
+    path: String,
+    document_id: Option<String>,
+    depth: u8,
+    quality: Option<QualityPreset>,
+    params: Option<HashMap<String, f64>>,
+    backend: Option<EvalBackend>,
+    stl_format: Option<StlFormat>,
+    target_triangles: Option<usize>,
+    max_error: Option<f32>,
+) -> Result<(), String> {
+    let host = WindowHost::new(&window);
+    let request = WatchRequest {
+        document_key: host.document_key(document_id.as_deref()),
+        params: params.unwrap_or_default(),
+        request: MeshRequest {
+            quality: MeshQuality::resolve(depth, quality),
+            center: [0.0, 0.0, 0.0],
+            auto_bounds: true,
+            backend: backend.unwrap_or(mesh_options.get().backend),
+            simplify: SimplifyOptions { target_triangles, max_error },
+            advanced: AdvancedMeshOptions::default(),
+        },
+        stl_format: stl_format.unwrap_or_default(),
+    };
+    watch_state.watch(&host, PathBuf::from(&path), request).inspect_err(|e| {
+        emit_log(&host, "error", e, Some("Compiler"));
+    })
+}
+
+/// Stop watching the file this window passed to `watch_file`
+#[tauri::command]
+fn unwatch_file(window: Window, watch_state: State<'_, WatchState>) {
+    if let Some(path) = watch_state.unwatch(window.label()) {
+        let host = WindowHost::new(&window);
+        emit_log(&host, "info", &format!("Stopped watching {}", path.display()), Some("Compiler"));
+    }
+}
+
+/// Mesh from the latest recompile of this window's watched file, framed like `compile_script`'s response
+#[tauri::command]
+fn get_watched_mesh(window: Window, watch_state: State<'_, WatchState>) -> Result<Response, String> {
+    watch_state
+        .mesh(window.label())
+        .map(Response::new)
+        .ok_or_else(|| "The watched file hasn't been recompiled yet".to_string())
+}
+
+/// Encode a compiled mesh as STL, returning the result header and payload
+///
+/// Triangle colors are stored in the binary STL attribute words; ASCII STL
+/// has nowhere to keep them.
+fn export_compiled_mesh(host: &dyn Host, compiled: &CompiledMesh, stl_format: StlFormat) -> (MeshResult, Vec<u8>) {
+    let mesh = &compiled.mesh;
+    let triangle_count = mesh.triangles.len();
+    
+    // Export to STL
+    emit_log(host, "info", "Exporting STL data", Some("Export"));
+    emit_progress(host, CompileStage::Export);
+    let stl_data = match export_mesh_to_stl(mesh, stl_format, &ExportOptions::default().part_name(), None) {
+        Ok(mut data) => {
+            match (&compiled.triangle_colors, stl_format) {
+                (Some(colors), StlFormat::Binary) => apply_stl_colors(&mut data, colors),
+                (Some(_), StlFormat::Ascii) => {
+                    emit_log(host, "warn", "ASCII STL can't store colors; they were dropped", Some("Export"));
+                }
+                (None, _) => {}
+            }
+            emit_log(host, "info", &format!("STL export complete ({})", prettify_byte_count(data.len() as u64)), Some("Export"));
+            data
+        }
+        Err(e) => {
+            let error_msg = format!("STL export failed: {}", e);
+            emit_log(host, "error", &error_msg, Some("Export"));
+            let result = MeshResult {
+                stl_format,
+                triangle_count: Some(triangle_count),
+                error: Some(error_msg),
+                ..Default::default()
+            };
+            return (result, Vec::new());
+        }
+    };
+    
+    emit_log(host, "info", "Mesh compilation completed successfully", Some("System"));
+    emit_progress(host, CompileStage::Done);
+    
+    let result = MeshResult {
+        success: true,
+        stl_format,
+        triangle_count: Some(triangle_count),
+        ..Default::default()
+    };
+    (result, stl_data)
+}
+
+/// Compile Rhai script and return GPU-ready indexed buffers for the preview
+///
+/// This skips STL encoding entirely, so the viewport can build a
+/// `BufferGeometry` without parsing a triangle soup in JavaScript.  Center
+/// and bounds arguments behave as in `compile_script`.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn compile_preview(
+    window: Window,
+    compile_state: State<'_, CompileState>,
+    mesh_options: State<'_, MeshOptionsState>,
+    document_id: Option<String>,
+    document_path: Option<String>,
+    code: String,
+    depth: u8,
+    center: Option<[f32; 3]>,
+    quality: Option<QualityPreset>,
+    auto_bounds: Option<bool>,
+    params: Option<HashMap<String, f64>>,
+    backend: Option<EvalBackend>,
+) -> Result<PreviewResult, String> {
+    let host = WindowHost::new(&window);
+    let editor = BuildSettings {
+        depth: Some(depth),
+        quality,
+        backend: Some(backend.unwrap_or(mesh_options.get().backend)),
+        params: params.unwrap_or_default(),
+        ..Default::default()
+    };
+    let (settings, _) = with_project_settings(&host, document_path.as_deref(), editor);
+    let source = ScriptSource::new(code, document_path.as_deref());
+    let request = MeshRequest {
+        center: center.unwrap_or([0.0, 0.0, 0.0]),
+        auto_bounds: auto_bounds.unwrap_or(true),
+        // Previews stay at full detail; decimation is for the exported mesh
+        simplify: SimplifyOptions::default(),
+        ..settings.mesh_request()
+    };
+    let params = settings.params;
+    let script_hash = sha256_hex(source.code.as_bytes());
+
+    let ticket = compile_state.enqueue(&host.document_key(document_id.as_deref()));
+    let turn = ticket.wait_turn().await;
+    if !compile_state.is_current(&ticket) {
+        return Ok(PreviewResult {
+            cancelled: true,
+            superseded: true,
+            generation: ticket.generation,
+            error: Some("Superseded by a newer compile request".to_string()),
+            ..Default::default()
+        });
+    }
+
+    let worker_handle = host.clone();
+    let worker_cancel = ticket.cancel.clone();
+    let worker_document = ticket.document_id.clone();
+    let generation = ticket.generation;
+    let compiled = tauri::async_runtime::spawn_blocking(move || {
+        let mut timings = CompileTimings::default();
+        let compiled = compile_progressive(
+            &worker_handle,
+            &worker_document,
+            &source,
+            &params,
+            &request,
+            &worker_cancel,
+            &mut timings,
+            &|preview| worker_handle.emit("compile_coarse_preview", &PreviewResult { generation, ..preview }),
+        );
+        (compiled, timings)
+    })
+    .await;
+    drop(turn);
+    compile_state.finish(&ticket);
+    let (compiled, mut timings) = compiled.map_err(|e| format!("Compile worker failed: {}", e))?;
+    let CompiledMesh {
+        mesh, bounds, shape_triangle_counts, triangle_colors, shape_colors, params, units, outline, ..
+    } =
+        match compiled {
+            Ok(compiled) => compiled,
+            Err(error) => {
+                return Ok(PreviewResult {
+                    cancelled: ticket.cancel.is_cancelled(),
+                    superseded: !compile_state.is_current(&ticket),
+                    generation: ticket.generation,
+                    timings,
+                    crashed: error.crashed,
+                    error: Some(error.message),
+                    diagnostics: error.diagnostics,
+                    ..Default::default()
+                });
+            }
+        };
+
+    emit_progress(&host, CompileStage::Export);
+    let start = Instant::now();
+    let buffers = MeshBuffers::from_mesh(&mesh);
+    timings.export_ms = elapsed_ms(start);
+    emit_progress(&host, CompileStage::Done);
+    emit_log(
+        &host,
+        "info",
+        &format!("Preview buffers ready ({} vertices, {} triangles)", mesh.vertices.len(), mesh.triangles.len()),
+        Some("System"),
+    );
+    let triangle_count = mesh.triangles.len();
+    let origin = MeshOrigin { script_hash, parameters: params.clone(), timings };
+    let last = LastMesh { mesh, triangle_colors, units, origin: Some(origin) };
+    host.app().state::<MeshCache>().insert(&ticket.document_id, Arc::new(last));
+
+    Ok(PreviewResult {
+        success: true,
+        generation: ticket.generation,
+        superseded: !compile_state.is_current(&ticket),
+        triangle_count: Some(triangle_count),
+        bounds,
+        shape_triangle_counts,
+        shape_colors: shape_colors.iter().map(|c| c.map(to_hex)).collect(),
+        parameters: params,
+        timings,
+        buffers: Some(buffers),
+        outline,
+        ..Default::default()
+    })
+}
+
+/// Preview only the expression in `selection`, as if it were the one shape drawn
+///
+/// `context_code` is the rest of the document, run first so the selection
+/// can use its variables and functions; the selection is then drawn on a
+/// line of its own after it, and everything the document itself draws is
+/// left out.  The result isn't kept as the document's last mesh, so
+/// exporting still writes the full model.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn compile_snippet(
+    window: Window,
+    compile_state: State<'_, CompileState>,
+    mesh_options: State<'_, MeshOptionsState>,
+    document_id: Option<String>,
+    document_path: Option<String>,
+    selection: String,
+    context_code: String,
+    depth: u8,
+    quality: Option<QualityPreset>,
+    params: Option<HashMap<String, f64>>,
+    backend: Option<EvalBackend>,
+) -> Result<PreviewResult, String> {
+    let expression = selection.trim().trim_end_matches(';').trim_end();
+    if expression.is_empty() {
+        return Err("Select an expression to preview".to_string());
+    }
+    let host = WindowHost::new(&window);
+    let editor = BuildSettings {
+        depth: Some(depth),
+        quality,
+        backend: Some(backend.unwrap_or(mesh_options.get().backend)),
+        params: params.unwrap_or_default(),
+        ..Default::default()
+    };
+    let (settings, _) = with_project_settings(&host, document_path.as_deref(), editor);
+    let code = format!("{}\ndraw({});\n", context_code, expression);
+    let source = ScriptSource::new(code, document_path.as_deref());
+    let request = MeshRequest { simplify: SimplifyOptions::default(), ..settings.mesh_request() };
+    let params = settings.params;
+
+    // Queued and cached apart from the document, so a snippet doesn't cancel its preview
+    let document_id = format!("{}:snippet", host.document_key(document_id.as_deref()));
+    let ticket = compile_state.enqueue(&document_id);
+    let turn = ticket.wait_turn().await;
+
+    let worker_handle = host.clone();
+    let cancel = ticket.cancel.clone();
+    let compiled = tauri::async_runtime::spawn_blocking(move || {
+        let mut timings = CompileTimings::default();
+        let compiled = catch_crash(&worker_handle, || {
+            run_script(&worker_handle, &document_id, &source, &params, &cancel, &mut timings).and_then(|mut script| {
+                // The selection is drawn last, after anything the document draws
+                let snippet = script.shapes.pop().ok_or_else(|| "The selection didn't draw a shape".to_string())?;
+                script.root = snippet.node;
+                script.shapes = vec![snippet];
+                script.materials.clear();
+                script.assertions.clear();
+                script.flat = !mesh::outline::depends_on_z(&script.ctx, script.root);
+                mesh_script(&worker_handle, script, &request, &cancel, &mut timings)
+            })
+        });
+        (compiled, timings)
+    })
+    .await;
+    drop(turn);
+    compile_state.finish(&ticket);
+    let (compiled, timings) = compiled.map_err(|e| format!("Compile worker failed: {}", e))?;
+    match compiled {
+        Ok(compiled) => Ok(PreviewResult {
+            success: true,
+            generation: ticket.generation,
+            triangle_count: Some(compiled.mesh.triangles.len()),
+            bounds: compiled.bounds,
+            shape_triangle_counts: compiled.shape_triangle_counts,
+            shape_colors: compiled.shape_colors.iter().map(|c| c.map(to_hex)).collect(),
+            parameters: compiled.params,
+            timings,
+            buffers: Some(MeshBuffers::from_mesh(&compiled.mesh)),
+            outline: compiled.outline,
+            ..Default::default()
+        }),
+        Err(error) => Ok(PreviewResult {
+            cancelled: ticket.cancel.is_cancelled(),
+            generation: ticket.generation,
+            timings,
+            crashed: error.crashed,
+            error: Some(error.message),
+            diagnostics: error.diagnostics,
+            ..Default::default()
+        }),
+    }
+}
+
+/// Hollow the compiled model for resin printing and make it the document's mesh
+///
+/// The script runs as in `compile_preview`, then the union of everything it
+/// draws is shelled to `hollow.thickness` and drilled with its drain holes
+/// before meshing, so any model can be hollowed without editing its script.
+/// The result replaces the document's last mesh, so exports write the
+/// hollowed model until the next compile.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn hollow_model(
+    window: Window,
+    compile_state: State<'_, CompileState>,
+    mesh_options: State<'_, MeshOptionsState>,
+    document_id: Option<String>,
+    document_path: Option<String>,
+    code: String,
+    depth: u8,
+    center: Option<[f32; 3]>,
+    quality: Option<QualityPreset>,
+    auto_bounds: Option<bool>,
+    params: Option<HashMap<String, f64>>,
+    backend: Option<EvalBackend>,
+    hollow: HollowOptions,
+) -> Result<PreviewResult, String> {
+    hollow.validate()?;
+    let host = WindowHost::new(&window);
+    let editor = BuildSettings {
+        depth: Some(depth),
+        quality,
+        backend: Some(backend.unwrap_or(mesh_options.get().backend)),
+        params: params.unwrap_or_default(),
+        ..Default::default()
+    };
+    let (settings, _) = with_project_settings(&host, document_path.as_deref(), editor);
+    let source = ScriptSource::new(code, document_path.as_deref());
+    let request = MeshRequest {
+        center: center.unwrap_or([0.0, 0.0, 0.0]),
+        auto_bounds: auto_bounds.unwrap_or(true),
+        simplify: SimplifyOptions::default(),
+        ..settings.mesh_request()
+    };
+    let params = settings.params;
+    let script_hash = sha256_hex(source.code.as_bytes());
+
+    let ticket = compile_state.enqueue(&host.document_key(document_id.as_deref()));
+    let turn = ticket.wait_turn().await;
+
+    let worker_handle = host.clone();
+    let cancel = ticket.cancel.clone();
+    let document_key = ticket.document_id.clone();
+    let compiled = tauri::async_runtime::spawn_blocking(move || {
+        let mut timings = CompileTimings::default();
+        let compiled = catch_crash(&worker_handle, || {
+            run_script(&worker_handle, &document_key, &source, &params, &cancel, &mut timings).and_then(|mut script| {
+                if script.flat {
+                    return Err("Only 3D shapes can be hollowed".to_string().into());
+                }
+                script.root = mesh::hollow::hollow(&mut script.ctx, script.root, &hollow)
+                    .map_err(|e| format!("Hollowing failed: {}", e))?;
+                // Material regions split the solid union, which no longer matches
+                script.materials.clear();
+                let message = format!(
+                    "Hollowing to {} thick walls with {} drain hole(s)",
+                    hollow.thickness,
+                    hollow.drain_holes.len()
+                );
+                emit_log(&worker_handle, "info", &message, Some("Mesh"));
+                mesh_script(&worker_handle, script, &request, &cancel, &mut timings)
+            })
+        });
+        (compiled, timings)
+    })
+    .await;
+    drop(turn);
+    compile_state.finish(&ticket);
+    let (compiled, timings) = compiled.map_err(|e| format!("Compile worker failed: {}", e))?;
+    match compiled {
+        Ok(compiled) => {
+            let result = PreviewResult {
+                success: true,
+                generation: ticket.generation,
+                superseded: !compile_state.is_current(&ticket),
+                triangle_count: Some(compiled.mesh.triangles.len()),
+                bounds: compiled.bounds,
+                shape_triangle_counts: compiled.shape_triangle_counts,
+                shape_colors: compiled.shape_colors.iter().map(|c| c.map(to_hex)).collect(),
+                parameters: compiled.params.clone(),
+                timings,
+                buffers: Some(MeshBuffers::from_mesh(&compiled.mesh)),
+                ..Default::default()
+            };
+            let origin = MeshOrigin { script_hash, parameters: compiled.params, timings };
+            let last = LastMesh {
+                mesh: compiled.mesh,
+                triangle_colors: compiled.triangle_colors,
+                units: compiled.units,
+                origin: Some(origin),
+            };
+            host.app().state::<MeshCache>().insert(&ticket.document_id, Arc::new(last));
+            Ok(result)
+        }
+        Err(error) => Ok(PreviewResult {
+            cancelled: ticket.cancel.is_cancelled(),
+            generation: ticket.generation,
+            timings,
+            crashed: error.crashed,
+            error: Some(error.message),
+            diagnostics: error.diagnostics,
+            ..Default::default()
+        }),
+    }
+}
+
+/// Dry-run the script and return its `param()` declarations
+///
+/// Only the script is evaluated; nothing is meshed.  `params` overrides are
+/// applied, so the returned values match what a compile would use.
+#[tauri::command]
+async fn get_parameters(
+    app_handle: AppHandle,
+    document_path: Option<String>,
+    code: String,
+    params: Option<HashMap<String, f64>>,
+) -> Result<Vec<ScriptParam>, String> {
+    let source = ScriptSource::new(code, document_path.as_deref());
+    let params = params.unwrap_or_default();
+    let worker_handle = app_handle.clone();
+    let script = tauri::async_runtime::spawn_blocking(move || {
+        compile_rhai_script(&worker_handle, &source, &params, &CancelToken::new())
+    })
+    .await
+    .map_err(|e| format!("Script worker failed: {}", e))?;
+    script.map(|s| s.params).map_err(|e| {
+        let error_msg = format!("Script evaluation failed: {}", e);
+        emit_log(&app_handle, "error", &error_msg, Some("Compiler"));
+        error_msg
+    })
+}
+
+/// Check a script for errors without meshing it
+///
+/// By default only the syntax is checked, which is cheap enough to run on
+/// every keystroke.  With `dry_run`, the script is also evaluated so runtime
+/// errors are caught too; its `print()` output is logged as in a compile.
+/// Nothing is logged for the errors themselves, since they go to the editor.
+/// A valid script's diagnostics are the warnings from `script_warnings`.
+#[tauri::command]
+async fn check_script(
+    app_handle: AppHandle,
+    document_path: Option<String>,
+    code: String,
+    dry_run: Option<bool>,
+    params: Option<HashMap<String, f64>>,
+) -> Result<CheckResult, String> {
+    let source = ScriptSource::new(code, document_path.as_deref());
+    let params = params.unwrap_or_default();
+    let checked = tauri::async_runtime::spawn_blocking(move || {
+        let checked = match fidget::rhai::engine().compile(&source.code) {
+            Err(e) => Err(anyhow::Error::from(EvalAltResult::from(e))),
+            Ok(_) if dry_run.unwrap_or(false) => {
+                compile_rhai_script(&app_handle, &source, &params, &CancelToken::new()).map(|_| ())
+            }
+            Ok(_) => Ok(()),
+        };
+        match checked {
+            Ok(()) => CheckResult { valid: true, diagnostics: script_warnings(&source.code) },
+            Err(e) => CheckResult { valid: false, diagnostics: vec![diagnostic_from_error(&e, &source.code)] },
+        }
+    })
+    .await
+    .map_err(|e| format!("Script worker failed: {}", e))?;
+    Ok(checked)
+}
+
+/// Static analysis warnings for a script, such as unused variables and shapes never drawn
+///
+/// Scripts that don't parse get none; their syntax error is reported instead.
+fn script_warnings(code: &str) -> Vec<Diagnostic> {
+    let mut engine = fidget::rhai::engine();
+    // The optimizer would inline constants and drop their declarations
+    engine.set_optimization_level(rhai::OptimizationLevel::None);
+    match engine.compile(code) {
+        Ok(ast) => lint_script(&ast, code, &shape_functions()),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// List every function scripts can call, for the API browser and hover docs
+#[tauri::command]
+fn get_api_reference(app_handle: AppHandle) -> Vec<ApiFunction> {
+    api_reference(&app_handle.plugin_functions())
+}
+
+/// List the functions, variables and constants a script defines
+///
+/// Only parses the script, so it's safe to call while the user is typing.
+/// Fails with the parse error if the script doesn't compile.
+#[tauri::command]
+fn get_script_symbols(code: String) -> Result<Vec<ScriptSymbol>, String> {
+    let mut engine = fidget::rhai::engine();
+    // The optimizer would inline constants and drop their declarations
+    engine.set_optimization_level(rhai::OptimizationLevel::None);
+    let ast = engine.compile(&code).map_err(|e| e.to_string())?;
+    Ok(script_symbols(&ast, &code))
+}
+
+/// Evaluate a snippet in the document's console, returning what it printed and its value
+///
+/// Variables and functions the snippet defines stay available to later
+/// snippets until `reset_console`.  The modeling library is available, but
+/// not the document-level functions such as `draw` and `param`, and
+/// nothing the document's script defines.
+#[tauri::command]
+async fn eval_expression(
+    window: Window,
+    console: State<'_, ConsoleState>,
+    document_id: Option<String>,
+    document_path: Option<String>,
+    code: String,
+) -> Result<ConsoleOutput, String> {
+    let host = WindowHost::new(&window);
+    let session = console.session(&host.document_key(document_id.as_deref()));
+    let source = ScriptSource::new(code, document_path.as_deref());
+    tauri::async_runtime::spawn_blocking(move || {
+        let (mut engine, _) = modeling_engine(&host, source.base_dir.as_deref(), &CancelToken::new());
+        let printed = Arc::new(Mutex::new(Vec::new()));
+        let sink = printed.clone();
+        engine.on_print(move |text| sink.lock().unwrap_or_else(|e| e.into_inner()).push(text.to_string()));
+        let sink = printed.clone();
+        engine.on_debug(move |text, _source, _pos| {
+            sink.lock().unwrap_or_else(|e| e.into_inner()).push(text.to_string())
+        });
+
+        let evaluated = eval_snippet(&engine, &mut session.lock().unwrap_or_else(|e| e.into_inner()), &source.code);
+        let printed = std::mem::take(&mut *printed.lock().unwrap_or_else(|e| e.into_inner()));
+        let mut output = ConsoleOutput { printed, ..Default::default() };
+        match evaluated {
+            Ok(value) if value.is_unit() => {}
+            Ok(value) => {
+                output.type_name = Some(value.type_name().to_string());
+                output.value = Some(value.to_string());
+            }
+            Err(e) => output.error = Some(e.to_string()),
+        }
+        output
+    })
+    .await
+    .map_err(|e| format!("Console worker failed: {}", e))
+}
+
+/// Forget the variables and functions defined in the document's console
+#[tauri::command]
+fn reset_console(window: Window, console: State<'_, ConsoleState>, document_id: Option<String>) {
+    console.reset(&WindowHost::new(&window).document_key(document_id.as_deref()));
+}
+
+/// Log what a scan of the extensions folder found
+fn log_extensions(host: &dyn Host, found: &[ExtensionInfo]) {
+    for extension in found {
+        if let Some(error) = &extension.error {
+            let message = format!("Extension '{}' has an error: {}", extension.name, error);
+            emit_log(host, "warn", &message, Some("Compiler"));
+        }
+    }
+    if !found.is_empty() {
+        emit_log(host, "info", &format!("Loaded {} extension(s)", found.len()), Some("Compiler"));
+    }
+}
+
+/// Scan the extensions folder again, returning the extensions scripts can now import
+///
+/// Run this after adding or removing files; edits to an extension that
+/// was already found apply on the next compile without it.
+#[tauri::command]
+fn reload_extensions(app_handle: AppHandle, extensions: State<'_, Extensions>) -> Vec<ExtensionInfo> {
+    let found = extensions.reload();
+    log_extensions(&app_handle, &found);
+    if found.is_empty() {
+        if let Some(dir) = extensions.dir() {
+            emit_log(&app_handle, "info", &format!("No extensions in {}", dir.display()), Some("Compiler"));
+        }
+    }
+    found
+}
+
+/// Materials the print profile and `analyze_mesh` can pick, with their densities and prices
+#[tauri::command]
+fn list_material_presets() -> Vec<MaterialPreset> {
+    Material::ALL.map(Material::preset).to_vec()
+}
+
+/// The reviewed libraries `install_library` can install by name
+///
+/// None have been added to the index yet, so this is empty for now.
+#[tauri::command]
+fn list_libraries() -> Result<Vec<LibraryEntry>, String> {
+    library_index().map(|index| index.libraries).map_err(|e| e.to_string())
+}
+
+/// Download a library into the extensions folder and make it importable
+///
+/// `url_or_name` is a library from `list_libraries`, or an `https://` URL
+/// of a `.rhai` file or `.zip` bundle of them, which needs its `sha256`.
+/// Returns the extensions it installed.
+#[tauri::command]
+async fn install_library(
+    app_handle: AppHandle,
+    extensions: State<'_, Extensions>,
+    url_or_name: String,
+    sha256: Option<String>,
+) -> Result<Vec<ExtensionInfo>, String> {
+    let dir = extensions.dir().map(Path::to_path_buf).ok_or("There's no extensions folder to install into")?;
+    let source = url_or_name.clone();
+    let written = tauri::async_runtime::spawn_blocking(move || {
+        script::packages::install_library(&dir, &url_or_name, sha256.as_deref())
+    })
+    .await
+    .map_err(|e| format!("Library install failed: {}", e))?
+    .map_err(|e| {
+        let error_msg = format!("Failed to install {}: {:#}", source, e);
+        emit_log(&app_handle, "error", &error_msg, Some("Compiler"));
+        error_msg
+    })?;
+
+    let found = extensions.reload();
+    let installed: Vec<ExtensionInfo> =
+        found.into_iter().filter(|info| written.iter().any(|path| Path::new(&info.path) == path)).collect();
+    let names: Vec<&str> = installed.iter().map(|info| info.name.as_str()).collect();
+    let message = format!("Installed {}: {}", source, names.join(", "));
+    emit_log(&app_handle, "info", &message, Some("Compiler"));
+    Ok(installed)
+}
+
+fn log_plugins(host: &dyn Host, found: &[PluginInfo]) {
+    for plugin in found {
+        match &plugin.error {
+            Some(error) => {
+                emit_log(host, "warn", &format!("Plugin {} failed to load: {}", plugin.path, error), Some("System"))
+            }
+            None => {
+                let message = format!(
+                    "Loaded plugin {} with {} function(s) and {} exporter(s)",
+                    plugin.path,
+                    plugin.functions.len(),
+                    plugin.exporters.len()
+                );
+                emit_log(host, "info", &message, Some("System"));
+            }
+        }
+    }
+}
+
+/// List the native plugins found at startup, with what each registered
+#[tauri::command]
+fn list_plugins(plugins: State<'_, Plugins>) -> Vec<PluginInfo> {
+    plugins.found().to_vec()
+}
+
+/// Export the mesh from a document's last compile with an exporter registered by a plugin
+///
+/// The mesh is handed over in model coordinates and units, moved back from
+/// the unit cube if it was meshed there, without the other processing the
+/// built-in exporters apply.
+#[tauri::command]
+fn export_with_plugin(
+    window: Window,
+    mesh_cache: State<'_, MeshCache>,
+    plugins: State<'_, Plugins>,
+    document_id: Option<String>,
+    exporter: String,
+    path: String,
+) -> Result<bool, String> {
+    let host = WindowHost::new(&window);
+    let Some(exporter) = plugins.exporter(&exporter) else {
+        let error_msg = format!("No plugin provides an exporter named '{}'", exporter);
+        emit_log(&host, "error", &error_msg, Some("Export"));
+        return Err(error_msg);
+    };
+    let Some(last) = mesh_cache.get(&host.document_key(document_id.as_deref())) else {
+        let error_msg = "Nothing has been compiled yet to export".to_string();
+        emit_log(&host, "error", &error_msg, Some("Export"));
+        return Err(error_msg);
+    };
+    let model = last.units.model_mesh(&last.mesh);
+    let data = exporter.export(model.as_ref().unwrap_or(&last.mesh)).map_err(|e| {
+        let error_msg = format!("{} export failed: {}", exporter.name, e);
+        emit_log(&host, "error", &error_msg, Some("Export"));
+        error_msg
+    })?;
+    write_export_file(&host, &path, &data, &exporter.name)
+}
+
+/// List the bundled example scripts, for File → New From Template
+#[tauri::command]
+fn list_templates() -> Vec<TemplateInfo> {
+    templates()
+}
+
+/// The script of a bundled template, to start a new untitled document from
+#[tauri::command]
+fn load_template(id: String) -> Result<String, String> {
+    template_script(&id).ok_or_else(|| format!("There's no template called '{}'", id))
+}
+
+/// Re-indent and re-space a script, for Format Document
+///
+/// See `script::format::format_rhai`.  Fails without changing anything
+/// if the script parsed before formatting but not after, which would be a
+/// formatter bug.
+#[tauri::command]
+fn format_script(app_handle: AppHandle, code: String) -> Result<String, String> {
+    let formatted = format_rhai(&code);
+    let engine = fidget::rhai::engine();
+    if engine.compile(&code).is_ok() {
+        if let Err(e) = engine.compile(&formatted) {
+            let error_msg = format!("Formatting would break the script ({}); it was left unchanged", e);
+            emit_log(&app_handle, "error", &error_msg, Some("Compiler"));
+            return Err(error_msg);
+        }
+    }
+    Ok(formatted)
+}
+
+/// Translate an OpenSCAD file into a script, for opening as a new untitled document
+///
+/// See `script::openscad::translate_openscad`.  Each construct that
+/// couldn't be carried over is logged as a warning.
+#[tauri::command]
+async fn import_openscad(app_handle: AppHandle, path: String) -> Result<OpenScadImport, String> {
+    let source = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let import = translate_openscad(&source).map_err(|e| {
+        let error_msg = format!("Failed to import {}: {}", path, e);
+        emit_log(&app_handle, "error", &error_msg, Some("Compiler"));
+        error_msg
+    })?;
+    for warning in &import.warnings {
+        emit_log(&app_handle, "warn", &format!("OpenSCAD import: {}", warning), Some("Compiler"));
+    }
+    emit_log(&app_handle, "info", &format!("Imported {}", path), Some("System"));
+    Ok(import)
+}
+
+/// Mesh each `draw_named` part separately and write `<name>.stl` into `folder`
+///
+/// Each part gets its own meshing region, so small parts aren't meshed at the
+/// resolution of the whole scene.  Unnamed `draw()` shapes are skipped, and
+/// parts whose names make the same file name get `-2`, `-3` and so on.
+/// `target_triangles` and `max_error` apply to each part separately, and
+/// `repair` runs `repair_mesh` on each part before encoding it.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn export_parts(
+    window: Window,
+    compile_state: State<'_, CompileState>,
+    mesh_options: State<'_, MeshOptionsState>,
+    document_id: Option<String>,
+    document_path: Option<String>,
+    code: String,
+    folder: String,
+    depth: u8,
+    quality: Option<QualityPreset>,
+    stl_format: Option<StlFormat>,
+    params: Option<HashMap<String, f64>>,
+    backend: Option<EvalBackend>,
+    target_triangles: Option<usize>,
+    max_error: Option<f32>,
+    repair: Option<bool>,
+) -> Result<PartsExportResult, String> {
+    let source = ScriptSource::new(code, document_path.as_deref());
+    let params = params.unwrap_or_default();
+    let request = MeshRequest {
+        quality: MeshQuality::resolve(depth, quality),
+        center: [0.0, 0.0, 0.0],
+        auto_bounds: true,
+        backend: backend.unwrap_or(mesh_options.get().backend),
+        simplify: SimplifyOptions { target_triangles, max_error },
+        advanced: AdvancedMeshOptions::default(),
+    };
+    let stl_format = stl_format.unwrap_or_default();
+    let repair = repair.unwrap_or(false);
+    let up_axis = export_up_axis(window.app_handle(), ExportFormat::Stl);
+    let script_name =
+        document_path.as_deref().and_then(|p| Path::new(p).file_name()).map(|n| n.to_string_lossy().into_owned());
+
+    // Queued separately from previews, so exporting doesn't cancel them
+    let host = WindowHost::new(&window);
+    let document_id = host.document_key(document_id.as_deref());
+    let ticket = compile_state.enqueue(&format!("{}:export_parts", document_id));
+    let turn = ticket.wait_turn().await;
+
+    let worker_handle = host.clone();
+    let cancel = ticket.cancel.clone();
+    let folder_path = std::path::PathBuf::from(&folder);
+    let exported = tauri::async_runtime::spawn_blocking(move || -> Result<Vec<PartExport>, String> {
+        let mut timings = CompileTimings::default();
+        let script =
+            run_script(&worker_handle, &document_id, &source, &params, &cancel, &mut timings).map_err(|e| e.message)?;
+        let encoded = encode_parts(
+            &worker_handle,
+            &script,
+            &request,
+            &PartEncoding { stl_format, repair, up_axis, script_name: script_name.as_deref() },
+            &cancel,
+            &mut timings,
+        )?;
+        let mut parts = Vec::with_capacity(encoded.len());
+        for part in encoded {
+            let path = folder_path.join(&part.file).to_string_lossy().into_owned();
+            write_export_file(&worker_handle, &path, &part.data, "STL")?;
+            parts.push(PartExport { name: part.name, path, triangle_count: part.triangle_count });
+        }
+        Ok(parts)
+    })
+    .await;
+    drop(turn);
+    compile_state.finish(&ticket);
+
+    match exported.map_err(|e| format!("Export worker failed: {}", e))? {
+        Ok(parts) => {
+            emit_log(&host, "info", &format!("Exported {} parts to {}", parts.len(), folder), Some("Export"));
+            Ok(PartsExportResult { success: true, parts, ..Default::default() })
+        }
+        Err(error_msg) => Ok(PartsExportResult {
+            cancelled: ticket.cancel.is_cancelled(),
+            error: Some(error_msg),
+            ..Default::default()
+        }),
+    }
+}
+
+/// How `encode_parts` writes each part
+struct PartEncoding<'a> {
+    stl_format: StlFormat,
+    repair: bool,
+    up_axis: UpAxis,
+    script_name: Option<&'a str>,
+}
+
+/// A named part meshed and encoded as STL by `encode_parts`
+struct EncodedPart {
+    name: String,
+    /// File name for the part, `<name>.stl` made safe for every platform and unique among the parts
+    file: String,
+    data: Vec<u8>,
+    triangle_count: usize,
+    vertex_count: usize,
+    measurements: MeshMeasurements,
+    color: Option<Rgb>,
+}
+
+/// Mesh each `draw_named` part of `script` separately and encode it as STL, in millimeters
+///
+/// Shared by `export_parts` and `export_archive`.  Fails if the script has
+/// no named parts.
+fn encode_parts(
+    host: &dyn Host,
+    script: &ScriptOutput,
+    request: &MeshRequest,
+    encoding: &PartEncoding,
+    cancel: &CancelToken,
+    timings: &mut CompileTimings,
+) -> Result<Vec<EncodedPart>, String> {
+    let named: Vec<_> = script.shapes.iter().filter(|s| s.name.is_some()).collect();
+    if named.is_empty() {
+        let error_msg = "Script has no draw_named(name, tree) parts to export".to_string();
+        emit_log(host, "error", &error_msg, Some("Export"));
+        return Err(error_msg);
+    }
+
+    let mut parts = Vec::with_capacity(named.len());
+    let mut stems = HashSet::new();
+    for shape in named {
+        let name = shape.name.clone().unwrap_or_default();
+        emit_log(host, "info", &format!("Meshing part '{}'", name), Some("Mesh"));
+        let mut meshed = mesh_node(host, script, shape.node, request, cancel, timings)?;
+        if encoding.repair {
+            let repaired = repair_mesh(&meshed.mesh);
+            emit_log(host, "info", &repaired.report.summary(), Some("Export"));
+            meshed.mesh = repaired.mesh;
+        }
+        let units = MeshUnits { unit: script.unit, to_model: meshed.transform };
+        let mesh = match units.export_unit(ExportFormat::Stl, None) {
+            Some(unit) => units.convert(&meshed.mesh, unit),
+            None => meshed.mesh,
+        };
+        let mesh = encoding.up_axis.orient(mesh);
+        let mut data = export_mesh_to_stl(&mesh, encoding.stl_format, &name, encoding.script_name).map_err(|e| {
+            let error_msg = format!("STL export of part '{}' failed: {}", name, e);
+            emit_log(host, "error", &error_msg, Some("Export"));
+            error_msg
+        })?;
+        if let (Some(color), StlFormat::Binary) = (shape.color, encoding.stl_format) {
+            apply_stl_colors(&mut data, &vec![Some(color); mesh.triangles.len()]);
+        }
+        parts.push(EncodedPart {
+            file: format!("{}.stl", unique_file_stem(&name, &mut stems)),
+            name,
+            data,
+            triangle_count: mesh.triangles.len(),
+            vertex_count: mesh.vertices.len(),
+            measurements: mesh::measure::measure_mesh(&mesh),
+            color: shape.color,
+        });
+    }
+    Ok(parts)
+}
+
+/// Mesh each `draw_named` part separately and pack them into one ZIP at `path`
+///
+/// The archive holds `<name>.stl` for each part, built as `export_parts`
+/// builds them, and a `manifest.json` listing every part with its triangle
+/// count, volume and bounds, and the settings used.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn export_archive(
+    window: Window,
+    compile_state: State<'_, CompileState>,
+    mesh_options: State<'_, MeshOptionsState>,
+    document_id: Option<String>,
+    document_path: Option<String>,
+    code: String,
+    path: String,
+    depth: u8,
+    quality: Option<QualityPreset>,
+    stl_format: Option<StlFormat>,
+    params: Option<HashMap<String, f64>>,
+    backend: Option<EvalBackend>,
+    target_triangles: Option<usize>,
+    max_error: Option<f32>,
+    repair: Option<bool>,
+) -> Result<PartsExportResult, String> {
+    let source = ScriptSource::new(code, document_path.as_deref());
+    let params = params.unwrap_or_default();
+    let request = MeshRequest {
+        quality: MeshQuality::resolve(depth, quality),
+        center: [0.0, 0.0, 0.0],
+        auto_bounds: true,
+        backend: backend.unwrap_or(mesh_options.get().backend),
+        simplify: SimplifyOptions { target_triangles, max_error },
+        advanced: AdvancedMeshOptions::default(),
+    };
+    let stl_format = stl_format.unwrap_or_default();
+    let repair = repair.unwrap_or(false);
+    let up_axis = export_up_axis(window.app_handle(), ExportFormat::Stl);
+    let script_name =
+        document_path.as_deref().and_then(|p| Path::new(p).file_name()).map(|n| n.to_string_lossy().into_owned());
+
+    // Queued apart from previews and the other exports, so none cancels another
+    let host = WindowHost::new(&window);
+    let document_id = host.document_key(document_id.as_deref());
+    let ticket = compile_state.enqueue(&format!("{}:export_archive", document_id));
+    let turn = ticket.wait_turn().await;
+
+    let worker_handle = host.clone();
+    let cancel = ticket.cancel.clone();
+    let archive_path = path.clone();
+    let exported = tauri::async_runtime::spawn_blocking(move || -> Result<Vec<PartExport>, String> {
+        let mut timings = CompileTimings::default();
+        let script =
+            run_script(&worker_handle, &document_id, &source, &params, &cancel, &mut timings).map_err(|e| e.message)?;
+        let encoded = encode_parts(
+            &worker_handle,
+            &script,
+            &request,
+            &PartEncoding { stl_format, repair, up_axis, script_name: script_name.as_deref() },
+            &cancel,
+            &mut timings,
+        )?;
+
+        let manifest = ArchiveManifest {
+            generator: format!("horseCAD {}", env!("CARGO_PKG_VERSION")),
+            script_name,
+            // STL has no unit field, so parts are always written in millimeters
+            unit: ModelUnit::Millimeter,
+            settings: ArchiveSettings {
+                depth: request.quality.depth,
+                stl_format,
+                up_axis,
+                repair,
+                target_triangles,
+                max_error,
+                params: params.into_iter().collect(),
+            },
+            parts: encoded
+                .iter()
+                .map(|part| ArchivePart {
+                    name: part.name.clone(),
+                    file: part.file.clone(),
+                    triangle_count: part.triangle_count,
+                    vertex_count: part.vertex_count,
+                    volume: part.measurements.volume,
+                    surface_area: part.measurements.surface_area,
+                    bounds: part.measurements.bounds,
+                    color: part.color.map(to_hex),
+                })
+                .collect(),
+        };
+        let files: Vec<_> = encoded.iter().map(|part| (part.file.clone(), part.data.clone())).collect();
+        let data = export_parts_archive(&files, &manifest).map_err(|e| {
+            let error_msg = format!("Parts archive export failed: {}", e);
+            emit_log(&worker_handle, "error", &error_msg, Some("Export"));
+            error_msg
+        })?;
+        write_export_file(&worker_handle, &archive_path, &data, "parts archive")?;
+        Ok(encoded
+            .into_iter()
+            .map(|part| PartExport { name: part.name, path: part.file, triangle_count: part.triangle_count })
+            .collect())
+    })
+    .await;
+    drop(turn);
+    compile_state.finish(&ticket);
+
+    match exported.map_err(|e| format!("Export worker failed: {}", e))? {
+        Ok(parts) => {
+            emit_log(&host, "info", &format!("Exported {} parts to {}", parts.len(), path), Some("Export"));
+            Ok(PartsExportResult { success: true, parts, ..Default::default() })
+        }
+        Err(error_msg) => Ok(PartsExportResult {
+            cancelled: ticket.cancel.is_cancelled(),
+            error: Some(error_msg),
+            ..Default::default()
+        }),
+    }
+}
+
+/// Mesh each `material()` region separately and write them as the objects of one 3MF at `path`
+///
+/// Regions never overlap: where tagged shapes do, the later `material()`
+/// call wins, and whatever no call covers is exported as the "Default"
+/// material if any of it is left.  Every region is meshed over the same
+/// box as the whole model, so the objects line up when a slicer loads
+/// them.  Vertices are in the script's `set_units` unit, or millimeters.
+/// Each material is listed as a part whose `path` is the 3MF file.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn export_materials(
+    window: Window,
+    compile_state: State<'_, CompileState>,
+    mesh_options: State<'_, MeshOptionsState>,
+    document_id: Option<String>,
+    document_path: Option<String>,
+    code: String,
+    path: String,
+    depth: u8,
+    quality: Option<QualityPreset>,
+    params: Option<HashMap<String, f64>>,
+    backend: Option<EvalBackend>,
+    repair: Option<bool>,
+) -> Result<PartsExportResult, String> {
+    let source = ScriptSource::new(code, document_path.as_deref());
+    let params = params.unwrap_or_default();
+    let mut request = MeshRequest {
+        quality: MeshQuality::resolve(depth, quality),
+        center: [0.0, 0.0, 0.0],
+        auto_bounds: true,
+        backend: backend.unwrap_or(mesh_options.get().backend),
+        simplify: SimplifyOptions::default(),
+        advanced: AdvancedMeshOptions::default(),
+    };
+    let repair = repair.unwrap_or(false);
+    let up_axis = export_up_axis(window.app_handle(), ExportFormat::ThreeMf);
+    let script_name =
+        document_path.as_deref().and_then(|p| Path::new(p).file_name()).map(|n| n.to_string_lossy().into_owned());
+
+    // Queued apart from previews and the other exports, so none cancels another
+    let host = WindowHost::new(&window);
+    let document_id = host.document_key(document_id.as_deref());
+    let ticket = compile_state.enqueue(&format!("{}:export_materials", document_id));
+    let turn = ticket.wait_turn().await;
+
+    let worker_handle = host.clone();
+    let cancel = ticket.cancel.clone();
+    let file_path = path.clone();
+    let exported = tauri::async_runtime::spawn_blocking(move || -> Result<Vec<PartExport>, String> {
+        let mut timings = CompileTimings::default();
+        let script =
+            run_script(&worker_handle, &document_id, &source, &params, &cancel, &mut timings).map_err(|e| e.message)?;
+        if script.materials.is_empty() {
+            let error_msg = "Script has no material(tree, name) calls to export".to_string();
+            emit_log(&worker_handle, "error", &error_msg, Some("Export"));
+            return Err(error_msg);
+        }
+        // Fit the whole model once, so every region is meshed on the same grid
+        if request.auto_bounds && script.bounds.is_none() && script.scale.is_none() {
+            let shape = VmShape::new(&script.ctx, script.root).map_err(|e| format!("Shape creation failed: {}", e))?;
+            request.advanced.bounds = detect_bounds(&shape).ok().flatten().filter(|b| b.half_extent() > 0.0);
+        }
+
+        let units = MeshUnits { unit: script.unit, to_model: None };
+        let unit = units.export_unit(ExportFormat::ThreeMf, None);
+        let mut meshes = Vec::with_capacity(script.materials.len());
+        for region in &script.materials {
+            emit_log(&worker_handle, "info", &format!("Meshing material '{}'", region.name), Some("Mesh"));
+            let mut meshed = mesh_node(&worker_handle, &script, region.node, &request, &cancel, &mut timings)?;
+            if meshed.mesh.triangles.is_empty() {
+                continue;
+            }
+            if repair {
+                let repaired = repair_mesh(&meshed.mesh);
+                emit_log(&worker_handle, "info", &repaired.report.summary(), Some("Export"));
+                meshed.mesh = repaired.mesh;
+            }
+            let units = MeshUnits { unit: script.unit, to_model: meshed.transform };
+            let mesh = match unit {
+                Some(unit) => units.convert(&meshed.mesh, unit),
+                None => meshed.mesh,
+            };
+            meshes.push((region.name.as_str(), up_axis.orient(mesh)));
+        }
+        if meshes.is_empty() {
+            let error_msg = "Every material region is empty".to_string();
+            emit_log(&worker_handle, "error", &error_msg, Some("Export"));
+            return Err(error_msg);
+        }
+
+        let objects: Vec<MaterialObject> = meshes
+            .iter()
+            .enumerate()
+            .map(|(i, (material, mesh))| MaterialObject {
+                material,
+                mesh,
+                color: if *material == DEFAULT_MATERIAL {
+                    DEFAULT_COLOR
+                } else {
+                    MATERIAL_COLORS[i % MATERIAL_COLORS.len()]
+                },
+            })
+            .collect();
+        let data = export_materials_to_3mf(&objects, unit.unwrap_or_default(), script_name.as_deref()).map_err(|e| {
+            let error_msg = format!("Multi-material 3MF export failed: {}", e);
+            emit_log(&worker_handle, "error", &error_msg, Some("Export"));
+            error_msg
+        })?;
+        write_export_file(&worker_handle, &file_path, &data, "3MF")?;
+        Ok(meshes
+            .iter()
+            .map(|(material, mesh)| PartExport {
+                name: material.to_string(),
+                path: file_path.clone(),
+                triangle_count: mesh.triangles.len(),
+            })
+            .collect())
+    })
+    .await;
+    drop(turn);
+    compile_state.finish(&ticket);
+
+    match exported.map_err(|e| format!("Export worker failed: {}", e))? {
+        Ok(parts) => {
+            emit_log(&host, "info", &format!("Exported {} materials to {}", parts.len(), path), Some("Export"));
+            Ok(PartsExportResult { success: true, parts, ..Default::default() })
+        }
+        Err(error_msg) => Ok(PartsExportResult {
+            cancelled: ticket.cancel.is_cancelled(),
+            error: Some(error_msg),
+            ..Default::default()
+        }),
+    }
+}
+
+/// Build every `.horsi` script in `folder` and write their STLs into `output_folder`
+///
+/// `settings` apply to every script, and `file_settings` override them per
+/// script, keyed by file name; the backend defaults to the one in the mesh
+/// options.  A `batch_progress` event is emitted as each script starts, and
+/// cancelling the `batch_export` document stops after the current script.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn batch_export(
+    app_handle: AppHandle,
+    compile_state: State<'_, CompileState>,
+    mesh_options: State<'_, MeshOptionsState>,
+    folder: String,
+    output_folder: String,
+    settings: Option<BuildSettings>,
+    file_settings: Option<HashMap<String, BuildSettings>>,
+    stl_format: Option<StlFormat>,
+) -> Result<BatchExportResult, String> {
+    let mut defaults = settings.unwrap_or_default();
+    defaults.backend = defaults.backend.or(Some(mesh_options.get().backend));
+    defaults.up_axis = defaults.up_axis.or(Some(app_handle.state::<SettingsStore>().get().export_up_axis));
+    let file_settings = file_settings.unwrap_or_default();
+    let stl_format = stl_format.unwrap_or_default();
+
+    let ticket = compile_state.enqueue("batch_export");
+    let turn = ticket.wait_turn().await;
+
+    let worker_handle = app_handle.clone();
+    let cancel = ticket.cancel.clone();
+    let exported = tauri::async_runtime::spawn_blocking(move || {
+        let on_progress = |progress: &BatchProgress| {
+            if let Err(e) = worker_handle.emit("batch_progress", progress) {
+                eprintln!("Failed to emit batch_progress event: {}", e);
+            }
+        };
+        batch_export_folder(
+            &worker_handle,
+            Path::new(&folder),
+            Path::new(&output_folder),
+            &defaults,
+            &file_settings,
+            stl_format,
+            &cancel,
+            &on_progress,
+        )
+    })
+    .await;
+    drop(turn);
+    compile_state.finish(&ticket);
+
+    exported.map_err(|e| format!("Export worker failed: {}", e))?
+}
+
+/// One export for the background queue, compiled from its own copy of the script
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExportJob {
+    pub document_id: Option<String>,
+    pub document_path: Option<String>,
+    pub code: String,
+    pub format: ExportFormat,
+    pub path: String,
+    /// Name of a `draw_named` part to export alone, instead of the whole model
+    pub part: Option<String>,
+    /// Over the configured backend and up axis, and under settings pinned to the script
+    #[serde(default)]
+    pub settings: BuildSettings,
+}
+
+/// Queue exports to run one at a time in the background, returning once they're queued
+///
+/// Each job is built and written as the CLI does, without touching the
+/// document's preview.  An `export_job` event carrying an `ExportJobInfo`
+/// is emitted as each starts and finishes; `cancel_export` stops them.
+#[tauri::command]
+async fn queue_exports(
+    window: Window,
+    export_queue: State<'_, ExportQueue>,
+    jobs: Vec<ExportJob>,
+) -> Result<Vec<ExportJobInfo>, String> {
+    let (queued, start) = export_queue.push(jobs);
+    emit_log(window.app_handle(), "info", &format!("Queued {} exports", queued.len()), Some("Export"));
+    if start {
+        tauri::async_runtime::spawn(run_export_queue(window));
+    }
+    Ok(queued)
+}
+
+/// Cancel the queued or running export `id`, or every export
+///
+/// Returns false if there was nothing to cancel.
+#[tauri::command]
+async fn cancel_export(
+    app_handle: AppHandle,
+    export_queue: State<'_, ExportQueue>,
+    id: Option<u64>,
+) -> Result<bool, String> {
+    let (removed, running) = export_queue.cancel(id);
+    for info in &removed {
+        emit_export_job(&app_handle, info);
+    }
+    if running || !removed.is_empty() {
+        emit_log(&app_handle, "warn", "Export cancelled", Some("Export"));
+    }
+    Ok(running || !removed.is_empty())
+}
+
+/// The running export, then the queued ones in the order they'll run
+#[tauri::command]
+async fn list_exports(export_queue: State<'_, ExportQueue>) -> Result<Vec<ExportJobInfo>, String> {
+    Ok(export_queue.list())
+}
+
+fn emit_export_job(app: &AppHandle, info: &ExportJobInfo) {
+    if let Err(e) = app.emit("export_job", info) {
+        eprintln!("Failed to emit export_job event: {}", e);
+    }
+}
+
+/// Run queued exports until the queue is empty
+async fn run_export_queue(window: Window) {
+    let host = WindowHost::new(&window);
+    let app = window.app_handle().clone();
+    let export_queue = app.state::<ExportQueue>();
+    while let Some(export) = export_queue.next() {
+        emit_export_job(&app, &export.info(ExportJobStatus::Running));
+        let worker_handle = host.clone();
+        let job = export.job.clone();
+        let cancel = export.cancel.clone();
+        let exported =
+            tauri::async_runtime::spawn_blocking(move || run_export_job(&worker_handle, &job, &cancel)).await;
+        let mut info = export.info(ExportJobStatus::Done);
+        match exported.map_err(|e| format!("Export worker failed: {}", e)).and_then(|exported| exported) {
+            Ok(triangle_count) => info.triangle_count = Some(triangle_count),
+            Err(error_msg) => {
+                let cancelled = export.cancel.is_cancelled();
+                info.status = if cancelled { ExportJobStatus::Cancelled } else { ExportJobStatus::Failed };
+                info.error = Some(error_msg);
+            }
+        }
+        emit_export_job(&app, &info);
+    }
+}
+
+/// Build and write one queued export, returning the triangles written
+fn run_export_job(host: &WindowHost, job: &ExportJob, cancel: &CancelToken) -> Result<usize, String> {
+    let defaults = BuildSettings {
+        backend: Some(host.app().state::<MeshOptionsState>().get().backend),
+        up_axis: Some(host.app().state::<SettingsStore>().get().export_up_axis),
+        ..Default::default()
+    };
+    let (settings, project) = with_project_settings(host, job.document_path.as_deref(), job.settings.or(&defaults));
+    let source = ScriptSource::new(job.code.clone(), job.document_path.as_deref());
+    let options = ExportOptions {
+        name: job.part.clone(),
+        unit: project.and_then(|project| project.unit),
+        script_name: job
+            .document_path
+            .as_deref()
+            .and_then(|p| Path::new(p).file_name())
+            .map(|n| n.to_string_lossy().into_owned()),
+        ..Default::default()
+    };
+    // Cached apart from the document, so an export doesn't replace its preview's script
+    let document_id = format!("{}:export_queue", host.document_key(job.document_id.as_deref()));
+    let mut timings = CompileTimings::default();
+    let model = build_model(
+        host,
+        &document_id,
+        &source,
+        &settings.params,
+        &settings.mesh_request(),
+        settings.repair.unwrap_or(false),
+        settings.up_axis.unwrap_or_default(),
+        job.format,
+        &options,
+        job.part.as_deref(),
+        cancel,
+        &mut timings,
+    )
+    .map_err(|e| e.message)?;
+    write_export_file(host, &job.path, &model.data, job.format.label())?;
+    Ok(model.triangle_count)
+}
+
+/// Mesh the script and find where its walls are thinner than `min_thickness`
+///
+/// The distance field is sampled inwards from each vertex of the mesh (see
+/// `thin_regions`), so `depth` and `quality` set how finely the surface is
+/// checked.  Queued separately from previews, like `export_parts`.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn check_wall_thickness(
+    window: Window,
+    compile_state: State<'_, CompileState>,
+    mesh_options: State<'_, MeshOptionsState>,
+    document_id: Option<String>,
+    document_path: Option<String>,
+    code: String,
+    depth: u8,
+    quality: Option<QualityPreset>,
+    params: Option<HashMap<String, f64>>,
+    min_thickness: f32,
+) -> Result<ThicknessReport, String> {
+    if min_thickness.is_nan() || min_thickness <= 0.0 {
+        return Err("Minimum thickness must be greater than zero".to_string());
+    }
+    let source = ScriptSource::new(code, document_path.as_deref());
+    let params = params.unwrap_or_default();
+    let request = MeshRequest {
+        quality: MeshQuality::resolve(depth, quality),
+        center: [0.0, 0.0, 0.0],
+        auto_bounds: true,
+        backend: mesh_options.get().backend,
+        simplify: SimplifyOptions::default(),
+        advanced: AdvancedMeshOptions::default(),
+    };
+
+    let host = WindowHost::new(&window);
+    let document_id = host.document_key(document_id.as_deref());
+    let ticket = compile_state.enqueue(&format!("{}:wall_thickness", document_id));
+    let turn = ticket.wait_turn().await;
+
+    let worker_handle = host.clone();
+    let cancel = ticket.cancel.clone();
+    let checked = tauri::async_runtime::spawn_blocking(move || -> Result<ThicknessReport, String> {
+        let mut timings = CompileTimings::default();
+        let script =
+            run_script(&worker_handle, &document_id, &source, &params, &cancel, &mut timings).map_err(|e| e.message)?;
+        let meshed = mesh_node(&worker_handle, &script, script.root, &request, &cancel, &mut timings)?;
+        let shape = mesh_space_shape(&script.ctx, script.root, meshed.transform)
+            .map_err(|e| format!("Shape creation failed: {}", e))?;
+        thin_regions(&meshed.mesh, &shape, min_thickness).map_err(|e| {
+            let error_msg = format!("Wall thickness check failed: {}", e);
+            emit_log(&worker_handle, "error", &error_msg, Some("Analysis"));
+            error_msg
+        })
+    })
+    .await;
+    drop(turn);
+    compile_state.finish(&ticket);
+
+    let report = checked.map_err(|e| format!("Analysis worker failed: {}", e))??;
+    let (level, message) = if report.regions.is_empty() {
+        ("info", format!("No walls thinner than {}", min_thickness))
+    } else {
+        (
+            "warn",
+            format!(
+                "Found {} regions thinner than {} ({} of {} vertices)",
+                report.regions.len() + report.omitted_regions,
+                min_thickness,
+                report.thin_vertices,
+                report.sampled
+            ),
+        )
+    };
+    emit_log(&host, level, &message, Some("Analysis"));
+    Ok(report)
+}
+
+/// Estimate the smallest wall or gap of the script's shape, and the depth that meshes it intact
+///
+/// Uses `code` if given, or else the document's last evaluated script, over
+/// the region a compile would mesh (see `feature_size`).  Warns when meshing
+/// at `depth`, or `quality`'s depth, would visibly lose the feature.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn suggest_depth(
+    window: Window,
+    compile_state: State<'_, CompileState>,
+    document_id: Option<String>,
+    document_path: Option<String>,
+    code: Option<String>,
+    depth: u8,
+    quality: Option<QualityPreset>,
+    params: Option<HashMap<String, f64>>,
+) -> Result<FeatureSizeReport, String> {
+    let source = code.map(|code| ScriptSource::new(code, document_path.as_deref()));
+    let params = params.unwrap_or_default();
+    let depth = MeshQuality::resolve(depth, quality).depth;
+
+    let host = WindowHost::new(&window);
+    let document_id = host.document_key(document_id.as_deref());
+    let ticket = compile_state.enqueue(&format!("{}:feature_size", document_id));
+    let turn = ticket.wait_turn().await;
+
+    let worker_handle = host.clone();
+    let cancel = ticket.cancel.clone();
+    let estimated = tauri::async_runtime::spawn_blocking(move || -> Result<FeatureSizeReport, String> {
+        let host = &worker_handle;
+        let script = script_for_render(host, &document_id, source, &params, &cancel)?;
+        check_cancelled(host, &cancel)?;
+        let shape = VmShape::new(&script.ctx, script.root).map_err(|e| format!("Shape creation failed: {}", e))?;
+        let region = script_region(&script, &shape).unwrap_or(Bounds { min: [-1.0; 3], max: [1.0; 3] });
+        // A script's scale sets the meshing cube exactly; bounds are padded
+        let margin = match (script.bounds, script.scale) {
+            (None, Some(_)) => 0.0,
+            _ => AdvancedMeshOptions::default().margin,
+        };
+        let report = feature_size(&shape, &region, margin, depth, &cancel).map_err(|e| {
+            let error_msg = format!("Feature size estimate failed: {}", e);
+            emit_log(host, "error", &error_msg, Some("Analysis"));
+            error_msg
+        })?;
+        check_cancelled(host, &cancel)?;
+        report.ok_or_else(|| "Feature size estimate cancelled".to_string())
+    })
+    .await;
+    drop(turn);
+    compile_state.finish(&ticket);
+
+    let report = estimated.map_err(|e| format!("Analysis worker failed: {}", e))??;
+    let (level, message) = match (report.min_feature, report.suggested_depth) {
+        (Some(feature), Some(suggested)) if report.loses_detail() => (
+            "warn",
+            format!(
+                "The smallest feature is {:.4} across, but cells at depth {} are {:.4}; use depth {}{} to keep it",
+                feature,
+                depth,
+                report.cell_size,
+                suggested,
+                if report.beyond_max_depth { " or more" } else { "" }
+            ),
+        ),
+        (Some(feature), Some(suggested)) => (
+            "info",
+            format!("The smallest feature is {:.4} across; depth {} is enough to resolve it", feature, suggested),
+        ),
+        _ => ("info", "Found no walls or gaps to size the depth by".to_string()),
+    };
+    emit_log(&host, level, &message, Some("Analysis"));
+    Ok(report)
+}
+
+/// Run `code` for rendering, or re-bind the document's last evaluated script if there is no code
+fn script_for_render(
+    host: &dyn Host,
+    document_id: &str,
+    source: Option<ScriptSource>,
+    params: &HashMap<String, f64>,
+    cancel: &CancelToken,
+) -> Result<ScriptOutput, String> {
+    match source {
+        Some(source) => {
+            let mut timings = CompileTimings::default();
+            run_script(host, document_id, &source, params, cancel, &mut timings).map_err(|e| e.message)
+        }
+        None => {
+            let cached = host.script_cache().latest(document_id).ok_or_else(|| {
+                let error_msg = "Nothing has been compiled yet".to_string();
+                emit_log(host, "error", &error_msg, Some("Render"));
+                error_msg
+            })?;
+            cached.bind(params).map_err(|e| format!("Parameter binding failed: {}", e))
+        }
+    }
+}
+
+/// Region a script's shape fills: the script's bounds, the region its scale sets, or else its detected extent
+fn script_region(script: &ScriptOutput, shape: &VmShape) -> Option<Bounds> {
+    match (script.bounds, script.scale) {
+        (Some(bounds), _) => Some(bounds),
+        (None, Some(scale)) => Some(Bounds { min: [-scale; 3], max: [scale; 3] }),
+        (None, None) => detect_bounds(shape).ok().flatten().filter(|b| b.half_extent() > 0.0),
+    }
+}
+
+/// Ray-cast a script's shape as `camera` sees it, straight from the distance field
+///
+/// The camera frames the script's region, as `script_region` finds it.
+fn render_script(
+    host: &dyn Host,
+    script: &ScriptOutput,
+    camera: &RenderCamera,
+    width: u32,
+    height: u32,
+    backend: EvalBackend,
+) -> Result<RgbaImage, String> {
+    let shape = VmShape::new(&script.ctx, script.root).map_err(|e| format!("Shape creation failed: {}", e))?;
+    let region = script_region(script, &shape);
+    render_region(host, script, shape, region, camera, width, height, backend)
+}
+
+/// Ray-cast a script's shape as `camera` sees it, framing `region`
+#[allow(clippy::too_many_arguments)]
+fn render_region(
+    host: &dyn Host,
+    script: &ScriptOutput,
+    shape: VmShape,
+    region: Option<Bounds>,
+    camera: &RenderCamera,
+    width: u32,
+    height: u32,
+    backend: EvalBackend,
+) -> Result<RgbaImage, String> {
+    let view = camera.view(region);
+    let pool = host.mesh_options().thread_pool()?;
+    let threads = Some(pool.as_ref());
+
+    let start = Instant::now();
+    let image = match backend {
+        #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+        EvalBackend::Jit if backend.is_available() => {
+            let jit = fidget::jit::JitShape::new(&script.ctx, script.root)
+                .map_err(|e| format!("JIT shape creation failed: {}", e))?;
+            render_shape(jit, view, width, height, camera.ambient_occlusion, threads)
+        }
+        _ => render_shape(shape, view, width, height, camera.ambient_occlusion, threads),
+    };
+    let image = image.ok_or_else(|| "Rendering cancelled".to_string())?;
+    emit_log(host, "info", &format!("Rendered {}x{} image in {} ms", width, height, elapsed_ms(start)), Some("Render"));
+    Ok(image)
+}
+
+/// Render a shaded PNG of a script's shape, without meshing it
+///
+/// The image is ray-cast from the distance field, so it's quick even for
+/// shapes that would take minutes to mesh finely.  Renders `code` if given,
+/// or else the document's last evaluated script.  `view` names a camera
+/// saved with `save_view`, used instead of `camera`.  `height` defaults to
+/// `width`.  The PNG is returned, and also written to `path` if given.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn render_image(
+    window: Window,
+    compile_state: State<'_, CompileState>,
+    mesh_options: State<'_, MeshOptionsState>,
+    document_id: Option<String>,
+    document_path: Option<String>,
+    code: Option<String>,
+    params: Option<HashMap<String, f64>>,
+    camera: Option<RenderCamera>,
+    view: Option<String>,
+    width: u32,
+    height: Option<u32>,
+    path: Option<String>,
+) -> Result<Response, String> {
+    let height = height.unwrap_or(width);
+    check_render_size(width, height)?;
+    let source = code.map(|code| ScriptSource::new(code, document_path.as_deref()));
+    let params = params.unwrap_or_default();
+    let camera = view_camera(document_path.as_deref(), camera, view.as_deref())?;
+    let backend = mesh_options.get().backend;
+
+    let host = WindowHost::new(&window);
+    let document_id = host.document_key(document_id.as_deref());
+    let ticket = compile_state.enqueue(&format!("{}:render", document_id));
+    let turn = ticket.wait_turn().await;
+
+    let worker_handle = host.clone();
+    let cancel = ticket.cancel.clone();
+    let rendered = tauri::async_runtime::spawn_blocking(move || -> Result<Vec<u8>, String> {
+        let script = script_for_render(&worker_handle, &document_id, source, &params, &cancel)?;
+        check_cancelled(&worker_handle, &cancel)?;
+        let image = render_script(&worker_handle, &script, &camera, width, height, backend).inspect_err(|e| {
+            emit_log(&worker_handle, "error", &format!("Render failed: {}", e), Some("Render"));
+        })?;
+        encode_png(&image)
+    })
+    .await;
+    drop(turn);
+    compile_state.finish(&ticket);
+
+    let png = rendered.map_err(|e| format!("Render worker failed: {}", e))??;
+    if let Some(path) = path {
+        write_export_file(&host, &path, &png, "PNG")?;
+    }
+    Ok(Response::new(png))
+}
+
+/// A dimension for `render_dimensions` to draw, between points picked as for `measure`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Dimension {
+    /// Two points for a length, or three for the angle at the second
+    pub points: Vec<[f32; 3]>,
+    /// Replaces the measured length or angle as the callout's text
+    pub label: Option<String>,
+}
+
+/// Render a PNG of a script's shape with dimension callouts drawn over it, for quick drawings
+///
+/// Each of `dimensions` is measured as `measure` measures its points, in
+/// viewport coordinates, and labelled with its length in the model's unit
+/// or its angle.  Other options match `render_image`.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn render_dimensions(
+    window: Window,
+    compile_state: State<'_, CompileState>,
+    mesh_options: State<'_, MeshOptionsState>,
+    mesh_cache: State<'_, MeshCache>,
+    document_id: Option<String>,
+    document_path: Option<String>,
+    code: Option<String>,
+    params: Option<HashMap<String, f64>>,
+    camera: Option<RenderCamera>,
+    view: Option<String>,
+    dimensions: Vec<Dimension>,
+    width: u32,
+    height: Option<u32>,
+    path: Option<String>,
+) -> Result<Response, String> {
+    let height = height.unwrap_or(width);
+    check_render_size(width, height)?;
+    let source = code.map(|code| ScriptSource::new(code, document_path.as_deref()));
+    let params = params.unwrap_or_default();
+    let camera = view_camera(document_path.as_deref(), camera, view.as_deref())?;
+    let backend = mesh_options.get().backend;
+
+    let host = WindowHost::new(&window);
+    let document_id = host.document_key(document_id.as_deref());
+    let last = mesh_cache.get(&document_id);
+    let to_model = last.as_ref().and_then(|last| last.units.to_model).unwrap_or_else(nalgebra::Matrix4::identity);
+    let unit = last.and_then(|last| last.units.unit).map_or(String::new(), |unit| format!(" {}", unit.symbol()));
+    let mut callouts = Vec::with_capacity(dimensions.len());
+    for dimension in dimensions {
+        if !dimension.points.iter().flatten().all(|v| v.is_finite()) {
+            return Err("Point coordinates must be finite".to_string());
+        }
+        let points: Vec<_> =
+            dimension.points.iter().map(|&p| to_model.transform_point(&nalgebra::Point3::from(p)).coords).collect();
+        let measured = measure_points(&points)?;
+        let label = dimension.label.unwrap_or_else(|| match measured.angle {
+            Some(angle) => format!("{:.1}°", angle),
+            None => format!("{:.2}{}", measured.distance, unit),
+        });
+        callouts.push(annotate::Callout { points: points.into_iter().map(Into::into).collect(), label });
+    }
+
+    let ticket = compile_state.enqueue(&format!("{}:render", document_id));
+    let turn = ticket.wait_turn().await;
+
+    let worker_handle = host.clone();
+    let cancel = ticket.cancel.clone();
+    let rendered = tauri::async_runtime::spawn_blocking(move || -> Result<Vec<u8>, String> {
+        let script = script_for_render(&worker_handle, &document_id, source, &params, &cancel)?;
+        check_cancelled(&worker_handle, &cancel)?;
+        let shape = VmShape::new(&script.ctx, script.root).map_err(|e| format!("Shape creation failed: {}", e))?;
+        let region = script_region(&script, &shape);
+        let mut image = render_region(&worker_handle, &script, shape, region, &camera, width, height, backend)
+            .inspect_err(|e| {
+                emit_log(&worker_handle, "error", &format!("Render failed: {}", e), Some("Render"));
+            })?;
+        annotate::draw_callouts(&mut image, camera.view(region), &callouts);
+        encode_png(&image)
+    })
+    .await;
+    drop(turn);
+    compile_state.finish(&ticket);
+
+    let png = rendered.map_err(|e| format!("Render worker failed: {}", e))??;
+    if let Some(path) = path {
+        write_export_file(&host, &path, &png, "PNG")?;
+    }
+    Ok(Response::new(png))
+}
+
+/// Time how fast a script's shape evaluates and meshes, to pick a depth before a long build
+///
+/// Benchmarks `code` if given, or else the document's last evaluated script,
+/// on `backend` or the configured one, over the region it would be meshed
+/// in.  Depths up to `max_depth` (12 unless given) are reported, the
+/// shallow ones as built and the deeper ones extrapolated.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn benchmark_shape(
+    window: Window,
+    compile_state: State<'_, CompileState>,
+    mesh_options: State<'_, MeshOptionsState>,
+    document_id: Option<String>,
+    document_path: Option<String>,
+    code: Option<String>,
+    params: Option<HashMap<String, f64>>,
+    backend: Option<EvalBackend>,
+    max_depth: Option<u8>,
+) -> Result<BenchmarkReport, String> {
+    let max_depth = max_depth.unwrap_or(12);
+    if !(1..=MAX_BENCHMARK_DEPTH).contains(&max_depth) {
+        return Err(format!("Benchmark depth must be between 1 and {}", MAX_BENCHMARK_DEPTH));
+    }
+    let source = code.map(|code| ScriptSource::new(code, document_path.as_deref()));
+    let params = params.unwrap_or_default();
+    let backend = backend.unwrap_or(mesh_options.get().backend);
+
+    let host = WindowHost::new(&window);
+    let document_id = host.document_key(document_id.as_deref());
+    let ticket = compile_state.enqueue(&format!("{}:benchmark", document_id));
+    let turn = ticket.wait_turn().await;
+
+    let worker_handle = host.clone();
+    let cancel = ticket.cancel.clone();
+    let benchmarked = tauri::async_runtime::spawn_blocking(move || -> Result<BenchmarkReport, String> {
+        let host = &worker_handle;
+        let script = script_for_render(host, &document_id, source, &params, &cancel)?;
+        check_cancelled(host, &cancel)?;
+        let shape = VmShape::new(&script.ctx, script.root).map_err(|e| format!("Shape creation failed: {}", e))?;
+        let region = script_region(&script, &shape).unwrap_or(Bounds { min: [-1.0; 3], max: [1.0; 3] });
+        let pool = host.mesh_options().thread_pool()?;
+        let threads = Some(pool.as_ref());
+        emit_log(
+            host,
+            "info",
+            &format!("Benchmarking the {} backend up to depth {}", backend.label(), max_depth),
+            Some("Mesh"),
+        );
+        let report = match backend {
+            #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+            EvalBackend::Jit if backend.is_available() => {
+                let jit = fidget::jit::JitShape::new(&script.ctx, script.root)
+                    .map_err(|e| format!("JIT shape creation failed: {}", e))?;
+                mesh::benchmark::benchmark_shape(&jit, backend.label(), &region, max_depth, threads, &cancel)
+            }
+            _ => {
+                mesh::benchmark::benchmark_shape(&shape, EvalBackend::Vm.label(), &region, max_depth, threads, &cancel)
+            }
+        };
+        let report = report.map_err(|e| {
+            let error_msg = format!("Benchmark failed: {}", e);
+            emit_log(host, "error", &error_msg, Some("Mesh"));
+            error_msg
+        })?;
+        check_cancelled(host, &cancel)?;
+        report.ok_or_else(|| "Benchmark cancelled".to_string())
+    })
+    .await;
+    drop(turn);
+    compile_state.finish(&ticket);
+
+    let report = benchmarked.map_err(|e| format!("Benchmark worker failed: {}", e))??;
+    if let Some(deepest) = report.depths.last() {
+        emit_log(
+            &host,
+            "info",
+            &format!(
+                "{} backend: {:.0} points/s, {:.0} intervals/s, {:.0} gradients/s; depth {} estimated at {:.1} s",
+                report.backend,
+                report.throughput.point,
+                report.throughput.interval,
+                report.throughput.gradient,
+                deepest.depth,
+                deepest.seconds
+            ),
+            Some("Mesh"),
+        );
+    }
+    Ok(report)
+}
+
+/// Remove the `frame_NNN.png` files a frames export wrote to `folder`, leaving anything else
+fn clear_frames(folder: &Path) -> std::io::Result<()> {
+    if !folder.is_dir() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(folder)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let is_frame = name
+            .to_str()
+            .and_then(|name| name.strip_prefix("frame_")?.strip_suffix(".png"))
+            .is_some_and(|number| !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit()));
+        if is_frame {
+            fs::remove_file(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+/// Render `frames` frames with `render` and write them to `path` in `format`
+///
+/// Frames for a GIF are kept until the last is drawn and then encoded to
+/// show at `fps` frames per second; otherwise each is written to the
+/// `path` folder as `frame_000.png` onwards as soon as it's drawn, after
+/// any frames an earlier, longer export left there are removed.  `what`
+/// names the animation in the log.
+#[allow(clippy::too_many_arguments)]
+fn write_animation(
+    host: &dyn Host,
+    path: &str,
+    format: TurntableFormat,
+    frames: u32,
+    fps: u32,
+    what: &str,
+    cancel: &CancelToken,
+    mut render: impl FnMut(u32) -> Result<RgbaImage, String>,
+) -> Result<bool, String> {
+    if format == TurntableFormat::Frames {
+        clear_frames(Path::new(path)).map_err(|e| format!("Failed to clear old frames from {}: {}", path, e))?;
+    }
+    let mut images = Vec::new();
+    for frame in 0..frames {
+        check_cancelled(host, cancel)?;
+        let image = render(frame).map_err(|e| format!("Render failed on frame {}: {}", frame + 1, e))?;
+        match format {
+            TurntableFormat::Gif => images.push(image),
+            TurntableFormat::Frames => {
+                fs::create_dir_all(path).map_err(|e| format!("Failed to create folder {}: {}", path, e))?;
+                let file = Path::new(path).join(format!("frame_{:03}.png", frame));
+                fs::write(&file, encode_png(&image)?)
+                    .map_err(|e| format!("Failed to write {}: {}", file.display(), e))?;
+            }
+        }
+    }
+    match format {
+        TurntableFormat::Gif => {
+            let data =
+                export_frames_to_gif(&images, frame_delay(fps)).map_err(|e| format!("GIF export failed: {}", e))?;
+            write_export_file(host, path, &data, "GIF")
+        }
+        TurntableFormat::Frames => {
+            emit_log(host, "info", &format!("Exported {} {} frames to {}", frames, what, path), Some("Export"));
+            Ok(true)
+        }
+    }
+}
+
+/// Render a script's shape spinning about its Z axis, for sharing as an animation
+///
+/// `frames` renders are taken at even steps of `camera`'s yaw over one
+/// full turn, shown at `fps` frames per second, at most 50.  A GIF is written to
+/// `path`; with the frames format, `path` is a folder that receives
+/// `frame_000.png` onwards.  Other options match `render_image`.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn export_turntable(
+    window: Window,
+    compile_state: State<'_, CompileState>,
+    mesh_options: State<'_, MeshOptionsState>,
+    document_id: Option<String>,
+    document_path: Option<String>,
+    code: Option<String>,
+    params: Option<HashMap<String, f64>>,
+    camera: Option<RenderCamera>,
+    view: Option<String>,
+    path: String,
+    frames: u32,
+    width: u32,
+    height: Option<u32>,
+    fps: Option<u32>,
+    format: Option<TurntableFormat>,
+) -> Result<bool, String> {
+    let height = height.unwrap_or(width);
+    check_render_size(width, height)?;
+    if frames == 0 || frames > MAX_TURNTABLE_FRAMES {
+        return Err(format!("A turntable must have between 1 and {} frames", MAX_TURNTABLE_FRAMES));
+    }
+    let fps = fps.unwrap_or(24).clamp(1, MAX_GIF_FPS);
+    let format = format.unwrap_or_default();
+    let source = code.map(|code| ScriptSource::new(code, document_path.as_deref()));
+    let params = params.unwrap_or_default();
+    let camera = view_camera(document_path.as_deref(), camera, view.as_deref())?;
+    let backend = mesh_options.get().backend;
+
+    // Queued apart from renders, so a thumbnail doesn't cancel a long export
+    let host = WindowHost::new(&window);
+    let document_id = host.document_key(document_id.as_deref());
+    let ticket = compile_state.enqueue(&format!("{}:export_turntable", document_id));
+    let turn = ticket.wait_turn().await;
+
+    let worker_handle = host.clone();
+    let cancel = ticket.cancel.clone();
+    let exported = tauri::async_runtime::spawn_blocking(move || -> Result<bool, String> {
+        let host = &worker_handle;
+        let script = script_for_render(host, &document_id, source, &params, &cancel)?;
+        write_animation(host, &path, format, frames, fps, "turntable", &cancel, |frame| {
+            let camera = RenderCamera { yaw: camera.yaw + 360.0 * frame as f32 / frames as f32, ..camera };
+            render_script(host, &script, &camera, width, height, backend)
+        })
+    })
+    .await;
+    drop(turn);
+    compile_state.finish(&ticket);
+
+    exported.map_err(|e| format!("Render worker failed: {}", e))?.inspect_err(|e| {
+        emit_log(&host, "error", e, Some("Export"));
+    })
+}
+
+/// Render a script as one of its parameters sweeps from `from` to `to`, such as the `t` of a `morph()`
+///
+/// `parameter` is a `param()` or `param_tree()` name; the sweep runs over
+/// its declared range unless `from` or `to` are given, or 0 to 1 if it has
+/// none.  Every frame is framed to cover both ends of the sweep, so the
+/// camera holds still.  Other options match `export_turntable`.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn export_param_animation(
+    window: Window,
+    compile_state: State<'_, CompileState>,
+    mesh_options: State<'_, MeshOptionsState>,
+    document_id: Option<String>,
+    document_path: Option<String>,
+    code: String,
+    params: Option<HashMap<String, f64>>,
+    parameter: String,
+    from: Option<f64>,
+    to: Option<f64>,
+    camera: Option<RenderCamera>,
+    view: Option<String>,
+    path: String,
+    frames: u32,
+    width: u32,
+    height: Option<u32>,
+    fps: Option<u32>,
+    format: Option<TurntableFormat>,
+) -> Result<bool, String> {
+    let height = height.unwrap_or(width);
+    check_render_size(width, height)?;
+    if frames == 0 || frames > MAX_TURNTABLE_FRAMES {
+        return Err(format!("An animation must have between 1 and {} frames", MAX_TURNTABLE_FRAMES));
+    }
+    let fps = fps.unwrap_or(24).clamp(1, MAX_GIF_FPS);
+    let format = format.unwrap_or_default();
+    let source = ScriptSource::new(code, document_path.as_deref());
+    let params = params.unwrap_or_default();
+    let camera = view_camera(document_path.as_deref(), camera, view.as_deref())?;
+    let backend = mesh_options.get().backend;
+
+    // Queued apart from renders, so a thumbnail doesn't cancel a long export
+    let host = WindowHost::new(&window);
+    let document_id = host.document_key(document_id.as_deref());
+    let ticket = compile_state.enqueue(&format!("{}:export_animation", document_id));
+    let turn = ticket.wait_turn().await;
+
+    let worker_handle = host.clone();
+    let cancel = ticket.cancel.clone();
+    let exported = tauri::async_runtime::spawn_blocking(move || -> Result<bool, String> {
+        let host = &worker_handle;
+        // One cache slot for every frame, apart from the document's own
+        let cache_key = format!("{}:animation", document_id);
+        let run = |value: Option<f64>| {
+            let mut params = params.clone();
+            if let Some(value) = value {
+                params.insert(parameter.clone(), value);
+            }
+            let mut timings = CompileTimings::default();
+            run_script(host, &cache_key, &source, &params, &cancel, &mut timings).map_err(|e| e.message)
+        };
+
+        // The parameter's declared range, read with the script's own value for it
+        let first = run(None)?;
+        let declared = first
+            .params
+            .iter()
+            .find(|p| p.name == parameter)
+            .ok_or_else(|| format!("The script has no parameter named '{}'", parameter))?;
+        let from = from.or(declared.min).unwrap_or(0.0);
+        let to = to.or(declared.max).unwrap_or(1.0);
+        let value_at = |frame: u32| match frames {
+            1 => from,
+            _ => from + (to - from) * frame as f64 / (frames - 1) as f64,
+        };
+
+        let mut region: Option<Bounds> = None;
+        for value in [from, to] {
+            let script = run(Some(value))?;
+            let shape = VmShape::new(&script.ctx, script.root).map_err(|e| format!("Shape creation failed: {}", e))?;
+            if let Some(bounds) = script_region(&script, &shape) {
+                region.get_or_insert(bounds).grow(&bounds);
+            }
+        }
+
+        write_animation(host, &path, format, frames, fps, "animation", &cancel, |frame| {
+            let script = run(Some(value_at(frame)))?;
+            let shape = VmShape::new(&script.ctx, script.root).map_err(|e| format!("Shape creation failed: {}", e))?;
+            render_region(host, &script, shape, region, &camera, width, height, backend)
+        })
+    })
+    .await;
+    drop(turn);
+    compile_state.finish(&ticket);
+
+    exported.map_err(|e| format!("Render worker failed: {}", e))?.inspect_err(|e| {
+        emit_log(&host, "error", e, Some("Export"));
+    })
+}
+
+/// Sample a script's distance field on a regular grid and write it to `path`
+///
+/// The grid covers the script's region, as `script_region` finds it, with
+/// `resolution` samples along its longest side.  It's written as a NumPy
+/// `.npy` array unless `format` asks for bare values; the returned layout
+/// places the samples in model space either way.  Samples `code` if
+/// given, or else the document's last evaluated script.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn export_sdf_grid(
+    window: Window,
+    compile_state: State<'_, CompileState>,
+    document_id: Option<String>,
+    document_path: Option<String>,
+    code: Option<String>,
+    params: Option<HashMap<String, f64>>,
+    path: String,
+    resolution: usize,
+    format: Option<GridFormat>,
+) -> Result<GridLayout, String> {
+    if !(2..=MAX_GRID_RESOLUTION).contains(&resolution) {
+        return Err(format!("Grid resolution must be between 2 and {}", MAX_GRID_RESOLUTION));
+    }
+    let source = code.map(|code| ScriptSource::new(code, document_path.as_deref()));
+    let params = params.unwrap_or_default();
+    let format = format.unwrap_or_default();
+
+    let host = WindowHost::new(&window);
+    let document_id = host.document_key(document_id.as_deref());
+    let ticket = compile_state.enqueue(&format!("{}:export_sdf_grid", document_id));
+    let turn = ticket.wait_turn().await;
+
+    let worker_handle = host.clone();
+    let cancel = ticket.cancel.clone();
+    let exported = tauri::async_runtime::spawn_blocking(move || -> Result<GridLayout, String> {
+        let host = &worker_handle;
+        let script = script_for_render(host, &document_id, source, &params, &cancel)?;
+        let shape = VmShape::new(&script.ctx, script.root).map_err(|e| format!("Shape creation failed: {}", e))?;
+        let bounds = script_region(&script, &shape)
+            .ok_or_else(|| "Couldn't find the shape to sample; give the script set_bounds()".to_string())?;
+        let start = Instant::now();
+        let grid = sample_grid(&shape, &bounds, resolution, &cancel)
+            .map_err(|e| format!("Sampling the distance field failed: {}", e))?
+            .ok_or_else(|| "Grid export cancelled".to_string())?;
+        let [nx, ny, nz] = grid.layout.dims;
+        let message = format!("Sampled a {}x{}x{} grid in {} ms", nx, ny, nz, elapsed_ms(start));
+        emit_log(host, "info", &message, Some("Export"));
+        write_export_file(host, &path, &export_grid(&grid, format), "distance field grid")?;
+        Ok(grid.layout)
+    })
+    .await;
+    drop(turn);
+    compile_state.finish(&ticket);
+
+    exported.map_err(|e| format!("Export worker failed: {}", e))?.inspect_err(|e| {
+        emit_log(&host, "error", e, Some("Export"));
+    })
+}
+
+/// Write the document's last compiled surface to `path` as a point cloud, for registration against scans
+///
+/// With the `surface` source, points are scattered over the mesh about
+/// `spacing` apart in model units and then snapped onto the distance
+/// field, bound with `params`, so they sit on the true surface rather than
+/// the mesh's approximation of it.  The `vertices` source writes the
+/// mesh's vertices as they are.  Every point carries its outward normal.
+/// Returns the number of points written.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn export_point_cloud_file(
+    window: Window,
+    mesh_cache: State<'_, MeshCache>,
+    document_id: Option<String>,
+    params: Option<HashMap<String, f64>>,
+    path: String,
+    format: Option<PointCloudFormat>,
+    source: Option<PointSource>,
+    spacing: Option<f32>,
+) -> Result<usize, String> {
+    let host = WindowHost::new(&window);
+    let document_id = host.document_key(document_id.as_deref());
+    let Some(last) = mesh_cache.get(&document_id) else {
+        return Err("Nothing has been compiled yet to sample".to_string());
+    };
+    let params = params.unwrap_or_default();
+    let format = format.or_else(|| {
+        [PointCloudFormat::Xyz, PointCloudFormat::Pcd].into_iter().find(|format| {
+            Path::new(&path).extension().is_some_and(|e| e.eq_ignore_ascii_case(format.extension()))
+        })
+    });
+    let format = format.unwrap_or_default();
+    let source = source.unwrap_or_default();
+
+    let worker_handle = host.clone();
+    let exported = tauri::async_runtime::spawn_blocking(move || -> Result<usize, String> {
+        let host = &worker_handle;
+        let to_model = last.units.to_model.unwrap_or_else(nalgebra::Matrix4::identity);
+        let start = Instant::now();
+        let cloud = match source {
+            PointSource::Vertices => mesh_vertices(&last.mesh, &to_model),
+            PointSource::Surface => {
+                let spacing = match spacing {
+                    Some(spacing) if spacing > 0.0 && spacing.is_finite() => spacing,
+                    Some(_) => return Err("Point spacing must be greater than zero".to_string()),
+                    None => return Err("Sampling the surface needs a point spacing".to_string()),
+                };
+                let count = surface_point_count(&last.mesh, &to_model, spacing);
+                if count > MAX_POINT_CLOUD_POINTS as f64 {
+                    return Err(format!(
+                        "A spacing of {} would give about {:.0} points, more than the limit of {}",
+                        spacing, count, MAX_POINT_CLOUD_POINTS
+                    ));
+                }
+                let (_, shape, _) = viewport_shape(host, None, &document_id, &params)?;
+                let cancel = CancelToken::new();
+                let mut cloud = sample_surface(&last.mesh, &to_model, spacing, &cancel)
+                    .ok_or_else(|| "Point cloud export cancelled".to_string())?;
+                project_to_surface(&shape, &mut cloud, &cancel)
+                    .map_err(|e| format!("Snapping points to the surface failed: {}", e))?
+                    .ok_or_else(|| "Point cloud export cancelled".to_string())?;
+                cloud
+            }
+        };
+        let message = format!("Sampled {} points in {} ms", cloud.points.len(), elapsed_ms(start));
+        emit_log(host, "info", &message, Some("Export"));
+        write_export_file(host, &path, &export_point_cloud(&cloud, format), "point cloud")?;
+        Ok(cloud.points.len())
+    })
+    .await;
+
+    exported.map_err(|e| format!("Export worker failed: {}", e))?.inspect_err(|e| {
+        emit_log(&host, "error", e, Some("Export"));
+    })
+}
+
+/// Write a script's shape as a GLSL distance function to `path`, for raymarching in shaders
+///
+/// The function is named `function_name`, `sdf` by default, and takes a
+/// point in model space.  Exports `code` if given, or else the document's
+/// last evaluated script, with `params` bound as constants.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn export_glsl(
+    window: Window,
+    compile_state: State<'_, CompileState>,
+    document_id: Option<String>,
+    document_path: Option<String>,
+    code: Option<String>,
+    params: Option<HashMap<String, f64>>,
+    path: String,
+    function_name: Option<String>,
+) -> Result<bool, String> {
+    let script_name =
+        document_path.as_deref().and_then(|p| Path::new(p).file_name()).map(|n| n.to_string_lossy().into_owned());
+    let source = code.map(|code| ScriptSource::new(code, document_path.as_deref()));
+    let params = params.unwrap_or_default();
+    let function_name = function_name.unwrap_or_else(|| "sdf".to_string());
+
+    let host = WindowHost::new(&window);
+    let document_id = host.document_key(document_id.as_deref());
+    let ticket = compile_state.enqueue(&format!("{}:export_glsl", document_id));
+    let turn = ticket.wait_turn().await;
+
+    let worker_handle = host.clone();
+    let cancel = ticket.cancel.clone();
+    let exported = tauri::async_runtime::spawn_blocking(move || -> Result<bool, String> {
+        let host = &worker_handle;
+        let script = script_for_render(host, &document_id, source, &params, &cancel)?;
+        let glsl = export_shape_to_glsl(&script.ctx, script.root, &function_name, script_name.as_deref())
+            .map_err(|e| format!("GLSL export failed: {}", e))?;
+        write_export_file(host, &path, glsl.as_bytes(), "GLSL")
+    })
+    .await;
+    drop(turn);
+    compile_state.finish(&ticket);
+
+    exported.map_err(|e| format!("Export worker failed: {}", e))?.inspect_err(|e| {
+        emit_log(&host, "error", e, Some("Export"));
+    })
+}
+
+/// A raymarching shader for the viewport to show the shape with, while or instead of meshing it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct GpuPreview {
+    /// GLSL ES 3.00 fragment shader, with the uniforms `raymarch_shader` lists
+    shader: String,
+    /// Region rays are clipped to, in model space; `None` if the shape has none
+    bounds: Option<Bounds>,
+}
+
+/// Build a fragment shader that raymarches a script's shape on the GPU
+///
+/// For heavy shapes that take minutes to mesh at a useful depth, the
+/// viewport can draw this at interactive rates and swap in the mesh when a
+/// compile finishes.  Only available with the experimental `gpu_preview`
+/// setting on.  Uses `code` if given, or else the document's last evaluated
+/// script, with `params` bound as constants; shapes are in model space.
+#[tauri::command]
+async fn compile_gpu_preview(
+    window: Window,
+    compile_state: State<'_, CompileState>,
+    settings: State<'_, SettingsStore>,
+    document_id: Option<String>,
+    document_path: Option<String>,
+    code: Option<String>,
+    params: Option<HashMap<String, f64>>,
+) -> Result<GpuPreview, String> {
+    if !settings.get().gpu_preview {
+        return Err("GPU preview is experimental; turn it on in the settings first".to_string());
+    }
+    let source = code.map(|code| ScriptSource::new(code, document_path.as_deref()));
+    let params = params.unwrap_or_default();
+
+    let host = WindowHost::new(&window);
+    let document_id = host.document_key(document_id.as_deref());
+    let ticket = compile_state.enqueue(&format!("{}:gpu_preview", document_id));
+    let turn = ticket.wait_turn().await;
+
+    let worker_handle = host.clone();
+    let cancel = ticket.cancel.clone();
+    let built = tauri::async_runtime::spawn_blocking(move || -> Result<GpuPreview, String> {
+        let host = &worker_handle;
+        let script = script_for_render(host, &document_id, source, &params, &cancel)?;
+        if script.flat {
+            return Err("2D scripts are previewed as outlines, not raymarched".to_string());
+        }
+        let shape = VmShape::new(&script.ctx, script.root).map_err(|e| format!("Shape build failed: {}", e))?;
+        let bounds = script_region(&script, &shape);
+        let shader = export::glsl::raymarch_shader(&script.ctx, script.root, bounds.as_ref())
+            .map_err(|e| format!("GPU preview failed: {}", e))?;
+        Ok(GpuPreview { shader, bounds })
+    })
+    .await;
+    drop(turn);
+    compile_state.finish(&ticket);
+
+    built.map_err(|e| format!("Preview worker failed: {}", e))?.inspect_err(|e| {
+        emit_log(&host, "error", e, Some("Render"));
+    })
+}
+
+/// Save a script's evaluated shape to `path` as a compiled `.vm` file
+///
+/// Other scripts load it back with `import_shape`, which skips re-running
+/// a slow script when only the meshing depth or the surroundings change.
+/// Saves `code` if given, or else the document's last evaluated script,
+/// with `params` bound as constants.  Returns the number of named parts.
+#[tauri::command]
+async fn save_compiled_shape(
+    window: Window,
+    compile_state: State<'_, CompileState>,
+    document_id: Option<String>,
+    document_path: Option<String>,
+    code: Option<String>,
+    params: Option<HashMap<String, f64>>,
+    path: String,
+) -> Result<usize, String> {
+    let source = code.map(|code| ScriptSource::new(code, document_path.as_deref()));
+    let params = params.unwrap_or_default();
+
+    let host = WindowHost::new(&window);
+    let document_id = host.document_key(document_id.as_deref());
+    let ticket = compile_state.enqueue(&format!("{}:save_compiled_shape", document_id));
+    let turn = ticket.wait_turn().await;
+
+    let worker_handle = host.clone();
+    let cancel = ticket.cancel.clone();
+    let saved = tauri::async_runtime::spawn_blocking(move || -> Result<usize, String> {
+        let host = &worker_handle;
+        let script = script_for_render(host, &document_id, source, &params, &cancel)?;
+        let parts: Vec<(&str, fidget::context::Node)> =
+            script.shapes.iter().filter_map(|s| Some((s.name.as_deref()?, s.node))).collect();
+        let data = export_shape_to_vm(&script.ctx, script.root, &parts)
+            .map_err(|e| format!("Saving the compiled shape failed: {}", e))?;
+        write_export_file(host, &path, &data, "compiled shape")?;
+        Ok(parts.len())
+    })
+    .await;
+    drop(turn);
+    compile_state.finish(&ticket);
+
+    saved.map_err(|e| format!("Export worker failed: {}", e))?.inspect_err(|e| {
+        emit_log(&host, "error", e, Some("Export"));
+    })
+}
+
+/// Cut a script's shape with horizontal planes at each of `heights`
+///
+/// The planes span the script's bounds, or the detected extent of the shape
+/// if it doesn't set any; in 2D mode that's its extent around the XY plane.
+fn slice_script(
+    host: &dyn Host,
+    script: &ScriptOutput,
+    heights: &[f32],
+    resolution: usize,
+    cancel: &CancelToken,
+) -> Result<Vec<Slice>, String> {
+    let shape = VmShape::new(&script.ctx, script.root).map_err(|e| format!("Shape creation failed: {}", e))?;
+    let bounds = match script.bounds {
+        Some(bounds) => bounds,
+        None if script.flat => mesh::outline::flat_bounds(&script.ctx, script.root)
+            .map_err(|e| format!("Bounds detection failed: {}", e))?
+            .ok_or_else(|| "Shape is empty or unbounded, so it can't be outlined".to_string())?,
+        None => detect_bounds(&shape)
+            .map_err(|e| format!("Bounds detection failed: {}", e))?
+            .ok_or_else(|| "Shape is empty or unbounded, so it can't be sliced".to_string())?,
+    };
+    let mut slices = Vec::with_capacity(heights.len());
+    for &z in heights {
+        check_cancelled(host, cancel)?;
+        slices.push(slice(&shape, &bounds, z, resolution).map_err(|e| format!("Slicing at z = {} failed: {}", z, e))?);
+    }
+    Ok(slices)
+}
+
+/// Validate a requested cross-section resolution, falling back to the default
+fn slice_resolution(resolution: Option<usize>) -> Result<usize, String> {
+    let resolution = resolution.unwrap_or(DEFAULT_SLICE_RESOLUTION);
+    if !(2..=MAX_SLICE_RESOLUTION).contains(&resolution) {
+        return Err(format!("Slice resolution must be between 2 and {}", MAX_SLICE_RESOLUTION));
+    }
+    Ok(resolution)
+}
+
+/// Run a script and cut its shape at each of `heights`, queued behind other work on the document
+#[allow(clippy::too_many_arguments)]
+async fn slice_document(
+    host: &WindowHost,
+    compile_state: &CompileState,
+    document_id: Option<String>,
+    document_path: Option<String>,
+    code: String,
+    params: Option<HashMap<String, f64>>,
+    heights: Vec<f32>,
+    resolution: usize,
+) -> Result<Vec<Slice>, String> {
+    let source = ScriptSource::new(code, document_path.as_deref());
+    let params = params.unwrap_or_default();
+
+    let document_id = host.document_key(document_id.as_deref());
+    let ticket = compile_state.enqueue(&format!("{}:slice", document_id));
+    let turn = ticket.wait_turn().await;
+
+    let worker_handle = host.clone();
+    let cancel = ticket.cancel.clone();
+    let sliced = tauri::async_runtime::spawn_blocking(move || -> Result<Vec<Slice>, String> {
+        let mut timings = CompileTimings::default();
+        let script =
+            run_script(&worker_handle, &document_id, &source, &params, &cancel, &mut timings).map_err(|e| e.message)?;
+        slice_script(&worker_handle, &script, &heights, resolution, &cancel).inspect_err(|e| {
+            emit_log(&worker_handle, "error", e, Some("Analysis"));
+        })
+    })
+    .await;
+    drop(turn);
+    compile_state.finish(&ticket);
+
+    sliced.map_err(|e| format!("Analysis worker failed: {}", e))?
+}
+
+/// Heights from `z_min` to `z_max` inclusive, `step` apart
+fn layer_heights(z_min: f32, z_max: f32, step: f32) -> Result<Vec<f32>, String> {
+    if !z_min.is_finite() || !z_max.is_finite() || z_max < z_min {
+        return Err("Slice range must run from a lower to a higher finite height".to_string());
+    }
+    if step.is_nan() || step <= 0.0 {
+        return Err("Slice step must be greater than zero".to_string());
+    }
+    // Allow for rounding, so a range that's an exact multiple of the step keeps its top layer
+    let count = ((z_max - z_min) / step * (1.0 + 1e-6)).floor() as usize + 1;
+    if count > MAX_SLICE_LAYERS {
+        return Err(format!("Slice range and step give {} layers; the limit is {}", count, MAX_SLICE_LAYERS));
+    }
+    Ok((0..count).map(|i| z_min + i as f32 * step).collect())
+}
+
+/// Cross-section of a script's shape at height `z`, as closed 2D outlines
+///
+/// `resolution` is the number of sampling cells along the longer side of the
+/// shape's bounds.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn slice_shape(
+    window: Window,
+    compile_state: State<'_, CompileState>,
+    document_id: Option<String>,
+    document_path: Option<String>,
+    code: String,
+    params: Option<HashMap<String, f64>>,
+    z: f32,
+    resolution: Option<usize>,
+) -> Result<Slice, String> {
+    let host = WindowHost::new(&window);
+    if !z.is_finite() {
+        return Err("Slice height must be a finite number".to_string());
+    }
+    let resolution = slice_resolution(resolution)?;
+    let mut slices =
+        slice_document(&host, &compile_state, document_id, document_path, code, params, vec![z], resolution)
+            .await?;
+    let slice = slices.remove(0);
+    emit_log(
+        &host,
+        "info",
+        &format!("Sliced at z = {}: {} outlines", z, slice.contours.len()),
+        Some("Analysis"),
+    );
+    Ok(slice)
+}
+
+/// Files written by a slice export
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SliceExportResult {
+    pub paths: Vec<String>,
+    /// Slices written, leaving out heights that miss the shape
+    pub layers: usize,
+    pub empty_layers: usize,
+}
+
+/// `path` with `_<index>` added to its file stem, zero-padded to fit `count` layers
+fn layer_path(path: &Path, index: usize, count: usize) -> String {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_else(|| "slice".to_string());
+    let extension = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    let width = count.saturating_sub(1).to_string().len();
+    path.with_file_name(format!("{}_{:0width$}{}", stem, index, extension)).to_string_lossy().into_owned()
+}
+
+/// Write slices taken every `step` from `z_min`, as one file per layer or all on one sheet
+///
+/// Slices that miss the shape are skipped.  Per-layer files are named after
+/// `path` with the layer number added.
+#[allow(clippy::too_many_arguments)]
+fn write_slice_files(
+    host: &dyn Host,
+    slices: Vec<Slice>,
+    path: String,
+    z_min: f32,
+    step: f32,
+    layout: SliceLayout,
+    format_name: &str,
+    encode: impl Fn(&[Slice]) -> Result<Vec<u8>>,
+) -> Result<SliceExportResult, String> {
+    let count = slices.len();
+    let slices: Vec<Slice> = slices.into_iter().filter(|s| !s.contours.is_empty()).collect();
+    if slices.is_empty() {
+        let error_msg = "None of the slices touch the shape".to_string();
+        emit_log(host, "error", &error_msg, Some("Export"));
+        return Err(error_msg);
+    }
+
+    let encode = |slices: &[Slice]| {
+        encode(slices).map_err(|e| {
+            let error_msg = format!("{} export failed: {}", format_name, e);
+            emit_log(host, "error", &error_msg, Some("Export"));
+            error_msg
+        })
+    };
+    let mut result = SliceExportResult { layers: slices.len(), empty_layers: count - slices.len(), ..Default::default() };
+    match layout {
+        SliceLayout::PerLayer => {
+            for slice in &slices {
+                let index = ((slice.z - z_min) / step).round() as usize;
+                let layer = layer_path(Path::new(&path), index, count);
+                write_export_file(host, &layer, &encode(std::slice::from_ref(slice))?, format_name)?;
+                result.paths.push(layer);
+            }
+        }
+        SliceLayout::Stacked => {
+            write_export_file(host, &path, &encode(&slices)?, format_name)?;
+            result.paths.push(path);
+        }
+    }
+    emit_log(
+        host,
+        "info",
+        &format!("Exported {} slices ({} empty skipped)", result.layers, result.empty_layers),
+        Some("Export"),
+    );
+    Ok(result)
+}
+
+/// Slice a script's shape every `step` from `z_min` to `z_max` and write the outlines as SVG
+///
+/// With the `per_layer` layout (the default) each slice goes to its own file;
+/// `stacked` lays every slice out on one sheet at `path`.  Model coordinates
+/// are taken to be in `unit`, millimeters by default.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn export_slices_svg(
+    window: Window,
+    compile_state: State<'_, CompileState>,
+    document_id: Option<String>,
+    document_path: Option<String>,
+    code: String,
+    params: Option<HashMap<String, f64>>,
+    path: String,
+    z_min: f32,
+    z_max: f32,
+    step: f32,
+    resolution: Option<usize>,
+    layout: Option<SliceLayout>,
+    unit: Option<ModelUnit>,
+) -> Result<SliceExportResult, String> {
+    let host = WindowHost::new(&window);
+    let heights = layer_heights(z_min, z_max, step)?;
+    let resolution = slice_resolution(resolution)?;
+    let unit = unit.unwrap_or_default();
+    let slices =
+        slice_document(&host, &compile_state, document_id, document_path, code, params, heights, resolution)
+            .await?;
+    write_slice_files(&host, slices, path, z_min, step, layout.unwrap_or_default(), "SVG", |slices| {
+        export_slices_to_svg(slices, unit)
+    })
+}
+
+/// Slice a script's shape every `step` from `z_min` to `z_max` and write the profiles as DXF
+///
+/// Takes the same options as `export_slices_svg`; on a `stacked` sheet each
+/// slice is on a DXF layer of its own.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn export_slices_dxf(
+    window: Window,
+    compile_state: State<'_, CompileState>,
+    document_id: Option<String>,
+    document_path: Option<String>,
+    code: String,
+    params: Option<HashMap<String, f64>>,
+    path: String,
+    z_min: f32,
+    z_max: f32,
+    step: f32,
+    resolution: Option<usize>,
+    layout: Option<SliceLayout>,
+    unit: Option<ModelUnit>,
+) -> Result<SliceExportResult, String> {
+    let host = WindowHost::new(&window);
+    let heights = layer_heights(z_min, z_max, step)?;
+    let resolution = slice_resolution(resolution)?;
+    let unit = unit.unwrap_or_default();
+    let slices =
+        slice_document(&host, &compile_state, document_id, document_path, code, params, heights, resolution)
+            .await?;
+    write_slice_files(&host, slices, path, z_min, step, layout.unwrap_or_default(), "DXF", |slices| {
+        export_slices_to_dxf(slices, unit)
+    })
+}
+
+/// Trace a 2D script's outline where it crosses z = 0 and write it as SVG
+///
+/// For laser cutting and other flat work, without meshing anything.
+/// `resolution` is the number of cells across the shape, and model
+/// coordinates are taken to be in `unit`, millimeters by default.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn export_outline_svg(
+    window: Window,
+    compile_state: State<'_, CompileState>,
+    document_id: Option<String>,
+    document_path: Option<String>,
+    code: String,
+    params: Option<HashMap<String, f64>>,
+    path: String,
+    resolution: Option<usize>,
+    unit: Option<ModelUnit>,
+) -> Result<SliceExportResult, String> {
+    let host = WindowHost::new(&window);
+    let resolution = slice_resolution(resolution)?;
+    let unit = unit.unwrap_or_default();
+    let slices =
+        slice_document(&host, &compile_state, document_id, document_path, code, params, vec![0.0], resolution)
+            .await?;
+    write_slice_files(&host, slices, path, 0.0, 1.0, SliceLayout::Stacked, "SVG", |slices| {
+        export_slices_to_svg(slices, unit)
+    })
+}
+
+/// Trace a 2D script's outline where it crosses z = 0 and write it as DXF
+///
+/// Takes the same options as `export_outline_svg`.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn export_outline_dxf(
+    window: Window,
+    compile_state: State<'_, CompileState>,
+    document_id: Option<String>,
+    document_path: Option<String>,
+    code: String,
+    params: Option<HashMap<String, f64>>,
+    path: String,
+    resolution: Option<usize>,
+    unit: Option<ModelUnit>,
+) -> Result<SliceExportResult, String> {
+    let host = WindowHost::new(&window);
+    let resolution = slice_resolution(resolution)?;
+    let unit = unit.unwrap_or_default();
+    let slices =
+        slice_document(&host, &compile_state, document_id, document_path, code, params, vec![0.0], resolution)
+            .await?;
+    write_slice_files(&host, slices, path, 0.0, 1.0, SliceLayout::Stacked, "DXF", |slices| {
+        export_slices_to_dxf(slices, unit)
+    })
+}
+
+/// Cancel the compile in flight for `document_id`, or for every document in the calling window
+///
+/// Returns false if no compile was running.
+#[tauri::command]
+fn cancel_compile(window: Window, compile_state: State<'_, CompileState>, document_id: Option<String>) -> bool {
+    let host = WindowHost::new(&window);
+    let cancelled = match document_id {
+        Some(document_id) => compile_state.cancel(Some(&host.document_key(Some(&document_id)))),
+        None => compile_state.cancel_prefix(&host.document_prefix()),
+    };
+    if cancelled {
+        emit_log(&host, "info", "Cancellation requested", Some("Compiler"));
+    }
+    cancelled
+}
+
+/// Current operation and time budget for script runs
+#[tauri::command]
+fn get_script_limits(limits_state: State<'_, ScriptLimitsState>) -> ScriptLimits {
+    limits_state.get()
+}
+
+/// Change the script budget; omitted fields keep their current value, and 0 means no limit
+///
+/// These are the limits outside any execution profile, which apply once
+/// the profile is switched off.
+#[tauri::command]
+fn set_script_limits(
+    app_handle: AppHandle,
+    settings: State<'_, SettingsStore>,
+    max_operations: Option<u64>,
+    timeout_ms: Option<u64>,
+) -> Result<ScriptLimits, String> {
+    let current = settings.get().script_limits;
+    let limits = ScriptLimits {
+        max_operations: max_operations.unwrap_or(current.max_operations),
+        timeout_ms: timeout_ms.unwrap_or(current.timeout_ms),
+    };
+    let updated = settings.update(&app_handle, |settings| settings.script_limits = limits)?;
+    let profile = match &updated.profile {
+        Some(name) => format!("; the '{}' profile's limits apply until it's switched off", name),
+        None => String::new(),
+    };
+    emit_log(
+        &app_handle,
+        "info",
+        &format!("Script limits set to {} operations, {} ms{}", limits.max_operations, limits.timeout_ms, profile),
+        Some("System"),
+    );
+    Ok(limits)
+}
+
+/// Run compiles under the execution profile named `profile` in the settings, or outside any if it's omitted
+///
+/// The profile's script and mesh limits take over from those in the
+/// settings, starting with the next compile.
+#[tauri::command]
+fn set_execution_profile(app_handle: AppHandle, profile: Option<String>) -> Result<Settings, String> {
+    select_profile(&app_handle, profile)
+}
+
+/// The saved preferences
+#[tauri::command]
+fn get_settings(settings: State<'_, SettingsStore>) -> Settings {
+    settings.get()
+}
+
+/// Replace the preferences, applying them straight away and saving them for later sessions
+#[tauri::command]
+fn set_settings(
+    app_handle: AppHandle,
+    store: State<'_, SettingsStore>,
+    settings: Settings,
+) -> Result<Settings, String> {
+    let settings = store.update(&app_handle, |current| *current = settings).inspect_err(|e| {
+        emit_log(&app_handle, "error", e, Some("System"));
+    })?;
+    emit_log(&app_handle, "info", "Settings saved", Some("System"));
+    Ok(settings)
+}
+
+/// Remember the sizes of the editor's panels for the next session
+///
+/// Hints are keyed by the frontend, and come back in `get_settings` under
+/// `window.panels`, along with the window geometry saved on close.
+#[tauri::command]
+fn set_panel_layout(
+    app_handle: AppHandle,
+    store: State<'_, SettingsStore>,
+    panels: HashMap<String, f64>,
+) -> Result<(), String> {
+    store.update(&app_handle, |settings| settings.window.get_or_insert_with(Default::default).panels = panels)?;
+    Ok(())
+}
+
+/// Current options for saving scripts
+#[tauri::command]
+fn get_save_options(save_options: State<'_, SaveOptionsState>) -> SaveOptions {
+    save_options.get()
+}
+
+/// Change the options for saving scripts; omitted fields keep their current value
+#[tauri::command]
+fn set_save_options(
+    app_handle: AppHandle,
+    save_options: State<'_, SaveOptionsState>,
+    settings: State<'_, SettingsStore>,
+    backups: Option<usize>,
+) -> SaveOptions {
+    let current = save_options.get();
+    let options = SaveOptions { backups: backups.unwrap_or(current.backups) };
+    // Backup counts are always valid
+    let _ = settings.update(&app_handle, |settings| settings.save = options);
+    emit_log(
+        &app_handle,
+        "info",
+        &format!("Saving keeps {} backup{}", options.backups, if options.backups == 1 { "" } else { "s" }),
+        Some("System"),
+    );
+    options
+}
+
+/// Current app-wide meshing options
+#[tauri::command]
+fn get_mesh_options(mesh_options: State<'_, MeshOptionsState>) -> MeshOptions {
+    mesh_options.get()
+}
+
+/// Change the meshing options; omitted fields keep their current value
+#[tauri::command]
+fn set_mesh_options(
+    app_handle: AppHandle,
+    settings: State<'_, SettingsStore>,
+    backend: Option<EvalBackend>,
+    threads: Option<usize>,
+    low_priority: Option<bool>,
+    limits: Option<MeshLimits>,
+) -> MeshOptions {
+    // The settings' own limits, which an execution profile may be standing in for
+    let current = settings.get().mesh;
+    let options = MeshOptions {
+        backend: backend.unwrap_or(current.backend),
+        threads: threads.unwrap_or(current.threads),
+        low_priority: low_priority.unwrap_or(current.low_priority),
+        limits: limits.unwrap_or(current.limits),
+    };
+    // Meshing options are always valid
+    let _ = settings.update(&app_handle, |settings| settings.mesh = options);
+    let threads = match options.threads {
+        0 => "one thread per core".to_string(),
+        1 => "1 thread".to_string(),
+        n => format!("{} threads", n),
+    };
+    emit_log(
+        &app_handle,
+        "info",
+        &format!(
+            "Meshing options set to the {} backend, {}{}",
+            options.backend.label(),
+            threads,
+            if options.low_priority { " at low priority" } else { "" }
+        ),
+        Some("System"),
+    );
+    options
+}
+
+/// Shape and meshing settings produced by a script
+struct ScriptOutput {
+    ctx: Context,
+    /// Union of every drawn shape
+    root: fidget::context::Node,
+    /// Each drawn shape, in call order
+    shapes: Vec<DrawnShape>,
+    /// Scale passed to `set_scale`, if the script called it
+    scale: Option<f32>,
+    /// Region passed to `set_bounds`, if the script called it
+    bounds: Option<Bounds>,
+    /// Unit passed to `set_units`, if the script called it
+    unit: Option<ModelUnit>,
+    /// Parameters declared with `param()` or `param_tree()`, in declaration order
+    params: Vec<ScriptParam>,
+    /// Variable standing in for each unbound `param_tree()` value
+    vars: HashMap<String, Var>,
+    /// Files loaded by `import`
+    imports: Vec<ImportStamp>,
+    /// `assert_*` calls, checked once the shape is meshed
+    assertions: Vec<DeclaredAssertion>,
+    /// The union split into non-overlapping regions by `material()`, if the script called it
+    materials: Vec<MaterialRegion>,
+    /// 2D mode: the script called `draw2d()`, or nothing it drew reads Z
+    flat: bool,
+}
+
+impl ScriptOutput {
+    /// Returns true if `overrides` only change `param_tree()` values
+    ///
+    /// Numeric `param()` values are baked into the graph when the script runs,
+    /// so changing one of them needs a full re-run.
+    fn can_rebind(&self, overrides: &HashMap<String, f64>) -> bool {
+        self.params.iter().all(|p| p.symbolic || p.resolve(overrides) == p.value)
+    }
+
+    /// Copy the output into a new context with `param_tree()` values bound
+    fn bind(&self, overrides: &HashMap<String, f64>) -> Result<ScriptOutput> {
+        let params: Vec<ScriptParam> = self
+            .params
+            .iter()
+            .map(|p| ScriptParam { value: p.resolve(overrides), ..p.clone() })
+            .collect();
+        let values = params
+            .iter()
+            .filter_map(|p| self.vars.get(&p.name).map(|&v| (v, p.value)))
+            .collect();
+        let nodes: Vec<_> = std::iter::once(self.root)
+            .chain(self.shapes.iter().map(|s| s.node))
+            .chain(self.materials.iter().map(|m| m.node))
+            .collect();
+        let (ctx, nodes) = bind_vars(&self.ctx, &nodes, &values)?;
+        let (shape_nodes, material_nodes) = nodes[1..].split_at(self.shapes.len());
+        let shapes = self
+            .shapes
+            .iter()
+            .zip(shape_nodes)
+            .map(|(s, &node)| DrawnShape { name: s.name.clone(), color: s.color, node })
+            .collect();
+        let materials = self
+            .materials
+            .iter()
+            .zip(material_nodes)
+            .map(|(m, &node)| MaterialRegion { name: m.name.clone(), node })
+            .collect();
+        Ok(ScriptOutput {
+            ctx,
+            root: nodes[0],
+            shapes,
+            scale: self.scale,
+            bounds: self.bounds,
+            unit: self.unit,
+            params,
+            vars: HashMap::new(),
+            imports: self.imports.clone(),
+            assertions: self.assertions.clone(),
+            materials,
+            flat: self.flat,
+        })
+    }
+}
+
+/// A `param()` declaration, as shown to the UI
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptParam {
+    pub name: String,
+    pub default: f64,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// Value the script saw, after overrides and clamping
+    pub value: f64,
+    /// Declared with `param_tree()`, so changing it doesn't re-run the script
+    pub symbolic: bool,
+}
+
+impl ScriptParam {
+    /// Value for this parameter given `overrides`, clamped to its range
+    fn resolve(&self, overrides: &HashMap<String, f64>) -> f64 {
+        let mut value = overrides.get(&self.name).copied().unwrap_or(self.default);
+        if let Some(min) = self.min {
+            value = value.max(min);
+        }
+        if let Some(max) = self.max {
+            value = value.min(max);
+        }
+        value
+    }
+}
+
+/// A shape passed to `draw()` or `draw_named()`
+struct DrawnShape {
+    name: Option<String>,
+    color: Option<Rgb>,
+    node: fidget::context::Node,
+}
+
+/// The part of the drawn shapes given one material
+struct MaterialRegion {
+    name: String,
+    node: fidget::context::Node,
+}
+
+/// Read a `[x, y, z]` array of numbers passed to a script function
+fn vec3_from_dynamic(name: &str, value: Dynamic) -> Result<[f32; 3], Box<EvalAltResult>> {
+    let array = value
+        .into_array()
+        .map_err(|_| format!("{} must be an array of three numbers", name))?;
+    if array.len() != 3 {
+        return Err(format!("{} must have exactly three elements", name).into());
+    }
+    let mut out = [0.0; 3];
+    for (o, v) in out.iter_mut().zip(array) {
+        *o = number_from_dynamic(name, &v)? as f32;
+    }
+    Ok(out)
+}
+
+/// Read a float or integer passed to a script function
+fn number_from_dynamic(name: &str, value: &Dynamic) -> Result<f64, Box<EvalAltResult>> {
+    match value.as_float() {
+        Ok(f) => Ok(f),
+        Err(_) => Ok(value.as_int().map_err(|_| format!("{} must be a number", name))? as f64),
+    }
+}
+
+/// Intersect `tree` with a box, so nothing outside `bounds` gets meshed
+fn clip_to_bounds(tree: Tree, bounds: &Bounds) -> Tree {
+    let center = bounds.center();
+    [Tree::x(), Tree::y(), Tree::z()]
+        .into_iter()
+        .enumerate()
+        .map(|(i, axis)| (axis - center[i]).abs() - (bounds.max[i] - bounds.min[i]) / 2.0)
+        .fold(tree, |clipped, slab| clipped.max(slab))
+}
+
+/// A Rhai engine with the modeling library, and the list it records imported files into
+///
+/// Scripts' `print()` and `debug()` output goes to the log panel with a
+/// "Script" source, and runs are stopped once they exceed the operation
+/// or time budget in `ScriptLimitsState`.  Files are resolved against
+/// `base_dir`.  The document-level functions (`draw`, `param`,
+/// `set_bounds` and so on) are left to the caller.
+fn modeling_engine(
+    host: &dyn Host,
+    base_dir: Option<&Path>,
+    cancel: &CancelToken,
+) -> (rhai::Engine, Arc<Mutex<Vec<PathBuf>>>) {
+    let mut engine = fidget::rhai::engine();
+    // Before horseCAD's own functions, so a plugin can't replace them
+    plugins::register(&mut engine, &host.plugin_functions());
+
+    let (resolver, imported) = DocumentResolver::new(base_dir, host.extensions());
+    engine.set_module_resolver(resolver);
+
+    let print_sink = host.log_sink();
+    engine.on_print(move |text| print_sink(&LogEntry::new("info", text, Some("Script"))));
+    let debug_sink = host.log_sink();
+    engine.on_debug(move |text, _source, pos| {
+        let message = match pos.line() {
+            Some(line) => format!("[line {}] {}", line, text),
+            None => text.to_string(),
+        };
+        debug_sink(&LogEntry::new("debug", &message, Some("Script")));
+    });
+
+    // Replaces fidget's default progress handler, which only limits steps
+    let cancel = cancel.clone();
+    let limits = host.script_limits();
+    let start = Instant::now();
+    engine.on_progress(move |count| {
+        if cancel.is_cancelled() {
+            Some("script cancelled".into())
+        } else if limits.max_operations > 0 && count > limits.max_operations {
+            Some(format!("script exceeded execution limit of {} operations", limits.max_operations).into())
+        } else if limits.timeout_ms > 0 && start.elapsed().as_millis() > u128::from(limits.timeout_ms) {
+            Some(format!("script exceeded execution limit of {} ms", limits.timeout_ms).into())
+        } else {
+            None
+        }
+    });
+    script::library::register(&mut engine);
+    script::sketch::register(&mut engine);
+    script::sweep::register(&mut engine);
+    script::operators::register(&mut engine);
+    script::hull::register(&mut engine);
+    script::assembly::register(&mut engine);
+    script::patterns::register(&mut engine);
+    let files = DocumentFiles::new(base_dir, imported.clone());
+    script::text::register(&mut engine, files.clone());
+    script::svg::register(&mut engine, files.clone());
+    script::dxf::register(&mut engine, files.clone());
+    script::heightmap::register(&mut engine, files.clone());
+    script::stl::register(&mut engine, files.clone());
+    script::vm::register(&mut engine, files);
+    (engine, imported)
+}
+
+/// Compile Rhai script using fidget engine
+///
+/// If the script calls `set_bounds`, the shape is clipped to that region.
+/// `param()` calls return the matching value from `overrides`, if present.
+/// `param_tree()` values are left as variables in the output, to be bound
+/// with `ScriptOutput::bind`.  `print()` and `debug()` output goes to the log
+/// panel with a "Script" source.
+///
+/// The run is stopped once it exceeds the operation or time budget in
+/// `ScriptLimitsState`.  `import` paths and files read by functions such as
+/// `text` and `import_svg` are resolved against the source's directory.
+fn compile_rhai_script(
+    host: &dyn Host,
+    source: &ScriptSource,
+    overrides: &HashMap<String, f64>,
+    cancel: &CancelToken,
+) -> Result<ScriptOutput> {
+    let (mut engine, imported) = modeling_engine(host, source.base_dir.as_deref(), cancel);
+    let out = Arc::new(Mutex::new(Vec::<(Option<String>, Option<Rgb>, Tree)>::new()));
+    let out_clone = out.clone();
+
+    // Functions registered here are documented in `script::api`
+    let scale = Arc::new(Mutex::new(None));
+    let scale_clone = scale.clone();
+
+    engine.register_fn(
+        "set_scale",
+        move |_ctx: NativeCallContext, scale_input: Dynamic| -> Result<(), Box<EvalAltResult>> {
+            let scale_input_float = scale_input.as_float();
+            if let Ok(scale_input_float) = scale_input_float {
+                let scale_input_f32 = scale_input_float as f32;
+                let mut scale = scale_clone.lock().unwrap();
+                *scale = Some(scale_input_f32);
+            } else {
+                return Err("scale must be a float".into());
+            }
+            Ok(())
+        },
+    );
+
+    let params = Arc::new(Mutex::new(Vec::<ScriptParam>::new()));
+    let declare_param = {
+        let params = params.clone();
+        let overrides = overrides.clone();
+        move |name: &str,
+              default: &Dynamic,
+              min: Option<&Dynamic>,
+              max: Option<&Dynamic>,
+              symbolic: bool|
+              -> Result<f64, Box<EvalAltResult>> {
+            let mut params = params.lock().unwrap_or_else(|e| e.into_inner());
+            // Repeated declarations see the same value as the first one
+            if let Some(existing) = params.iter().find(|p| p.name == name) {
+                if existing.symbolic != symbolic {
+                    return Err(format!("param '{}' is declared with both param() and param_tree()", name).into());
+                }
+                return Ok(existing.value);
+            }
+            let default = number_from_dynamic("param default", default)?;
+            let min = min.map(|m| number_from_dynamic("param min", m)).transpose()?;
+            let max = max.map(|m| number_from_dynamic("param max", m)).transpose()?;
+            if let (Some(min), Some(max)) = (min, max) {
+                if min > max {
+                    return Err(format!("param '{}' has min greater than max", name).into());
+                }
+            }
+            let mut param = ScriptParam { name: name.to_string(), default, min, max, value: default, symbolic };
+            param.value = param.resolve(&overrides);
+            let value = param.value;
+            params.push(param);
+            Ok(value)
+        }
+    };
+    let declare = declare_param.clone();
+    engine.register_fn("param", move |name: &str, default: Dynamic| declare(name, &default, None, None, false));
+    let declare = declare_param.clone();
+    engine.register_fn("param", move |name: &str, default: Dynamic, min: Dynamic, max: Dynamic| {
+        declare(name, &default, Some(&min), Some(&max), false)
+    });
+
+    // Symbolic parameters return a variable, bound to its value after the run
+    let vars = Arc::new(Mutex::new(HashMap::<String, Var>::new()));
+    let declare_tree = {
+        let vars = vars.clone();
+        move |name: &str, default: &Dynamic, min: Option<&Dynamic>, max: Option<&Dynamic>| -> Result<Tree, Box<EvalAltResult>> {
+            declare_param(name, default, min, max, true)?;
+            let var = *vars.lock().unwrap_or_else(|e| e.into_inner()).entry(name.to_string()).or_insert_with(Var::new);
+            Ok(Tree::from(var))
+        }
+    };
+    let declare = declare_tree.clone();
+    engine.register_fn("param_tree", move |name: &str, default: Dynamic| declare(name, &default, None, None));
+    engine.register_fn("param_tree", move |name: &str, default: Dynamic, min: Dynamic, max: Dynamic| {
+        declare_tree(name, &default, Some(&min), Some(&max))
+    });
+
+    let bounds = Arc::new(Mutex::new(None));
+    let bounds_clone = bounds.clone();
+
+    engine.register_fn(
+        "set_bounds",
+        move |_ctx: NativeCallContext, min: Dynamic, max: Dynamic| -> Result<(), Box<EvalAltResult>> {
+            let min = vec3_from_dynamic("bounds min", min)?;
+            let max = vec3_from_dynamic("bounds max", max)?;
+            if (0..3).any(|i| min[i] >= max[i]) {
+                return Err("bounds min must be less than max on every axis".into());
+            }
+            *bounds_clone.lock().unwrap_or_else(|e| e.into_inner()) = Some(Bounds { min, max });
+            Ok(())
+        },
+    );
+
+    let unit = Arc::new(Mutex::new(None));
+    let unit_clone = unit.clone();
+
+    let assertions = Arc::new(Mutex::new(Vec::new()));
+    script::assertions::register(&mut engine, assertions.clone());
+
+    let tagged = Arc::new(Mutex::new(Vec::new()));
+    script::materials::register(&mut engine, tagged.clone());
+
+    engine.register_fn("set_units", move |name: &str| -> Result<(), Box<EvalAltResult>> {
+        let Some(parsed) = ModelUnit::from_name(name) else {
+            return Err(format!("unknown unit '{}'; expected mm, cm, m, um, in or ft", name).into());
+        };
+        *unit_clone.lock().unwrap_or_else(|e| e.into_inner()) = Some(parsed);
+        Ok(())
+    });
+
+    // Register the draw functions; every drawn shape is added to the scene
+    let push_shape = move |ctx: &NativeCallContext,
+                           name: Option<&str>,
+                           d: Dynamic,
+                           color: Option<&str>|
+          -> Result<(), Box<EvalAltResult>> {
+        if name.is_some_and(|n| n.trim().is_empty()) {
+            return Err("part name must not be empty".into());
+        }
+        let color = color.map(parse_hex_color).transpose()?;
+        let tree = Tree::from_dynamic(ctx, d, None)?;
+        let mut out = out_clone.lock().unwrap();
+        if let Some(name) = name {
+            if out.iter().any(|(n, _, _)| n.as_deref() == Some(name)) {
+                return Err(format!("part '{}' is drawn more than once", name).into());
+            }
+        }
+        out.push((name.map(str::to_string), color, tree));
+        Ok(())
+    };
+    let push = push_shape.clone();
+    engine.register_fn("draw", move |ctx: NativeCallContext, d: Dynamic| push(&ctx, None, d, None));
+    let push = push_shape.clone();
+    engine.register_fn("draw", move |ctx: NativeCallContext, d: Dynamic, color: &str| {
+        push(&ctx, None, d, Some(color))
+    });
+    let push = push_shape.clone();
+    engine.register_fn("draw_named", move |ctx: NativeCallContext, name: &str, d: Dynamic| {
+        push(&ctx, Some(name), d, None)
+    });
+    let push = push_shape.clone();
+    engine.register_fn(
+        "draw_named",
+        move |ctx: NativeCallContext, name: &str, d: Dynamic, color: &str| push(&ctx, Some(name), d, Some(color)),
+    );
+    let drew_2d = Arc::new(Mutex::new(false));
+    let (push, marked) = (push_shape.clone(), drew_2d.clone());
+    engine.register_fn("draw2d", move |ctx: NativeCallContext, d: Dynamic| {
+        *marked.lock().unwrap_or_else(|e| e.into_inner()) = true;
+        push(&ctx, None, d, None)
+    });
+    let (push, marked) = (push_shape.clone(), drew_2d.clone());
+    engine.register_fn("draw2d", move |ctx: NativeCallContext, d: Dynamic, color: &str| {
+        *marked.lock().unwrap_or_else(|e| e.into_inner()) = true;
+        push(&ctx, None, d, Some(color))
+    });
+    engine.register_fn("draw", move |ctx: NativeCallContext, assembly: Assembly| -> Result<(), Box<EvalAltResult>> {
+        if assembly.parts.is_empty() {
+            return Err("assembly has no parts to draw".into());
+        }
+        for part in &assembly.parts {
+            push_shape(&ctx, Some(&part.name), Dynamic::from(part.placed()), part.color.as_deref())?;
+        }
+        Ok(())
+    });
+
+    
+    // Run the script; a terminated run reports why instead of "Script terminated"
+    engine.run(&source.code).map_err(|e| {
+        let reason = match &*e {
+            EvalAltResult::ErrorTerminated(reason, _) => Some(reason.to_string()),
+            _ => None,
+        };
+        let error = anyhow::Error::from(*e);
+        match reason {
+            Some(reason) => error.context(reason),
+            None => error,
+        }
+    })?;
+    
+    // Extract the result
+    let trees = std::mem::take(&mut *out.lock().unwrap());
+    
+    let output_scale = {
+        let guard = scale.lock().unwrap();
+        *guard
+    };
+    
+    let output_bounds = *bounds.lock().unwrap_or_else(|e| e.into_inner());
+    let output_unit = *unit.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut imports = std::mem::take(&mut *imported.lock().unwrap_or_else(|e| e.into_inner()));
+    imports.sort();
+    imports.dedup();
+    let imports = imports.into_iter().map(ImportStamp::new).collect();
+
+    // Clipping each shape also clips their union
+    let trees: Vec<_> = trees
+        .into_iter()
+        .map(|(name, color, tree)| match &output_bounds {
+            Some(bounds) => (name, color, clip_to_bounds(tree, bounds)),
+            None => (name, color, tree),
+        })
+        .collect();
+
+    if let Some(tree) = trees.iter().map(|(_, _, t)| t.clone()).reduce(|a, b| a.min(b)) {
+        let mut ctx = Context::new();
+        let node = ctx.import(&tree);
+        let shapes = trees
+            .iter()
+            .map(|(name, color, t)| DrawnShape { name: name.clone(), color: *color, node: ctx.import(t) })
+            .collect();
+        let flat = *drew_2d.lock().unwrap_or_else(|e| e.into_inner()) || !mesh::outline::depends_on_z(&ctx, node);
+        let tagged = std::mem::take(&mut *tagged.lock().unwrap_or_else(|e| e.into_inner()));
+        let materials = if tagged.is_empty() {
+            Vec::new()
+        } else {
+            material_regions(&tree, &tagged)
+                .into_iter()
+                .map(|(name, region)| MaterialRegion { name, node: ctx.import(&region) })
+                .collect()
+        };
+        let params = std::mem::take(&mut *params.lock().unwrap_or_else(|e| e.into_inner()));
+        let vars = std::mem::take(&mut *vars.lock().unwrap_or_else(|e| e.into_inner()));
+        Ok(ScriptOutput {
+            ctx,
+            root: node,
+            shapes,
+            scale: output_scale,
+            bounds: output_bounds,
+            unit: output_unit,
+            params,
+            vars,
+            imports,
+            assertions: std::mem::take(&mut *assertions.lock().unwrap_or_else(|e| e.into_inner())),
+            materials,
+            flat,
+        })
+    } else {
+        Err(anyhow::anyhow!("script must include a draw(tree) or draw_named(name, tree) call"))
+    }
+}
+
+/// Save .horsi file
+///
+/// The file is replaced atomically, so a crash mid-save leaves the previous
+/// version intact, and the previous version is first kept as a backup (see
+/// `SaveOptions::backups`).  `metadata` is written after the script;
+/// without it, whatever metadata the file already had is kept.
+#[tauri::command]
+async fn save_horsi_file(
+    app_handle: AppHandle,
+    window: Window,
+    save_options: State<'_, SaveOptionsState>,
+    path: String,
+    content: String,
+    metadata: Option<DocumentMetadata>,
+) -> Result<bool, String> {
+    let target = Path::new(&path);
+    let metadata = metadata.or_else(|| HorsiFile::parse(&fs::read_to_string(target).ok()?).metadata);
+    let text = HorsiFile { script: content.clone(), metadata }.to_text().inspect_err(|e| {
+        emit_log(&app_handle, "error", &format!("Failed to save file {}: {}", path, e), Some("File"));
+    })?;
+    if let Err(e) = rotate_backups(target, save_options.get().backups) {
+        // Still save; losing a backup is better than losing the edits
+        emit_log(&app_handle, "warn", &format!("Failed to back up {}: {}", path, e), Some("File"));
+    }
+    match write_atomically(target, text.as_bytes()) {
+        Ok(_) => {
+            let opened = app_handle.state::<OpenedDocuments>();
+            opened.track(window.label(), target, &text);
+            opened.set_dirty(window.label(), false);
+            remember_file(&app_handle, &path);
+            app_handle.state::<Recovery>().clear_path(&path);
+            record_version(&app_handle, &path, VersionReason::Save, &content);
+            emit_log(&app_handle, "info", &format!("Saved file: {}", path), Some("File"));
+            Ok(true)
+        }
+        Err(e) => {
+            let error_msg = format!("Failed to save file {}: {}", path, e);
+            emit_log(&app_handle, "error", &error_msg, Some("File"));
+            Err(error_msg)
+        }
+    }
+}
+
+/// Store a version of a script in the history, logging rather than failing if it can't be written
+fn record_version(app_handle: &AppHandle, script: &str, reason: VersionReason, content: &str) {
+    if let Err(e) = app_handle.state::<VersionHistory>().record(script, reason, content) {
+        emit_log(app_handle, "warn", &e, Some("File"));
+    }
+}
+
+/// Stored versions of a script, newest first
+///
+/// A saved script is looked up by `path`, and an unsaved one by its
+/// document id.  Versions are stored on every successful compile and save.
+#[tauri::command]
+fn list_versions(
+    window: Window,
+    history: State<'_, VersionHistory>,
+    document_id: Option<String>,
+    path: Option<String>,
+) -> Vec<VersionInfo> {
+    let document_key = WindowHost::new(&window).document_key(document_id.as_deref());
+    history.list(path.as_deref().unwrap_or(&document_key))
+}
+
+/// A stored version of a script, for the editor to load in place of its buffer
+///
+/// The file on disk isn't touched; the restored text is saved like any
+/// other edit.
+#[tauri::command]
+fn restore_version(
+    window: Window,
+    history: State<'_, VersionHistory>,
+    document_id: Option<String>,
+    path: Option<String>,
+    id: String,
+) -> Result<ScriptVersion, String> {
+    let host = WindowHost::new(&window);
+    let document_key = host.document_key(document_id.as_deref());
+    let Some(version) = history.get(path.as_deref().unwrap_or(&document_key), &id) else {
+        return Err(format!("No version {} is stored for this script", id));
+    };
+    emit_log(&host, "info", &format!("Restored the version from {}", version.saved_at), Some("File"));
+    Ok(version)
+}
+
+/// Load .horsi file
+///
+/// Returns the script alone; its saved settings and thumbnail are read with
+/// `load_horsi_metadata`.
+#[tauri::command]
+async fn load_horsi_file(app_handle: AppHandle, window: Window, path: String) -> Result<String, String> {
+    match fs::read_to_string(&path) {
+        Ok(text) => {
+            app_handle.state::<OpenedDocuments>().track(window.label(), Path::new(&path), &text);
+            remember_file(&app_handle, &path);
+            emit_log(&app_handle, "info", &format!("Loaded file: {}", path), Some("File"));
+            Ok(HorsiFile::parse(&text).script)
+        }
+        Err(e) => {
+            let error_msg = format!("Failed to load file {}: {}", path, e);
+            emit_log(&app_handle, "error", &error_msg, Some("File"));
+            Err(error_msg)
+        }
+    }
+}
+
+/// Read the settings and thumbnail saved in a .horsi file, if it has any
+#[tauri::command]
+async fn load_horsi_metadata(path: String) -> Result<Option<DocumentMetadata>, String> {
+    let text = fs::read_to_string(&path).map_err(|e| format!("Failed to load file {}: {}", path, e))?;
+    Ok(HorsiFile::parse(&text).metadata)
+}
+
+/// Pack the saved document at `document_path` and everything its script loads into a bundle at `path`
+///
+/// The script is run to find its imports and the assets it reads, such as
+/// SVGs, heightmaps and fonts, with the parameters saved in its metadata;
+/// unsaved edits aren't included.  The bundle is a ZIP that `open_bundle`
+/// extracts again; files from outside the document's folder are listed as
+/// external in the returned manifest rather than packed.
+#[tauri::command]
+async fn export_bundle(
+    app_handle: AppHandle,
+    extensions: State<'_, Extensions>,
+    document_path: String,
+    path: String,
+) -> Result<BundleManifest, String> {
+    let fail = |error_msg: String| {
+        emit_log(&app_handle, "error", &error_msg, Some("Export"));
+        error_msg
+    };
+    let text = fs::read_to_string(&document_path)
+        .map_err(|e| fail(format!("Failed to load file {}: {}", document_path, e)))?;
+    let file = HorsiFile::parse(&text);
+    let params = file.metadata.map(|m| m.params).unwrap_or_default();
+    let source = ScriptSource::new(file.script, Some(&document_path));
+    let modules = extensions.modules();
+    let worker_handle = app_handle.clone();
+    let worker_document = document_path.clone();
+    let bundled = tauri::async_runtime::spawn_blocking(move || {
+        let script = compile_rhai_script(&worker_handle, &source, &params, &CancelToken::new())
+            .map_err(|e| format!("The script must run to find the files it loads: {}", e))?;
+        let loaded: Vec<PathBuf> = script.imports.into_iter().map(|stamp| stamp.path).collect();
+        let generator = format!("horseCAD {}", env!("CARGO_PKG_VERSION"));
+        bundle::pack_bundle(Path::new(&worker_document), &loaded, &modules, &generator)
+            .map_err(|e| format!("Failed to pack the bundle: {:#}", e))
+    })
+    .await
+    .map_err(|e| fail(format!("Bundle worker failed: {}", e)))?
+    .map_err(fail)?;
+    let (data, manifest) = bundled;
+    write_atomically(Path::new(&path), &data).map_err(|e| fail(format!("Failed to save file {}: {}", path, e)))?;
+
+    let message = format!("Bundled {} file(s) into {}", manifest.files.len(), path);
+    emit_log(&app_handle, "info", &message, Some("Export"));
+    if !manifest.external.is_empty() {
+        let message = format!(
+            "Left out files from outside the document's folder, which won't open with the bundle: {}",
+            manifest.external.join(", ")
+        );
+        emit_log(&app_handle, "warn", &message, Some("Export"));
+    }
+    Ok(manifest)
+}
+
+/// Extract the bundle at `path` into the empty folder `dir`, returning the path of its document
+///
+/// The document is then opened with `load_horsi_file` as usual, and its
+/// imports and assets resolve from the files extracted next to it.
+#[tauri::command]
+async fn open_bundle(app_handle: AppHandle, path: String, dir: String) -> Result<String, String> {
+    let fail = |error_msg: String| {
+        emit_log(&app_handle, "error", &error_msg, Some("File"));
+        error_msg
+    };
+    let data = fs::read(&path).map_err(|e| fail(format!("Failed to load file {}: {}", path, e)))?;
+    let unpacked = tauri::async_runtime::spawn_blocking(move || bundle::unpack_bundle(&data, Path::new(&dir)));
+    let (document, manifest) = unpacked
+        .await
+        .map_err(|e| fail(format!("Bundle worker failed: {}", e)))?
+        .map_err(|e| fail(format!("Failed to open bundle {}: {:#}", path, e)))?;
+    let message = format!("Extracted {} file(s) from {} ({})", manifest.files.len(), path, manifest.generator);
+    emit_log(&app_handle, "info", &message, Some("File"));
+    Ok(document.to_string_lossy().into_owned())
+}
+
+/// Change the metadata of the script at `path` with `update`, leaving the script as it is on disk
+///
+/// Unsaved edits in the editor aren't saved along with it.
+fn update_metadata(
+    app_handle: &AppHandle,
+    path: &str,
+    update: impl FnOnce(&mut DocumentMetadata),
+) -> Result<(), String> {
+    let fail = |error_msg: String| {
+        emit_log(app_handle, "error", &error_msg, Some("File"));
+        error_msg
+    };
+    let target = Path::new(path);
+    let text = fs::read_to_string(target).map_err(|e| fail(format!("Failed to load file {}: {}", path, e)))?;
+    let mut file = HorsiFile::parse(&text);
+    let mut metadata = file.metadata.take().unwrap_or_default();
+    update(&mut metadata);
+    file.metadata = (metadata != DocumentMetadata::default()).then_some(metadata);
+    let text = file.to_text().map_err(fail)?;
+    write_atomically(target, text.as_bytes()).map_err(|e| fail(format!("Failed to save file {}: {}", path, e)))?;
+
+    app_handle.state::<OpenedDocuments>().retrack(target, &text);
+    Ok(())
+}
+
+/// Pin build settings to the script at `path`, or unpin them with `None`
+///
+/// Only the file's metadata changes; the script is written back as it is
+/// on disk, so unsaved edits in the editor aren't saved along with it.
+#[tauri::command]
+async fn set_project_settings(
+    app_handle: AppHandle,
+    path: String,
+    settings: Option<ProjectSettings>,
+) -> Result<(), String> {
+    update_metadata(&app_handle, &path, |metadata| metadata.project = settings)?;
+    emit_log(&app_handle, "info", &format!("Updated the build settings saved in {}", path), Some("File"));
+    Ok(())
+}
+
+/// Remember how the script at `path` was compiled, so the menu's Compile repeats it after reopening
+///
+/// Like `set_project_settings`, only the file's metadata is written.
+/// These are the editor's choices, not pinned settings, so the project's
+/// build settings still beat them.
+#[tauri::command]
+async fn save_compile_defaults(app_handle: AppHandle, path: String, defaults: CompileDefaults) -> Result<(), String> {
+    update_metadata(&app_handle, &path, |metadata| metadata.set_compile_defaults(defaults))?;
+    emit_log(&app_handle, "debug", &format!("Saved the compile settings in {}", path), Some("File"));
+    Ok(())
+}
+
+/// Save `camera` as the view called `name` in the script at `path`, or delete the view with `None`
+///
+/// Like `set_project_settings`, only the file's metadata is written.
+#[tauri::command]
+async fn save_view(
+    app_handle: AppHandle,
+    path: String,
+    name: String,
+    camera: Option<RenderCamera>,
+) -> Result<(), String> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("A view needs a name".to_string());
+    }
+    let message = match camera {
+        Some(_) => format!("Saved the view '{}' in {}", name, path),
+        None => format!("Deleted the view '{}' from {}", name, path),
+    };
+    update_metadata(&app_handle, &path, |metadata| match camera {
+        Some(camera) => {
+            metadata.views.insert(name, camera);
+        }
+        None => {
+            metadata.views.remove(&name);
+        }
+    })?;
+    emit_log(&app_handle, "info", &message, Some("File"));
+    Ok(())
+}
+
+/// Camera views saved in the script at `path`, by name
+#[tauri::command]
+async fn list_views(path: String) -> BTreeMap<String, RenderCamera> {
+    read_views(Path::new(&path))
+}
+
+/// The view called `view` saved in the document at `document_path`, or else `camera`
+fn view_camera(
+    document_path: Option<&str>,
+    camera: Option<RenderCamera>,
+    view: Option<&str>,
+) -> Result<RenderCamera, String> {
+    let Some(view) = view else {
+        return Ok(camera.unwrap_or_default());
+    };
+    let path = document_path.ok_or_else(|| format!("The view '{}' needs a saved document", view))?;
+    read_views(Path::new(path)).remove(view).ok_or_else(|| format!("No view called '{}' is saved in {}", view, path))
+}
+
+/// Compile settings saved in the file of the document open in `window`, if it has any
+fn tracked_compile_defaults(app: &AppHandle, window: &str) -> Option<CompileDefaults> {
+    let path = app.state::<OpenedDocuments>().tracked_path(window)?;
+    let metadata = HorsiFile::parse(&fs::read_to_string(path).ok()?).metadata?;
+    Some(metadata.compile_defaults()).filter(|d| *d != CompileDefaults::default())
+}
+
+/// Read the open document's file again, after `file_changed_externally`
+///
+/// The reloaded contents are taken as saved, clearing the dirty state.
+#[tauri::command]
+async fn reload_file(
+    app_handle: AppHandle,
+    window: Window,
+    opened: State<'_, OpenedDocuments>,
+) -> Result<String, String> {
+    let path = opened.tracked_path(window.label()).ok_or("No file is open to reload")?;
+    match fs::read_to_string(&path) {
+        Ok(text) => {
+            opened.track(window.label(), &path, &text);
+            opened.set_dirty(window.label(), false);
+            emit_log(&app_handle, "info", &format!("Reloaded file: {}", path.display()), Some("File"));
+            Ok(HorsiFile::parse(&text).script)
+        }
+        Err(e) => {
+            let error_msg = format!("Failed to reload file {}: {}", path.display(), e);
+            emit_log(&app_handle, "error", &error_msg, Some("File"));
+            Err(error_msg)
+        }
+    }
+}
+
+/// Scripts opened from outside the app before the frontend was ready for them
+///
+/// Call once the `open_document` listener is in place; later files are
+/// only sent as that event.
+#[tauri::command]
+fn take_opened_documents(opened: State<'_, OpenedDocuments>) -> Vec<OpenedDocument> {
+    opened.take()
+}
+
+/// Move a script to the top of the recent files list and the Open Recent menu
+///
+/// Scripts opened or saved through the backend are added on their own.
+#[tauri::command]
+fn add_recent_file(app_handle: AppHandle, recent: State<'_, RecentFiles>, path: String) {
+    recent.add(&app_handle, &path);
+}
+
+/// Recently opened and saved scripts, newest first
+#[tauri::command]
+fn get_recent_files(recent: State<'_, RecentFiles>) -> Vec<String> {
+    recent.files()
+}
+
+/// Keep the unsaved contents of the editor, to be autosaved in case the app crashes
+///
+/// Buffers are written to the recovery folder at the autosave interval, so
+/// this is cheap enough to call on every edit.
+#[tauri::command]
+fn update_autosave(
+    window: Window,
+    recovery: State<'_, Recovery>,
+    document_id: Option<String>,
+    path: Option<String>,
+    content: String,
+) {
+    recovery.update(&WindowHost::new(&window).document_key(document_id.as_deref()), path, content);
+}
+
+/// Drop the autosaved copy of a document whose changes were saved or discarded
+///
+/// Saving through `save_horsi_file` does this for documents with a path.
+#[tauri::command]
+fn clear_autosave(window: Window, recovery: State<'_, Recovery>, document_id: Option<String>) {
+    recovery.clear(&WindowHost::new(&window).document_key(document_id.as_deref()));
+}
+
+/// Unsaved documents autosaved by a run of the app that crashed, newest first
+#[tauri::command]
+fn get_recovered_documents(recovery: State<'_, Recovery>) -> Vec<RecoveredDocument> {
+    recovery.recovered()
+}
+
+/// A window's share of the last session, from `restore_session`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RestoredSession {
+    /// When the last run quit, or `None` if it didn't leave a session
+    saved_at: Option<String>,
+    documents: Vec<SessionDocument>,
+    /// Main window geometry and panel sizes from the last session
+    layout: Option<WindowState>,
+}
+
+/// Reopen what was open when the app last quit, for the calling window
+///
+/// The first window to call gets the documents the last run's main window
+/// had, and the last run's other windows are opened again; they get their
+/// documents when they call in turn.  Each document comes back with its
+/// path, the unsaved contents it had, which go back into autosave straight
+/// away, and the settings and parameters of its last compile, so the
+/// frontend can compile it again, usually straight from the disk cache.
+/// The main window's geometry is restored at startup before it loads.
+#[tauri::command]
+fn restore_session(
+    window: Window,
+    session: State<'_, SessionStore>,
+    recovery: State<'_, Recovery>,
+    settings: State<'_, SettingsStore>,
+) -> RestoredSession {
+    let app_handle = window.app_handle();
+    session.assign_windows(window.label(), || match open_document_window(app_handle) {
+        Ok(opened) => Some(opened.label().to_string()),
+        Err(e) => {
+            let message = format!("Failed to reopen a window from the last session: {}", e);
+            emit_log(app_handle, "warn", &message, Some("File"));
+            None
+        }
+    });
+    let host = WindowHost::new(&window);
+    let documents = session.take(window.label());
+    for document in &documents {
+        let key = host.document_key(document.document_id.as_deref());
+        if let Some(unsaved) = &document.unsaved {
+            recovery.update(&key, document.path.clone(), unsaved.clone());
+        }
+        // Tracked again at once, so quitting before the next compile keeps it
+        session.track(&key, window.label(), document.document_id.as_deref(), |tracked| {
+            *tracked = SessionDocument { unsaved: None, ..document.clone() };
+        });
+    }
+    if !documents.is_empty() {
+        let message = format!("Restored {} document(s) from the last session", documents.len());
+        emit_log(&host, "info", &message, Some("File"));
+    }
+    RestoredSession { saved_at: session.saved_at(), documents, layout: settings.get().window }
+}
+
+/// Record that the window has a document open, so the next session reopens it
+///
+/// Compiling a document records it too, with its compile settings.  Call
+/// this when a document is opened or saved under a new path.
+#[tauri::command]
+fn track_session_document(
+    window: Window,
+    session: State<'_, SessionStore>,
+    document_id: Option<String>,
+    path: Option<String>,
+) {
+    let key = WindowHost::new(&window).document_key(document_id.as_deref());
+    session.track(&key, window.label(), document_id.as_deref(), |document| document.path = path);
+}
+
+/// Leave a document the window closed out of the next session
+#[tauri::command]
+fn close_session_document(window: Window, session: State<'_, SessionStore>, document_id: Option<String>) {
+    session.close(&WindowHost::new(&window).document_key(document_id.as_deref()));
+}
+
+/// Delete the documents offered by `get_recovered_documents`, once restored or declined
+#[tauri::command]
+fn discard_recovered_documents(recovery: State<'_, Recovery>) {
+    recovery.discard_recovered();
+}
+
+/// Record whether the document open in this window has unsaved changes
+///
+/// Documents opened from outside the app report this back, as
+/// `replaces_unsaved`, so edits aren't dropped without asking.
+#[tauri::command]
+fn set_document_dirty(window: Window, opened: State<'_, OpenedDocuments>, dirty: bool) {
+    opened.set_dirty(window.label(), dirty);
+}
+
+/// Export STL file
+///
+/// If `format` differs from the encoding of `stl_data`, the mesh is to be
+/// repaired, simplified or transformed, or it's given a `part_name` or
+/// `script_name` for the header, the mesh is re-encoded before writing.
+/// So is a mesh whose script called `set_units`, which is scaled into
+/// millimeters as `export_last_mesh` does, using the units of the
+/// document's last compile in the window.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn export_stl_file(
+    app_handle: AppHandle,
+    window: Window,
+    document_id: Option<String>,
+    path: String,
+    stl_data: Vec<u8>,
+    format: Option<StlFormat>,
+    part_name: Option<String>,
+    script_name: Option<String>,
+    target_triangles: Option<usize>,
+    max_error: Option<f32>,
+    repair: Option<bool>,
+    transform: Option<ExportTransform>,
+) -> Result<bool, String> {
+    let current_format = if is_ascii_stl(&stl_data) { StlFormat::Ascii } else { StlFormat::Binary };
+    let format = format.unwrap_or(current_format);
+    let options = SimplifyOptions { target_triangles, max_error };
+    let repair = repair.unwrap_or(false);
+    let transform = export_transform_matrix(&app_handle, transform.as_ref())?;
+    let up_axis = export_up_axis(&app_handle, ExportFormat::Stl);
+    let units = stl_data_units(&window, document_id.as_deref(), ExportFormat::Stl, None);
+    let is_named = part_name.is_some() || script_name.is_some();
+    let is_processed =
+        options.is_enabled() || repair || units.is_some() || transform.is_some() || up_axis != UpAxis::Z;
+    let is_reencoded = format != current_format || is_named || is_processed;
+    let stl_data = if is_reencoded {
+        let (mesh, colors) =
+            export_mesh_from_stl_data(&app_handle, &stl_data, units, &options, repair, transform.as_ref(), up_axis)?;
+        let export = ExportOptions { name: part_name, script_name, ..Default::default() };
+        let encoded = export_mesh_to_stl(&mesh, format, &export.part_name(), export.script_name.as_deref());
+        let mut data = encoded.map_err(|e| {
+            let error_msg = format!("STL export failed: {}", e);
+            emit_log(&app_handle, "error", &error_msg, Some("Export"));
+            error_msg
+        })?;
+        if let (Some(colors), StlFormat::Binary) = (colors, format) {
+            apply_stl_colors(&mut data, &colors);
+        }
+        data
+    } else {
+        stl_data
+    };
+
+    match fs::write(&path, stl_data) {
+        Ok(_) => {
+            emit_log(&app_handle, "info", &format!("Exported STL: {}", path), Some("Export"));
+            Ok(true)
+        }
+        Err(e) => {
+            let error_msg = format!("Failed to export STL {}: {}", path, e);
+            emit_log(&app_handle, "error", &error_msg, Some("Export"));
+            Err(error_msg)
+        }
+    }
+}
+
+/// Export mesh data sent as a raw binary request body
+///
+/// The body holds STL bytes (binary or ASCII) from the last compile, and the
+/// `x-export-options` header a URI-encoded JSON `BinaryExportOptions` object.
+/// This avoids serializing the mesh as a JSON number array on the way in.
+/// Units are applied as `export_stl_file` applies them.
+#[tauri::command]
+async fn export_mesh_binary(app_handle: AppHandle, window: Window, request: Request<'_>) -> Result<bool, String> {
+    let InvokeBody::Raw(stl_data) = request.body() else {
+        return Err("export_mesh_binary expects a raw binary body".to_string());
+    };
+    let mut options: BinaryExportOptions = decode_json_header(request.headers(), EXPORT_OPTIONS_HEADER)?;
+
+    let up_axis = export_up_axis(&app_handle, options.format);
+    let units = stl_data_units(&window, options.document_id.as_deref(), options.format, options.export.unit);
+    if let Some((_, unit)) = units {
+        options.export.unit = Some(unit);
+    }
+    let is_passthrough = units.is_none()
+        && !options.simplify.is_enabled()
+        && !options.repair
+        && options.export.name.is_none()
+        && options.export.script_name.is_none()
+        && up_axis == UpAxis::Z
+        && match options.format {
+            ExportFormat::Stl => !is_ascii_stl(stl_data),
+            ExportFormat::StlAscii => is_ascii_stl(stl_data),
+            _ => false,
+        };
+    let data = if is_passthrough {
+        stl_data.clone()
+    } else {
+        let (mesh, colors) =
+            export_mesh_from_stl_data(&app_handle, stl_data, units, &options.simplify, options.repair, None, up_axis)?;
+        export_mesh(&mesh, colors.as_deref(), options.format, &options.export).map_err(|e| {
+            let error_msg = format!("{} export failed: {}", options.format.label(), e);
+            emit_log(&app_handle, "error", &error_msg, Some("Export"));
+            error_msg
+        })?
+    };
+    write_export_file(&app_handle, &options.path, &data, options.format.label())
+}
+
+/// Write exported mesh data to disk, logging the outcome
+fn write_export_file(host: &dyn Host, path: &str, data: &[u8], format_name: &str) -> Result<bool, String> {
+    match fs::write(path, data) {
+        Ok(_) => {
+            emit_log(host, "info", &format!("Exported {}: {}", format_name, path), Some("Export"));
+            Ok(true)
+        }
+        Err(e) => {
+            let error_msg = format!("Failed to export {} {}: {}", format_name, path, e);
+            emit_log(host, "error", &error_msg, Some("Export"));
+            Err(error_msg)
+        }
+    }
+}
+
+/// Rebuild the compiled mesh from the STL bytes held by the frontend
+///
+/// The frontend only holds the STL bytes from the last compile, so other
+/// formats are converted from those rather than compiling the script again.
+fn mesh_from_stl_data(host: &dyn Host, stl_data: &[u8]) -> Result<fidget::mesh::Mesh, String> {
+    parse_stl(stl_data).map_err(|e| {
+        let error_msg = format!("Failed to read mesh data: {}", e);
+        emit_log(host, "error", &error_msg, Some("Export"));
+        error_msg
+    })
+}
+
+/// Per-triangle colors, with `None` for uncolored triangles
+type TriangleColors = Vec<Option<Rgb>>;
+
+/// Check an export transform, logging why it can't be used
+fn export_transform_matrix(
+    host: &dyn Host,
+    transform: Option<&ExportTransform>,
+) -> Result<Option<Matrix4<f32>>, String> {
+    transform.map(|t| t.to_matrix()).transpose().inspect_err(|error_msg| {
+        emit_log(host, "error", error_msg, Some("Export"));
+    })
+}
+
+/// Up axis for a `format` export, from the settings
+fn export_up_axis(app_handle: &AppHandle, format: ExportFormat) -> UpAxis {
+    app_handle.state::<SettingsStore>().get().export_up_axis.for_format(format)
+}
+
+/// How to scale mesh data from the last compile of a document in `window` into the unit a `format` export is in
+///
+/// `None` if the script didn't call `set_units`, or the document hasn't
+/// been compiled in the window; the mesh is written as it is then.
+fn stl_data_units(
+    window: &Window,
+    document_id: Option<&str>,
+    format: ExportFormat,
+    requested: Option<ModelUnit>,
+) -> Option<(MeshUnits, ModelUnit)> {
+    let key = WindowHost::new(window).document_key(document_id);
+    let units = window.state::<MeshCache>().get(&key)?.units;
+    Some((units, units.export_unit(format, requested)?))
+}
+
+/// Rebuild the compiled mesh and its triangle colors for export, prepared as `prepare_export_mesh` does
+///
+/// With `units`, the mesh is first scaled into model coordinates in the
+/// unit given, as `MeshUnits::convert` does.
+fn export_mesh_from_stl_data(
+    host: &dyn Host,
+    stl_data: &[u8],
+    units: Option<(MeshUnits, ModelUnit)>,
+    options: &SimplifyOptions,
+    repair: bool,
+    transform: Option<&Matrix4<f32>>,
+    up_axis: UpAxis,
+) -> Result<(Mesh, Option<TriangleColors>), String> {
+    let mut mesh = mesh_from_stl_data(host, stl_data)?;
+    if let Some((units, unit)) = units {
+        mesh = units.convert(&mesh, unit);
+    }
+    let colors = parse_stl_colors(stl_data);
+    Ok(prepare_export_mesh(host, mesh, colors, options, repair, transform, up_axis))
+}
+
+/// Repair, simplify, transform and orient a mesh for export as requested, keeping its triangle colors in step
+fn prepare_export_mesh(
+    host: &dyn Host,
+    mut mesh: Mesh,
+    mut colors: Option<TriangleColors>,
+    options: &SimplifyOptions,
+    repair: bool,
+    transform: Option<&Matrix4<f32>>,
+    up_axis: UpAxis,
+) -> (Mesh, Option<TriangleColors>) {
+    if repair {
+        let repaired = repair_mesh(&mesh);
+        emit_log(host, "info", &repaired.report.summary(), Some("Export"));
+        colors = colors.map(|colors| repaired.sources.iter().map(|&t| colors[t]).collect());
+        mesh = repaired.mesh;
+    }
+    if options.is_enabled() {
+        let simplified = simplify_mesh(host, &mesh, options);
+        colors = colors.map(|colors| simplified.sources.iter().map(|&t| colors[t]).collect());
+        mesh = simplified.mesh;
+    }
+    if let Some(transform) = transform {
+        mesh = transform_mesh(mesh, transform);
+    }
+    (up_axis.orient(mesh), colors)
+}
+
+/// Check mesh data from the last compile for holes, non-manifold edges and inverted normals
+#[tauri::command]
+async fn validate_mesh(app_handle: AppHandle, stl_data: Vec<u8>) -> Result<MeshReport, String> {
+    let mesh = mesh_from_stl_data(&app_handle, &stl_data)?;
+    Ok(mesh::validate::validate_mesh(&mesh))
+}
+
+/// Measure the volume, surface area and bounding box of mesh data from the last compile
+#[tauri::command]
+async fn measure_mesh(app_handle: AppHandle, stl_data: Vec<u8>) -> Result<MeshMeasurements, String> {
+    let mesh = mesh_from_stl_data(&app_handle, &stl_data)?;
+    Ok(mesh::measure::measure_mesh(&mesh))
+}
+
+/// Run `f` on a compiled mesh in model coordinates and millimeters
+///
+/// Scripts without `set_units` are taken to be in millimeters already.
+fn with_mesh_in_millimeters<T>(last: &LastMesh, f: impl FnOnce(&Mesh) -> T) -> T {
+    let converted = match last.units.export_unit(ExportFormat::Stl, None) {
+        Some(unit) => Some(last.units.convert(&last.mesh, unit)),
+        None => last.units.model_mesh(&last.mesh),
+    };
+    f(converted.as_ref().unwrap_or(&last.mesh))
+}
+
+/// Measure a compiled mesh in model coordinates and millimeters
+fn measure_in_millimeters(last: &LastMesh) -> MeshMeasurements {
+    with_mesh_in_millimeters(last, mesh::measure::measure_mesh)
+}
+
+/// Estimate printing a compiled mesh with the print profile in the settings
+fn estimate_last_print(app_handle: &AppHandle, last: &LastMesh) -> PrintEstimate {
+    let profile = app_handle.state::<SettingsStore>().get().print;
+    estimate_print(&measure_in_millimeters(last), &profile)
+}
+
+/// Compare the solids of a document's last two compiles
+///
+/// Both meshes are compared in millimeters, as exports are, on a grid with
+/// `resolution` cells along the longest side.  A script refactored without
+/// changing its geometry reports `unchanged`; otherwise the added and
+/// removed volume and where it is are listed.
+#[tauri::command]
+async fn diff_last_compiles(
+    window: Window,
+    mesh_cache: State<'_, MeshCache>,
+    document_id: Option<String>,
+    resolution: Option<usize>,
+) -> Result<MeshDiff, String> {
+    let host = WindowHost::new(&window);
+    let document_id = host.document_key(document_id.as_deref());
+    let (Some(before), Some(after)) = (mesh_cache.previous(&document_id), mesh_cache.get(&document_id)) else {
+        return Err("Compile at least twice to compare the last two results".to_string());
+    };
+    let resolution = resolution.unwrap_or(DEFAULT_DIFF_RESOLUTION);
+    if !(2..=MAX_DIFF_RESOLUTION).contains(&resolution) {
+        return Err(format!("Diff resolution must be between 2 and {}", MAX_DIFF_RESOLUTION));
+    }
+    let diff = tauri::async_runtime::spawn_blocking(move || {
+        let in_millimeters = |last: &LastMesh| last.units.convert(&last.mesh, ModelUnit::Millimeter);
+        diff_meshes(&in_millimeters(&before), &in_millimeters(&after), resolution)
+    })
+    .await
+    .map_err(|e| format!("Diff worker failed: {}", e))?;
+
+    let message = if diff.unchanged {
+        "Geometry unchanged since the previous compile".to_string()
+    } else {
+        format!(
+            "Since the previous compile: {:.3} mm³ added, {:.3} mm³ removed in {} region(s)",
+            diff.added_volume,
+            diff.removed_volume,
+            diff.regions.len()
+        )
+    };
+    emit_log(&host, "info", &message, Some("Analysis"));
+    Ok(diff)
+}
+
+/// Check whether a document's last compile fits on the print bed
+///
+/// `bed` overrides the size kept in settings.  Both are in millimeters, as
+/// are meshes from scripts that don't call `set_units`, and the model is
+/// measured where the script placed it rather than where it was meshed.  A
+/// model that won't fit is logged as a warning.
+#[tauri::command]
+fn check_bed_fit(
+    window: Window,
+    mesh_cache: State<'_, MeshCache>,
+    settings: State<'_, SettingsStore>,
+    document_id: Option<String>,
+    bed: Option<[f32; 3]>,
+) -> Result<BedFit, String> {
+    let host = WindowHost::new(&window);
+    let Some(last) = mesh_cache.get(&host.document_key(document_id.as_deref())) else {
+        return Err("Nothing has been compiled yet to check".to_string());
+    };
+    let bed = bed.unwrap_or(settings.get().print_bed);
+    if !bed.iter().all(|&size| size.is_finite() && size > 0.0) {
+        return Err("Every side of the print bed must be greater than zero".to_string());
+    }
+    let fit = bed_fit(measure_in_millimeters(&last).bounds, bed);
+    if !fit.fits {
+        let [x, y, z] = fit.size;
+        let [bx, by, bz] = fit.bed;
+        let hint = if fit.fits_rotated { "; it fits turned a quarter turn" } else { "" };
+        let message = format!(
+            "Model is {:.1} × {:.1} × {:.1} mm and won't fit the {} × {} × {} mm bed{}",
+            x, y, z, bx, by, bz, hint
+        );
+        emit_log(&host, "warn", &message, Some("Analysis"));
+    }
+    Ok(fit)
+}
+
+/// The document's last evaluated script, bound with `params`, for querying at viewport points
+///
+/// Also returns the map from viewport to model coordinates, which is only
+/// more than the identity when the last mesh was scaled into the unit cube;
+/// distances in the field are always in model units.
+fn viewport_shape(
+    host: &dyn Host,
+    to_model: Option<nalgebra::Matrix4<f32>>,
+    document_id: &str,
+    params: &HashMap<String, f64>,
+) -> Result<(ScriptOutput, VmShape, nalgebra::Matrix4<f32>), String> {
+    let script = script_for_render(host, document_id, None, params, &CancelToken::new())?;
+    let shape = VmShape::new(&script.ctx, script.root).map_err(|e| format!("Shape creation failed: {}", e))?;
+    Ok((script, shape, to_model.unwrap_or_else(nalgebra::Matrix4::identity)))
+}
+
+/// Evaluate the document's last compiled shape at a point in the viewport
+///
+/// Returns the signed distance there, negative inside, so clearances and
+/// boolean logic can be checked numerically.  `params` should be the values
+/// the shape was last compiled with.
+#[tauri::command]
+async fn evaluate_at(
+    window: Window,
+    mesh_cache: State<'_, MeshCache>,
+    document_id: Option<String>,
+    params: Option<HashMap<String, f64>>,
+    x: f32,
+    y: f32,
+    z: f32,
+) -> Result<Probe, String> {
+    if ![x, y, z].iter().all(|v| v.is_finite()) {
+        return Err("Point coordinates must be finite".to_string());
+    }
+    let params = params.unwrap_or_default();
+    let host = WindowHost::new(&window);
+    let document_id = host.document_key(document_id.as_deref());
+    let to_model = mesh_cache.get(&document_id).and_then(|last| last.units.to_model);
+
+    let worker_handle = host.clone();
+    let probed = tauri::async_runtime::spawn_blocking(move || -> Result<Probe, String> {
+        let (_, shape, to_model) = viewport_shape(&worker_handle, to_model, &document_id, &params)?;
+        let p = to_model.transform_point(&nalgebra::Point3::new(x, y, z));
+        probe_point(&shape, [p.x, p.y, p.z]).map_err(|e| format!("Evaluation failed: {}", e))
     })
+    .await;
+    let probe = probed.map_err(|e| format!("Analysis worker failed: {}", e))?.inspect_err(|e| {
+        emit_log(&host, "error", e, Some("Analysis"));
+    })?;
+    let side = if probe.inside { "inside" } else { "outside" };
+    let message = format!("Distance at ({}, {}, {}) is {} ({})", x, y, z, probe.distance, side);
+    emit_log(&host, "info", &message, Some("Analysis"));
+    Ok(probe)
 }
 
-/// Compile Rhai script using fidget engine
-fn compile_rhai_script(code: &str) -> Result<(Context, fidget::context::Node, f32)> {
-    let mut engine = fidget::rhai::engine();
-    let out = Arc::new(Mutex::new(None));
-    let out_clone = out.clone();
+/// Gradient of the document's last compiled shape at a viewport point
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct SurfaceGradient {
+    /// Partial derivatives of the distance in model units
+    gradient: [f32; 3],
+    /// Unit surface normal, for placing annotations flush with the surface
+    normal: [f32; 3],
+    /// Draft angle in degrees against the requested pull direction
+    draft_angle: Option<f32>,
+}
 
-    let scale = Arc::new(Mutex::new(1.0_f32)); // Default scale, can be adjusted or passed as parameter if needed
-    let scale_clone = scale.clone();
+/// Evaluate the gradient of the document's last compiled shape at a point in the viewport
+///
+/// The gradient comes from fidget's automatic differentiation rather than
+/// finite differences, so it's exact wherever the field is smooth.  Passing
+/// `pull` also returns the draft angle for a mold opening in that
+/// direction.  `params` should be the values the shape was last compiled with.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn gradient_at(
+    window: Window,
+    mesh_cache: State<'_, MeshCache>,
+    document_id: Option<String>,
+    params: Option<HashMap<String, f64>>,
+    x: f32,
+    y: f32,
+    z: f32,
+    pull: Option<[f32; 3]>,
+) -> Result<SurfaceGradient, String> {
+    if ![x, y, z].iter().chain(pull.iter().flatten()).all(|v| v.is_finite()) {
+        return Err("Point and pull direction must be finite".to_string());
+    }
+    let pull = pull.map(nalgebra::Vector3::from);
+    if pull.is_some_and(|pull| pull.norm() == 0.0) {
+        return Err("Pull direction can't be zero".to_string());
+    }
+    let params = params.unwrap_or_default();
+    let host = WindowHost::new(&window);
+    let document_id = host.document_key(document_id.as_deref());
+    let to_model = mesh_cache.get(&document_id).and_then(|last| last.units.to_model);
 
-    engine.register_fn(
-        "set_scale",
-        move |_ctx: NativeCallContext, scale_input: Dynamic| -> Result<(), Box<EvalAltResult>> {
-            let scale_input_float = scale_input.as_float();
-            if let Ok(scale_input_float) = scale_input_float {
-                let scale_input_f32 = scale_input_float as f32;
-                let mut scale = scale_clone.lock().unwrap();
-                *scale = scale_input_f32;
-            } else {
-                return Err("scale must be a float".into());
-            }
-            Ok(())
-        },
-    );
+    let worker_handle = host.clone();
+    let probed = tauri::async_runtime::spawn_blocking(move || -> Result<Probe, String> {
+        let (_, shape, to_model) = viewport_shape(&worker_handle, to_model, &document_id, &params)?;
+        let p = to_model.transform_point(&nalgebra::Point3::new(x, y, z));
+        probe_point(&shape, [p.x, p.y, p.z]).map_err(|e| format!("Evaluation failed: {}", e))
+    })
+    .await;
+    let probe = probed.map_err(|e| format!("Analysis worker failed: {}", e))?.inspect_err(|e| {
+        emit_log(&host, "error", e, Some("Analysis"));
+    })?;
+    if probe.normal == [0.0; 3] {
+        let message = format!("Field is flat at ({}, {}, {}), so it has no normal", x, y, z);
+        emit_log(&host, "warn", &message, Some("Analysis"));
+    }
+    Ok(SurfaceGradient {
+        gradient: probe.gradient,
+        normal: probe.normal,
+        draft_angle: pull.map(|pull| draft_angle(probe.normal, pull)),
+    })
+}
 
-    // Register the draw function
-    engine.register_fn(
-        "draw",
-        move |ctx: NativeCallContext, d: Dynamic| -> Result<(), Box<EvalAltResult>> {
-            let tree = Tree::from_dynamic(&ctx, d, None)?;
-            let mut out = out_clone.lock().unwrap();
-            if out.is_some() {
-                return Err("can only draw one shape".into());
-            }
-            *out = Some(tree);
-            Ok(())
-        },
-    );
+/// Cast a ray from `origin` along `direction` at the document's last compiled shape
+///
+/// The ray is sphere-traced through the distance field, so the hit is on
+/// the exact surface rather than the mesh approximating it.  Both the ray
+/// and the hit are in viewport coordinates.  Returns `None` if the ray
+/// misses.  `params` should be the values the shape was last compiled with.
+#[tauri::command]
+async fn raycast(
+    window: Window,
+    mesh_cache: State<'_, MeshCache>,
+    document_id: Option<String>,
+    params: Option<HashMap<String, f64>>,
+    origin: [f32; 3],
+    direction: [f32; 3],
+) -> Result<Option<RayHit>, String> {
+    let origin = nalgebra::Point3::from(origin);
+    let direction = nalgebra::Vector3::from(direction);
+    if !origin.iter().chain(direction.iter()).all(|v| v.is_finite()) {
+        return Err("Ray origin and direction must be finite".to_string());
+    }
+    if direction.norm() == 0.0 {
+        return Err("Ray direction can't be zero".to_string());
+    }
+    let params = params.unwrap_or_default();
+    let host = WindowHost::new(&window);
+    let document_id = host.document_key(document_id.as_deref());
+    let to_model = mesh_cache.get(&document_id).and_then(|last| last.units.to_model);
 
-    
-    // Run the script
-    engine.run(code)?;
-    
-    // Extract the result
-    let tree = {
-        let mut guard = out.lock().unwrap();
-        guard.take()
+    let worker_handle = host.clone();
+    let traced = tauri::async_runtime::spawn_blocking(move || -> Result<Option<RayHit>, String> {
+        let (script, shape, to_model) = viewport_shape(&worker_handle, to_model, &document_id, &params)?;
+        let to_viewport = to_model.try_inverse().ok_or_else(|| "Viewport transform is singular".to_string())?;
+        let model_origin = to_model.transform_point(&origin);
+        let model_direction = to_model.transform_vector(&direction).normalize();
+        let hit = trace_ray(&shape, model_origin.coords, model_direction, script_region(&script, &shape))
+            .map_err(|e| format!("Ray cast failed: {}", e))?;
+        Ok(hit.map(|hit| {
+            let point = to_viewport.transform_point(&hit.point.into());
+            RayHit { point: point.into(), normal: hit.normal, distance: (point - origin).norm() }
+        }))
+    })
+    .await;
+    traced.map_err(|e| format!("Analysis worker failed: {}", e))?.inspect_err(|e| {
+        emit_log(&host, "error", e, Some("Analysis"));
+    })
+}
+
+/// Measure between points picked in the viewport, such as `raycast` hits
+///
+/// Two points give their distance and offset along each axis; a third adds
+/// the angle at the second.  Lengths are in model units, so they match the
+/// script even when the mesh was scaled into the unit cube for meshing.
+#[tauri::command]
+fn measure(
+    window: Window,
+    mesh_cache: State<'_, MeshCache>,
+    document_id: Option<String>,
+    points: Vec<[f32; 3]>,
+) -> Result<PointMeasurement, String> {
+    if !points.iter().flatten().all(|v| v.is_finite()) {
+        return Err("Point coordinates must be finite".to_string());
+    }
+    let host = WindowHost::new(&window);
+    let last = mesh_cache.get(&host.document_key(document_id.as_deref()));
+    let to_model = last.as_ref().and_then(|last| last.units.to_model).unwrap_or_else(nalgebra::Matrix4::identity);
+    let points: Vec<_> =
+        points.into_iter().map(|p| to_model.transform_point(&nalgebra::Point3::from(p)).coords).collect();
+    let measured = measure_points(&points)?;
+
+    let unit = last.and_then(|last| last.units.unit).map_or(String::new(), |unit| format!(" {}", unit.symbol()));
+    let [dx, dy, dz] = measured.delta;
+    let mut message = format!("Distance {}{} (Δ {}, {}, {})", measured.distance, unit, dx, dy, dz);
+    if let Some(angle) = measured.angle {
+        message.push_str(&format!(", angle {:.2}°", angle));
+    }
+    emit_log(&host, "info", &message, Some("Analysis"));
+    Ok(measured)
+}
+
+/// Recent log entries at or above `level` from `source`, oldest first
+///
+/// Up to `limit` of the newest matching entries are returned, from the last
+/// `MAX_LOG_HISTORY` logged, so the log panel can be refilled after a reload.
+#[tauri::command]
+fn get_logs(
+    history: State<'_, LogHistory>,
+    level: Option<LogLevel>,
+    source: Option<String>,
+    limit: Option<usize>,
+) -> Vec<LogEntry> {
+    history.query(level.unwrap_or_default(), source.as_deref(), limit.unwrap_or(MAX_LOG_HISTORY))
+}
+
+/// Only send log entries at or above `level` to the frontend from now on
+///
+/// Quieter entries are still kept for `get_logs` and written to the log file.
+#[tauri::command]
+fn set_log_level(
+    app_handle: AppHandle,
+    settings: State<'_, SettingsStore>,
+    level: LogLevel,
+) -> Result<LogLevel, String> {
+    settings.update(&app_handle, |settings| settings.log_level = level)?;
+    Ok(level)
+}
+
+/// Compiles recorded this session, with statistics over them
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CompileHistoryReport {
+    /// Oldest first
+    pub records: Vec<CompileRecord>,
+    pub stats: CompileStats,
+}
+
+/// This session's compiles of `document_id`, or of every document in the window if it's omitted
+///
+/// Compare `stats.latest_change_ms` or the records' timings to see whether
+/// recent edits made meshing faster or slower.
+#[tauri::command]
+fn get_compile_history(
+    window: Window,
+    compile_history: State<'_, CompileHistory>,
+    document_id: Option<String>,
+) -> CompileHistoryReport {
+    let host = WindowHost::new(&window);
+    let records = match document_id {
+        Some(id) => compile_history.query(&host.document_key(Some(&id))),
+        None => compile_history.query_prefix(&host.document_prefix()),
     };
-    
-    let output_scale = {
-        let guard = scale.lock().unwrap();
-        *guard
+    let stats = CompileStats::of(&records);
+    CompileHistoryReport { records, stats }
+}
+
+/// Write the recent log entries to `path` for a bug report, returning how many were written
+///
+/// The entries come with the app version, OS, default depth and the
+/// timings of the last compile, which is usually what a report needs.
+#[tauri::command]
+fn export_logs(
+    app_handle: AppHandle,
+    history: State<'_, LogHistory>,
+    settings: State<'_, SettingsStore>,
+    compile_history: State<'_, CompileHistory>,
+    path: String,
+    format: Option<LogExportFormat>,
+) -> Result<usize, String> {
+    let report = LogReport {
+        app_version: app_handle.package_info().version.to_string(),
+        os: std::env::consts::OS.to_string(),
+        arch: std::env::consts::ARCH.to_string(),
+        default_depth: settings.get().default_depth,
+        last_compile: compile_history.last(),
+        entries: history.query(LogLevel::Debug, None, MAX_LOG_HISTORY),
     };
-    
-    if let Some(tree) = tree {
-        let mut ctx = Context::new();
-        let node = ctx.import(&tree);
-        Ok((ctx, node, output_scale))
-    } else {
-        Err(anyhow::anyhow!("script must include a draw(tree) call"))
-    }
+    let data = report
+        .encode(format.unwrap_or_default())
+        .inspect_err(|e| emit_log(&app_handle, "error", e, Some("System")))?;
+    write_export_file(&app_handle, &path, &data, "logs")?;
+    Ok(report.entries.len())
 }
 
-/// Export mesh to STL format
-fn export_mesh_to_stl(mesh: &Mesh) -> Result<Vec<u8>> {
-    let mut buffer = Vec::new();
-    mesh.write_stl(&mut buffer)
-        .context("Failed to write STL data")?;
-    Ok(buffer)
+/// Show the log file in the system file manager, returning its path
+///
+/// The file keeps every log entry across sessions, rotated daily and by
+/// size; see `LogFile`.
+#[tauri::command]
+fn reveal_log_file(app_handle: AppHandle, log_file: State<'_, LogFile>) -> Result<String, String> {
+    let path = log_file.path().ok_or_else(|| "There's no app data folder to keep a log file in".to_string())?;
+    if !path.exists() {
+        return Err(format!("Nothing has been logged to {} yet", path.display()));
+    }
+    app_handle
+        .opener()
+        .reveal_item_in_dir(&path)
+        .map_err(|e| format!("Failed to reveal the log file {}: {}", path.display(), e))?;
+    Ok(path.to_string_lossy().into_owned())
 }
 
-/// Save .horsi file
+/// Measure a document's last compile and compute its mass properties
+///
+/// The mesh is measured in millimeters, as `check_bed_fit` does.  `density`
+/// is in mass per cubic millimeter; without it, `material` or else the
+/// print profile's material gives the density in grams and a price, and
+/// with neither the density is 1, which makes the mass equal to the volume.
 #[tauri::command]
-async fn save_horsi_file(app_handle: AppHandle, path: String, content: String) -> Result<bool, String> {
-    match fs::write(&path, content) {
-        Ok(_) => {
-            emit_log(&app_handle, "info", &format!("Saved file: {}", path), Some("File"));
-            Ok(true)
-        }
-        Err(e) => {
-            let error_msg = format!("Failed to save file {}: {}", path, e);
-            emit_log(&app_handle, "error", &error_msg, Some("File"));
-            Err(error_msg)
+async fn analyze_mesh(
+    window: Window,
+    mesh_cache: State<'_, MeshCache>,
+    settings: State<'_, SettingsStore>,
+    document_id: Option<String>,
+    density: Option<f64>,
+    material: Option<Material>,
+) -> Result<MeshAnalysis, String> {
+    let host = WindowHost::new(&window);
+    let Some(last) = mesh_cache.get(&host.document_key(document_id.as_deref())) else {
+        return Err("Nothing has been compiled yet to analyze".to_string());
+    };
+    // An explicit density isn't mixed with the price of a material it may not be
+    let material = material.or_else(|| density.is_none().then(|| settings.get().print.material).flatten());
+    let preset = material.map(Material::preset);
+    // Grams per cubic centimeter is a thousandth of a gram per cubic millimeter
+    let density = density.or(preset.map(|p| p.density / 1000.0)).unwrap_or(1.0);
+    if density.is_nan() || density <= 0.0 {
+        return Err("Density must be greater than zero".to_string());
+    }
+    let analysis = with_mesh_in_millimeters(&last, |model| {
+        let mass = mass_properties(model, density);
+        MeshAnalysis {
+            measurements: mesh::measure::measure_mesh(model),
+            cost: preset.map(|p| mass.mass / 1000.0 * p.cost_per_kg),
+            mass,
+            validation: mesh::validate::validate_mesh(model),
         }
+    });
+    let center = analysis.mass.center_of_mass;
+    emit_log(
+        &host,
+        "info",
+        &format!(
+            "Mass {:.3} with center of mass at ({:.3}, {:.3}, {:.3})",
+            analysis.mass.mass, center[0], center[1], center[2]
+        ),
+        Some("Analysis"),
+    );
+    Ok(analysis)
+}
+
+/// Find overhangs in mesh data from the last compile when printed along `build_direction`
+///
+/// The build direction defaults to +Z and `max_angle` to 45°, measured from
+/// vertical.
+#[tauri::command]
+async fn analyze_overhangs(
+    app_handle: AppHandle,
+    stl_data: Vec<u8>,
+    build_direction: Option<[f32; 3]>,
+    max_angle: Option<f32>,
+) -> Result<OverhangReport, String> {
+    let direction = nalgebra::Vector3::from(build_direction.unwrap_or([0.0, 0.0, 1.0]));
+    let length = direction.norm();
+    if !length.is_finite() || length <= 0.0 {
+        return Err("Build direction must be a finite, non-zero vector".to_string());
+    }
+    let max_angle = max_angle.unwrap_or(45.0);
+    if !(0.0..90.0).contains(&max_angle) {
+        return Err("Maximum overhang angle must be at least 0 and less than 90 degrees".to_string());
     }
+    let mesh = mesh_from_stl_data(&app_handle, &stl_data)?;
+    let report = overhang_report(&mesh, direction, max_angle);
+    emit_log(
+        &app_handle,
+        if report.regions.is_empty() { "info" } else { "warn" },
+        &format!(
+            "{} overhang regions past {}° ({:.3} of surface)",
+            report.regions.len() + report.omitted_regions,
+            max_angle,
+            report.overhang_area
+        ),
+        Some("Analysis"),
+    );
+    Ok(report)
 }
 
-/// Load .horsi file
+/// Build pillar supports under the overhangs of mesh data from the last compile, and write them to `path` as STL
+///
+/// The build direction defaults to +Z; see `generate_supports` for how the
+/// pillars are placed.  The supports are a separate mesh in the same
+/// coordinates as `stl_data`, so they line up with the part's own export
+/// when both are loaded into a slicer.
 #[tauri::command]
-async fn load_horsi_file(app_handle: AppHandle, path: String) -> Result<String, String> {
-    match fs::read_to_string(&path) {
-        Ok(content) => {
-            emit_log(&app_handle, "info", &format!("Loaded file: {}", path), Some("File"));
-            Ok(content)
-        }
-        Err(e) => {
-            let error_msg = format!("Failed to load file {}: {}", path, e);
-            emit_log(&app_handle, "error", &error_msg, Some("File"));
-            Err(error_msg)
-        }
+async fn export_supports(
+    app_handle: AppHandle,
+    stl_data: Vec<u8>,
+    path: String,
+    build_direction: Option<[f32; 3]>,
+    options: Option<SupportOptions>,
+    stl_format: Option<StlFormat>,
+) -> Result<SupportReport, String> {
+    let direction = nalgebra::Vector3::from(build_direction.unwrap_or([0.0, 0.0, 1.0]));
+    let length = direction.norm();
+    if !length.is_finite() || length <= 0.0 {
+        return Err("Build direction must be a finite, non-zero vector".to_string());
+    }
+    let options = options.unwrap_or_default();
+    if !(0.0..90.0).contains(&options.max_angle) {
+        return Err("Maximum overhang angle must be at least 0 and less than 90 degrees".to_string());
     }
+    let mesh = mesh_from_stl_data(&app_handle, &stl_data)?;
+    let supports = generate_supports(&mesh, direction, &options).inspect_err(|e| {
+        emit_log(&app_handle, "error", &format!("Support generation failed: {}", e), Some("Export"));
+    })?;
+    if supports.report.pillars == 0 {
+        let error_msg = format!("Nothing overhangs past {}°, so no supports are needed", options.max_angle);
+        emit_log(&app_handle, "warn", &error_msg, Some("Export"));
+        return Err(error_msg);
+    }
+    let data = export_mesh_to_stl(&supports.mesh, stl_format.unwrap_or_default(), "supports", None)
+        .map_err(|e| format!("STL export of supports failed: {}", e))?;
+    write_export_file(&app_handle, &path, &data, "STL")?;
+    emit_log(
+        &app_handle,
+        "info",
+        &format!(
+            "Built {} support pillars ({} standing on the part), {} apart",
+            supports.report.pillars, supports.report.on_part, supports.report.spacing
+        ),
+        Some("Export"),
+    );
+    Ok(supports.report)
 }
 
-/// Export STL file
+/// Export OBJ file
 #[tauri::command]
-async fn export_stl_file(app_handle: AppHandle, path: String, stl_data: Vec<u8>) -> Result<bool, String> {
-    match fs::write(&path, stl_data) {
-        Ok(_) => {
-            emit_log(&app_handle, "info", &format!("Exported STL: {}", path), Some("Export"));
-            Ok(true)
-        }
-        Err(e) => {
-            let error_msg = format!("Failed to export STL {}: {}", path, e);
-            emit_log(&app_handle, "error", &error_msg, Some("Export"));
-            Err(error_msg)
+#[allow(clippy::too_many_arguments)]
+async fn export_obj_file(
+    app_handle: AppHandle,
+    window: Window,
+    document_id: Option<String>,
+    path: String,
+    stl_data: Vec<u8>,
+    target_triangles: Option<usize>,
+    max_error: Option<f32>,
+    repair: Option<bool>,
+    normals: Option<NormalOptions>,
+) -> Result<bool, String> {
+    let simplify = SimplifyOptions { target_triangles, max_error };
+    let up_axis = export_up_axis(&app_handle, ExportFormat::Obj);
+    let units = stl_data_units(&window, document_id.as_deref(), ExportFormat::Obj, None);
+    let (mesh, _) =
+        export_mesh_from_stl_data(&app_handle, &stl_data, units, &simplify, repair.unwrap_or(false), None, up_axis)?;
+    let obj_data = export_mesh_to_obj(&mesh, &normals.unwrap_or_default()).map_err(|e| {
+        let error_msg = format!("OBJ export failed: {}", e);
+        emit_log(&app_handle, "error", &error_msg, Some("Export"));
+        error_msg
+    })?;
+    write_export_file(&app_handle, &path, &obj_data, "OBJ")
+}
+
+/// Export binary PLY file
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn export_ply_file(
+    app_handle: AppHandle,
+    window: Window,
+    document_id: Option<String>,
+    path: String,
+    stl_data: Vec<u8>,
+    target_triangles: Option<usize>,
+    max_error: Option<f32>,
+    repair: Option<bool>,
+    normals: Option<NormalOptions>,
+) -> Result<bool, String> {
+    let simplify = SimplifyOptions { target_triangles, max_error };
+    let up_axis = export_up_axis(&app_handle, ExportFormat::Ply);
+    let units = stl_data_units(&window, document_id.as_deref(), ExportFormat::Ply, None);
+    let (mesh, colors) =
+        export_mesh_from_stl_data(&app_handle, &stl_data, units, &simplify, repair.unwrap_or(false), None, up_axis)?;
+    let ply_data = export_mesh_to_ply(&mesh, colors.as_deref(), &normals.unwrap_or_default()).map_err(|e| {
+        let error_msg = format!("PLY export failed: {}", e);
+        emit_log(&app_handle, "error", &error_msg, Some("Export"));
+        error_msg
+    })?;
+    write_export_file(&app_handle, &path, &ply_data, "PLY")
+}
+
+/// Export OFF file for CGAL-based and other geometry processing tools
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn export_off_file(
+    app_handle: AppHandle,
+    window: Window,
+    document_id: Option<String>,
+    path: String,
+    stl_data: Vec<u8>,
+    target_triangles: Option<usize>,
+    max_error: Option<f32>,
+    repair: Option<bool>,
+) -> Result<bool, String> {
+    let simplify = SimplifyOptions { target_triangles, max_error };
+    let up_axis = export_up_axis(&app_handle, ExportFormat::Off);
+    let units = stl_data_units(&window, document_id.as_deref(), ExportFormat::Off, None);
+    let (mesh, colors) =
+        export_mesh_from_stl_data(&app_handle, &stl_data, units, &simplify, repair.unwrap_or(false), None, up_axis)?;
+    let off_data = export_mesh_to_off(&mesh, colors.as_deref()).map_err(|e| {
+        let error_msg = format!("OFF export failed: {}", e);
+        emit_log(&app_handle, "error", &error_msg, Some("Export"));
+        error_msg
+    })?;
+    write_export_file(&app_handle, &path, &off_data, "OFF")
+}
+
+/// Export GLB file for web viewers
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn export_glb_file(
+    app_handle: AppHandle,
+    window: Window,
+    document_id: Option<String>,
+    path: String,
+    stl_data: Vec<u8>,
+    name: Option<String>,
+    target_triangles: Option<usize>,
+    max_error: Option<f32>,
+    repair: Option<bool>,
+    normals: Option<NormalOptions>,
+) -> Result<bool, String> {
+    let simplify = SimplifyOptions { target_triangles, max_error };
+    let up_axis = export_up_axis(&app_handle, ExportFormat::Glb);
+    let units = stl_data_units(&window, document_id.as_deref(), ExportFormat::Glb, None);
+    let (mesh, _) =
+        export_mesh_from_stl_data(&app_handle, &stl_data, units, &simplify, repair.unwrap_or(false), None, up_axis)?;
+    let name = name.unwrap_or_else(|| "horsecad_model".to_string());
+    let glb_data = export_mesh_to_glb(&mesh, &name, &normals.unwrap_or_default()).map_err(|e| {
+        let error_msg = format!("GLB export failed: {}", e);
+        emit_log(&app_handle, "error", &error_msg, Some("Export"));
+        error_msg
+    })?;
+    write_export_file(&app_handle, &path, &glb_data, "GLB")
+}
+
+/// Export 3MF file with units and document metadata
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn export_3mf_file(
+    app_handle: AppHandle,
+    window: Window,
+    document_id: Option<String>,
+    path: String,
+    stl_data: Vec<u8>,
+    unit: Option<ModelUnit>,
+    script_name: Option<String>,
+    part_name: Option<String>,
+    target_triangles: Option<usize>,
+    max_error: Option<f32>,
+    repair: Option<bool>,
+) -> Result<bool, String> {
+    let simplify = SimplifyOptions { target_triangles, max_error };
+    let up_axis = export_up_axis(&app_handle, ExportFormat::ThreeMf);
+    let units = stl_data_units(&window, document_id.as_deref(), ExportFormat::ThreeMf, unit);
+    let (mesh, colors) =
+        export_mesh_from_stl_data(&app_handle, &stl_data, units, &simplify, repair.unwrap_or(false), None, up_axis)?;
+    let unit = units.map(|(_, unit)| unit).or(unit);
+    let options = ExportOptions { name: part_name, unit, script_name, ..Default::default() };
+
+    let data = export_mesh(&mesh, colors.as_deref(), ExportFormat::ThreeMf, &options).map_err(|e| {
+        let error_msg = format!("3MF export failed: {}", e);
+        emit_log(&app_handle, "error", &error_msg, Some("Export"));
+        error_msg
+    })?;
+    write_export_file(&app_handle, &path, &data, "3MF")
+}
+
+/// Export AMF file with units and per-object metadata
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn export_amf_file(
+    app_handle: AppHandle,
+    window: Window,
+    document_id: Option<String>,
+    path: String,
+    stl_data: Vec<u8>,
+    unit: Option<ModelUnit>,
+    script_name: Option<String>,
+    part_name: Option<String>,
+    target_triangles: Option<usize>,
+    max_error: Option<f32>,
+    repair: Option<bool>,
+) -> Result<bool, String> {
+    let simplify = SimplifyOptions { target_triangles, max_error };
+    let up_axis = export_up_axis(&app_handle, ExportFormat::Amf);
+    let units = stl_data_units(&window, document_id.as_deref(), ExportFormat::Amf, unit);
+    let (mesh, colors) =
+        export_mesh_from_stl_data(&app_handle, &stl_data, units, &simplify, repair.unwrap_or(false), None, up_axis)?;
+    let unit = units.map(|(_, unit)| unit).or(unit);
+    let options = ExportOptions { name: part_name, unit, script_name, ..Default::default() };
+
+    let data = export_mesh(&mesh, colors.as_deref(), ExportFormat::Amf, &options).map_err(|e| {
+        let error_msg = format!("AMF export failed: {}", e);
+        emit_log(&app_handle, "error", &error_msg, Some("Export"));
+        error_msg
+    })?;
+    write_export_file(&app_handle, &path, &data, "AMF")
+}
+
+/// Export the mesh from a document's last compile in the calling window
+///
+/// The mesh is kept by the backend after each successful compile, so
+/// nothing is sent over IPC and nothing is re-meshed.  Options match the
+/// per-format export commands; `unit`, `script_name` and `part_name` are
+/// used by the formats that record them.  Settings pinned in the file at
+/// `document_path` take precedence, except `format`, which falls back to
+/// the pinned format and then the default one only if not given.  If the
+/// script called `set_units`, vertices are scaled into the unit written,
+/// before `transform` is applied.  With `report`, or the `export_reports`
+/// setting if it isn't given, an `ExportReport` is written next to the file.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn export_last_mesh(
+    window: Window,
+    mesh_cache: State<'_, MeshCache>,
+    settings: State<'_, SettingsStore>,
+    document_id: Option<String>,
+    document_path: Option<String>,
+    path: String,
+    format: Option<ExportFormat>,
+    unit: Option<ModelUnit>,
+    script_name: Option<String>,
+    part_name: Option<String>,
+    target_triangles: Option<usize>,
+    max_error: Option<f32>,
+    repair: Option<bool>,
+    transform: Option<ExportTransform>,
+    normals: Option<NormalOptions>,
+    precision: Option<PrecisionOptions>,
+    report: Option<bool>,
+) -> Result<bool, String> {
+    let host = WindowHost::new(&window);
+    let transform = export_transform_matrix(&host, transform.as_ref())?;
+    let Some(last) = mesh_cache.get(&host.document_key(document_id.as_deref())) else {
+        let error_msg = "Nothing has been compiled yet to export".to_string();
+        emit_log(&host, "error", &error_msg, Some("Export"));
+        return Err(error_msg);
+    };
+    let preferences = settings.get();
+    let editor = BuildSettings {
+        target_triangles,
+        max_error,
+        repair,
+        up_axis: Some(preferences.export_up_axis),
+        ..Default::default()
+    };
+    let (build, project) = with_project_settings(&host, document_path.as_deref(), editor);
+    let project = project.unwrap_or_default();
+    let format = format.or(project.export_format).unwrap_or(preferences.default_export_format);
+    let unit = project.unit.or(unit);
+    let simplify = SimplifyOptions { target_triangles: build.target_triangles, max_error: build.max_error };
+    let repair = build.repair.unwrap_or(false);
+    let export_unit = last.units.export_unit(format, unit);
+    let up_axis = build.up_axis.unwrap_or_default().for_format(format);
+    // Only copy the cached mesh when something is going to change it
+    let processed;
+    let is_processed =
+        simplify.is_enabled() || repair || export_unit.is_some() || transform.is_some() || up_axis != UpAxis::Z;
+    let (mesh, colors) = if is_processed {
+        let copy = match export_unit {
+            Some(export_unit) => last.units.convert(&last.mesh, export_unit),
+            None => Mesh { vertices: last.mesh.vertices.clone(), triangles: last.mesh.triangles.clone() },
+        };
+        let colors = last.triangle_colors.clone();
+        processed = prepare_export_mesh(&host, copy, colors, &simplify, repair, transform.as_ref(), up_axis);
+        (&processed.0, processed.1.as_deref())
+    } else {
+        (&last.mesh, last.triangle_colors.as_deref())
+    };
+    let options = ExportOptions {
+        name: part_name,
+        unit: export_unit.or(unit),
+        script_name,
+        normals: normals.unwrap_or_default(),
+        precision: precision.unwrap_or_default(),
+    };
+    let data = export_mesh(mesh, colors, format, &options).map_err(|e| {
+        let error_msg = format!("{} export failed: {}", format.label(), e);
+        emit_log(&host, "error", &error_msg, Some("Export"));
+        error_msg
+    })?;
+    let written = write_export_file(&host, &path, &data, format.label())?;
+    if report.unwrap_or(preferences.export_reports) {
+        // The export itself succeeded, so a report that can't be written is only a warning
+        let report = ExportReport::new(Path::new(&path), format, options.unit, mesh, last.origin.as_ref());
+        let report_path = report_path(Path::new(&path));
+        match write_json(&report_path, &report) {
+            Ok(()) => emit_log(&host, "info", &format!("Wrote report: {}", report_path.display()), Some("Export")),
+            Err(e) => {
+                let message = format!("Failed to write report {}: {}", report_path.display(), e);
+                emit_log(&host, "warn", &message, Some("Export"));
+            }
         }
     }
+    Ok(written)
 }
 
 /// Show save dialog for .horsi files
@@ -334,8 +6835,112 @@ async fn show_stl_save_dialog(app_handle: AppHandle) -> Result<Option<String>, S
     }
 }
 
+/// Show a native save dialog filtered to a single export format
+fn show_export_save_dialog(
+    app_handle: &AppHandle,
+    filter_name: &str,
+    extension: &str,
+    title: &str,
+) -> Result<Option<String>, String> {
+    use std::sync::mpsc;
+    let (tx, rx) = mpsc::channel();
+
+    app_handle.dialog()
+        .file()
+        .add_filter(filter_name, &[extension])
+        .set_title(title)
+        .save_file(move |path| {
+            let _ = tx.send(path);
+        });
+
+    match rx.recv() {
+        Ok(Some(path)) => Ok(Some(path.to_string())),
+        Ok(None) => Ok(None),
+        Err(_) => Err("Dialog error".to_string()),
+    }
+}
+
+/// Show save dialog for OBJ files
+/// Show a folder picker for `export_parts`
+#[tauri::command]
+async fn show_folder_dialog(app_handle: AppHandle) -> Result<Option<String>, String> {
+    use std::sync::mpsc;
+    let (tx, rx) = mpsc::channel();
+
+    app_handle.dialog()
+        .file()
+        .set_title("Export Parts To Folder")
+        .pick_folder(move |path| {
+            let _ = tx.send(path);
+        });
+
+    match rx.recv() {
+        Ok(Some(path)) => Ok(Some(path.to_string())),
+        Ok(None) => Ok(None),
+        Err(_) => Err("Dialog error".to_string()),
+    }
+}
+
+#[tauri::command]
+async fn show_obj_save_dialog(app_handle: AppHandle) -> Result<Option<String>, String> {
+    show_export_save_dialog(&app_handle, "OBJ Files", "obj", "Export OBJ File")
+}
+
+/// Show save dialog for PLY files
+#[tauri::command]
+async fn show_ply_save_dialog(app_handle: AppHandle) -> Result<Option<String>, String> {
+    show_export_save_dialog(&app_handle, "PLY Files", "ply", "Export PLY File")
+}
+
+/// Show save dialog for OFF files
+#[tauri::command]
+async fn show_off_save_dialog(app_handle: AppHandle) -> Result<Option<String>, String> {
+    show_export_save_dialog(&app_handle, "OFF Files", "off", "Export OFF File")
+}
+
+/// Show save dialog for GLB files
+#[tauri::command]
+async fn show_glb_save_dialog(app_handle: AppHandle) -> Result<Option<String>, String> {
+    show_export_save_dialog(&app_handle, "glTF Binary", "glb", "Export GLB File")
+}
+
+/// Show save dialog for 3MF files
+#[tauri::command]
+async fn show_3mf_save_dialog(app_handle: AppHandle) -> Result<Option<String>, String> {
+    show_export_save_dialog(&app_handle, "3MF Files", "3mf", "Export 3MF File")
+}
+
+/// Show save dialog for AMF files
+#[tauri::command]
+async fn show_amf_save_dialog(app_handle: AppHandle) -> Result<Option<String>, String> {
+    show_export_save_dialog(&app_handle, "AMF Files", "amf", "Export AMF File")
+}
+
+/// Show save dialog for `export_archive`
+#[tauri::command]
+async fn show_archive_save_dialog(app_handle: AppHandle) -> Result<Option<String>, String> {
+    show_export_save_dialog(&app_handle, "ZIP Archives", "zip", "Export Parts Archive")
+}
 
 /// Basic greet function (keeping for compatibility)
+/// Open another editor window, with documents, logs and compiles of its own
+fn open_document_window(app_handle: &AppHandle) -> tauri::Result<WebviewWindow> {
+    static NEXT_WINDOW: AtomicUsize = AtomicUsize::new(1);
+    let label = format!("document-{}", NEXT_WINDOW.fetch_add(1, Ordering::Relaxed));
+    tauri::WebviewWindowBuilder::new(app_handle, label, tauri::WebviewUrl::default())
+        .title("horseCAD")
+        .inner_size(800.0, 600.0)
+        .build()
+}
+
+/// Open a new editor window, returning its label
+#[tauri::command]
+fn new_window(app_handle: AppHandle) -> Result<String, String> {
+    open_document_window(&app_handle)
+        .map(|window| window.label().to_string())
+        .map_err(|e| format!("Failed to open a new window: {}", e))
+}
+
 #[tauri::command]
 fn greet(name: &str) -> String {
     format!("Hello, {}! You've been greeted from Rust!", name)
@@ -346,26 +6951,225 @@ pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
+        .manage(CompileState::default())
+        .manage(ExportQueue::default())
+        .manage(ScriptCache::default())
+        .manage(ScriptLimitsState::default())
+        .manage(LogHistory::default())
+        .manage(CompileHistory::default())
+        .manage(ConsoleState::default())
+        .manage(MeshOptionsState::default())
+        .manage(WatchState::default())
+        .manage(MeshCache::default())
+        .manage(SnapshotStore::default())
+        .manage(SaveOptionsState::default())
+        .manage(OpenedDocuments::new())
+        .manage(HttpApi::default())
         .invoke_handler(tauri::generate_handler![
             greet,
+            new_window,
             compile_script,
+            compile_preview,
+            compile_snippet,
+            hollow_model,
+            cancel_compile,
+            watch_file,
+            unwatch_file,
+            get_watched_mesh,
+            get_script_limits,
+            set_script_limits,
+            set_execution_profile,
+            get_mesh_options,
+            set_mesh_options,
+            get_save_options,
+            get_settings,
+            set_settings,
+            set_panel_layout,
+            set_save_options,
+            get_parameters,
+            check_script,
+            get_script_symbols,
+            get_api_reference,
+            list_plugins,
+            export_with_plugin,
+            export_parts,
+            export_archive,
+            export_materials,
+            diff_last_compiles,
+            list_snapshots,
+            restore_snapshot,
+            list_versions,
+            restore_version,
+            batch_export,
+            queue_exports,
+            cancel_export,
+            list_exports,
+            check_wall_thickness,
+            suggest_depth,
+            render_image,
+            benchmark_shape,
+            export_turntable,
+            export_param_animation,
+            slice_shape,
+            export_slices_svg,
+            export_slices_dxf,
+            export_outline_svg,
+            export_outline_dxf,
             save_horsi_file,
             load_horsi_file,
+            load_horsi_metadata,
+            export_bundle,
+            open_bundle,
+            set_project_settings,
+            save_compile_defaults,
+            save_view,
+            render_dimensions,
+            list_views,
+            take_opened_documents,
+            reload_file,
+            set_document_dirty,
+            add_recent_file,
+            get_recent_files,
+            update_autosave,
+            clear_autosave,
+            get_recovered_documents,
+            discard_recovered_documents,
+            restore_session,
+            track_session_document,
+            close_session_document,
             export_stl_file,
+            export_obj_file,
+            export_ply_file,
+            export_glb_file,
+            export_3mf_file,
+            export_amf_file,
+            export_off_file,
+            export_mesh_binary,
+            export_last_mesh,
+            validate_mesh,
+            measure_mesh,
+            export_sdf_grid,
+            export_point_cloud_file,
+            export_glsl,
+            compile_gpu_preview,
+            stream_mesh,
+            save_compiled_shape,
+            check_bed_fit,
+            reveal_log_file,
+            get_logs,
+            set_log_level,
+            export_logs,
+            get_compile_history,
+            eval_expression,
+            reset_console,
+            format_script,
+            import_openscad,
+            reload_extensions,
+            list_libraries,
+            list_material_presets,
+            install_library,
+            list_templates,
+            load_template,
+            evaluate_at,
+            gradient_at,
+            raycast,
+            measure,
+            analyze_mesh,
+            analyze_overhangs,
+            export_supports,
             show_save_dialog,
             show_open_dialog,
-            show_stl_save_dialog
+            show_stl_save_dialog,
+            show_folder_dialog,
+            show_obj_save_dialog,
+            show_ply_save_dialog,
+            show_glb_save_dialog,
+            show_3mf_save_dialog,
+            show_amf_save_dialog,
+            show_archive_save_dialog,
+            show_off_save_dialog
         ])
+        .on_window_event(|window, event| match event {
+            tauri::WindowEvent::DragDrop(tauri::DragDropEvent::Drop { paths, .. }) => {
+                open_dropped(window, paths);
+            }
+            tauri::WindowEvent::Focused(true) => check_external_change(window.app_handle(), window.label()),
+            tauri::WindowEvent::Destroyed => {
+                window.app_handle().state::<OpenedDocuments>().forget(window.label());
+                window.app_handle().state::<WatchState>().unwatch(window.label());
+                let last = !window.app_handle().webview_windows().keys().any(|label| label != window.label());
+                window.app_handle().state::<SessionStore>().close_window(window.label(), last);
+                let prefix = WindowHost::new(window).document_prefix();
+                window.app_handle().state::<MeshCache>().remove_prefix(&prefix);
+                window.app_handle().state::<SnapshotStore>().remove_prefix(&prefix);
+            }
+            // Only the main window's geometry is restored at startup
+            tauri::WindowEvent::CloseRequested { .. } if window.label() == "main" => {
+                let app_handle = window.app_handle();
+                let store = app_handle.state::<SettingsStore>();
+                let saved = store.update(app_handle, |settings| {
+                    let state = settings.window.get_or_insert_with(Default::default);
+                    if let Err(e) = state.capture(window) {
+                        eprintln!("Failed to read the window geometry: {}", e);
+                    }
+                });
+                if let Err(e) = saved {
+                    eprintln!("Failed to save the window state: {}", e);
+                }
+            }
+            _ => {}
+        })
         .setup(|app| {
+            app.manage(LogFile::new(app.path().app_data_dir().ok().map(|dir| dir.join("logs"))));
+            let extensions_dir = app.path().app_config_dir().ok().map(|dir| dir.join("extensions"));
+            let (extensions, found) = Extensions::load(extensions_dir);
+            log_extensions(app.handle(), &found);
+            app.manage(extensions);
+            let plugins = Plugins::load(app.path().app_config_dir().ok().map(|dir| dir.join("plugins")));
+            log_plugins(app.handle(), plugins.found());
+            app.manage(plugins);
+            let recent_store = app.path().app_data_dir().ok().map(|dir| dir.join("recent_files.json"));
+            app.manage(RecentFiles::load(recent_store));
+            app.manage(Recovery::new(app.path().app_data_dir().ok().map(|dir| dir.join("recovery"))));
+            app.manage(SessionStore::load(app.path().app_data_dir().ok().map(|dir| dir.join("session.json"))));
+            app.manage(VersionHistory::new(app.path().app_data_dir().ok().map(|dir| dir.join("history"))));
+            let disk_cache =
+                app.path().app_cache_dir().ok().map(|dir| DiskCache::new(dir.join("meshes"), DEFAULT_DISK_CACHE_BYTES));
+            app.manage(CompileCache::new(DEFAULT_COMPILE_CACHE_BYTES, disk_cache));
+            let (settings, problem) = SettingsStore::load(app.path().app_config_dir().ok().map(|dir| dir.join("settings.json")));
+            apply_settings(app.handle(), &settings.get());
+            if let (Some(window), Some(state)) = (app.get_webview_window("main"), settings.get().window) {
+                if let Err(e) = state.restore(&window.as_ref().window()) {
+                    eprintln!("Failed to restore the window state: {}", e);
+                }
+            }
+            app.manage(settings);
+            if let Some(problem) = problem {
+                emit_log(app.handle(), "warn", &format!("{}; using the default settings", problem), Some("System"));
+            }
+            spawn_autosave(app.handle().clone());
+
             // Create the menu
+            let recent_menu = SubmenuBuilder::new(app, "Open Recent").build()?;
             let file_menu = SubmenuBuilder::new(app, "File")
                 .item(&MenuItemBuilder::with_id("new", "New").accelerator("CmdOrCtrl+N").build(app)?)
+                .item(&MenuItemBuilder::with_id("new_window", "New Window").accelerator("CmdOrCtrl+Shift+N").build(app)?)
+                .item(&template_menu(app.handle())?)
                 .item(&MenuItemBuilder::with_id("open", "Open...").accelerator("CmdOrCtrl+O").build(app)?)
+                .item(&recent_menu)
                 .separator()
                 .item(&MenuItemBuilder::with_id("save", "Save").accelerator("CmdOrCtrl+S").build(app)?)
                 .item(&MenuItemBuilder::with_id("save_as", "Save As...").accelerator("CmdOrCtrl+Shift+S").build(app)?)
                 .separator()
                 .item(&MenuItemBuilder::with_id("export_stl", "Export STL...").accelerator("CmdOrCtrl+E").build(app)?)
+                .item(&MenuItemBuilder::with_id("export_obj", "Export OBJ...").build(app)?)
+                .item(&MenuItemBuilder::with_id("export_3mf", "Export 3MF...").build(app)?)
+                .item(&MenuItemBuilder::with_id("export_amf", "Export AMF...").build(app)?)
+                .item(&MenuItemBuilder::with_id("export_ply", "Export PLY...").build(app)?)
+                .item(&MenuItemBuilder::with_id("export_off", "Export OFF...").build(app)?)
+                .item(&MenuItemBuilder::with_id("export_glb", "Export GLB...").build(app)?)
+                .item(&MenuItemBuilder::with_id("export_parts", "Export Parts...").build(app)?)
+                .item(&MenuItemBuilder::with_id("export_archive", "Export Parts Archive...").build(app)?)
                 .separator()
                 .item(&PredefinedMenuItem::quit(app, Some("Quit"))?)
                 .build()?;
@@ -380,8 +7184,18 @@ pub fn run() {
                 .item(&PredefinedMenuItem::select_all(app, Some("Select All"))?)
                 .build()?;
 
+            let quality_menu = SubmenuBuilder::new(app, "Quality")
+                .item(&MenuItemBuilder::with_id("quality_draft", "Draft").build(app)?)
+                .item(&MenuItemBuilder::with_id("quality_normal", "Normal").build(app)?)
+                .item(&MenuItemBuilder::with_id("quality_fine", "Fine").build(app)?)
+                .build()?;
+
+            let limits_menu = SubmenuBuilder::new(app, "Limits").build()?;
             let view_menu = SubmenuBuilder::new(app, "View")
                 .item(&MenuItemBuilder::with_id("compile", "Compile").accelerator("CmdOrCtrl+R").build(app)?)
+                .item(&MenuItemBuilder::with_id("cancel_compile", "Cancel Compile").accelerator("CmdOrCtrl+.").build(app)?)
+                .item(&quality_menu)
+                .item(&limits_menu)
                 .separator()
                 .item(&MenuItemBuilder::with_id("toggle_logs", "Toggle Logs").accelerator("CmdOrCtrl+L").build(app)?)
                 .build()?;
@@ -393,6 +7207,17 @@ pub fn run() {
                 .build()?;
 
             app.set_menu(menu)?;
+            app.state::<RecentFiles>().attach_menu(app.handle(), recent_menu);
+            app.state::<SettingsStore>().attach_menu(app.handle(), limits_menu);
+
+            // Scripts passed on the command line, as the OS does for file associations
+            for path in scripts_in_args(std::env::args()) {
+                let _ = open_document(app.handle(), &path, None);
+            }
+            // As are horsecad:// links, except on macOS
+            for url in links_in_args(std::env::args()) {
+                handle_deep_link(app.handle(), &url);
+            }
 
             // Handle menu events
             app.on_menu_event(move |app, event| {
@@ -402,11 +7227,29 @@ pub fn run() {
                             eprintln!("Failed to emit menu_new event: {}", e);
                         }
                     }
+                    "new_window" => {
+                        if let Err(e) = open_document_window(app) {
+                            eprintln!("Failed to open a new window: {}", e);
+                        }
+                    }
                     "open" => {
                         if let Err(e) = app.emit("menu_open", ()) {
                             eprintln!("Failed to emit menu_open event: {}", e);
                         }
                     }
+                    id if id.starts_with(RECENT_MENU_PREFIX) => {
+                        let index = id[RECENT_MENU_PREFIX.len()..].parse().ok();
+                        if let Some(path) = index.and_then(|i| app.state::<RecentFiles>().get(i)) {
+                            let _ = open_document(app, Path::new(&path), None);
+                        }
+                    }
+                    CLEAR_RECENT_MENU_ID => app.state::<RecentFiles>().clear(app),
+                    id if id.starts_with(TEMPLATE_MENU_PREFIX) => {
+                        let template = &id[TEMPLATE_MENU_PREFIX.len()..];
+                        if let Err(e) = app.emit("menu_new_from_template", template) {
+                            eprintln!("Failed to emit menu_new_from_template event: {}", e);
+                        }
+                    }
                     "save" => {
                         if let Err(e) = app.emit("menu_save", ()) {
                             eprintln!("Failed to emit menu_save event: {}", e);
@@ -422,22 +7265,103 @@ pub fn run() {
                             eprintln!("Failed to emit menu_export_stl event: {}", e);
                         }
                     }
+                    "export_obj" => {
+                        if let Err(e) = app.emit("menu_export_obj", ()) {
+                            eprintln!("Failed to emit menu_export_obj event: {}", e);
+                        }
+                    }
+                    "export_3mf" => {
+                        if let Err(e) = app.emit("menu_export_3mf", ()) {
+                            eprintln!("Failed to emit menu_export_3mf event: {}", e);
+                        }
+                    }
+                    "export_amf" => {
+                        if let Err(e) = app.emit("menu_export_amf", ()) {
+                            eprintln!("Failed to emit menu_export_amf event: {}", e);
+                        }
+                    }
                     "compile" => {
-                        if let Err(e) = app.emit("menu_compile", ()) {
+                        // The settings the file was last compiled with, for the focused window's editor to use
+                        let window = focused_window(app);
+                        let defaults = tracked_compile_defaults(app, &window);
+                        if let Err(e) = app.emit_to(window.as_str(), "menu_compile", defaults) {
                             eprintln!("Failed to emit menu_compile event: {}", e);
                         }
                     }
+                    "cancel_compile" if app.state::<CompileState>().cancel(None) => {
+                        emit_log(app, "info", "Cancellation requested", Some("Compiler"));
+                    }
+                    id @ ("quality_draft" | "quality_normal" | "quality_fine") => {
+                        let preset = id.trim_start_matches("quality_");
+                        if let Err(e) = app.emit("menu_quality", preset) {
+                            eprintln!("Failed to emit menu_quality event: {}", e);
+                        }
+                    }
+                    NO_PROFILE_MENU_ID => {
+                        let _ = select_profile(app, None);
+                    }
+                    id if id.starts_with(PROFILE_MENU_PREFIX) => {
+                        let _ = select_profile(app, Some(id[PROFILE_MENU_PREFIX.len()..].to_string()));
+                    }
                     "toggle_logs" => {
                         if let Err(e) = app.emit("menu_toggle_logs", ()) {
                             eprintln!("Failed to emit menu_toggle_logs event: {}", e);
                         }
                     }
+                    "export_ply" => {
+                        if let Err(e) = app.emit("menu_export_ply", ()) {
+                            eprintln!("Failed to emit menu_export_ply event: {}", e);
+                        }
+                    }
+                    "export_off" => {
+                        if let Err(e) = app.emit("menu_export_off", ()) {
+                            eprintln!("Failed to emit menu_export_off event: {}", e);
+                        }
+                    }
+                    "export_glb" => {
+                        if let Err(e) = app.emit("menu_export_glb", ()) {
+                            eprintln!("Failed to emit menu_export_glb event: {}", e);
+                        }
+                    }
+                    "export_parts" => {
+                        if let Err(e) = app.emit("menu_export_parts", ()) {
+                            eprintln!("Failed to emit menu_export_parts event: {}", e);
+                        }
+                    }
+                    "export_archive" => {
+                        if let Err(e) = app.emit("menu_export_archive", ()) {
+                            eprintln!("Failed to emit menu_export_archive event: {}", e);
+                        }
+                    }
                     _ => {}
                 }
             });
 
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app, event| match event {
+            tauri::RunEvent::ExitRequested { .. } => app.state::<SessionStore>().begin_exit(),
+            // Autosaves left behind are only for recovering from a crash; the session keeps unsaved buffers
+            tauri::RunEvent::Exit => {
+                let recovery = app.state::<Recovery>();
+                if let Err(e) = app.state::<SessionStore>().save(|key| recovery.unsaved(key)) {
+                    eprintln!("{}", e);
+                }
+                recovery.end_session();
+            }
+            // macOS passes files opened from Finder and links as an event rather than as arguments
+            #[cfg(target_os = "macos")]
+            tauri::RunEvent::Opened { urls } => {
+                for url in &urls {
+                    if url.scheme() == deep_link::DEEP_LINK_SCHEME {
+                        handle_deep_link(app, url.as_str());
+                    } else if let Some(path) = url.to_file_path().ok().filter(|path| open::is_script(path)) {
+                        let _ = open_document(app, &path, None);
+                    }
+                }
+            }
+            _ => {}
+        });
 }
@@ -0,0 +1,118 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::LogEntry;
+
+/// Size past which the log file is rotated
+pub const MAX_LOG_BYTES: u64 = 5 * 1024 * 1024;
+
+/// Rotated files kept besides the current one
+pub const KEPT_LOG_FILES: usize = 5;
+
+struct OpenLog {
+    file: File,
+    bytes: u64,
+    /// UTC date of the entries in the file, as `YYYY-MM-DD`
+    day: String,
+}
+
+/// Every log entry, appended to `horsecad.log` in a folder of the app data
+///
+/// The file is rotated when it passes `MAX_LOG_BYTES` or the first entry
+/// of a new day arrives: `horsecad.log` becomes `horsecad.1.log`, older
+/// files move up one, and the oldest past `KEPT_LOG_FILES` is deleted.
+/// Logging carries on without a file if the folder can't be written.
+pub struct LogFile {
+    dir: Option<PathBuf>,
+    open: Mutex<Option<OpenLog>>,
+}
+
+impl LogFile {
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        LogFile { dir, open: Mutex::new(None) }
+    }
+
+    /// The file being written to, if there is a log folder
+    pub fn path(&self) -> Option<PathBuf> {
+        self.dir.as_ref().map(|dir| dir.join("horsecad.log"))
+    }
+
+    /// Path of the `n`th rotated file, 0 being the current one
+    fn rotated_path(&self, n: usize) -> Option<PathBuf> {
+        match n {
+            0 => self.path(),
+            n => self.dir.as_ref().map(|dir| dir.join(format!("horsecad.{}.log", n))),
+        }
+    }
+
+    pub fn write(&self, entry: &LogEntry) {
+        let Some(path) = self.path() else { return };
+        let line = format!(
+            "{} {:<5} [{}] {}\n",
+            entry.timestamp,
+            entry.level.to_uppercase(),
+            entry.source.as_deref().unwrap_or("-"),
+            entry.message
+        );
+        let day = entry.timestamp.get(..10).unwrap_or_default().to_string();
+
+        let mut open = self.open.lock().unwrap_or_else(|e| e.into_inner());
+        if open.as_ref().is_some_and(|log| log.bytes + line.len() as u64 > MAX_LOG_BYTES || log.day != day) {
+            *open = None;
+            self.rotate();
+        }
+        if open.is_none() {
+            *open = self.open_file(&path, &day);
+        }
+        if let Some(log) = open.as_mut() {
+            if let Err(e) = log.file.write_all(line.as_bytes()) {
+                eprintln!("Failed to write to the log file: {}", e);
+                *open = None;
+                return;
+            }
+            log.bytes += line.len() as u64;
+        }
+    }
+
+    /// Open the current file for appending, rotating it first if it's full or from an earlier day
+    fn open_file(&self, path: &Path, day: &str) -> Option<OpenLog> {
+        if let Ok(metadata) = std::fs::metadata(path) {
+            let modified = metadata.modified().ok().map(chrono::DateTime::<chrono::Utc>::from);
+            let modified_day = modified.map(|m| m.format("%Y-%m-%d").to_string());
+            if metadata.len() >= MAX_LOG_BYTES || modified_day.as_deref().is_some_and(|d| d != day) {
+                self.rotate();
+            }
+        }
+        let opened = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| OpenOptions::new().create(true).append(true).open(path));
+        match opened {
+            Ok(file) => {
+                let bytes = file.metadata().map(|m| m.len()).unwrap_or(0);
+                Some(OpenLog { file, bytes, day: day.to_string() })
+            }
+            Err(e) => {
+                eprintln!("Failed to open the log file {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    /// Shift each file up one place, dropping the oldest
+    fn rotate(&self) {
+        if let Some(oldest) = self.rotated_path(KEPT_LOG_FILES) {
+            let _ = std::fs::remove_file(oldest);
+        }
+        for n in (0..KEPT_LOG_FILES).rev() {
+            let (Some(from), Some(to)) = (self.rotated_path(n), self.rotated_path(n + 1)) else { return };
+            if from.exists() {
+                if let Err(e) = std::fs::rename(&from, &to) {
+                    eprintln!("Failed to rotate the log file {}: {}", from.display(), e);
+                }
+            }
+        }
+    }
+}
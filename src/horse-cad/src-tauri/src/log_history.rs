@@ -0,0 +1,155 @@
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::state::CompileRecord;
+use crate::LogEntry;
+
+/// Entries kept for `get_logs`, oldest dropped first
+pub const MAX_LOG_HISTORY: usize = 2000;
+
+/// Severity of a log entry, least severe first
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    #[default]
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    /// Level of an entry's `level` string; anything unrecognised counts as info
+    pub fn of(level: &str) -> LogLevel {
+        match level {
+            "debug" => LogLevel::Debug,
+            "warn" | "warning" => LogLevel::Warn,
+            "error" => LogLevel::Error,
+            _ => LogLevel::Info,
+        }
+    }
+}
+
+/// The most recent log entries, and the level below which entries aren't sent to the frontend
+///
+/// Everything is kept whatever the level, so history fetched after a reload
+/// can still be filtered differently.
+#[derive(Default)]
+pub struct LogHistory {
+    entries: Mutex<VecDeque<LogEntry>>,
+    level: Mutex<LogLevel>,
+}
+
+impl LogHistory {
+    /// Keep `entry`, returning whether it's at or above the live level
+    pub fn record(&self, entry: &LogEntry) -> bool {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() == MAX_LOG_HISTORY {
+            entries.pop_front();
+        }
+        entries.push_back(entry.clone());
+        LogLevel::of(&entry.level) >= self.level()
+    }
+
+    /// The last `limit` entries at or above `level` from `source`, oldest first
+    pub fn query(&self, level: LogLevel, source: Option<&str>, limit: usize) -> Vec<LogEntry> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let mut matching: Vec<LogEntry> = entries
+            .iter()
+            .rev()
+            .filter(|e| LogLevel::of(&e.level) >= level)
+            .filter(|e| source.is_none_or(|source| e.source.as_deref() == Some(source)))
+            .take(limit)
+            .cloned()
+            .collect();
+        matching.reverse();
+        matching
+    }
+
+    pub fn level(&self) -> LogLevel {
+        *self.level.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn set_level(&self, level: LogLevel) {
+        *self.level.lock().unwrap_or_else(|e| e.into_inner()) = level;
+    }
+}
+
+/// File layout for `export_logs`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogExportFormat {
+    /// Readable text, one entry per line as in the log file
+    #[default]
+    Text,
+    Json,
+}
+
+/// Everything attached to a bug report: where horseCAD was running, and what it logged
+#[derive(Debug, Serialize)]
+pub struct LogReport {
+    pub app_version: String,
+    pub os: String,
+    pub arch: String,
+    /// Octree depth new documents compile at
+    pub default_depth: u8,
+    pub last_compile: Option<CompileRecord>,
+    /// Oldest first
+    pub entries: Vec<LogEntry>,
+}
+
+impl LogReport {
+    pub fn encode(&self, format: LogExportFormat) -> Result<Vec<u8>, String> {
+        match format {
+            LogExportFormat::Json => {
+                serde_json::to_vec_pretty(self).map_err(|e| format!("Failed to encode the logs: {}", e))
+            }
+            LogExportFormat::Text => Ok(self.to_text().into_bytes()),
+        }
+    }
+
+    fn to_text(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "horseCAD {} on {} ({})", self.app_version, self.os, self.arch);
+        let _ = writeln!(out, "Default depth: {}", self.default_depth);
+        match &self.last_compile {
+            Some(compile) => {
+                let outcome = if compile.success { "succeeded" } else { "failed" };
+                let triangles = compile.triangle_count.map_or("no".to_string(), |n| n.to_string());
+                let _ = writeln!(
+                    out,
+                    "Last compile: {} at depth {}, {} triangles, {}",
+                    outcome, compile.depth, triangles, compile.timestamp
+                );
+                let t = &compile.timings;
+                let stages = [
+                    ("script eval", t.script_eval_ms),
+                    ("shape build", t.shape_build_ms),
+                    ("bounds", t.bounds_ms),
+                    ("octree", t.octree_ms),
+                    ("dual walk", t.dual_walk_ms),
+                    ("weld", t.weld_ms),
+                    ("features", t.features_ms),
+                    ("simplify", t.simplify_ms),
+                    ("validate", t.validate_ms),
+                    ("export", t.export_ms),
+                ];
+                for (stage, ms) in stages {
+                    let _ = writeln!(out, "  {:<12} {:>10.1} ms", stage, ms);
+                }
+            }
+            None => {
+                let _ = writeln!(out, "Last compile: none this session");
+            }
+        }
+        let _ = writeln!(out);
+        for entry in &self.entries {
+            let (level, source) = (entry.level.to_uppercase(), entry.source.as_deref().unwrap_or("-"));
+            let _ = writeln!(out, "{} {:<5} [{}] {}", entry.timestamp, level, source, entry.message);
+        }
+        out
+    }
+}
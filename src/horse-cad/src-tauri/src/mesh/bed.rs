@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+
+use super::bounds::Bounds;
+
+/// Build volume of a typical hobby printer, in millimeters
+pub const DEFAULT_BED_SIZE: [f32; 3] = [220.0, 220.0, 250.0];
+
+/// How a mesh's bounding box compares with a printer's build volume
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct BedFit {
+    pub fits: bool,
+    /// Fits once turned a quarter turn about Z, swapping X and Y
+    pub fits_rotated: bool,
+    /// Size of the mesh along each axis
+    pub size: [f32; 3],
+    pub bed: [f32; 3],
+    /// How far the mesh runs past the bed along each axis, 0 where it fits
+    pub overflow: [f32; 3],
+}
+
+/// Check whether a mesh with `bounds` fits on a bed of size `bed`
+///
+/// Only the size of the box matters, since the slicer places the model;
+/// an empty mesh fits any bed.
+pub fn bed_fit(bounds: Option<Bounds>, bed: [f32; 3]) -> BedFit {
+    let size = bounds.map_or([0.0; 3], |b| std::array::from_fn(|i| b.max[i] - b.min[i]));
+    let overflow = std::array::from_fn(|i| (size[i] - bed[i]).max(0.0));
+    let fits = overflow.iter().all(|&o| o == 0.0);
+    let fits_rotated = size[0] <= bed[1] && size[1] <= bed[0] && size[2] <= bed[2];
+    BedFit { fits, fits_rotated, size, bed, overflow }
+}
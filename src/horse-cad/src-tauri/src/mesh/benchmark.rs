@@ -0,0 +1,180 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use fidget::{
+    eval::MathFunction,
+    mesh::{Octree, Settings as MeshSettings},
+    render::{RenderHints, ThreadPool, View3},
+    shape::{EzShape, Shape},
+    types::{Grad, Interval},
+};
+use serde::{Deserialize, Serialize};
+
+use super::bounds::Bounds;
+use crate::state::CancelToken;
+
+/// Deepest depth a benchmark reports on
+pub const MAX_BENCHMARK_DEPTH: u8 = 16;
+
+/// Shallowest octree that's actually built and timed
+const FIRST_TIMED_DEPTH: u8 = 4;
+
+/// Once one octree takes this long, deeper ones are extrapolated instead of built
+const DEPTH_TIME_BUDGET: Duration = Duration::from_secs(2);
+
+/// How long each kind of evaluation is repeated for
+const EVAL_TIME_BUDGET: Duration = Duration::from_millis(100);
+
+/// Points in each batch of float and gradient slice evaluation
+const BATCH_SIZE: usize = 4096;
+
+/// Evaluations per second of each kind the mesher uses
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct EvalThroughput {
+    /// Single distances, evaluated in batches
+    pub point: f64,
+    /// Interval bounds over small boxes, as the octree uses to skip empty cells
+    pub interval: f64,
+    /// Distances with their gradients, evaluated in batches
+    pub gradient: f64,
+}
+
+/// Time and size of an octree mesh at one depth
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct DepthEstimate {
+    pub depth: u8,
+    pub triangles: u64,
+    pub seconds: f64,
+    /// Whether this depth was built; otherwise it's extrapolated from the deepest one that was
+    pub measured: bool,
+}
+
+/// How fast a shape evaluates and meshes, for choosing a depth before a long build
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkReport {
+    pub backend: String,
+    pub threads: usize,
+    pub throughput: EvalThroughput,
+    /// Shallowest first, up to the depth asked for
+    pub depths: Vec<DepthEstimate>,
+}
+
+/// Time evaluating and meshing `shape` over `region`
+///
+/// Each kind of evaluation is repeated over samples spread through the
+/// region for a tenth of a second.  Octrees are then built from depth 4
+/// down until one takes two seconds or `max_depth` is reached; deeper
+/// depths are extrapolated by the growth seen over the last two levels,
+/// usually about four times per level.  Only the shape's own evaluation is
+/// timed, so a real compile adds a few seconds for welding and encoding.
+/// `backend` names the evaluator `F` is, for the report.  Returns `None`
+/// if `cancel` is triggered, which is checked between steps.
+pub fn benchmark_shape<F: MathFunction + RenderHints + Clone>(
+    shape: &Shape<F>,
+    backend: &str,
+    region: &Bounds,
+    max_depth: u8,
+    threads: Option<&ThreadPool>,
+    cancel: &CancelToken,
+) -> Result<Option<BenchmarkReport>> {
+    let samples = sample_points(region, BATCH_SIZE);
+    let [xs, ys, zs]: [Vec<f32>; 3] = [0, 1, 2].map(|i| samples.iter().map(|p| p[i]).collect());
+
+    let tape = shape.ez_float_slice_tape();
+    let mut eval = Shape::<F>::new_float_slice_eval();
+    let point = repeat_for(EVAL_TIME_BUDGET, BATCH_SIZE, || eval.eval(&tape, &xs, &ys, &zs).map(|_| ()))?;
+    if cancel.is_cancelled() {
+        return Ok(None);
+    }
+
+    // Boxes about the size of a depth 8 cell, which is where most interval work happens
+    let cell = (0..3).map(|i| region.max[i] - region.min[i]).fold(0.0, f32::max) / 256.0;
+    let tape = shape.ez_interval_tape();
+    let mut eval = Shape::<F>::new_interval_eval();
+    let mut next = 0;
+    let interval = repeat_for(EVAL_TIME_BUDGET, 1, || {
+        let p = samples[next % samples.len()];
+        next += 1;
+        let [x, y, z] = [0, 1, 2].map(|i| Interval::new(p[i], p[i] + cell));
+        eval.eval(&tape, x, y, z).map(|_| ())
+    })?;
+    if cancel.is_cancelled() {
+        return Ok(None);
+    }
+
+    let gx: Vec<Grad> = xs.iter().map(|&x| Grad::new(x, 1.0, 0.0, 0.0)).collect();
+    let gy: Vec<Grad> = ys.iter().map(|&y| Grad::new(y, 0.0, 1.0, 0.0)).collect();
+    let gz: Vec<Grad> = zs.iter().map(|&z| Grad::new(z, 0.0, 0.0, 1.0)).collect();
+    let tape = shape.ez_grad_slice_tape();
+    let mut eval = Shape::<F>::new_grad_slice_eval();
+    let gradient = repeat_for(EVAL_TIME_BUDGET, BATCH_SIZE, || eval.eval(&tape, &gx, &gy, &gz).map(|_| ()))?;
+
+    let center = region.center();
+    let view = View3::from_center_and_scale(nalgebra::Vector3::new(center[0], center[1], center[2]), region.half_extent());
+    let mut depths: Vec<DepthEstimate> = Vec::new();
+    for depth in FIRST_TIMED_DEPTH.min(max_depth)..=max_depth {
+        if cancel.is_cancelled() {
+            return Ok(None);
+        }
+        let settings = MeshSettings { depth, view, threads };
+        let start = Instant::now();
+        let triangles = Octree::build(shape, settings).walk_dual(settings).triangles.len() as u64;
+        let elapsed = start.elapsed();
+        depths.push(DepthEstimate { depth, triangles, seconds: elapsed.as_secs_f64(), measured: true });
+        if elapsed >= DEPTH_TIME_BUDGET {
+            break;
+        }
+    }
+
+    // Each level splits every surface cell in four, so growth settles near 4x
+    let growth = |before: f64, after: f64| {
+        if before > 0.0 && after > before {
+            (after / before).clamp(2.0, 8.0)
+        } else {
+            4.0
+        }
+    };
+    let (time_growth, triangle_growth) = match depths.as_slice() {
+        [.., a, b] => (growth(a.seconds, b.seconds), growth(a.triangles as f64, b.triangles as f64)),
+        _ => (4.0, 4.0),
+    };
+    while let Some(last) = depths.last().copied().filter(|d| d.depth < max_depth) {
+        depths.push(DepthEstimate {
+            depth: last.depth + 1,
+            triangles: (last.triangles as f64 * triangle_growth) as u64,
+            seconds: last.seconds * time_growth,
+            measured: false,
+        });
+    }
+    Ok(Some(BenchmarkReport {
+        backend: backend.to_string(),
+        threads: threads.map_or(1, ThreadPool::thread_count),
+        throughput: EvalThroughput { point, interval, gradient },
+        depths,
+    }))
+}
+
+/// Run `step`, which evaluates `per_step` samples, until `budget` has passed; returns samples per second
+fn repeat_for(budget: Duration, per_step: usize, mut step: impl FnMut() -> Result<(), fidget::Error>) -> Result<f64> {
+    let start = Instant::now();
+    let mut steps = 0u64;
+    while steps == 0 || start.elapsed() < budget {
+        step()?;
+        steps += 1;
+    }
+    Ok((steps * per_step as u64) as f64 / start.elapsed().as_secs_f64().max(1e-9))
+}
+
+/// `count` points spread through `region` in a fixed pseudo-random pattern
+fn sample_points(region: &Bounds, count: usize) -> Vec<[f32; 3]> {
+    // Additive recurrence on the plastic number's powers gives an even spread
+    const ALPHA: [f64; 3] = [0.819_172_513_396_164_4, 0.671_043_606_703_789_2, 0.549_700_477_901_970_3];
+    (0..count)
+        .map(|n| {
+            std::array::from_fn(|i| {
+                let t = (0.5 + ALPHA[i] * n as f64).fract() as f32;
+                region.min[i] + t * (region.max[i] - region.min[i])
+            })
+        })
+        .collect()
+}
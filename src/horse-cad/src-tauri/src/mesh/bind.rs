@@ -0,0 +1,100 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{anyhow, Result};
+use fidget::{
+    context::{BinaryOpcode, Context, Node, Op, UnaryOpcode},
+    var::Var,
+};
+
+/// Copy `roots` out of `ctx` into a new context, replacing variables with constants
+///
+/// Every variable in `values` becomes a constant, and the copied graph is
+/// constant-folded as it is rebuilt, so the result meshes as quickly as if
+/// the values had been written into the script.  Unbound variables are kept.
+/// Returns the new context and the copied roots, in order.
+pub fn bind_vars(ctx: &Context, roots: &[Node], values: &HashMap<Var, f64>) -> Result<(Context, Vec<Node>)> {
+    let mut out = Context::new();
+    let mut copied: HashMap<Node, Node> = HashMap::new();
+    for (node, op) in post_order(ctx, roots)? {
+        let new = match op {
+            Op::Const(c) => out.constant(c.0),
+            Op::Input(v) => match values.get(&v) {
+                Some(&value) => out.constant(value),
+                None => out.var(v),
+            },
+            Op::Unary(op, a) => unary(&mut out, op, copied[&a])?,
+            Op::Binary(op, a, b) => binary(&mut out, op, copied[&a], copied[&b])?,
+        };
+        copied.insert(node, new);
+    }
+
+    let roots = roots.iter().map(|n| copied[n]).collect();
+    Ok((out, roots))
+}
+
+/// Every node that `roots` depend on, each once and after its operands, with its op
+///
+/// Nodes come in the order of `roots`, so the first root's graph is listed
+/// first.  The walk keeps its own stack, since script graphs can be deeper
+/// than recursion allows.
+pub fn post_order(ctx: &Context, roots: &[Node]) -> Result<Vec<(Node, Op)>> {
+    let mut order = Vec::new();
+    let mut done: HashSet<Node> = HashSet::new();
+    let mut todo: Vec<(Node, bool)> = roots.iter().rev().map(|&n| (n, false)).collect();
+    while let Some((node, children_done)) = todo.pop() {
+        if done.contains(&node) {
+            continue;
+        }
+        let op = *ctx.get_op(node).ok_or_else(|| anyhow!("node is not in this context"))?;
+        if !children_done {
+            todo.push((node, true));
+            match op {
+                Op::Binary(_, a, b) => todo.extend([(b, false), (a, false)]),
+                Op::Unary(_, a) => todo.push((a, false)),
+                Op::Input(..) | Op::Const(..) => {}
+            }
+            continue;
+        }
+        done.insert(node);
+        order.push((node, op));
+    }
+    Ok(order)
+}
+
+fn unary(ctx: &mut Context, op: UnaryOpcode, a: Node) -> Result<Node> {
+    Ok(match op {
+        UnaryOpcode::Neg => ctx.neg(a),
+        UnaryOpcode::Abs => ctx.abs(a),
+        UnaryOpcode::Recip => ctx.recip(a),
+        UnaryOpcode::Sqrt => ctx.sqrt(a),
+        UnaryOpcode::Square => ctx.square(a),
+        UnaryOpcode::Floor => ctx.floor(a),
+        UnaryOpcode::Ceil => ctx.ceil(a),
+        UnaryOpcode::Round => ctx.round(a),
+        UnaryOpcode::Sin => ctx.sin(a),
+        UnaryOpcode::Cos => ctx.cos(a),
+        UnaryOpcode::Tan => ctx.tan(a),
+        UnaryOpcode::Asin => ctx.asin(a),
+        UnaryOpcode::Acos => ctx.acos(a),
+        UnaryOpcode::Atan => ctx.atan(a),
+        UnaryOpcode::Exp => ctx.exp(a),
+        UnaryOpcode::Ln => ctx.ln(a),
+        UnaryOpcode::Not => ctx.not(a),
+    }?)
+}
+
+fn binary(ctx: &mut Context, op: BinaryOpcode, a: Node, b: Node) -> Result<Node> {
+    Ok(match op {
+        BinaryOpcode::Add => ctx.add(a, b),
+        BinaryOpcode::Sub => ctx.sub(a, b),
+        BinaryOpcode::Mul => ctx.mul(a, b),
+        BinaryOpcode::Div => ctx.div(a, b),
+        BinaryOpcode::Atan => ctx.atan2(a, b),
+        BinaryOpcode::Min => ctx.min(a, b),
+        BinaryOpcode::Max => ctx.max(a, b),
+        BinaryOpcode::Compare => ctx.compare(a, b),
+        BinaryOpcode::Mod => ctx.modulo(a, b),
+        BinaryOpcode::And => ctx.and(a, b),
+        BinaryOpcode::Or => ctx.or(a, b),
+    }?)
+}
@@ -0,0 +1,184 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use anyhow::Result;
+use fidget::{shape::EzShape, types::Interval, vm::VmShape};
+use serde::{Deserialize, Serialize};
+
+/// Half-width of the cube searched for geometry
+const SEARCH_EXTENT: f32 = 1024.0;
+/// Subdivision depth of the coarse occupancy pass
+const COARSE_DEPTH: u32 = 4;
+/// Precision of each face, relative to the bounding box size
+const RELATIVE_TOLERANCE: f32 = 1e-3;
+/// Floor on face precision, so degenerate shapes still terminate
+const MIN_TOLERANCE: f32 = 1e-6;
+/// Upper bound on face refinement rounds
+const MAX_ROUNDS: usize = 8;
+
+/// Axis-aligned bounding box in model coordinates
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Bounds {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+impl Bounds {
+    pub fn center(&self) -> [f32; 3] {
+        std::array::from_fn(|i| (self.min[i] + self.max[i]) / 2.0)
+    }
+
+    /// Half of the largest side length
+    pub fn half_extent(&self) -> f32 {
+        (0..3).map(|i| self.max[i] - self.min[i]).fold(0.0, f32::max) / 2.0
+    }
+
+    /// Widen the box to also cover `other`
+    pub fn grow(&mut self, other: &Bounds) {
+        for i in 0..3 {
+            self.min[i] = self.min[i].min(other.min[i]);
+            self.max[i] = self.max[i].max(other.max[i]);
+        }
+    }
+
+    fn octants(&self) -> impl Iterator<Item = Bounds> + '_ {
+        let mid = self.center();
+        (0..8).map(move |octant| {
+            let upper = |i: usize| octant & (1 << i) != 0;
+            Bounds {
+                min: std::array::from_fn(|i| if upper(i) { mid[i] } else { self.min[i] }),
+                max: std::array::from_fn(|i| if upper(i) { self.max[i] } else { mid[i] }),
+            }
+        })
+    }
+}
+
+/// What interval evaluation proves about a cell
+enum Occupancy {
+    Empty,
+    Full,
+    Ambiguous,
+}
+
+/// Cell in the best-first search for one face of the bounding box
+struct Candidate {
+    key: f32,
+    cell: Bounds,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.total_cmp(&other.key)
+    }
+}
+
+/// Find a tight bounding box for the solid region of `shape`
+///
+/// A coarse octree pass finds the rough extent of the shape, then each face
+/// is tightened with a best-first search which only subdivides the cells
+/// that could push that face outwards; this repeats with a finer tolerance
+/// until the box stops shrinking.  Interval arithmetic makes the result
+/// conservative: it may be slightly larger than the true bounds, never
+/// smaller.  Returns `None` if the shape is empty or reaches the edge of the
+/// search region (e.g. an inverted shape).
+pub fn detect_bounds(shape: &VmShape) -> Result<Option<Bounds>> {
+    let tape = shape.ez_interval_tape();
+    let mut eval = VmShape::new_interval_eval();
+    let mut classify = |cell: &Bounds| -> Result<Occupancy> {
+        let (value, _trace) = eval.eval(
+            &tape,
+            Interval::new(cell.min[0], cell.max[0]),
+            Interval::new(cell.min[1], cell.max[1]),
+            Interval::new(cell.min[2], cell.max[2]),
+        )?;
+        Ok(if value.lower() > 0.0 {
+            Occupancy::Empty
+        } else if value.upper() < 0.0 {
+            Occupancy::Full
+        } else {
+            Occupancy::Ambiguous
+        })
+    };
+
+    let search = Bounds { min: [-SEARCH_EXTENT; 3], max: [SEARCH_EXTENT; 3] };
+    let mut coarse: Option<Bounds> = None;
+    let mut stack = vec![(search, 0u32)];
+    while let Some((cell, level)) = stack.pop() {
+        match classify(&cell)? {
+            Occupancy::Empty => {}
+            Occupancy::Ambiguous if level < COARSE_DEPTH => {
+                stack.extend(cell.octants().map(|c| (c, level + 1)));
+            }
+            _ => match coarse.as_mut() {
+                Some(b) => b.grow(&cell),
+                None => coarse = Some(cell),
+            },
+        }
+    }
+
+    let Some(coarse) = coarse else {
+        return Ok(None);
+    };
+    if (0..3).any(|i| coarse.min[i] <= search.min[i] || coarse.max[i] >= search.max[i]) {
+        return Ok(None);
+    }
+
+    let mut bounds = coarse;
+    for _ in 0..MAX_ROUNDS {
+        let tolerance = (bounds.half_extent() * 2.0 * RELATIVE_TOLERANCE).max(MIN_TOLERANCE);
+        let refined = refine_faces(&mut classify, &bounds, tolerance)?;
+        let converged = refined.half_extent() > bounds.half_extent() / 2.0;
+        bounds = refined;
+        if converged {
+            break;
+        }
+    }
+    Ok(Some(bounds))
+}
+
+/// Move each face of `region` inwards until it touches the shape
+fn refine_faces(
+    classify: &mut impl FnMut(&Bounds) -> Result<Occupancy>,
+    region: &Bounds,
+    tolerance: f32,
+) -> Result<Bounds> {
+    let mut bounds = *region;
+    for axis in 0..3 {
+        for upper in [false, true] {
+            // Keys are ordered so the most extreme cell is always popped first
+            let key = |cell: &Bounds| if upper { cell.max[axis] } else { -cell.min[axis] };
+            let mut heap = BinaryHeap::from([Candidate { key: key(region), cell: *region }]);
+            while let Some(Candidate { cell, .. }) = heap.pop() {
+                match classify(&cell)? {
+                    Occupancy::Empty => {}
+                    Occupancy::Ambiguous if cell.max[axis] - cell.min[axis] > tolerance => {
+                        heap.extend(cell.octants().map(|c| Candidate { key: key(&c), cell: c }));
+                    }
+                    _ => {
+                        if upper {
+                            bounds.max[axis] = cell.max[axis];
+                        } else {
+                            bounds.min[axis] = cell.min[axis];
+                        }
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    Ok(bounds)
+}
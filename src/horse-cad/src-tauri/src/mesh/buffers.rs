@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+
+use fidget::mesh::Mesh;
+use serde::{Deserialize, Serialize};
+
+use super::normals::vertex_normals;
+
+/// Triangles in each chunk of a streamed mesh, unless the caller picks a size
+pub const DEFAULT_CHUNK_TRIANGLES: usize = 1 << 18;
+
+/// Flat vertex/normal/index buffers matching a Three.js `BufferGeometry`
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct MeshBuffers {
+    /// Vertex positions as packed `[x, y, z]` triples
+    pub positions: Vec<f32>,
+    /// Smooth vertex normals as packed `[x, y, z]` triples
+    pub normals: Vec<f32>,
+    /// Triangle vertex indices, three per triangle
+    pub indices: Vec<u32>,
+}
+
+impl MeshBuffers {
+    pub fn from_mesh(mesh: &Mesh) -> Self {
+        let positions = mesh.vertices.iter().flat_map(|v| [v.x, v.y, v.z]).collect();
+        let normals = vertex_normals(mesh).iter().flat_map(|n| [n.x, n.y, n.z]).collect();
+        let indices = mesh.triangles.iter().flat_map(|t| [t.x as u32, t.y as u32, t.z as u32]).collect();
+        Self { positions, normals, indices }
+    }
+
+    /// Split a mesh into self-contained buffers of up to `max_triangles` each, in triangle order
+    ///
+    /// Each chunk holds only the vertices its triangles use, indexed from
+    /// zero, so it can be uploaded as a geometry of its own.  Normals are
+    /// computed over the whole mesh first, so chunk seams shade smoothly.
+    /// Chunks are built as the iterator is advanced.
+    pub fn chunks(mesh: &Mesh, max_triangles: usize) -> impl Iterator<Item = MeshBuffers> + '_ {
+        let normals = vertex_normals(mesh);
+        mesh.triangles.chunks(max_triangles.max(1)).map(move |triangles| {
+            let mut local: HashMap<usize, u32> = HashMap::new();
+            let mut chunk = MeshBuffers::default();
+            for v in triangles.iter().flat_map(|t| [t.x, t.y, t.z]) {
+                let index = *local.entry(v).or_insert_with(|| {
+                    let (p, n) = (mesh.vertices[v], normals[v]);
+                    chunk.positions.extend([p.x, p.y, p.z]);
+                    chunk.normals.extend([n.x, n.y, n.z]);
+                    (chunk.positions.len() / 3 - 1) as u32
+                });
+                chunk.indices.push(index);
+            }
+            chunk
+        })
+    }
+
+    /// Positions, then normals, then indices, packed little-endian
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity((self.positions.len() + self.normals.len() + self.indices.len()) * 4);
+        out.extend(self.positions.iter().chain(&self.normals).flat_map(|v| v.to_le_bytes()));
+        out.extend(self.indices.iter().flat_map(|i| i.to_le_bytes()));
+        out
+    }
+}
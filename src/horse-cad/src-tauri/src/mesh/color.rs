@@ -0,0 +1,56 @@
+/// 8-bit RGB color
+pub type Rgb = [u8; 3];
+
+/// Color used for triangles without one in formats that need a value for all
+pub const DEFAULT_COLOR: Rgb = [0xb4, 0xb4, 0xb4];
+
+/// Display colors given to materials in turn, by a multi-material export
+pub const MATERIAL_COLORS: [Rgb; 6] = [
+    [0xe6, 0x7e, 0x22],
+    [0x34, 0x98, 0xdb],
+    [0x2e, 0xcc, 0x71],
+    [0x9b, 0x59, 0xb6],
+    [0xf1, 0xc4, 0x0f],
+    [0xe7, 0x4c, 0x3c],
+];
+
+/// VisCAM/SolidView flag marking a binary STL attribute word as a color
+const RGB555_VALID: u16 = 1 << 15;
+
+/// Parse a `#rrggbb` or `#rgb` color string
+pub fn parse_hex_color(text: &str) -> Result<Rgb, String> {
+    let hex = text.trim().strip_prefix('#').unwrap_or(text.trim());
+    let digits: Vec<u8> = hex
+        .chars()
+        .map(|c| c.to_digit(16).map(|d| d as u8))
+        .collect::<Option<_>>()
+        .ok_or_else(|| format!("invalid color '{}'", text))?;
+    match digits.as_slice() {
+        [r, g, b] => Ok([r * 17, g * 17, b * 17]),
+        [r1, r0, g1, g0, b1, b0] => Ok([r1 * 16 + r0, g1 * 16 + g0, b1 * 16 + b0]),
+        _ => Err(format!("color '{}' must be #rgb or #rrggbb", text)),
+    }
+}
+
+pub fn to_hex(color: Rgb) -> String {
+    format!("#{:02X}{:02X}{:02X}", color[0], color[1], color[2])
+}
+
+/// Pack a color into a binary STL attribute word
+///
+/// This is the VisCAM/SolidView convention: five bits per channel with
+/// blue in the low bits, and the top bit set to mark the color as valid.
+pub fn to_rgb555(color: Rgb) -> u16 {
+    let [r, g, b] = color.map(|c| (c >> 3) as u16);
+    RGB555_VALID | (r << 10) | (g << 5) | b
+}
+
+/// Unpack a binary STL attribute word written by `to_rgb555`
+pub fn from_rgb555(word: u16) -> Option<Rgb> {
+    if word & RGB555_VALID == 0 {
+        return None;
+    }
+    // Replicate the high bits so 0x1F expands to 0xFF
+    let expand = |c: u16| ((c << 3) | (c >> 2)) as u8;
+    Some([expand((word >> 10) & 0x1F), expand((word >> 5) & 0x1F), expand(word & 0x1F)])
+}
@@ -0,0 +1,107 @@
+use std::collections::{BTreeMap, HashSet};
+
+use anyhow::Result;
+use fidget::{
+    context::{BinaryOpcode, Context, Node, Op},
+    shape::EzShape,
+    types::Interval,
+    vm::VmShape,
+};
+use serde::{Deserialize, Serialize};
+
+use super::bounds::Bounds;
+
+/// Cells along each side of the grid pruned tapes are averaged over
+const CELL_GRID: usize = 8;
+
+/// How big a shape's expression is, and how much of it each evaluation runs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShapeComplexity {
+    /// Distinct nodes in the expression; repeated subexpressions are stored once
+    pub node_count: usize,
+    /// How many nodes do each operation, such as `min` or `sqrt`
+    pub ops: BTreeMap<String, usize>,
+    /// Instructions in the evaluation tape, which every point runs without pruning
+    pub tape_length: usize,
+    /// `min` and `max` instructions, the choices interval pruning can cut
+    pub choice_count: usize,
+    /// Instructions left once the tape is pruned over the whole meshing region
+    pub region_tape_length: usize,
+    /// Average instructions left once pruned over each small cell the surface may cross
+    ///
+    /// This is roughly what the octree runs near the surface.  Far below
+    /// `tape_length`, such as for a union of many small parts, means most
+    /// of the shape is cut away locally and it meshes much faster than its
+    /// size suggests.
+    pub cell_tape_length: f64,
+}
+
+/// Count the nodes of the expression at `root`, and how far its tape prunes over `region`
+pub fn shape_complexity(ctx: &Context, root: Node, region: &Bounds) -> Result<ShapeComplexity> {
+    let mut ops = BTreeMap::new();
+    let mut seen = HashSet::new();
+    let mut todo = vec![root];
+    while let Some(node) = todo.pop() {
+        if !seen.insert(node) {
+            continue;
+        }
+        let Some(op) = ctx.get_op(node) else { continue };
+        let name = match op {
+            Op::Input(_) => "input".to_string(),
+            Op::Const(_) => "const".to_string(),
+            // Apart from the one-argument `atan`
+            Op::Binary(BinaryOpcode::Atan, ..) => "atan2".to_string(),
+            Op::Binary(opcode, ..) => format!("{:?}", opcode).to_lowercase(),
+            Op::Unary(opcode, _) => format!("{:?}", opcode).to_lowercase(),
+        };
+        *ops.entry(name).or_insert(0) += 1;
+        match *op {
+            Op::Binary(_, a, b) => todo.extend([a, b]),
+            Op::Unary(_, a) => todo.push(a),
+            Op::Input(_) | Op::Const(_) => {}
+        }
+    }
+
+    let shape = VmShape::new(ctx, root)?;
+    let tape = shape.ez_interval_tape();
+    let mut eval = VmShape::new_interval_eval();
+    let mut workspace = Default::default();
+    let mut pruned_length = |bounds: [[f32; 2]; 3]| -> Result<(Interval, usize)> {
+        let [x, y, z] = bounds.map(|[lo, hi]| Interval::new(lo, hi));
+        let (value, trace) = eval.eval(&tape, x, y, z)?;
+        let length = match trace {
+            Some(trace) => shape.simplify(trace, Default::default(), &mut workspace)?.size(),
+            None => shape.size(),
+        };
+        Ok((value, length))
+    };
+
+    let (_, region_tape_length) = pruned_length(std::array::from_fn(|a| [region.min[a], region.max[a]]))?;
+    let step: [f32; 3] = std::array::from_fn(|a| (region.max[a] - region.min[a]) / CELL_GRID as f32);
+    let (mut total, mut cells) = (0, 0);
+    for k in 0..CELL_GRID {
+        for j in 0..CELL_GRID {
+            for i in 0..CELL_GRID {
+                let cell = [i, j, k];
+                let bounds = std::array::from_fn(|a| {
+                    let lo = region.min[a] + cell[a] as f32 * step[a];
+                    [lo, lo + step[a]]
+                });
+                let (value, length) = pruned_length(bounds)?;
+                if value.lower() <= 0.0 && value.upper() >= 0.0 {
+                    total += length;
+                    cells += 1;
+                }
+            }
+        }
+    }
+
+    Ok(ShapeComplexity {
+        node_count: seen.len(),
+        ops,
+        tape_length: shape.size(),
+        choice_count: shape.inner().choice_count(),
+        region_tape_length,
+        cell_tape_length: if cells > 0 { total as f64 / cells as f64 } else { region_tape_length as f64 },
+    })
+}
@@ -0,0 +1,97 @@
+use anyhow::Result;
+use fidget::{
+    eval::MathFunction,
+    render::View3,
+    shape::{EzShape, Shape},
+    types::Interval,
+};
+
+/// Cells along each side of the coarse grid the pre-pass evaluates
+const CULL_RESOLUTION: usize = 16;
+
+/// A smaller cube to mesh, found to hold everything the full one would
+#[derive(Debug, Clone, Copy)]
+pub struct CulledRegion {
+    pub view: View3,
+    /// Depth that keeps the finest cells the same size in the smaller cube
+    pub depth: u8,
+    /// Levels dropped from the requested depth
+    pub levels: u8,
+    /// Share of the coarse cells the surface may pass through
+    pub occupied: f32,
+}
+
+/// Interval-evaluate a coarse grid over `view`'s cube, and find a smaller cube holding all of the surface
+///
+/// Cells whose interval excludes zero are completely empty or full.  The
+/// culled cube covers every cell the surface may cross, and every full
+/// cell on the outside of the region, which is cut flat there.  Its side
+/// is the region's halved `levels` times and it's placed on the grid of
+/// finest cells at `depth`, so meshing it at `depth - levels` gives the
+/// same cells as the full octree without building the empty space around
+/// them.  Returns `None` if no level can be dropped.
+pub fn cull_region<F: MathFunction + Clone>(shape: &Shape<F>, view: View3, depth: u8) -> Result<Option<CulledRegion>> {
+    // Coarse cells finer than the finest octree cells have nothing to save
+    if (1usize << depth.min(16)) < CULL_RESOLUTION {
+        return Ok(None);
+    }
+    let (center, scale, ..) = view.components();
+    let side = 2.0 * scale;
+    let min = [center.x - scale, center.y - scale, center.z - scale];
+    let step = side / CULL_RESOLUTION as f32;
+
+    let tape = shape.ez_interval_tape();
+    let mut eval = Shape::<F>::new_interval_eval();
+    let n = CULL_RESOLUTION;
+    let mut lo = [usize::MAX; 3];
+    let mut hi = [0usize; 3];
+    let mut occupied = 0;
+    for k in 0..n {
+        for j in 0..n {
+            for i in 0..n {
+                let cell = [i, j, k];
+                let [x, y, z] =
+                    [0, 1, 2].map(|a| Interval::new(min[a] + cell[a] as f32 * step, min[a] + (cell[a] + 1) as f32 * step));
+                let (value, _) = eval.eval(&tape, x, y, z)?;
+                let surface = value.lower() <= 0.0 && value.upper() >= 0.0;
+                let on_outside = cell.iter().any(|&c| c == 0 || c == n - 1);
+                if surface {
+                    occupied += 1;
+                }
+                if surface || (on_outside && value.upper() < 0.0) {
+                    for a in 0..3 {
+                        lo[a] = lo[a].min(cell[a]);
+                        hi[a] = hi[a].max(cell[a] + 1);
+                    }
+                }
+            }
+        }
+    }
+    if lo[0] == usize::MAX {
+        return Ok(None);
+    }
+
+    // One finest cell of slack on every side keeps cells the surface only
+    // touches inside the culled cube
+    let fine = side / (1u64 << depth) as f32;
+    let extent = (0..3).map(|a| (hi[a] - lo[a]) as f32 * step).fold(0.0, f32::max) + 2.0 * fine;
+    let levels = (0..depth).take_while(|&l| side / (1u64 << (l + 1)) as f32 >= extent).count() as u8;
+    if levels == 0 {
+        return Ok(None);
+    }
+    let cube = side / (1u64 << levels) as f32;
+    let origin: [f32; 3] = std::array::from_fn(|a| {
+        let start = ((lo[a] as f32 * step - fine) / fine).floor().max(0.0) * fine;
+        min[a] + start.min(side - cube)
+    });
+    let half = cube / 2.0;
+    Ok(Some(CulledRegion {
+        view: View3::from_center_and_scale(
+            nalgebra::Vector3::new(origin[0] + half, origin[1] + half, origin[2] + half),
+            half,
+        ),
+        depth: depth - levels,
+        levels,
+        occupied: occupied as f32 / (n * n * n) as f32,
+    }))
+}
@@ -0,0 +1,223 @@
+use fidget::mesh::Mesh;
+use serde::{Deserialize, Serialize};
+
+use super::bounds::Bounds;
+use super::distance::ray_crossing;
+
+/// Cells along the longest side of the compared region, unless asked otherwise
+pub const DEFAULT_DIFF_RESOLUTION: usize = 128;
+
+/// Most cells along the longest side, which bounds the grid's memory
+pub const MAX_DIFF_RESOLUTION: usize = 256;
+
+/// Changed regions listed individually, largest first; the totals cover them all
+const MAX_DIFF_REGIONS: usize = 32;
+
+/// Whether a changed region gained or lost material
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffKind {
+    Added,
+    Removed,
+}
+
+/// A connected block of cells that changed the same way
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffRegion {
+    pub kind: DiffKind,
+    pub volume: f64,
+    pub bounds: Bounds,
+}
+
+/// How the solid changed from one mesh to the next
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeshDiff {
+    /// No cell changed, so the two meshes enclose the same solid at this resolution
+    pub unchanged: bool,
+    /// Whether the two meshes have exactly the same vertices and triangles
+    pub identical: bool,
+    /// Volume inside the new mesh but not the old one
+    pub added_volume: f64,
+    /// Volume inside the old mesh but not the new one
+    pub removed_volume: f64,
+    /// Box around every added cell
+    pub added_bounds: Option<Bounds>,
+    /// Box around every removed cell
+    pub removed_bounds: Option<Bounds>,
+    /// Largest changed regions first
+    pub regions: Vec<DiffRegion>,
+    /// Side of a grid cell; changes smaller than about this can be missed or are noise
+    pub cell_size: f64,
+    pub before_triangles: usize,
+    pub after_triangles: usize,
+}
+
+/// Cubic cells covering both meshes
+struct DiffGrid {
+    origin: [f64; 3],
+    spacing: f64,
+    dims: [usize; 3],
+}
+
+impl DiffGrid {
+    fn index(&self, [i, j, k]: [usize; 3]) -> usize {
+        i + self.dims[0] * (j + self.dims[1] * k)
+    }
+
+    fn cell_bounds(&self, [i, j, k]: [usize; 3]) -> Bounds {
+        let min: [f64; 3] = std::array::from_fn(|a| self.origin[a] + [i, j, k][a] as f64 * self.spacing);
+        Bounds { min: min.map(|m| m as f32), max: min.map(|m| (m + self.spacing) as f32) }
+    }
+}
+
+/// Compare the solids enclosed by two meshes in the same coordinates
+///
+/// Both are voxelized on one grid with `resolution` cells along the longest
+/// side of their combined bounds, by the parity of surface crossings below
+/// each cell's center, so they should be watertight.  Cells inside one and
+/// not the other are counted into added and removed volume and grouped
+/// into connected regions.
+pub fn diff_meshes(before: &Mesh, after: &Mesh, resolution: usize) -> MeshDiff {
+    let identical = before.vertices == after.vertices && before.triangles == after.triangles;
+    let mut diff = MeshDiff {
+        unchanged: true,
+        identical,
+        added_volume: 0.0,
+        removed_volume: 0.0,
+        added_bounds: None,
+        removed_bounds: None,
+        regions: Vec::new(),
+        cell_size: 0.0,
+        before_triangles: before.triangles.len(),
+        after_triangles: after.triangles.len(),
+    };
+
+    let (min, max) = before.vertices.iter().chain(&after.vertices).fold(
+        ([f64::INFINITY; 3], [f64::NEG_INFINITY; 3]),
+        |(lo, hi), v| (std::array::from_fn(|a| lo[a].min(v[a] as f64)), std::array::from_fn(|a| hi[a].max(v[a] as f64))),
+    );
+    let extent = (0..3).map(|a| max[a] - min[a]).fold(0.0, f64::max);
+    if identical || !(extent > 0.0 && extent.is_finite()) {
+        return diff;
+    }
+
+    let resolution = resolution.clamp(2, MAX_DIFF_RESOLUTION);
+    let spacing = extent / resolution as f64;
+    let grid = DiffGrid {
+        origin: min.map(|m| m - spacing),
+        spacing,
+        dims: std::array::from_fn(|a| ((max[a] - min[a]) / spacing).ceil() as usize + 2),
+    };
+    diff.cell_size = spacing;
+    let old = occupancy(before, &grid);
+    let new = occupancy(after, &grid);
+
+    let cell_volume = spacing.powi(3);
+    let mut visited = vec![false; old.len()];
+    let mut regions = Vec::new();
+    for start in 0..old.len() {
+        if visited[start] || old[start] == new[start] {
+            continue;
+        }
+        let kind = if new[start] { DiffKind::Added } else { DiffKind::Removed };
+        let (cells, bounds) = flood_region(&grid, &old, &new, &mut visited, start);
+        let volume = cells as f64 * cell_volume;
+        let (total, overall) = match kind {
+            DiffKind::Added => (&mut diff.added_volume, &mut diff.added_bounds),
+            DiffKind::Removed => (&mut diff.removed_volume, &mut diff.removed_bounds),
+        };
+        *total += volume;
+        *overall = Some(match overall.take() {
+            Some(b) => union(&b, &bounds),
+            None => bounds,
+        });
+        regions.push(DiffRegion { kind, volume, bounds });
+    }
+    regions.sort_by(|a, b| b.volume.total_cmp(&a.volume));
+    regions.truncate(MAX_DIFF_REGIONS);
+    diff.unchanged = regions.is_empty();
+    diff.regions = regions;
+    diff
+}
+
+/// Which cells of `grid` have their centers inside `mesh`
+fn occupancy(mesh: &Mesh, grid: &DiffGrid) -> Vec<bool> {
+    let [nx, ny, nz] = grid.dims;
+    // Nudged off the cell centers so rays don't graze edges and vertices
+    let nudge = [0.000_137 * grid.spacing, 0.000_291 * grid.spacing];
+    let center = |axis: usize, n: usize| grid.origin[axis] + (n as f64 + 0.5) * grid.spacing;
+    let mut crossings: Vec<Vec<f64>> = vec![Vec::new(); nx * ny];
+    for t in &mesh.triangles {
+        let tri: [[f64; 3]; 3] = [t.x, t.y, t.z].map(|i| mesh.vertices[i].map(f64::from).into());
+        let range = |axis: usize, count: usize| {
+            let lo = tri.iter().map(|v| v[axis]).fold(f64::INFINITY, f64::min);
+            let hi = tri.iter().map(|v| v[axis]).fold(f64::NEG_INFINITY, f64::max);
+            let first = ((lo - grid.origin[axis]) / grid.spacing - 0.5).floor().max(0.0) as usize;
+            let last = ((hi - grid.origin[axis]) / grid.spacing + 0.5).ceil().max(0.0) as usize;
+            first..last.min(count)
+        };
+        for j in range(1, ny) {
+            for i in range(0, nx) {
+                if let Some(z) = ray_crossing(center(0, i) + nudge[0], center(1, j) + nudge[1], &tri) {
+                    crossings[i + nx * j].push(z);
+                }
+            }
+        }
+    }
+
+    let mut inside = vec![false; nx * ny * nz];
+    for (column, hits) in crossings.iter_mut().enumerate() {
+        hits.sort_by(f64::total_cmp);
+        let (i, j) = (column % nx, column / nx);
+        let mut below = 0;
+        for k in 0..nz {
+            let z = center(2, k);
+            while below < hits.len() && hits[below] < z {
+                below += 1;
+            }
+            inside[grid.index([i, j, k])] = below % 2 == 1;
+        }
+    }
+    inside
+}
+
+/// Mark every cell face-connected to `start` that changed the same way, returning their count and bounds
+fn flood_region(grid: &DiffGrid, old: &[bool], new: &[bool], visited: &mut [bool], start: usize) -> (usize, Bounds) {
+    let [nx, ny, nz] = grid.dims;
+    let added = new[start];
+    let same_change = |index: usize| old[index] != new[index] && new[index] == added;
+    let coords = |index: usize| [index % nx, (index / nx) % ny, index / (nx * ny)];
+
+    let mut stack = vec![start];
+    visited[start] = true;
+    let mut bounds = grid.cell_bounds(coords(start));
+    let mut cells = 0;
+    while let Some(index) = stack.pop() {
+        cells += 1;
+        let [i, j, k] = coords(index);
+        bounds = union(&bounds, &grid.cell_bounds([i, j, k]));
+        let neighbours = [
+            (i > 0).then(|| [i - 1, j, k]),
+            (i + 1 < nx).then(|| [i + 1, j, k]),
+            (j > 0).then(|| [i, j - 1, k]),
+            (j + 1 < ny).then(|| [i, j + 1, k]),
+            (k > 0).then(|| [i, j, k - 1]),
+            (k + 1 < nz).then(|| [i, j, k + 1]),
+        ];
+        for n in neighbours.into_iter().flatten() {
+            let n = grid.index(n);
+            if !visited[n] && same_change(n) {
+                visited[n] = true;
+                stack.push(n);
+            }
+        }
+    }
+    (cells, bounds)
+}
+
+fn union(a: &Bounds, b: &Bounds) -> Bounds {
+    Bounds {
+        min: std::array::from_fn(|i| a.min[i].min(b.min[i])),
+        max: std::array::from_fn(|i| a.max[i].max(b.max[i])),
+    }
+}
@@ -0,0 +1,160 @@
+use fidget::mesh::Mesh;
+
+/// Samples of padding around the mesh's bounding box on every side
+const PADDING: usize = 2;
+
+/// Width of the band of accurate distances around the surface, in samples
+pub const BAND_SAMPLES: f64 = 3.0;
+
+/// Signed distances to a mesh's surface on a regular grid
+///
+/// Distances are exact within the band around the surface and clamped to
+/// `±band` outside it; negative is inside.  Samples are indexed with X
+/// varying fastest.
+#[derive(Debug, Clone)]
+pub struct DistanceGrid {
+    pub origin: [f64; 3],
+    pub spacing: f64,
+    pub dims: [usize; 3],
+    pub band: f64,
+    pub values: Vec<f64>,
+}
+
+impl DistanceGrid {
+    pub fn index(&self, [i, j, k]: [usize; 3]) -> usize {
+        i + self.dims[0] * (j + self.dims[1] * k)
+    }
+
+    pub fn get(&self, at: [usize; 3]) -> f64 {
+        self.values[self.index(at)]
+    }
+}
+
+/// Sample the signed distance to a closed mesh, with `max_samples` along its longest side
+///
+/// Inside and outside are decided by counting surface crossings along Z,
+/// so the mesh must be watertight.  Returns `None` for empty or flat meshes.
+pub fn distance_grid(mesh: &Mesh, max_samples: usize) -> Option<DistanceGrid> {
+    let vertices: Vec<[f64; 3]> = mesh.vertices.iter().map(|v| [v.x, v.y, v.z].map(f64::from)).collect();
+    let triangles: Vec<[[f64; 3]; 3]> =
+        mesh.triangles.iter().map(|t| [vertices[t.x], vertices[t.y], vertices[t.z]]).collect();
+    let (min, max) = vertices.iter().fold(([f64::INFINITY; 3], [f64::NEG_INFINITY; 3]), |(lo, hi), v| {
+        (std::array::from_fn(|a| lo[a].min(v[a])), std::array::from_fn(|a| hi[a].max(v[a])))
+    });
+    let extent = (0..3).map(|a| max[a] - min[a]).fold(0.0, f64::max);
+    if triangles.is_empty() || extent <= 0.0 {
+        return None;
+    }
+
+    let spacing = extent / (max_samples.max(2 * PADDING + 2) - 1 - 2 * PADDING) as f64;
+    let origin = min.map(|m| m - PADDING as f64 * spacing);
+    let dims: [usize; 3] = std::array::from_fn(|a| ((max[a] - min[a]) / spacing).ceil() as usize + 1 + 2 * PADDING);
+    let band = BAND_SAMPLES * spacing;
+    let mut grid = DistanceGrid { origin, spacing, dims, band, values: vec![band; dims.iter().product()] };
+
+    // Samples a triangle's bounding box touches, optionally grown by `margin`
+    let sample_range = |tri: &[[f64; 3]; 3], axis: usize, margin: f64| {
+        let lo = tri.iter().map(|v| v[axis]).fold(f64::INFINITY, f64::min) - margin;
+        let hi = tri.iter().map(|v| v[axis]).fold(f64::NEG_INFINITY, f64::max) + margin;
+        let first = ((lo - origin[axis]) / spacing).ceil().max(0.0) as usize;
+        let last = (((hi - origin[axis]) / spacing).floor().max(-1.0) + 1.0) as usize;
+        first..last.min(dims[axis])
+    };
+
+    // Unsigned distance, only near each triangle
+    for tri in &triangles {
+        for k in sample_range(tri, 2, band) {
+            for j in sample_range(tri, 1, band) {
+                for i in sample_range(tri, 0, band) {
+                    let p = [i, j, k].map(|n| n as f64);
+                    let p = std::array::from_fn(|a| origin[a] + p[a] * spacing);
+                    let index = grid.index([i, j, k]);
+                    grid.values[index] = grid.values[index].min(point_triangle_distance(p, tri));
+                }
+            }
+        }
+    }
+
+    // Sign from the parity of crossings below each sample, along a ray
+    // nudged off the grid so it doesn't graze edges and vertices
+    let nudge = [0.000_137 * spacing, 0.000_291 * spacing];
+    let mut crossings: Vec<Vec<f64>> = vec![Vec::new(); dims[0] * dims[1]];
+    for tri in &triangles {
+        for j in sample_range(tri, 1, spacing) {
+            for i in sample_range(tri, 0, spacing) {
+                let x = origin[0] + i as f64 * spacing + nudge[0];
+                let y = origin[1] + j as f64 * spacing + nudge[1];
+                if let Some(z) = ray_crossing(x, y, tri) {
+                    crossings[i + dims[0] * j].push(z);
+                }
+            }
+        }
+    }
+    for (column, hits) in crossings.iter_mut().enumerate() {
+        hits.sort_by(f64::total_cmp);
+        let (i, j) = (column % dims[0], column / dims[0]);
+        for k in 0..dims[2] {
+            let z = origin[2] + k as f64 * spacing;
+            if hits.iter().take_while(|&&h| h < z).count() % 2 == 1 {
+                let index = grid.index([i, j, k]);
+                grid.values[index] = -grid.values[index];
+            }
+        }
+    }
+    Some(grid)
+}
+
+/// Height at which the vertical line through `(x, y)` crosses a triangle
+pub fn ray_crossing(x: f64, y: f64, [a, b, c]: &[[f64; 3]; 3]) -> Option<f64> {
+    let det = (b[1] - c[1]) * (a[0] - c[0]) + (c[0] - b[0]) * (a[1] - c[1]);
+    if det == 0.0 {
+        return None;
+    }
+    let l1 = ((b[1] - c[1]) * (x - c[0]) + (c[0] - b[0]) * (y - c[1])) / det;
+    let l2 = ((c[1] - a[1]) * (x - c[0]) + (a[0] - c[0]) * (y - c[1])) / det;
+    let l3 = 1.0 - l1 - l2;
+    (l1 >= 0.0 && l2 >= 0.0 && l3 >= 0.0).then(|| l1 * a[2] + l2 * b[2] + l3 * c[2])
+}
+
+/// Distance from `p` to the closest point on a triangle
+fn point_triangle_distance(p: [f64; 3], tri: &[[f64; 3]; 3]) -> f64 {
+    let [a, b, c] = *tri;
+    let sub = |u: [f64; 3], v: [f64; 3]| -> [f64; 3] { std::array::from_fn(|i| u[i] - v[i]) };
+    let dot = |u: [f64; 3], v: [f64; 3]| u[0] * v[0] + u[1] * v[1] + u[2] * v[2];
+    let along = |o: [f64; 3], d: [f64; 3], t: f64| -> [f64; 3] { std::array::from_fn(|i| o[i] + d[i] * t) };
+
+    // Closest point by Voronoi region, after Ericson's Real-Time Collision Detection
+    let (ab, ac, ap) = (sub(b, a), sub(c, a), sub(p, a));
+    let (d1, d2) = (dot(ab, ap), dot(ac, ap));
+    let closest = if d1 <= 0.0 && d2 <= 0.0 {
+        a
+    } else {
+        let bp = sub(p, b);
+        let (d3, d4) = (dot(ab, bp), dot(ac, bp));
+        let cp = sub(p, c);
+        let (d5, d6) = (dot(ab, cp), dot(ac, cp));
+        let vc = d1 * d4 - d3 * d2;
+        let vb = d5 * d2 - d1 * d6;
+        let va = d3 * d6 - d5 * d4;
+        if d3 >= 0.0 && d4 <= d3 {
+            b
+        } else if d6 >= 0.0 && d5 <= d6 {
+            c
+        } else if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+            along(a, ab, d1 / (d1 - d3))
+        } else if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+            along(a, ac, d2 / (d2 - d6))
+        } else if va <= 0.0 && (d4 - d3) >= 0.0 && (d5 - d6) >= 0.0 {
+            along(b, sub(c, b), (d4 - d3) / ((d4 - d3) + (d5 - d6)))
+        } else {
+            let denom = va + vb + vc;
+            if denom == 0.0 {
+                a
+            } else {
+                along(along(a, ab, vb / denom), ac, vc / denom)
+            }
+        }
+    };
+    let d = sub(p, closest);
+    dot(d, d).sqrt()
+}
@@ -0,0 +1,95 @@
+use anyhow::Result;
+use fidget::{shape::EzShape, types::Interval, vm::VmShape};
+
+use super::bounds::{detect_bounds, Bounds};
+
+/// Distance from the origin at which the field is checked for an inverted sign
+const FAR_AWAY: f32 = 1.0e6;
+
+/// Why meshing `shape` over `region` gave no triangles, and what to change
+///
+/// `shape` and `region` are in model space; `cell` is the size of the
+/// finest octree cell at `depth`.  Interval arithmetic over the region
+/// tells the likely causes apart: a shape that misses the region, one the
+/// region sits entirely inside, or a surface too small or too broken for
+/// the octree to find.
+pub fn diagnose_empty_mesh(shape: &VmShape, region: &Bounds, cell: f32, depth: u8) -> Result<String> {
+    let extent_ok = (0..3).all(|i| {
+        let size = region.max[i] - region.min[i];
+        size.is_finite() && size > 0.0
+    });
+    if !extent_ok {
+        return Ok(format!(
+            "The meshing region, {:?} to {:?}, has no volume; check the scale given to set_scale() or the box \
+             given to set_bounds().",
+            region.min, region.max
+        ));
+    }
+
+    let tape = shape.ez_interval_tape();
+    let mut eval = VmShape::new_interval_eval();
+    let (value, _) = eval.eval(
+        &tape,
+        Interval::new(region.min[0], region.max[0]),
+        Interval::new(region.min[1], region.max[1]),
+        Interval::new(region.min[2], region.max[2]),
+    )?;
+
+    if value.lower() > 0.0 {
+        return Ok(match detect_bounds(shape)? {
+            Some(found) => format!(
+                "The shape lies outside the meshing region: it spans {:?} to {:?}, but the region is {:?} to {:?}. \
+                 Widen set_bounds() to cover it, or remove it to let the bounds be detected.",
+                found.min, found.max, region.min, region.max
+            ),
+            None if far_inside(shape)? => inverted_message(),
+            None => "The shape is empty: its distance is positive everywhere. A difference() or intersection() \
+                     may be removing all of it."
+                .to_string(),
+        });
+    }
+    if value.upper() < 0.0 {
+        if far_inside(shape)? {
+            return Ok(inverted_message());
+        }
+        return Ok(format!(
+            "The meshing region, {:?} to {:?}, lies entirely inside the shape, so there's no surface to mesh. \
+             Widen set_bounds() or lower set_scale() so the region contains the whole model.",
+            region.min, region.max
+        ));
+    }
+
+    let center = region.center();
+    let point_tape = shape.ez_point_tape();
+    let mut point_eval = VmShape::new_point_eval();
+    let (at_center, _) = point_eval.eval(&point_tape, center[0], center[1], center[2])?;
+    if value.lower().is_nan() || value.upper().is_nan() || at_center.is_nan() {
+        return Ok("The distance field is NaN in the meshing region. Look for a division by zero, or sqrt() or \
+                   log() of a negative number, in the script."
+            .to_string());
+    }
+    Ok(format!(
+        "The surface is smaller than an octree cell, {} across at depth {}, so it fell between the samples. \
+         Raise the depth, or shrink the meshing region around the model.",
+        cell, depth
+    ))
+}
+
+/// Whether the field is negative at every corner of a huge cube, as it is for an inside-out shape
+fn far_inside(shape: &VmShape) -> Result<bool> {
+    let tape = shape.ez_point_tape();
+    let mut eval = VmShape::new_point_eval();
+    for corner in 0..8 {
+        let [x, y, z] = std::array::from_fn(|i| if corner & (1 << i) != 0 { FAR_AWAY } else { -FAR_AWAY });
+        if eval.eval(&tape, x, y, z)?.0 >= 0.0 {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+fn inverted_message() -> String {
+    "The shape's sign is inverted: its distance is negative far from the model, so everything outside it counts as \
+     solid. Negate the distance, or swap the operands of difference()."
+        .to_string()
+}
@@ -0,0 +1,141 @@
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use super::measure::MeshMeasurements;
+
+/// A common material the settings can pick instead of entering a density and price
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Material {
+    Pla,
+    Petg,
+    Abs,
+    Resin,
+    Aluminum,
+}
+
+/// A material and the figures estimates use for it
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct MaterialPreset {
+    pub material: Material,
+    pub name: &'static str,
+    /// Grams per cubic centimeter
+    pub density: f64,
+    /// Typical price per kilogram, in US dollars
+    pub cost_per_kg: f64,
+}
+
+impl Material {
+    pub const ALL: [Material; 5] = [Material::Pla, Material::Petg, Material::Abs, Material::Resin, Material::Aluminum];
+
+    /// Density and price of the material; prices are rough retail figures for filament, resin and bar stock
+    pub fn preset(self) -> MaterialPreset {
+        let (name, density, cost_per_kg) = match self {
+            Material::Pla => ("PLA", 1.24, 20.0),
+            Material::Petg => ("PETG", 1.27, 22.0),
+            Material::Abs => ("ABS", 1.04, 20.0),
+            Material::Resin => ("Resin", 1.12, 40.0),
+            Material::Aluminum => ("Aluminum", 2.70, 8.0),
+        };
+        MaterialPreset { material: self, name, density, cost_per_kg }
+    }
+}
+
+/// Printer and filament settings used to estimate a print, in millimeters and seconds
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PrintProfile {
+    pub layer_height: f64,
+    /// Fraction of the interior filled, from 0 to 1
+    pub infill: f64,
+    /// Thickness of the solid walls, top and bottom around the infill
+    pub wall_thickness: f64,
+    pub filament_diameter: f64,
+    /// Filament density, in grams per cubic centimeter
+    pub filament_density: f64,
+    /// Filament price per kilogram
+    pub cost_per_kg: f64,
+    /// Preset whose density and price replace the two above
+    pub material: Option<Material>,
+    /// Plastic the printer lays down per second, in cubic millimeters
+    pub flow_rate: f64,
+    /// Time spent on travel and layer changes for each layer
+    pub layer_time: f64,
+}
+
+impl Default for PrintProfile {
+    /// PLA on a typical 0.4 mm nozzle printer
+    fn default() -> Self {
+        PrintProfile {
+            layer_height: 0.2,
+            infill: 0.2,
+            wall_thickness: 0.8,
+            filament_diameter: 1.75,
+            filament_density: 1.24,
+            cost_per_kg: 20.0,
+            material: None,
+            flow_rate: 8.0,
+            layer_time: 2.0,
+        }
+    }
+}
+
+impl PrintProfile {
+    pub fn validate(&self) -> Result<(), String> {
+        let positive = [self.layer_height, self.filament_diameter, self.filament_density, self.flow_rate];
+        if !positive.iter().all(|&v| v.is_finite() && v > 0.0) {
+            return Err("Layer height, filament size and density, and flow rate must be greater than zero".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.infill) {
+            return Err("Infill must be between 0 and 1".to_string());
+        }
+        if !(self.wall_thickness >= 0.0 && self.layer_time >= 0.0 && self.cost_per_kg >= 0.0) {
+            return Err("Wall thickness, layer time and price can't be negative".to_string());
+        }
+        Ok(())
+    }
+
+    /// Density and price per kilogram, from the material preset if one is chosen
+    pub fn material_properties(&self) -> (f64, f64) {
+        match self.material.map(Material::preset) {
+            Some(preset) => (preset.density, preset.cost_per_kg),
+            None => (self.filament_density, self.cost_per_kg),
+        }
+    }
+}
+
+/// Rough cost of printing a mesh
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct PrintEstimate {
+    pub layers: u32,
+    pub time_secs: f64,
+    pub mass_grams: f64,
+    pub filament_meters: f64,
+    /// Price of the material used, at the profile's price per kilogram
+    pub cost: f64,
+}
+
+/// Estimate printing a mesh measured in millimeters
+///
+/// The walls are taken to be the surface area times the wall thickness,
+/// and the rest of the volume is filled at the infill fraction.  Time is
+/// that plastic at the profile's flow rate plus a fixed time per layer, so
+/// it ignores supports, travel and acceleration; treat it as a guide for
+/// comparing designs rather than a slicer's figure.
+pub fn estimate_print(measurements: &MeshMeasurements, profile: &PrintProfile) -> PrintEstimate {
+    let walls = (measurements.surface_area * profile.wall_thickness).min(measurements.volume);
+    let plastic = walls + (measurements.volume - walls) * profile.infill;
+    let height = measurements.bounds.map_or(0.0, |b| (b.max[2] - b.min[2]) as f64);
+    let layers = (height / profile.layer_height).ceil() as u32;
+    let filament_area = PI * (profile.filament_diameter / 2.0).powi(2);
+    let (density, cost_per_kg) = profile.material_properties();
+    let mass_grams = plastic / 1000.0 * density;
+    PrintEstimate {
+        layers,
+        time_secs: plastic / profile.flow_rate + layers as f64 * profile.layer_time,
+        mass_grams,
+        filament_meters: plastic / filament_area / 1000.0,
+        cost: mass_grams / 1000.0 * cost_per_kg,
+    }
+}
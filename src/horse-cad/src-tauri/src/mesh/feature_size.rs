@@ -0,0 +1,218 @@
+use anyhow::Result;
+use fidget::{shape::EzShape, types::Grad, vm::VmShape};
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+
+use super::bounds::Bounds;
+use super::grid::sample_grid;
+use crate::state::CancelToken;
+
+/// Samples along the longest side of the grid that surface points are picked from
+const SEED_RESOLUTION: usize = 64;
+
+/// Most surface points traced, so a large surface doesn't trace for long
+const MAX_SEEDS: usize = 8192;
+
+/// Most sphere-tracing steps across a feature before the ray is given up on
+const MAX_STEPS: usize = 128;
+
+/// Share of the traced features allowed to be smaller than the one reported
+///
+/// A few rays glance along an edge or start where the gradient is poor,
+/// and measure much less than anything really there.
+const FEATURE_PERCENTILE: f32 = 0.02;
+
+/// Share of its width a ray has to get clear of the surface somewhere for the width to count
+///
+/// A ray starting on an edge can run along the face beside it, never
+/// leaving the surface, and would measure a feature that isn't there.
+const MIN_CLEARANCE: f32 = 0.2;
+
+/// Finest cells a feature has to span to come through meshing intact
+const CELLS_PER_FEATURE: f32 = 2.0;
+
+/// Deepest depth that's suggested
+pub const MAX_SUGGESTED_DEPTH: u8 = 12;
+
+/// The smallest feature of a shape and the depth it takes to mesh it
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FeatureSizeReport {
+    /// Thinnest wall or narrowest gap found, in model units, or `None` if neither was
+    pub min_feature: Option<f32>,
+    /// Middle of that feature, in model coordinates
+    pub location: Option<[f32; 3]>,
+    /// Thinnest wall found, across the inside of the shape
+    pub thinnest_wall: Option<f32>,
+    /// Narrowest gap found, across the outside between two surfaces
+    pub narrowest_gap: Option<f32>,
+    /// Surface points traced from
+    pub sampled: usize,
+    /// Depth the estimate was made for
+    pub depth: u8,
+    /// Side of the finest octree cell at `depth`
+    pub cell_size: f32,
+    /// Shallowest depth whose finest cells are small enough for the smallest feature
+    pub suggested_depth: Option<u8>,
+    /// The smallest feature needs more than `MAX_SUGGESTED_DEPTH`, which is suggested instead
+    pub beyond_max_depth: bool,
+}
+
+impl FeatureSizeReport {
+    /// Whether meshing at `depth` will visibly lose the smallest feature
+    pub fn loses_detail(&self) -> bool {
+        self.suggested_depth.is_some_and(|suggested| self.depth < suggested)
+    }
+}
+
+/// A ray traced across a wall or gap from a point on the surface
+struct Trace {
+    origin: Vector3<f32>,
+    direction: Vector3<f32>,
+    /// Tracing a wall, inside the shape, rather than a gap outside it
+    wall: bool,
+    t: f32,
+    /// Furthest the ray has been from any surface
+    clearance: f32,
+    /// Distance to the far surface once it's been reached
+    width: Option<f32>,
+    done: bool,
+}
+
+/// Estimate the smallest feature of `shape` in `region`, and the depth that resolves it
+///
+/// Points near the surface are picked from a coarse grid over the region
+/// and moved onto the surface along the field's gradient.  From each, a
+/// ray is sphere-traced inwards to the far side of the wall and outwards
+/// to the far side of any gap, and the distance across is the feature's
+/// width there.  The smallest feature is taken at a low percentile of the
+/// widths, and rays that never get clear of the surface aren't counted,
+/// so rays glancing along an edge don't decide it.  The meshing cube is
+/// the region's widest side grown by `margin`, as the compile meshes it,
+/// and the suggested depth is the shallowest whose finest cells fit the
+/// feature across twice.  Features thinner than the grid between surface
+/// points are still found, since the points sit on their surface, but
+/// anything that falls between grid samples entirely isn't.  The estimate
+/// assumes an exact distance field, so it's rougher after deformers and
+/// smooth blends.  Returns `None` if `cancel` is triggered.
+pub fn feature_size(
+    shape: &VmShape,
+    region: &Bounds,
+    margin: f32,
+    depth: u8,
+    cancel: &CancelToken,
+) -> Result<Option<FeatureSizeReport>> {
+    let side = 2.0 * region.half_extent() * (1.0 + margin);
+    let mut report =
+        FeatureSizeReport { depth, cell_size: side / (1u64 << depth.min(63)) as f32, ..Default::default() };
+    let Some(grid) = sample_grid(shape, region, SEED_RESOLUTION, cancel)? else { return Ok(None) };
+    let step = grid.layout.step;
+    let [nx, ny, _] = grid.layout.dims;
+
+    // Samples close enough to the surface that the gradient leads onto it
+    let near: Vec<usize> = (0..grid.values.len()).filter(|&i| grid.values[i].abs() < step).collect();
+    let stride = near.len().div_ceil(MAX_SEEDS).max(1);
+    let points: Vec<Vector3<f32>> = near
+        .iter()
+        .step_by(stride)
+        .map(|&i| {
+            let cell = [i % nx, i / nx % ny, i / (nx * ny)];
+            Vector3::from_fn(|a, _| grid.layout.min[a] + cell[a] as f32 * step)
+        })
+        .collect();
+    if points.is_empty() {
+        return Ok(Some(report));
+    }
+
+    let mut traces = Vec::with_capacity(points.len() * 2);
+    for (origin, normal) in onto_surface(shape, points)? {
+        for wall in [true, false] {
+            let direction = if wall { -normal } else { normal };
+            traces.push(Trace { origin, direction, wall, t: step * 1e-3, clearance: 0.0, width: None, done: false });
+        }
+    }
+    report.sampled = traces.len() / 2;
+
+    // Close enough to the far surface to have reached it, and as far as a ray goes looking for one
+    let arrived = step * 2.5e-4;
+    let reach = side * 3f32.sqrt();
+    let tape = shape.ez_float_slice_tape();
+    let mut eval = VmShape::new_float_slice_eval();
+    for _ in 0..MAX_STEPS {
+        if cancel.is_cancelled() {
+            return Ok(None);
+        }
+        let active: Vec<usize> = (0..traces.len()).filter(|&i| !traces[i].done).collect();
+        if active.is_empty() {
+            break;
+        }
+        let at: Vec<Vector3<f32>> =
+            active.iter().map(|&i| traces[i].origin + traces[i].direction * traces[i].t).collect();
+        let [xs, ys, zs] = [0, 1, 2].map(|a| at.iter().map(|p| p[a]).collect::<Vec<f32>>());
+        let values = eval.eval(&tape, &xs, &ys, &zs)?;
+        for (&i, &d) in active.iter().zip(values) {
+            let trace = &mut traces[i];
+            // Distance left to the far surface, as far as the field can tell
+            let left = if trace.wall { -d } else { d };
+            if !left.is_finite() || trace.t > reach {
+                trace.done = true;
+            } else if left < arrived {
+                trace.width = Some(trace.t).filter(|&t| trace.clearance >= MIN_CLEARANCE * t);
+                trace.done = true;
+            } else {
+                trace.clearance = trace.clearance.max(left);
+                trace.t += left;
+            }
+        }
+    }
+
+    let mut widths: Vec<(f32, bool, Vector3<f32>)> = traces
+        .iter()
+        .filter_map(|trace| trace.width.map(|w| (w, trace.wall, trace.origin + trace.direction * (w / 2.0))))
+        .collect();
+    widths.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let percentile = |widths: &[&(f32, bool, Vector3<f32>)]| {
+        let index = ((widths.len() as f32 * FEATURE_PERCENTILE) as usize).min(widths.len().checked_sub(1)?);
+        Some(*widths[index])
+    };
+    let all: Vec<_> = widths.iter().collect();
+    let walls: Vec<_> = widths.iter().filter(|w| w.1).collect();
+    let gaps: Vec<_> = widths.iter().filter(|w| !w.1).collect();
+    report.thinnest_wall = percentile(&walls).map(|w| w.0);
+    report.narrowest_gap = percentile(&gaps).map(|w| w.0);
+    if let Some((width, _, at)) = percentile(&all) {
+        let needed = (side * CELLS_PER_FEATURE / width).log2().ceil().max(1.0);
+        report.min_feature = Some(width);
+        report.location = Some(at.into());
+        report.suggested_depth = Some((needed as u8).min(MAX_SUGGESTED_DEPTH));
+        report.beyond_max_depth = needed > MAX_SUGGESTED_DEPTH as f32;
+    }
+    Ok(Some(report))
+}
+
+/// Move each of `points` onto the surface of `shape`, returning it with the outward normal there
+///
+/// Two Newton steps along the gradient are taken, so points on curved
+/// surfaces land on them closely enough to start tracing from.  Points
+/// where the field is flat or not finite are dropped.
+fn onto_surface(shape: &VmShape, mut points: Vec<Vector3<f32>>) -> Result<Vec<(Vector3<f32>, Vector3<f32>)>> {
+    let tape = shape.ez_grad_slice_tape();
+    let mut eval = VmShape::new_grad_slice_eval();
+    let mut normals = vec![Vector3::zeros(); points.len()];
+    for _ in 0..2 {
+        let xs: Vec<Grad> = points.iter().map(|p| Grad::new(p.x, 1.0, 0.0, 0.0)).collect();
+        let ys: Vec<Grad> = points.iter().map(|p| Grad::new(p.y, 0.0, 1.0, 0.0)).collect();
+        let zs: Vec<Grad> = points.iter().map(|p| Grad::new(p.z, 0.0, 0.0, 1.0)).collect();
+        let out = eval.eval(&tape, &xs, &ys, &zs)?;
+        let mut kept = Vec::with_capacity(points.len());
+        for (p, g) in points.iter().zip(out) {
+            let gradient = Vector3::new(g.dx, g.dy, g.dz);
+            let length = gradient.norm();
+            if length > 0.0 && length.is_finite() && g.v.is_finite() {
+                let normal = gradient / length;
+                kept.push((p - normal * (g.v / length), normal));
+            }
+        }
+        (points, normals) = kept.into_iter().unzip();
+    }
+    Ok(points.into_iter().zip(normals).collect())
+}
@@ -0,0 +1,143 @@
+use anyhow::Result;
+use fidget::{mesh::Mesh, vm::VmShape};
+use nalgebra::{Matrix3, Vector3};
+use serde::{Deserialize, Serialize};
+
+use super::points::{project_to_surface, PointCloud};
+use crate::state::CancelToken;
+
+/// How hard the sharp-feature pass tries to rebuild crisp edges and corners
+///
+/// fidget's dual contouring places each vertex with a fixed QEF cutoff; this
+/// pass solves each vertex's QEF again from the distance field with these
+/// thresholds instead.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FeatureOptions {
+    /// Run the pass after meshing
+    pub enabled: bool,
+    /// Smallest QEF eigenvalue, as a share of the largest, still taken as a feature direction
+    ///
+    /// Lower values snap to more edges and corners, and to more surface
+    /// noise; higher ones leave near-flat regions alone.  The default is
+    /// fidget's own cutoff.
+    pub eigenvalue_cutoff: f32,
+    /// Largest QEF error accepted for a feature vertex, as a share of its squared edge length
+    ///
+    /// Vertices whose best fit is worse, or that would move further than
+    /// their edges are long, fall back to the mass point of their samples.
+    pub max_error: f32,
+}
+
+impl Default for FeatureOptions {
+    fn default() -> Self {
+        FeatureOptions { enabled: false, eigenvalue_cutoff: 1e-3, max_error: 0.01 }
+    }
+}
+
+impl FeatureOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.eigenvalue_cutoff > 0.0 && self.eigenvalue_cutoff < 1.0) {
+            return Err("Feature eigenvalue cutoff must be between 0 and 1".to_string());
+        }
+        if !(self.max_error.is_finite() && self.max_error >= 0.0) {
+            return Err("Feature QEF error limit can't be negative".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// What the sharp-feature pass did
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeatureReport {
+    /// Vertices whose samples span an edge or corner
+    pub feature_vertices: usize,
+    /// Feature vertices placed at the QEF solution
+    pub sharpened: usize,
+    /// Feature vertices placed at their mass point, because the fit was too poor
+    pub mass_point_fallbacks: usize,
+}
+
+/// Re-place the vertices of `mesh` on the sharp features of `shape`
+///
+/// Each vertex is sampled along with the centres of its triangles, snapped
+/// onto the surface, giving a plane per sample.  Where those planes span
+/// more than one direction the vertex sits on a feature, and moves to the
+/// point that best fits them all; elsewhere it is left alone.  `shape`
+/// must be in the mesh's coordinates.  Returns `None` if `cancel` is
+/// triggered.
+pub fn sharpen_features(
+    shape: &VmShape,
+    mesh: &mut Mesh,
+    options: &FeatureOptions,
+    cancel: &CancelToken,
+) -> Result<Option<FeatureReport>> {
+    let vertex_count = mesh.vertices.len();
+    let mut samples = PointCloud::default();
+    let mut vertex_triangles = vec![Vec::new(); vertex_count];
+    let mut edge_length = vec![(0.0f32, 0usize); vertex_count];
+    for v in &mesh.vertices {
+        samples.points.push(*v);
+        samples.normals.push(Vector3::zeros());
+    }
+    for (f, t) in mesh.triangles.iter().enumerate() {
+        let [a, b, c] = [t.x, t.y, t.z].map(|i| mesh.vertices[i]);
+        samples.points.push((a + b + c) / 3.0);
+        samples.normals.push((b - a).cross(&(c - a)).try_normalize(f32::EPSILON).unwrap_or_else(Vector3::zeros));
+        for (k, i) in [t.x, t.y, t.z].into_iter().enumerate() {
+            let j = [t.y, t.z, t.x][k];
+            vertex_triangles[i].push(f);
+            edge_length[i].0 += (mesh.vertices[j] - mesh.vertices[i]).norm();
+            edge_length[i].1 += 1;
+        }
+    }
+    if project_to_surface(shape, &mut samples, cancel)?.is_none() {
+        return Ok(None);
+    }
+
+    let mut report = FeatureReport::default();
+    for (v, triangles) in vertex_triangles.iter().enumerate() {
+        let planes: Vec<_> = std::iter::once(v)
+            .chain(triangles.iter().map(|f| vertex_count + f))
+            .map(|i| (samples.points[i], samples.normals[i]))
+            .filter(|(_, n)| *n != Vector3::zeros())
+            .collect();
+        let (total, count) = edge_length[v];
+        if planes.len() < 2 || count == 0 {
+            continue;
+        }
+        let h = total / count as f32;
+        let center = planes.iter().map(|(p, _)| p).sum::<Vector3<f32>>() / planes.len() as f32;
+        let mut ata = Matrix3::zeros();
+        let mut atb = Vector3::zeros();
+        for (p, n) in &planes {
+            ata += n * n.transpose();
+            atb += n * n.dot(&(p - center));
+        }
+
+        let svd = ata.svd(true, true);
+        let largest = svd.singular_values.max();
+        let cutoff = largest * options.eigenvalue_cutoff;
+        let rank = svd.singular_values.iter().filter(|s| **s >= cutoff).count();
+        if rank < 2 {
+            continue;
+        }
+        report.feature_vertices += 1;
+        let Ok(offset) = svd.solve(&atb, cutoff) else {
+            mesh.vertices[v] = center;
+            report.mass_point_fallbacks += 1;
+            continue;
+        };
+        let pos = center + offset;
+        let error = planes.iter().map(|(p, n)| n.dot(&(pos - p)).powi(2)).sum::<f32>() / planes.len() as f32;
+        let moved_too_far = (pos - mesh.vertices[v]).norm() > h;
+        if error > options.max_error * h * h || moved_too_far || !pos.iter().all(|c| c.is_finite()) {
+            mesh.vertices[v] = center;
+            report.mass_point_fallbacks += 1;
+        } else {
+            mesh.vertices[v] = pos;
+            report.sharpened += 1;
+        }
+    }
+    Ok(Some(report))
+}
@@ -0,0 +1,69 @@
+use anyhow::Result;
+use fidget::{shape::EzShape, vm::VmShape};
+use serde::{Deserialize, Serialize};
+
+use super::bounds::Bounds;
+use crate::state::CancelToken;
+
+/// Most samples a grid may have along its longest side
+pub const MAX_GRID_RESOLUTION: usize = 512;
+
+/// Where a sampled grid sits, so its values can be placed back in model space
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GridLayout {
+    /// Samples along X, Y and Z
+    pub dims: [usize; 3],
+    /// Position of the first sample
+    pub min: [f32; 3],
+    /// Distance between neighbouring samples, the same along every axis
+    pub step: f32,
+}
+
+/// Distance field values at the corners of a regular grid
+pub struct SdfGrid {
+    pub layout: GridLayout,
+    /// One value per sample, X varying fastest and Z slowest
+    pub values: Vec<f32>,
+}
+
+/// Sample `shape` on a cubic grid covering `bounds`
+///
+/// The grid has `resolution` samples along the longest side of the bounds
+/// and the same spacing along the others, and runs one step past the
+/// bounds on every side, so the surface is always enclosed.  Returns
+/// `None` if `cancel` is triggered, which is checked between Z layers.
+pub fn sample_grid(
+    shape: &VmShape,
+    bounds: &Bounds,
+    resolution: usize,
+    cancel: &CancelToken,
+) -> Result<Option<SdfGrid>> {
+    let size = (0..3).map(|i| bounds.max[i] - bounds.min[i]).fold(0.0, f32::max);
+    let step = match size / resolution.saturating_sub(1).max(1) as f32 {
+        step if step > 0.0 && step.is_finite() => step,
+        _ => 1.0,
+    };
+    let min: [f32; 3] = std::array::from_fn(|i| bounds.min[i] - step);
+    let dims: [usize; 3] = std::array::from_fn(|i| ((bounds.max[i] - bounds.min[i]) / step).ceil() as usize + 3);
+    let [nx, ny, nz] = dims;
+
+    let layer = nx * ny;
+    let (mut xs, mut ys) = (Vec::with_capacity(layer), Vec::with_capacity(layer));
+    for j in 0..ny {
+        for i in 0..nx {
+            xs.push(min[0] + i as f32 * step);
+            ys.push(min[1] + j as f32 * step);
+        }
+    }
+    let tape = shape.ez_float_slice_tape();
+    let mut eval = VmShape::new_float_slice_eval();
+    let mut values = Vec::with_capacity(layer * nz);
+    for k in 0..nz {
+        if cancel.is_cancelled() {
+            return Ok(None);
+        }
+        let zs = vec![min[2] + k as f32 * step; layer];
+        values.extend_from_slice(eval.eval(&tape, &xs, &ys, &zs)?);
+    }
+    Ok(Some(SdfGrid { layout: GridLayout { dims, min, step }, values }))
+}
@@ -0,0 +1,75 @@
+use anyhow::{anyhow, Result};
+use fidget::{
+    context::{Context, Node, Tree},
+    vm::VmShape,
+};
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+
+use super::probe::probe_point;
+
+/// How `hollow` shells a shape and where it drills drain holes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HollowOptions {
+    /// Wall left inside the surface, in model units
+    pub thickness: f32,
+    /// Diameter of each drain hole, in model units
+    pub drain_diameter: f32,
+    /// Points on the surface to drill a drain hole through, in model coordinates
+    pub drain_holes: Vec<[f32; 3]>,
+}
+
+impl Default for HollowOptions {
+    fn default() -> Self {
+        HollowOptions { thickness: 2.0, drain_diameter: 3.0, drain_holes: Vec::new() }
+    }
+}
+
+impl HollowOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.thickness.is_finite() && self.thickness > 0.0) {
+            return Err("Wall thickness must be greater than zero".to_string());
+        }
+        let diameter = self.drain_diameter.is_finite() && self.drain_diameter > 0.0;
+        if !self.drain_holes.is_empty() && !diameter {
+            return Err("Drain hole diameter must be greater than zero".to_string());
+        }
+        if !self.drain_holes.iter().flatten().all(|v| v.is_finite()) {
+            return Err("Drain hole positions must be finite".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Hollow the shape at `root` to a wall `thickness` thick, with drain holes through it
+///
+/// The wall is the part of the shape within `thickness` of its surface,
+/// as `shell()` makes it, so it's exact only where the distance field is.
+/// Each hole is a cylinder along the surface normal at its position, found
+/// from the field's gradient, reaching a wall and a diameter either side of
+/// it so it opens into the cavity; positions should be on or near the
+/// surface.
+pub fn hollow(ctx: &mut Context, root: Node, options: &HollowOptions) -> Result<Node> {
+    let shape = ctx.export(root)?;
+    let mut hollowed = shape.clone().max(-(shape + options.thickness as f64));
+    if !options.drain_holes.is_empty() {
+        let field = VmShape::new(ctx, root)?;
+        let radius = options.drain_diameter as f64 / 2.0;
+        let reach = (options.thickness + options.drain_diameter) as f64;
+        for &p in &options.drain_holes {
+            let normal = Vector3::from(probe_point(&field, p)?.normal);
+            if normal == Vector3::zeros() {
+                return Err(anyhow!("the shape has no surface direction at drain hole {:?}", p));
+            }
+            let [dx, dy, dz] = [Tree::x() - p[0] as f64, Tree::y() - p[1] as f64, Tree::z() - p[2] as f64];
+            let along = dx.clone() * normal.x as f64 + dy.clone() * normal.y as f64 + dz.clone() * normal.z as f64;
+            let [rx, ry, rz] = [(dx, normal.x), (dy, normal.y), (dz, normal.z)]
+                .map(|(d, n)| d - along.clone() * n as f64);
+            let radial = (rx.square() + ry.square() + rz.square()).sqrt();
+            let hole = (radial - radius).max(along.abs() - reach);
+            hollowed = hollowed.max(-hole);
+        }
+    }
+    Ok(ctx.import(&hollowed))
+}
@@ -0,0 +1,59 @@
+use serde::{Deserialize, Serialize};
+
+/// Rough memory the pipeline holds per triangle, in bytes
+///
+/// Covers the octree, the mesh and its welded copy, per-triangle colors and
+/// the encoded STL, which are alive at the same time at the end of a compile.
+pub const BYTES_PER_TRIANGLE: u64 = 160;
+
+/// Octrees shallower than this are never checked, since they can't get large
+pub const MIN_CHECKED_DEPTH: u8 = 8;
+
+/// How many levels shallower than requested the estimating octree is built
+///
+/// Each level quarters the triangle count, so three levels costs about a
+/// sixtieth of the full build.
+pub const PROBE_LEVELS: u8 = 3;
+
+/// Limits on a single mesh, so a too-deep compile fails before it exhausts memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MeshLimits {
+    /// Most triangles a mesh may have, or 0 for no limit
+    pub max_triangles: u64,
+    /// Most memory a compile may be estimated to need, in megabytes, or 0 for no limit
+    pub max_memory_mb: u64,
+}
+
+impl Default for MeshLimits {
+    fn default() -> Self {
+        MeshLimits { max_triangles: 20_000_000, max_memory_mb: 4096 }
+    }
+}
+
+impl MeshLimits {
+    /// Check a mesh expected to have `triangles` at `depth`
+    ///
+    /// The error names the deepest depth that would fit, counting on each
+    /// level down quartering the triangles.
+    pub fn check(&self, triangles: u64, depth: u8) -> Result<(), String> {
+        let memory_mb = triangles * BYTES_PER_TRIANGLE / (1 << 20);
+        let over = if self.max_triangles > 0 && triangles > self.max_triangles {
+            format!("over the limit of {} triangles", self.max_triangles)
+        } else if self.max_memory_mb > 0 && memory_mb > self.max_memory_mb {
+            format!("needing about {} MB, over the limit of {} MB", memory_mb, self.max_memory_mb)
+        } else {
+            return Ok(());
+        };
+        let fits = |d: u8| {
+            let t = triangles.checked_shr(2 * (depth - d) as u32).unwrap_or(0);
+            (self.max_triangles == 0 || t <= self.max_triangles)
+                && (self.max_memory_mb == 0 || t * BYTES_PER_TRIANGLE / (1 << 20) <= self.max_memory_mb)
+        };
+        let hint = match (1..depth).rev().find(|&d| fits(d)) {
+            Some(d) => format!("; try depth {} or lower", d),
+            None => String::new(),
+        };
+        Err(format!("Meshing at depth {} would make about {} triangles, {}{}", depth, triangles, over, hint))
+    }
+}
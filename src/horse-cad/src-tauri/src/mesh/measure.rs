@@ -0,0 +1,86 @@
+use fidget::mesh::Mesh;
+use nalgebra::{Matrix3, Vector3};
+use serde::{Deserialize, Serialize};
+
+use super::bounds::Bounds;
+
+/// Size of a meshed solid, in the mesh's units
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct MeshMeasurements {
+    /// Enclosed volume; only meaningful for a watertight mesh
+    pub volume: f64,
+    pub surface_area: f64,
+    /// Box around the vertices, or `None` for an empty mesh
+    pub bounds: Option<Bounds>,
+}
+
+/// Measure the volume, surface area and bounding box of a mesh
+///
+/// The volume is the sum of signed tetrahedra from the origin to each
+/// triangle, so it's exact for a closed mesh whatever its position.  It's
+/// reported as a magnitude, so an inside-out mesh still measures correctly.
+pub fn measure_mesh(mesh: &Mesh) -> MeshMeasurements {
+    let (mut volume, mut surface_area) = (0.0, 0.0);
+    for t in &mesh.triangles {
+        let [a, b, c] = [t.x, t.y, t.z].map(|v| mesh.vertices[v].cast::<f64>());
+        volume += a.dot(&b.cross(&c)) / 6.0;
+        surface_area += (b - a).cross(&(c - a)).norm() / 2.0;
+    }
+
+    let bounds = mesh.vertices.iter().fold(None, |bounds: Option<Bounds>, v| {
+        let v = [v.x, v.y, v.z];
+        Some(match bounds {
+            Some(b) => Bounds {
+                min: std::array::from_fn(|i| b.min[i].min(v[i])),
+                max: std::array::from_fn(|i| b.max[i].max(v[i])),
+            },
+            None => Bounds { min: v, max: v },
+        })
+    });
+    MeshMeasurements { volume: volume.abs(), surface_area, bounds }
+}
+
+/// Mass, center of mass and inertia of a solid of uniform density
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct MassProperties {
+    pub density: f64,
+    pub mass: f64,
+    pub center_of_mass: [f64; 3],
+    /// Inertia tensor about the center of mass, in mass times length squared
+    pub inertia: [[f64; 3]; 3],
+}
+
+/// Integrate the mass properties of a closed mesh filled with `density`
+///
+/// Each triangle forms a tetrahedron with the origin, and the tetrahedra's
+/// signed volumes and second moments are summed, so cavities are subtracted
+/// as they should be.  The result is only meaningful for a watertight mesh.
+pub fn mass_properties(mesh: &Mesh, density: f64) -> MassProperties {
+    // Second moment of the unit tetrahedron at the origin, times 120
+    let canonical = Matrix3::new(2.0, 1.0, 1.0, 1.0, 2.0, 1.0, 1.0, 1.0, 2.0) / 120.0;
+    let mut volume = 0.0;
+    let mut first = Vector3::zeros();
+    let mut second = Matrix3::zeros();
+    for t in &mesh.triangles {
+        let [a, b, c] = [t.x, t.y, t.z].map(|v| mesh.vertices[v].cast::<f64>());
+        let corners = Matrix3::from_columns(&[a, b, c]);
+        let det = corners.determinant();
+        volume += det / 6.0;
+        first += (a + b + c) * (det / 24.0);
+        second += corners * canonical * corners.transpose() * det;
+    }
+    if volume == 0.0 {
+        return MassProperties { density, ..Default::default() };
+    }
+
+    let center = first / volume;
+    // Shift the second moment to the center of mass, then convert to inertia
+    let central = (second - center * center.transpose() * volume) * density;
+    let inertia = Matrix3::identity() * central.trace() - central;
+    MassProperties {
+        density,
+        mass: volume.abs() * density,
+        center_of_mass: center.into(),
+        inertia: std::array::from_fn(|i| std::array::from_fn(|j| inertia[(i, j)] * volume.signum())),
+    }
+}
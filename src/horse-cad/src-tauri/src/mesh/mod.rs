@@ -0,0 +1,35 @@
+pub mod bed;
+pub mod benchmark;
+pub mod bind;
+pub mod bounds;
+pub mod buffers;
+pub mod color;
+pub mod complexity;
+pub mod cull;
+pub mod diff;
+pub mod distance;
+pub mod empty;
+pub mod estimate;
+pub mod feature_size;
+pub mod features;
+pub mod grid;
+pub mod hollow;
+pub mod limits;
+pub mod measure;
+pub mod normals;
+pub mod outline;
+pub mod overhang;
+pub mod parts;
+pub mod points;
+pub mod probe;
+pub mod quality;
+pub mod region;
+pub mod repair;
+pub mod simplify;
+pub mod slice;
+pub mod supports;
+pub mod thickness;
+pub mod transform;
+pub mod units;
+pub mod validate;
+pub mod weld;
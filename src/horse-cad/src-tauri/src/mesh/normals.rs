@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+
+use fidget::mesh::Mesh;
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+
+/// Compute smooth per-vertex normals for an indexed mesh
+///
+/// Each face contributes its unnormalized cross product, so larger triangles
+/// have proportionally more influence on the shared vertex normal.
+pub fn vertex_normals(mesh: &Mesh) -> Vec<Vector3<f32>> {
+    let mut normals = vec![Vector3::zeros(); mesh.vertices.len()];
+
+    for t in &mesh.triangles {
+        let a = mesh.vertices[t.x];
+        let b = mesh.vertices[t.y];
+        let c = mesh.vertices[t.z];
+        let face_normal = (b - a).cross(&(c - a));
+        for i in t {
+            normals[*i] += face_normal;
+        }
+    }
+
+    for n in &mut normals {
+        *n = n.try_normalize(f32::EPSILON).unwrap_or_else(Vector3::z);
+    }
+    normals
+}
+
+/// How exported vertex normals are shaded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NormalMode {
+    /// One normal per vertex, averaging every face around it by its angle there
+    #[default]
+    Smooth,
+    /// Each face keeps its own normal, for a faceted look
+    Flat,
+    /// Smooth across edges, but split where faces meet at more than the crease angle
+    Crease,
+}
+
+/// Crease angle used when none is given, in degrees
+pub const DEFAULT_CREASE_ANGLE: f32 = 30.0;
+
+/// Normal policy for formats that store vertex normals
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NormalOptions {
+    pub mode: NormalMode,
+    /// Largest angle between faces that is still shaded smooth, in degrees, for `Crease`
+    pub crease_angle: f32,
+}
+
+impl Default for NormalOptions {
+    fn default() -> Self {
+        NormalOptions { mode: NormalMode::default(), crease_angle: DEFAULT_CREASE_ANGLE }
+    }
+}
+
+/// Normals for each corner of each triangle
+pub struct ShadedNormals {
+    /// Distinct normals, shared between corners that meet smoothly
+    pub normals: Vec<Vector3<f32>>,
+    /// Index into `normals` for each corner, in triangle order
+    pub corners: Vec<[usize; 3]>,
+}
+
+/// Normals for every corner of `mesh`, shaded as `options` asks
+///
+/// A corner's normal averages the faces around its vertex that are shaded
+/// together with its own face, each weighted by its angle at the vertex,
+/// so the result doesn't depend on how the surface was triangulated.
+/// Corners of a vertex that end up with the same normal share it.
+pub fn shade_normals(mesh: &Mesh, options: &NormalOptions) -> ShadedNormals {
+    let mut face_normals = Vec::with_capacity(mesh.triangles.len());
+    let mut corner_angles = Vec::with_capacity(mesh.triangles.len());
+    let mut vertex_faces = vec![Vec::new(); mesh.vertices.len()];
+    for (f, t) in mesh.triangles.iter().enumerate() {
+        let [a, b, c] = [t.x, t.y, t.z].map(|i| mesh.vertices[i]);
+        face_normals.push((b - a).cross(&(c - a)).try_normalize(f32::EPSILON).unwrap_or_else(Vector3::zeros));
+        corner_angles.push([(b - a).angle(&(c - a)), (c - b).angle(&(a - b)), (a - c).angle(&(b - c))]);
+        for (corner, i) in [t.x, t.y, t.z].into_iter().enumerate() {
+            vertex_faces[i].push((f, corner));
+        }
+    }
+    let threshold = match options.mode {
+        NormalMode::Smooth => -1.0,
+        NormalMode::Flat => 1.0,
+        NormalMode::Crease => options.crease_angle.clamp(0.0, 180.0).to_radians().cos(),
+    };
+
+    let mut shaded = ShadedNormals { normals: Vec::new(), corners: vec![[0; 3]; mesh.triangles.len()] };
+    for faces in &vertex_faces {
+        // Normals already given to this vertex's corners, with their indices
+        let mut seen: Vec<(Vector3<f32>, usize)> = Vec::new();
+        for &(f, corner) in faces {
+            let own = face_normals[f];
+            let normal = if options.mode == NormalMode::Flat {
+                own
+            } else {
+                faces
+                    .iter()
+                    .filter(|&&(g, _)| g == f || own.dot(&face_normals[g]) >= threshold)
+                    .map(|&(g, c)| face_normals[g] * corner_angles[g][c])
+                    .sum::<Vector3<f32>>()
+            };
+            // A corner whose faces cancel out falls back to its own face, or else to +Z
+            let normal =
+                normal.try_normalize(f32::EPSILON).or_else(|| own.try_normalize(0.5)).unwrap_or_else(Vector3::z);
+            let index = match seen.iter().find(|(n, _)| *n == normal) {
+                Some(&(_, index)) => index,
+                None => {
+                    shaded.normals.push(normal);
+                    seen.push((normal, shaded.normals.len() - 1));
+                    shaded.normals.len() - 1
+                }
+            };
+            shaded.corners[f][corner] = index;
+        }
+    }
+    shaded
+}
+
+/// Copy of `mesh` with a vertex for each distinct position and normal, and those normals
+///
+/// For formats that store one normal per vertex, where a hard edge needs
+/// its vertices duplicated.  Triangles keep their order, so per-triangle
+/// colors still line up.
+pub fn split_by_normals(mesh: &Mesh, shaded: &ShadedNormals) -> (Mesh, Vec<Vector3<f32>>) {
+    let mut split = Mesh::default();
+    let mut normals = Vec::new();
+    let mut index: HashMap<(usize, usize), usize> = HashMap::new();
+    for (t, corners) in mesh.triangles.iter().zip(&shaded.corners) {
+        let mut triangle = Vector3::zeros();
+        for (k, (&v, &n)) in [t.x, t.y, t.z].iter().zip(corners).enumerate() {
+            triangle[k] = *index.entry((v, n)).or_insert_with(|| {
+                split.vertices.push(mesh.vertices[v]);
+                normals.push(shaded.normals[n]);
+                split.vertices.len() - 1
+            });
+        }
+        split.triangles.push(triangle);
+    }
+    (split, normals)
+}
@@ -0,0 +1,60 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use fidget::{
+    context::{Context, Node, Op, Tree},
+    var::Var,
+    vm::VmShape,
+};
+
+use super::bounds::{detect_bounds, Bounds};
+use super::slice::{slice, Slice};
+
+/// Half the thickness of the slab a flat shape is cut to for finding its bounds
+const SLAB_HALF_THICKNESS: f64 = 1.0;
+
+/// Whether the expression at `root` reads Z at all
+///
+/// A shape that doesn't is the same at every height, an endless prism of
+/// a 2D sketch, which can't be meshed but outlines exactly.
+pub fn depends_on_z(ctx: &Context, root: Node) -> bool {
+    let mut seen = HashSet::new();
+    let mut todo = vec![root];
+    while let Some(node) = todo.pop() {
+        if !seen.insert(node) {
+            continue;
+        }
+        match ctx.get_op(node) {
+            Some(Op::Input(var)) if *var == Var::Z => return true,
+            Some(&Op::Binary(_, a, b)) => todo.extend([a, b]),
+            Some(&Op::Unary(_, a)) => todo.push(a),
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Extent of `root` around the XY plane, for a shape that may go on forever along Z
+///
+/// The shape is cut to a thin slab around the plane first, so the search
+/// stops; X and Y come out as the shape's own extent.  `None` if it's empty
+/// there or unbounded in X or Y.
+pub fn flat_bounds(ctx: &Context, root: Node) -> Result<Option<Bounds>> {
+    let tree = ctx.export(root)?;
+    let cut = tree.max(Tree::z().abs() - SLAB_HALF_THICKNESS);
+    let mut ctx = Context::new();
+    let cut = ctx.import(&cut);
+    detect_bounds(&VmShape::new(&ctx, cut)?)
+}
+
+/// Outlines of `root` where it crosses the XY plane, for a script in 2D mode
+///
+/// The grid covers `bounds` if given, or else the shape's `flat_bounds`.
+/// `resolution` is the number of cells along the longer side.  `None` if
+/// there's no extent to outline.
+pub fn outline(ctx: &Context, root: Node, bounds: Option<Bounds>, resolution: usize) -> Result<Option<Slice>> {
+    let Some(bounds) = bounds.map_or_else(|| flat_bounds(ctx, root), |b| Ok(Some(b)))? else {
+        return Ok(None);
+    };
+    slice(&VmShape::new(ctx, root)?, &bounds, 0.0, resolution).map(Some)
+}
@@ -0,0 +1,187 @@
+use std::collections::{BTreeMap, HashMap};
+
+use fidget::mesh::Mesh;
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+
+/// Width of each histogram bin, in degrees
+const BIN_DEGREES: f32 = 10.0;
+
+/// Overhang regions reported, largest first
+const MAX_REGIONS: usize = 100;
+
+/// Smallest region listed, as a share of the mesh's surface area
+///
+/// Dual contouring leaves thin slivers tilted past the limit along sharp
+/// edges, which would otherwise bury the real overhangs.
+const MIN_REGION_SHARE: f64 = 1e-3;
+
+/// Distance above the lowest point, as a fraction of the height, that
+/// counts as resting on the build plate
+const PLATE_TOLERANCE: f32 = 1e-4;
+
+/// Downward-facing surface within a range of overhang angles
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OverhangBin {
+    pub min_angle: f32,
+    pub max_angle: f32,
+    pub triangles: usize,
+    pub area: f64,
+}
+
+/// A connected patch of surface steeper than the allowed overhang
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OverhangRegion {
+    /// Area-weighted center of the patch
+    pub center: [f32; 3],
+    pub area: f64,
+    pub triangles: usize,
+    /// Largest overhang angle in the patch
+    pub worst_angle: f32,
+}
+
+/// Outcome of an overhang check
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct OverhangReport {
+    /// Unit build direction the angles were measured against
+    pub build_direction: [f32; 3],
+    pub max_angle: f32,
+    /// Downward-facing triangles by overhang angle, from vertical walls at
+    /// 0° to flat ceilings at 90°; triangles on the build plate are left out
+    pub histogram: Vec<OverhangBin>,
+    /// Total area steeper than `max_angle`
+    pub overhang_area: f64,
+    /// Connected patches steeper than `max_angle`, largest first, leaving
+    /// out slivers smaller than `MIN_REGION_SHARE` of the surface
+    pub regions: Vec<OverhangRegion>,
+    /// Regions beyond the first `MAX_REGIONS`, which aren't listed
+    pub omitted_regions: usize,
+}
+
+/// Measure how far each downward-facing triangle overhangs when printed along `build_direction`
+///
+/// A triangle's overhang angle is how far its surface leans past vertical
+/// over empty space, so 0° is a vertical wall and 90° a flat ceiling.
+/// Those beyond `max_angle` (45° is the usual rule of thumb for FDM) need
+/// support, and are grouped into regions that share edges.  The bottom
+/// face resting on the build plate isn't an overhang, so it's skipped.
+///
+/// `build_direction` points from the build plate upwards and must not be
+/// zero.
+pub fn overhang_report(mesh: &Mesh, build_direction: Vector3<f32>, max_angle: f32) -> OverhangReport {
+    let up = build_direction.normalize();
+    let angles = overhang_angles(mesh, up);
+
+    let bins = (90.0 / BIN_DEGREES).ceil() as usize;
+    let mut histogram: Vec<OverhangBin> = (0..bins)
+        .map(|i| OverhangBin {
+            min_angle: i as f32 * BIN_DEGREES,
+            max_angle: ((i + 1) as f32 * BIN_DEGREES).min(90.0),
+            triangles: 0,
+            area: 0.0,
+        })
+        .collect();
+
+    // Overhang angle and area of each triangle past the limit
+    let mut flagged: HashMap<usize, (f32, f64)> = HashMap::new();
+    let mut surface_area = 0.0;
+    for (i, t) in mesh.triangles.iter().enumerate() {
+        let [a, b, c] = [t.x, t.y, t.z].map(|v| mesh.vertices[v]);
+        let area = f64::from((b - a).cross(&(c - a)).norm()) / 2.0;
+        surface_area += area;
+        let Some(angle) = angles[i] else { continue };
+        let bin = &mut histogram[((angle / BIN_DEGREES) as usize).min(bins - 1)];
+        bin.triangles += 1;
+        bin.area += area;
+        if angle > max_angle {
+            flagged.insert(i, (angle, area));
+        }
+    }
+
+    // Group flagged triangles that share an edge
+    let mut parent: HashMap<usize, usize> = flagged.keys().map(|&t| (t, t)).collect();
+    fn find(parent: &mut HashMap<usize, usize>, mut t: usize) -> usize {
+        while parent[&t] != t {
+            let grandparent = parent[&parent[&t]];
+            parent.insert(t, grandparent);
+            t = grandparent;
+        }
+        t
+    }
+    let mut edge_owner: HashMap<(usize, usize), usize> = HashMap::new();
+    let mut order: Vec<usize> = flagged.keys().copied().collect();
+    order.sort_unstable();
+    for &i in &order {
+        let t = mesh.triangles[i];
+        for (a, b) in [(t.x, t.y), (t.y, t.z), (t.z, t.x)] {
+            if let Some(&other) = edge_owner.get(&(a.min(b), a.max(b))) {
+                let (x, y) = (find(&mut parent, i), find(&mut parent, other));
+                parent.insert(x, y);
+            } else {
+                edge_owner.insert((a.min(b), a.max(b)), i);
+            }
+        }
+    }
+
+    let mut groups: BTreeMap<usize, OverhangRegion> = BTreeMap::new();
+    let mut weighted_centers: BTreeMap<usize, Vector3<f64>> = BTreeMap::new();
+    for &i in &order {
+        let (angle, area) = flagged[&i];
+        let root = find(&mut parent, i);
+        let t = mesh.triangles[i];
+        let centroid = (mesh.vertices[t.x] + mesh.vertices[t.y] + mesh.vertices[t.z]).cast::<f64>() / 3.0;
+        *weighted_centers.entry(root).or_insert_with(Vector3::zeros) += centroid * area;
+        let region = groups.entry(root).or_insert(OverhangRegion {
+            center: [0.0; 3],
+            area: 0.0,
+            triangles: 0,
+            worst_angle: 0.0,
+        });
+        region.area += area;
+        region.triangles += 1;
+        region.worst_angle = region.worst_angle.max(angle);
+    }
+    let mut regions: Vec<OverhangRegion> = groups
+        .into_iter()
+        .map(|(root, mut region)| {
+            if region.area > 0.0 {
+                region.center = (weighted_centers[&root] / region.area).cast::<f32>().into();
+            }
+            region
+        })
+        .filter(|region| region.area >= surface_area * MIN_REGION_SHARE)
+        .collect();
+    regions.sort_by(|a, b| b.area.total_cmp(&a.area));
+    let omitted_regions = regions.len().saturating_sub(MAX_REGIONS);
+    regions.truncate(MAX_REGIONS);
+
+    OverhangReport {
+        build_direction: up.into(),
+        max_angle,
+        histogram,
+        overhang_area: order.iter().map(|i| flagged[i].1).sum(),
+        regions,
+        omitted_regions,
+    }
+}
+
+/// Overhang angle of each triangle of `mesh` that faces down along the unit vector `up`, in degrees
+///
+/// `None` for triangles facing up or exactly sideways, degenerate ones,
+/// and those resting on the build plate at the lowest point.
+pub fn overhang_angles(mesh: &Mesh, up: Vector3<f32>) -> Vec<Option<f32>> {
+    let heights: Vec<f32> = mesh.vertices.iter().map(|v| v.dot(&up)).collect();
+    let (low, high) = heights.iter().fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), &h| (lo.min(h), hi.max(h)));
+    let plate = low + (high - low) * PLATE_TOLERANCE;
+    mesh.triangles
+        .iter()
+        .map(|t| {
+            if [t.x, t.y, t.z].iter().all(|&v| heights[v] <= plate) {
+                return None;
+            }
+            let [a, b, c] = [t.x, t.y, t.z].map(|v| mesh.vertices[v]);
+            let facing = (b - a).cross(&(c - a)).try_normalize(f32::EPSILON)?.dot(&up);
+            (facing < 0.0).then(|| (-facing).min(1.0).asin().to_degrees())
+        })
+        .collect()
+}
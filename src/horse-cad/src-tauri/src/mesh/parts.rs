@@ -0,0 +1,38 @@
+use anyhow::Result;
+use fidget::{mesh::Mesh, shape::EzShape, vm::VmShape};
+
+/// Assign each triangle of a combined mesh to one of the shapes it was built from
+///
+/// `shapes` must be in the same coordinate space as the mesh vertices.  Each
+/// triangle goes to the shape whose surface is closest to its centroid, which
+/// matches how the union of the shapes picks its surface.
+pub fn triangle_owners(mesh: &Mesh, shapes: &[VmShape]) -> Result<Vec<usize>> {
+    if shapes.len() <= 1 {
+        return Ok(vec![0; mesh.triangles.len()]);
+    }
+
+    let tapes: Vec<_> = shapes.iter().map(|s| s.ez_point_tape()).collect();
+    let mut eval = VmShape::new_point_eval();
+    let mut owners = Vec::with_capacity(mesh.triangles.len());
+    for t in &mesh.triangles {
+        let c = (mesh.vertices[t.x] + mesh.vertices[t.y] + mesh.vertices[t.z]) / 3.0;
+        let mut best = (0, f32::INFINITY);
+        for (i, tape) in tapes.iter().enumerate() {
+            let (d, _trace) = eval.eval(tape, c.x, c.y, c.z)?;
+            if d.abs() < best.1 {
+                best = (i, d.abs());
+            }
+        }
+        owners.push(best.0);
+    }
+    Ok(owners)
+}
+
+/// Count the triangles owned by each of `count` shapes
+pub fn owner_counts(owners: &[usize], count: usize) -> Vec<usize> {
+    let mut counts = vec![0; count];
+    for &o in owners {
+        counts[o] += 1;
+    }
+    counts
+}
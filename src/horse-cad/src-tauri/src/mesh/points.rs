@@ -0,0 +1,139 @@
+use anyhow::Result;
+use fidget::{mesh::Mesh, shape::EzShape, types::Grad, vm::VmShape};
+use nalgebra::{Matrix4, Point3, Vector3};
+use serde::{Deserialize, Serialize};
+
+use super::normals::vertex_normals;
+use crate::state::CancelToken;
+
+/// Most points a cloud may hold
+pub const MAX_POINT_CLOUD_POINTS: usize = 10_000_000;
+
+/// Points evaluated together when snapping to the surface
+const PROJECT_BATCH: usize = 4096;
+
+/// Newton steps taken toward the surface from each sampled point
+const PROJECT_STEPS: usize = 3;
+
+/// Where a point cloud's points come from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PointSource {
+    /// Random points over the surface, snapped onto the distance field
+    #[default]
+    Surface,
+    /// The mesh's own vertices, with smoothed normals
+    Vertices,
+}
+
+/// Points with unit outward normals, in model space
+#[derive(Debug, Default, Clone)]
+pub struct PointCloud {
+    pub points: Vec<Vector3<f32>>,
+    pub normals: Vec<Vector3<f32>>,
+}
+
+/// Points a surface sampled every `spacing` apart would hold, about one per `spacing²` of area
+pub fn surface_point_count(mesh: &Mesh, to_model: &Matrix4<f32>, spacing: f32) -> f64 {
+    let area: f64 = mesh.triangles.iter().map(|t| triangle(mesh, to_model, t).3 as f64).sum();
+    area / (spacing as f64 * spacing as f64)
+}
+
+/// Corners of a triangle in model space, and its area
+type ModelTriangle = (Vector3<f32>, Vector3<f32>, Vector3<f32>, f32);
+
+/// Triangle `t` of `mesh`, moved into model space
+fn triangle(mesh: &Mesh, to_model: &Matrix4<f32>, t: &Vector3<usize>) -> ModelTriangle {
+    let [a, b, c] = [t.x, t.y, t.z].map(|i| to_model.transform_point(&Point3::from(mesh.vertices[i])).coords);
+    let area = (b - a).cross(&(c - a)).norm() / 2.0;
+    (a, b, c, area)
+}
+
+/// The vertices of `mesh` in model space, with area-weighted normals
+pub fn mesh_vertices(mesh: &Mesh, to_model: &Matrix4<f32>) -> PointCloud {
+    let points = mesh.vertices.iter().map(|v| to_model.transform_point(&Point3::from(*v)).coords).collect();
+    let normals = vertex_normals(mesh)
+        .into_iter()
+        .map(|n| to_model.transform_vector(&n).try_normalize(f32::EPSILON).unwrap_or_else(Vector3::z))
+        .collect();
+    PointCloud { points, normals }
+}
+
+/// Small deterministic generator, so the same mesh always gives the same cloud
+struct SplitMix(u64);
+
+impl SplitMix {
+    /// Uniform in `[0, 1)`
+    fn next(&mut self) -> f32 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        ((z ^ (z >> 31)) >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+/// Scatter points over the triangles of `mesh`, one per `spacing²` of area on average
+///
+/// Each triangle gets its share of points by area, with the fractions
+/// carried on so small triangles still add up, and its points are placed
+/// uniformly at random inside it.  Normals are the triangles' own, until
+/// `project_to_surface` refines them.  Returns `None` if `cancel` is
+/// triggered.
+pub fn sample_surface(mesh: &Mesh, to_model: &Matrix4<f32>, spacing: f32, cancel: &CancelToken) -> Option<PointCloud> {
+    let mut cloud = PointCloud::default();
+    let mut rng = SplitMix(0x5eed);
+    let mut owed = 0.0;
+    for (i, t) in mesh.triangles.iter().enumerate() {
+        if i % 65536 == 0 && cancel.is_cancelled() {
+            return None;
+        }
+        let (a, b, c, area) = triangle(mesh, to_model, t);
+        let Some(normal) = (b - a).cross(&(c - a)).try_normalize(f32::EPSILON) else {
+            continue;
+        };
+        owed += area / (spacing * spacing);
+        while owed >= 1.0 {
+            owed -= 1.0;
+            let (mut u, mut v) = (rng.next(), rng.next());
+            if u + v > 1.0 {
+                (u, v) = (1.0 - u, 1.0 - v);
+            }
+            cloud.points.push(a + (b - a) * u + (c - a) * v);
+            cloud.normals.push(normal);
+        }
+    }
+    Some(cloud)
+}
+
+/// Move each point onto the zero level of `shape` and take its normal from the field
+///
+/// The mesh only approximates the surface, so a few Newton steps along the
+/// gradient bring sampled points to where the field itself puts it.  Points
+/// where the field is flat stay put and keep their normals.  Returns `None`
+/// if `cancel` is triggered, which is checked between batches.
+pub fn project_to_surface(shape: &VmShape, cloud: &mut PointCloud, cancel: &CancelToken) -> Result<Option<()>> {
+    let tape = shape.ez_grad_slice_tape();
+    let mut eval = VmShape::new_grad_slice_eval();
+    for (points, normals) in cloud.points.chunks_mut(PROJECT_BATCH).zip(cloud.normals.chunks_mut(PROJECT_BATCH)) {
+        if cancel.is_cancelled() {
+            return Ok(None);
+        }
+        for _ in 0..PROJECT_STEPS {
+            let axis = |i: usize, d: [f32; 3]| -> Vec<Grad> {
+                points.iter().map(|p| Grad::new(p[i], d[0], d[1], d[2])).collect()
+            };
+            let (xs, ys, zs) = (axis(0, [1.0, 0.0, 0.0]), axis(1, [0.0, 1.0, 0.0]), axis(2, [0.0, 0.0, 1.0]));
+            let out = eval.eval(&tape, &xs, &ys, &zs)?;
+            for ((p, n), g) in points.iter_mut().zip(normals.iter_mut()).zip(out) {
+                let gradient = Vector3::new(g.dx, g.dy, g.dz);
+                let length = gradient.norm();
+                if length > f32::EPSILON && length.is_finite() && g.v.is_finite() {
+                    *p -= gradient * (g.v / (length * length));
+                    *n = gradient / length;
+                }
+            }
+        }
+    }
+    Ok(Some(()))
+}
@@ -0,0 +1,173 @@
+use anyhow::Result;
+use fidget::{shape::EzShape, types::Grad, vm::VmShape};
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+
+use super::bounds::Bounds;
+
+/// Most sphere-tracing steps before a ray is taken to have missed
+const MAX_STEPS: usize = 1024;
+
+/// Halvings used to pin down a surface a step jumped over
+const BISECTION_STEPS: usize = 24;
+
+/// How far a ray is traced when the shape has no known region
+const UNBOUNDED_DISTANCE: f32 = 1.0e5;
+
+/// The distance field at one point
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct Probe {
+    /// Signed distance to the surface, negative inside
+    pub distance: f32,
+    pub inside: bool,
+    /// Rate of change of the distance along each axis; its length is about
+    /// 1 in an exact distance field
+    pub gradient: [f32; 3],
+    /// Direction the distance grows fastest, which is the surface normal
+    /// near the surface; zero where the field is flat
+    pub normal: [f32; 3],
+}
+
+/// Evaluate `shape` and its gradient at `p`
+pub fn probe_point(shape: &VmShape, p: [f32; 3]) -> Result<Probe> {
+    let tape = shape.ez_grad_slice_tape();
+    let mut eval = VmShape::new_grad_slice_eval();
+    let [x, y, z] = [Grad::new(p[0], 1.0, 0.0, 0.0), Grad::new(p[1], 0.0, 1.0, 0.0), Grad::new(p[2], 0.0, 0.0, 1.0)];
+    let out = eval.eval(&tape, &[x], &[y], &[z])?;
+    let Grad { v, dx, dy, dz } = out[0];
+    let length = (dx * dx + dy * dy + dz * dz).sqrt();
+    let normal = if length > 0.0 && length.is_finite() { [dx / length, dy / length, dz / length] } else { [0.0; 3] };
+    Ok(Probe { distance: v, inside: v < 0.0, gradient: [dx, dy, dz], normal })
+}
+
+/// Draft angle of a surface with `normal` for a mold pulled along `pull`, in degrees
+///
+/// Positive where the surface faces along the pull and releases cleanly,
+/// zero on walls parallel to the pull, negative on undercuts.
+pub fn draft_angle(normal: [f32; 3], pull: Vector3<f32>) -> f32 {
+    let cos = Vector3::from(normal).dot(&pull.normalize()).clamp(-1.0, 1.0);
+    cos.asin().to_degrees()
+}
+
+/// Where a ray first meets the surface
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct RayHit {
+    pub point: [f32; 3],
+    /// Outward surface normal at the hit
+    pub normal: [f32; 3],
+    /// Distance along the ray from its origin
+    pub distance: f32,
+}
+
+/// Sphere-trace `shape` from `origin` along the unit vector `direction`
+///
+/// Each step moves by the distance to the surface, which can't pass
+/// through it in an exact field.  Fields that only bound the distance, as
+/// after deformers and smooth blends, can step past a thin wall, so a
+/// step that lands inside is bisected back to the surface.  The ray is
+/// clipped to `region` when the shape has one.  A ray starting inside the
+/// shape hits at its origin.
+pub fn trace_ray(
+    shape: &VmShape,
+    origin: Vector3<f32>,
+    direction: Vector3<f32>,
+    region: Option<Bounds>,
+) -> Result<Option<RayHit>> {
+    let (mut t, end) = match region {
+        Some(region) => match clip_ray(&region, origin, direction) {
+            Some(span) => span,
+            None => return Ok(None),
+        },
+        None => (0.0, UNBOUNDED_DISTANCE),
+    };
+    let tolerance = region.map_or(1.0e-4, |r| r.half_extent() * 1.0e-5).max(f32::EPSILON);
+
+    let tape = shape.ez_point_tape();
+    let mut eval = VmShape::new_point_eval();
+    let mut distance_at = |t: f32| -> Result<f32> {
+        let p = origin + direction * t;
+        Ok(eval.eval(&tape, p.x, p.y, p.z)?.0)
+    };
+    let mut last_outside = t;
+    for _ in 0..MAX_STEPS {
+        let d = distance_at(t)?;
+        if d < 0.0 && t > last_outside {
+            // Stepped through the surface; it lies between here and the last point outside
+            let (mut outside, mut inside) = (last_outside, t);
+            for _ in 0..BISECTION_STEPS {
+                let mid = (outside + inside) / 2.0;
+                if distance_at(mid)? < 0.0 {
+                    inside = mid;
+                } else {
+                    outside = mid;
+                }
+            }
+            t = inside;
+            break;
+        }
+        if d < tolerance {
+            break;
+        }
+        last_outside = t;
+        t += d;
+        if t > end {
+            return Ok(None);
+        }
+    }
+    if t > end {
+        return Ok(None);
+    }
+
+    let point = origin + direction * t;
+    let probe = probe_point(shape, [point.x, point.y, point.z])?;
+    if probe.distance > tolerance * 100.0 {
+        // Ran out of steps creeping along a grazing surface
+        return Ok(None);
+    }
+    Ok(Some(RayHit { point: [point.x, point.y, point.z], normal: probe.normal, distance: t }))
+}
+
+/// Span of distances along a ray that lies within `bounds`, if it crosses them at all
+fn clip_ray(bounds: &Bounds, origin: Vector3<f32>, direction: Vector3<f32>) -> Option<(f32, f32)> {
+    let (mut near, mut far) = (0.0f32, f32::INFINITY);
+    for i in 0..3 {
+        let (a, b) = ((bounds.min[i] - origin[i]) / direction[i], (bounds.max[i] - origin[i]) / direction[i]);
+        // A ray parallel to a slab gives NaN when it starts on its face; min/max skip it
+        near = near.max(a.min(b));
+        far = far.min(a.max(b));
+    }
+    (near <= far).then_some((near, far))
+}
+
+/// Dimensions between picked points
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct PointMeasurement {
+    /// Straight-line distance from the first point to the second
+    pub distance: f32,
+    /// Second point minus the first, along each axis
+    pub delta: [f32; 3],
+    /// Angle at the second point between the first and third, in degrees,
+    /// when three points are given
+    pub angle: Option<f32>,
+}
+
+/// Measure between two points, or the angle formed by three
+pub fn measure_points(points: &[Vector3<f32>]) -> Result<PointMeasurement, String> {
+    let (a, b, c) = match points {
+        [a, b] => (a, b, None),
+        [a, b, c] => (a, b, Some(c)),
+        _ => return Err(format!("Measuring takes two or three points, not {}", points.len())),
+    };
+    let delta = b - a;
+    let angle = match c {
+        Some(c) => {
+            let (u, v) = (a - b, c - b);
+            if u.norm() == 0.0 || v.norm() == 0.0 {
+                return Err("The angle's corner point must differ from the other two".to_string());
+            }
+            Some(u.angle(&v).to_degrees())
+        }
+        None => None,
+    };
+    Ok(PointMeasurement { distance: delta.norm(), delta: delta.into(), angle })
+}
@@ -0,0 +1,166 @@
+use serde::{Deserialize, Serialize};
+
+use super::bounds::Bounds;
+use super::features::FeatureOptions;
+
+/// Named meshing presets, so users don't have to guess raw octree depths
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QualityPreset {
+    Draft,
+    #[default]
+    Normal,
+    Fine,
+}
+
+impl QualityPreset {
+    /// Meshing parameters for this preset
+    pub fn settings(&self) -> MeshQuality {
+        match self {
+            // Small octrees finish faster on one thread than with pool overhead
+            QualityPreset::Draft => MeshQuality { depth: 4, threaded: false },
+            QualityPreset::Normal => MeshQuality { depth: 6, threaded: true },
+            QualityPreset::Fine => MeshQuality { depth: 8, threaded: true },
+        }
+    }
+}
+
+/// Resolved meshing parameters for a single compile
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MeshQuality {
+    /// Octree depth passed to fidget's mesher
+    pub depth: u8,
+    /// Whether octree construction uses the global thread pool
+    pub threaded: bool,
+}
+
+impl MeshQuality {
+    /// Pick the preset's settings if given, otherwise use `depth` directly
+    pub fn resolve(depth: u8, preset: Option<QualityPreset>) -> Self {
+        match preset {
+            Some(preset) => preset.settings(),
+            None => MeshQuality { depth, threaded: true },
+        }
+    }
+}
+
+/// Meshing knobs beyond depth and preset, for users who know what they're trading off
+///
+/// fidget subdivides cells adaptively down to the requested depth and has
+/// no minimum depth, so the region, its padding and threading are what
+/// shape the octree; the rest tune horseCAD's own clean-up.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AdvancedMeshOptions {
+    /// Region to mesh, overriding the script's bounds and bounds detection
+    pub bounds: Option<Bounds>,
+    /// Padding around the meshing region, as a share of its size
+    pub margin: f32,
+    /// Build the octree on the thread pool, overriding the depth's default
+    pub threaded: Option<bool>,
+    /// Merge the coincident vertices meshing leaves at sharp features
+    pub weld: bool,
+    /// Check the triangle and memory limits in the mesh options
+    pub check_limits: bool,
+    /// Rebuild sharp edges and corners from the distance field after meshing
+    pub features: FeatureOptions,
+    /// Coarsen the mesh wherever the surface is flat enough not to need the full depth
+    #[serde(alias = "adaptive")]
+    pub coarsen: CoarsenOptions,
+    /// Interval-evaluate a coarse grid first and build the octree only over the part the surface occupies
+    pub cull: bool,
+    /// Warn about walls thinner than this, in model units, or skip the check
+    pub min_wall: Option<f32>,
+}
+
+impl Default for AdvancedMeshOptions {
+    fn default() -> Self {
+        AdvancedMeshOptions {
+            bounds: None,
+            margin: 0.05,
+            threaded: None,
+            weld: true,
+            check_limits: true,
+            features: FeatureOptions::default(),
+            coarsen: CoarsenOptions::default(),
+            cull: false,
+            min_wall: None,
+        }
+    }
+}
+
+impl AdvancedMeshOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.margin.is_finite() && self.margin >= 0.0) {
+            return Err("Bounds margin can't be negative".to_string());
+        }
+        if let Some(bounds) = self.bounds {
+            // Rejects NaN too, which fails every comparison
+            let valid = (0..3).all(|i| bounds.min[i] < bounds.max[i]);
+            if !valid || !bounds.min.iter().chain(&bounds.max).all(|v| v.is_finite()) {
+                return Err("Meshing bounds must have a finite minimum below the maximum on every axis".to_string());
+            }
+        }
+        if !(self.coarsen.tolerance.is_finite() && self.coarsen.tolerance > 0.0) {
+            return Err("Coarsening tolerance must be greater than zero".to_string());
+        }
+        if self.min_wall.is_some_and(|w| !(w.is_finite() && w > 0.0)) {
+            return Err("Minimum wall thickness must be greater than zero".to_string());
+        }
+        self.features.validate()
+    }
+}
+
+/// Coarsening after meshing, which keeps the full depth's triangles only where the surface needs them
+///
+/// This isn't adaptive subdivision: fidget's octree has one depth for the
+/// whole region, so the octree is still built to the requested depth
+/// wherever the surface is, and meshing takes as long as without it.
+/// fidget already stops subdividing cells that interval arithmetic shows
+/// are clear of the surface, and merges leaf cells whose QEF fits exactly,
+/// but any curvature at all keeps a region at the full depth.  Afterwards
+/// edges are collapsed wherever the surface moves less than `tolerance` as
+/// a result, so flat and gently curved regions end up coarse and fine
+/// features keep every triangle they had.  Only the mesh is smaller.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CoarsenOptions {
+    pub enabled: bool,
+    /// Largest distance the surface may move, as a share of the finest octree cell
+    pub tolerance: f32,
+}
+
+impl Default for CoarsenOptions {
+    fn default() -> Self {
+        CoarsenOptions { enabled: false, tolerance: 0.25 }
+    }
+}
+
+/// Evaluator fidget uses to build the octree
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EvalBackend {
+    /// Bytecode interpreter, available everywhere
+    #[default]
+    Vm,
+    /// Native code compiled for the shape; much faster on x86-64 and AArch64
+    Jit,
+}
+
+impl EvalBackend {
+    /// Whether this build can use the backend
+    pub fn is_available(&self) -> bool {
+        match self {
+            EvalBackend::Vm => true,
+            EvalBackend::Jit => cfg!(any(target_arch = "x86_64", target_arch = "aarch64")),
+        }
+    }
+
+    /// Name shown in the logs
+    pub fn label(&self) -> &'static str {
+        match self {
+            EvalBackend::Vm => "VM",
+            EvalBackend::Jit => "JIT",
+        }
+    }
+}
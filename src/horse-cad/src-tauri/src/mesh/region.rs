@@ -0,0 +1,123 @@
+use anyhow::Result;
+use fidget::{shape::EzShape, types::Interval, vm::VmShape};
+use nalgebra::{Matrix4, Point3};
+use serde::{Deserialize, Serialize};
+
+use super::bounds::Bounds;
+
+/// Quadtree depth to which each face of the region is searched for solid
+const FACE_DEPTH: u32 = 6;
+
+/// The box the octree covers, in model space
+///
+/// That's the view's cube around `bounds`, padded by `margin`, or without
+/// bounds the unit cube mapped back into model space by `transform`.
+pub fn meshing_region(bounds: Option<Bounds>, transform: Option<Matrix4<f32>>, margin: f32) -> Bounds {
+    match bounds {
+        Some(bounds) => {
+            let center = bounds.center();
+            let half = bounds.half_extent() * (1.0 + margin);
+            Bounds { min: center.map(|c| c - half), max: center.map(|c| c + half) }
+        }
+        None => {
+            let t = transform.unwrap_or_else(Matrix4::identity);
+            let [a, b] = [-1.0, 1.0].map(|c| t.transform_point(&Point3::new(c, c, c)));
+            Bounds { min: std::array::from_fn(|i| a[i].min(b[i])), max: std::array::from_fn(|i| a[i].max(b[i])) }
+        }
+    }
+}
+
+/// One side of the meshing region
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RegionFace {
+    MinX,
+    MaxX,
+    MinY,
+    MaxY,
+    MinZ,
+    MaxZ,
+}
+
+impl RegionFace {
+    const ALL: [RegionFace; 6] =
+        [RegionFace::MinX, RegionFace::MaxX, RegionFace::MinY, RegionFace::MaxY, RegionFace::MinZ, RegionFace::MaxZ];
+
+    /// Axis the face is perpendicular to, and whether it's on the positive side
+    fn axis(&self) -> (usize, bool) {
+        match self {
+            RegionFace::MinX => (0, false),
+            RegionFace::MaxX => (0, true),
+            RegionFace::MinY => (1, false),
+            RegionFace::MaxY => (1, true),
+            RegionFace::MinZ => (2, false),
+            RegionFace::MaxZ => (2, true),
+        }
+    }
+
+    /// Name shown in the logs, such as `+Z`
+    pub fn label(&self) -> &'static str {
+        match self {
+            RegionFace::MinX => "-X",
+            RegionFace::MaxX => "+X",
+            RegionFace::MinY => "-Y",
+            RegionFace::MaxY => "+Y",
+            RegionFace::MinZ => "-Z",
+            RegionFace::MaxZ => "+Z",
+        }
+    }
+}
+
+/// Faces of `region` that the solid of `shape` reaches, where the mesh is cut off flat
+///
+/// Each face is split into tiles, and interval arithmetic skips the tiles
+/// the shape can't reach; a tile that is still undecided at the finest
+/// level counts if the field is negative at its center.
+pub fn clipped_faces(shape: &VmShape, region: &Bounds) -> Result<Vec<RegionFace>> {
+    let tape = shape.ez_interval_tape();
+    let mut eval = VmShape::new_interval_eval();
+    let point_tape = shape.ez_point_tape();
+    let mut point_eval = VmShape::new_point_eval();
+
+    let mut clipped = Vec::new();
+    for face in RegionFace::ALL {
+        let (axis, upper) = face.axis();
+        let level = if upper { region.max[axis] } else { region.min[axis] };
+        let mut tile = *region;
+        tile.min[axis] = level;
+        tile.max[axis] = level;
+        let mut stack = vec![(tile, 0)];
+        while let Some((tile, depth)) = stack.pop() {
+            let interval = |i: usize| Interval::new(tile.min[i], tile.max[i]);
+            let (value, _) = eval.eval(&tape, interval(0), interval(1), interval(2))?;
+            if value.lower() > 0.0 {
+                continue;
+            }
+            let solid = if value.upper() < 0.0 {
+                true
+            } else if depth == FACE_DEPTH {
+                let [x, y, z] = tile.center();
+                point_eval.eval(&point_tape, x, y, z)?.0 < 0.0
+            } else {
+                let center = tile.center();
+                for quadrant in 0..4 {
+                    let mut child = tile;
+                    for (bit, i) in (0..3).filter(|&i| i != axis).enumerate() {
+                        if quadrant & (1 << bit) != 0 {
+                            child.min[i] = center[i];
+                        } else {
+                            child.max[i] = center[i];
+                        }
+                    }
+                    stack.push((child, depth + 1));
+                }
+                false
+            };
+            if solid {
+                clipped.push(face);
+                break;
+            }
+        }
+    }
+    Ok(clipped)
+}
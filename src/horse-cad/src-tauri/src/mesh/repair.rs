@@ -0,0 +1,216 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use fidget::mesh::Mesh;
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+
+/// Largest hole, in edges around its rim, that `repair_mesh` will fill
+const MAX_HOLE_EDGES: usize = 32;
+
+/// What `repair_mesh` changed
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct RepairReport {
+    pub degenerate_removed: usize,
+    pub duplicates_removed: usize,
+    pub triangles_flipped: usize,
+    pub holes_filled: usize,
+}
+
+impl RepairReport {
+    /// One-line description for logs
+    pub fn summary(&self) -> String {
+        format!(
+            "Repaired mesh: removed {} degenerate and {} duplicate triangles, flipped {}, filled {} holes",
+            self.degenerate_removed, self.duplicates_removed, self.triangles_flipped, self.holes_filled
+        )
+    }
+}
+
+/// A repaired mesh and where its triangles came from
+pub struct Repaired {
+    pub mesh: Mesh,
+    /// Input triangle for each output triangle; hole patches point at a
+    /// triangle on the hole's rim, so per-triangle colors carry over
+    pub sources: Vec<usize>,
+    pub report: RepairReport,
+}
+
+/// Fix the small defects dual contouring leaves at sharp features
+///
+/// Zero-area and repeated triangles are removed first.  Orientation is then
+/// made consistent across each connected piece, siding with the majority of
+/// its triangles, and the mesh is turned outside in if it encloses negative
+/// volume.
+/// Finally, holes of up to `MAX_HOLE_EDGES` edges are closed with a fan
+/// around their centroid; larger openings are left for the user to fix.
+pub fn repair_mesh(mesh: &Mesh) -> Repaired {
+    let mut report = RepairReport::default();
+    let mut vertices = mesh.vertices.clone();
+    let mut triangles: Vec<[usize; 3]> = Vec::with_capacity(mesh.triangles.len());
+    let mut sources = Vec::with_capacity(mesh.triangles.len());
+
+    let mut seen = HashSet::new();
+    for (i, t) in mesh.triangles.iter().enumerate() {
+        let t = [t.x, t.y, t.z];
+        if is_degenerate(&vertices, t) {
+            report.degenerate_removed += 1;
+            continue;
+        }
+        let mut corners = t;
+        corners.sort_unstable();
+        if !seen.insert(corners) {
+            report.duplicates_removed += 1;
+            continue;
+        }
+        triangles.push(t);
+        sources.push(i);
+    }
+
+    report.triangles_flipped = orient(&vertices, &mut triangles);
+    report.holes_filled = fill_holes(&mut vertices, &mut triangles, &mut sources);
+
+    let mesh = Mesh {
+        vertices,
+        triangles: triangles.into_iter().map(Vector3::from).collect(),
+    };
+    Repaired { mesh, sources, report }
+}
+
+fn is_degenerate(vertices: &[Vector3<f32>], [a, b, c]: [usize; 3]) -> bool {
+    if a == b || b == c || c == a {
+        return true;
+    }
+    let (a, b, c) = (vertices[a], vertices[b], vertices[c]);
+    let longest = (b - a).norm().max((c - b).norm()).max((a - c).norm());
+    (b - a).cross(&(c - a)).norm() <= longest * longest * 1e-6
+}
+
+/// The three directed edges of a triangle
+fn directed_edges([a, b, c]: [usize; 3]) -> [(usize, usize); 3] {
+    [(a, b), (b, c), (c, a)]
+}
+
+/// Make neighbouring triangles wind the same way and pieces face outwards
+///
+/// Returns the number of triangles flipped.  Orientation only spreads across
+/// edges shared by exactly two triangles.
+fn orient(vertices: &[Vector3<f32>], triangles: &mut [[usize; 3]]) -> usize {
+    let mut edge_triangles: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+    for (i, &t) in triangles.iter().enumerate() {
+        for (a, b) in directed_edges(t) {
+            edge_triangles.entry((a.min(b), a.max(b))).or_default().push(i);
+        }
+    }
+
+    let mut flipped = vec![false; triangles.len()];
+    let mut visited = vec![false; triangles.len()];
+    for start in 0..triangles.len() {
+        if visited[start] {
+            continue;
+        }
+        visited[start] = true;
+        let mut piece = vec![start];
+        let mut queue = VecDeque::from([start]);
+        while let Some(i) = queue.pop_front() {
+            for (a, b) in directed_edges(triangles[i]) {
+                let Some(&[t0, t1]) = edge_triangles.get(&(a.min(b), a.max(b))).map(Vec::as_slice) else {
+                    continue;
+                };
+                let neighbor = if t0 == i { t1 } else { t0 };
+                if visited[neighbor] {
+                    continue;
+                }
+                // A consistent neighbour runs along the shared edge the other way
+                if directed_edges(triangles[neighbor]).contains(&(a, b)) {
+                    triangles[neighbor].swap(1, 2);
+                    flipped[neighbor] = !flipped[neighbor];
+                }
+                visited[neighbor] = true;
+                piece.push(neighbor);
+                queue.push_back(neighbor);
+            }
+        }
+
+        // The starting triangle may have been the odd one out; most weren't
+        if piece.iter().filter(|&&i| flipped[i]).count() * 2 > piece.len() {
+            for &i in &piece {
+                triangles[i].swap(1, 2);
+                flipped[i] = !flipped[i];
+            }
+        }
+    }
+
+    // Pieces may be cavities facing inwards, so only the whole mesh is turned outside in
+    let volume: f64 = triangles
+        .iter()
+        .map(|t| {
+            let [a, b, c] = t.map(|v| vertices[v]);
+            f64::from(a.dot(&b.cross(&c)))
+        })
+        .sum();
+    if volume < 0.0 {
+        for (t, f) in triangles.iter_mut().zip(&mut flipped) {
+            t.swap(1, 2);
+            *f = !*f;
+        }
+    }
+    flipped.iter().filter(|&&f| f).count()
+}
+
+/// Close small holes with triangle fans, returning how many were filled
+fn fill_holes(vertices: &mut Vec<Vector3<f32>>, triangles: &mut Vec<[usize; 3]>, sources: &mut Vec<usize>) -> usize {
+    let mut uses: HashMap<(usize, usize), usize> = HashMap::new();
+    for &t in triangles.iter() {
+        for (a, b) in directed_edges(t) {
+            *uses.entry((a.min(b), a.max(b))).or_default() += 1;
+        }
+    }
+
+    // Open edges reversed, so a walk along them winds patches to match
+    let mut open: HashMap<usize, Vec<(usize, usize)>> = HashMap::new();
+    for (i, &t) in triangles.iter().enumerate() {
+        for (a, b) in directed_edges(t) {
+            if uses[&(a.min(b), a.max(b))] == 1 {
+                open.entry(b).or_default().push((a, sources[i]));
+            }
+        }
+    }
+
+    let mut filled = 0;
+    let mut starts: Vec<usize> = open.keys().copied().collect();
+    starts.sort_unstable();
+    for start in starts {
+        // Rim vertices so far, each with the triangle its outgoing edge came from
+        let mut path: Vec<(usize, usize)> = Vec::new();
+        let mut v = start;
+        while let Some((to, source)) = open.get_mut(&v).and_then(Vec::pop) {
+            path.push((v, source));
+            // Back at a visited vertex: cut out the loop, which handles rims
+            // that touch at a vertex
+            if let Some(at) = path.iter().position(|&(r, _)| r == to) {
+                let rim = path.split_off(at);
+                if (3..=MAX_HOLE_EDGES).contains(&rim.len()) {
+                    fill_rim(vertices, triangles, sources, &rim);
+                    filled += 1;
+                }
+            }
+            v = to;
+        }
+    }
+    filled
+}
+
+/// Close one hole, given its rim in walking order
+fn fill_rim(vertices: &mut Vec<Vector3<f32>>, triangles: &mut Vec<[usize; 3]>, sources: &mut Vec<usize>, rim: &[(usize, usize)]) {
+    if let [(a, source), (b, _), (c, _)] = *rim {
+        triangles.push([a, b, c]);
+        sources.push(source);
+        return;
+    }
+    let center = vertices.len();
+    vertices.push(rim.iter().map(|&(r, _)| vertices[r]).sum::<Vector3<f32>>() / rim.len() as f32);
+    for (k, &(r, source)) in rim.iter().enumerate() {
+        triangles.push([r, rim[(k + 1) % rim.len()].0, center]);
+        sources.push(source);
+    }
+}
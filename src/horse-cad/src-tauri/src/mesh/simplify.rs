@@ -0,0 +1,311 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use fidget::mesh::Mesh;
+use nalgebra::{Matrix4, Vector3, Vector4};
+use serde::{Deserialize, Serialize};
+
+/// Limits for `simplify`; with neither set the mesh is left alone
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SimplifyOptions {
+    /// Stop collapsing once the mesh is down to this many triangles
+    pub target_triangles: Option<usize>,
+    /// Largest distance, in model units, the surface may move from the original
+    pub max_error: Option<f32>,
+}
+
+impl SimplifyOptions {
+    pub fn is_enabled(&self) -> bool {
+        self.target_triangles.is_some() || self.max_error.is_some()
+    }
+}
+
+/// A simplified mesh and where its triangles came from
+pub struct Simplified {
+    pub mesh: Mesh,
+    /// Input triangle that each output triangle is a remnant of, so
+    /// per-triangle colors can be carried over
+    pub sources: Vec<usize>,
+}
+
+/// Candidate edge collapse, ordered so the cheapest pops first
+struct Collapse {
+    cost: f64,
+    keep: usize,
+    remove: usize,
+    /// Vertex versions when this was computed; stale entries are skipped
+    versions: (u32, u32),
+    target: Vector3<f64>,
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .cost
+            .total_cmp(&self.cost)
+            .then_with(|| (other.keep, other.remove).cmp(&(self.keep, self.remove)))
+    }
+}
+
+/// Working state of an edge-collapse pass
+struct Simplifier {
+    positions: Vec<Vector3<f64>>,
+    quadrics: Vec<Matrix4<f64>>,
+    versions: Vec<u32>,
+    removed: Vec<bool>,
+    /// Vertices on open or non-manifold edges, which never move
+    pinned: Vec<bool>,
+    triangles: Vec<[usize; 3]>,
+    alive: Vec<bool>,
+    /// Triangles using each vertex; may still list dead triangles
+    vertex_triangles: Vec<Vec<usize>>,
+}
+
+/// Reduce the triangle count of a closed mesh by quadric edge collapse
+///
+/// Edges are collapsed cheapest first, where the cost is the squared
+/// distance from the merged vertex to the planes of the triangles it
+/// replaces (Garland and Heckbert's quadric error).  Collapsing stops when
+/// the mesh is down to `target_triangles` or the next collapse would move
+/// the surface by more than `max_error`, whichever comes first.
+///
+/// Collapses that would fold a triangle over or pinch the surface into a
+/// non-manifold edge are skipped, and vertices on open edges stay put, so
+/// watertight meshes stay watertight.
+pub fn simplify(mesh: &Mesh, options: &SimplifyOptions) -> Simplified {
+    let target = options.target_triangles.unwrap_or(0);
+    let max_cost = options.max_error.map_or(f64::INFINITY, |e| f64::from(e).powi(2));
+
+    let mut state = Simplifier::new(mesh);
+    let mut live = mesh.triangles.len();
+    let mut heap = BinaryHeap::new();
+    for (a, b) in state.edges() {
+        if let Some(collapse) = state.collapse(a, b) {
+            heap.push(collapse);
+        }
+    }
+
+    while live > target {
+        let Some(collapse) = heap.pop() else { break };
+        if collapse.cost > max_cost {
+            break;
+        }
+        let (keep, remove) = (collapse.keep, collapse.remove);
+        if state.removed[keep]
+            || state.removed[remove]
+            || (state.versions[keep], state.versions[remove]) != collapse.versions
+            || !state.is_valid(keep, remove, &collapse.target)
+        {
+            continue;
+        }
+
+        live -= state.apply(keep, remove, collapse.target);
+        for neighbor in state.neighbors(keep) {
+            if let Some(collapse) = state.collapse(keep, neighbor) {
+                heap.push(collapse);
+            }
+        }
+    }
+    state.finish()
+}
+
+/// Plane quadric of a triangle, or `None` if it's degenerate
+fn plane_quadric(a: Vector3<f64>, b: Vector3<f64>, c: Vector3<f64>) -> Option<Matrix4<f64>> {
+    let normal = (b - a).cross(&(c - a)).try_normalize(1e-12)?;
+    let plane = Vector4::new(normal.x, normal.y, normal.z, -normal.dot(&a));
+    Some(plane * plane.transpose())
+}
+
+/// Quadric error of placing a vertex at `p`
+fn quadric_error(q: &Matrix4<f64>, p: &Vector3<f64>) -> f64 {
+    let h = p.push(1.0);
+    h.dot(&(q * h)).max(0.0)
+}
+
+impl Simplifier {
+    fn new(mesh: &Mesh) -> Self {
+        let count = mesh.vertices.len();
+        let positions: Vec<Vector3<f64>> = mesh.vertices.iter().map(|v| v.cast()).collect();
+        let triangles: Vec<[usize; 3]> = mesh.triangles.iter().map(|t| [t.x, t.y, t.z]).collect();
+
+        let mut quadrics = vec![Matrix4::zeros(); count];
+        let mut vertex_triangles = vec![Vec::new(); count];
+        for (i, t) in triangles.iter().enumerate() {
+            let quadric = plane_quadric(positions[t[0]], positions[t[1]], positions[t[2]]);
+            for &v in t {
+                vertex_triangles[v].push(i);
+                if let Some(q) = quadric {
+                    quadrics[v] += q;
+                }
+            }
+        }
+
+        let mut state = Simplifier {
+            positions,
+            quadrics,
+            versions: vec![0; count],
+            removed: vec![false; count],
+            pinned: vec![false; count],
+            alive: vec![true; triangles.len()],
+            triangles,
+            vertex_triangles,
+        };
+
+        // Edges used by anything other than exactly two triangles are open or non-manifold
+        let mut edges = state.edge_uses();
+        edges.sort_unstable();
+        for run in edges.chunk_by(|a, b| a == b) {
+            if run.len() != 2 {
+                let (a, b) = run[0];
+                state.pinned[a] = true;
+                state.pinned[b] = true;
+            }
+        }
+        state
+    }
+
+    /// Every use of an edge by a triangle, as `(smaller, larger)` vertex pairs
+    fn edge_uses(&self) -> Vec<(usize, usize)> {
+        self.triangles
+            .iter()
+            .flat_map(|t| [(t[0], t[1]), (t[1], t[2]), (t[2], t[0])])
+            .map(|(a, b)| (a.min(b), a.max(b)))
+            .collect()
+    }
+
+    /// Each distinct edge once
+    fn edges(&self) -> Vec<(usize, usize)> {
+        let mut edges = self.edge_uses();
+        edges.sort_unstable();
+        edges.dedup();
+        edges
+    }
+
+    /// Live triangles using vertex `v`
+    fn triangles_of(&self, v: usize) -> impl Iterator<Item = usize> + '_ {
+        self.vertex_triangles[v].iter().copied().filter(|&t| self.alive[t])
+    }
+
+    /// Vertices sharing an edge with `v`, sorted and without repeats
+    fn neighbors(&self, v: usize) -> Vec<usize> {
+        let mut neighbors: Vec<usize> =
+            self.triangles_of(v).flat_map(|t| self.triangles[t]).filter(|&n| n != v).collect();
+        neighbors.sort_unstable();
+        neighbors.dedup();
+        neighbors
+    }
+
+    /// Cheapest way to merge `a` and `b`, or `None` if either is pinned
+    fn collapse(&self, a: usize, b: usize) -> Option<Collapse> {
+        if self.pinned[a] || self.pinned[b] {
+            return None;
+        }
+        let q = self.quadrics[a] + self.quadrics[b];
+        let (pa, pb) = (self.positions[a], self.positions[b]);
+        let midpoint = (pa + pb) / 2.0;
+
+        // The quadric's minimum, unless it's ill-defined or far off the edge
+        let optimum = q
+            .fixed_view::<3, 3>(0, 0)
+            .into_owned()
+            .try_inverse()
+            .map(|inverse| -(inverse * q.fixed_view::<3, 1>(0, 3)))
+            .filter(|p| (p - midpoint).norm() <= (pb - pa).norm());
+
+        let (cost, target) = optimum
+            .into_iter()
+            .chain([midpoint, pa, pb])
+            .map(|p| (quadric_error(&q, &p), p))
+            .min_by(|x, y| x.0.total_cmp(&y.0))?;
+        Some(Collapse { cost, keep: a, remove: b, versions: (self.versions[a], self.versions[b]), target })
+    }
+
+    /// Whether merging `remove` into `keep` at `target` keeps the surface a clean manifold
+    fn is_valid(&self, keep: usize, remove: usize, target: &Vector3<f64>) -> bool {
+        // Link condition: the only shared neighbors are across the collapsing edge
+        let shared_triangles = self.triangles_of(keep).filter(|&t| self.triangles[t].contains(&remove)).count();
+        let keep_neighbors = self.neighbors(keep);
+        let shared_neighbors = self.neighbors(remove).iter().filter(|n| keep_neighbors.binary_search(n).is_ok()).count();
+        if shared_neighbors != shared_triangles {
+            return false;
+        }
+
+        // No surviving triangle may flip over or collapse to a sliver
+        [keep, remove].iter().all(|&moved| {
+            self.triangles_of(moved)
+                .filter(|&t| !(self.triangles[t].contains(&keep) && self.triangles[t].contains(&remove)))
+                .all(|t| {
+                    let corners = self.triangles[t].map(|v| self.positions[v]);
+                    let moved_corners = self.triangles[t].map(|v| if v == moved { *target } else { self.positions[v] });
+                    let before = (corners[1] - corners[0]).cross(&(corners[2] - corners[0]));
+                    let after = (moved_corners[1] - moved_corners[0]).cross(&(moved_corners[2] - moved_corners[0]));
+                    after.dot(&before) > 0.0 && after.norm() > before.norm() * 1e-3
+                })
+        })
+    }
+
+    /// Merge `remove` into `keep`, returning the number of triangles removed
+    fn apply(&mut self, keep: usize, remove: usize, target: Vector3<f64>) -> usize {
+        self.positions[keep] = target;
+        self.quadrics[keep] = self.quadrics[keep] + self.quadrics[remove];
+        self.removed[remove] = true;
+        self.versions[keep] += 1;
+
+        let mut dropped = 0;
+        for t in std::mem::take(&mut self.vertex_triangles[remove]) {
+            if !self.alive[t] {
+                continue;
+            }
+            if self.triangles[t].contains(&keep) {
+                self.alive[t] = false;
+                dropped += 1;
+            } else {
+                for v in &mut self.triangles[t] {
+                    if *v == remove {
+                        *v = keep;
+                    }
+                }
+                self.vertex_triangles[keep].push(t);
+            }
+        }
+        let alive = &self.alive;
+        self.vertex_triangles[keep].retain(|&t| alive[t]);
+        dropped
+    }
+
+    /// Compact the surviving vertices and triangles into a new mesh
+    fn finish(self) -> Simplified {
+        let mut remap = vec![usize::MAX; self.positions.len()];
+        let mut mesh = Mesh::new();
+        let mut sources = Vec::new();
+        for (i, t) in self.triangles.iter().enumerate() {
+            if !self.alive[i] {
+                continue;
+            }
+            let corners = t.map(|v| {
+                if remap[v] == usize::MAX {
+                    remap[v] = mesh.vertices.len();
+                    mesh.vertices.push(self.positions[v].cast());
+                }
+                remap[v]
+            });
+            mesh.triangles.push(Vector3::from(corners));
+            sources.push(i);
+        }
+        Simplified { mesh, sources }
+    }
+}
@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use fidget::{shape::EzShape, vm::VmShape};
+use serde::{Deserialize, Serialize};
+
+use super::bounds::Bounds;
+
+/// Outlines where a horizontal plane cuts through a shape
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Slice {
+    pub z: f32,
+    /// Corner of the sampled region with the smallest X and Y
+    pub min: [f32; 2],
+    /// Corner of the sampled region with the largest X and Y
+    pub max: [f32; 2],
+    /// Closed outlines, each joining back from its last point to its first;
+    /// solid is always on the left, so outer edges wind counter-clockwise and
+    /// holes clockwise
+    pub contours: Vec<Vec<[f32; 2]>>,
+}
+
+/// Grid edge a contour crosses: the grid point it starts from, and whether it runs along Y
+type EdgeKey = (usize, usize, bool);
+
+/// Cut `shape` with the plane at height `z`, within the XY extent of `bounds`
+///
+/// The distance field is sampled on a square grid with `resolution` cells
+/// along the longer side of the bounds, and outlines are traced through it
+/// with marching squares.  Ambiguous cells are resolved by the value at
+/// their center.  The grid runs one cell past the bounds on every side, so
+/// outlines always close.
+pub fn slice(shape: &VmShape, bounds: &Bounds, z: f32, resolution: usize) -> Result<Slice> {
+    let size = (bounds.max[0] - bounds.min[0]).max(bounds.max[1] - bounds.min[1]);
+    let step = size / resolution.max(1) as f32;
+    let min = [bounds.min[0] - step, bounds.min[1] - step];
+    let mut slice = Slice { z, min, max: min, contours: Vec::new() };
+    if step <= 0.0 || !step.is_finite() {
+        return Ok(slice);
+    }
+
+    let cells = |axis: usize| ((bounds.max[axis] - bounds.min[axis]) / step).ceil() as usize + 2;
+    let (nx, ny) = (cells(0), cells(1));
+    slice.max = [min[0] + nx as f32 * step, min[1] + ny as f32 * step];
+    let position = |i: usize, j: usize| [min[0] + i as f32 * step, min[1] + j as f32 * step];
+
+    let count = (nx + 1) * (ny + 1);
+    let (mut xs, mut ys) = (Vec::with_capacity(count), Vec::with_capacity(count));
+    for j in 0..=ny {
+        for i in 0..=nx {
+            let [x, y] = position(i, j);
+            xs.push(x);
+            ys.push(y);
+        }
+    }
+    let tape = shape.ez_float_slice_tape();
+    let mut eval = VmShape::new_float_slice_eval();
+    let mut values = eval.eval(&tape, &xs, &ys, &vec![z; count])?.to_vec();
+    for j in 0..=ny {
+        for i in 0..=nx {
+            // Anything on the border or undefined counts as outside
+            let v = &mut values[j * (nx + 1) + i];
+            if i == 0 || j == 0 || i == nx || j == ny || v.is_nan() {
+                *v = v.max(step);
+            }
+        }
+    }
+    let value = |i: usize, j: usize| values[j * (nx + 1) + i];
+    let crossing = |(i, j, along_y): EdgeKey| {
+        let (a, b) = if along_y { ((i, j), (i, j + 1)) } else { ((i, j), (i + 1, j)) };
+        let (va, vb) = (value(a.0, a.1), value(b.0, b.1));
+        let t = va / (va - vb);
+        let (pa, pb) = (position(a.0, a.1), position(b.0, b.1));
+        [pa[0] + (pb[0] - pa[0]) * t, pa[1] + (pb[1] - pa[1]) * t]
+    };
+
+    // Each segment runs from the edge where a contour leaves a cell's solid
+    // corners, walking counter-clockwise, to the edge where it comes back in
+    let mut next: HashMap<EdgeKey, EdgeKey> = HashMap::new();
+    let mut starts = Vec::new();
+    for j in 0..ny {
+        for i in 0..nx {
+            let corners = [(i, j), (i + 1, j), (i + 1, j + 1), (i, j + 1)];
+            let edges = [(i, j, false), (i + 1, j, true), (i, j + 1, false), (i, j, true)];
+            let inside = corners.map(|(ci, cj)| value(ci, cj) < 0.0);
+            let crossed: Vec<usize> = (0..4).filter(|&k| inside[k] != inside[(k + 1) % 4]).collect();
+            if crossed.is_empty() {
+                continue;
+            }
+            let center = corners.iter().map(|&(ci, cj)| value(ci, cj)).sum::<f32>() / 4.0;
+            let joined = crossed.len() == 4 && center < 0.0;
+            for (p, &k) in crossed.iter().enumerate() {
+                if !inside[k] {
+                    continue;
+                }
+                let partner = if joined {
+                    crossed[(p + 1) % crossed.len()]
+                } else {
+                    crossed[(p + crossed.len() - 1) % crossed.len()]
+                };
+                next.insert(edges[k], edges[partner]);
+                starts.push(edges[k]);
+            }
+        }
+    }
+
+    for start in starts {
+        let mut contour = Vec::new();
+        let mut key = start;
+        while let Some(to) = next.remove(&key) {
+            contour.push(crossing(key));
+            key = to;
+        }
+        let contour = drop_collinear(contour, step * step * 1e-4);
+        if contour.len() >= 3 {
+            slice.contours.push(contour);
+        }
+    }
+    Ok(slice)
+}
+
+/// Remove points lying on the line through their neighbours, such as along flat faces
+///
+/// `tolerance` is the largest area of the triangle a removed point makes
+/// with its neighbours, doubled.
+fn drop_collinear(points: Vec<[f32; 2]>, tolerance: f32) -> Vec<[f32; 2]> {
+    let mut kept: Vec<[f32; 2]> = Vec::with_capacity(points.len());
+    for p in points {
+        while let [.., a, b] = kept[..] {
+            if turn(a, b, p).abs() > tolerance {
+                break;
+            }
+            kept.pop();
+        }
+        kept.push(p);
+    }
+    // The walk started at an arbitrary point, which may sit on a straight run too
+    while kept.len() > 3 {
+        let [a, b, p] = [kept[kept.len() - 1], kept[0], kept[1]];
+        if turn(a, b, p).abs() > tolerance {
+            break;
+        }
+        kept.remove(0);
+    }
+    kept
+}
+
+/// Twice the signed area of the triangle `a`, `b`, `p`
+fn turn(a: [f32; 2], b: [f32; 2], p: [f32; 2]) -> f32 {
+    (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0])
+}
@@ -0,0 +1,226 @@
+use std::collections::HashMap;
+
+use fidget::mesh::Mesh;
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+
+use super::overhang::overhang_angles;
+
+/// Sides of each pillar's cross-section
+const PILLAR_SIDES: usize = 8;
+
+/// Most grid columns a support pass may test, so a tiny spacing can't run for minutes
+const MAX_COLUMNS: usize = 250_000;
+
+/// Pillars per side of the part's footprint when no spacing is given
+const DEFAULT_PILLARS_PER_SIDE: f32 = 25.0;
+
+/// How `generate_supports` places and sizes its pillars
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SupportOptions {
+    /// Overhang angle past which a surface is supported, in degrees from vertical
+    pub max_angle: f32,
+    /// Distance between pillars; by default a 25th of the footprint's longer side
+    pub spacing: Option<f32>,
+    /// Radius of each pillar's shaft; by default an eighth of the spacing
+    pub radius: Option<f32>,
+    /// Radius where a pillar touches the part; by default 40% of the shaft's
+    pub tip_radius: Option<f32>,
+}
+
+impl Default for SupportOptions {
+    fn default() -> Self {
+        SupportOptions { max_angle: 45.0, spacing: None, radius: None, tip_radius: None }
+    }
+}
+
+/// What a support pass built
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct SupportReport {
+    pub pillars: usize,
+    /// Pillars standing on a lower part of the model rather than the build plate
+    pub on_part: usize,
+    pub triangle_count: usize,
+    /// Spacing used, after defaults
+    pub spacing: f32,
+    pub radius: f32,
+    pub tip_radius: f32,
+}
+
+/// Support pillars for a part, as a mesh of their own
+pub struct Supports {
+    pub mesh: Mesh,
+    pub report: SupportReport,
+}
+
+/// A column crossing the part's surface
+#[derive(Debug, Clone, Copy)]
+struct Hit {
+    height: f32,
+    /// The surface faces down here, so the column leaves the part going down
+    down: bool,
+    /// The surface overhangs past the limit and needs a pillar
+    overhang: bool,
+}
+
+/// Build pillars under the overhangs of `mesh` when printed along `build_direction`
+///
+/// Columns on a square grid `spacing` apart, across the build plate, are
+/// traced through the mesh.  Wherever one meets surface steeper than
+/// `max_angle` from below, a pillar stands from the nearest surface under
+/// it, or the build plate, up to a tapered tip that reaches slightly into
+/// the part.  Plate pillars get a wide foot; those on the part start from
+/// a tip too.  Gaps too short to fit a pillar are left to bridge.  Each
+/// pillar is a closed prism, so the mesh can be exported and sliced
+/// alongside the part.
+///
+/// `build_direction` must not be zero.
+pub fn generate_supports(
+    mesh: &Mesh,
+    build_direction: Vector3<f32>,
+    options: &SupportOptions,
+) -> Result<Supports, String> {
+    let up = build_direction.normalize();
+    let across = if up.x.abs() < 0.9 { Vector3::x() } else { Vector3::y() };
+    let u = (across - up * across.dot(&up)).normalize();
+    let v = up.cross(&u);
+    let local: Vec<[f32; 3]> = mesh.vertices.iter().map(|p| [p.dot(&u), p.dot(&v), p.dot(&up)]).collect();
+    if local.is_empty() {
+        return Err("The mesh is empty".to_string());
+    }
+    let mut low = [f32::INFINITY; 3];
+    let mut high = [f32::NEG_INFINITY; 3];
+    for p in &local {
+        for a in 0..3 {
+            low[a] = low[a].min(p[a]);
+            high[a] = high[a].max(p[a]);
+        }
+    }
+    let plate = low[2];
+    let footprint = (high[0] - low[0]).max(high[1] - low[1]);
+
+    let spacing = options.spacing.unwrap_or(footprint / DEFAULT_PILLARS_PER_SIDE);
+    if !spacing.is_finite() || spacing <= 0.0 {
+        return Err("Support spacing must be greater than zero, and the part must have a footprint".to_string());
+    }
+    let radius = options.radius.unwrap_or(spacing / 8.0);
+    let tip_radius = options.tip_radius.unwrap_or(radius * 0.4);
+    if !(radius > 0.0 && tip_radius > 0.0 && tip_radius <= radius) {
+        return Err("Support radii must be greater than zero, with the tip no wider than the shaft".to_string());
+    }
+    let columns = [0, 1].map(|a| ((high[a] - low[a]) / spacing).floor() as usize + 1);
+    if columns[0].saturating_mul(columns[1]) > MAX_COLUMNS {
+        return Err(format!("Support spacing {} is too fine for a part this size", spacing));
+    }
+    // Centered on the footprint, so the pillars sit evenly under it
+    let origin = [0, 1].map(|a| (low[a] + high[a]) / 2.0 - (columns[a] - 1) as f32 * spacing / 2.0);
+
+    let angles = overhang_angles(mesh, up);
+    let mut hits: HashMap<(usize, usize), Vec<Hit>> = HashMap::new();
+    for (t, triangle) in mesh.triangles.iter().enumerate() {
+        let [a, b, c] = [triangle.x, triangle.y, triangle.z].map(|i| local[i]);
+        // Twice the signed area seen from above: negative when the triangle faces down
+        let area = (b[0] - a[0]) * (c[1] - a[1]) - (c[0] - a[0]) * (b[1] - a[1]);
+        if area.abs() <= f32::EPSILON * footprint * footprint {
+            continue;
+        }
+        let overhang = angles[t].is_some_and(|angle| angle > options.max_angle);
+        let cell = |x: f32, a: usize| (x - origin[a]) / spacing;
+        let first = [0, 1].map(|k| cell(a[k].min(b[k]).min(c[k]), k).ceil().max(0.0) as usize);
+        let last = [0, 1].map(|k| (cell(a[k].max(b[k]).max(c[k]), k).floor() as i64).min(columns[k] as i64 - 1));
+        for i in first[0] as i64..=last[0] {
+            for j in first[1] as i64..=last[1] {
+                let p = [origin[0] + i as f32 * spacing, origin[1] + j as f32 * spacing];
+                let wa = ((b[0] - p[0]) * (c[1] - p[1]) - (c[0] - p[0]) * (b[1] - p[1])) / area;
+                let wb = ((c[0] - p[0]) * (a[1] - p[1]) - (a[0] - p[0]) * (c[1] - p[1])) / area;
+                let wc = 1.0 - wa - wb;
+                if wa < 0.0 || wb < 0.0 || wc < 0.0 {
+                    continue;
+                }
+                let height = wa * a[2] + wb * b[2] + wc * c[2];
+                hits.entry((i as usize, j as usize)).or_default().push(Hit { height, down: area < 0.0, overhang });
+            }
+        }
+    }
+
+    let mut supports = Mesh::default();
+    let mut report = SupportReport { spacing, radius, tip_radius, ..Default::default() };
+    let mut keys: Vec<_> = hits.keys().copied().collect();
+    keys.sort_unstable();
+    // Columns through a shared edge or vertex cross both triangles there
+    let same = footprint.max(high[2] - low[2]) * 1e-5;
+    for key in keys {
+        let mut column = hits.remove(&key).unwrap_or_default();
+        column.sort_by(|a, b| a.height.total_cmp(&b.height));
+        column.dedup_by(|b, a| (b.height - a.height).abs() <= same && b.down == a.down);
+        for (k, hit) in column.iter().enumerate() {
+            if !hit.overhang {
+                continue;
+            }
+            // Below an overhang is empty space down to a surface facing up, or the plate
+            let base = match k.checked_sub(1).map(|below| column[below]) {
+                None => None,
+                Some(below) if !below.down => Some(below.height),
+                Some(_) => continue,
+            };
+            let bottom = base.unwrap_or(plate);
+            if hit.height - bottom < 2.0 * radius {
+                continue;
+            }
+            let at = u * (origin[0] + key.0 as f32 * spacing) + v * (origin[1] + key.1 as f32 * spacing);
+            let rings = pillar_rings(bottom, hit.height, base.is_some(), radius, tip_radius);
+            add_pillar(&mut supports, at, [u, v, up], &rings);
+            report.pillars += 1;
+            report.on_part += base.is_some() as usize;
+        }
+    }
+    report.triangle_count = supports.triangles.len();
+    Ok(Supports { mesh: supports, report })
+}
+
+/// Height and radius of each ring of a pillar from height `bottom` to `top`, lowest first
+///
+/// The shaft tapers to `tip_radius` over its last few radii, ending half a
+/// tip radius inside the part.  A pillar standing on the part tapers the
+/// same way at its foot; one on the plate gets a foot twice as wide.
+fn pillar_rings(bottom: f32, top: f32, on_part: bool, radius: f32, tip_radius: f32) -> [(f32, f32); 4] {
+    let taper = (3.0 * radius).min((top - bottom) / 3.0);
+    let embed = tip_radius / 2.0;
+    if on_part {
+        [(bottom - embed, tip_radius), (bottom + taper, radius), (top - taper, radius), (top + embed, tip_radius)]
+    } else {
+        [(bottom, 2.0 * radius), (bottom + taper / 2.0, radius), (top - taper, radius), (top + embed, tip_radius)]
+    }
+}
+
+/// Add a closed pillar through `at` along the last axis of `frame`, made of `rings`
+///
+/// `frame` is a right-handed set of unit axes, the last pointing up, and
+/// `at` lies in the plane of the first two.
+fn add_pillar(mesh: &mut Mesh, at: Vector3<f32>, frame: [Vector3<f32>; 3], rings: &[(f32, f32)]) {
+    let [u, v, up] = frame;
+    let start = mesh.vertices.len();
+    for &(height, r) in rings {
+        for side in 0..PILLAR_SIDES {
+            let angle = std::f32::consts::TAU * side as f32 / PILLAR_SIDES as f32;
+            mesh.vertices.push(at + up * height + (u * angle.cos() + v * angle.sin()) * r);
+        }
+    }
+    let ring = |k: usize, side: usize| start + k * PILLAR_SIDES + side % PILLAR_SIDES;
+    for k in 0..rings.len() - 1 {
+        for side in 0..PILLAR_SIDES {
+            let (a, b, c, d) = (ring(k, side), ring(k, side + 1), ring(k + 1, side + 1), ring(k + 1, side));
+            mesh.triangles.push(Vector3::new(a, b, d));
+            mesh.triangles.push(Vector3::new(b, c, d));
+        }
+    }
+    let bottom_center = mesh.vertices.len();
+    mesh.vertices.push(at + up * rings[0].0);
+    let top_center = mesh.vertices.len();
+    mesh.vertices.push(at + up * rings[rings.len() - 1].0);
+    for side in 0..PILLAR_SIDES {
+        mesh.triangles.push(Vector3::new(bottom_center, ring(0, side + 1), ring(0, side)));
+        mesh.triangles.push(Vector3::new(top_center, ring(rings.len() - 1, side), ring(rings.len() - 1, side + 1)));
+    }
+}
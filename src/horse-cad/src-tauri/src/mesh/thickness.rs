@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use fidget::{mesh::Mesh, shape::EzShape, vm::VmShape};
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+
+use super::normals::vertex_normals;
+
+/// Thin regions reported, largest first
+const MAX_REGIONS: usize = 100;
+
+/// Smallest patch of thin vertices that's reported
+///
+/// Normals are poorly estimated at a few vertices where the octree splits
+/// cells, which shows up as isolated thin vertices on thick walls.
+const MIN_REGION_VERTICES: usize = 3;
+
+/// Share of the minimum thickness a wall may fall short by and still pass
+///
+/// Walls at least as thick as the minimum all estimate to exactly the
+/// minimum, so some slack keeps rounding from flagging them.
+const TOLERANCE: f32 = 0.05;
+
+/// A patch of surface where the wall is thinner than requested
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThinRegion {
+    /// Average position of the thin vertices
+    pub center: [f32; 3],
+    /// Estimated thickness at the thinnest vertex
+    pub min_thickness: f32,
+    pub vertex_count: usize,
+}
+
+/// Outcome of a wall thickness check
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThicknessReport {
+    /// Thickness the walls were checked against
+    pub min_thickness: f32,
+    /// Surface vertices that were tested
+    pub sampled: usize,
+    /// Vertices in thin regions, listed or not
+    pub thin_vertices: usize,
+    /// Connected patches of thin vertices, largest first
+    pub regions: Vec<ThinRegion>,
+    /// Regions beyond the first `MAX_REGIONS`, which aren't listed
+    pub omitted_regions: usize,
+}
+
+/// Find where the walls of `shape` are thinner than `min_thickness`
+///
+/// From each mesh vertex, the distance field is sampled half the minimum
+/// thickness inwards along the normal.  Where the wall is thick enough that
+/// point is at least that deep inside; otherwise it's closer to the far
+/// side, and the shortfall gives the local thickness.  One evaluation per
+/// vertex is all it takes, with no ray casting against the mesh.
+///
+/// `shape` must be in the same coordinate space as the mesh vertices.  The
+/// estimate assumes an exact distance field, so it's approximate after
+/// deformers and smooth blends.
+pub fn thin_regions(mesh: &Mesh, shape: &VmShape, min_thickness: f32) -> Result<ThicknessReport> {
+    let normals = vertex_normals(mesh);
+    let tape = shape.ez_point_tape();
+    let mut eval = VmShape::new_point_eval();
+    let half = min_thickness / 2.0;
+
+    let mut thickness = vec![None; mesh.vertices.len()];
+    for (i, (v, n)) in mesh.vertices.iter().zip(&normals).enumerate() {
+        // Vertices sit slightly off the surface, so measure from where they are
+        let (surface, _trace) = eval.eval(&tape, v.x, v.y, v.z)?;
+        let p = v - n * half;
+        let (d, _trace) = eval.eval(&tape, p.x, p.y, p.z)?;
+        let estimate = half - (d - surface);
+        if estimate < min_thickness * (1.0 - TOLERANCE) {
+            thickness[i] = Some(estimate.max(0.0));
+        }
+    }
+
+    // Group thin vertices that share an edge
+    let mut parent: Vec<usize> = (0..mesh.vertices.len()).collect();
+    fn find(parent: &mut [usize], mut v: usize) -> usize {
+        while parent[v] != v {
+            parent[v] = parent[parent[v]];
+            v = parent[v];
+        }
+        v
+    }
+    for t in &mesh.triangles {
+        for (a, b) in [(t.x, t.y), (t.y, t.z), (t.z, t.x)] {
+            if thickness[a].is_some() && thickness[b].is_some() {
+                let (a, b) = (find(&mut parent, a), find(&mut parent, b));
+                parent[a] = b;
+            }
+        }
+    }
+
+    // Sum of positions, thinnest estimate and vertex count of each patch
+    let mut groups: BTreeMap<usize, (Vector3<f32>, f32, usize)> = BTreeMap::new();
+    for (i, t) in thickness.iter().enumerate() {
+        if let Some(t) = *t {
+            let root = find(&mut parent, i);
+            let group = groups.entry(root).or_insert((Vector3::zeros(), f32::INFINITY, 0));
+            group.0 += mesh.vertices[i];
+            group.1 = group.1.min(t);
+            group.2 += 1;
+        }
+    }
+    let mut regions: Vec<ThinRegion> = groups
+        .into_values()
+        .map(|(sum, min_thickness, vertex_count)| ThinRegion {
+            center: (sum / vertex_count as f32).into(),
+            min_thickness,
+            vertex_count,
+        })
+        .filter(|region| region.vertex_count >= MIN_REGION_VERTICES)
+        .collect();
+    regions.sort_by_key(|region| std::cmp::Reverse(region.vertex_count));
+    let thin_vertices = regions.iter().map(|region| region.vertex_count).sum();
+    let omitted_regions = regions.len().saturating_sub(MAX_REGIONS);
+    regions.truncate(MAX_REGIONS);
+
+    Ok(ThicknessReport {
+        min_thickness,
+        sampled: mesh.vertices.len(),
+        thin_vertices,
+        regions,
+        omitted_regions,
+    })
+}
@@ -0,0 +1,59 @@
+use fidget::mesh::Mesh;
+use nalgebra::{Matrix4, Point3, Rotation3, Vector3};
+use serde::{Deserialize, Serialize};
+
+/// Change applied to a mesh as it's written, leaving the script alone
+///
+/// Each vertex is mirrored, scaled, rotated, then translated, and finally
+/// multiplied by `matrix` if one is given.  Lengths are in the units of the
+/// exported file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportTransform {
+    /// Flip across the plane facing each of X, Y and Z
+    pub mirror: [bool; 3],
+    /// Factor along each axis
+    pub scale: Option<[f32; 3]>,
+    /// Turn about X, then Y, then Z, in degrees
+    pub rotate: Option<[f32; 3]>,
+    pub translate: Option<[f32; 3]>,
+    /// Row-major 4×4 homogeneous matrix
+    pub matrix: Option<[[f32; 4]; 4]>,
+}
+
+impl ExportTransform {
+    /// The whole transform as one matrix, if it can be applied to a mesh
+    pub fn to_matrix(self) -> Result<Matrix4<f32>, String> {
+        let mirror = Vector3::from_fn(|i, _| if self.mirror[i] { -1.0 } else { 1.0 });
+        let scale = Vector3::from(self.scale.unwrap_or([1.0; 3]));
+        let [x, y, z] = self.rotate.unwrap_or_default().map(f32::to_radians);
+        let mut m = Matrix4::new_translation(&Vector3::from(self.translate.unwrap_or_default()))
+            * Rotation3::from_euler_angles(x, y, z).to_homogeneous()
+            * Matrix4::new_nonuniform_scaling(&mirror.component_mul(&scale));
+        if let Some(rows) = self.matrix {
+            m = Matrix4::from_fn(|r, c| rows[r][c]) * m;
+        }
+        if m.iter().any(|v| !v.is_finite()) {
+            return Err("The export transform must be finite".to_string());
+        }
+        if m.fixed_view::<3, 3>(0, 0).determinant().abs() <= f32::EPSILON {
+            return Err("The export transform flattens the model; every scale must be non-zero".to_string());
+        }
+        Ok(m)
+    }
+}
+
+/// `mesh` with `m` applied to each vertex
+///
+/// A transform that mirrors the model also reverses triangle winding, so
+/// it's flipped back to keep normals facing out.
+pub fn transform_mesh(mesh: Mesh, m: &Matrix4<f32>) -> Mesh {
+    let mirrored = m.fixed_view::<3, 3>(0, 0).determinant() < 0.0;
+    let vertices = mesh.vertices.iter().map(|v| m.transform_point(&Point3::from(*v)).coords).collect();
+    let triangles = if mirrored {
+        mesh.triangles.into_iter().map(|t| Vector3::new(t.x, t.z, t.y)).collect()
+    } else {
+        mesh.triangles
+    };
+    Mesh { vertices, triangles }
+}
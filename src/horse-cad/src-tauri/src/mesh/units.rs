@@ -0,0 +1,47 @@
+use fidget::mesh::Mesh;
+use nalgebra::{Matrix4, Point3};
+
+use crate::export::{ExportFormat, ModelUnit};
+
+/// How a compiled mesh's coordinates relate to real lengths
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MeshUnits {
+    /// Unit passed to `set_units`, if the script called it
+    pub unit: Option<ModelUnit>,
+    /// Maps mesh vertices to model coordinates, if the shape was scaled into the unit cube for meshing
+    pub to_model: Option<Matrix4<f32>>,
+}
+
+impl MeshUnits {
+    /// Unit `format` is written in, if the script declared one
+    ///
+    /// Formats without units get millimeters, which slicers assume; 3MF
+    /// and AMF record their unit, so they keep the script's unless
+    /// `requested` names another.
+    pub fn export_unit(&self, format: ExportFormat, requested: Option<ModelUnit>) -> Option<ModelUnit> {
+        let unit = self.unit?;
+        Some(match format {
+            ExportFormat::ThreeMf | ExportFormat::Amf => requested.unwrap_or(unit),
+            _ => ModelUnit::Millimeter,
+        })
+    }
+
+    /// `mesh` with its vertices in model coordinates, measured in `output`
+    pub fn convert(&self, mesh: &Mesh, output: ModelUnit) -> Mesh {
+        let factor = self.unit.unwrap_or_default().millimeters() / output.millimeters();
+        let vertices = mesh
+            .vertices
+            .iter()
+            .map(|v| match &self.to_model {
+                Some(t) => t.transform_point(&Point3::from(*v)).coords * factor,
+                None => v * factor,
+            })
+            .collect();
+        Mesh { vertices, triangles: mesh.triangles.clone() }
+    }
+
+    /// `mesh` moved back into model coordinates, in the script's own unit, or `None` if it's there already
+    pub fn model_mesh(&self, mesh: &Mesh) -> Option<Mesh> {
+        self.to_model.map(|_| self.convert(mesh, self.unit.unwrap_or_default()))
+    }
+}
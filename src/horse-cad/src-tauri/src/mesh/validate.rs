@@ -0,0 +1,123 @@
+use fidget::mesh::Mesh;
+use serde::{Deserialize, Serialize};
+
+/// Whether a mesh is a closed, consistently oriented solid
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct MeshReport {
+    /// Every edge is shared by exactly two triangles
+    pub watertight: bool,
+    /// Watertight, consistently oriented and facing outwards
+    pub printable: bool,
+    /// Edges used by only one triangle
+    pub open_edges: usize,
+    /// Separate loops of open edges, each the rim of a hole
+    pub holes: usize,
+    /// Edges shared by more than two triangles
+    pub non_manifold_edges: usize,
+    /// Shared edges whose triangles wind in opposite directions, so one is flipped
+    pub flipped_edges: usize,
+    /// Triangles with (nearly) zero area
+    pub degenerate_triangles: usize,
+    /// The surface encloses negative volume, so its normals point inwards
+    pub inside_out: bool,
+}
+
+impl MeshReport {
+    /// One-line description for logs
+    pub fn summary(&self) -> String {
+        if self.printable {
+            return "Mesh is watertight and consistently oriented".to_string();
+        }
+        let mut problems = Vec::new();
+        for (count, what) in [
+            (self.holes, "holes"),
+            (self.non_manifold_edges, "non-manifold edges"),
+            (self.flipped_edges, "flipped edges"),
+            (self.degenerate_triangles, "degenerate triangles"),
+        ] {
+            if count > 0 {
+                problems.push(format!("{} {}", count, what));
+            }
+        }
+        if self.inside_out {
+            problems.push("normals pointing inwards".to_string());
+        }
+        format!("Mesh has {}", problems.join(", "))
+    }
+}
+
+/// Check a mesh for holes, non-manifold edges and inverted normals
+pub fn validate_mesh(mesh: &Mesh) -> MeshReport {
+    // Directed edges, sorted so both uses of an undirected edge are adjacent
+    let mut edges: Vec<((usize, usize), bool)> = mesh
+        .triangles
+        .iter()
+        .flat_map(|t| [(t.x, t.y), (t.y, t.z), (t.z, t.x)])
+        .map(|(a, b)| ((a.min(b), a.max(b)), a < b))
+        .collect();
+    edges.sort_unstable();
+
+    let mut report = MeshReport::default();
+    let mut rims = DisjointSet::new(mesh.vertices.len());
+    for run in edges.chunk_by(|a, b| a.0 == b.0) {
+        let (a, b) = run[0].0;
+        match run {
+            [_] => {
+                report.open_edges += 1;
+                rims.union(a, b);
+            }
+            [(_, first), (_, second)] => {
+                if first == second {
+                    report.flipped_edges += 1;
+                }
+            }
+            _ => report.non_manifold_edges += 1,
+        }
+    }
+    report.holes = edges
+        .chunk_by(|a, b| a.0 == b.0)
+        .filter(|run| run.len() == 1)
+        .map(|run| rims.find(run[0].0 .0))
+        .collect::<std::collections::HashSet<_>>()
+        .len();
+
+    let mut volume = 0.0;
+    for t in &mesh.triangles {
+        let (a, b, c) = (mesh.vertices[t.x], mesh.vertices[t.y], mesh.vertices[t.z]);
+        let area = (b - a).cross(&(c - a)).norm();
+        let longest = (b - a).norm().max((c - b).norm()).max((a - c).norm());
+        if area <= longest * longest * 1e-6 {
+            report.degenerate_triangles += 1;
+        }
+        volume += f64::from(a.dot(&b.cross(&c)));
+    }
+    report.inside_out = volume < 0.0;
+
+    report.watertight = report.open_edges == 0 && report.non_manifold_edges == 0;
+    report.printable = report.watertight && report.flipped_edges == 0 && !report.inside_out;
+    report
+}
+
+/// Union-find over vertex indices, for grouping open edges into hole rims
+struct DisjointSet {
+    parent: Vec<usize>,
+}
+
+impl DisjointSet {
+    fn new(count: usize) -> Self {
+        DisjointSet { parent: (0..count).collect() }
+    }
+
+    fn find(&mut self, mut v: usize) -> usize {
+        while self.parent[v] != v {
+            self.parent[v] = self.parent[self.parent[v]];
+            v = self.parent[v];
+        }
+        v
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        self.parent[a] = b;
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+
+use fidget::mesh::Mesh;
+use nalgebra::Vector3;
+
+/// Default weld tolerance, as a fraction of the mesh's bounding box diagonal
+const RELATIVE_TOLERANCE: f32 = 1e-5;
+
+/// A welded mesh
+pub struct Welded {
+    pub mesh: Mesh,
+    /// Vertices merged into another one
+    pub merged: usize,
+}
+
+/// Default weld tolerance for `mesh`, scaled to its size
+pub fn weld_tolerance(mesh: &Mesh) -> f32 {
+    let Some(first) = mesh.vertices.first() else { return 0.0 };
+    let (min, max) = mesh.vertices.iter().fold((*first, *first), |(min, max), v| (min.inf(v), max.sup(v)));
+    (max - min).norm() * RELATIVE_TOLERANCE
+}
+
+/// Merge vertices closer together than `tolerance` and drop triangles that collapse
+///
+/// Dual contouring places separate vertices at the same spot where a sharp
+/// feature crosses several cells, joined up by zero-area triangles.
+/// Vertices are bucketed in a hash grid of `tolerance`-sized cells, so each
+/// only has to be compared against its neighbouring cells; the first vertex
+/// seen in a cluster is the one that's kept.
+///
+/// Merging can fold the zero-area triangles into pairs of coincident faces
+/// wound in opposite directions; these cancel out and are removed too.
+pub fn weld(mesh: &Mesh, tolerance: f32) -> Welded {
+    let cell_of = |v: &Vector3<f32>| v.map(|c| (c / tolerance).floor() as i64);
+    let mut grid: HashMap<Vector3<i64>, Vec<usize>> = HashMap::new();
+    let mut welded = Mesh::new();
+    let mut remap = Vec::with_capacity(mesh.vertices.len());
+
+    for v in &mesh.vertices {
+        let existing = if tolerance > 0.0 {
+            let cell = cell_of(v);
+            (0..27)
+                .map(|i| cell + Vector3::new(i % 3 - 1, i / 3 % 3 - 1, i / 9 - 1))
+                .filter_map(|c| grid.get(&c))
+                .flatten()
+                .copied()
+                .find(|&w| (welded.vertices[w] - v).norm() <= tolerance)
+        } else {
+            None
+        };
+        let index = existing.unwrap_or_else(|| {
+            let index = welded.vertices.len();
+            welded.vertices.push(*v);
+            if tolerance > 0.0 {
+                grid.entry(cell_of(v)).or_default().push(index);
+            }
+            index
+        });
+        remap.push(index);
+    }
+
+    let triangles: Vec<_> = mesh
+        .triangles
+        .iter()
+        .map(|t| t.map(|v| remap[v]))
+        .filter(|t| t.x != t.y && t.y != t.z && t.z != t.x)
+        .collect();
+
+    // Net winding of each set of corners: +1 for every face wound one way, -1 the other
+    let mut windings: HashMap<[usize; 3], i32> = HashMap::new();
+    for t in &triangles {
+        let (corners, winding) = sorted_corners(t);
+        *windings.entry(corners).or_default() += winding;
+    }
+    for t in triangles {
+        let (corners, winding) = sorted_corners(&t);
+        let net = windings.get_mut(&corners).unwrap();
+        // Keep one face with the winding that's left over, if any
+        if *net != 0 && net.signum() == winding {
+            *net = 0;
+            welded.triangles.push(t);
+        }
+    }
+    let merged = mesh.vertices.len() - welded.vertices.len();
+    Welded { mesh: welded, merged }
+}
+
+/// A triangle's corners in ascending order, and whether that kept (+1) or reversed (-1) its winding
+fn sorted_corners(t: &Vector3<usize>) -> ([usize; 3], i32) {
+    let [a, b, c] = [t.x, t.y, t.z];
+    // Rotating the corners keeps the winding, so start from the smallest
+    let [a, b, c] = if a < b && a < c {
+        [a, b, c]
+    } else if b < c {
+        [b, c, a]
+    } else {
+        [c, a, b]
+    };
+    if b < c {
+        ([a, b, c], 1)
+    } else {
+        ([a, c, b], -1)
+    }
+}
@@ -0,0 +1,262 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, Window};
+
+use crate::emit_log;
+use crate::horsi::{DocumentMetadata, HorsiFile};
+use crate::recent::remember_file;
+
+/// Extension of horseCAD scripts
+pub const SCRIPT_EXTENSION: &str = "horsi";
+
+/// A script opened from outside the app, sent as the `open_document` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenedDocument {
+    /// Empty for a script that isn't a file yet, such as one opened from a link
+    pub path: String,
+    /// The script, without the file's metadata section
+    pub content: String,
+    #[serde(default)]
+    pub metadata: Option<DocumentMetadata>,
+    /// The document it replaces had unsaved changes, so the frontend may want to ask first
+    #[serde(default)]
+    pub replaces_unsaved: bool,
+}
+
+/// Sent when the open document's file was changed by something other than the app
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileChangedExternally {
+    pub path: String,
+    /// The editor has changes of its own that reloading would discard
+    pub has_unsaved_changes: bool,
+}
+
+/// The open document's file as the app last read or wrote it
+struct FileStamp {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    hash: u64,
+}
+
+fn content_hash(content: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// The document open in one window: its file, if it has one, and whether it has unsaved changes
+#[derive(Default)]
+struct WindowDocument {
+    dirty: bool,
+    file: Option<FileStamp>,
+}
+
+/// Scripts opened before the frontend was listening for them
+///
+/// Files passed on the command line arrive before the window has loaded,
+/// so they wait here until the frontend calls `take_opened_documents`;
+/// anything opened after that is only sent as an event.
+///
+/// Also tracks each window's open document: its file, to notice when it
+/// changes on disk, and whether it has unsaved changes, which the frontend
+/// reports through `set_document_dirty`.
+pub struct OpenedDocuments {
+    pending: Mutex<Option<Vec<OpenedDocument>>>,
+    /// By window label
+    windows: Mutex<HashMap<String, WindowDocument>>,
+}
+
+impl OpenedDocuments {
+    pub fn new() -> Self {
+        OpenedDocuments { pending: Mutex::new(Some(Vec::new())), windows: Mutex::default() }
+    }
+
+    /// Record `content` as what the file at `path`, open in `window`, holds
+    pub fn track(&self, window: &str, path: &Path, content: &str) {
+        let stamp = FileStamp { path: path.to_path_buf(), modified: modified_time(path), hash: content_hash(content) };
+        let mut windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+        windows.entry(window.to_string()).or_default().file = Some(stamp);
+    }
+
+    /// Record `content` as what the file at `path` holds, in every window that has it open
+    pub fn retrack(&self, path: &Path, content: &str) {
+        for document in self.windows.lock().unwrap_or_else(|e| e.into_inner()).values_mut() {
+            if let Some(stamp) = document.file.as_mut().filter(|stamp| stamp.path == path) {
+                stamp.modified = modified_time(path);
+                stamp.hash = content_hash(content);
+            }
+        }
+    }
+
+    /// The file of the document open in `window`, if it has one
+    pub fn tracked_path(&self, window: &str) -> Option<PathBuf> {
+        let windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+        windows.get(window)?.file.as_ref().map(|stamp| stamp.path.clone())
+    }
+
+    /// Replace `window`'s document with a clean one kept in `file`, returning whether the old one was dirty
+    fn replace(&self, window: &str, file: Option<FileStamp>) -> bool {
+        let mut windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+        let replaced = windows.insert(window.to_string(), WindowDocument { dirty: false, file });
+        replaced.is_some_and(|document| document.dirty)
+    }
+
+    /// Whether the file open in `window` now holds something other than what the app last saw
+    ///
+    /// Returns the file with whether the document has unsaved changes.  A
+    /// change is only reported once; the new contents become the ones
+    /// tracked, whether or not the frontend reloads them.
+    fn take_external_change(&self, window: &str) -> Option<(PathBuf, bool)> {
+        let mut windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+        let document = windows.get_mut(window)?;
+        let stamp = document.file.as_mut()?;
+        let modified = modified_time(&stamp.path);
+        if modified == stamp.modified {
+            return None;
+        }
+        stamp.modified = modified;
+        // Only a change of contents counts, not just a newer timestamp
+        let hash = content_hash(&std::fs::read_to_string(&stamp.path).ok()?);
+        if hash == stamp.hash {
+            return None;
+        }
+        stamp.hash = hash;
+        Some((stamp.path.clone(), document.dirty))
+    }
+
+    pub fn set_dirty(&self, window: &str, dirty: bool) {
+        self.windows.lock().unwrap_or_else(|e| e.into_inner()).entry(window.to_string()).or_default().dirty = dirty;
+    }
+
+    /// Stop tracking the document of `window`, once it has closed
+    pub fn forget(&self, window: &str) {
+        self.windows.lock().unwrap_or_else(|e| e.into_inner()).remove(window);
+    }
+
+    /// Hand over the scripts opened so far, and send any later ones straight away
+    pub fn take(&self) -> Vec<OpenedDocument> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner()).take().unwrap_or_default()
+    }
+
+    /// Keep `document` for the frontend, returning it back if the frontend is already listening
+    fn hold(&self, document: OpenedDocument) -> Option<OpenedDocument> {
+        match self.pending.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            Some(pending) => {
+                pending.push(document);
+                None
+            }
+            None => Some(document),
+        }
+    }
+}
+
+/// Whether `path` names a horseCAD script
+pub fn is_script(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e.eq_ignore_ascii_case(SCRIPT_EXTENSION))
+}
+
+/// Scripts named on the command line, skipping the program name and any flags
+pub fn scripts_in_args(args: impl IntoIterator<Item = String>) -> Vec<PathBuf> {
+    args.into_iter()
+        .skip(1)
+        .filter(|arg| !arg.starts_with('-'))
+        .map(PathBuf::from)
+        .filter(|path| is_script(path))
+        .collect()
+}
+
+/// Label of the focused window, or of the main one if none is focused
+pub fn focused_window(app_handle: &AppHandle) -> String {
+    let windows = app_handle.webview_windows();
+    let focused = windows.into_iter().find(|(_, window)| window.is_focused().unwrap_or(false));
+    focused.map(|(label, _)| label).unwrap_or_else(|| "main".to_string())
+}
+
+/// Read a script opened from outside the app and pass it to the frontend
+///
+/// The script goes to the window labelled `target`, or else to the focused
+/// window, or the main one if none is focused.
+pub fn open_document(app_handle: &AppHandle, path: &Path, target: Option<&str>) -> Result<(), String> {
+    let path = path.to_string_lossy().into_owned();
+    let text = std::fs::read_to_string(&path).map_err(|e| {
+        let error_msg = format!("Failed to open file {}: {}", path, e);
+        emit_log(app_handle, "error", &error_msg, Some("File"));
+        error_msg
+    })?;
+    remember_file(app_handle, &path);
+    emit_log(app_handle, "info", &format!("Opened file: {}", path), Some("File"));
+
+    let target = target.map(str::to_string).unwrap_or_else(|| focused_window(app_handle));
+    let file = Path::new(&path);
+    let stamp = FileStamp { path: file.to_path_buf(), modified: modified_time(file), hash: content_hash(&text) };
+    // The newly opened document starts out clean
+    let replaces_unsaved = app_handle.state::<OpenedDocuments>().replace(&target, Some(stamp));
+    let HorsiFile { script, metadata } = HorsiFile::parse(&text);
+    let document = OpenedDocument { path, content: script, metadata, replaces_unsaved };
+    deliver(app_handle, document, &target);
+    Ok(())
+}
+
+/// Pass `text`, which isn't from a file, to the frontend as a new untitled document
+///
+/// It goes to the focused window, as `open_document` does, and saving it
+/// asks for a path.
+pub fn open_untitled(app_handle: &AppHandle, text: &str) {
+    let target = focused_window(app_handle);
+    let replaces_unsaved = app_handle.state::<OpenedDocuments>().replace(&target, None);
+    let HorsiFile { script, metadata } = HorsiFile::parse(text);
+    let document = OpenedDocument { path: String::new(), content: script, metadata, replaces_unsaved };
+    deliver(app_handle, document, &target);
+}
+
+/// Hold `document` until the frontend is listening, or send it to the window labelled `target`
+fn deliver(app_handle: &AppHandle, document: OpenedDocument, target: &str) {
+    let Some(document) = app_handle.state::<OpenedDocuments>().hold(document) else { return };
+    if let Err(e) = app_handle.emit_to(target, "open_document", &document) {
+        eprintln!("Failed to emit open_document event: {}", e);
+    }
+}
+
+/// Open the first script among files dropped on `window`
+///
+/// Other files are ignored with a warning, since only one script is open
+/// at a time.
+pub fn open_dropped(window: &Window, paths: &[PathBuf]) {
+    let app_handle = window.app_handle();
+    let (scripts, others): (Vec<&PathBuf>, Vec<&PathBuf>) = paths.iter().partition(|path| is_script(path));
+    for path in others {
+        let message = format!("Can't open {}: only .{} scripts can be dropped", path.display(), SCRIPT_EXTENSION);
+        emit_log(app_handle, "warn", &message, Some("File"));
+    }
+    let Some(first) = scripts.first() else { return };
+    if scripts.len() > 1 {
+        let message = format!("{} scripts were dropped; opening only {}", scripts.len(), first.display());
+        emit_log(app_handle, "warn", &message, Some("File"));
+    }
+    let _ = open_document(app_handle, first, Some(window.label()));
+}
+
+/// Emit `file_changed_externally` to `window` if its document's file changed on disk
+///
+/// Checked when the window regains focus and when the watcher sees the
+/// file change, so edits made elsewhere aren't silently overwritten.
+pub fn check_external_change(app_handle: &AppHandle, window: &str) {
+    let opened = app_handle.state::<OpenedDocuments>();
+    let Some((path, has_unsaved_changes)) = opened.take_external_change(window) else { return };
+    let path = path.to_string_lossy().into_owned();
+    emit_log(app_handle, "info", &format!("{} was changed outside horseCAD", path), Some("File"));
+    let event = FileChangedExternally { path, has_unsaved_changes };
+    if let Err(e) = app_handle.emit_to(window, "file_changed_externally", &event) {
+        eprintln!("Failed to emit file_changed_externally event: {}", e);
+    }
+}
@@ -0,0 +1,476 @@
+use std::any::TypeId;
+use std::ffi::{c_char, c_void, CStr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use fidget::{context::Tree, mesh::Mesh};
+use libloading::Library;
+use rhai::{Array, Dynamic, Engine, NativeCallContext};
+use serde::{Deserialize, Serialize};
+
+use crate::export::vm::{binary_opcode, unary_opcode};
+use crate::number_from_dynamic;
+use crate::utils::file_utils::sha256_hex;
+
+/// Version of the structs below; plugins built against another version are refused
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+/// Function every plugin exports, called once at startup
+///
+/// `extern "C" fn horsecad_plugin_init(registrar: *const PluginRegistrar) -> i32`,
+/// returning 0 on success.
+const INIT_SYMBOL: &[u8] = b"horsecad_plugin_init\0";
+
+/// Function every plugin exports, returning the `PLUGIN_ABI_VERSION` it was built against
+///
+/// `extern "C" fn horsecad_plugin_abi_version() -> u32`, called before
+/// `horsecad_plugin_init` so a plugin for another version is never handed
+/// structs it doesn't match.
+const ABI_VERSION_SYMBOL: &[u8] = b"horsecad_plugin_abi_version\0";
+
+/// Script function in a plugin, called with its arguments as numbers and returning 0 on success
+pub type PluginFn = unsafe extern "C" fn(args: *const f64, arg_count: usize, out: *const FunctionOutput) -> i32;
+
+/// Exporter in a plugin, writing `mesh` through `out` and returning 0 on success
+pub type PluginExportFn = unsafe extern "C" fn(mesh: *const PluginMesh, out: *const ExportOutput) -> i32;
+
+/// Handed to `horsecad_plugin_init` for the plugin to register what it provides
+///
+/// Strings are NUL-terminated UTF-8 and are copied, so they only need to
+/// live for the call.
+#[repr(C)]
+pub struct PluginRegistrar {
+    pub abi_version: u32,
+    pub ctx: *mut c_void,
+    /// Add script function `name` taking `arg_count` numbers, with a one-line description or null
+    pub register_function: unsafe extern "C" fn(
+        ctx: *mut c_void,
+        name: *const c_char,
+        arg_count: usize,
+        doc: *const c_char,
+        func: PluginFn,
+    ),
+    /// Add export format `name`, for files ending in `extension`
+    pub register_exporter:
+        unsafe extern "C" fn(ctx: *mut c_void, name: *const c_char, extension: *const c_char, func: PluginExportFn),
+}
+
+/// Where a plugin function puts its result: a list of numbers, or a shape
+///
+/// Shapes are built a node at a time.  Each builder returns the new node's
+/// index for later nodes to refer to, and opcodes are numbered as in
+/// compiled shape files.  A function that calls `set_shape` returns that
+/// node; otherwise it returns the numbers pushed, as an array.
+#[repr(C)]
+pub struct FunctionOutput {
+    pub ctx: *mut c_void,
+    pub push_number: unsafe extern "C" fn(ctx: *mut c_void, value: f64),
+    /// 0, 1 or 2 for X, Y or Z
+    pub input: unsafe extern "C" fn(ctx: *mut c_void, axis: u8) -> u32,
+    pub constant: unsafe extern "C" fn(ctx: *mut c_void, value: f64) -> u32,
+    pub unary: unsafe extern "C" fn(ctx: *mut c_void, op: u8, a: u32) -> u32,
+    pub binary: unsafe extern "C" fn(ctx: *mut c_void, op: u8, a: u32, b: u32) -> u32,
+    pub set_shape: unsafe extern "C" fn(ctx: *mut c_void, node: u32),
+    /// Fail the call with `message`
+    pub error: unsafe extern "C" fn(ctx: *mut c_void, message: *const c_char),
+}
+
+/// Mesh handed to a plugin exporter, in model units
+#[repr(C)]
+pub struct PluginMesh {
+    /// X, Y and Z of each vertex
+    pub vertices: *const f32,
+    pub vertex_count: usize,
+    /// Three vertex indices per triangle, counter-clockwise seen from outside
+    pub triangles: *const u32,
+    pub triangle_count: usize,
+}
+
+/// Where a plugin exporter writes the file
+#[repr(C)]
+pub struct ExportOutput {
+    pub ctx: *mut c_void,
+    /// Append `len` bytes to the file
+    pub write: unsafe extern "C" fn(ctx: *mut c_void, data: *const u8, len: usize),
+    /// Fail the export with `message`
+    pub error: unsafe extern "C" fn(ctx: *mut c_void, message: *const c_char),
+}
+
+/// A script function provided by a plugin
+#[derive(Clone)]
+pub struct PluginFunction {
+    pub name: String,
+    pub arg_count: usize,
+    pub doc: Option<String>,
+    func: PluginFn,
+}
+
+/// An export format provided by a plugin
+#[derive(Clone)]
+pub struct PluginExporter {
+    pub name: String,
+    pub extension: String,
+    func: PluginExportFn,
+}
+
+/// An export format a plugin registered, for listing in the export menu
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExporterInfo {
+    pub name: String,
+    /// File extension, without the dot
+    pub extension: String,
+}
+
+/// A library found in the plugins folder
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginInfo {
+    pub path: String,
+    /// Script functions it registered, as `name/arg_count`
+    pub functions: Vec<String>,
+    pub exporters: Vec<ExporterInfo>,
+    /// Why it couldn't be loaded, in which case nothing it registered is used
+    pub error: Option<String>,
+}
+
+/// Native libraries in the app config folder that add script functions and export formats
+///
+/// Each `.so`, `.dylib` or `.dll` in the folder is loaded at startup and,
+/// if its `horsecad_plugin_abi_version` matches [`PLUGIN_ABI_VERSION`],
+/// its `horsecad_plugin_init` called with a [`PluginRegistrar`].  Plugins
+/// run with the app's full permissions, so only install ones you trust.
+/// Libraries stay loaded until the app quits, since engines keep pointers
+/// to their functions; changes need a restart.
+pub struct Plugins {
+    functions: Arc<Vec<PluginFunction>>,
+    exporters: Vec<PluginExporter>,
+    found: Vec<PluginInfo>,
+    fingerprint: String,
+    _libraries: Vec<Library>,
+}
+
+/// What one plugin registered while its init function ran
+#[derive(Default)]
+struct Registration {
+    functions: Vec<PluginFunction>,
+    exporters: Vec<PluginExporter>,
+    errors: Vec<String>,
+}
+
+impl Plugins {
+    /// Load every library in `dir`, creating it so users have somewhere to put plugins
+    pub fn load(dir: Option<PathBuf>) -> Self {
+        let mut plugins = Plugins {
+            functions: Arc::default(),
+            exporters: Vec::new(),
+            found: Vec::new(),
+            fingerprint: String::new(),
+            _libraries: Vec::new(),
+        };
+        let Some(dir) = dir else { return plugins };
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            eprintln!("Failed to create the plugins folder {}: {}", dir.display(), e);
+        }
+        let mut files: Vec<PathBuf> = std::fs::read_dir(&dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == std::env::consts::DLL_EXTENSION))
+            .collect();
+        files.sort();
+
+        let mut functions = Vec::new();
+        let mut identity = String::new();
+        for path in files {
+            let path_label = path.display().to_string();
+            let mut info = PluginInfo { path: path_label, functions: Vec::new(), exporters: Vec::new(), error: None };
+            match load_library(&path) {
+                Ok((library, registration)) => {
+                    info.functions =
+                        registration.functions.iter().map(|f| format!("{}/{}", f.name, f.arg_count)).collect();
+                    info.exporters = registration
+                        .exporters
+                        .iter()
+                        .map(|e| ExporterInfo { name: e.name.clone(), extension: e.extension.clone() })
+                        .collect();
+                    let metadata = std::fs::metadata(&path).ok();
+                    let size = metadata.as_ref().map(|m| m.len());
+                    let modified = metadata.and_then(|m| m.modified().ok());
+                    identity += &format!("{} {:?} {:?} {:?}\n", info.path, size, modified, info.functions);
+                    functions.extend(registration.functions);
+                    plugins.exporters.extend(registration.exporters);
+                    plugins._libraries.push(library);
+                }
+                Err(e) => info.error = Some(e),
+            }
+            plugins.found.push(info);
+        }
+        plugins.functions = Arc::new(functions);
+        if !identity.is_empty() {
+            plugins.fingerprint = sha256_hex(identity.as_bytes());
+        }
+        plugins
+    }
+
+    /// Identifies the loaded libraries and the script functions they registered, or empty if none loaded
+    ///
+    /// A rebuilt plugin has a new size or modification time, so compiles
+    /// cached with another plugin set aren't reused.
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+
+    pub fn functions(&self) -> Arc<Vec<PluginFunction>> {
+        self.functions.clone()
+    }
+
+    pub fn exporter(&self, name: &str) -> Option<&PluginExporter> {
+        self.exporters.iter().find(|exporter| exporter.name == name)
+    }
+
+    /// Every library found at startup, including ones that failed to load
+    pub fn found(&self) -> &[PluginInfo] {
+        &self.found
+    }
+}
+
+/// Open the library at `path` and run its init function
+fn load_library(path: &Path) -> Result<(Library, Registration), String> {
+    // SAFETY: loading a library runs its initialisers; plugins are trusted native code
+    let library = unsafe { Library::new(path) }.map_err(|e| e.to_string())?;
+    // SAFETY: the symbol's type is the one documented for plugins
+    let abi_version = unsafe { library.get::<unsafe extern "C" fn() -> u32>(ABI_VERSION_SYMBOL) }
+        .map_err(|_| "it doesn't export horsecad_plugin_abi_version".to_string())?;
+    // SAFETY: the function takes nothing and only returns a number
+    let version = unsafe { abi_version() };
+    if version != PLUGIN_ABI_VERSION {
+        return Err(format!(
+            "it was built for plugin ABI version {}, but this version of HorseCAD uses {}",
+            version, PLUGIN_ABI_VERSION
+        ));
+    }
+    let mut registration = Registration::default();
+    let status = {
+        // SAFETY: the symbol's type is the one documented for plugins
+        let init = unsafe { library.get::<unsafe extern "C" fn(*const PluginRegistrar) -> i32>(INIT_SYMBOL) }
+            .map_err(|_| "it doesn't export horsecad_plugin_init".to_string())?;
+        let registrar = PluginRegistrar {
+            abi_version: PLUGIN_ABI_VERSION,
+            ctx: &mut registration as *mut Registration as *mut c_void,
+            register_function,
+            register_exporter,
+        };
+        // SAFETY: `registrar` and `registration` outlive the call
+        unsafe { init(&registrar) }
+    };
+    if status != 0 {
+        return Err(format!("horsecad_plugin_init failed with status {}", status));
+    }
+    if !registration.errors.is_empty() {
+        return Err(registration.errors.join("; "));
+    }
+    Ok((library, registration))
+}
+
+/// Copy a NUL-terminated string from a plugin, or `None` for null
+///
+/// # Safety
+/// `ptr` must be null or point to a NUL-terminated string.
+unsafe fn plugin_string(ptr: *const c_char) -> Option<String> {
+    (!ptr.is_null()).then(|| CStr::from_ptr(ptr).to_string_lossy().into_owned())
+}
+
+unsafe extern "C" fn register_function(
+    ctx: *mut c_void,
+    name: *const c_char,
+    arg_count: usize,
+    doc: *const c_char,
+    func: PluginFn,
+) {
+    let registration = &mut *(ctx as *mut Registration);
+    match plugin_string(name) {
+        Some(name) if is_identifier(&name) => {
+            registration.functions.push(PluginFunction { name, arg_count, doc: plugin_string(doc), func })
+        }
+        name => registration.errors.push(format!("'{}' isn't a valid function name", name.unwrap_or_default())),
+    }
+}
+
+unsafe extern "C" fn register_exporter(
+    ctx: *mut c_void,
+    name: *const c_char,
+    extension: *const c_char,
+    func: PluginExportFn,
+) {
+    let registration = &mut *(ctx as *mut Registration);
+    match (plugin_string(name), plugin_string(extension)) {
+        (Some(name), Some(extension)) if !name.is_empty() => {
+            let extension = extension.trim_start_matches('.').to_string();
+            registration.exporters.push(PluginExporter { name, extension, func })
+        }
+        _ => registration.errors.push("an exporter needs a name and a file extension".to_string()),
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// State behind a [`FunctionOutput`] during one call
+#[derive(Default)]
+struct CallState {
+    numbers: Vec<f64>,
+    nodes: Vec<Tree>,
+    shape: Option<Tree>,
+    error: Option<String>,
+}
+
+impl CallState {
+    fn node(&self, index: u32) -> Option<Tree> {
+        self.nodes.get(index as usize).cloned()
+    }
+
+    /// Add a node, or record an error and return an index no node has
+    fn push(&mut self, tree: Option<Tree>) -> u32 {
+        match tree {
+            Some(tree) => {
+                self.nodes.push(tree);
+                self.nodes.len() as u32 - 1
+            }
+            None => {
+                self.error.get_or_insert_with(|| "invalid node or opcode".to_string());
+                u32::MAX
+            }
+        }
+    }
+}
+
+unsafe fn call_state<'a>(ctx: *mut c_void) -> &'a mut CallState {
+    &mut *(ctx as *mut CallState)
+}
+
+unsafe extern "C" fn push_number(ctx: *mut c_void, value: f64) {
+    call_state(ctx).numbers.push(value);
+}
+
+unsafe extern "C" fn input(ctx: *mut c_void, axis: u8) -> u32 {
+    let tree = match axis {
+        0 => Some(Tree::x()),
+        1 => Some(Tree::y()),
+        2 => Some(Tree::z()),
+        _ => None,
+    };
+    call_state(ctx).push(tree)
+}
+
+unsafe extern "C" fn constant(ctx: *mut c_void, value: f64) -> u32 {
+    call_state(ctx).push(Some(Tree::constant(value)))
+}
+
+unsafe extern "C" fn unary(ctx: *mut c_void, op: u8, a: u32) -> u32 {
+    let state = call_state(ctx);
+    let tree = state.node(a).and_then(|a| unary_opcode(op, &a));
+    state.push(tree)
+}
+
+unsafe extern "C" fn binary(ctx: *mut c_void, op: u8, a: u32, b: u32) -> u32 {
+    let state = call_state(ctx);
+    let tree = state.node(a).zip(state.node(b)).and_then(|(a, b)| binary_opcode(op, a, b));
+    state.push(tree)
+}
+
+unsafe extern "C" fn set_shape(ctx: *mut c_void, node: u32) {
+    let state = call_state(ctx);
+    match state.node(node) {
+        Some(tree) => state.shape = Some(tree),
+        None => state.error = Some(format!("node {} doesn't exist", node)),
+    }
+}
+
+unsafe extern "C" fn call_error(ctx: *mut c_void, message: *const c_char) {
+    call_state(ctx).error = Some(plugin_string(message).unwrap_or_default());
+}
+
+impl PluginFunction {
+    fn call(&self, args: &[f64]) -> Result<Dynamic, String> {
+        let mut state = CallState::default();
+        let out = FunctionOutput {
+            ctx: &mut state as *mut CallState as *mut c_void,
+            push_number,
+            input,
+            constant,
+            unary,
+            binary,
+            set_shape,
+            error: call_error,
+        };
+        // SAFETY: `out` and `state` outlive the call, and the library stays loaded
+        let status = unsafe { (self.func)(args.as_ptr(), args.len(), &out) };
+        match (status, state.error) {
+            (_, Some(error)) => Err(format!("{} failed: {}", self.name, error)),
+            (0, None) => Ok(match state.shape {
+                Some(shape) => Dynamic::from(shape),
+                None => Dynamic::from_array(state.numbers.into_iter().map(Dynamic::from_float).collect::<Array>()),
+            }),
+            (status, None) => Err(format!("{} failed with status {}", self.name, status)),
+        }
+    }
+}
+
+impl PluginExporter {
+    /// Write `mesh` in this format
+    pub fn export(&self, mesh: &Mesh) -> Result<Vec<u8>, String> {
+        let vertices: Vec<f32> = mesh.vertices.iter().flat_map(|v| [v.x, v.y, v.z]).collect();
+        let triangles: Vec<u32> = mesh.triangles.iter().flat_map(|t| [t.x as u32, t.y as u32, t.z as u32]).collect();
+        let input = PluginMesh {
+            vertices: vertices.as_ptr(),
+            vertex_count: mesh.vertices.len(),
+            triangles: triangles.as_ptr(),
+            triangle_count: mesh.triangles.len(),
+        };
+        let mut state = ExportState::default();
+        let out = ExportOutput { ctx: &mut state as *mut ExportState as *mut c_void, write, error: export_error };
+        // SAFETY: the buffers, `out` and `state` outlive the call, and the library stays loaded
+        let status = unsafe { (self.func)(&input, &out) };
+        match (status, state.error) {
+            (_, Some(error)) => Err(error),
+            (0, None) => Ok(state.data),
+            (status, None) => Err(format!("exporter failed with status {}", status)),
+        }
+    }
+}
+
+/// State behind an [`ExportOutput`] during one export
+#[derive(Default)]
+struct ExportState {
+    data: Vec<u8>,
+    error: Option<String>,
+}
+
+unsafe extern "C" fn write(ctx: *mut c_void, data: *const u8, len: usize) {
+    if !data.is_null() {
+        (*(ctx as *mut ExportState)).data.extend_from_slice(std::slice::from_raw_parts(data, len));
+    }
+}
+
+unsafe extern "C" fn export_error(ctx: *mut c_void, message: *const c_char) {
+    (*(ctx as *mut ExportState)).error = Some(plugin_string(message).unwrap_or_default());
+}
+
+/// Register each plugin function on `engine`
+///
+/// Arguments may be integers or floats; anything else is an error.
+pub fn register(engine: &mut Engine, functions: &[PluginFunction]) {
+    for function in functions {
+        let function = function.clone();
+        let arg_types = vec![TypeId::of::<Dynamic>(); function.arg_count];
+        engine.register_raw_fn(function.name.clone(), arg_types, move |_: NativeCallContext, args| {
+            let args = args
+                .iter()
+                .enumerate()
+                .map(|(i, arg)| number_from_dynamic(&format!("{} argument {}", function.name, i + 1), arg))
+                .collect::<Result<Vec<f64>, _>>()?;
+            function.call(&args).map_err(Into::into)
+        });
+    }
+}
@@ -0,0 +1,116 @@
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use tauri::menu::{MenuItemBuilder, PredefinedMenuItem, Submenu};
+use tauri::{AppHandle, Manager, Wry};
+
+use crate::emit_log;
+use crate::utils::file_utils::{set_aside, write_json};
+
+/// How many files the recent list keeps
+pub const MAX_RECENT_FILES: usize = 10;
+
+/// Menu id prefix of the items in the Open Recent submenu, followed by the item's index
+pub const RECENT_MENU_PREFIX: &str = "open_recent_";
+
+/// Menu id of the item that empties the recent list
+pub const CLEAR_RECENT_MENU_ID: &str = "clear_recent";
+
+/// Recently opened and saved scripts, newest first
+///
+/// The list is kept as a JSON array of paths in the app data folder, and
+/// mirrored into the File menu's Open Recent submenu.
+pub struct RecentFiles {
+    store: Option<PathBuf>,
+    files: Mutex<Vec<String>>,
+    menu: Mutex<Option<Submenu<Wry>>>,
+}
+
+impl RecentFiles {
+    /// Read the list kept in `store`, starting empty if there is none yet
+    pub fn load(store: Option<PathBuf>) -> Self {
+        let files = store
+            .as_ref()
+            .and_then(|path| {
+                let text = std::fs::read_to_string(path).ok()?;
+                let files = serde_json::from_str::<Vec<String>>(&text).ok();
+                if files.is_none() {
+                    // Keep an unreadable list rather than save an empty one over it
+                    let _ = set_aside(path);
+                }
+                files
+            })
+            .unwrap_or_default();
+        RecentFiles { store, files: Mutex::new(files), menu: Mutex::new(None) }
+    }
+
+    pub fn files(&self) -> Vec<String> {
+        self.files.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// The file at `index` in the list, as numbered in the menu
+    pub fn get(&self, index: usize) -> Option<String> {
+        self.files.lock().unwrap_or_else(|e| e.into_inner()).get(index).cloned()
+    }
+
+    /// Keep the submenu that shows the list, and fill it in
+    pub fn attach_menu(&self, app_handle: &AppHandle, menu: Submenu<Wry>) {
+        *self.menu.lock().unwrap_or_else(|e| e.into_inner()) = Some(menu);
+        self.refresh_menu(app_handle);
+    }
+
+    /// Move `path` to the top of the list, adding it if it isn't there
+    pub fn add(&self, app_handle: &AppHandle, path: &str) {
+        {
+            let mut files = self.files.lock().unwrap_or_else(|e| e.into_inner());
+            files.retain(|file| file != path);
+            files.insert(0, path.to_string());
+            files.truncate(MAX_RECENT_FILES);
+        }
+        self.save(app_handle);
+    }
+
+    pub fn clear(&self, app_handle: &AppHandle) {
+        self.files.lock().unwrap_or_else(|e| e.into_inner()).clear();
+        self.save(app_handle);
+    }
+
+    fn save(&self, app_handle: &AppHandle) {
+        self.refresh_menu(app_handle);
+        let Some(store) = &self.store else { return };
+        if let Err(e) = write_json(store, &self.files()) {
+            let message = format!("Failed to save recent files to {}: {}", store.display(), e);
+            emit_log(app_handle, "warn", &message, Some("File"));
+        }
+    }
+
+    /// Rebuild the Open Recent submenu from the list
+    fn refresh_menu(&self, app_handle: &AppHandle) {
+        let Some(menu) = self.menu.lock().unwrap_or_else(|e| e.into_inner()).clone() else { return };
+        let rebuilt = (|| -> tauri::Result<()> {
+            for item in menu.items()? {
+                menu.remove(&item)?;
+            }
+            let files = self.files();
+            for (i, file) in files.iter().enumerate() {
+                let item = MenuItemBuilder::with_id(format!("{}{}", RECENT_MENU_PREFIX, i), file).build(app_handle)?;
+                menu.append(&item)?;
+            }
+            menu.append(&PredefinedMenuItem::separator(app_handle)?)?;
+            let clear = MenuItemBuilder::with_id(CLEAR_RECENT_MENU_ID, "Clear Recent")
+                .enabled(!files.is_empty())
+                .build(app_handle)?;
+            menu.append(&clear)
+        })();
+        if let Err(e) = rebuilt {
+            eprintln!("Failed to update the Open Recent menu: {}", e);
+        }
+    }
+}
+
+/// Add `path` to the recent list, if the list has been set up
+pub fn remember_file(app_handle: &AppHandle, path: &str) {
+    if let Some(recent) = app_handle.try_state::<RecentFiles>() {
+        recent.add(app_handle, path);
+    }
+}
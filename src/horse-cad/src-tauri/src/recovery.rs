@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Mutex,
+};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::emit_log;
+use crate::export::sanitize_file_stem;
+
+/// Seconds between autosaves unless the settings say otherwise
+pub const DEFAULT_AUTOSAVE_INTERVAL_SECS: u64 = 30;
+
+/// Snapshot of an editor buffer with unsaved changes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecoveredDocument {
+    pub document_id: String,
+    /// File the buffer was opened from, if it has been saved before
+    pub path: Option<String>,
+    pub content: String,
+    /// RFC 3339 time the snapshot was taken
+    pub saved_at: String,
+}
+
+struct Buffer {
+    document: RecoveredDocument,
+    /// Whether this version has been written to disk yet
+    written: bool,
+}
+
+/// Autosaved copies of unsaved buffers, kept in case the app crashes
+///
+/// Each run writes its snapshots to a folder of its own under the recovery
+/// folder, and removes that folder when it exits cleanly.  Folders left
+/// behind by earlier runs are what a crash left, and are offered back
+/// through `get_recovered_documents`.
+pub struct Recovery {
+    session: Option<PathBuf>,
+    buffers: Mutex<HashMap<String, Buffer>>,
+    /// Snapshots from earlier runs, with the folders they were read from
+    recovered: Mutex<Vec<(PathBuf, RecoveredDocument)>>,
+    /// Seconds between autosaves, or 0 for none
+    interval_secs: AtomicU64,
+}
+
+impl Recovery {
+    /// Start a session under `root`, reading what earlier runs left there
+    pub fn new(root: Option<PathBuf>) -> Self {
+        let mut recovered = Vec::new();
+        if let Some(sessions) = root.as_ref().and_then(|root| std::fs::read_dir(root).ok()) {
+            for session in sessions.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+                let Ok(files) = std::fs::read_dir(&session) else { continue };
+                for file in files.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+                    let document = std::fs::read_to_string(&file)
+                        .ok()
+                        .and_then(|text| serde_json::from_str::<RecoveredDocument>(&text).ok());
+                    if let Some(document) = document {
+                        recovered.push((session.clone(), document));
+                    }
+                }
+            }
+        }
+        recovered.sort_by(|a, b| b.1.saved_at.cmp(&a.1.saved_at));
+
+        let session_name = format!("{}-{}", chrono::Utc::now().format("%Y%m%dT%H%M%S"), std::process::id());
+        Recovery {
+            session: root.map(|root| root.join(session_name)),
+            buffers: Mutex::new(HashMap::new()),
+            recovered: Mutex::new(recovered),
+            interval_secs: AtomicU64::new(DEFAULT_AUTOSAVE_INTERVAL_SECS),
+        }
+    }
+
+    pub fn set_interval(&self, seconds: u64) {
+        self.interval_secs.store(seconds, Ordering::Relaxed);
+    }
+
+    /// Keep the latest contents of a buffer with unsaved changes, for the next autosave
+    pub fn update(&self, document_id: &str, path: Option<String>, content: String) {
+        let document = RecoveredDocument {
+            document_id: document_id.to_string(),
+            path,
+            content,
+            saved_at: chrono::Utc::now().to_rfc3339(),
+        };
+        let buffer = Buffer { document, written: false };
+        self.buffers.lock().unwrap_or_else(|e| e.into_inner()).insert(document_id.to_string(), buffer);
+    }
+
+    /// Latest unsaved contents of a document, if it has changes that haven't been saved
+    pub fn unsaved(&self, document_id: &str) -> Option<String> {
+        let buffers = self.buffers.lock().unwrap_or_else(|e| e.into_inner());
+        buffers.get(document_id).map(|buffer| buffer.document.content.clone())
+    }
+
+    /// Forget the snapshot of a document that has been saved or closed
+    pub fn clear(&self, document_id: &str) {
+        self.buffers.lock().unwrap_or_else(|e| e.into_inner()).remove(document_id);
+        if let Some(file) = self.snapshot_path(document_id) {
+            let _ = std::fs::remove_file(file);
+        }
+    }
+
+    /// Forget the snapshots of any document saved to `path`
+    pub fn clear_path(&self, path: &str) {
+        let saved: Vec<String> = self
+            .buffers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .values()
+            .filter(|buffer| buffer.document.path.as_deref() == Some(path))
+            .map(|buffer| buffer.document.document_id.clone())
+            .collect();
+        for document_id in saved {
+            self.clear(&document_id);
+        }
+    }
+
+    /// Write buffers that changed since the last autosave, returning how many were written
+    pub fn flush(&self) -> Result<usize, String> {
+        let Some(session) = &self.session else { return Ok(0) };
+        let mut buffers = self.buffers.lock().unwrap_or_else(|e| e.into_inner());
+        let mut written = 0;
+        for buffer in buffers.values_mut().filter(|buffer| !buffer.written) {
+            std::fs::create_dir_all(session)
+                .map_err(|e| format!("Failed to create recovery folder {}: {}", session.display(), e))?;
+            let file = session.join(snapshot_name(&buffer.document.document_id));
+            let text = serde_json::to_string(&buffer.document).map_err(|e| e.to_string())?;
+            std::fs::write(&file, text).map_err(|e| format!("Failed to autosave {}: {}", file.display(), e))?;
+            buffer.written = true;
+            written += 1;
+        }
+        Ok(written)
+    }
+
+    /// Snapshots left by runs that didn't exit cleanly, newest first
+    pub fn recovered(&self) -> Vec<RecoveredDocument> {
+        self.recovered.lock().unwrap_or_else(|e| e.into_inner()).iter().map(|(_, document)| document.clone()).collect()
+    }
+
+    /// Delete the snapshots left by earlier runs, once they've been restored or declined
+    pub fn discard_recovered(&self) {
+        let mut recovered = self.recovered.lock().unwrap_or_else(|e| e.into_inner());
+        let mut sessions: Vec<PathBuf> = recovered.drain(..).map(|(session, _)| session).collect();
+        sessions.sort();
+        sessions.dedup();
+        for session in sessions {
+            let _ = std::fs::remove_dir_all(session);
+        }
+    }
+
+    /// Remove this run's snapshots, as it is exiting cleanly
+    pub fn end_session(&self) {
+        if let Some(session) = &self.session {
+            let _ = std::fs::remove_dir_all(session);
+        }
+    }
+
+    fn snapshot_path(&self, document_id: &str) -> Option<PathBuf> {
+        self.session.as_ref().map(|session| session.join(snapshot_name(document_id)))
+    }
+}
+
+fn snapshot_name(document_id: &str) -> String {
+    format!("{}.json", sanitize_file_stem(document_id))
+}
+
+/// Write unsaved buffers to the recovery folder at the autosave interval
+///
+/// The interval is checked every second, so changing it in the settings
+/// takes effect straight away.
+pub fn spawn_autosave(app_handle: AppHandle) {
+    std::thread::spawn(move || {
+        let mut last = Instant::now();
+        loop {
+            std::thread::sleep(Duration::from_secs(1));
+            let recovery = app_handle.state::<Recovery>();
+            let interval = recovery.interval_secs.load(Ordering::Relaxed);
+            if interval == 0 || last.elapsed() < Duration::from_secs(interval) {
+                continue;
+            }
+            last = Instant::now();
+            autosave(&app_handle, &recovery);
+        }
+    });
+}
+
+fn autosave(app_handle: &AppHandle, recovery: &Recovery) {
+    match recovery.flush() {
+        Ok(0) => {}
+        Ok(written) => {
+            let message = format!("Autosaved {} unsaved document{}", written, if written == 1 { "" } else { "s" });
+            emit_log(app_handle, "debug", &message, Some("File"));
+        }
+        Err(e) => emit_log(app_handle, "warn", &e, Some("File")),
+    }
+}
@@ -0,0 +1,107 @@
+use std::io::Cursor;
+
+use fidget::eval::Function;
+use fidget::render::{effects, RenderHints, ThreadPool, View3, VoxelRenderConfig, VoxelSize};
+use fidget::shape::Shape;
+use image::{ImageFormat, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+use crate::mesh::bounds::Bounds;
+
+/// Largest width or height an offscreen render may have, in pixels
+pub const MAX_RENDER_SIZE: u32 = 4096;
+
+/// Most frames a turntable may have
+pub const MAX_TURNTABLE_FRAMES: u32 = 720;
+
+/// How `export_turntable` writes its frames
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TurntableFormat {
+    /// One looping animated GIF
+    #[default]
+    Gif,
+    /// A folder of numbered PNGs, for turning into video elsewhere
+    Frames,
+}
+
+/// Camera for an offscreen render, orbiting the center of the shape
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RenderCamera {
+    /// Turn about the model's Z axis, in degrees
+    pub yaw: f32,
+    /// Tilt away from looking straight down the Z axis, in degrees
+    pub pitch: f32,
+    /// Magnification, with 1 fitting the whole shape in the frame
+    pub zoom: f32,
+    /// Shade with ambient occlusion, which is slower but shows depth better
+    pub ambient_occlusion: bool,
+}
+
+impl Default for RenderCamera {
+    fn default() -> Self {
+        RenderCamera { yaw: 30.0, pitch: -60.0, zoom: 1.0, ambient_occlusion: true }
+    }
+}
+
+impl RenderCamera {
+    /// View looking at `bounds`, or at the default ±1 region if the shape has none
+    ///
+    /// The frame fits the bounding cube's diagonal, so the shape stays in
+    /// view from any angle.
+    pub fn view(&self, bounds: Option<Bounds>) -> View3 {
+        let (center, half_extent) = match bounds {
+            Some(bounds) => (bounds.center(), bounds.half_extent()),
+            None => ([0.0; 3], 1.0),
+        };
+        View3::from_components(
+            nalgebra::Vector3::new(center[0], center[1], center[2]),
+            half_extent * 3f32.sqrt() / self.zoom.max(f32::EPSILON),
+            self.yaw.to_radians(),
+            self.pitch.to_radians(),
+        )
+    }
+}
+
+/// Check a requested render size
+pub fn check_render_size(width: u32, height: u32) -> Result<(), String> {
+    if width == 0 || height == 0 || width > MAX_RENDER_SIZE || height > MAX_RENDER_SIZE {
+        return Err(format!("Render size must be between 1 and {} pixels on each side", MAX_RENDER_SIZE));
+    }
+    Ok(())
+}
+
+/// Ray-cast `shape` into a shaded image, with empty pixels left transparent
+///
+/// Returns `None` if rendering was cancelled.
+pub fn render_shape<F: Function + RenderHints>(
+    shape: Shape<F>,
+    view: View3,
+    width: u32,
+    height: u32,
+    ambient_occlusion: bool,
+    threads: Option<&ThreadPool>,
+) -> Option<RgbaImage> {
+    let config = VoxelRenderConfig {
+        image_size: VoxelSize::new(width, height, width.max(height)),
+        view,
+        tile_sizes: F::tile_sizes_3d(),
+        threads,
+        ..Default::default()
+    };
+    let geometry = effects::denoise_normals(&config.run(shape)?, threads);
+    let shaded = effects::apply_shading(&geometry, ambient_occlusion, threads);
+    Some(RgbaImage::from_fn(width, height, |x, y| {
+        let (row, col) = (y as usize, x as usize);
+        let [r, g, b] = shaded[(row, col)];
+        let alpha = if geometry[(row, col)].depth > 0 { u8::MAX } else { 0 };
+        image::Rgba([r, g, b, alpha])
+    }))
+}
+
+pub fn encode_png(image: &RgbaImage) -> Result<Vec<u8>, String> {
+    let mut data = Vec::new();
+    image.write_to(&mut Cursor::new(&mut data), ImageFormat::Png).map_err(|e| format!("PNG encoding failed: {}", e))?;
+    Ok(data)
+}
@@ -0,0 +1,171 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::plugins::PluginFunction;
+
+/// A function callable from scripts, as shown in the API browser
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiFunction {
+    pub name: String,
+    pub arity: usize,
+    /// Call signature, for functions registered by horseCAD
+    pub signature: Option<String>,
+    /// One-line description, for functions registered by horseCAD
+    pub doc: Option<String>,
+    /// "horsecad", "fidget" or "plugin"
+    pub source: String,
+}
+
+/// Signature and description of each function registered by `compile_rhai_script`
+///
+/// Keep this in sync when adding script functions.
+const HORSECAD_FUNCTIONS: &[(&str, usize, &str, &str)] = &[
+    ("draw", 1, "draw(tree)", "Add a shape to the scene"),
+    ("draw", 2, "draw(tree, color)", "Add a shape with a #rrggbb color"),
+    ("draw_named", 2, "draw_named(name, tree)", "Add a named part, exported separately by Export Parts"),
+    ("draw_named", 3, "draw_named(name, tree, color)", "Add a named part with a #rrggbb color"),
+    ("draw2d", 1, "draw2d(tree)", "Add a shape in 2D mode: its outline where it crosses z = 0 is traced instead of meshed"),
+    ("draw2d", 2, "draw2d(tree, color)", "Add a shape in 2D mode with a #rrggbb color"),
+    ("material", 2, "material(tree, name)", "Tag part of the model with a material, exported as its own object by Export Materials"),
+    ("draw", 1, "draw(assembly)", "Add every part of an assembly as a named part, in its placed position"),
+    ("assembly", 0, "assembly()", "Empty assembly of parts, each with its own position"),
+    ("add", 5, "assembly.add(name, part, [x, y, z], [rx, ry, rz])", "Place a part in an assembly, turned about X, then Y, then Z in degrees, then moved"),
+    ("add", 6, "assembly.add(name, part, [x, y, z], [rx, ry, rz], color)", "Place a part with a #rrggbb color"),
+    ("part", 2, "assembly.part(name)", "A part of an assembly in its placed position, e.g. to intersect with another to check the fit"),
+    ("explode", 2, "explode(assembly, factor)", "An assembly with every part's translation scaled by factor, for an exploded view"),
+    ("place", 3, "place(part, [x, y, z], [rx, ry, rz])", "A shape turned about X, then Y, then Z in degrees, then moved"),
+    ("set_scale", 1, "set_scale(scale)", "Scale the meshing region, in model units"),
+    ("set_units", 1, "set_units(unit)", "Declare the unit model coordinates are in: mm, cm, m, um, in or ft; exports are scaled to match"),
+    ("set_bounds", 2, "set_bounds([x, y, z], [x, y, z])", "Mesh only inside this box"),
+    ("param", 2, "param(name, default)", "Declare a numeric parameter shown in the UI"),
+    ("param", 4, "param(name, default, min, max)", "Declare a numeric parameter with a range"),
+    ("param_tree", 2, "param_tree(name, default)", "Declare a parameter usable in tree math; changing it skips re-running the script"),
+    ("param_tree", 4, "param_tree(name, default, min, max)", "Declare a tree parameter with a range"),
+    ("assert_volume_between", 2, "assert_volume_between(min, max)", "Fail the compile's checks unless the meshed volume is in this range"),
+    ("assert_bounds_within", 1, "assert_bounds_within([[x, y, z], [x, y, z]])", "Fail the compile's checks if the shape reaches outside this box"),
+    ("assert_bounds_within", 2, "assert_bounds_within([x, y, z], [x, y, z])", "Fail the compile's checks if the shape reaches outside this box"),
+    ("assert_point_inside", 3, "assert_point_inside(x, y, z)", "Fail the compile's checks unless this point is inside the shape"),
+    ("rounded_box", 2, "rounded_box([x, y, z], radius)", "Centered box with rounded edges"),
+    ("torus", 2, "torus(major, minor)", "Ring around the Z axis"),
+    ("capsule", 3, "capsule([x, y, z], [x, y, z], radius)", "Rounded rod between two points"),
+    ("cone", 2, "cone(radius, height)", "Cone along Z with its base on z = 0"),
+    ("hex_prism", 2, "hex_prism(radius, height)", "Hexagonal prism along Z from z = 0, radius to the corners"),
+    ("countersunk_hole", 3, "countersunk_hole(diameter, depth, head_diameter)", "Cutter for a flat-head screw, surface at z = 0"),
+    ("honeycomb_panel", 3, "honeycomb_panel([x, y, z], cell, wall)", "Plate from z = 0 with hexagonal cut-outs"),
+    ("screw_thread", 4, "screw_thread(diameter, pitch, length, internal)", "ISO metric thread along Z from z = 0; internal gives a cutter for a threaded hole"),
+    ("spur_gear", 4, "spur_gear(module, teeth, thickness, pressure_angle)", "Involute spur gear along Z from z = 0, pressure angle in degrees"),
+    ("circle2d", 1, "circle2d(radius)", "2D disc centered on the origin"),
+    ("circle2d", 2, "circle2d([x, y], radius)", "2D disc"),
+    ("rect2d", 1, "rect2d([width, height])", "2D rectangle centered on the origin"),
+    ("polygon2d", 1, "polygon2d([[x, y], ...])", "Closed 2D polygon through the points"),
+    ("offset2d", 2, "offset2d(shape2d, distance)", "Grow a 2D shape outwards, or shrink it with a negative distance"),
+    ("union2d", 2, "union2d(a, b)", "2D shape covering both a and b"),
+    ("difference2d", 2, "difference2d(a, b)", "2D shape a with b cut away"),
+    ("text", 3, "text(text, font, size)", "2D outline of text in a .ttf/.otf font, path relative to the document"),
+    ("import_svg", 1, "import_svg(path)", "2D shape from the paths in an SVG file, path relative to the document"),
+    ("import_dxf", 1, "import_dxf(path)", "2D shape from the lines, arcs and circles in a DXF file, path relative to the document"),
+    ("heightmap", 4, "heightmap(path, width, depth, max_height)", "Solid under a grayscale PNG, white at max_height, path relative to the document"),
+    ("import_stl", 1, "import_stl(path)", "Approximate solid from a closed STL mesh, path relative to the document"),
+    ("import_shape", 1, "import_shape(path)", "Exact shape saved from another script as a .vm file, path relative to the document"),
+    ("import_shape", 2, "import_shape(path, part)", "One named part of a shape saved as a .vm file"),
+    ("extrude", 2, "extrude(shape2d, height)", "Sweep a 2D shape along Z from z = 0"),
+    ("loft", 3, "loft(profile_a, profile_b, height)", "Blend from one 2D shape at z = 0 to another at z = height"),
+    ("sweep", 2, "sweep(profile2d, [[x, y, z], ...])", "Sweep a 2D shape along a path; sketch Y is up, X to the left"),
+    ("bezier_path", 2, "bezier_path([[x, y, z], ...], segments)", "Points along a Bezier curve through its end points, for sweep"),
+    ("revolve", 2, "revolve(shape2d, angle)", "Spin a 2D shape around the Z axis; sketch X is the radius, Y the height"),
+    ("twist", 2, "twist(shape, degrees_per_unit)", "Turn slices about the Z axis in proportion to their height"),
+    ("bend", 2, "bend(shape, radius)", "Wrap the X axis around a circle; positive radii curl upwards"),
+    ("taper", 2, "taper(shape, factor)", "Scale slices in X and Y by 1 + factor * z"),
+    ("array_linear", 3, "array_linear(shape, count, [x, y, z])", "Copies of a shape in a row, each offset by the spacing"),
+    ("array_polar", 3, "array_polar(shape, count, radius)", "Copies of a shape moved out to radius and spaced around the Z axis"),
+    ("mirror_x", 1, "mirror_x(shape)", "A shape together with its mirror image across x = 0"),
+    ("mirror_y", 1, "mirror_y(shape)", "A shape together with its mirror image across y = 0"),
+    ("mirror_z", 1, "mirror_z(shape)", "A shape together with its mirror image across z = 0"),
+    ("offset", 2, "offset(shape, distance)", "Grow a shape outwards, or shrink it with a negative distance"),
+    ("hull", 1, "hull([a, b, ...])", "Approximate convex hull of an array of shapes, as a polyhedron of 100 faces"),
+    ("hull", 2, "hull(a, b)", "Approximate convex hull of two shapes; also takes three or four"),
+    ("shell", 2, "shell(shape, thickness)", "Hollow a shape, keeping a wall of the given thickness inside its surface"),
+    ("smooth_union", 3, "smooth_union(a, b, k)", "Union with a fillet about k wide where the shapes meet"),
+    ("smooth_difference", 3, "smooth_difference(a, b, k)", "Cut b from a, rounding the cut edges by about k"),
+    ("smooth_intersection", 3, "smooth_intersection(a, b, k)", "Intersection with edges rounded by about k"),
+    ("morph", 3, "morph(a, b, t)", "Blend from shape a at t = 0 to shape b at t = 1"),
+    ("symmetry", 2, "symmetry(shape, plane)", "Reflect the positive half of a shape across \"yz\", \"xz\" or \"xy\", evaluating only that half"),
+    ("gyroid", 2, "gyroid(period, thickness)", "Gyroid sheet filling space, for use with infill"),
+    ("lattice", 2, "lattice(cell, thickness)", "Cubic lattice of round struts filling space, for use with infill"),
+    ("infill", 2, "infill(solid, pattern)", "A pattern cut down to the inside of a solid"),
+    ("infill", 3, "infill(solid, pattern, wall)", "A solid with a skin of the given thickness, filled with a pattern"),
+    ("displace", 3, "displace(shape, noise_scale, amplitude)", "Roughen a surface with smooth noise; features about noise_scale apart, up to amplitude high"),
+    ("voronoi", 3, "voronoi(shape, cell_size, wall_thickness)", "The walls of a 3D Voronoi foam, cut down to a shape"),
+];
+
+/// Every function available to scripts, sorted by name and arity
+///
+/// fidget's functions are read from its engine, so they're listed without
+/// docs; property accessors and operators are left out.  Functions from
+/// `plugins` are listed with the description they registered.
+pub fn api_reference(plugins: &[PluginFunction]) -> Vec<ApiFunction> {
+    let engine = fidget::rhai::engine();
+    let fidget_functions: BTreeSet<(String, usize)> = engine
+        .gen_fn_signatures(false)
+        .iter()
+        .filter_map(|signature| name_and_arity(signature))
+        .filter(|(name, _)| name.chars().all(|c| c.is_alphanumeric() || c == '_'))
+        .collect();
+
+    let mut functions: Vec<ApiFunction> = HORSECAD_FUNCTIONS
+        .iter()
+        .map(|&(name, arity, signature, doc)| ApiFunction {
+            name: name.to_string(),
+            arity,
+            signature: Some(signature.to_string()),
+            doc: Some(doc.to_string()),
+            source: "horsecad".to_string(),
+        })
+        .collect();
+    functions.extend(
+        fidget_functions
+            .into_iter()
+            .filter(|(name, arity)| !HORSECAD_FUNCTIONS.iter().any(|&(n, a, ..)| n == name && a == *arity))
+            .map(|(name, arity)| ApiFunction { name, arity, signature: None, doc: None, source: "fidget".to_string() }),
+    );
+    functions.extend(plugins.iter().map(|function| ApiFunction {
+        name: function.name.clone(),
+        arity: function.arg_count,
+        signature: None,
+        doc: function.doc.clone(),
+        source: "plugin".to_string(),
+    }));
+    functions.sort_by(|a, b| (&a.name, a.arity).cmp(&(&b.name, b.arity)));
+    functions
+}
+
+/// fidget's functions that return a shape, by name
+///
+/// Math functions like `sqrt` that Rhai also has for plain numbers are
+/// left out, since their results often aren't shapes.
+pub fn shape_functions() -> BTreeSet<String> {
+    let names = |signatures: Vec<String>| -> BTreeSet<String> {
+        signatures.iter().filter_map(|signature| name_and_arity(signature)).map(|(name, _)| name).collect()
+    };
+    let numeric = names(rhai::Engine::new().gen_fn_signatures(true));
+    let returning_trees = fidget::rhai::engine()
+        .gen_fn_signatures(false)
+        .into_iter()
+        .filter(|signature| signature.split(" -> ").nth(1).is_some_and(|ret| ret.contains("tree::Tree")))
+        .collect();
+    names(returning_trees)
+        .into_iter()
+        .filter(|name| name.chars().all(|c| c.is_alphanumeric() || c == '_') && !numeric.contains(name))
+        .collect()
+}
+
+/// Split a Rhai signature like `move(_: Dynamic, _: map) -> Tree` into name and arity
+///
+/// Parameter types can contain commas (e.g. `Result<Tree, Box<..>>`), but
+/// every parameter starts with `_: `, so those are counted instead.
+fn name_and_arity(signature: &str) -> Option<(String, usize)> {
+    let (name, rest) = signature.split_once('(')?;
+    let params = rest.split(" -> ").next().unwrap_or(rest);
+    Some((name.to_string(), params.matches("_: ").count()))
+}
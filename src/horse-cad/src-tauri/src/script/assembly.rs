@@ -0,0 +1,137 @@
+use fidget::{context::Tree, rhai::FromDynamic};
+use nalgebra::{Isometry3, Translation3, UnitQuaternion};
+use rhai::{Dynamic, Engine, EvalAltResult, NativeCallContext};
+
+use crate::mesh::color::parse_hex_color;
+use crate::{number_from_dynamic, vec3_from_dynamic};
+
+type ShapeResult = Result<Tree, Box<EvalAltResult>>;
+
+/// A part of an assembly, with where it sits
+#[derive(Clone)]
+pub struct Part {
+    pub name: String,
+    /// The part as modeled, before it's placed
+    pub tree: Tree,
+    pub color: Option<String>,
+    pub translation: [f32; 3],
+    /// Turns about X, then Y, then Z, in degrees
+    pub rotation: [f32; 3],
+}
+
+impl Part {
+    /// The part moved into its place in the assembly
+    pub fn placed(&self) -> Tree {
+        place(&self.tree, self.translation, self.rotation)
+    }
+}
+
+/// Parts positioned together in one document, drawn as separate named parts
+#[derive(Clone, Default)]
+pub struct Assembly {
+    pub parts: Vec<Part>,
+}
+
+/// Register `assembly`, `place` and the `Assembly` methods on `engine`
+///
+/// `draw(assembly)` is registered with the other draw functions, since it
+/// adds to the same list of parts.
+pub fn register(engine: &mut Engine) {
+    engine.register_type_with_name::<Assembly>("Assembly");
+    engine.register_fn("assembly", Assembly::default);
+    engine.register_fn(
+        "place",
+        |ctx: NativeCallContext, part: Dynamic, translation: Dynamic, rotation: Dynamic| -> ShapeResult {
+            let translation = vec3_from_dynamic("place translation", translation)?;
+            let rotation = vec3_from_dynamic("place rotation", rotation)?;
+            Ok(place(&Tree::from_dynamic(&ctx, part, None)?, translation, rotation))
+        },
+    );
+    engine.register_fn(
+        "add",
+        |ctx: NativeCallContext,
+         assembly: &mut Assembly,
+         name: &str,
+         part: Dynamic,
+         translation: Dynamic,
+         rotation: Dynamic|
+         -> Result<(), Box<EvalAltResult>> { add(&ctx, assembly, name, part, translation, rotation, None) },
+    );
+    engine.register_fn(
+        "add",
+        |ctx: NativeCallContext,
+         assembly: &mut Assembly,
+         name: &str,
+         part: Dynamic,
+         translation: Dynamic,
+         rotation: Dynamic,
+         color: &str|
+         -> Result<(), Box<EvalAltResult>> { add(&ctx, assembly, name, part, translation, rotation, Some(color)) },
+    );
+    engine.register_fn("part", |assembly: &mut Assembly, name: &str| -> ShapeResult {
+        match assembly.parts.iter().find(|p| p.name == name) {
+            Some(part) => Ok(part.placed()),
+            None => Err(format!("assembly has no part named '{}'", name).into()),
+        }
+    });
+    engine.register_fn("explode", |assembly: Assembly, factor: Dynamic| explode(assembly, &factor));
+    engine.register_get("len", |assembly: &mut Assembly| assembly.parts.len() as i64);
+}
+
+/// Move `tree` by turning it about X, then Y, then Z, by `rotation` degrees, then shifting it by `translation`
+///
+/// Turns are about the origin, so model each part around its own origin
+/// and let the translation put it in place.
+pub fn place(tree: &Tree, translation: [f32; 3], rotation: [f32; 3]) -> Tree {
+    let [rx, ry, rz] = rotation.map(|d| (d as f64).to_radians());
+    let [tx, ty, tz] = translation.map(f64::from);
+    let placement = Isometry3::from_parts(Translation3::new(tx, ty, tz), UnitQuaternion::from_euler_angles(rx, ry, rz));
+    // The field is remapped with the inverse: a point of the placed part is
+    // looked up where it came from
+    tree.remap_affine(nalgebra::convert(placement.inverse()))
+}
+
+/// `assembly.add(name, part, translation, rotation[, color])`: add a part at its place in the assembly
+#[allow(clippy::too_many_arguments)]
+fn add(
+    ctx: &NativeCallContext,
+    assembly: &mut Assembly,
+    name: &str,
+    part: Dynamic,
+    translation: Dynamic,
+    rotation: Dynamic,
+    color: Option<&str>,
+) -> Result<(), Box<EvalAltResult>> {
+    if name.trim().is_empty() {
+        return Err("part name must not be empty".into());
+    }
+    if assembly.parts.iter().any(|p| p.name == name) {
+        return Err(format!("assembly already has a part named '{}'", name).into());
+    }
+    if let Some(color) = color {
+        parse_hex_color(color)?;
+    }
+    assembly.parts.push(Part {
+        name: name.to_string(),
+        tree: Tree::from_dynamic(ctx, part, None)?,
+        color: color.map(str::to_string),
+        translation: vec3_from_dynamic(&format!("translation of part '{}'", name), translation)?,
+        rotation: vec3_from_dynamic(&format!("rotation of part '{}'", name), rotation)?,
+    });
+    Ok(())
+}
+
+/// `explode(assembly, factor)`: the assembly with every part's translation scaled by `factor`
+///
+/// Parts placed away from the origin move further out with factors above
+/// one, pulling the assembly apart along the directions it was put together.
+fn explode(mut assembly: Assembly, factor: &Dynamic) -> Result<Assembly, Box<EvalAltResult>> {
+    let factor = number_from_dynamic("explode factor", factor)? as f32;
+    if !factor.is_finite() || factor < 0.0 {
+        return Err("explode factor can't be negative".into());
+    }
+    for part in &mut assembly.parts {
+        part.translation = part.translation.map(|t| t * factor);
+    }
+    Ok(assembly)
+}
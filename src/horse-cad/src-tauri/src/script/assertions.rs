@@ -0,0 +1,194 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use fidget::{shape::EzShape, vm::VmShape};
+use rhai::{Dynamic, Engine, EvalAltResult, NativeCallContext, Position};
+use serde::{Deserialize, Serialize};
+
+use super::diagnostics::Diagnostic;
+use crate::mesh::bounds::Bounds;
+use crate::mesh::measure::MeshMeasurements;
+use crate::{number_from_dynamic, vec3_from_dynamic};
+
+/// Slack allowed past an asserted box, as a share of its largest side
+///
+/// Mesh vertices sit on the surface only to within float rounding, so a
+/// part exactly filling its box would otherwise fail.
+const BOUNDS_TOLERANCE: f32 = 1e-4;
+
+/// Something a script asserts about its shape, checked once it's meshed
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Assertion {
+    VolumeBetween { min: f64, max: f64 },
+    BoundsWithin { bounds: Bounds },
+    PointInside { point: [f32; 3] },
+}
+
+impl Assertion {
+    /// Name of the script function that makes this assertion
+    pub fn function(&self) -> &'static str {
+        match self {
+            Assertion::VolumeBetween { .. } => "assert_volume_between",
+            Assertion::BoundsWithin { .. } => "assert_bounds_within",
+            Assertion::PointInside { .. } => "assert_point_inside",
+        }
+    }
+}
+
+/// An assertion and where the script made it
+#[derive(Debug, Clone, Copy)]
+pub struct DeclaredAssertion {
+    pub assertion: Assertion,
+    pub position: Position,
+}
+
+/// How one assertion fared against the compiled shape
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssertionResult {
+    pub assertion: Assertion,
+    pub passed: bool,
+    /// What was measured, and what was expected if it failed
+    pub message: String,
+    /// 1-based line of the call, if known
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+}
+
+impl AssertionResult {
+    /// Editor squiggle for a failed assertion
+    pub fn diagnostic(&self) -> Diagnostic {
+        Diagnostic {
+            line: self.line,
+            column: self.column,
+            length: self.assertion.function().len(),
+            message: self.message.clone(),
+            severity: "error".to_string(),
+        }
+    }
+}
+
+/// Register `assert_volume_between`, `assert_bounds_within` and `assert_point_inside` on `engine`
+///
+/// The calls only record their assertions into `declared`; the shape isn't
+/// known until the script has finished, so they're checked after meshing.
+pub fn register(engine: &mut Engine, declared: Arc<Mutex<Vec<DeclaredAssertion>>>) {
+    let record = move |ctx: &NativeCallContext, assertion: Assertion| {
+        let entry = DeclaredAssertion { assertion, position: ctx.position() };
+        declared.lock().unwrap_or_else(|e| e.into_inner()).push(entry);
+    };
+
+    let push = record.clone();
+    engine.register_fn(
+        "assert_volume_between",
+        move |ctx: NativeCallContext, min: Dynamic, max: Dynamic| -> Result<(), Box<EvalAltResult>> {
+            let min = number_from_dynamic("assert_volume_between min", &min)?;
+            let max = number_from_dynamic("assert_volume_between max", &max)?;
+            if min > max {
+                return Err("assert_volume_between min must not be more than max".into());
+            }
+            push(&ctx, Assertion::VolumeBetween { min, max });
+            Ok(())
+        },
+    );
+    let push = record.clone();
+    engine.register_fn(
+        "assert_bounds_within",
+        move |ctx: NativeCallContext, min: Dynamic, max: Dynamic| -> Result<(), Box<EvalAltResult>> {
+            push(&ctx, Assertion::BoundsWithin { bounds: box_from_corners(min, max)? });
+            Ok(())
+        },
+    );
+    let push = record.clone();
+    engine.register_fn(
+        "assert_bounds_within",
+        move |ctx: NativeCallContext, corners: Dynamic| -> Result<(), Box<EvalAltResult>> {
+            let corners = match corners.into_array() {
+                Ok(corners) if corners.len() == 2 => corners,
+                _ => return Err("assert_bounds_within box must be [[x, y, z], [x, y, z]]".into()),
+            };
+            let [min, max]: [Dynamic; 2] = corners.try_into().unwrap();
+            push(&ctx, Assertion::BoundsWithin { bounds: box_from_corners(min, max)? });
+            Ok(())
+        },
+    );
+    engine.register_fn(
+        "assert_point_inside",
+        move |ctx: NativeCallContext, x: Dynamic, y: Dynamic, z: Dynamic| -> Result<(), Box<EvalAltResult>> {
+            let point = [("x", x), ("y", y), ("z", z)]
+                .map(|(axis, v)| number_from_dynamic(&format!("assert_point_inside {}", axis), &v).map(|v| v as f32));
+            let [x, y, z] = point;
+            record(&ctx, Assertion::PointInside { point: [x?, y?, z?] });
+            Ok(())
+        },
+    );
+}
+
+fn box_from_corners(min: Dynamic, max: Dynamic) -> Result<Bounds, Box<EvalAltResult>> {
+    let min = vec3_from_dynamic("assert_bounds_within min", min)?;
+    let max = vec3_from_dynamic("assert_bounds_within max", max)?;
+    if (0..3).any(|i| min[i] > max[i]) {
+        return Err("assert_bounds_within min must not be more than max on any axis".into());
+    }
+    Ok(Bounds { min, max })
+}
+
+/// Check each assertion against the compiled shape
+///
+/// `measurements` are of the mesh and `shape` is the distance field, both
+/// in model coordinates.  Points are inside where the field is negative.
+pub fn check_assertions(
+    declared: &[DeclaredAssertion],
+    measurements: &MeshMeasurements,
+    shape: &VmShape,
+) -> Result<Vec<AssertionResult>> {
+    let tape = shape.ez_point_tape();
+    let mut eval = VmShape::new_point_eval();
+    let mut results = Vec::with_capacity(declared.len());
+    for DeclaredAssertion { assertion, position } in declared {
+        let (passed, message) = match *assertion {
+            Assertion::VolumeBetween { min, max } => {
+                let volume = measurements.volume;
+                let passed = volume >= min && volume <= max;
+                let message = if passed {
+                    format!("Volume {:.3} is between {} and {}", volume, min, max)
+                } else {
+                    format!("Volume is {:.3}, not between {} and {}", volume, min, max)
+                };
+                (passed, message)
+            }
+            Assertion::BoundsWithin { bounds } => match measurements.bounds {
+                Some(found) => {
+                    let slack = 2.0 * bounds.half_extent() * BOUNDS_TOLERANCE;
+                    let passed = (0..3)
+                        .all(|i| found.min[i] >= bounds.min[i] - slack && found.max[i] <= bounds.max[i] + slack);
+                    let relation = if passed { "is within" } else { "goes outside" };
+                    let message = format!(
+                        "Shape spans {:?} to {:?}, which {} {:?} to {:?}",
+                        found.min, found.max, relation, bounds.min, bounds.max
+                    );
+                    (passed, message)
+                }
+                None => (false, "Mesh is empty, so it has no bounds to check".to_string()),
+            },
+            Assertion::PointInside { point } => {
+                let (distance, _) = eval.eval(&tape, point[0], point[1], point[2])?;
+                let passed = distance < 0.0;
+                let message = if passed {
+                    format!("Point {:?} is inside, {:.4} below the surface", point, -distance)
+                } else {
+                    format!("Point {:?} is outside, {:.4} from the surface", point, distance)
+                };
+                (passed, message)
+            }
+        };
+        results.push(AssertionResult {
+            assertion: *assertion,
+            passed,
+            message,
+            line: position.line(),
+            column: position.position(),
+        });
+    }
+    Ok(results)
+}
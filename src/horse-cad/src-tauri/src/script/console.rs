@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use rhai::{Dynamic, Engine, EvalAltResult, Scope, AST};
+use serde::{Deserialize, Serialize};
+
+/// Variables and functions a console has defined, kept between snippets
+#[derive(Default)]
+pub struct ConsoleSession {
+    scope: Scope<'static>,
+    functions: AST,
+}
+
+/// Outcome of `eval_expression`
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ConsoleOutput {
+    /// Lines from `print()` and `debug()`, in order
+    pub printed: Vec<String>,
+    /// The snippet's value, unless it was `()`
+    pub value: Option<String>,
+    pub type_name: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Managed state holding each document's console session, keyed like the compile queue
+#[derive(Default)]
+pub struct ConsoleState {
+    sessions: Mutex<HashMap<String, Arc<Mutex<ConsoleSession>>>>,
+}
+
+impl ConsoleState {
+    /// The session for `document_id`, started empty on first use
+    pub fn session(&self, document_id: &str) -> Arc<Mutex<ConsoleSession>> {
+        self.sessions.lock().unwrap_or_else(|e| e.into_inner()).entry(document_id.to_string()).or_default().clone()
+    }
+
+    /// Forget everything the console for `document_id` has defined
+    pub fn reset(&self, document_id: &str) {
+        self.sessions.lock().unwrap_or_else(|e| e.into_inner()).remove(document_id);
+    }
+}
+
+/// Run `code` in `session`, keeping any variables it declares and functions it defines
+///
+/// Functions are kept even if the snippet's statements then fail, since
+/// the definitions themselves compiled.
+pub fn eval_snippet(engine: &Engine, session: &mut ConsoleSession, code: &str) -> Result<Dynamic, Box<EvalAltResult>> {
+    let snippet = engine.compile_with_scope(&session.scope, code)?;
+    let ast = session.functions.merge(&snippet);
+    session.functions = ast.clone_functions_only();
+    engine.eval_ast_with_scope(&mut session.scope, &ast)
+}
@@ -0,0 +1,72 @@
+use rhai::{EvalAltResult, Position};
+use serde::{Deserialize, Serialize};
+
+/// A script problem with its location, for editor squiggles
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Diagnostic {
+    /// 1-based line, if the problem has a location
+    pub line: Option<usize>,
+    /// 1-based column, if the problem has a location
+    pub column: Option<usize>,
+    /// Length of the offending token, in characters
+    pub length: usize,
+    pub message: String,
+    /// "error" or "warning"
+    pub severity: String,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>, position: Position, code: &str) -> Self {
+        Diagnostic {
+            line: position.line(),
+            column: position.position(),
+            length: token_length(code, position),
+            message: message.into(),
+            severity: "error".to_string(),
+        }
+    }
+
+    pub fn warning(message: impl Into<String>, position: Position, code: &str) -> Self {
+        Diagnostic { severity: "warning".to_string(), ..Diagnostic::error(message, position, code) }
+    }
+}
+
+/// Build a diagnostic from a script evaluation error
+///
+/// Errors raised inside a function call point at the innermost location, so
+/// the squiggle lands on the failing line rather than the outer call.
+pub fn diagnostic_from_error(error: &anyhow::Error, code: &str) -> Diagnostic {
+    let Some(outer) = error.downcast_ref::<EvalAltResult>() else {
+        return Diagnostic::error(error.to_string(), Position::NONE, code);
+    };
+    let mut err = outer;
+    while let EvalAltResult::ErrorInFunctionCall(_, _, inner, _) | EvalAltResult::ErrorInModule(_, inner, _) = err {
+        if inner.position().is_none() {
+            break;
+        }
+        err = inner;
+    }
+
+    // The top-level message may carry context added by the caller
+    let message = if std::ptr::eq(err, outer) { error.to_string() } else { err.to_string() };
+    // Rhai appends the position to its messages; it's reported separately here
+    let suffix = format!(" ({})", err.position());
+    let message = message.strip_suffix(&suffix).unwrap_or(&message);
+    Diagnostic::error(message, err.position(), code)
+}
+
+/// Length of the identifier or number at `position`, or 1 for anything else
+fn token_length(code: &str, position: Position) -> usize {
+    let (Some(line), Some(column)) = (position.line(), position.position()) else {
+        return 0;
+    };
+    let Some(text) = code.lines().nth(line - 1) else {
+        return 1;
+    };
+    let length = text
+        .chars()
+        .skip(column - 1)
+        .take_while(|c| c.is_alphanumeric() || *c == '_' || *c == '.')
+        .count();
+    length.max(1)
+}
@@ -0,0 +1,136 @@
+use std::f64::consts::TAU;
+
+use dxf::{entities::EntityType, Drawing};
+use fidget::context::Tree;
+use rhai::{Engine, EvalAltResult};
+
+use super::imports::DocumentFiles;
+use super::sketch::contours_sdf;
+
+/// Line segments used for a full circle; arcs get a share of these
+const CIRCLE_SEGMENTS: usize = 48;
+
+/// Largest gap between the ends of entities that are joined into one profile
+const JOIN_TOLERANCE: f64 = 1e-6;
+
+type Contour = Vec<[f64; 2]>;
+
+/// Register `import_dxf` on `engine`, loading files through `files`
+pub fn register(engine: &mut Engine, files: DocumentFiles) {
+    engine.register_fn("import_dxf", move |path: &str| dxf_shape(&files.read(path)?, path));
+}
+
+/// `import_dxf(path)`: 2D shape from the profiles in a DXF drawing
+///
+/// Circles and closed polylines are profiles on their own; lines, arcs and
+/// open polylines are joined end to end into closed profiles.  Profiles are
+/// filled with the even-odd rule, so inner profiles become holes.
+fn dxf_shape(data: &[u8], path: &str) -> Result<Tree, Box<EvalAltResult>> {
+    let drawing =
+        Drawing::load(&mut &data[..]).map_err(|e| format!("failed to parse DXF '{}': {}", path, e))?;
+
+    let mut contours: Vec<Contour> = Vec::new();
+    let mut pieces: Vec<Contour> = Vec::new();
+    for entity in drawing.entities() {
+        match &entity.specific {
+            EntityType::Line(line) => pieces.push(vec![[line.p1.x, line.p1.y], [line.p2.x, line.p2.y]]),
+            EntityType::Circle(circle) => {
+                let mut contour = arc_points([circle.center.x, circle.center.y], circle.radius, 0.0, TAU);
+                contour.pop();
+                contours.push(contour);
+            }
+            EntityType::Arc(arc) => {
+                let start = arc.start_angle.to_radians();
+                let sweep = (arc.end_angle - arc.start_angle).rem_euclid(360.0).to_radians();
+                pieces.push(arc_points([arc.center.x, arc.center.y], arc.radius, start, sweep));
+            }
+            EntityType::LwPolyline(poly) => {
+                let vertices: Vec<_> = poly.vertices.iter().map(|v| ([v.x, v.y], v.bulge)).collect();
+                push_polyline(&vertices, poly.is_closed(), &mut contours, &mut pieces);
+            }
+            EntityType::Polyline(poly) => {
+                let vertices: Vec<_> = poly.vertices().map(|v| ([v.location.x, v.location.y], v.bulge)).collect();
+                push_polyline(&vertices, poly.is_closed(), &mut contours, &mut pieces);
+            }
+            _ => {}
+        }
+    }
+    contours.extend(join_pieces(pieces).map_err(|[x, y]| {
+        format!("DXF '{}' has a profile that isn't closed, near ({:.3}, {:.3})", path, x, y)
+    })?);
+    contours_sdf(&contours).ok_or_else(|| format!("DXF '{}' has no lines, arcs or circles", path).into())
+}
+
+/// Points along a circular arc, from `start` turning by `sweep` radians
+fn arc_points(center: [f64; 2], radius: f64, start: f64, sweep: f64) -> Contour {
+    let steps = ((sweep.abs() / TAU) * CIRCLE_SEGMENTS as f64).ceil().max(1.0) as usize;
+    (0..=steps)
+        .map(|i| {
+            let angle = start + sweep * i as f64 / steps as f64;
+            [center[0] + radius * angle.cos(), center[1] + radius * angle.sin()]
+        })
+        .collect()
+}
+
+/// Flatten a polyline whose segments may bulge into arcs
+///
+/// A vertex's bulge is the tangent of a quarter of the arc's included
+/// angle to the next vertex, positive for counter-clockwise arcs.
+fn push_polyline(vertices: &[([f64; 2], f64)], closed: bool, contours: &mut Vec<Contour>, pieces: &mut Vec<Contour>) {
+    let Some(&(first, _)) = vertices.first() else { return };
+    let mut points = vec![first];
+    let segments = if closed { vertices.len() } else { vertices.len() - 1 };
+    for i in 0..segments {
+        let (p, bulge) = vertices[i];
+        let (q, _) = vertices[(i + 1) % vertices.len()];
+        if bulge == 0.0 {
+            points.push(q);
+            continue;
+        }
+        let sweep = 4.0 * bulge.atan();
+        let (dx, dy) = (q[0] - p[0], q[1] - p[1]);
+        let chord = dx.hypot(dy);
+        if chord == 0.0 {
+            continue;
+        }
+        // The center is off the chord's midpoint, to the left for positive bulges
+        let offset = chord / 2.0 / (sweep / 2.0).tan();
+        let center = [(p[0] + q[0]) / 2.0 - dy / chord * offset, (p[1] + q[1]) / 2.0 + dx / chord * offset];
+        let radius = (p[0] - center[0]).hypot(p[1] - center[1]);
+        let start = (p[1] - center[1]).atan2(p[0] - center[0]);
+        points.extend(arc_points(center, radius, start, sweep).into_iter().skip(1));
+    }
+    if closed {
+        points.pop();
+        contours.push(points);
+    } else {
+        pieces.push(points);
+    }
+}
+
+/// Join open pieces end to end into closed contours
+///
+/// Returns the loose end of the first piece that can't be closed.
+fn join_pieces(mut pieces: Vec<Contour>) -> Result<Vec<Contour>, [f64; 2]> {
+    let near = |a: [f64; 2], b: [f64; 2]| (a[0] - b[0]).hypot(a[1] - b[1]) <= JOIN_TOLERANCE;
+    let mut contours = Vec::new();
+    while let Some(mut contour) = pieces.pop() {
+        loop {
+            let end = *contour.last().unwrap();
+            if contour.len() > 2 && near(end, contour[0]) {
+                contour.pop();
+                break;
+            }
+            let Some(i) = pieces.iter().position(|p| near(p[0], end) || near(*p.last().unwrap(), end)) else {
+                return Err(end);
+            };
+            let mut next = pieces.swap_remove(i);
+            if !near(next[0], end) {
+                next.reverse();
+            }
+            contour.extend(next.into_iter().skip(1));
+        }
+        contours.push(contour);
+    }
+    Ok(contours)
+}
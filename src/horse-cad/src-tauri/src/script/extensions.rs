@@ -0,0 +1,81 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+/// Extension files found by the last scan, by the name scripts import them as
+pub type ExtensionModules = BTreeMap<String, PathBuf>;
+
+/// A `.rhai` file found in the extensions folder
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtensionInfo {
+    /// Name to import it as: `import "<name>" as lib;`
+    pub name: String,
+    pub path: String,
+    /// Syntax error found when the folder was scanned
+    pub error: Option<String>,
+}
+
+/// Shape libraries shared between documents, kept as `.rhai` files in the app config folder
+///
+/// Any script can `import` an extension by its file name without the
+/// extension, as if it were next to the document; a file that really is
+/// next to the document takes precedence.  The folder is only scanned at
+/// startup and by `reload_extensions`, but files are read when imported,
+/// so edits to an existing extension apply on the next compile.
+pub struct Extensions {
+    dir: Option<PathBuf>,
+    modules: Mutex<Arc<ExtensionModules>>,
+}
+
+impl Extensions {
+    /// Scan `dir`, creating it so users have somewhere to put extensions
+    pub fn load(dir: Option<PathBuf>) -> (Self, Vec<ExtensionInfo>) {
+        if let Some(dir) = &dir {
+            if let Err(e) = std::fs::create_dir_all(dir) {
+                eprintln!("Failed to create the extensions folder {}: {}", dir.display(), e);
+            }
+        }
+        let extensions = Extensions { dir, modules: Mutex::new(Arc::default()) };
+        let found = extensions.reload();
+        (extensions, found)
+    }
+
+    pub fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+
+    /// Scan the folder again, replacing the extensions scripts can import
+    pub fn reload(&self) -> Vec<ExtensionInfo> {
+        let mut files: Vec<PathBuf> = self
+            .dir
+            .as_ref()
+            .and_then(|dir| std::fs::read_dir(dir).ok())
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "rhai"))
+            .collect();
+        files.sort();
+
+        let engine = fidget::rhai::engine();
+        let mut modules = ExtensionModules::new();
+        let mut found = Vec::with_capacity(files.len());
+        for path in files {
+            let Some(name) = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()) else { continue };
+            let error = match std::fs::read_to_string(&path) {
+                Ok(code) => engine.compile(&code).err().map(|e| e.to_string()),
+                Err(e) => Some(format!("failed to read: {}", e)),
+            };
+            found.push(ExtensionInfo { name: name.clone(), path: path.to_string_lossy().into_owned(), error });
+            modules.insert(name, path);
+        }
+        *self.modules.lock().unwrap_or_else(|e| e.into_inner()) = Arc::new(modules);
+        found
+    }
+
+    pub fn modules(&self) -> Arc<ExtensionModules> {
+        self.modules.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
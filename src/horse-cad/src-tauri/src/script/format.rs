@@ -0,0 +1,308 @@
+/// Spaces per level of nesting
+const INDENT: &str = "    ";
+
+/// Multi-character operators, longest first so the lexer matches greedily
+const OPERATORS: [&str; 29] = [
+    "**=", "<<=", ">>=", "..=", "==", "!=", "<=", ">=", "&&", "||", "+=", "-=", "*=", "/=", "%=", "|=", "&=", "^=",
+    "**", "<<", ">>", "..", "::", "=>", "?.", "?[", "??", "#{", "->",
+];
+
+/// Words that start or join statements rather than ending a value
+const KEYWORDS: [&str; 22] = [
+    "let", "const", "if", "else", "while", "loop", "do", "until", "for", "in", "fn", "return", "throw", "try", "catch",
+    "switch", "import", "export", "as", "private", "global", "is",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    /// Identifier, keyword or number
+    Word,
+    Str,
+    Comment,
+    Punct,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Token<'a> {
+    kind: Kind,
+    text: &'a str,
+    /// Line breaks between this token and the previous one
+    breaks: usize,
+}
+
+impl Token<'_> {
+    fn is(&self, text: &str) -> bool {
+        self.kind == Kind::Punct && self.text == text
+    }
+
+    fn is_keyword(&self) -> bool {
+        self.kind == Kind::Word && KEYWORDS.contains(&self.text)
+    }
+
+    fn is_closer(&self) -> bool {
+        self.is("}") || self.is(")") || self.is("]")
+    }
+}
+
+/// What an open bracket started
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Open {
+    Block,
+    Map,
+    Switch,
+    /// Parentheses or square brackets
+    Group,
+}
+
+/// Re-indent and re-space Rhai source
+///
+/// Line breaks are kept where the author put them, apart from runs of
+/// blank lines, which become one.  Each line is indented four spaces per
+/// open bracket, plus one level when it continues the previous line's
+/// expression, and tokens are spaced consistently.  A statement that ends
+/// a line without a semicolon gets one, unless it's the last in a block,
+/// where it may be the block's value.  Comments and string contents are
+/// left as written.
+pub fn format_rhai(code: &str) -> String {
+    let tokens = lex(code);
+    let mut lines: Vec<Vec<Token>> = Vec::new();
+    for token in tokens {
+        match lines.last_mut() {
+            Some(line) if token.breaks == 0 => line.push(token),
+            _ => lines.push(vec![token]),
+        }
+    }
+
+    let mut out = String::new();
+    // Open brackets, with the line each was opened on
+    let mut stack: Vec<(Open, usize)> = Vec::new();
+    let mut pending_switch = false;
+    let mut in_params = false;
+    // Last two tokens emitted, for deciding whether a `-` or `|` starts a value
+    let (mut before, mut prev): (Option<Token>, Option<Token>) = (None, None);
+    let mut continues = false;
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 && line[0].breaks > 1 {
+            out.push('\n');
+        }
+        let closers = line.iter().take_while(|t| t.is_closer()).count();
+        let continued = !line[0].is("{") && closers == 0 && (continues || starts_continuation_of(&line[0]));
+        // Brackets opened together, as in `map(|v| {`, only indent once
+        let open = &stack[..stack.len().saturating_sub(closers)];
+        let levels = open.iter().enumerate().filter(|(k, (_, line))| *k == 0 || open[k - 1].1 != *line).count();
+        let depth = levels + usize::from(continued);
+        for _ in 0..depth {
+            out.push_str(INDENT);
+        }
+
+        let mut last_closed = None;
+        let mut text = String::new();
+        for (j, token) in line.iter().enumerate() {
+            if j > 0 && spaced(&line[j - 1], token, before.as_ref(), in_params) {
+                text.push(' ');
+            }
+            if token.is("|") {
+                in_params = !in_params && !prev.as_ref().is_some_and(ends_value);
+            }
+            text.push_str(token.text);
+            match token.text {
+                "switch" if token.kind == Kind::Word => pending_switch = true,
+                _ if token.kind != Kind::Punct => {}
+                "{" => stack.push((if std::mem::take(&mut pending_switch) { Open::Switch } else { Open::Block }, i)),
+                "#{" => stack.push((Open::Map, i)),
+                "(" | "[" | "?[" => stack.push((Open::Group, i)),
+                "}" | ")" | "]" => last_closed = stack.pop().map(|(open, _)| open),
+                _ => {}
+            }
+            before = prev.replace(*token);
+        }
+
+        // Where a missing semicolon would go: before any trailing comment
+        let code_end = line.iter().rposition(|t| t.kind != Kind::Comment);
+        let next = lines[i + 1..].iter().map(|l| l[0]).find(|t| t.kind != Kind::Comment);
+        if let Some(end) = code_end {
+            let last = &line[end];
+            let in_statements = matches!(stack.last(), None | Some((Open::Block, _)));
+            let is_value = ends_value(last) && !(last.is("}") && last_closed != Some(Open::Map));
+            let ends_statement = match next {
+                Some(next) => !next.is("}") && !starts_continuation_of(&next),
+                None => stack.is_empty(),
+            };
+            if in_statements && is_value && ends_statement && !in_params {
+                let split = text_end_of(line, end, &text);
+                text.insert(split, ';');
+                before = prev.replace(Token { kind: Kind::Punct, text: ";", breaks: 0 });
+            }
+            continues = match last.kind {
+                Kind::Punct => is_operator(last),
+                Kind::Word => last.is_keyword() && !matches!(last.text, "else" | "loop" | "do" | "try"),
+                _ => false,
+            };
+        }
+        out.push_str(text.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+/// Byte offset in the formatted `text` just after token `end` of `line`
+fn text_end_of(line: &[Token], end: usize, text: &str) -> usize {
+    let trailing: usize = line[end + 1..].iter().map(|t| t.text.len() + 1).sum();
+    text.len() - trailing
+}
+
+/// Whether a token ends an expression, so what follows is an operator rather than an operand
+fn ends_value(token: &Token) -> bool {
+    match token.kind {
+        Kind::Word => !token.is_keyword(),
+        Kind::Str => true,
+        Kind::Comment => false,
+        Kind::Punct => token.is(")") || token.is("]") || token.is("}"),
+    }
+}
+
+/// A binary or assignment operator, after which an expression must continue
+fn is_operator(token: &Token) -> bool {
+    !matches!(token.text, "(" | ")" | "[" | "]" | "{" | "}" | "#{" | "?[" | ";" | "," | "|" | "!")
+}
+
+/// Whether `next`, starting a line, carries on the statement of the line before
+fn starts_continuation_of(next: &Token) -> bool {
+    match next.kind {
+        Kind::Punct => !matches!(next.text, "#{" | "!" | "|" | "||"),
+        Kind::Word => matches!(next.text, "else" | "catch" | "in" | "as" | "is"),
+        _ => false,
+    }
+}
+
+/// Whether to put a space between neighbouring tokens `a` and `b`
+///
+/// `before` is the token before `a`, and `in_params` is set between a
+/// closure's pipes.
+fn spaced(a: &Token, b: &Token, before: Option<&Token>, in_params: bool) -> bool {
+    if b.kind == Kind::Comment {
+        return true;
+    }
+    let unary = a.kind == Kind::Punct && matches!(a.text, "-" | "+" | "!") && !before.is_some_and(ends_value);
+    let tight = in_params && (a.is("|") || b.is("|"))
+        || b.kind == Kind::Punct && matches!(b.text, "," | ";" | ")" | "]" | "." | "?." | "::" | "?[" | ":")
+        || a.kind == Kind::Punct && matches!(a.text, "(" | "[" | "?[" | "." | "?." | "::" | ".." | "..=")
+        || b.is("..")
+        || b.is("..=")
+        || unary
+        || a.is("!") && b.text == "in"
+        || (a.is("{") || a.is("#{")) && b.is("}");
+    let call = (b.is("(") || b.is("[")) && (a.kind == Kind::Word && !a.is_keyword() || a.is(")") || a.is("]"));
+    !tight && !call
+}
+
+/// Split `code` into tokens, keeping comments and recording line breaks
+fn lex(code: &str) -> Vec<Token<'_>> {
+    let bytes = code.as_bytes();
+    let mut tokens = Vec::new();
+    let mut breaks = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        if c == b'\n' {
+            breaks += 1;
+            i += 1;
+            continue;
+        }
+        if c.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        let rest = &code[i..];
+        let kind = if rest.starts_with("//") {
+            i += rest.find('\n').unwrap_or(rest.len());
+            Kind::Comment
+        } else if rest.starts_with("/*") {
+            i += block_comment_len(rest);
+            Kind::Comment
+        } else if c == b'"' || c == b'\'' {
+            i += quoted_len(rest, c);
+            Kind::Str
+        } else if c == b'`' {
+            // Doubled backticks stand for one inside the string
+            let mut end = 1;
+            loop {
+                match rest[end..].find('`') {
+                    Some(close) if rest[end + close + 1..].starts_with('`') => end += close + 2,
+                    Some(close) => break end += close + 1,
+                    None => break end = rest.len(),
+                }
+            }
+            i += end;
+            Kind::Str
+        } else if c.is_ascii_alphanumeric() || c == b'_' || !c.is_ascii() {
+            i += word_len(rest);
+            Kind::Word
+        } else {
+            i += OPERATORS.iter().find(|op| rest.starts_with(*op)).map_or(1, |op| op.len());
+            Kind::Punct
+        };
+        // Keep to character boundaries, in case the code isn't ASCII
+        while !code.is_char_boundary(i) {
+            i += 1;
+        }
+        tokens.push(Token { kind, text: &code[start..i], breaks });
+        breaks = 0;
+    }
+    tokens
+}
+
+/// Length of a possibly nested `/* */` comment at the start of `rest`
+fn block_comment_len(rest: &str) -> usize {
+    let mut depth = 0;
+    let mut i = 0;
+    while i < rest.len() {
+        if rest[i..].starts_with("/*") {
+            depth += 1;
+            i += 2;
+        } else if rest[i..].starts_with("*/") {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return i;
+            }
+        } else {
+            i += 1;
+        }
+    }
+    rest.len()
+}
+
+/// Length of a string or character literal opened by `quote`
+fn quoted_len(rest: &str, quote: u8) -> usize {
+    let bytes = rest.as_bytes();
+    let mut i = 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'\n' => return i,
+            b if b == quote => return i + 1,
+            _ => i += 1,
+        }
+    }
+    rest.len()
+}
+
+/// Length of an identifier or number, including decimals and exponents
+fn word_len(rest: &str) -> usize {
+    let bytes = rest.as_bytes();
+    let is_number = bytes[0].is_ascii_digit();
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        let next_is_digit = bytes.get(i + 1).is_some_and(u8::is_ascii_digit);
+        let decimal = b == b'.' && next_is_digit;
+        let exponent = (b == b'-' || b == b'+') && matches!(bytes[i - 1], b'e' | b'E') && next_is_digit;
+        if !(b.is_ascii_alphanumeric() || b == b'_' || !b.is_ascii() || is_number && (decimal || exponent)) {
+            break;
+        }
+        i += 1;
+    }
+    i
+}
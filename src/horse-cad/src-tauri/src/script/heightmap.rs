@@ -0,0 +1,104 @@
+use fidget::context::Tree;
+use image::{imageops::FilterType, GrayImage};
+use rhai::{Dynamic, Engine, EvalAltResult};
+
+use super::imports::DocumentFiles;
+use crate::number_from_dynamic;
+
+/// Most samples taken along either side of an image; larger ones are scaled down
+///
+/// Every cell between samples adds a patch to the tree, so this bounds how
+/// slow the heightmap is to mesh.
+const MAX_SAMPLES: u32 = 64;
+
+/// Register `heightmap` on `engine`, loading images through `files`
+pub fn register(engine: &mut Engine, files: DocumentFiles) {
+    engine.register_fn(
+        "heightmap",
+        move |path: &str, width: Dynamic, depth: Dynamic, max_height: Dynamic| -> Result<Tree, Box<EvalAltResult>> {
+            let size = [
+                number_from_dynamic("heightmap width", &width)?,
+                number_from_dynamic("heightmap depth", &depth)?,
+                number_from_dynamic("heightmap max height", &max_height)?,
+            ];
+            if size.iter().any(|&s| s <= 0.0) {
+                return Err("heightmap width, depth and max height must be greater than zero".into());
+            }
+            let image = image::load_from_memory(&files.read(path)?)
+                .map_err(|e| format!("failed to load image '{}': {}", path, e))?
+                .to_luma8();
+            heightmap_shape(&image, size).ok_or_else(|| format!("image '{}' is too small for a heightmap", path).into())
+        },
+    );
+}
+
+/// `heightmap(path, width, depth, max_height)`: solid under a grayscale image
+///
+/// The image covers `[0, width]` by `[0, depth]` with its top edge at
+/// `y = depth`; black is height 0 and white is `max_height`, interpolated
+/// between samples.  The solid starts at `z = 0`.
+fn heightmap_shape(image: &GrayImage, [width, depth, max_height]: [f64; 3]) -> Option<Tree> {
+    let (w, h) = image.dimensions();
+    if w < 2 || h < 2 {
+        return None;
+    }
+    let scale = (MAX_SAMPLES as f64 / w.max(h) as f64).min(1.0);
+    let samples = image::imageops::resize(
+        image,
+        ((w as f64 * scale).round() as u32).max(2),
+        ((h as f64 * scale).round() as u32).max(2),
+        FilterType::Triangle,
+    );
+
+    let (nx, ny) = samples.dimensions();
+    let grid = Grid {
+        cell: [width / (nx - 1) as f64, depth / (ny - 1) as f64],
+        // Image rows run top to bottom, while Y runs upwards
+        heights: (0..ny)
+            .rev()
+            .map(|row| (0..nx).map(|col| samples.get_pixel(col, row)[0] as f64 / 255.0 * max_height).collect())
+            .collect(),
+    };
+    // Patches are offset by one so the selection below never sees a zero
+    let surface = Tree::z() - (grid.select([0, nx as usize - 1], [0, ny as usize - 1]) - 1.0);
+
+    let (x, y) = (Tree::x(), Tree::y());
+    let footprint = ((x - width / 2.0).abs() - width / 2.0).max((y - depth / 2.0).abs() - depth / 2.0);
+    Some(surface.max(-Tree::z()).max(footprint))
+}
+
+/// Samples of a heightmap on a regular grid, indexed `[row][column]` from the origin
+struct Grid {
+    cell: [f64; 2],
+    heights: Vec<Vec<f64>>,
+}
+
+impl Grid {
+    /// Pick the bilinear patch under the current point among the cells in a range
+    ///
+    /// Each split is a short-circuiting `and`/`or` on which side of the
+    /// split the point falls, so meshing can drop the cells it isn't in.
+    fn select(&self, cols: [usize; 2], rows: [usize; 2]) -> Tree {
+        let split = |[lo, hi]: [usize; 2]| (lo + hi).div_ceil(2);
+        if cols[1] - cols[0] >= rows[1] - rows[0] && cols[1] - cols[0] > 1 {
+            let mid = split(cols);
+            let below = Tree::constant(mid as f64 * self.cell[0]).compare(Tree::x()).max(0.0);
+            below.and(self.select([cols[0], mid], rows)).or(self.select([mid, cols[1]], rows))
+        } else if rows[1] - rows[0] > 1 {
+            let mid = split(rows);
+            let below = Tree::constant(mid as f64 * self.cell[1]).compare(Tree::y()).max(0.0);
+            below.and(self.select(cols, [rows[0], mid])).or(self.select(cols, [mid, rows[1]]))
+        } else {
+            self.patch(cols[0], rows[0])
+        }
+    }
+
+    /// Bilinear interpolation across one cell, plus one
+    fn patch(&self, col: usize, row: usize) -> Tree {
+        let h = |c: usize, r: usize| self.heights[r][c];
+        let (h00, h10, h01, h11) = (h(col, row), h(col + 1, row), h(col, row + 1), h(col + 1, row + 1));
+        let u = (Tree::x() - col as f64 * self.cell[0]) / self.cell[0];
+        let v = (Tree::y() - row as f64 * self.cell[1]) / self.cell[1];
+        u.clone() * (h10 - h00) + v.clone() * (h01 - h00) + u * v * (h11 - h10 - h01 + h00) + (h00 + 1.0)
+    }
+}
@@ -0,0 +1,121 @@
+use anyhow::{anyhow, Result};
+use fidget::{
+    context::{Context, Tree},
+    rhai::FromDynamic,
+    shape::EzShape,
+    vm::VmShape,
+};
+use rhai::{Dynamic, Engine, EvalAltResult, NativeCallContext};
+
+use crate::mesh::bounds::detect_bounds;
+
+/// Samples along each side of a shape's bounds when finding how far it reaches
+const SAMPLES_PER_AXIS: usize = 48;
+
+/// Face directions spread evenly over the sphere, on top of the 26 box directions
+const SPREAD_DIRECTIONS: usize = 74;
+
+type ShapeResult = Result<Tree, Box<EvalAltResult>>;
+
+/// Register `hull(a, b, ...)` on `engine`
+///
+/// Takes one to four shapes, or a single array of any number of them.
+pub fn register(engine: &mut Engine) {
+    let call = |ctx: &NativeCallContext, args: Vec<Dynamic>| -> ShapeResult {
+        let mut shapes = Vec::new();
+        for arg in args {
+            // Array elements are hulled one by one, rather than unioned first
+            let items = if arg.is_array() { arg.into_array().unwrap() } else { vec![arg] };
+            for item in items {
+                shapes.push(Tree::from_dynamic(ctx, item, None)?);
+            }
+        }
+        hull(&shapes).map_err(|e| format!("hull failed: {}", e).into())
+    };
+    engine.register_fn("hull", move |ctx: NativeCallContext, a: Dynamic| call(&ctx, vec![a]));
+    engine.register_fn("hull", move |ctx: NativeCallContext, a: Dynamic, b: Dynamic| call(&ctx, vec![a, b]));
+    engine.register_fn("hull", move |ctx: NativeCallContext, a: Dynamic, b: Dynamic, c: Dynamic| {
+        call(&ctx, vec![a, b, c])
+    });
+    engine.register_fn("hull", move |ctx: NativeCallContext, a: Dynamic, b: Dynamic, c: Dynamic, d: Dynamic| {
+        call(&ctx, vec![a, b, c, d])
+    });
+}
+
+/// Approximate convex hull of `shapes`, as the intersection of planes that just touch them
+///
+/// Each shape is sampled on a grid over its bounds; a sample at `p`, inside
+/// with distance `d`, shows the shape reaches `n · p - d` along direction
+/// `n`, exactly for a sphere around it and at most one sample apart
+/// otherwise.  One plane is fitted along each of a hundred directions: the
+/// 26 of a cube's faces, edges and corners, so boxes keep their flat sides,
+/// and the rest spread evenly.  The result is a polyhedron whose flat
+/// faces stand in for curved parts of the true hull, and whose distance
+/// field is conservative, exact on its faces.
+pub fn hull(shapes: &[Tree]) -> Result<Tree> {
+    if shapes.is_empty() {
+        return Err(anyhow!("no shapes to hull"));
+    }
+    let directions = hull_directions();
+    let mut reach = vec![f32::NEG_INFINITY; directions.len()];
+    for shape in shapes {
+        let mut ctx = Context::new();
+        let node = ctx.import(shape);
+        let shape = VmShape::new(&ctx, node)?;
+        let bounds = detect_bounds(&shape)?.ok_or_else(|| anyhow!("every shape must be bounded and not empty"))?;
+        let step: [f32; 3] = std::array::from_fn(|a| (bounds.max[a] - bounds.min[a]) / SAMPLES_PER_AXIS as f32);
+
+        let tape = shape.ez_float_slice_tape();
+        let mut eval = VmShape::new_float_slice_eval();
+        let n = SAMPLES_PER_AXIS;
+        let (mut xs, mut ys) = (Vec::with_capacity(n * n), Vec::with_capacity(n * n));
+        for j in 0..n {
+            for i in 0..n {
+                xs.push(bounds.min[0] + (i as f32 + 0.5) * step[0]);
+                ys.push(bounds.min[1] + (j as f32 + 0.5) * step[1]);
+            }
+        }
+        for k in 0..n {
+            let zs = vec![bounds.min[2] + (k as f32 + 0.5) * step[2]; n * n];
+            let distances = eval.eval(&tape, &xs, &ys, &zs)?;
+            for (i, &d) in distances.iter().enumerate().filter(|(_, d)| **d <= 0.0) {
+                let p = [xs[i], ys[i], zs[i]];
+                for (r, dir) in reach.iter_mut().zip(&directions) {
+                    *r = r.max(dir[0] * p[0] + dir[1] * p[1] + dir[2] * p[2] - d);
+                }
+            }
+        }
+    }
+    if reach[0] == f32::NEG_INFINITY {
+        return Err(anyhow!("the shapes are too thin to sample"));
+    }
+
+    let planes = directions.iter().zip(&reach).map(|(dir, &r)| {
+        Tree::x() * dir[0] as f64 + Tree::y() * dir[1] as f64 + Tree::z() * dir[2] as f64 - r as f64
+    });
+    Ok(planes.reduce(|a, b| a.max(b)).unwrap())
+}
+
+/// Unit directions the hull's planes face
+fn hull_directions() -> Vec<[f32; 3]> {
+    let mut directions = Vec::new();
+    for x in -1i8..=1 {
+        for y in -1i8..=1 {
+            for z in -1i8..=1 {
+                if (x, y, z) != (0, 0, 0) {
+                    let length = ((x * x + y * y + z * z) as f32).sqrt();
+                    directions.push([x as f32 / length, y as f32 / length, z as f32 / length]);
+                }
+            }
+        }
+    }
+    // Fibonacci sphere, turning by the golden angle at each step down
+    let golden = std::f32::consts::PI * (3.0 - 5f32.sqrt());
+    for i in 0..SPREAD_DIRECTIONS {
+        let z = 1.0 - 2.0 * (i as f32 + 0.5) / SPREAD_DIRECTIONS as f32;
+        let radius = (1.0 - z * z).sqrt();
+        let angle = golden * i as f32;
+        directions.push([radius * angle.cos(), radius * angle.sin(), z]);
+    }
+    directions
+}
@@ -0,0 +1,131 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use rhai::{
+    module_resolvers::FileModuleResolver, Engine, EvalAltResult, GlobalRuntimeState, Module, ModuleResolver,
+    Position, Scope, Shared,
+};
+
+use super::extensions::ExtensionModules;
+
+/// A file the script loaded, through `import` or a function reading it, and when it was last modified
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportStamp {
+    pub path: PathBuf,
+    pub modified: Option<SystemTime>,
+}
+
+impl ImportStamp {
+    pub fn new(path: PathBuf) -> Self {
+        let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+        ImportStamp { path, modified }
+    }
+
+    /// Returns true if the file hasn't changed since it was stamped
+    pub fn is_current(&self) -> bool {
+        *self == ImportStamp::new(self.path.clone())
+    }
+}
+
+/// Module resolver for `import` statements, rooted at the document's directory
+///
+/// Paths with no file next to the document fall back to the user's
+/// extensions.  Every file it loads is recorded, so cached results can be
+/// invalidated when an imported file changes.  Documents that haven't been
+/// saved have no directory, so their imports fail with an explanation
+/// unless they name an extension.
+pub struct DocumentResolver {
+    base_dir: Option<PathBuf>,
+    extensions: Arc<ExtensionModules>,
+    /// Loads files by absolute path, once they've been found
+    files: FileModuleResolver,
+    loaded: Arc<Mutex<Vec<PathBuf>>>,
+}
+
+impl DocumentResolver {
+    /// Returns the resolver and the list it records loaded files into
+    pub fn new(base_dir: Option<&Path>, extensions: Arc<ExtensionModules>) -> (Self, Arc<Mutex<Vec<PathBuf>>>) {
+        let loaded = Arc::new(Mutex::new(Vec::new()));
+        let resolver = DocumentResolver {
+            base_dir: base_dir.map(Path::to_path_buf),
+            extensions,
+            files: FileModuleResolver::new(),
+            loaded: loaded.clone(),
+        };
+        (resolver, loaded)
+    }
+
+    /// The file `path` names, recorded as loaded
+    fn file(&self, path: &str, pos: Position) -> Result<String, Box<EvalAltResult>> {
+        // `import` paths always get the `.rhai` extension, whatever they end in
+        let local = self.base_dir.as_ref().map(|dir| dir.join(path).with_extension("rhai"));
+        // Extensions come second, so a document's own files are never shadowed
+        let extension = self.extensions.get(path.strip_suffix(".rhai").unwrap_or(path));
+        let file = match (local, extension) {
+            (Some(local), _) if local.exists() => local,
+            (_, Some(extension)) => extension.clone(),
+            (Some(local), None) => local,
+            (None, None) => {
+                let message = format!("save the document before importing '{}'", path);
+                return Err(EvalAltResult::ErrorRuntime(message.into(), pos).into());
+            }
+        };
+        let name = file.to_string_lossy().into_owned();
+        self.loaded.lock().unwrap_or_else(|e| e.into_inner()).push(file);
+        Ok(name)
+    }
+}
+
+/// Reads data files named by scripts, such as fonts, next to the document
+///
+/// Files are recorded in the same list as the resolver's imports.
+#[derive(Clone)]
+pub struct DocumentFiles {
+    base_dir: Option<PathBuf>,
+    loaded: Arc<Mutex<Vec<PathBuf>>>,
+}
+
+impl DocumentFiles {
+    pub fn new(base_dir: Option<&Path>, loaded: Arc<Mutex<Vec<PathBuf>>>) -> Self {
+        DocumentFiles { base_dir: base_dir.map(Path::to_path_buf), loaded }
+    }
+
+    /// Read `path`, relative to the document unless it's absolute
+    pub fn read(&self, path: &str) -> Result<Vec<u8>, Box<EvalAltResult>> {
+        let file = if Path::new(path).is_absolute() {
+            PathBuf::from(path)
+        } else {
+            self.base_dir
+                .as_ref()
+                .ok_or_else(|| format!("save the document before loading '{}'", path))?
+                .join(path)
+        };
+        let data = std::fs::read(&file).map_err(|e| format!("failed to read '{}': {}", path, e))?;
+        self.loaded.lock().unwrap_or_else(|e| e.into_inner()).push(file);
+        Ok(data)
+    }
+}
+
+impl ModuleResolver for DocumentResolver {
+    fn resolve(
+        &self,
+        engine: &Engine,
+        source: Option<&str>,
+        path: &str,
+        pos: Position,
+    ) -> Result<Shared<Module>, Box<EvalAltResult>> {
+        self.files.resolve(engine, source, &self.file(path, pos)?, pos)
+    }
+
+    fn resolve_raw(
+        &self,
+        engine: &Engine,
+        global: &mut GlobalRuntimeState,
+        scope: &mut Scope,
+        path: &str,
+        pos: Position,
+    ) -> Result<Shared<Module>, Box<EvalAltResult>> {
+        self.files.resolve_raw(engine, global, scope, &self.file(path, pos)?, pos)
+    }
+}
@@ -0,0 +1,273 @@
+use fidget::context::Tree;
+use rhai::{Dynamic, Engine, EvalAltResult};
+
+use super::sketch::{contours_sdf, prism};
+use crate::{number_from_dynamic, vec3_from_dynamic};
+
+type ShapeResult = Result<Tree, Box<EvalAltResult>>;
+
+/// Line segments along each involute flank of a gear tooth
+const FLANK_SEGMENTS: usize = 8;
+
+/// Register the built-in shape library on `engine`
+///
+/// Shapes are centered on the origin, except where noted; prisms, cones and
+/// holes run along Z, which is up for printing.
+pub fn register(engine: &mut Engine) {
+    engine.register_fn("rounded_box", |size: Dynamic, radius: Dynamic| rounded_box(size, &radius));
+    engine.register_fn("torus", |major: Dynamic, minor: Dynamic| torus(&major, &minor));
+    engine.register_fn("capsule", |a: Dynamic, b: Dynamic, radius: Dynamic| capsule(a, b, &radius));
+    engine.register_fn("cone", |radius: Dynamic, height: Dynamic| cone(&radius, &height));
+    engine.register_fn("hex_prism", |radius: Dynamic, height: Dynamic| hex_prism(&radius, &height));
+    engine.register_fn("countersunk_hole", |diameter: Dynamic, depth: Dynamic, head: Dynamic| {
+        countersunk_hole(&diameter, &depth, &head)
+    });
+    engine.register_fn("honeycomb_panel", |size: Dynamic, cell: Dynamic, wall: Dynamic| {
+        honeycomb_panel(size, &cell, &wall)
+    });
+    // `thread` is a reserved word in Rhai, so it can't be a function name
+    engine.register_fn("screw_thread", |diameter: Dynamic, pitch: Dynamic, length: Dynamic, internal: bool| {
+        screw_thread(&diameter, &pitch, &length, internal)
+    });
+    engine.register_fn("spur_gear", |module: Dynamic, teeth: Dynamic, thickness: Dynamic, pressure: Dynamic| {
+        spur_gear(&module, &teeth, &thickness, &pressure)
+    });
+}
+
+/// Read a number that must be greater than zero
+pub(crate) fn positive(name: &str, value: &Dynamic) -> Result<f64, Box<EvalAltResult>> {
+    let value = number_from_dynamic(name, value)?;
+    if value > 0.0 {
+        Ok(value)
+    } else {
+        Err(format!("{} must be greater than zero", name).into())
+    }
+}
+
+/// Distance from the Z axis
+fn radial() -> Tree {
+    (Tree::x().square() + Tree::y().square()).sqrt()
+}
+
+/// Solid between `z = 0` and `z = height`
+fn slab(height: f64) -> Tree {
+    (Tree::z() - height / 2.0).abs() - height / 2.0
+}
+
+/// Hexagon with the given apothem in the XY plane, flats facing ±X
+fn hexagon(x: Tree, y: Tree, apothem: f64) -> Tree {
+    let (s, c) = std::f64::consts::FRAC_PI_3.sin_cos();
+    let a = x.clone().abs();
+    let b = (x.clone() * c + y.clone() * s).abs();
+    let d = (x * c - y * s).abs();
+    a.max(b).max(d) - apothem
+}
+
+/// `rounded_box([x, y, z], radius)`: box with rounded edges and corners
+fn rounded_box(size: Dynamic, radius: &Dynamic) -> ShapeResult {
+    let size = vec3_from_dynamic("rounded_box size", size)?;
+    let radius = number_from_dynamic("rounded_box radius", radius)?;
+    if size.iter().any(|&s| s <= 0.0) {
+        return Err("rounded_box size must be positive on every axis".into());
+    }
+    let max_radius = size.iter().fold(f32::INFINITY, |m, &s| m.min(s)) as f64 / 2.0;
+    if radius < 0.0 || radius > max_radius {
+        return Err(format!("rounded_box radius must be between 0 and {}", max_radius).into());
+    }
+    let q: Vec<Tree> = [Tree::x(), Tree::y(), Tree::z()]
+        .into_iter()
+        .zip(size)
+        .map(|(axis, s)| axis.abs() - (s as f64 / 2.0 - radius))
+        .collect();
+    let outside = q.iter().map(|c| c.max(0.0).square()).reduce(|a, b| a + b).unwrap().sqrt();
+    let inside = q[0].max(q[1].clone()).max(q[2].clone()).min(0.0);
+    Ok(outside + inside - radius)
+}
+
+/// `torus(major, minor)`: ring around the Z axis
+fn torus(major: &Dynamic, minor: &Dynamic) -> ShapeResult {
+    let major = positive("torus major radius", major)?;
+    let minor = positive("torus minor radius", minor)?;
+    Ok(((radial() - major).square() + Tree::z().square()).sqrt() - minor)
+}
+
+/// `capsule([x, y, z], [x, y, z], radius)`: rounded rod between two points
+fn capsule(a: Dynamic, b: Dynamic, radius: &Dynamic) -> ShapeResult {
+    let a = vec3_from_dynamic("capsule start", a)?.map(f64::from);
+    let b = vec3_from_dynamic("capsule end", b)?.map(f64::from);
+    let radius = positive("capsule radius", radius)?;
+    let ba: [f64; 3] = std::array::from_fn(|i| b[i] - a[i]);
+    let length_sq: f64 = ba.iter().map(|c| c * c).sum();
+    if length_sq == 0.0 {
+        return Err("capsule start and end must differ".into());
+    }
+    let pa: Vec<Tree> = [Tree::x(), Tree::y(), Tree::z()].into_iter().zip(a).map(|(axis, c)| axis - c).collect();
+    // Position of the closest point along the segment, clamped to its ends
+    let h = (pa[0].clone() * ba[0] + pa[1].clone() * ba[1] + pa[2].clone() * ba[2]) / length_sq;
+    let h = h.max(0.0).min(1.0);
+    let d = pa
+        .into_iter()
+        .zip(ba)
+        .map(|(p, c)| (p - h.clone() * c).square())
+        .reduce(|x, y| x + y)
+        .unwrap();
+    Ok(d.sqrt() - radius)
+}
+
+/// `cone(radius, height)`: base on `z = 0`, tip at `z = height`
+fn cone(radius: &Dynamic, height: &Dynamic) -> ShapeResult {
+    let radius = positive("cone radius", radius)?;
+    let height = positive("cone height", height)?;
+    let side = (radial() * height + Tree::z() * radius - radius * height) / radius.hypot(height);
+    Ok(side.max(-Tree::z()))
+}
+
+/// `hex_prism(radius, height)`: hexagonal prism from `z = 0`, `radius` to the corners
+fn hex_prism(radius: &Dynamic, height: &Dynamic) -> ShapeResult {
+    let radius = positive("hex_prism radius", radius)?;
+    let height = positive("hex_prism height", height)?;
+    let apothem = radius * std::f64::consts::FRAC_PI_6.cos();
+    Ok(hexagon(Tree::x(), Tree::y(), apothem).max(slab(height)))
+}
+
+/// `countersunk_hole(diameter, depth, head_diameter)`: cutter for a flat-head screw
+///
+/// The surface is at `z = 0` and the hole goes down to `z = -depth`, with a
+/// 90° countersink; subtract it from a part.  The cutter extends above the
+/// surface so it doesn't leave a skin behind.
+fn countersunk_hole(diameter: &Dynamic, depth: &Dynamic, head: &Dynamic) -> ShapeResult {
+    let radius = positive("countersunk_hole diameter", diameter)? / 2.0;
+    let depth = positive("countersunk_hole depth", depth)?;
+    let head_radius = positive("countersunk_hole head diameter", head)? / 2.0;
+    if head_radius <= radius {
+        return Err("countersunk_hole head diameter must be larger than the hole".into());
+    }
+    let floor = -Tree::z() - depth;
+    let top = Tree::z() - head_radius;
+    let shaft = (radial() - radius).max(floor.clone()).max(top.clone());
+    let sink = ((radial() - Tree::z() - head_radius) / std::f64::consts::SQRT_2).max(floor).max(top);
+    Ok(shaft.min(sink))
+}
+
+/// `honeycomb_panel([x, y, z], cell, wall)`: plate from `z = 0` with hexagonal cut-outs
+///
+/// `cell` is the width of each hexagon across flats; `wall` is the width of
+/// the walls between cells and of the solid rim.
+fn honeycomb_panel(size: Dynamic, cell: &Dynamic, wall: &Dynamic) -> ShapeResult {
+    let size = vec3_from_dynamic("honeycomb_panel size", size)?.map(f64::from);
+    let cell = positive("honeycomb_panel cell", cell)?;
+    let wall = positive("honeycomb_panel wall", wall)?;
+    if size.iter().any(|&s| s <= 0.0) {
+        return Err("honeycomb_panel size must be positive on every axis".into());
+    }
+    if 2.0 * wall >= size[0].min(size[1]) {
+        return Err("honeycomb_panel walls leave no room for cells".into());
+    }
+
+    // Two offset rectangular lattices together form a hexagonal grid
+    let pitch = cell + wall;
+    let spacing = [pitch, pitch * 3f64.sqrt()];
+    let lattice = |offset: f64| {
+        let [u, v] = [Tree::x(), Tree::y()]
+            .into_iter()
+            .zip(spacing)
+            .map(|(axis, s)| (axis - s * offset).modulo(s) - s / 2.0)
+            .collect::<Vec<_>>()
+            .try_into()
+            .unwrap();
+        hexagon(u, v, cell / 2.0)
+    };
+    let cells = lattice(0.0).min(lattice(0.5));
+
+    let rim = [Tree::x(), Tree::y()]
+        .into_iter()
+        .zip(size)
+        .map(|(axis, s)| axis.abs() - (s / 2.0 - wall))
+        .reduce(|a, b| a.max(b))
+        .unwrap();
+    let plate = [Tree::x(), Tree::y()]
+        .into_iter()
+        .zip(size)
+        .map(|(axis, s)| axis.abs() - s / 2.0)
+        .fold(slab(size[2]), |a, b| a.max(b));
+    Ok(plate.max(-cells.max(rim)))
+}
+
+/// `screw_thread(diameter, pitch, length, internal)`: right-handed ISO metric thread along Z from `z = 0`
+///
+/// The profile is the basic ISO one: 60° flanks, with flats of `pitch / 8`
+/// at the major diameter and `pitch / 4` at the minor one.  An internal
+/// thread is a cutter for a threaded hole; its crest is left sharp past the
+/// major diameter so a bolt's flat crest has room.  Subtract it from a part.
+fn screw_thread(diameter: &Dynamic, pitch: &Dynamic, length: &Dynamic, internal: bool) -> ShapeResult {
+    let major = positive("screw_thread diameter", diameter)? / 2.0;
+    let pitch = positive("screw_thread pitch", pitch)?;
+    let length = positive("screw_thread length", length)?;
+    let height = pitch * 3f64.sqrt() / 2.0;
+    let minor = major - height * 5.0 / 8.0;
+    if minor <= 0.0 {
+        return Err("screw_thread pitch is too coarse for its diameter".into());
+    }
+
+    // Axial distance from the middle of the nearest crest, following the helix
+    let turn = Tree::y().atan2(Tree::x()) / std::f64::consts::TAU * pitch;
+    let from_crest = ((Tree::z() - turn).modulo(pitch) - pitch / 2.0).abs();
+    let flank = -(from_crest - pitch / 16.0) * 3f64.sqrt() + major;
+    let profile = if internal { flank.max(minor) } else { flank.max(minor).min(major) };
+    // The flanks' slope would overstate the distance, so scale it back down
+    let surface = (radial() - profile) / 2.0;
+    Ok(surface.max(slab(length)))
+}
+
+/// `spur_gear(module, teeth, thickness, pressure_angle)`: involute spur gear from `z = 0`
+///
+/// The gear is centered on the Z axis with a tooth along +X.  Teeth have
+/// the standard addendum of one module and dedendum of 1.25 modules, and
+/// the roots are left sharp.  Only one tooth is built; the rest repeat it
+/// around the axis, so the tree doesn't grow with the tooth count.
+fn spur_gear(module: &Dynamic, teeth: &Dynamic, thickness: &Dynamic, pressure: &Dynamic) -> ShapeResult {
+    let module = positive("spur_gear module", module)?;
+    let teeth = number_from_dynamic("spur_gear teeth", teeth)?;
+    if teeth < 4.0 || teeth.fract() != 0.0 {
+        return Err("spur_gear teeth must be a whole number of at least 4".into());
+    }
+    let thickness = positive("spur_gear thickness", thickness)?;
+    let pressure = number_from_dynamic("spur_gear pressure angle", pressure)?;
+    if pressure <= 0.0 || pressure >= 45.0 {
+        return Err("spur_gear pressure angle must be between 0 and 45 degrees".into());
+    }
+
+    let pitch = module * teeth / 2.0;
+    let base = pitch * pressure.to_radians().cos();
+    let tip = pitch + module;
+    let root = pitch - 1.25 * module;
+    let involute = |r: f64| {
+        let angle = (base / r).min(1.0).acos();
+        angle.tan() - angle
+    };
+    // Angle from the tooth's center line to its flank, which is half the
+    // pitch angle at the pitch circle
+    let flank_angle = |r: f64| std::f64::consts::PI / (2.0 * teeth) + involute(pitch) - involute(r);
+    if flank_angle(tip) <= 0.0 {
+        return Err("spur_gear teeth come to a point; use more teeth or a smaller pressure angle".into());
+    }
+    let polar = |r: f64, angle: f64| [r * angle.cos(), r * angle.sin()];
+
+    // One tooth, counter-clockwise, running radially below the base circle
+    // and reaching well inside the root circle
+    let start = base.max(root);
+    let radii: Vec<f64> = (0..=FLANK_SEGMENTS).map(|i| start + (tip - start) * i as f64 / FLANK_SEGMENTS as f64).collect();
+    let mut tooth = vec![polar(root / 2.0, -flank_angle(start))];
+    tooth.extend(radii.iter().map(|&r| polar(r, -flank_angle(r))));
+    tooth.push(polar(tip, 0.0));
+    tooth.extend(radii.iter().rev().map(|&r| polar(r, flank_angle(r))));
+    tooth.push(polar(root / 2.0, flank_angle(start)));
+    let tooth = contours_sdf(&[tooth]).unwrap();
+
+    // Turn the current point back into the sector of the first tooth
+    let sector = std::f64::consts::TAU / teeth;
+    let angle = Tree::y().atan2(Tree::x());
+    let local = angle.clone() - (angle / sector).round() * sector;
+    let tooth = tooth.remap_xyz(radial() * local.cos(), radial() * local.sin(), Tree::constant(0.0));
+    Ok(prism(tooth.min(radial() - root), thickness))
+}
@@ -0,0 +1,162 @@
+use std::collections::BTreeSet;
+
+use rhai::{ASTNode, Expr, Position, ScriptFuncDef, Stmt, StmtBlock, AST};
+
+use super::diagnostics::Diagnostic;
+
+/// Functions that add shapes to the scene
+const DRAW_FUNCTIONS: [&str; 3] = ["draw", "draw_named", "draw2d"];
+
+/// A `let` or `const` and the function body it's in
+struct Declaration {
+    name: String,
+    position: Position,
+    scope: Option<usize>,
+    /// The function that made its initial value, if it's a call
+    init_call: Option<String>,
+}
+
+/// Warn about likely mistakes in a script that still runs
+///
+/// Flags variables that are never read, `let`s that shadow a function's
+/// parameter, calls to `shape_functions` whose result is thrown away, and
+/// scripts that never call `draw()`.  Variables are matched by name within
+/// the function body (or top level) they're declared in, so a use anywhere
+/// later counts, even of a later variable with the same name; this misses
+/// some unused variables rather than flagging used ones.  Names starting
+/// with `_` are never flagged.  `ast` should be compiled without
+/// optimization, or unused constants may already be gone.
+pub fn lint_script(ast: &AST, code: &str, shape_functions: &BTreeSet<String>) -> Vec<Diagnostic> {
+    let functions: Vec<&ScriptFuncDef> = ast.iter_fn_def().map(|f| f.as_ref()).collect();
+    // Innermost body holding a position, since closures are functions inside functions
+    let scope_of = |pos: Position| {
+        functions
+            .iter()
+            .enumerate()
+            .filter(|(_, f)| {
+                let span = f.body.span();
+                location(span.start()) <= location(pos) && location(pos) <= location(span.end())
+            })
+            .max_by_key(|(_, f)| location(f.body.span().start()))
+            .map(|(i, _)| i)
+    };
+
+    let mut declarations = Vec::new();
+    let mut uses: Vec<(String, Position, Option<usize>)> = Vec::new();
+    let mut draws = false;
+    ast.walk(&mut |path: &[ASTNode]| {
+        match path.last() {
+            Some(ASTNode::Stmt(Stmt::Var(decl, ..))) => {
+                let (ident, init, _) = decl.as_ref();
+                let init_call = match init {
+                    Expr::FnCall(call, _) => Some(call.name.to_string()),
+                    Expr::Dot(dot, ..) => method_name(&dot.rhs),
+                    _ => None,
+                };
+                declarations.push(Declaration {
+                    name: ident.name.to_string(),
+                    position: ident.pos,
+                    scope: scope_of(ident.pos),
+                    init_call,
+                });
+            }
+            Some(ASTNode::Expr(Expr::Variable(var, _, pos))) => {
+                uses.push((var.1.to_string(), *pos, scope_of(*pos)));
+            }
+            Some(
+                ASTNode::Stmt(Stmt::FnCall(call, _))
+                | ASTNode::Expr(Expr::FnCall(call, _) | Expr::MethodCall(call, _)),
+            ) => {
+                draws |= DRAW_FUNCTIONS.contains(&call.name.as_str());
+            }
+            _ => {}
+        }
+        true
+    });
+
+    let mut diagnostics = Vec::new();
+    for decl in &declarations {
+        if decl.name.starts_with('_') {
+            continue;
+        }
+        let used = uses.iter().any(|(name, pos, scope)| {
+            *name == decl.name && *scope == decl.scope && location(*pos) > location(decl.position)
+        });
+        if !used {
+            let message = match &decl.init_call {
+                Some(call) if shape_functions.contains(call) => {
+                    format!("Shape '{}' is built but never drawn or used", decl.name)
+                }
+                _ => format!("'{}' is never used", decl.name),
+            };
+            diagnostics.push(Diagnostic::warning(message, decl.position, code));
+        }
+        if let Some(f) = decl.scope.map(|i| functions[i]) {
+            if !f.name.starts_with("anon$") && f.params.iter().any(|p| *p == decl.name) {
+                let message = format!("'{}' shadows the parameter of {}() with the same name", decl.name, f.name);
+                diagnostics.push(Diagnostic::warning(message, decl.position, code));
+            }
+        }
+    }
+
+    // The script's own value is ignored, but a function's last statement is its result
+    let mut discarded = Vec::new();
+    discarded_calls(ast.statements(), false, &mut discarded);
+    for f in &functions {
+        discarded_calls(f.body.statements(), true, &mut discarded);
+    }
+    for (name, position) in discarded {
+        if shape_functions.contains(&name) {
+            let message = format!("The shape {}() makes is thrown away; pass it to draw() or store it", name);
+            diagnostics.push(Diagnostic::warning(message, position, code));
+        }
+    }
+
+    if !draws {
+        let message = "The script never calls draw(), draw_named() or draw2d(), so there's nothing to mesh";
+        diagnostics.push(Diagnostic::warning(message, Position::NONE, code));
+    }
+    diagnostics.sort_by_key(|d| (d.line, d.column));
+    diagnostics
+}
+
+/// Calls whose value is dropped in `statements`, with where they're made
+///
+/// With `keeps_last`, the block's last statement is its value, as in a
+/// function body.  Branches of an `if` pass its value on; loop bodies
+/// never keep one.
+fn discarded_calls(statements: &[Stmt], keeps_last: bool, found: &mut Vec<(String, Position)>) {
+    for (i, stmt) in statements.iter().enumerate() {
+        let kept = keeps_last && i + 1 == statements.len();
+        match stmt {
+            Stmt::FnCall(call, pos) if !kept => found.push((call.name.to_string(), *pos)),
+            Stmt::Expr(expr) if !kept => match expr.as_ref() {
+                Expr::FnCall(call, pos) => found.push((call.name.to_string(), *pos)),
+                Expr::Dot(dot, _, pos) => found.extend(method_name(&dot.rhs).map(|name| (name, *pos))),
+                _ => {}
+            },
+            Stmt::If(flow, _) => {
+                discarded_calls(flow.body.statements(), kept, found);
+                discarded_calls(flow.branch.statements(), kept, found);
+            }
+            Stmt::While(flow, _) | Stmt::Do(flow, ..) => discarded_calls(flow.body.statements(), false, found),
+            Stmt::For(for_loop, _) => discarded_calls(for_loop.2.body.statements(), false, found),
+            Stmt::Block(block) => discarded_calls(StmtBlock::statements(block), kept, found),
+            _ => {}
+        }
+    }
+}
+
+/// Name of the last method called in a chain like `sphere(1).move([1, 0, 0])`
+fn method_name(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::MethodCall(call, _) => Some(call.name.to_string()),
+        Expr::Dot(dot, ..) => method_name(&dot.rhs),
+        _ => None,
+    }
+}
+
+/// Position as a comparable `(line, column)` pair
+fn location(pos: Position) -> (usize, usize) {
+    (pos.line().unwrap_or(0), pos.position().unwrap_or(0))
+}
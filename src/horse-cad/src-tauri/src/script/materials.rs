@@ -0,0 +1,65 @@
+use std::sync::{Arc, Mutex};
+
+use fidget::{context::Tree, rhai::FromDynamic};
+use rhai::{Dynamic, Engine, EvalAltResult, NativeCallContext};
+
+/// Material of whatever no `material()` call covers
+pub const DEFAULT_MATERIAL: &str = "Default";
+
+/// Register `material(shape, name)` on `engine`, recording each tagged shape into `tagged`
+///
+/// The call returns the shape unchanged, so it can be drawn or combined as
+/// usual; only the multi-material export uses the tags.
+pub fn register(engine: &mut Engine, tagged: Arc<Mutex<Vec<(String, Tree)>>>) {
+    engine.register_fn(
+        "material",
+        move |ctx: NativeCallContext, shape: Dynamic, name: &str| -> Result<Tree, Box<EvalAltResult>> {
+            let name = name.trim();
+            if name.is_empty() {
+                return Err("material name must not be empty".into());
+            }
+            let tree = Tree::from_dynamic(&ctx, shape, None)?;
+            tagged.lock().unwrap_or_else(|e| e.into_inner()).push((name.to_string(), tree.clone()));
+            Ok(tree)
+        },
+    );
+}
+
+/// Split `model` into one region per material, such that no two regions overlap
+///
+/// Where tagged shapes overlap, the later `material()` call wins, so a
+/// detail tagged after the body it sits in takes its own material.  Each
+/// region is clipped to `model`, so tagging a shape that isn't drawn adds
+/// nothing, and the rest of `model` gets `DEFAULT_MATERIAL`.  Regions with
+/// the same name are merged into one.
+pub fn material_regions(model: &Tree, tagged: &[(String, Tree)]) -> Vec<(String, Tree)> {
+    let mut regions: Vec<(String, Tree)> = Vec::new();
+    // Everything tagged after the current shape, which it has to give way to
+    let mut claimed: Option<Tree> = None;
+    for (name, shape) in tagged.iter().rev() {
+        let region = match &claimed {
+            Some(claimed) => shape.clone().max(-claimed.clone()),
+            None => shape.clone(),
+        };
+        claimed = Some(match claimed {
+            Some(claimed) => claimed.min(shape.clone()),
+            None => shape.clone(),
+        });
+        match regions.iter_mut().find(|(n, _)| n == name) {
+            Some((_, merged)) => *merged = merged.clone().min(region),
+            None => regions.push((name.clone(), region)),
+        }
+    }
+    regions.reverse();
+    let mut regions: Vec<(String, Tree)> =
+        regions.into_iter().map(|(name, region)| (name, model.clone().max(region))).collect();
+    let rest = match claimed {
+        Some(claimed) => model.clone().max(-claimed),
+        None => model.clone(),
+    };
+    match regions.iter_mut().find(|(n, _)| n == DEFAULT_MATERIAL) {
+        Some((_, merged)) => *merged = merged.clone().min(rest),
+        None => regions.insert(0, (DEFAULT_MATERIAL.to_string(), rest)),
+    }
+    regions
+}
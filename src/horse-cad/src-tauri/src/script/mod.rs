@@ -0,0 +1,25 @@
+pub mod api;
+pub mod assembly;
+pub mod assertions;
+pub mod console;
+pub mod diagnostics;
+pub mod format;
+pub mod dxf;
+pub mod extensions;
+pub mod heightmap;
+pub mod hull;
+pub mod imports;
+pub mod library;
+pub mod lint;
+pub mod materials;
+pub mod openscad;
+pub mod operators;
+pub mod packages;
+pub mod patterns;
+pub mod sketch;
+pub mod stl;
+pub mod svg;
+pub mod sweep;
+pub mod symbols;
+pub mod text;
+pub mod vm;
@@ -0,0 +1,1507 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+
+use crate::script::format::format_rhai;
+
+/// Operators, two-character ones first so the lexer matches greedily
+const PUNCTUATION: [&str; 28] = [
+    "<=", ">=", "==", "!=", "&&", "||", "(", ")", "[", "]", "{", "}", ",", ";", "=", ":", "?", "+", "-", "*", "/", "%",
+    "!", "<", ">", "^", "#", ".",
+];
+
+/// Names a translated script can't use for its own variables and functions
+///
+/// Rhai keywords, the axis variables, and the functions the translation
+/// itself calls; clashing OpenSCAD names get a trailing underscore.
+const RESERVED: [&str; 71] = [
+    "let", "const", "if", "else", "switch", "do", "while", "until", "loop", "for", "in", "continue", "break", "return",
+    "throw", "try", "catch", "fn", "private", "import", "export", "as", "global", "true", "false", "this", "is",
+    "shared", "sync", "spawn", "go", "var", "static", "new", "use", "with", "module", "package", "super", "call",
+    "curry", "type_of", "print", "debug", "eval", "x", "y", "z", "sphere", "move", "scale", "union", "difference",
+    "intersection", "rounded_box", "circle2d", "rect2d", "polygon2d", "extrude", "revolve", "offset2d", "twist", "draw",
+    "PI", "sin", "cos", "sqrt", "min", "max", "floor", "hull",
+];
+
+/// Colors OpenSCAD scripts use by name, for `draw`'s hex colors
+const COLOR_NAMES: [(&str, &str); 12] = [
+    ("red", "#ff0000"),
+    ("green", "#008000"),
+    ("blue", "#0000ff"),
+    ("yellow", "#ffff00"),
+    ("orange", "#ffa500"),
+    ("purple", "#800080"),
+    ("black", "#000000"),
+    ("white", "#ffffff"),
+    ("gray", "#808080"),
+    ("grey", "#808080"),
+    ("cyan", "#00ffff"),
+    ("magenta", "#ff00ff"),
+];
+
+/// Rhai functions standing in for OpenSCAD built-ins, with the others each one calls
+///
+/// Only the ones a translation uses are written into it.
+const HELPERS: [(&str, &[&str], &str); 17] = [
+    (
+        "scad_vec3",
+        &[],
+        r#"// A number as the same size on every axis, or a 2D vector with `fill` as Z
+fn scad_vec3(v, fill) {
+    if type_of(v) != "array" {
+        return [v, v, v];
+    }
+    if v.len() == 2 { [v[0], v[1], fill] } else { v }
+}"#,
+    ),
+    (
+        "scad_range",
+        &[],
+        r#"// The values of an OpenSCAD range [start:step:end], end included
+fn scad_range(start, step, end) {
+    let values = [];
+    if step == 0.0 {
+        return values;
+    }
+    let steps = floor((end - start) / step + 1e-9);
+    for i in 0..=steps.to_int() {
+        values.push(start + i * step);
+    }
+    values
+}"#,
+    ),
+    (
+        "scad_union",
+        &[],
+        r#"// Join shapes, skipping the () an `if` without an `else` leaves
+fn scad_union(parts) {
+    let shapes = parts.filter(|p| type_of(p) != "()");
+    if shapes.is_empty() { () } else { union(shapes) }
+}"#,
+    ),
+    (
+        "scad_difference",
+        &["scad_union"],
+        r#"// The first shape with all the others cut away
+fn scad_difference(parts) {
+    let base = parts[0];
+    let cut = scad_union(parts.extract(1));
+    if type_of(base) == "()" || type_of(cut) == "()" { base } else { difference(base, cut) }
+}"#,
+    ),
+    (
+        "scad_intersection",
+        &[],
+        r#"// Where every shape overlaps
+fn scad_intersection(parts) {
+    let shapes = parts.filter(|p| type_of(p) != "()");
+    if shapes.is_empty() { () } else { intersection(shapes) }
+}"#,
+    ),
+    (
+        "scad_cube",
+        &["scad_vec3"],
+        r#"// OpenSCAD's cube: from the origin, or centered on it
+fn scad_cube(size, center) {
+    let size = scad_vec3(size, 0.0);
+    let shape = rounded_box(size, 0.0);
+    if center { shape } else { move(shape, size.map(|s| s / 2.0)) }
+}"#,
+    ),
+    (
+        "scad_square",
+        &[],
+        r#"// OpenSCAD's square: from the origin, or centered on it
+fn scad_square(size, center) {
+    if type_of(size) != "array" {
+        size = [size, size];
+    }
+    let shape = rect2d(size);
+    if center { shape } else { move(shape, [size[0] / 2.0, size[1] / 2.0, 0.0]) }
+}"#,
+    ),
+    (
+        "scad_cylinder",
+        &[],
+        r#"// OpenSCAD's cylinder: radius r1 at the bottom to r2 at height h, a cone if either is 0
+fn scad_cylinder(h, r1, r2, center) {
+    let radial = sqrt(x * x + y * y);
+    let side = ((radial - r1) * h + z * (r1 - r2)) / sqrt(h * h + (r1 - r2) * (r1 - r2));
+    let shape = max(side, max(-z, z - h));
+    if center { move(shape, [0.0, 0.0, -h / 2.0]) } else { shape }
+}"#,
+    ),
+    (
+        "scad_rotate",
+        &[],
+        r#"// OpenSCAD's rotate([ax, ay, az]): about X, then Y, then Z, in degrees
+fn scad_rotate(shape, a) {
+    if type_of(a) != "array" {
+        a = [0.0, 0.0, a];
+    }
+    let c = a.map(|v| cos(v * PI() / 180.0));
+    let s = a.map(|v| sin(v * PI() / 180.0));
+    // Each remap looks up the point turned back the other way
+    shape = shape.remap(x, c[0] * y + s[0] * z, c[0] * z - s[0] * y);
+    shape = shape.remap(c[1] * x - s[1] * z, y, s[1] * x + c[1] * z);
+    shape.remap(c[2] * x + s[2] * y, c[2] * y - s[2] * x, z)
+}"#,
+    ),
+    (
+        "scad_rotate_axis",
+        &[],
+        r#"// OpenSCAD's rotate(a, v): `a` degrees about the axis `v`
+fn scad_rotate_axis(shape, a, v) {
+    let n = sqrt(v[0] * v[0] + v[1] * v[1] + v[2] * v[2]);
+    let k = v.map(|c| c / n);
+    let c = cos(a * PI() / 180.0);
+    let s = sin(a * PI() / 180.0);
+    let along = (k[0] * x + k[1] * y + k[2] * z) * (1.0 - c);
+    shape.remap(
+        x * c - (k[1] * z - k[2] * y) * s + k[0] * along,
+        y * c - (k[2] * x - k[0] * z) * s + k[1] * along,
+        z * c - (k[0] * y - k[1] * x) * s + k[2] * along
+    )
+}"#,
+    ),
+    (
+        "scad_mirror",
+        &[],
+        r#"// OpenSCAD's mirror(v): reflect through the plane through the origin with normal `v`
+fn scad_mirror(shape, v) {
+    let d = (v[0] * x + v[1] * y + v[2] * z) * (2.0 / (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]));
+    shape.remap(x - v[0] * d, y - v[1] * d, z - v[2] * d)
+}"#,
+    ),
+    (
+        "scad_linear_extrude",
+        &[],
+        r#"// OpenSCAD's linear_extrude, twisting `degrees` clockwise over the height
+fn scad_linear_extrude(shape, height, center, degrees) {
+    shape = extrude(shape, height);
+    if degrees != 0.0 {
+        shape = twist(shape, -degrees / height);
+    }
+    if center { move(shape, [0.0, 0.0, -height / 2.0]) } else { shape }
+}"#,
+    ),
+    (
+        "scad_elementwise",
+        &[],
+        r#"// `a op b` element by element, spreading a number over the other vector
+fn scad_elementwise(a, b, op) {
+    let n = if type_of(a) != "array" {
+        b.len()
+    } else if type_of(b) != "array" {
+        a.len()
+    } else {
+        min(a.len(), b.len())
+    };
+    let out = [];
+    for i in 0..n {
+        let p = if type_of(a) == "array" { a[i] } else { a };
+        let q = if type_of(b) == "array" { b[i] } else { b };
+        out.push(switch op { "+" => p + q, "-" => p - q, _ => p * q });
+    }
+    out
+}"#,
+    ),
+    ("scad_add", &["scad_elementwise"], "fn scad_add(a, b) {\n    scad_elementwise(a, b, \"+\")\n}"),
+    ("scad_sub", &["scad_elementwise"], "fn scad_sub(a, b) {\n    scad_elementwise(a, b, \"-\")\n}"),
+    (
+        "scad_mul",
+        &["scad_elementwise"],
+        r#"// Two vectors multiply to their dot product, as in OpenSCAD
+fn scad_mul(a, b) {
+    if type_of(a) != "array" || type_of(b) != "array" {
+        return scad_elementwise(a, b, "*");
+    }
+    let sum = 0.0;
+    for i in 0..min(a.len(), b.len()) {
+        sum += a[i] * b[i];
+    }
+    sum
+}"#,
+    ),
+    (
+        "scad_norm",
+        &[],
+        "fn scad_norm(v) {\n    let sum = 0.0;\n    for c in v {\n        sum += c * c;\n    }\n    sqrt(sum)\n}",
+    ),
+];
+
+/// Binding strength of translated expressions, for deciding where they need parentheses
+const TERNARY: u8 = 1;
+const UNARY: u8 = 8;
+const POWER: u8 = 9;
+const PRIMARY: u8 = 10;
+
+/// A translated script, with notes on what couldn't be carried over
+#[derive(Debug, Clone, Serialize)]
+pub struct OpenScadImport {
+    pub code: String,
+    /// One line per construct that was left out or approximated, starting with its line number
+    pub warnings: Vec<String>,
+}
+
+/// Translate OpenSCAD source into an equivalent horseCAD script
+///
+/// Covers `cube`, `sphere`, `cylinder`, `circle`, `square` and `polygon`;
+/// `translate`, `rotate`, `scale` and `mirror`; `union`, `difference` and
+/// `intersection`; `linear_extrude`, `rotate_extrude` and `offset`;
+/// variables, `for` loops, `if`, list comprehensions, and user modules and
+/// functions.  Top-level objects become `draw` calls, with their `color` if
+/// it's a literal.  Anything else, such as `minkowski`, `children()` or
+/// `include`, is left out or approximated, with a warning that's also
+/// written at the top of the script.  Fails on source that doesn't parse,
+/// and on a translation that Rhai can't parse, which would be a bug.
+pub fn translate_openscad(source: &str) -> Result<OpenScadImport, String> {
+    let mut parser = Parser { tokens: lex(source)?, pos: 0, warnings: Vec::new() };
+    let mut statements = Vec::new();
+    while parser.pos < parser.tokens.len() {
+        statements.extend(parser.statement()?);
+    }
+
+    let mut translator = Translator { warnings: parser.warnings, ..Translator::default() };
+    translator.collect(&statements, true);
+    let body = translator.statements(&statements, None);
+    if !body.iter().any(|line| line.contains("draw(")) {
+        translator.warnings.push("Nothing is drawn; call the translated modules with draw()".to_string());
+    }
+
+    let mut code = String::from("// Translated from OpenSCAD\n");
+    for warning in &translator.warnings {
+        code.push_str(&format!("// {}\n", warning));
+    }
+    let mut used = translator.helpers.clone();
+    for (name, needs, _) in HELPERS {
+        if used.contains(name) {
+            used.extend(needs.iter().copied());
+        }
+    }
+    for (name, _, helper) in HELPERS {
+        if used.contains(name) {
+            code.push_str(&format!("\n{}\n", helper));
+        }
+    }
+    for definition in &translator.definitions {
+        code.push_str(&format!("\n{}\n", definition));
+    }
+    code.push('\n');
+    code.push_str(&body.join("\n"));
+    let code = format_rhai(&code);
+    // Anything that doesn't parse is a translator bug, and is better caught here than on the first compile
+    if let Err(e) = fidget::rhai::engine().compile(&code) {
+        return Err(format!("the translated script doesn't parse ({}); this is a bug, please report it", e));
+    }
+    Ok(OpenScadImport { code, warnings: translator.warnings })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    Number(f64),
+    Ident(String),
+    Str(String),
+    Punct(&'static str),
+    /// The `<path>` after `include` or `use`
+    Path(String),
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    tok: Tok,
+    line: usize,
+}
+
+fn lex(source: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        if c == '\n' {
+            line += 1;
+            i += 1;
+            continue;
+        }
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c == '/' && next == Some('/') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            continue;
+        }
+        if c == '/' && next == Some('*') {
+            let start = line;
+            i += 2;
+            while !(chars.get(i) == Some(&'*') && chars.get(i + 1) == Some(&'/')) {
+                match chars.get(i) {
+                    None => return Err(format!("line {}: unclosed comment", start)),
+                    Some('\n') => line += 1,
+                    _ => {}
+                }
+                i += 1;
+            }
+            i += 2;
+            continue;
+        }
+
+        let start = i;
+        let after_include =
+            tokens.last().is_some_and(|t| matches!(&t.tok, Tok::Ident(word) if word == "include" || word == "use"));
+        let tok = if c.is_ascii_digit() || c == '.' && next.is_some_and(|n| n.is_ascii_digit()) {
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            if matches!(chars.get(i), Some('e' | 'E')) {
+                let sign = usize::from(matches!(chars.get(i + 1), Some('+' | '-')));
+                if chars.get(i + 1 + sign).is_some_and(|d| d.is_ascii_digit()) {
+                    i += 1 + sign;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let text: String = chars[start..i].iter().collect();
+            Tok::Number(text.parse().map_err(|_| format!("line {}: invalid number '{}'", line, text))?)
+        } else if c.is_alphabetic() || c == '_' || c == '$' {
+            i += 1;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            Tok::Ident(chars[start..i].iter().collect())
+        } else if c == '"' {
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None | Some('\n') => return Err(format!("line {}: unclosed string", line)),
+                    Some('"') => break,
+                    Some('\\') => {
+                        i += 1;
+                        match chars.get(i) {
+                            Some('n') => text.push('\n'),
+                            Some('t') => text.push('\t'),
+                            Some(&escaped) => text.push(escaped),
+                            None => {}
+                        }
+                    }
+                    Some(&c) => text.push(c),
+                }
+                i += 1;
+            }
+            i += 1;
+            Tok::Str(text)
+        } else if c == '<' && after_include {
+            while i < chars.len() && chars[i] != '>' && chars[i] != '\n' {
+                i += 1;
+            }
+            let path = chars[start + 1..i].iter().collect();
+            i += 1;
+            Tok::Path(path)
+        } else {
+            let pair: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+            let punct = PUNCTUATION
+                .iter()
+                .find(|p| pair.starts_with(*p))
+                .ok_or_else(|| format!("line {}: unexpected character '{}'", line, c))?;
+            i += punct.len();
+            Tok::Punct(punct)
+        };
+        tokens.push(Token { tok, line });
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone)]
+struct Arg {
+    name: Option<String>,
+    value: Expr,
+}
+
+/// A module or function parameter and its default
+type Param = (String, Option<Expr>);
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Number(f64),
+    Bool(bool),
+    Undef,
+    Str(String),
+    Var(String),
+    Vector(Vec<Expr>),
+    /// `[start : step : end]`
+    Range(Box<Expr>, Box<Expr>, Box<Expr>),
+    /// `[for (var = values) if (filter) body]`
+    Comprehension {
+        var: String,
+        values: Box<Expr>,
+        filter: Option<Box<Expr>>,
+        body: Box<Expr>,
+    },
+    Let(Vec<(String, Expr)>, Box<Expr>),
+    Unary(&'static str, Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    Ternary(Box<Expr>, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Arg>),
+    Index(Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone)]
+struct Stmt {
+    line: usize,
+    kind: StmtKind,
+}
+
+#[derive(Debug, Clone)]
+enum StmtKind {
+    Assign(String, Expr),
+    /// A module instantiation, such as `translate([1, 0, 0]) cube(2);`
+    Call {
+        name: String,
+        args: Vec<Arg>,
+        children: Vec<Stmt>,
+    },
+    For(Vec<(String, Expr)>, Vec<Stmt>),
+    If(Expr, Vec<Stmt>, Vec<Stmt>),
+    Module(String, Vec<Param>, Vec<Stmt>),
+    Function(String, Vec<Param>, Expr),
+}
+
+/// Binary operators from loosest to tightest, all left-associative
+const BINARY_LEVELS: [&[&str]; 6] =
+    [&["||"], &["&&"], &["==", "!="], &["<", "<=", ">", ">="], &["+", "-"], &["*", "/", "%"]];
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    warnings: Vec<String>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Tok> {
+        self.tokens.get(self.pos).map(|t| &t.tok)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens.get(self.pos).or(self.tokens.last()).map_or(1, |t| t.line)
+    }
+
+    fn is(&self, punct: &str) -> bool {
+        matches!(self.peek(), Some(Tok::Punct(p)) if *p == punct)
+    }
+
+    fn is_word(&self, word: &str) -> bool {
+        matches!(self.peek(), Some(Tok::Ident(w)) if w == word)
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        let found = self.is(punct);
+        self.pos += usize::from(found);
+        found
+    }
+
+    fn eat_word(&mut self, word: &str) -> bool {
+        let found = self.is_word(word);
+        self.pos += usize::from(found);
+        found
+    }
+
+    fn error(&self, expected: &str) -> String {
+        let found = match self.peek() {
+            None => "the end of the file".to_string(),
+            Some(Tok::Number(n)) => n.to_string(),
+            Some(Tok::Ident(word)) => format!("'{}'", word),
+            Some(Tok::Str(text)) => format!("\"{}\"", text),
+            Some(Tok::Punct(p)) => format!("'{}'", p),
+            Some(Tok::Path(path)) => format!("<{}>", path),
+        };
+        format!("line {}: expected {} but found {}", self.line(), expected, found)
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<(), String> {
+        if self.eat(punct) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("'{}'", punct)))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Some(Tok::Ident(word)) => {
+                let word = word.clone();
+                self.pos += 1;
+                Ok(word)
+            }
+            _ => Err(self.error("a name")),
+        }
+    }
+
+    /// One statement, or the contents of a `{ }` block
+    fn statement(&mut self) -> Result<Vec<Stmt>, String> {
+        let line = self.line();
+        if self.eat(";") {
+            return Ok(Vec::new());
+        }
+        if self.eat("{") {
+            let mut body = Vec::new();
+            while !self.eat("}") {
+                if self.peek().is_none() {
+                    return Err(format!("line {}: '{{' is never closed", line));
+                }
+                body.extend(self.statement()?);
+            }
+            return Ok(body);
+        }
+        // Disabled and background objects aren't part of the model; highlighted and root ones are
+        if self.eat("*") || self.eat("%") {
+            self.statement()?;
+            return Ok(Vec::new());
+        }
+        if self.eat("#") || self.eat("!") {
+            return self.statement();
+        }
+
+        let name = self.ident()?;
+        let kind = match name.as_str() {
+            "module" => {
+                let name = self.ident()?;
+                let params = self.params()?;
+                StmtKind::Module(name, params, self.statement()?)
+            }
+            "function" => {
+                let name = self.ident()?;
+                let params = self.params()?;
+                self.expect("=")?;
+                let body = self.expr()?;
+                self.expect(";")?;
+                StmtKind::Function(name, params, body)
+            }
+            "include" | "use" => {
+                if let Some(Tok::Path(path)) = self.peek() {
+                    let warning = format!("{} <{}> wasn't followed; translate that file too", name, path);
+                    self.warnings.push(format!("line {}: {}", line, warning));
+                    self.pos += 1;
+                }
+                self.eat(";");
+                return Ok(Vec::new());
+            }
+            "if" => {
+                self.expect("(")?;
+                let cond = self.expr()?;
+                self.expect(")")?;
+                let then = self.statement()?;
+                let otherwise = if self.eat_word("else") { self.statement()? } else { Vec::new() };
+                StmtKind::If(cond, then, otherwise)
+            }
+            "for" => {
+                self.expect("(")?;
+                let vars = self.assignments()?;
+                StmtKind::For(vars, self.statement()?)
+            }
+            // Its assignments only reach its children, which is close enough to assigning them in the block
+            "let" => {
+                self.expect("(")?;
+                let vars = self.assignments()?;
+                let mut body: Vec<Stmt> =
+                    vars.into_iter().map(|(name, value)| Stmt { line, kind: StmtKind::Assign(name, value) }).collect();
+                body.extend(self.statement()?);
+                return Ok(body);
+            }
+            _ if self.eat("=") => {
+                let value = self.expr()?;
+                self.expect(";")?;
+                StmtKind::Assign(name, value)
+            }
+            _ => {
+                self.expect("(")?;
+                let args = self.args()?;
+                StmtKind::Call { name, args, children: self.statement()? }
+            }
+        };
+        Ok(vec![Stmt { line, kind }])
+    }
+
+    /// `name = value, ...)`, after the opening parenthesis
+    fn assignments(&mut self) -> Result<Vec<(String, Expr)>, String> {
+        let mut vars = Vec::new();
+        while !self.eat(")") {
+            let name = self.ident()?;
+            self.expect("=")?;
+            vars.push((name, self.expr()?));
+            if !self.eat(",") {
+                self.expect(")")?;
+                break;
+            }
+        }
+        Ok(vars)
+    }
+
+    /// `(name, name = default, ...)`
+    fn params(&mut self) -> Result<Vec<Param>, String> {
+        self.expect("(")?;
+        let mut params = Vec::new();
+        while !self.eat(")") {
+            let name = self.ident()?;
+            let default = if self.eat("=") { Some(self.expr()?) } else { None };
+            params.push((name, default));
+            if !self.eat(",") {
+                self.expect(")")?;
+                break;
+            }
+        }
+        Ok(params)
+    }
+
+    /// `value, name = value, ...)`, after the opening parenthesis
+    fn args(&mut self) -> Result<Vec<Arg>, String> {
+        let mut args = Vec::new();
+        while !self.eat(")") {
+            let named = matches!(self.peek(), Some(Tok::Ident(_)))
+                && matches!(self.tokens.get(self.pos + 1).map(|t| &t.tok), Some(Tok::Punct("=")));
+            let name = if named { Some(self.ident()?) } else { None };
+            if named {
+                self.pos += 1;
+            }
+            args.push(Arg { name, value: self.expr()? });
+            if !self.eat(",") {
+                self.expect(")")?;
+                break;
+            }
+        }
+        Ok(args)
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
+        let cond = self.binary(0)?;
+        if !self.eat("?") {
+            return Ok(cond);
+        }
+        let then = self.expr()?;
+        self.expect(":")?;
+        let otherwise = self.expr()?;
+        Ok(Expr::Ternary(Box::new(cond), Box::new(then), Box::new(otherwise)))
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr, String> {
+        if level == BINARY_LEVELS.len() {
+            return self.unary();
+        }
+        let mut left = self.binary(level + 1)?;
+        while let Some(&op) = BINARY_LEVELS[level].iter().find(|op| self.is(op)) {
+            self.pos += 1;
+            let right = self.binary(level + 1)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        for op in ["-", "!", "+"] {
+            if self.eat(op) {
+                return Ok(Expr::Unary(op, Box::new(self.unary()?)));
+            }
+        }
+        let base = self.postfix()?;
+        if self.eat("^") {
+            return Ok(Expr::Binary("^", Box::new(base), Box::new(self.unary()?)));
+        }
+        Ok(base)
+    }
+
+    fn postfix(&mut self) -> Result<Expr, String> {
+        let mut value = self.primary()?;
+        loop {
+            if self.eat("[") {
+                let index = self.expr()?;
+                self.expect("]")?;
+                value = Expr::Index(Box::new(value), Box::new(index));
+            } else if self.eat(".") {
+                let axis = match self.ident()?.as_str() {
+                    "x" => 0.0,
+                    "y" => 1.0,
+                    "z" => 2.0,
+                    other => return Err(format!("line {}: unknown member '.{}'", self.line(), other)),
+                };
+                value = Expr::Index(Box::new(value), Box::new(Expr::Number(axis)));
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        let token = self.peek().cloned();
+        match token {
+            Some(Tok::Number(n)) => {
+                self.pos += 1;
+                Ok(Expr::Number(n))
+            }
+            Some(Tok::Str(text)) => {
+                self.pos += 1;
+                Ok(Expr::Str(text))
+            }
+            Some(Tok::Ident(word)) => {
+                self.pos += 1;
+                match word.as_str() {
+                    "true" => Ok(Expr::Bool(true)),
+                    "false" => Ok(Expr::Bool(false)),
+                    "undef" => Ok(Expr::Undef),
+                    "let" => {
+                        self.expect("(")?;
+                        let vars = self.assignments()?;
+                        Ok(Expr::Let(vars, Box::new(self.expr()?)))
+                    }
+                    "function" | "each" => {
+                        Err(format!("line {}: '{}' expressions aren't supported", self.line(), word))
+                    }
+                    _ if self.eat("(") => Ok(Expr::Call(word, self.args()?)),
+                    _ => Ok(Expr::Var(word)),
+                }
+            }
+            Some(Tok::Punct("(")) => {
+                self.pos += 1;
+                let value = self.expr()?;
+                self.expect(")")?;
+                Ok(value)
+            }
+            Some(Tok::Punct("[")) => {
+                self.pos += 1;
+                self.vector()
+            }
+            _ => Err(self.error("a value")),
+        }
+    }
+
+    /// A vector, range or list comprehension, after the opening bracket
+    fn vector(&mut self) -> Result<Expr, String> {
+        if self.eat("]") {
+            return Ok(Expr::Vector(Vec::new()));
+        }
+        if self.eat_word("for") {
+            self.expect("(")?;
+            let var = self.ident()?;
+            self.expect("=")?;
+            let values = Box::new(self.expr()?);
+            self.expect(")")?;
+            let filter = if self.eat_word("if") {
+                self.expect("(")?;
+                let filter = self.expr()?;
+                self.expect(")")?;
+                Some(Box::new(filter))
+            } else {
+                None
+            };
+            let body = Box::new(self.expr()?);
+            self.expect("]")?;
+            return Ok(Expr::Comprehension { var, values, filter, body });
+        }
+
+        let first = self.expr()?;
+        if self.eat(":") {
+            let second = self.expr()?;
+            let range = if self.eat(":") {
+                Expr::Range(Box::new(first), Box::new(second), Box::new(self.expr()?))
+            } else {
+                Expr::Range(Box::new(first), Box::new(Expr::Number(1.0)), Box::new(second))
+            };
+            self.expect("]")?;
+            return Ok(range);
+        }
+        let mut items = vec![first];
+        while self.eat(",") && !self.is("]") {
+            items.push(self.expr()?);
+        }
+        self.expect("]")?;
+        Ok(Expr::Vector(items))
+    }
+}
+
+/// The arguments of one call, looked up by position or name
+struct Args<'a>(&'a [Arg]);
+
+impl Args<'_> {
+    fn get(&self, position: usize, name: &str) -> Option<&Expr> {
+        let named = self.0.iter().find(|arg| arg.name.as_deref() == Some(name));
+        named.or_else(|| self.0.iter().filter(|arg| arg.name.is_none()).nth(position)).map(|arg| &arg.value)
+    }
+
+    fn named(&self, name: &str) -> Option<&Expr> {
+        self.0.iter().find(|arg| arg.name.as_deref() == Some(name)).map(|arg| &arg.value)
+    }
+}
+
+#[derive(Default)]
+struct Translator {
+    warnings: Vec<String>,
+    helpers: HashSet<&'static str>,
+    /// Translated user modules and functions, which Rhai only allows at the top level
+    definitions: Vec<String>,
+    /// Parameters of each user module and function, for filling in named and default arguments
+    signatures: HashMap<String, Vec<Param>>,
+    /// Last value assigned to each top-level name, which functions inline
+    globals: HashMap<String, Expr>,
+    /// Top-level names being inlined, to catch one defined in terms of itself
+    inlining: Vec<String>,
+    /// Variables last assigned a vector, whose arithmetic goes through helpers
+    vectors: HashSet<String>,
+    /// Set while translating a module or function body
+    in_function: bool,
+    /// Parameters and variables in scope in the function being translated
+    locals: Vec<String>,
+    /// Line of the statement being translated, for warnings
+    line: usize,
+}
+
+/// A child statement, translated for combining with its siblings
+enum Part {
+    Statement(String),
+    Shape(String),
+    /// A shape that may turn out to be `()`, from an `if` without an `else`
+    MaybeShape(String),
+}
+
+impl Translator {
+    fn warn(&mut self, message: String) {
+        self.warnings.push(format!("line {}: {}", self.line, message));
+    }
+
+    fn helper(&mut self, name: &'static str) -> &'static str {
+        self.helpers.insert(name);
+        name
+    }
+
+    /// Record every module and function signature, and the top-level variables
+    fn collect(&mut self, statements: &[Stmt], top: bool) {
+        for statement in statements {
+            match &statement.kind {
+                StmtKind::Assign(name, value) if top => {
+                    self.globals.insert(name.clone(), value.clone());
+                }
+                StmtKind::Module(name, params, body) => {
+                    self.signatures.insert(name.clone(), params.clone());
+                    self.collect(body, false);
+                }
+                StmtKind::Function(name, params, _) => {
+                    self.signatures.insert(name.clone(), params.clone());
+                }
+                StmtKind::Call { children, .. } => self.collect(children, false),
+                StmtKind::For(_, body) => self.collect(body, false),
+                StmtKind::If(_, then, otherwise) => {
+                    self.collect(then, false);
+                    self.collect(otherwise, false);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Statements that draw each object, for the top level and the loops and `if`s on it
+    ///
+    /// Assignments come first, since OpenSCAD makes them before any object
+    /// in the same scope.
+    fn statements(&mut self, statements: &[Stmt], color: Option<&str>) -> Vec<String> {
+        let mut assignments = Vec::new();
+        let mut lines = Vec::new();
+        for statement in statements {
+            self.line = statement.line;
+            match &statement.kind {
+                StmtKind::Assign(name, value) => {
+                    if let Some(line) = self.assignment(name, value) {
+                        assignments.push(line);
+                    }
+                }
+                StmtKind::Module(..) | StmtKind::Function(..) => self.define(statement),
+                StmtKind::For(vars, body) => {
+                    let scope = self.locals.len();
+                    let (heads, closing) = self.loop_heads(vars);
+                    let body = self.statements(body, color);
+                    self.locals.truncate(scope);
+                    if !body.is_empty() {
+                        lines.push(format!("{}\n{}\n{}", heads, body.join("\n"), closing));
+                    }
+                }
+                StmtKind::If(cond, then, otherwise) => {
+                    let cond = self.expr(cond);
+                    let then = self.statements(then, color);
+                    let otherwise = self.statements(otherwise, color);
+                    if otherwise.is_empty() {
+                        lines.push(format!("if {} {{\n{}\n}}", cond, then.join("\n")));
+                    } else {
+                        let (then, otherwise) = (then.join("\n"), otherwise.join("\n"));
+                        lines.push(format!("if {} {{\n{}\n}} else {{\n{}\n}}", cond, then, otherwise));
+                    }
+                }
+                StmtKind::Call { name, args, children } => {
+                    if let Some(line) = self.side_effect(name, args) {
+                        lines.push(line);
+                        continue;
+                    }
+                    let args = Args(args);
+                    let hex = match name.as_str() {
+                        "color" => args.get(0, "c").and_then(color_hex),
+                        _ => None,
+                    };
+                    if name == "color" && hex.is_some() || matches!(name.as_str(), "group" | "render") {
+                        lines.extend(self.statements(children, hex.as_deref().or(color)));
+                        continue;
+                    }
+                    let Some(shape) = self.shape(statement) else { continue };
+                    match color {
+                        Some(color) => lines.push(format!("draw({}, \"{}\");", shape, color)),
+                        None => lines.push(format!("draw({});", shape)),
+                    }
+                }
+            }
+        }
+        assignments.extend(lines);
+        assignments
+    }
+
+    /// `let name = value;`, or nothing for special variables such as `$fn`
+    fn assignment(&mut self, name: &str, value: &Expr) -> Option<String> {
+        if name.starts_with('$') {
+            return None;
+        }
+        let line = format!("let {} = {};", rhai_name(name), self.expr(value));
+        if self.is_vector(value) {
+            self.vectors.insert(name.to_string());
+        } else {
+            self.vectors.remove(name);
+        }
+        self.locals.push(name.to_string());
+        Some(line)
+    }
+
+    /// The opening and closing lines of the nested `for` loops over `vars`
+    fn loop_heads(&mut self, vars: &[(String, Expr)]) -> (String, String) {
+        let mut heads = Vec::new();
+        for (name, values) in vars {
+            heads.push(format!("for {} in {} {{", rhai_name(name), self.expr(values)));
+            self.vectors.remove(name);
+            self.locals.push(name.clone());
+        }
+        (heads.join("\n"), vec!["}"; vars.len()].join("\n"))
+    }
+
+    /// `echo` and `assert`, which make no shape
+    fn side_effect(&mut self, name: &str, args: &[Arg]) -> Option<String> {
+        match name {
+            "echo" => {
+                let values: Vec<String> = args
+                    .iter()
+                    .map(|arg| {
+                        let value = match &arg.value {
+                            Expr::Str(text) => text.replace('`', "``"),
+                            value => format!("${{{}}}", self.expr(value)),
+                        };
+                        match &arg.name {
+                            Some(name) => format!("{} = {}", name, value),
+                            None => value,
+                        }
+                    })
+                    .collect();
+                Some(format!("print(`ECHO: {}`);", values.join(", ")))
+            }
+            "assert" => {
+                let args = Args(args);
+                let cond = args.get(0, "condition").map_or("true".to_string(), |cond| self.operand(cond, PRIMARY));
+                let message = args.get(1, "message").map_or("\"assertion failed\"".to_string(), |m| self.expr(m));
+                Some(format!("if !{} {{\nthrow {};\n}}", cond, message))
+            }
+            _ => None,
+        }
+    }
+
+    /// Translate a module or function into a Rhai function
+    fn define(&mut self, statement: &Stmt) {
+        let saved = (self.in_function, std::mem::take(&mut self.locals), self.line);
+        self.in_function = true;
+        let (name, params, body) = match &statement.kind {
+            StmtKind::Module(name, params, body) => {
+                self.locals = params.iter().map(|(name, _)| name.clone()).collect();
+                let body = self.combine("union", body).unwrap_or_else(|| "()".to_string());
+                (name, params, body)
+            }
+            StmtKind::Function(name, params, body) => {
+                self.locals = params.iter().map(|(name, _)| name.clone()).collect();
+                (name, params, self.expr(body))
+            }
+            _ => unreachable!("only modules and functions are defined"),
+        };
+        let params: Vec<String> = params.iter().map(|(name, _)| rhai_name(name)).collect();
+        self.definitions.push(format!("fn {}({}) {{\n{}\n}}", rhai_name(name), params.join(", "), body));
+        (self.in_function, self.locals, self.line) = saved;
+    }
+
+    /// One expression joining the shapes `statements` make with `op`, if they make any
+    fn combine(&mut self, op: &str, statements: &[Stmt]) -> Option<String> {
+        let scope = self.locals.len();
+        let mut assignments = Vec::new();
+        let mut parts = Vec::new();
+        for statement in statements {
+            self.line = statement.line;
+            match &statement.kind {
+                StmtKind::Assign(name, value) => {
+                    assignments.extend(self.assignment(name, value).map(Part::Statement));
+                }
+                StmtKind::Module(..) | StmtKind::Function(..) => self.define(statement),
+                StmtKind::Call { name, args, .. } if matches!(name.as_str(), "echo" | "assert") => {
+                    parts.extend(self.side_effect(name, args).map(Part::Statement));
+                }
+                StmtKind::If(cond, then, otherwise) => {
+                    let cond = self.expr(cond);
+                    let then = self.combine("union", then);
+                    let otherwise = self.combine("union", otherwise);
+                    match (then, otherwise) {
+                        (Some(then), Some(otherwise)) => {
+                            parts.push(Part::Shape(format!("if {} {{\n{}\n}} else {{\n{}\n}}", cond, then, otherwise)))
+                        }
+                        (None, None) => {}
+                        (Some(then), None) => {
+                            parts.push(Part::MaybeShape(format!("if {} {{\n{}\n}} else {{ () }}", cond, then)))
+                        }
+                        (None, Some(otherwise)) => {
+                            parts.push(Part::MaybeShape(format!("if {} {{ () }} else {{\n{}\n}}", cond, otherwise)))
+                        }
+                    }
+                }
+                _ => parts.extend(self.shape(statement).map(Part::Shape)),
+            }
+        }
+        self.locals.truncate(scope);
+
+        let mut statements: Vec<String> = Vec::new();
+        let mut shapes = Vec::new();
+        let mut maybe = false;
+        for part in assignments.into_iter().chain(parts) {
+            match part {
+                Part::Statement(line) => statements.push(line),
+                Part::Shape(shape) => shapes.push(shape),
+                Part::MaybeShape(shape) => {
+                    maybe = true;
+                    shapes.push(shape);
+                }
+            }
+        }
+        if shapes.is_empty() {
+            return None;
+        }
+        let joined = if maybe {
+            let helper = match op {
+                "difference" => self.helper("scad_difference"),
+                "intersection" => self.helper("scad_intersection"),
+                _ => self.helper("scad_union"),
+            };
+            format!("{}([\n{}\n])", helper, shapes.join(",\n"))
+        } else if shapes.len() == 1 {
+            shapes.remove(0)
+        } else if op == "difference" {
+            let base = shapes.remove(0);
+            format!("difference({}, {})", base, reduce("union", shapes))
+        } else {
+            reduce(op, shapes)
+        };
+        if statements.is_empty() {
+            Some(joined)
+        } else {
+            Some(format!("{{\n{}\n{}\n}}", statements.join("\n"), joined))
+        }
+    }
+
+    /// The shape a module instantiation or loop makes, if it makes one
+    fn shape(&mut self, statement: &Stmt) -> Option<String> {
+        self.line = statement.line;
+        let (name, args, children) = match &statement.kind {
+            StmtKind::Call { name, args, children } => (name.as_str(), Args(args), children),
+            StmtKind::For(vars, body) => {
+                let scope = self.locals.len();
+                let (heads, closing) = self.loop_heads(vars);
+                let body = self.combine("union", body);
+                self.locals.truncate(scope);
+                let union = self.helper("scad_union");
+                return Some(format!(
+                    "{{\nlet scad_parts = [];\n{}\nscad_parts.push({});\n{}\n{}(scad_parts)\n}}",
+                    heads, body?, closing, union
+                ));
+            }
+            _ => return self.combine("union", std::slice::from_ref(statement)),
+        };
+        if let Some(params) = self.signatures.get(name).cloned() {
+            if !children.is_empty() {
+                self.warn(format!("children of {}() were left out, since children() isn't supported", name));
+            }
+            return Some(format!("{}({})", rhai_name(name), self.call_args(&params, args.0).join(", ")));
+        }
+
+        let number = |value: f64| Expr::Number(value);
+        let halved = |value: &Expr| Expr::Binary("/", Box::new(value.clone()), Box::new(Expr::Number(2.0)));
+        let radius = |this: &mut Self, r: Option<&Expr>, d: Option<&Expr>| match (r, d) {
+            (Some(r), _) => Some(this.expr(r)),
+            (None, Some(d)) => Some(this.expr(&halved(d))),
+            (None, None) => None,
+        };
+        match name {
+            "cube" => {
+                let size = self.expr(args.get(0, "size").unwrap_or(&number(1.0)));
+                let center = self.expr(args.get(1, "center").unwrap_or(&Expr::Bool(false)));
+                Some(format!("{}({}, {})", self.helper("scad_cube"), size, center))
+            }
+            "sphere" => {
+                let r = radius(self, args.get(0, "r"), args.named("d")).unwrap_or_else(|| "1.0".to_string());
+                Some(format!("sphere({})", r))
+            }
+            "cylinder" => {
+                let h = self.expr(args.get(0, "h").unwrap_or(&number(1.0)));
+                let r = radius(self, args.named("r"), args.named("d"));
+                let r1 = radius(self, args.get(1, "r1"), args.named("d1")).or(r.clone());
+                let r2 = radius(self, args.get(2, "r2"), args.named("d2")).or(r);
+                let center = self.expr(args.get(3, "center").unwrap_or(&Expr::Bool(false)));
+                let (r1, r2) = (r1.unwrap_or_else(|| "1.0".to_string()), r2.unwrap_or_else(|| "1.0".to_string()));
+                Some(format!("{}({}, {}, {}, {})", self.helper("scad_cylinder"), h, r1, r2, center))
+            }
+            "circle" => {
+                let r = radius(self, args.get(0, "r"), args.named("d")).unwrap_or_else(|| "1.0".to_string());
+                Some(format!("circle2d({})", r))
+            }
+            "square" => {
+                let size = self.expr(args.get(0, "size").unwrap_or(&number(1.0)));
+                let center = self.expr(args.get(1, "center").unwrap_or(&Expr::Bool(false)));
+                Some(format!("{}({}, {})", self.helper("scad_square"), size, center))
+            }
+            "polygon" => {
+                if args.get(1, "paths").is_some() {
+                    self.warn("polygon paths were ignored; its points are joined in order".to_string());
+                }
+                let points = self.expr(args.get(0, "points").unwrap_or(&Expr::Vector(Vec::new())));
+                Some(format!("polygon2d({})", points))
+            }
+            "union" | "group" | "render" | "color" => self.combine("union", children),
+            "difference" | "intersection" => self.combine(name, children),
+            "hull" => {
+                self.warn("hull() is approximated with 100 flat faces".to_string());
+                let shapes = self.combine("union", children)?;
+                Some(format!("hull({})", shapes))
+            }
+            "minkowski" => {
+                self.warn(format!("{}() isn't supported, so its children were joined instead", name));
+                self.combine("union", children)
+            }
+            "multmatrix" => {
+                self.warn("multmatrix() isn't supported, so its children were left untransformed".to_string());
+                self.combine("union", children)
+            }
+            "translate" | "scale" | "mirror" | "rotate" | "linear_extrude" | "rotate_extrude" | "offset" => {
+                let child = self.combine("union", children)?;
+                Some(self.transform(name, &args, child))
+            }
+            _ => {
+                self.warn(format!("{}() isn't supported and was left out", name));
+                None
+            }
+        }
+    }
+
+    /// `child` moved, turned or extruded by the transform `name`
+    fn transform(&mut self, name: &str, args: &Args, child: String) -> String {
+        let zero = Expr::Number(0.0);
+        match name {
+            "translate" => format!("move({}, {})", child, self.vec3(args.get(0, "v").unwrap_or(&zero), "0.0")),
+            "scale" => format!("scale({}, {})", child, self.vec3(args.get(0, "v").unwrap_or(&zero), "1.0")),
+            "mirror" => {
+                let normal = self.vec3(args.get(0, "v").unwrap_or(&Expr::Number(1.0)), "0.0");
+                format!("{}({}, {})", self.helper("scad_mirror"), child, normal)
+            }
+            "rotate" => {
+                let a = self.expr(args.get(0, "a").unwrap_or(&zero));
+                match args.get(1, "v") {
+                    Some(v) => {
+                        let v = self.vec3(v, "0.0");
+                        format!("{}({}, {}, {})", self.helper("scad_rotate_axis"), child, a, v)
+                    }
+                    None => format!("{}({}, {})", self.helper("scad_rotate"), child, a),
+                }
+            }
+            "linear_extrude" => {
+                if args.named("scale").is_some() || args.named("slices").is_some() {
+                    self.warn("linear_extrude scale and slices were ignored".to_string());
+                }
+                let height = self.expr(args.get(0, "height").unwrap_or(&Expr::Number(100.0)));
+                let center = self.expr(args.named("center").unwrap_or(&Expr::Bool(false)));
+                let twist = self.expr(args.named("twist").unwrap_or(&zero));
+                format!("{}({}, {}, {}, {})", self.helper("scad_linear_extrude"), child, height, center, twist)
+            }
+            "rotate_extrude" => {
+                format!("revolve({}, {})", child, self.expr(args.named("angle").unwrap_or(&Expr::Number(360.0))))
+            }
+            _ => {
+                let distance = args.get(0, "r").or(args.named("delta")).unwrap_or(&zero);
+                format!("offset2d({}, {})", child, self.expr(distance))
+            }
+        }
+    }
+
+    /// A vector for a transform, written out when it's a literal
+    fn vec3(&mut self, value: &Expr, fill: &str) -> String {
+        match value {
+            Expr::Vector(items) if items.len() == 3 => self.expr(value),
+            Expr::Vector(items) if items.len() == 2 => {
+                format!("[{}, {}, {}]", self.expr(&items[0]), self.expr(&items[1]), fill)
+            }
+            _ => format!("{}({}, {})", self.helper("scad_vec3"), self.expr(value), fill),
+        }
+    }
+
+    /// Arguments of a call to a user module or function, in parameter order with defaults filled in
+    fn call_args(&mut self, params: &[Param], args: &[Arg]) -> Vec<String> {
+        let args = Args(args);
+        params
+            .iter()
+            .enumerate()
+            .map(|(i, (name, default))| match args.get(i, name).or(default.as_ref()) {
+                Some(value) => self.expr(value),
+                None => "()".to_string(),
+            })
+            .collect()
+    }
+
+    fn expr(&mut self, expr: &Expr) -> String {
+        self.expr_with_precedence(expr).0
+    }
+
+    /// `expr`, in parentheses if it binds more loosely than `precedence`
+    fn operand(&mut self, expr: &Expr, precedence: u8) -> String {
+        match self.expr_with_precedence(expr) {
+            (text, own) if own < precedence => format!("({})", text),
+            (text, _) => text,
+        }
+    }
+
+    fn expr_with_precedence(&mut self, expr: &Expr) -> (String, u8) {
+        let text = match expr {
+            Expr::Number(n) => number(*n),
+            Expr::Bool(b) => b.to_string(),
+            Expr::Undef => "()".to_string(),
+            Expr::Str(text) => format!("{:?}", text),
+            Expr::Var(name) if self.is_global(name) => return self.global(name),
+            Expr::Var(name) => self.var(name),
+            Expr::Vector(items) => {
+                let items: Vec<String> = items.iter().map(|item| self.expr(item)).collect();
+                format!("[{}]", items.join(", "))
+            }
+            Expr::Range(start, step, end) => {
+                let (start, step, end) = (self.expr(start), self.expr(step), self.expr(end));
+                format!("{}({}, {}, {})", self.helper("scad_range"), start, step, end)
+            }
+            Expr::Comprehension { var, values, filter, body } => {
+                let values = self.operand(values, PRIMARY);
+                self.locals.push(var.clone());
+                let filter = filter.as_ref().map(|filter| self.expr(filter));
+                let body = self.expr(body);
+                self.locals.pop();
+                let var = rhai_name(var);
+                match filter {
+                    Some(filter) => format!("{}.filter(|{}| {}).map(|{}| {})", values, var, filter, var, body),
+                    None => format!("{}.map(|{}| {})", values, var, body),
+                }
+            }
+            Expr::Let(vars, body) => {
+                let scope = self.locals.len();
+                let lets: Vec<String> =
+                    vars.iter().filter_map(|(name, value)| self.assignment(name, value)).collect();
+                let body = self.expr(body);
+                self.locals.truncate(scope);
+                return (format!("{{\n{}\n{}\n}}", lets.join("\n"), body), TERNARY);
+            }
+            Expr::Unary(op, value) => {
+                if *op == "-" && self.is_vector(value) {
+                    format!("{}({}, -1.0)", self.helper("scad_mul"), self.expr(value))
+                } else if *op == "+" {
+                    return self.expr_with_precedence(value);
+                } else {
+                    return (format!("{}{}", op, self.operand(value, PRIMARY)), UNARY);
+                }
+            }
+            Expr::Binary(op, a, b) => return self.binary(op, a, b),
+            Expr::Ternary(cond, then, otherwise) => {
+                let (cond, then, otherwise) = (self.expr(cond), self.expr(then), self.expr(otherwise));
+                return (format!("if {} {{ {} }} else {{ {} }}", cond, then, otherwise), TERNARY);
+            }
+            Expr::Call(name, args) => return self.call(name, args),
+            Expr::Index(value, index) => {
+                let value = self.operand(value, PRIMARY);
+                match **index {
+                    Expr::Number(n) if n >= 0.0 && n.fract() == 0.0 => format!("{}[{}]", value, n),
+                    _ => format!("{}[{}.to_int()]", value, self.operand(index, PRIMARY)),
+                }
+            }
+        };
+        (text, PRIMARY)
+    }
+
+    fn var(&mut self, name: &str) -> String {
+        match name {
+            "PI" => "PI()".to_string(),
+            _ if name.starts_with('$') => {
+                self.warn(format!("{} has no equivalent, so 0 was used", name));
+                "0.0".to_string()
+            }
+            _ => rhai_name(name),
+        }
+    }
+
+    /// Whether `name` is a top-level variable read inside a module or function
+    fn is_global(&self, name: &str) -> bool {
+        self.in_function && !self.locals.iter().any(|local| local == name) && self.globals.contains_key(name)
+    }
+
+    /// A top-level variable read inside a module or function, as its last assigned value
+    ///
+    /// Rhai functions can't see the script's variables, so the value is
+    /// inlined, with the function's own names out of scope while it's
+    /// translated so they can't capture the names it uses.
+    fn global(&mut self, name: &str) -> (String, u8) {
+        if self.inlining.iter().any(|inlined| inlined == name) {
+            self.warn(format!("{} is defined in terms of itself, so 0 was used", name));
+            return ("0.0".to_string(), PRIMARY);
+        }
+        let value = self.globals[name].clone();
+        let locals = std::mem::take(&mut self.locals);
+        self.inlining.push(name.to_string());
+        let text = self.expr_with_precedence(&value);
+        self.inlining.pop();
+        self.locals = locals;
+        text
+    }
+
+    fn binary(&mut self, op: &str, a: &Expr, b: &Expr) -> (String, u8) {
+        if op == "^" {
+            return (format!("{} ** {}", self.operand(a, PRIMARY), self.operand(b, PRIMARY)), POWER);
+        }
+        let vector_helper = match op {
+            "+" if self.is_vector(a) || self.is_vector(b) => Some("scad_add"),
+            "-" if self.is_vector(a) || self.is_vector(b) => Some("scad_sub"),
+            "*" if self.is_vector(a) || self.is_vector(b) => Some("scad_mul"),
+            _ => None,
+        };
+        if let Some(helper) = vector_helper {
+            let (a, b) = (self.expr(a), self.expr(b));
+            return (format!("{}({}, {})", self.helper(helper), a, b), PRIMARY);
+        }
+        if op == "/" && self.is_vector(a) {
+            let (a, b) = (self.expr(a), self.operand(b, PRIMARY));
+            return (format!("{}({}, 1.0 / {})", self.helper("scad_mul"), a, b), PRIMARY);
+        }
+        // BINARY_LEVELS starts at `||`, which binds just tighter than a ternary
+        let level = BINARY_LEVELS.iter().position(|ops| ops.contains(&op)).unwrap_or(0) as u8 + TERNARY + 1;
+        let (a, b) = (self.operand(a, level), self.operand(b, level + 1));
+        (format!("{} {} {}", a, op, b), level)
+    }
+
+    fn call(&mut self, name: &str, args: &[Arg]) -> (String, u8) {
+        if let Some(params) = self.signatures.get(name).cloned() {
+            return (format!("{}({})", rhai_name(name), self.call_args(&params, args).join(", ")), PRIMARY);
+        }
+        let values: Vec<&Expr> = args.iter().map(|arg| &arg.value).collect();
+        let mul = BINARY_LEVELS.len() as u8 + TERNARY;
+        match (name, values.as_slice()) {
+            ("sin" | "cos" | "tan", [a]) => (format!("{}({} * PI() / 180.0)", name, self.operand(a, mul)), PRIMARY),
+            ("asin" | "acos" | "atan", [a]) => (format!("{}({}) * 180.0 / PI()", name, self.expr(a)), mul),
+            ("atan2", [y, x]) => {
+                let (y, x) = (self.expr(y), self.expr(x));
+                (format!("atan({}, {}) * 180.0 / PI()", y, x), mul)
+            }
+            ("abs" | "sqrt" | "exp" | "ln" | "log" | "floor" | "ceil" | "round" | "sign", [a]) => {
+                (format!("{}({})", name, self.expr(a)), PRIMARY)
+            }
+            ("pow", [a, b]) => (format!("{} ** {}", self.operand(a, PRIMARY), self.operand(b, PRIMARY)), POWER),
+            ("min" | "max", [first, rest @ ..]) if !rest.is_empty() => {
+                let mut text = self.expr(first);
+                for value in rest {
+                    text = format!("{}({}, {})", name, text, self.expr(value));
+                }
+                (text, PRIMARY)
+            }
+            ("len", [a]) => (format!("{}.len()", self.operand(a, PRIMARY)), PRIMARY),
+            ("norm", [a]) => (format!("{}({})", self.helper("scad_norm"), self.expr(a)), PRIMARY),
+            ("str", _) => {
+                let parts: Vec<String> = values.iter().map(|value| format!("${{{}}}", self.expr(value))).collect();
+                (format!("`{}`", parts.join("")), PRIMARY)
+            }
+            _ => {
+                self.warn(format!("{}() has no equivalent and was kept as a call", name));
+                let values: Vec<String> = values.iter().map(|value| self.expr(value)).collect();
+                (format!("{}({})", rhai_name(name), values.join(", ")), PRIMARY)
+            }
+        }
+    }
+
+    /// Whether `expr` is known to be a vector, so `+`, `-` and `*` must work element by element
+    fn is_vector(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::Vector(_) | Expr::Range(..) | Expr::Comprehension { .. } => true,
+            Expr::Var(name) => self.vectors.contains(name),
+            Expr::Binary("+" | "-", a, b) => self.is_vector(a) || self.is_vector(b),
+            Expr::Binary("*", a, b) => self.is_vector(a) != self.is_vector(b),
+            Expr::Binary("/", a, _) | Expr::Unary("-", a) => self.is_vector(a),
+            Expr::Ternary(_, a, b) => self.is_vector(a) || self.is_vector(b),
+            _ => false,
+        }
+    }
+}
+
+/// A number as a Rhai float, so it never takes part in integer division
+fn number(value: f64) -> String {
+    let text = value.to_string();
+    if text.contains('.') || !value.is_finite() {
+        text
+    } else {
+        format!("{}.0", text)
+    }
+}
+
+/// An OpenSCAD name as a Rhai one that can't clash with a keyword, axis or helper
+fn rhai_name(name: &str) -> String {
+    if RESERVED.contains(&name) || name.starts_with("scad_") {
+        format!("{}_", name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// `#rrggbb` for a literal OpenSCAD color: a name, a hex string or a vector of fractions
+fn color_hex(color: &Expr) -> Option<String> {
+    match color {
+        Expr::Str(text) if text.starts_with('#') => Some(text.clone()),
+        Expr::Str(text) => {
+            let name = text.to_ascii_lowercase();
+            COLOR_NAMES.iter().find(|(known, _)| *known == name).map(|(_, hex)| hex.to_string())
+        }
+        Expr::Vector(items) if items.len() >= 3 => {
+            let mut hex = String::from("#");
+            for item in &items[..3] {
+                let Expr::Number(fraction) = item else { return None };
+                hex.push_str(&format!("{:02x}", (fraction.clamp(0.0, 1.0) * 255.0).round() as u8));
+            }
+            Some(hex)
+        }
+        _ => None,
+    }
+}
+
+/// `op` applied to every shape: a variadic call for up to eight, or an array for more
+fn reduce(op: &str, mut shapes: Vec<String>) -> String {
+    if shapes.len() == 1 {
+        return shapes.remove(0);
+    }
+    if shapes.len() <= 8 {
+        return format!("{}({})", op, shapes.join(", "));
+    }
+    format!("{}([{}])", op, shapes.join(", "))
+}
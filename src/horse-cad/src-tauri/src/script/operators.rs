@@ -0,0 +1,233 @@
+use fidget::{context::Tree, rhai::FromDynamic};
+use rhai::{Dynamic, Engine, EvalAltResult, NativeCallContext};
+
+use crate::{number_from_dynamic, vec3_from_dynamic};
+
+type ShapeResult = Result<Tree, Box<EvalAltResult>>;
+
+/// Register operators that reshape existing shapes on `engine`
+///
+/// Deformers warp space, so their results are no longer exact distances;
+/// meshing still works, but offsets applied afterwards are approximate.
+pub fn register(engine: &mut Engine) {
+    engine.register_fn("twist", |ctx: NativeCallContext, shape: Dynamic, rate: Dynamic| {
+        twist(Tree::from_dynamic(&ctx, shape, None)?, &rate)
+    });
+    engine.register_fn("bend", |ctx: NativeCallContext, shape: Dynamic, radius: Dynamic| {
+        bend(Tree::from_dynamic(&ctx, shape, None)?, &radius)
+    });
+    engine.register_fn("taper", |ctx: NativeCallContext, shape: Dynamic, factor: Dynamic| {
+        taper(Tree::from_dynamic(&ctx, shape, None)?, &factor)
+    });
+    engine.register_fn(
+        "array_linear",
+        |ctx: NativeCallContext, shape: Dynamic, count: Dynamic, spacing: Dynamic| {
+            array_linear(Tree::from_dynamic(&ctx, shape, None)?, &count, spacing)
+        },
+    );
+    engine.register_fn("array_polar", |ctx: NativeCallContext, shape: Dynamic, count: Dynamic, radius: Dynamic| {
+        array_polar(Tree::from_dynamic(&ctx, shape, None)?, &count, &radius)
+    });
+    for (name, axis) in [("mirror_x", 0), ("mirror_y", 1), ("mirror_z", 2)] {
+        engine.register_fn(name, move |ctx: NativeCallContext, shape: Dynamic| -> ShapeResult {
+            Ok(mirror(Tree::from_dynamic(&ctx, shape, None)?, axis))
+        });
+    }
+    engine.register_fn("offset", |ctx: NativeCallContext, shape: Dynamic, distance: Dynamic| -> ShapeResult {
+        let distance = number_from_dynamic("offset distance", &distance)?;
+        Ok(Tree::from_dynamic(&ctx, shape, None)? - distance)
+    });
+    engine.register_fn("shell", |ctx: NativeCallContext, shape: Dynamic, thickness: Dynamic| {
+        shell(Tree::from_dynamic(&ctx, shape, None)?, &thickness)
+    });
+    engine.register_fn("smooth_union", |ctx: NativeCallContext, a: Dynamic, b: Dynamic, k: Dynamic| -> ShapeResult {
+        let (a, b) = (Tree::from_dynamic(&ctx, a, None)?, Tree::from_dynamic(&ctx, b, None)?);
+        Ok(-smooth_max(-a, -b, blend_radius("smooth_union", &k)?))
+    });
+    engine.register_fn(
+        "smooth_difference",
+        |ctx: NativeCallContext, a: Dynamic, b: Dynamic, k: Dynamic| -> ShapeResult {
+            let (a, b) = (Tree::from_dynamic(&ctx, a, None)?, Tree::from_dynamic(&ctx, b, None)?);
+            Ok(smooth_max(a, -b, blend_radius("smooth_difference", &k)?))
+        },
+    );
+    engine.register_fn(
+        "smooth_intersection",
+        |ctx: NativeCallContext, a: Dynamic, b: Dynamic, k: Dynamic| -> ShapeResult {
+            let (a, b) = (Tree::from_dynamic(&ctx, a, None)?, Tree::from_dynamic(&ctx, b, None)?);
+            Ok(smooth_max(a, b, blend_radius("smooth_intersection", &k)?))
+        },
+    );
+    engine.register_fn("morph", |ctx: NativeCallContext, a: Dynamic, b: Dynamic, t: Dynamic| -> ShapeResult {
+        let (a, b) = (Tree::from_dynamic(&ctx, a, None)?, Tree::from_dynamic(&ctx, b, None)?);
+        Ok(morph(a, b, Tree::from_dynamic(&ctx, t, None)?))
+    });
+    engine.register_fn("symmetry", |ctx: NativeCallContext, shape: Dynamic, plane: &str| {
+        symmetry(Tree::from_dynamic(&ctx, shape, None)?, plane)
+    });
+}
+
+/// Read a number of copies, which must be a positive whole number
+fn count_from_dynamic(name: &str, value: &Dynamic) -> Result<f64, Box<EvalAltResult>> {
+    let count = number_from_dynamic(name, value)?;
+    if count < 1.0 || count.fract() != 0.0 {
+        return Err(format!("{} must be a positive whole number", name).into());
+    }
+    Ok(count)
+}
+
+/// Read the blend size of a smooth boolean, which must be greater than zero
+fn blend_radius(function: &str, k: &Dynamic) -> Result<f64, Box<EvalAltResult>> {
+    let k = number_from_dynamic(&format!("{} k", function), k)?;
+    if k <= 0.0 {
+        return Err(format!("{} k must be greater than zero", function).into());
+    }
+    Ok(k)
+}
+
+/// Polynomial smooth maximum of two distances, blending where they are within `k`
+///
+/// The smooth booleans are built from this: `smooth_intersection(a, b, k)`
+/// is `smooth_max(a, b)`, `smooth_difference` is `smooth_max(a, -b)` and
+/// `smooth_union` is `-smooth_max(-a, -b)`.  `k` is roughly the width of the
+/// fillet.
+fn smooth_max(a: Tree, b: Tree, k: f64) -> Tree {
+    let overlap = (-(a.clone() - b.clone()).abs() + k).max(0.0);
+    a.max(b) + overlap.square() / (4.0 * k)
+}
+
+/// `morph(a, b, t)`: blend linearly from `a` at `t = 0` to `b` at `t = 1`
+///
+/// `t` may be a number or a `param_tree()` value, so sliding it only
+/// re-binds the shape.  Values outside 0 to 1 extrapolate past either
+/// shape.  In between the result is no longer an exact distance, and it can
+/// break apart where the two shapes don't overlap.
+fn morph(a: Tree, b: Tree, t: Tree) -> Tree {
+    a.clone() + (b - a) * t
+}
+
+/// `twist(shape, degrees_per_unit)`: turn each slice about the Z axis in proportion to its height
+///
+/// Slices turn counter-clockwise seen from above as Z increases; the slice
+/// at `z = 0` stays put.
+fn twist(shape: Tree, rate: &Dynamic) -> ShapeResult {
+    let rate = number_from_dynamic("twist rate", rate)?.to_radians();
+    let angle = Tree::z() * rate;
+    let (sin, cos) = (angle.sin(), angle.cos());
+    let (x, y) = (Tree::x(), Tree::y());
+    Ok(shape.remap_xyz(x.clone() * cos.clone() + y.clone() * sin.clone(), y * cos - x * sin, Tree::z()))
+}
+
+/// `bend(shape, radius)`: wrap the X axis around a circle of `radius`
+///
+/// The circle's axis runs along Y through `z = radius`, so a positive radius
+/// curls the shape's ends upwards and a negative one downwards.  Lengths
+/// along X are kept at `z = 0`; the origin stays put.
+fn bend(shape: Tree, radius: &Dynamic) -> ShapeResult {
+    let radius = number_from_dynamic("bend radius", radius)?;
+    if radius == 0.0 {
+        return Err("bend radius must not be zero".into());
+    }
+    // Work as if the radius were positive, flipping Z for negative radii
+    let (bend, flip) = (radius.abs(), radius.signum());
+    let z = Tree::z() * flip;
+    let x = Tree::x();
+    let below = -(z.clone() - bend);
+    let angle = x.clone().atan2(below.clone());
+    let distance = (x.square() + below.square()).sqrt();
+    Ok(shape.remap_xyz(angle * bend, Tree::y(), (-(distance - bend)) * flip))
+}
+
+/// `taper(shape, factor)`: scale each slice in X and Y by `1 + factor * z`
+///
+/// Slices are scaled about the Z axis and the slice at `z = 0` is kept; a
+/// negative factor narrows the shape upwards, down to a point at
+/// `z = -1 / factor`.
+fn taper(shape: Tree, factor: &Dynamic) -> ShapeResult {
+    let factor = number_from_dynamic("taper factor", factor)?;
+    // Clamped so the shape vanishes instead of flipping past the point
+    let scale = (Tree::z() * factor + 1.0).max(1e-6);
+    let warped = shape.remap_xyz(Tree::x() / scale.clone(), Tree::y() / scale.clone(), Tree::z());
+    Ok(warped * scale)
+}
+
+/// `array_linear(shape, count, [x, y, z])`: `count` copies, each offset by the spacing from the last
+///
+/// The first copy stays where it is.  Copies are made by repeating space
+/// rather than by unions, so the tree doesn't grow with the count, but each
+/// copy must fit within half the spacing of its own position.
+fn array_linear(shape: Tree, count: &Dynamic, spacing: Dynamic) -> ShapeResult {
+    let count = count_from_dynamic("array_linear count", count)?;
+    let spacing = vec3_from_dynamic("array_linear spacing", spacing)?.map(f64::from);
+    let length_squared: f64 = spacing.iter().map(|s| s * s).sum();
+    if length_squared == 0.0 {
+        return Err("array_linear spacing must not be zero".into());
+    }
+    // Nearest copy to the current point, along the spacing
+    let along = (Tree::x() * spacing[0] + Tree::y() * spacing[1] + Tree::z() * spacing[2]) / length_squared;
+    let copy = along.round().max(0.0).min(count - 1.0);
+    Ok(shape.remap_xyz(
+        Tree::x() - copy.clone() * spacing[0],
+        Tree::y() - copy.clone() * spacing[1],
+        Tree::z() - copy * spacing[2],
+    ))
+}
+
+/// `array_polar(shape, count, radius)`: `count` copies spaced evenly around the Z axis
+///
+/// The shape is moved out to `(radius, 0)` and then turned into place, so
+/// its +X faces away from the axis in every copy.  As with `array_linear`,
+/// each copy must stay within its own share of the circle.
+fn array_polar(shape: Tree, count: &Dynamic, radius: &Dynamic) -> ShapeResult {
+    let count = count_from_dynamic("array_polar count", count)?;
+    let radius = number_from_dynamic("array_polar radius", radius)?;
+    if radius < 0.0 {
+        return Err("array_polar radius must not be negative".into());
+    }
+    // Turn the current point back into the sector of the first copy
+    let sector = std::f64::consts::TAU / count;
+    let angle = Tree::y().atan2(Tree::x());
+    let local = angle.clone() - (angle / sector).round() * sector;
+    let distance = (Tree::x().square() + Tree::y().square()).sqrt();
+    Ok(shape.remap_xyz(distance.clone() * local.cos() - radius, distance * local.sin(), Tree::z()))
+}
+
+/// Evaluate `shape` with the coordinate along `axis` transformed by `f`
+fn remap_axis(shape: Tree, axis: usize, f: impl Fn(Tree) -> Tree) -> Tree {
+    let mut p = [Tree::x(), Tree::y(), Tree::z()];
+    p[axis] = f(p[axis].clone());
+    let [x, y, z] = p;
+    shape.remap_xyz(x, y, z)
+}
+
+/// `mirror_x(shape)` and friends: the shape together with its mirror image across `axis = 0`
+fn mirror(shape: Tree, axis: usize) -> Tree {
+    shape.min(remap_axis(shape.clone(), axis, |c| -c))
+}
+
+/// `symmetry(shape, plane)`: reflect the positive half of a shape across a plane
+///
+/// The plane is `"yz"`, `"xz"` or `"xy"`, through the origin.  Only the half
+/// on its positive side is ever evaluated, so this is cheaper than `mirror_x`
+/// and friends, which evaluate the shape twice.
+fn symmetry(shape: Tree, plane: &str) -> ShapeResult {
+    let axis = match plane {
+        "yz" | "zy" => 0,
+        "xz" | "zx" => 1,
+        "xy" | "yx" => 2,
+        _ => return Err(format!("symmetry plane must be \"yz\", \"xz\" or \"xy\", not \"{}\"", plane).into()),
+    };
+    Ok(remap_axis(shape, axis, |c| c.abs()))
+}
+
+/// `shell(shape, thickness)`: hollow a shape, keeping a wall of `thickness` inside its surface
+///
+/// The outside is unchanged, so a shelled enclosure fits where the solid
+/// did; cut an opening into it to reach the hollow.
+fn shell(shape: Tree, thickness: &Dynamic) -> ShapeResult {
+    let thickness = number_from_dynamic("shell thickness", thickness)?;
+    if thickness <= 0.0 {
+        return Err("shell thickness must be greater than zero".into());
+    }
+    Ok(shape.max(-(shape.clone() + thickness)))
+}
@@ -0,0 +1,147 @@
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::download::{download, MAX_DOWNLOAD_BYTES};
+use crate::utils::file_utils::{sha256_hex, write_atomically};
+
+/// Reviewed community libraries that can be installed by name
+///
+/// The index ships with the app, so adding a library to it goes through
+/// review like any other change, and a checksum pins the reviewed version.
+/// No library has been reviewed in yet, so it's empty and only installs
+/// from a URL work; the by-name path is scaffolding until the first entry.
+const LIBRARY_INDEX: &str = include_str!("../../libraries.json");
+
+/// Most `.rhai` files one bundle may hold
+const MAX_BUNDLE_FILES: usize = 256;
+
+/// A library listed in the index
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LibraryEntry {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// A `.rhai` file, or a `.zip` of them
+    pub url: String,
+    /// SHA-256 of the file at `url`, in hex
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LibraryIndex {
+    pub libraries: Vec<LibraryEntry>,
+}
+
+/// The libraries that can be installed by name
+pub fn library_index() -> Result<LibraryIndex> {
+    serde_json::from_str(LIBRARY_INDEX).context("The bundled library index isn't valid")
+}
+
+/// Download a library into the extensions folder `dir`, returning the files written
+///
+/// `url_or_name` is either the name of a library in the index, whose
+/// checksum the index gives, or an `https://` URL, which needs `sha256`.
+/// The download must match the checksum, and every module in it must
+/// compile, before anything is written.  A bundle is a `.zip` whose `.rhai`
+/// files are each installed as an extension; anything else is taken to be
+/// a single `.rhai` file named after the library or URL.  Files replace
+/// extensions of the same name, so installing again updates a library.
+pub fn install_library(dir: &Path, url_or_name: &str, sha256: Option<&str>) -> Result<Vec<PathBuf>> {
+    let entry = if url_or_name.contains("://") {
+        let sha256 = sha256.ok_or_else(|| anyhow!("Installing from a URL needs the file's SHA-256 to check it by"))?;
+        let file = url_or_name.split(['?', '#']).next().unwrap_or_default().rsplit('/').next().unwrap_or_default();
+        let name = file.strip_suffix(".zip").or_else(|| file.strip_suffix(".rhai")).unwrap_or(file);
+        LibraryEntry {
+            name: name.to_string(),
+            description: String::new(),
+            url: url_or_name.to_string(),
+            sha256: sha256.to_string(),
+        }
+    } else {
+        let index = library_index()?;
+        if index.libraries.is_empty() {
+            bail!("The library index is empty so far; install '{}' from its URL with its SHA-256", url_or_name);
+        }
+        let mut entry = index
+            .libraries
+            .into_iter()
+            .find(|entry| entry.name == url_or_name)
+            .ok_or_else(|| anyhow!("There's no library called '{}' in the index", url_or_name))?;
+        // An explicit checksum pins the version, if the index has moved on
+        if let Some(sha256) = sha256 {
+            entry.sha256 = sha256.to_string();
+        }
+        entry
+    };
+    check_module_name(&entry.name)?;
+
+    let data = download(&entry.url, MAX_DOWNLOAD_BYTES)?;
+    let found = sha256_hex(&data);
+    if !found.eq_ignore_ascii_case(entry.sha256.trim()) {
+        bail!("{} has SHA-256 {}, not the expected {}; it wasn't installed", entry.url, found, entry.sha256.trim());
+    }
+
+    let modules = if data.starts_with(b"PK\x03\x04") {
+        bundle_modules(&data)?
+    } else {
+        let code = String::from_utf8(data).with_context(|| format!("{} isn't a text file", entry.url))?;
+        vec![(entry.name.clone(), code)]
+    };
+    let engine = fidget::rhai::engine();
+    for (name, code) in &modules {
+        engine.compile(code).map_err(|e| anyhow!("{}.rhai in {} doesn't compile: {}", name, entry.name, e))?;
+    }
+
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let mut written = Vec::with_capacity(modules.len());
+    for (name, code) in modules {
+        let path = dir.join(format!("{}.rhai", name));
+        write_atomically(&path, code.as_bytes()).with_context(|| format!("Failed to write {}", path.display()))?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// The `.rhai` files in a zip, by file name without the extension
+///
+/// Folders inside the zip are ignored, since extensions are imported by
+/// file name alone.
+fn bundle_modules(data: &[u8]) -> Result<Vec<(String, String)>> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(data)).context("The bundle isn't a valid zip")?;
+    let mut modules: Vec<(String, String)> = Vec::new();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let Some(name) = file.name().rsplit('/').next().and_then(|n| n.strip_suffix(".rhai")).map(str::to_string)
+        else {
+            continue;
+        };
+        check_module_name(&name)?;
+        if modules.iter().any(|(other, _)| *other == name) {
+            bail!("The bundle has more than one {}.rhai", name);
+        }
+        if modules.len() == MAX_BUNDLE_FILES {
+            bail!("The bundle has more than {} modules", MAX_BUNDLE_FILES);
+        }
+        let mut code = String::new();
+        (&mut file)
+            .take(MAX_DOWNLOAD_BYTES)
+            .read_to_string(&mut code)
+            .with_context(|| format!("Failed to read {}.rhai from the bundle", name))?;
+        modules.push((name, code));
+    }
+    if modules.is_empty() {
+        bail!("The bundle has no .rhai files");
+    }
+    Ok(modules)
+}
+
+/// Names become file names, so only plain ones are allowed
+fn check_module_name(name: &str) -> Result<()> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        bail!("'{}' isn't a usable library name; use letters, digits, '_' and '-'", name);
+    }
+    Ok(())
+}
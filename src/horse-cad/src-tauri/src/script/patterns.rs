@@ -0,0 +1,138 @@
+use std::f64::consts::TAU;
+
+use fidget::{context::Tree, rhai::FromDynamic};
+use rhai::{Dynamic, Engine, EvalAltResult, NativeCallContext};
+
+use super::library::positive;
+use crate::number_from_dynamic;
+
+type ShapeResult = Result<Tree, Box<EvalAltResult>>;
+
+/// Register procedural patterns, `infill`, `displace` and `voronoi` on `engine`
+///
+/// Patterns fill all of space, so they're meant to be cut down to a part
+/// with `infill` or `intersection` rather than drawn on their own.
+pub fn register(engine: &mut Engine) {
+    engine.register_fn("gyroid", |period: Dynamic, thickness: Dynamic| gyroid(&period, &thickness));
+    engine.register_fn("lattice", |cell: Dynamic, thickness: Dynamic| lattice(&cell, &thickness));
+    engine.register_fn("infill", |ctx: NativeCallContext, solid: Dynamic, pattern: Dynamic| -> ShapeResult {
+        Ok(Tree::from_dynamic(&ctx, solid, None)?.max(Tree::from_dynamic(&ctx, pattern, None)?))
+    });
+    engine.register_fn(
+        "infill",
+        |ctx: NativeCallContext, solid: Dynamic, pattern: Dynamic, wall: Dynamic| {
+            infill(Tree::from_dynamic(&ctx, solid, None)?, Tree::from_dynamic(&ctx, pattern, None)?, &wall)
+        },
+    );
+    engine.register_fn(
+        "displace",
+        |ctx: NativeCallContext, shape: Dynamic, scale: Dynamic, amplitude: Dynamic| -> ShapeResult {
+            let scale = positive("displace noise scale", &scale)?;
+            let amplitude = number_from_dynamic("displace amplitude", &amplitude)?;
+            Ok(Tree::from_dynamic(&ctx, shape, None)? + noise(scale) * amplitude)
+        },
+    );
+    engine.register_fn("voronoi", |ctx: NativeCallContext, shape: Dynamic, cell: Dynamic, wall: Dynamic| {
+        voronoi(Tree::from_dynamic(&ctx, shape, None)?, &cell, &wall)
+    });
+}
+
+/// `gyroid(period, thickness)`: gyroid sheet repeating every `period` along each axis
+///
+/// The distance is estimated from the gyroid's implicit function, so the
+/// wall thickness is only approximate.
+fn gyroid(period: &Dynamic, thickness: &Dynamic) -> ShapeResult {
+    let period = positive("gyroid period", period)?;
+    let thickness = positive("gyroid thickness", thickness)?;
+    let [x, y, z] = [Tree::x(), Tree::y(), Tree::z()].map(|axis| axis * (TAU / period));
+    let field = x.sin() * y.cos() + y.sin() * z.cos() + z.sin() * x.cos();
+    Ok(field.abs() * (period / TAU) - thickness / 2.0)
+}
+
+/// `lattice(cell, thickness)`: cubic lattice of round struts along the edges of `cell`-sized cubes
+///
+/// Struts run along X, Y and Z through every multiple of `cell`, and
+/// `thickness` is their diameter.
+fn lattice(cell: &Dynamic, thickness: &Dynamic) -> ShapeResult {
+    let cell = positive("lattice cell", cell)?;
+    let thickness = positive("lattice thickness", thickness)?;
+    if thickness >= cell {
+        return Err("lattice thickness must be less than the cell size".into());
+    }
+    // Offset from the nearest strut position on each axis
+    let [x, y, z] = [Tree::x(), Tree::y(), Tree::z()].map(|axis| (axis + cell / 2.0).modulo(cell) - cell / 2.0);
+    let strut = |a: &Tree, b: &Tree| (a.square() + b.square()).sqrt();
+    Ok(strut(&y, &z).min(strut(&x, &z)).min(strut(&x, &y)) - thickness / 2.0)
+}
+
+/// `infill(solid, pattern, wall)`: a solid with a skin of `wall` thickness, filled with `pattern`
+///
+/// Without `wall`, the pattern is just cut down to the solid.
+fn infill(solid: Tree, pattern: Tree, wall: &Dynamic) -> ShapeResult {
+    let wall = positive("infill wall", wall)?;
+    let skin = solid.max(-(solid.clone() + wall));
+    Ok(solid.max(pattern).min(skin))
+}
+
+/// `voronoi(shape, cell_size, wall_thickness)`: the walls of a 3D Voronoi foam, cut down to `shape`
+///
+/// Each `cell_size` cube of space holds one seed at a pseudo-random spot
+/// near its middle, and walls run halfway between neighbouring seeds.  The
+/// distance to a wall is estimated from the two nearest seeds, so it's
+/// approximate away from the walls.
+fn voronoi(shape: Tree, cell: &Dynamic, wall: &Dynamic) -> ShapeResult {
+    let cell = positive("voronoi cell size", cell)?;
+    let wall = positive("voronoi wall thickness", wall)?;
+    if wall >= cell {
+        return Err("voronoi wall thickness must be less than the cell size".into());
+    }
+    let p = [Tree::x(), Tree::y(), Tree::z()].map(|axis| axis / cell);
+    // The 2×2×2 cubes whose centers are closest; seeds stay within the
+    // middle half of their cube, so the nearest two are almost always there
+    let corner = p.clone().map(|c| (c - 0.5).floor());
+
+    let (mut nearest, mut second) = (Tree::constant(f64::INFINITY), Tree::constant(f64::INFINITY));
+    for offset in (0..8).map(|i| [i & 1, i >> 1 & 1, i >> 2].map(f64::from)) {
+        let at: [Tree; 3] = std::array::from_fn(|a| corner[a].clone() + offset[a]);
+        let distance = (0..3)
+            .map(|a| (at[a].clone() + 0.25 + hash(&at, a as f64) * 0.5 - p[a].clone()).square())
+            .reduce(|x, y| x + y)
+            .unwrap()
+            .sqrt();
+        second = second.min(nearest.max(distance.clone()));
+        nearest = nearest.min(distance);
+    }
+    let walls = (second - nearest) * (cell / 2.0) - wall / 2.0;
+    Ok(shape.max(walls))
+}
+
+/// Pseudo-random number in `[0, 1)` for a lattice point, different for each `seed`
+///
+/// The classic shader hash; the lattice point must be whole numbers so
+/// every evaluation of a cell sees the same values.
+fn hash(point: &[Tree; 3], seed: f64) -> Tree {
+    let mixed = (point[0].clone() * 127.1 + point[1].clone() * 311.7 + point[2].clone() * 74.7 + seed).sin() * 43758.5453;
+    mixed.clone() - mixed.floor()
+}
+
+/// Perlin gradient noise with features about `scale` apart, roughly in `[-1, 1]`
+///
+/// Each lattice corner gets a pseudo-random gradient, and the corners' ramps
+/// are blended with the quintic fade, so the noise is smooth everywhere.
+fn noise(scale: f64) -> Tree {
+    let p = [Tree::x(), Tree::y(), Tree::z()].map(|axis| axis / scale);
+    let cell = p.clone().map(|c| c.floor());
+    let f: [Tree; 3] = std::array::from_fn(|a| p[a].clone() - cell[a].clone());
+    let fade = f.clone().map(|t| t.clone() * t.clone() * t.clone() * (t.clone() * (t * 6.0 - 15.0) + 10.0));
+    let corner = |c: [f64; 3]| {
+        let at: [Tree; 3] = std::array::from_fn(|a| cell[a].clone() + c[a]);
+        (0..3)
+            .map(|a| (hash(&at, a as f64) * 2.0 - 1.0) * (f[a].clone() - c[a]))
+            .reduce(|x, y| x + y)
+            .unwrap()
+    };
+    let lerp = |a: Tree, b: Tree, t: &Tree| a.clone() + (b - a) * t.clone();
+    let edge = |y: f64, z: f64| lerp(corner([0.0, y, z]), corner([1.0, y, z]), &fade[0]);
+    let face = |z: f64| lerp(edge(0.0, z), edge(1.0, z), &fade[1]);
+    lerp(face(0.0), face(1.0), &fade[2])
+}
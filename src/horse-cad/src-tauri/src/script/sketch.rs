@@ -0,0 +1,209 @@
+use fidget::{context::Tree, rhai::FromDynamic};
+use rhai::{Dynamic, Engine, EvalAltResult, NativeCallContext};
+
+use crate::number_from_dynamic;
+
+type ShapeResult = Result<Tree, Box<EvalAltResult>>;
+
+/// Line segments used for each quadratic curve when flattening outlines
+const QUAD_SEGMENTS: usize = 4;
+
+/// Line segments used for each cubic curve when flattening outlines
+const CUBIC_SEGMENTS: usize = 6;
+
+/// Register 2D sketch functions on `engine`
+///
+/// Sketches are trees in X and Y; `extrude` and `revolve` lift them into 3D.
+pub fn register(engine: &mut Engine) {
+    engine.register_fn("circle2d", |radius: Dynamic| circle2d([0.0; 2], &radius));
+    engine.register_fn("circle2d", |center: Dynamic, radius: Dynamic| {
+        circle2d(point_from_dynamic("circle2d center", center)?, &radius)
+    });
+    engine.register_fn("rect2d", |size: Dynamic| rect2d(size));
+    engine.register_fn("polygon2d", |points: Dynamic| polygon2d(points));
+    engine.register_fn("offset2d", |ctx: NativeCallContext, shape: Dynamic, distance: Dynamic| -> ShapeResult {
+        let distance = number_from_dynamic("offset2d distance", &distance)?;
+        Ok(Tree::from_dynamic(&ctx, shape, None)? - distance)
+    });
+    engine.register_fn("union2d", |ctx: NativeCallContext, a: Dynamic, b: Dynamic| -> ShapeResult {
+        Ok(Tree::from_dynamic(&ctx, a, None)?.min(Tree::from_dynamic(&ctx, b, None)?))
+    });
+    engine.register_fn("difference2d", |ctx: NativeCallContext, a: Dynamic, b: Dynamic| -> ShapeResult {
+        Ok(Tree::from_dynamic(&ctx, a, None)?.max(-Tree::from_dynamic(&ctx, b, None)?))
+    });
+    engine.register_fn("extrude", |ctx: NativeCallContext, shape: Dynamic, height: Dynamic| {
+        extrude(Tree::from_dynamic(&ctx, shape, None)?, &height)
+    });
+    engine.register_fn("loft", |ctx: NativeCallContext, a: Dynamic, b: Dynamic, height: Dynamic| {
+        loft(Tree::from_dynamic(&ctx, a, None)?, Tree::from_dynamic(&ctx, b, None)?, &height)
+    });
+    engine.register_fn("revolve", |ctx: NativeCallContext, shape: Dynamic, angle: Dynamic| {
+        revolve(Tree::from_dynamic(&ctx, shape, None)?, &angle)
+    });
+}
+
+/// Read an `[x, y]` point
+fn point_from_dynamic(name: &str, value: Dynamic) -> Result<[f64; 2], Box<EvalAltResult>> {
+    let array = value
+        .into_array()
+        .map_err(|_| format!("{} must be an array of two numbers", name))?;
+    if array.len() != 2 {
+        return Err(format!("{} must have exactly two elements", name).into());
+    }
+    Ok([number_from_dynamic(name, &array[0])?, number_from_dynamic(name, &array[1])?])
+}
+
+/// 1 where `a < b` and 0 elsewhere
+fn less_than<T: Into<Tree>>(a: Tree, b: T) -> Tree {
+    b.into().compare(a).max(0.0)
+}
+
+/// `circle2d([x, y], radius)`: disc, centered on the origin when no center is given
+fn circle2d(center: [f64; 2], radius: &Dynamic) -> ShapeResult {
+    let radius = number_from_dynamic("circle2d radius", radius)?;
+    if radius <= 0.0 {
+        return Err("circle2d radius must be greater than zero".into());
+    }
+    Ok(((Tree::x() - center[0]).square() + (Tree::y() - center[1]).square()).sqrt() - radius)
+}
+
+/// `rect2d([width, height])`: rectangle centered on the origin
+fn rect2d(size: Dynamic) -> ShapeResult {
+    let [w, h] = point_from_dynamic("rect2d size", size)?;
+    if w <= 0.0 || h <= 0.0 {
+        return Err("rect2d size must be positive on both axes".into());
+    }
+    let qx = Tree::x().abs() - w / 2.0;
+    let qy = Tree::y().abs() - h / 2.0;
+    let outside = (qx.max(0.0).square() + qy.max(0.0).square()).sqrt();
+    Ok(outside + qx.max(qy).min(0.0))
+}
+
+/// `polygon2d([[x, y], ...])`: closed polygon through the points, in either winding
+fn polygon2d(points: Dynamic) -> ShapeResult {
+    let points = points
+        .into_array()
+        .map_err(|_| "polygon2d points must be an array of [x, y] points")?
+        .into_iter()
+        .map(|p| point_from_dynamic("polygon2d point", p))
+        .collect::<Result<Vec<_>, _>>()?;
+    if points.len() < 3 {
+        return Err("polygon2d needs at least three points".into());
+    }
+    contours_sdf(&[points]).ok_or_else(|| "polygon2d points must not all be the same".into())
+}
+
+/// Distance field of closed contours, filled with the even-odd rule
+///
+/// Contours inside other contours become holes, which is how glyph and SVG
+/// outlines describe them.  Returns `None` if there are no edges.
+pub(crate) fn contours_sdf(contours: &[Vec<[f64; 2]>]) -> Option<Tree> {
+    let (x, y) = (Tree::x(), Tree::y());
+    let mut distance_sq: Option<Tree> = None;
+    let mut crossings = Tree::constant(0.0);
+    for points in contours {
+        for (i, &[ax, ay]) in points.iter().enumerate() {
+            let [bx, by] = points[(i + 1) % points.len()];
+            let (ex, ey) = (bx - ax, by - ay);
+            let length_sq = ex * ex + ey * ey;
+            if length_sq == 0.0 {
+                continue;
+            }
+
+            // Squared distance to the edge, clamped to its ends
+            let (px, py) = (x.clone() - ax, y.clone() - ay);
+            let t = ((px.clone() * ex + py.clone() * ey) / length_sq).max(0.0).min(1.0);
+            let d = (px - t.clone() * ex).square() + (py - t * ey).square();
+            distance_sq = Some(match distance_sq {
+                Some(prev) => prev.min(d),
+                None => d,
+            });
+
+            // Count edges crossed by a ray towards +X to tell inside from outside
+            if ey != 0.0 {
+                let crossing_x = (y.clone() - ay) * (ex / ey) + ax;
+                let in_span = (1.0 - less_than(y.clone(), ay.min(by))) * less_than(y.clone(), ay.max(by));
+                crossings += in_span * less_than(x.clone(), crossing_x);
+            }
+        }
+    }
+    let sign = 1.0 - crossings.modulo(2.0) * 2.0;
+    Some(sign * distance_sq?.sqrt())
+}
+
+/// Flatten a quadratic curve from the end of `contour` onto it
+pub(crate) fn push_quad(contour: &mut Vec<[f64; 2]>, p1: [f64; 2], p2: [f64; 2]) {
+    let Some(&p0) = contour.last() else { return };
+    for i in 1..=QUAD_SEGMENTS {
+        let t = i as f64 / QUAD_SEGMENTS as f64;
+        let u = 1.0 - t;
+        contour.push(std::array::from_fn(|k| u * u * p0[k] + 2.0 * u * t * p1[k] + t * t * p2[k]));
+    }
+}
+
+/// Flatten a cubic curve from the end of `contour` onto it
+pub(crate) fn push_cubic(contour: &mut Vec<[f64; 2]>, p1: [f64; 2], p2: [f64; 2], p3: [f64; 2]) {
+    let Some(&p0) = contour.last() else { return };
+    for i in 1..=CUBIC_SEGMENTS {
+        let t = i as f64 / CUBIC_SEGMENTS as f64;
+        let u = 1.0 - t;
+        contour.push(std::array::from_fn(|k| {
+            u * u * u * p0[k] + 3.0 * u * u * t * p1[k] + 3.0 * u * t * t * p2[k] + t * t * t * p3[k]
+        }));
+    }
+}
+
+/// `extrude(shape2d, height)`: sweep a sketch along Z from `z = 0` to `z = height`
+fn extrude(shape: Tree, height: &Dynamic) -> ShapeResult {
+    let height = number_from_dynamic("extrude height", height)?;
+    if height <= 0.0 {
+        return Err("extrude height must be greater than zero".into());
+    }
+    Ok(prism(shape, height))
+}
+
+/// Bound a 2D distance to `0 <= z <= height`
+///
+/// This is the exact distance to the prism, so offsets and blends stay
+/// accurate.
+pub(crate) fn prism(d: Tree, height: f64) -> Tree {
+    let h = (Tree::z() - height / 2.0).abs() - height / 2.0;
+    let outside = (d.max(0.0).square() + h.max(0.0).square()).sqrt();
+    outside + d.max(h).min(0.0)
+}
+
+/// `loft(profile_a, profile_b, height)`: blend from one sketch at `z = 0` to another at `z = height`
+///
+/// The cross-section at each height interpolates the two distance fields,
+/// so profiles should overlap for a smooth transition.
+fn loft(a: Tree, b: Tree, height: &Dynamic) -> ShapeResult {
+    let height = number_from_dynamic("loft height", height)?;
+    if height <= 0.0 {
+        return Err("loft height must be greater than zero".into());
+    }
+    let t = (Tree::z() / height).max(0.0).min(1.0);
+    Ok(prism(a.clone() + (b - a) * t, height))
+}
+
+/// `revolve(shape2d, angle)`: spin a sketch around the Z axis by `angle` degrees
+///
+/// The sketch's X is the distance from the axis and its Y becomes Z; the
+/// sweep starts at +X and turns counter-clockwise seen from above.
+fn revolve(shape: Tree, angle: &Dynamic) -> ShapeResult {
+    let angle = number_from_dynamic("revolve angle", angle)?;
+    if angle <= 0.0 || angle > 360.0 {
+        return Err("revolve angle must be greater than 0 and at most 360 degrees".into());
+    }
+    let radial = (Tree::x().square() + Tree::y().square()).sqrt();
+    let solid = shape.remap_xyz(radial, Tree::z(), Tree::constant(0.0));
+    if angle == 360.0 {
+        return Ok(solid);
+    }
+
+    // Clip to the wedge between the start and end half-planes
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let start = -Tree::y();
+    let end = Tree::y() * cos - Tree::x() * sin;
+    let wedge = if angle <= 180.0 { start.max(end) } else { start.min(end) };
+    Ok(solid.max(wedge))
+}
@@ -0,0 +1,95 @@
+use fidget::context::Tree;
+use rhai::{Engine, EvalAltResult};
+
+use super::imports::DocumentFiles;
+use crate::export::stl::parse_stl;
+use crate::mesh::distance::{distance_grid, DistanceGrid};
+
+/// Samples along the longest side of an imported mesh
+///
+/// Detail smaller than about one sample is lost; more samples make the
+/// tree larger and slower to mesh.
+const MAX_SAMPLES: usize = 32;
+
+/// Register `import_stl` on `engine`, loading meshes through `files`
+pub fn register(engine: &mut Engine, files: DocumentFiles) {
+    engine.register_fn("import_stl", move |path: &str| -> Result<Tree, Box<EvalAltResult>> {
+        let mesh = parse_stl(&files.read(path)?).map_err(|e| format!("failed to parse STL '{}': {}", path, e))?;
+        let grid = distance_grid(&mesh, MAX_SAMPLES).ok_or_else(|| format!("STL '{}' has no volume", path))?;
+        Ok(grid_tree(&grid))
+    });
+}
+
+/// `import_stl(path)`: approximate distance field of a closed STL mesh
+///
+/// The field is interpolated from a grid of samples, so edges are rounded
+/// off at the sample spacing.  Distances are accurate a few samples deep
+/// either side of the surface, which is enough to offset or shell the
+/// model by about that much.
+fn grid_tree(grid: &DistanceGrid) -> Tree {
+    // Keep to the sampled box, where the field is interpolated rather than extrapolated
+    let clamp = |axis: Tree, a: usize| {
+        let hi = grid.origin[a] + (grid.dims[a] - 1) as f64 * grid.spacing;
+        axis.max(grid.origin[a]).min(hi)
+    };
+    let p = [clamp(Tree::x(), 0), clamp(Tree::y(), 1), clamp(Tree::z(), 2)];
+    let last = grid.dims.map(|d| d - 1);
+    // Shifted so selected branches are never zero; see `select`
+    let offset = 2.0 * grid.band;
+    select(grid, &p, [0; 3], last, offset) - offset
+}
+
+/// Pick the trilinear patch under `p` among the cells between samples `lo` and `hi`
+///
+/// Each split is a short-circuiting `and`/`or` on which side of the split
+/// `p` falls, so meshing can drop the cells it isn't in.  Where it can't
+/// yet, the result is clamped to the range's samples, which bound every
+/// patch in it; otherwise the `and` would widen the interval to zero and
+/// no region could be proven empty or full.  Ranges with a single value
+/// are a constant, so only cells near the surface cost anything.
+fn select(grid: &DistanceGrid, p: &[Tree; 3], lo: [usize; 3], hi: [usize; 3], offset: f64) -> Tree {
+    let (min, max) = sample_range(grid, lo, hi);
+    if min == max {
+        return Tree::constant(min + offset);
+    }
+    let axis = (0..3).max_by_key(|&a| hi[a] - lo[a]).unwrap();
+    if hi[axis] - lo[axis] == 1 {
+        return patch(grid, p, lo) + offset;
+    }
+    let mid = (lo[axis] + hi[axis]).div_ceil(2);
+    let (mut lo_hi, mut hi_lo) = (hi, lo);
+    lo_hi[axis] = mid;
+    hi_lo[axis] = mid;
+    let split = grid.origin[axis] + mid as f64 * grid.spacing;
+    let below = Tree::constant(split).compare(p[axis].clone()).max(0.0);
+    let picked = below.and(select(grid, p, lo, lo_hi, offset)).or(select(grid, p, hi_lo, hi, offset));
+    picked.max(min + offset).min(max + offset)
+}
+
+/// Smallest and largest sample in a range
+fn sample_range(grid: &DistanceGrid, lo: [usize; 3], hi: [usize; 3]) -> (f64, f64) {
+    let mut range = (f64::INFINITY, f64::NEG_INFINITY);
+    for k in lo[2]..=hi[2] {
+        for j in lo[1]..=hi[1] {
+            for i in lo[0]..=hi[0] {
+                let value = grid.get([i, j, k]);
+                range = (range.0.min(value), range.1.max(value));
+            }
+        }
+    }
+    range
+}
+
+/// Trilinear interpolation across the cell whose lowest sample is `at`
+fn patch(grid: &DistanceGrid, p: &[Tree; 3], at: [usize; 3]) -> Tree {
+    let [u, v, w]: [Tree; 3] =
+        std::array::from_fn(|a| (p[a].clone() - (grid.origin[a] + at[a] as f64 * grid.spacing)) / grid.spacing);
+    let c = |di: usize, dj: usize, dk: usize| grid.get([at[0] + di, at[1] + dj, at[2] + dk]);
+    let lerp = |a: Tree, b: Tree, t: &Tree| a.clone() + (b - a) * t.clone();
+    let edge = |dj: usize, dk: usize| {
+        let a = c(0, dj, dk);
+        u.clone() * (c(1, dj, dk) - a) + a
+    };
+    let face = |dk: usize| lerp(edge(0, dk), edge(1, dk), &v);
+    lerp(face(0), face(1), &w)
+}
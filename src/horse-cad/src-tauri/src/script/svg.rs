@@ -0,0 +1,65 @@
+use fidget::context::Tree;
+use rhai::{Engine, EvalAltResult};
+use usvg::tiny_skia_path::{PathSegment, Point};
+use usvg::{Group, Node, Options, Transform};
+
+use super::imports::DocumentFiles;
+use super::sketch::{contours_sdf, push_cubic, push_quad};
+
+/// Register `import_svg` on `engine`, loading files through `files`
+pub fn register(engine: &mut Engine, files: DocumentFiles) {
+    engine.register_fn("import_svg", move |path: &str| svg_shape(&files.read(path)?, path));
+}
+
+/// `import_svg(path)`: 2D shape from the paths in an SVG file
+///
+/// One SVG user unit is one model unit, with Y flipped so the drawing
+/// stays upright.  Every path is treated as a closed outline, filled with
+/// the even-odd rule whether or not it has a fill, so both logos and
+/// stroked laser-cut profiles work; text must be converted to paths first.
+fn svg_shape(data: &[u8], path: &str) -> Result<Tree, Box<EvalAltResult>> {
+    let tree = usvg::Tree::from_data(data, &Options::default())
+        .map_err(|e| format!("failed to parse SVG '{}': {}", path, e))?;
+    let mut contours = Vec::new();
+    collect_contours(tree.root(), &mut contours);
+    contours_sdf(&contours).ok_or_else(|| format!("SVG '{}' has no paths", path).into())
+}
+
+fn collect_contours(group: &Group, contours: &mut Vec<Vec<[f64; 2]>>) {
+    for node in group.children() {
+        match node {
+            Node::Group(group) => collect_contours(group, contours),
+            Node::Path(path) => {
+                let transform = path.abs_transform();
+                for segment in path.data().segments() {
+                    let p = |point: Point| map_point(transform, point);
+                    match segment {
+                        PathSegment::MoveTo(start) => contours.push(vec![p(start)]),
+                        PathSegment::LineTo(end) => {
+                            if let Some(contour) = contours.last_mut() {
+                                contour.push(p(end));
+                            }
+                        }
+                        PathSegment::QuadTo(p1, p2) => {
+                            if let Some(contour) = contours.last_mut() {
+                                push_quad(contour, p(p1), p(p2));
+                            }
+                        }
+                        PathSegment::CubicTo(p1, p2, p3) => {
+                            if let Some(contour) = contours.last_mut() {
+                                push_cubic(contour, p(p1), p(p2), p(p3));
+                            }
+                        }
+                        PathSegment::Close => {}
+                    }
+                }
+            }
+            Node::Image(_) | Node::Text(_) => {}
+        }
+    }
+}
+
+fn map_point(transform: Transform, mut point: Point) -> [f64; 2] {
+    transform.map_point(&mut point);
+    [f64::from(point.x), -f64::from(point.y)]
+}
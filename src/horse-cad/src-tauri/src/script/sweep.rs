@@ -0,0 +1,133 @@
+use fidget::{context::Tree, rhai::FromDynamic};
+use rhai::{Array, Dynamic, Engine, EvalAltResult, NativeCallContext};
+
+use crate::{number_from_dynamic, vec3_from_dynamic};
+
+type Vec3 = [f64; 3];
+
+/// Register `sweep` and path helpers on `engine`
+pub fn register(engine: &mut Engine) {
+    engine.register_fn("sweep", |ctx: NativeCallContext, profile: Dynamic, path: Dynamic| {
+        sweep(Tree::from_dynamic(&ctx, profile, None)?, path_points("sweep path", path)?)
+    });
+    engine.register_fn("bezier_path", |points: Dynamic, segments: Dynamic| {
+        bezier_path(path_points("bezier_path points", points)?, &segments)
+    });
+}
+
+fn sub(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: Vec3, b: Vec3) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: Vec3, b: Vec3) -> Vec3 {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn normalize(a: Vec3) -> Vec3 {
+    let length = dot(a, a).sqrt();
+    a.map(|c| c / length)
+}
+
+/// Rotate `v` about the unit `axis` by the angle with the given sine and cosine
+fn rotate(v: Vec3, axis: Vec3, sin: f64, cos: f64) -> Vec3 {
+    let k = cross(axis, v);
+    let along = dot(axis, v) * (1.0 - cos);
+    std::array::from_fn(|i| v[i] * cos + k[i] * sin + axis[i] * along)
+}
+
+/// Distance along `direction` from `origin` to the current point, as a tree
+fn project(origin: Vec3, direction: Vec3) -> Tree {
+    (Tree::x() - origin[0]) * direction[0] + (Tree::y() - origin[1]) * direction[1] + (Tree::z() - origin[2]) * direction[2]
+}
+
+/// Read an array of `[x, y, z]` points, dropping repeats
+fn path_points(name: &str, value: Dynamic) -> Result<Vec<Vec3>, Box<EvalAltResult>> {
+    let mut points: Vec<Vec3> = Vec::new();
+    for point in value.into_array().map_err(|_| format!("{} must be an array of [x, y, z] points", name))? {
+        let point = vec3_from_dynamic(name, point)?.map(f64::from);
+        if points.last() != Some(&point) {
+            points.push(point);
+        }
+    }
+    if points.len() < 2 {
+        return Err(format!("{} needs at least two distinct points", name).into());
+    }
+    Ok(points)
+}
+
+/// `sweep(profile2d, [[x, y, z], ...])`: sweep a sketch along a polyline
+///
+/// The sketch's Y starts out pointing as close to +Z as the path allows,
+/// with X to the left of the direction of travel, and the frame turns with
+/// the path without twisting.  Segments are mitered at the joints and the
+/// ends are cut square.
+fn sweep(profile: Tree, points: Vec<Vec3>) -> Result<Tree, Box<EvalAltResult>> {
+    let tangents: Vec<Vec3> = points.windows(2).map(|w| normalize(sub(w[1], w[0]))).collect();
+    if tangents.windows(2).any(|t| dot(t[0], t[1]) < -0.999) {
+        return Err("sweep path doubles back on itself".into());
+    }
+
+    // Start with the up vector closest to +Z, falling back to +X for vertical paths
+    let first = tangents[0];
+    let reference = if first[2].abs() > 0.999 { [1.0, 0.0, 0.0] } else { [0.0, 0.0, 1.0] };
+    let mut up = normalize(sub(reference, first.map(|c| c * dot(reference, first))));
+
+    let mut shape: Option<Tree> = None;
+    for (i, &t) in tangents.iter().enumerate() {
+        if i > 0 {
+            // Carry the frame across the joint by the same rotation as the tangent
+            let previous = tangents[i - 1];
+            let axis = cross(previous, t);
+            let sin = dot(axis, axis).sqrt();
+            if sin > 1e-12 {
+                up = normalize(rotate(up, axis.map(|c| c / sin), sin, dot(previous, t)));
+            }
+        }
+        let left = cross(up, t);
+        let (start, end) = (points[i], points[i + 1]);
+        let section = profile.remap_xyz(project(start, left), project(start, up), Tree::constant(0.0));
+
+        // Miter planes halfway between neighbouring segments, or square ends
+        let start_normal = match i.checked_sub(1) {
+            Some(previous) => normalize(std::array::from_fn(|k| tangents[previous][k] + t[k])),
+            None => t,
+        };
+        let end_normal = match tangents.get(i + 1) {
+            Some(&next) => normalize(std::array::from_fn(|k| t[k] + next[k])),
+            None => t,
+        };
+        let segment = section.max(-project(start, start_normal)).max(project(end, end_normal));
+        shape = Some(match shape {
+            Some(shape) => shape.min(segment),
+            None => segment,
+        });
+    }
+    Ok(shape.unwrap())
+}
+
+/// `bezier_path([[x, y, z], ...], segments)`: points along a Bezier curve, for `sweep`
+///
+/// The first and last points are the curve's ends and the rest are its
+/// control points; the curve is split into `segments` straight pieces.
+fn bezier_path(controls: Vec<Vec3>, segments: &Dynamic) -> Result<Array, Box<EvalAltResult>> {
+    let segments = number_from_dynamic("bezier_path segments", segments)?;
+    if segments < 1.0 || segments.fract() != 0.0 {
+        return Err("bezier_path segments must be a positive whole number".into());
+    }
+    let segments = segments as usize;
+    Ok((0..=segments)
+        .map(|i| {
+            // de Casteljau's algorithm
+            let t = i as f64 / segments as f64;
+            let mut points = controls.clone();
+            while points.len() > 1 {
+                points = points.windows(2).map(|w| std::array::from_fn(|k| w[0][k] + (w[1][k] - w[0][k]) * t)).collect();
+            }
+            Dynamic::from_array(points[0].iter().map(|&c| Dynamic::from_float(c)).collect())
+        })
+        .collect())
+}
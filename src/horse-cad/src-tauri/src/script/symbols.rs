@@ -0,0 +1,82 @@
+use rhai::{ASTFlags, ASTNode, Position, Stmt, AST};
+use serde::{Deserialize, Serialize};
+
+/// A user-defined name in a script, for autocomplete and outline views
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptSymbol {
+    pub name: String,
+    /// "function", "variable" or "constant"
+    pub kind: String,
+    /// 1-based line of the definition
+    pub line: usize,
+    /// 1-based column of the definition
+    pub column: usize,
+    /// Function whose body defines the symbol; `None` at the top level
+    pub scope: Option<String>,
+    /// Parameter names, for functions
+    pub params: Vec<String>,
+}
+
+/// Collect the functions, variables and constants defined in `ast`
+///
+/// `ast` should be compiled without optimization, or constants may already
+/// have been folded away.  Functions are listed first, then top-level
+/// variables, then variables inside function bodies.
+pub fn script_symbols(ast: &AST, code: &str) -> Vec<ScriptSymbol> {
+    let functions: Vec<_> = ast.iter_fn_def().collect();
+    let mut symbols: Vec<ScriptSymbol> = functions
+        .iter()
+        .map(|f| {
+            let start = f.body.span().start();
+            let (line, column) = fn_name_location(code, &f.name, start);
+            ScriptSymbol {
+                name: f.name.to_string(),
+                kind: "function".to_string(),
+                line,
+                column,
+                scope: None,
+                params: f.params.iter().map(|p| p.to_string()).collect(),
+            }
+        })
+        .collect();
+
+    ast.walk(&mut |path: &[ASTNode]| {
+        if let Some(ASTNode::Stmt(Stmt::Var(decl, flags, _))) = path.last() {
+            let ident = &decl.0;
+            let scope = functions.iter().find(|f| {
+                let span = f.body.span();
+                location(span.start()) <= location(ident.pos) && location(ident.pos) <= location(span.end())
+            });
+            symbols.push(ScriptSymbol {
+                name: ident.name.to_string(),
+                kind: if flags.contains(ASTFlags::CONSTANT) { "constant" } else { "variable" }.to_string(),
+                line: ident.pos.line().unwrap_or(1),
+                column: ident.pos.position().unwrap_or(1),
+                scope: scope.map(|f| f.name.to_string()),
+                params: Vec::new(),
+            });
+        }
+        true
+    });
+    symbols
+}
+
+/// Position as a comparable `(line, column)` pair
+fn location(pos: Position) -> (usize, usize) {
+    (pos.line().unwrap_or(0), pos.position().unwrap_or(0))
+}
+
+/// Find `fn name` on the lines up to a function's body
+///
+/// Rhai doesn't record where a function's name is, only its body; falls back
+/// to the body's position if the name can't be found.
+fn fn_name_location(code: &str, name: &str, body: Position) -> (usize, usize) {
+    let body_line = body.line().unwrap_or(1);
+    let pattern = format!("fn {}", name);
+    code.lines()
+        .enumerate()
+        .take(body_line)
+        .filter_map(|(i, text)| text.find(&pattern).map(|col| (i + 1, text[..col].chars().count() + 4)))
+        .last()
+        .unwrap_or((body_line, body.position().unwrap_or(1)))
+}
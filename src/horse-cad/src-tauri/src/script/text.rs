@@ -0,0 +1,92 @@
+use fidget::context::Tree;
+use rhai::{Dynamic, Engine, EvalAltResult};
+use ttf_parser::{Face, OutlineBuilder};
+
+use super::imports::DocumentFiles;
+use super::sketch::{contours_sdf, push_cubic, push_quad};
+use crate::number_from_dynamic;
+
+/// Register `text` on `engine`, loading fonts through `files`
+pub fn register(engine: &mut Engine, files: DocumentFiles) {
+    engine.register_fn("text", move |text: &str, font: &str, size: Dynamic| {
+        let size = number_from_dynamic("text size", &size)?;
+        text_shape(text, &files.read(font)?, font, size)
+    });
+}
+
+/// `text(text, font, size)`: 2D outline of `text` set in a TrueType or OpenType font
+///
+/// `size` is the font's em height in model units.  The first line's
+/// baseline is on `y = 0` and starts at `x = 0`; further lines go below it.
+fn text_shape(text: &str, data: &[u8], font: &str, size: f64) -> Result<Tree, Box<EvalAltResult>> {
+    if size <= 0.0 {
+        return Err("text size must be greater than zero".into());
+    }
+    let face = Face::parse(data, 0).map_err(|e| format!("failed to parse font '{}': {}", font, e))?;
+    let scale = size / f64::from(face.units_per_em());
+    let line_height = f64::from(face.ascender() - face.descender() + face.line_gap()) * scale;
+
+    let mut outline = Outline { contours: Vec::new(), scale, origin: [0.0; 2] };
+    for (row, line) in text.lines().enumerate() {
+        outline.origin = [0.0, -(row as f64) * line_height];
+        for c in line.chars() {
+            let glyph = face
+                .glyph_index(c)
+                .ok_or_else(|| format!("font '{}' has no glyph for '{}'", font, c))?;
+            face.outline_glyph(glyph, &mut outline);
+            outline.origin[0] += f64::from(face.glyph_hor_advance(glyph).unwrap_or(0)) * scale;
+        }
+    }
+    contours_sdf(&outline.contours).ok_or_else(|| "text has no visible characters".into())
+}
+
+/// Collects glyph outlines as polygons, flattening curves into segments
+struct Outline {
+    contours: Vec<Vec<[f64; 2]>>,
+    scale: f64,
+    origin: [f64; 2],
+}
+
+impl Outline {
+    fn point(&self, x: f32, y: f32) -> [f64; 2] {
+        [self.origin[0] + f64::from(x) * self.scale, self.origin[1] + f64::from(y) * self.scale]
+    }
+
+    fn contour(&mut self) -> &mut Vec<[f64; 2]> {
+        if self.contours.is_empty() {
+            self.contours.push(vec![self.origin]);
+        }
+        self.contours.last_mut().unwrap()
+    }
+}
+
+impl OutlineBuilder for Outline {
+    fn move_to(&mut self, x: f32, y: f32) {
+        let start = self.point(x, y);
+        self.contours.push(vec![start]);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        let end = self.point(x, y);
+        self.contour().push(end);
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let [p1, p2] = [self.point(x1, y1), self.point(x, y)];
+        push_quad(self.contour(), p1, p2);
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let [p1, p2, p3] = [self.point(x1, y1), self.point(x2, y2), self.point(x, y)];
+        push_cubic(self.contour(), p1, p2, p3);
+    }
+
+    fn close(&mut self) {
+        // Contours are closed implicitly; drop the duplicate end point if any
+        if let Some(contour) = self.contours.last_mut() {
+            if contour.len() > 1 && contour.first() == contour.last() {
+                contour.pop();
+            }
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use fidget::context::Tree;
+use rhai::{Engine, EvalAltResult};
+
+use super::imports::DocumentFiles;
+use crate::export::vm::{parse_vm, VmShapes};
+
+/// Register `import_shape` on `engine`, loading compiled shapes through `files`
+///
+/// `import_shape(path)` returns everything the saved script drew, and
+/// `import_shape(path, part)` a single named part of it.  The shape is the
+/// saved expression itself, so it's exact and costs nothing to evaluate
+/// beyond what the original script's shape did.
+pub fn register(engine: &mut Engine, files: DocumentFiles) {
+    let load = move |path: &str| -> Result<VmShapes, Box<EvalAltResult>> {
+        Ok(parse_vm(&files.read(path)?).map_err(|e| format!("failed to load compiled shape '{}': {}", path, e))?)
+    };
+    let load_part = load.clone();
+    engine.register_fn("import_shape", move |path: &str| -> Result<Tree, Box<EvalAltResult>> {
+        Ok(load(path)?.root)
+    });
+    engine.register_fn("import_shape", move |path: &str, part: &str| -> Result<Tree, Box<EvalAltResult>> {
+        let shapes = load_part(path)?;
+        let names = shapes.parts.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>().join(", ");
+        shapes
+            .parts
+            .into_iter()
+            .find(|(name, _)| name == part)
+            .map(|(_, tree)| tree)
+            .ok_or_else(|| format!("compiled shape '{}' has no part '{}' (parts: {})", path, part, names).into())
+    });
+}
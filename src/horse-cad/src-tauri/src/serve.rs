@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex};
+
+use base64::Engine as _;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::export::{precision::PrecisionOptions, ExportFormat, ExportOptions, ModelUnit};
+use crate::horsi::{read_project_settings, ProjectSettings};
+use crate::host::{ConsoleHost, Host, LogSink};
+use crate::mesh::measure::mass_properties;
+use crate::mesh::normals::NormalOptions;
+use crate::plugins::PluginFunction;
+use crate::script::extensions::ExtensionModules;
+use crate::state::{CancelToken, MeshOptionsState, ScriptCache, ScriptLimits};
+use crate::{
+    build_model, compile_mesh, BuildSettings, CompileError, CompileProgress, CompileTimings, CompiledMesh, LogEntry,
+    ScriptSource,
+};
+
+/// Id given to the script cache for every request, since requests aren't documents
+const SERVE_DOCUMENT_ID: &str = "serve";
+
+/// The script a request works on: its code, or the file to read it from
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ScriptInput {
+    script: Option<String>,
+    /// Where the script is saved, for imports and the settings pinned in it
+    path: Option<String>,
+    settings: BuildSettings,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+enum Command {
+    /// Mesh a script and report its measurements and checks
+    Compile {
+        #[serde(flatten)]
+        input: ScriptInput,
+    },
+    /// Mesh a script and write it to `output`, or return it base64-encoded as `data`
+    Export {
+        #[serde(flatten)]
+        input: ScriptInput,
+        output: Option<String>,
+        format: Option<ExportFormat>,
+        unit: Option<ModelUnit>,
+        /// Shading of the normals in OBJ, PLY and GLB
+        normals: Option<NormalOptions>,
+        /// Rounding and snapping of the written coordinates
+        precision: Option<PrecisionOptions>,
+    },
+    /// Mesh a script and compute its mass properties, at `density` per cubic unit
+    Analyze {
+        #[serde(flatten)]
+        input: ScriptInput,
+        density: Option<f64>,
+    },
+    /// Stop the request with id `target`
+    Cancel { target: Value },
+}
+
+/// One line of input
+#[derive(Debug, Deserialize)]
+struct Request {
+    /// Echoed in every message about the request
+    #[serde(default)]
+    id: Value,
+    #[serde(flatten)]
+    command: Command,
+}
+
+/// Standard output, shared so each message is written as one whole line
+#[derive(Clone)]
+struct Output(Arc<Mutex<std::io::Stdout>>);
+
+impl Output {
+    fn send(&self, message: &Value) {
+        let mut stdout = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = writeln!(stdout, "{}", message).and_then(|_| stdout.flush());
+    }
+}
+
+/// Host for one request, sending its logs and progress as messages carrying the request's id
+#[derive(Clone)]
+struct RequestHost {
+    id: Value,
+    out: Output,
+    shared: Arc<ConsoleHost>,
+}
+
+impl RequestHost {
+    fn send(&self, kind: &str, mut body: Value) {
+        body["id"] = self.id.clone();
+        body["type"] = kind.into();
+        self.out.send(&body);
+    }
+}
+
+impl Host for RequestHost {
+    fn log(&self, entry: &LogEntry) {
+        self.send("log", json!({ "level": entry.level, "message": entry.message, "source": entry.source }));
+    }
+
+    fn progress(&self, progress: &CompileProgress) {
+        self.send("progress", json!({ "stage": progress.stage, "percent": progress.percent }));
+    }
+
+    fn log_sink(&self) -> LogSink {
+        let host = self.clone();
+        Arc::new(move |entry| host.log(entry))
+    }
+
+    fn script_cache(&self) -> &ScriptCache {
+        self.shared.script_cache()
+    }
+
+    fn mesh_options(&self) -> &MeshOptionsState {
+        self.shared.mesh_options()
+    }
+
+    fn script_limits(&self) -> ScriptLimits {
+        self.shared.script_limits()
+    }
+
+    fn extensions(&self) -> Arc<ExtensionModules> {
+        self.shared.extensions()
+    }
+
+    fn plugin_functions(&self) -> Arc<Vec<PluginFunction>> {
+        self.shared.plugin_functions()
+    }
+}
+
+/// Run `horsecad --serve`: read requests from stdin, one JSON object per line, until it closes
+///
+/// Each request runs on its own thread, so a `cancel` can reach one that
+/// is still compiling.  Every output line is a JSON object with the
+/// request's `id` and a `type`: `log` and `progress` while it runs, then
+/// one `result` or `error`.  Lines that can't be parsed get an `error`
+/// with a null id, and a request reusing the id of one still running gets
+/// an `error` without being run.  Once stdin closes, running requests are
+/// finished before exiting.
+pub fn run() -> ExitCode {
+    let out = Output(Arc::new(Mutex::new(std::io::stdout())));
+    let shared = Arc::new(ConsoleHost::new(false));
+    // Cancel tokens of running requests, by their id as JSON
+    let running: Arc<Mutex<HashMap<String, CancelToken>>> = Arc::default();
+    let mut workers = Vec::new();
+
+    for line in std::io::stdin().lock().lines() {
+        let line = match line {
+            Ok(line) if line.trim().is_empty() => continue,
+            Ok(line) => line,
+            Err(e) => {
+                eprintln!("error: can't read stdin: {}", e);
+                break;
+            }
+        };
+        let request = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => request,
+            Err(e) => {
+                out.send(&json!({ "id": null, "type": "error", "error": format!("Invalid request: {}", e) }));
+                continue;
+            }
+        };
+        let host = RequestHost { id: request.id, out: out.clone(), shared: shared.clone() };
+        if let Command::Cancel { target } = &request.command {
+            let token = running.lock().unwrap_or_else(|e| e.into_inner()).get(&target.to_string()).cloned();
+            if let Some(token) = &token {
+                token.cancel();
+            }
+            host.send("result", json!({ "cancelled": token.is_some() }));
+            continue;
+        }
+
+        let cancel = CancelToken::new();
+        // Requests without an id can't be cancelled, so several can run at once
+        let key = (!host.id.is_null()).then(|| host.id.to_string());
+        if let Some(key) = &key {
+            let mut running = running.lock().unwrap_or_else(|e| e.into_inner());
+            if running.contains_key(key) {
+                host.send("error", json!({ "error": format!("Request {} is still running", key) }));
+                continue;
+            }
+            running.insert(key.clone(), cancel.clone());
+        }
+        let running = running.clone();
+        workers.push(std::thread::spawn(move || {
+            match handle(&host, request.command, &cancel) {
+                Ok(result) => host.send("result", result),
+                Err(e) => host.send("error", json!({ "error": e.message, "diagnostics": e.diagnostics })),
+            }
+            if let Some(key) = &key {
+                running.lock().unwrap_or_else(|e| e.into_inner()).remove(key);
+            }
+        }));
+        workers.retain(|worker| !worker.is_finished());
+    }
+
+    for worker in workers {
+        let _ = worker.join();
+    }
+    ExitCode::SUCCESS
+}
+
+/// The script's code, its request settings over those pinned in its file, and the rest of the pinned settings
+fn load_script(input: ScriptInput) -> Result<(ScriptSource, BuildSettings, ProjectSettings), CompileError> {
+    let project = input.path.as_deref().and_then(|path| read_project_settings(Path::new(path))).unwrap_or_default();
+    let code = match (input.script, &input.path) {
+        (Some(code), _) => code,
+        (None, Some(path)) => std::fs::read_to_string(path).map_err(|e| format!("Can't read {}: {}", path, e))?,
+        (None, None) => return Err("A request needs a script or a path".to_string().into()),
+    };
+    let settings = input.settings.or(&project.build);
+    Ok((ScriptSource::new(code, input.path.as_deref()), settings, project))
+}
+
+/// Mesh the script `input` names
+fn compile(
+    host: &RequestHost,
+    input: ScriptInput,
+    cancel: &CancelToken,
+    timings: &mut CompileTimings,
+) -> Result<CompiledMesh, CompileError> {
+    let (source, settings, _) = load_script(input)?;
+    compile_mesh(host, SERVE_DOCUMENT_ID, &source, &settings.params, &settings.mesh_request(), cancel, timings)
+}
+
+fn handle(host: &RequestHost, command: Command, cancel: &CancelToken) -> Result<Value, CompileError> {
+    let mut timings = CompileTimings::default();
+    match command {
+        Command::Compile { input } => {
+            let compiled = compile(host, input, cancel, &mut timings)?;
+            Ok(json!({
+                "triangle_count": compiled.mesh.triangles.len(),
+                "validation": compiled.validation,
+                "measurements": compiled.measurements,
+                "params": compiled.params,
+                "timings": timings,
+            }))
+        }
+        Command::Analyze { input, density } => {
+            let density = density.unwrap_or(1.0);
+            if density.is_nan() || density <= 0.0 {
+                return Err("Density must be greater than zero".to_string().into());
+            }
+            let compiled = compile(host, input, cancel, &mut timings)?;
+            let model = compiled.units.model_mesh(&compiled.mesh);
+            Ok(json!({
+                "measurements": compiled.measurements,
+                "mass": mass_properties(model.as_ref().unwrap_or(&compiled.mesh), density),
+                "validation": compiled.validation,
+                "timings": timings,
+            }))
+        }
+        Command::Export { input, output, format, unit, normals, precision } => {
+            let path = input.path.clone();
+            let (source, settings, project) = load_script(input)?;
+            let from_output = output.as_deref().and_then(|output| {
+                Path::new(output).extension().and_then(|e| e.to_str()).and_then(ExportFormat::from_extension)
+            });
+            let format = format.or(from_output).or(project.export_format).unwrap_or(ExportFormat::Stl);
+            let options = ExportOptions {
+                name: None,
+                unit: unit.or(project.unit),
+                script_name: path.as_deref().and_then(|path| Path::new(path).file_name()).map(|name| {
+                    name.to_string_lossy().into_owned()
+                }),
+                normals: normals.unwrap_or_default(),
+                precision: precision.unwrap_or_default(),
+            };
+            let model = build_model(
+                host,
+                SERVE_DOCUMENT_ID,
+                &source,
+                &settings.params,
+                &settings.mesh_request(),
+                settings.repair.unwrap_or(false),
+                settings.up_axis.unwrap_or_default(),
+                format,
+                &options,
+                None,
+                cancel,
+                &mut timings,
+            )?;
+            let mut result = json!({
+                "format": format,
+                "triangle_count": model.triangle_count,
+                "validation": model.validation,
+                "timings": timings,
+            });
+            match output {
+                Some(output) => {
+                    std::fs::write(&output, &model.data).map_err(|e| format!("Failed to write {}: {}", output, e))?;
+                    result["output"] = output.into();
+                }
+                None => result["data"] = base64::engine::general_purpose::STANDARD.encode(&model.data).into(),
+            }
+            Ok(result)
+        }
+        Command::Cancel { .. } => unreachable!("cancel requests are answered as they arrive"),
+    }
+}
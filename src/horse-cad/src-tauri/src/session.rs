@@ -0,0 +1,178 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::horsi::CompileDefaults;
+use crate::utils::file_utils::write_json;
+
+/// Label of the window the app starts with
+const MAIN_WINDOW: &str = "main";
+
+/// A document that was open when the app quit
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionDocument {
+    /// Label of the window it was open in
+    pub window: String,
+    /// The frontend's id for it within the window, if it gave one
+    pub document_id: Option<String>,
+    /// File it was opened from or last saved to
+    pub path: Option<String>,
+    /// Editor contents that hadn't been saved, as last autosaved
+    pub unsaved: Option<String>,
+    /// Settings it last compiled with, so it can compile again straight away
+    pub compile: Option<CompileDefaults>,
+    /// Parameter values of the last compile
+    pub params: HashMap<String, f64>,
+}
+
+/// What `session.json` keeps between runs
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    /// RFC 3339 time the app quit
+    pub saved_at: String,
+    pub documents: Vec<SessionDocument>,
+}
+
+/// The documents open in each window, saved when the app quits and offered back by `restore_session`
+///
+/// Closing a window closes its documents, unless it's the last one or the
+/// app is quitting, so the session is whatever was open at the end.  The
+/// file is removed once read, so a session is only ever restored once.
+pub struct SessionStore {
+    path: Option<PathBuf>,
+    /// Open documents by document key
+    documents: Mutex<BTreeMap<String, SessionDocument>>,
+    /// The last run's documents not yet handed back, by the label of the window they go to
+    previous: Mutex<Option<BTreeMap<String, Vec<SessionDocument>>>>,
+    saved_at: Option<String>,
+    assigned: AtomicBool,
+    exiting: AtomicBool,
+}
+
+impl SessionStore {
+    /// Read the session kept at `path`, if the last run saved one
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let session = path
+            .as_ref()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|text| serde_json::from_str::<Session>(&text).ok());
+        if let Some(path) = path.as_ref().filter(|_| session.is_some()) {
+            let _ = std::fs::remove_file(path);
+        }
+        let saved_at = session.as_ref().map(|s| s.saved_at.clone());
+        let previous = session.map(|session| {
+            let mut windows: BTreeMap<String, Vec<SessionDocument>> = BTreeMap::new();
+            for document in session.documents {
+                windows.entry(document.window.clone()).or_default().push(document);
+            }
+            windows
+        });
+        SessionStore {
+            path,
+            documents: Mutex::new(BTreeMap::new()),
+            previous: Mutex::new(previous),
+            saved_at,
+            assigned: AtomicBool::new(false),
+            exiting: AtomicBool::new(false),
+        }
+    }
+
+    /// Record the document at `key` as open, changing it with `update`
+    pub fn track(
+        &self,
+        key: &str,
+        window: &str,
+        document_id: Option<&str>,
+        update: impl FnOnce(&mut SessionDocument),
+    ) {
+        let mut documents = self.documents.lock().unwrap_or_else(|e| e.into_inner());
+        let document = documents.entry(key.to_string()).or_insert_with(|| SessionDocument {
+            window: window.to_string(),
+            document_id: document_id.map(str::to_string),
+            ..Default::default()
+        });
+        update(document);
+    }
+
+    /// Forget the document at `key`, which has been closed
+    pub fn close(&self, key: &str) {
+        self.documents.lock().unwrap_or_else(|e| e.into_inner()).remove(key);
+    }
+
+    /// Forget the documents of a window that was closed, unless the app is quitting
+    ///
+    /// `last` says no other window is open, in which case the app quits
+    /// next and the documents stay.
+    pub fn close_window(&self, label: &str, last: bool) {
+        if last || self.exiting.load(Ordering::Relaxed) {
+            return;
+        }
+        self.documents.lock().unwrap_or_else(|e| e.into_inner()).retain(|_, document| document.window != label);
+    }
+
+    /// Stop windows closing from here on from closing their documents
+    pub fn begin_exit(&self) {
+        self.exiting.store(true, Ordering::Relaxed);
+    }
+
+    /// Write the open documents for the next run, taking unsaved contents from `unsaved` by document key
+    pub fn save(&self, unsaved: impl Fn(&str) -> Option<String>) -> Result<(), String> {
+        let Some(path) = &self.path else { return Ok(()) };
+        let documents: Vec<SessionDocument> = self
+            .documents
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|(key, document)| SessionDocument { unsaved: unsaved(key), ..document.clone() })
+            .collect();
+        if documents.is_empty() {
+            return Ok(());
+        }
+        let session = Session { saved_at: chrono::Utc::now().to_rfc3339(), documents };
+        write_json(path, &session).map_err(|e| format!("Failed to save the session to {}: {}", path.display(), e))
+    }
+
+    /// Give the last run's windows to this run's, the first time it's called
+    ///
+    /// The main window's documents go to `first`, the window restoring the
+    /// session; each other window gets a new one from `open`, and its
+    /// documents are dropped if that fails.  Later calls do nothing.
+    pub fn assign_windows(&self, first: &str, mut open: impl FnMut() -> Option<String>) {
+        if self.assigned.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        let mut previous = self.previous.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(windows) = previous.take() else { return };
+        let mut assigned = BTreeMap::new();
+        for (label, documents) in windows {
+            let to = if label == MAIN_WINDOW { Some(first.to_string()) } else { open() };
+            if let Some(to) = to {
+                assigned.insert(to, documents);
+            }
+        }
+        *previous = Some(assigned);
+    }
+
+    /// Hand over the last run's documents for the window `label`, once `assign_windows` has run
+    pub fn take(&self, label: &str) -> Vec<SessionDocument> {
+        let taken =
+            self.previous.lock().unwrap_or_else(|e| e.into_inner()).as_mut().and_then(|windows| windows.remove(label));
+        taken
+            .unwrap_or_default()
+            .into_iter()
+            .map(|document| SessionDocument { window: label.to_string(), ..document })
+            .collect()
+    }
+
+    /// When the last run quit, if it left a session
+    pub fn saved_at(&self) -> Option<String> {
+        self.saved_at.clone()
+    }
+}
@@ -0,0 +1,271 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::menu::{CheckMenuItemBuilder, PredefinedMenuItem, Submenu};
+use tauri::{AppHandle, Manager, Wry};
+
+use crate::emit_log;
+use crate::export::{ExportFormat, UpAxis};
+use crate::http_api::{HttpApi, HttpApiOptions};
+use crate::log_history::{LogHistory, LogLevel};
+use crate::mesh::bed::DEFAULT_BED_SIZE;
+use crate::mesh::estimate::PrintProfile;
+use crate::mesh::limits::MeshLimits;
+use crate::mesh::quality::QualityPreset;
+use crate::recovery::{Recovery, DEFAULT_AUTOSAVE_INTERVAL_SECS};
+use crate::state::{MeshOptions, MeshOptionsState, SaveOptions, SaveOptionsState, ScriptLimits, ScriptLimitsState};
+use crate::utils::file_utils::{set_aside, write_json};
+use crate::window_state::WindowState;
+
+/// Menu id prefix of the profiles in the View menu's Limits submenu, followed by the profile's name
+pub const PROFILE_MENU_PREFIX: &str = "execution_profile_";
+
+/// Menu id of the Limits item that goes back to the limits outside any profile
+pub const NO_PROFILE_MENU_ID: &str = "no_execution_profile";
+
+/// Script and mesh limits compiles can be switched to together, such as tight ones while editing
+///
+/// Each compile takes the limits in force when it starts, so switching
+/// profiles only affects the compiles after it.  Zero means no limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExecutionProfile {
+    pub script_limits: ScriptLimits,
+    pub mesh_limits: MeshLimits,
+}
+
+/// User preferences kept between sessions
+///
+/// Missing fields take their defaults, so settings written by older
+/// versions still load.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Octree depth new documents compile at
+    pub default_depth: u8,
+    /// Format the export dialog starts on
+    pub default_export_format: ExportFormat,
+    /// Axis exported meshes have pointing up
+    pub export_up_axis: UpAxis,
+    /// Write a `.report.json` next to each exported mesh, unless the export says otherwise
+    pub export_reports: bool,
+    /// Printer build volume models are checked against, in millimeters
+    pub print_bed: [f32; 3],
+    /// Printer and filament that print estimates assume
+    pub print: PrintProfile,
+    /// Seconds between autosaves of unsaved buffers; 0 turns autosave off
+    pub autosave_interval_secs: u64,
+    pub mesh: MeshOptions,
+    pub script_limits: ScriptLimits,
+    /// Named limits to switch between, such as bounded ones for editing and unbounded ones for final exports
+    pub profiles: BTreeMap<String, ExecutionProfile>,
+    /// Profile in `profiles` compiles run under, or `None` for `script_limits` and the limits in `mesh`
+    pub profile: Option<String>,
+    /// Least severe log entries sent to the log panel
+    pub log_level: LogLevel,
+    pub save: SaveOptions,
+    /// Local HTTP server other tools can compile and export through; off by default
+    pub http_api: HttpApiOptions,
+    /// Let the viewport raymarch shapes on the GPU while it waits for a mesh; experimental
+    pub gpu_preview: bool,
+    /// Main window geometry from the last session, if it has closed before
+    pub window: Option<WindowState>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            default_depth: QualityPreset::default().settings().depth,
+            default_export_format: ExportFormat::Stl,
+            export_up_axis: UpAxis::Z,
+            export_reports: false,
+            print_bed: DEFAULT_BED_SIZE,
+            print: PrintProfile::default(),
+            autosave_interval_secs: DEFAULT_AUTOSAVE_INTERVAL_SECS,
+            mesh: MeshOptions::default(),
+            script_limits: ScriptLimits::default(),
+            profiles: default_profiles(),
+            profile: None,
+            log_level: LogLevel::default(),
+            save: SaveOptions::default(),
+            http_api: HttpApiOptions::default(),
+            gpu_preview: false,
+            window: None,
+        }
+    }
+}
+
+impl Settings {
+    /// Check values the rest of the app can't work with
+    pub fn validate(&self) -> Result<(), String> {
+        if self.default_depth == 0 {
+            return Err("The default octree depth must be greater than zero".to_string());
+        }
+        if !self.print_bed.iter().all(|&size| size.is_finite() && size > 0.0) {
+            return Err("Every side of the print bed must be greater than zero".to_string());
+        }
+        self.print.validate()?;
+        if let Some(name) = self.profile.as_ref().filter(|name| !self.profiles.contains_key(*name)) {
+            return Err(format!("There is no execution profile named '{}'", name));
+        }
+        if self.http_api.port == 0 {
+            return Err("The HTTP API port must be greater than zero".to_string());
+        }
+        Ok(())
+    }
+
+    /// Script and mesh limits compiles run under, from the chosen profile if there is one
+    pub fn limits(&self) -> (ScriptLimits, MeshLimits) {
+        match self.profile.as_ref().and_then(|name| self.profiles.get(name)) {
+            Some(profile) => (profile.script_limits, profile.mesh_limits),
+            None => (self.script_limits, self.mesh.limits),
+        }
+    }
+}
+
+/// Profiles settings start with: today's limits for editing, and none for final builds
+fn default_profiles() -> BTreeMap<String, ExecutionProfile> {
+    let unlimited = ExecutionProfile {
+        script_limits: ScriptLimits { max_operations: 0, timeout_ms: 0 },
+        mesh_limits: MeshLimits { max_triangles: 0, max_memory_mb: 0 },
+    };
+    BTreeMap::from([("interactive".to_string(), ExecutionProfile::default()), ("final".to_string(), unlimited)])
+}
+
+/// Managed state holding the settings, stored as JSON in the app config folder
+pub struct SettingsStore {
+    path: Option<PathBuf>,
+    settings: Mutex<Settings>,
+    /// The View menu's Limits submenu, kept showing the chosen profile
+    menu: Mutex<Option<Submenu<Wry>>>,
+}
+
+/// Set aside a settings file that couldn't be used, adding where it went to `problem`
+fn set_aside_note(path: &Path, problem: String) -> String {
+    match set_aside(path) {
+        Ok(backup) => format!("{}; the file was kept as {}", problem, backup.display()),
+        Err(e) => format!("{}; the file couldn't be kept aside: {}", problem, e),
+    }
+}
+
+impl SettingsStore {
+    /// Read the settings kept at `path`
+    ///
+    /// A missing file gives the defaults; an unreadable one does too, and is
+    /// moved to its backup so saving doesn't overwrite it, with the reason
+    /// returned so it can be logged once the app is running.
+    pub fn load(path: Option<PathBuf>) -> (Self, Option<String>) {
+        let (settings, problem) = match path.as_ref().map(|path| (path, std::fs::read_to_string(path))) {
+            Some((path, Ok(text))) => match serde_json::from_str::<Settings>(&text) {
+                Ok(settings) if settings.validate().is_ok() => (settings, None),
+                Ok(settings) => (Settings::default(), settings.validate().err().map(|e| set_aside_note(path, e))),
+                Err(e) => {
+                    let problem = format!("Failed to read settings from {}: {}", path.display(), e);
+                    (Settings::default(), Some(set_aside_note(path, problem)))
+                }
+            },
+            _ => (Settings::default(), None),
+        };
+        (SettingsStore { path, settings: Mutex::new(settings), menu: Mutex::new(None) }, problem)
+    }
+
+    pub fn get(&self) -> Settings {
+        self.settings.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Change the settings, apply them to the running app, and write them to disk
+    pub fn update(&self, app_handle: &AppHandle, change: impl FnOnce(&mut Settings)) -> Result<Settings, String> {
+        let settings = {
+            let mut settings = self.settings.lock().unwrap_or_else(|e| e.into_inner());
+            let mut changed = settings.clone();
+            change(&mut changed);
+            changed.validate()?;
+            *settings = changed;
+            settings.clone()
+        };
+        apply_settings(app_handle, &settings);
+        self.save(app_handle, &settings);
+        self.refresh_menu(app_handle, &settings);
+        Ok(settings)
+    }
+
+    /// Keep the Limits submenu, and fill it in with the profiles
+    pub fn attach_menu(&self, app_handle: &AppHandle, menu: Submenu<Wry>) {
+        *self.menu.lock().unwrap_or_else(|e| e.into_inner()) = Some(menu);
+        self.refresh_menu(app_handle, &self.get());
+    }
+
+    fn refresh_menu(&self, app_handle: &AppHandle, settings: &Settings) {
+        let Some(menu) = self.menu.lock().unwrap_or_else(|e| e.into_inner()).clone() else { return };
+        let rebuilt = (|| -> tauri::Result<()> {
+            for item in menu.items()? {
+                menu.remove(&item)?;
+            }
+            let custom = CheckMenuItemBuilder::with_id(NO_PROFILE_MENU_ID, "Custom")
+                .checked(settings.profile.is_none())
+                .build(app_handle)?;
+            menu.append(&custom)?;
+            menu.append(&PredefinedMenuItem::separator(app_handle)?)?;
+            for name in settings.profiles.keys() {
+                let item = CheckMenuItemBuilder::with_id(format!("{}{}", PROFILE_MENU_PREFIX, name), name)
+                    .checked(settings.profile.as_ref() == Some(name))
+                    .build(app_handle)?;
+                menu.append(&item)?;
+            }
+            Ok(())
+        })();
+        if let Err(e) = rebuilt {
+            eprintln!("Failed to update the Limits menu: {}", e);
+        }
+    }
+
+    fn save(&self, app_handle: &AppHandle, settings: &Settings) {
+        let Some(path) = &self.path else { return };
+        if let Err(e) = write_json(path, settings) {
+            let message = format!("Failed to save settings to {}: {}", path.display(), e);
+            emit_log(app_handle, "warn", &message, Some("System"));
+        }
+    }
+}
+
+/// Hand `settings` to the parts of the app that use them
+pub fn apply_settings(app_handle: &AppHandle, settings: &Settings) {
+    let (script_limits, mesh_limits) = settings.limits();
+    app_handle.state::<MeshOptionsState>().set(MeshOptions { limits: mesh_limits, ..settings.mesh });
+    app_handle.state::<ScriptLimitsState>().set(script_limits);
+    app_handle.state::<SaveOptionsState>().set(settings.save);
+    app_handle.state::<LogHistory>().set_level(settings.log_level);
+    if let Some(recovery) = app_handle.try_state::<Recovery>() {
+        recovery.set_interval(settings.autosave_interval_secs);
+    }
+    if let Some(http_api) = app_handle.try_state::<HttpApi>() {
+        http_api.apply(app_handle, settings.http_api);
+    }
+}
+
+/// Run compiles under the profile `name`, or the limits outside any profile if `None`
+pub fn select_profile(app_handle: &AppHandle, name: Option<String>) -> Result<Settings, String> {
+    let store = app_handle.state::<SettingsStore>();
+    let settings = store.update(app_handle, |settings| settings.profile = name).inspect_err(|e| {
+        emit_log(app_handle, "error", e, Some("System"));
+        // A check item ticks itself when clicked, so show the profile still in force
+        store.refresh_menu(app_handle, &store.get());
+    })?;
+    let (script, mesh) = settings.limits();
+    let limit = |value: u64, unit: &str, none: &str| match value {
+        0 => none.to_string(),
+        n => format!("{} {}", n, unit),
+    };
+    let message = format!(
+        "Compiles now run under {}: {}, {}, {} and {}",
+        settings.profile.as_ref().map_or("custom limits".to_string(), |name| format!("the '{}' profile", name)),
+        limit(script.max_operations, "operations", "unlimited operations"),
+        limit(script.timeout_ms, "ms", "no time limit"),
+        limit(mesh.max_triangles, "triangles", "unlimited triangles"),
+        limit(mesh.max_memory_mb, "MB", "no memory limit"),
+    );
+    emit_log(app_handle, "info", &message, Some("System"));
+    Ok(settings)
+}
@@ -0,0 +1,823 @@
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+
+use fidget::{mesh::Mesh, render::ThreadPool};
+use serde::{Deserialize, Serialize};
+use tauri::async_runtime::Mutex as AsyncMutex;
+
+use crate::disk_cache::DiskCache;
+use crate::export::ExportFormat;
+use crate::mesh::color::Rgb;
+use crate::mesh::limits::MeshLimits;
+use crate::mesh::quality::EvalBackend;
+use crate::mesh::units::MeshUnits;
+use crate::script::imports::ImportStamp;
+use crate::{CompileTimings, ExportJob, MeshResult, ScriptOutput, ScriptParam, ScriptSource};
+
+/// Document id used when the frontend doesn't specify one
+pub const DEFAULT_DOCUMENT_ID: &str = "default";
+
+/// Cooperative cancellation flag checked between compile stages
+#[derive(Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Compile bookkeeping for a single document
+#[derive(Default)]
+struct DocumentQueue {
+    /// Generation of the newest request for this document
+    generation: u64,
+    /// Cancel token of the newest request, until it finishes
+    active: Option<CancelToken>,
+    /// Held while a compile for this document is running
+    running: Arc<AsyncMutex<()>>,
+}
+
+/// A queued compile request
+pub struct CompileTicket {
+    pub document_id: String,
+    pub generation: u64,
+    pub cancel: CancelToken,
+    running: Arc<AsyncMutex<()>>,
+}
+
+impl CompileTicket {
+    /// Wait until no other compile for this document is running
+    ///
+    /// The returned guard holds the document's turn until it is dropped.
+    pub async fn wait_turn(&self) -> impl Sized + '_ {
+        self.running.lock().await
+    }
+}
+
+/// Managed state coalescing compile requests per document
+///
+/// Only the newest request for a document is allowed to run: enqueueing a
+/// request cancels the older one, and requests that were superseded while
+/// waiting for their turn are dropped without meshing.
+#[derive(Default)]
+pub struct CompileState {
+    documents: Mutex<HashMap<String, DocumentQueue>>,
+}
+
+impl CompileState {
+    /// Queue a compile for `document_id`, cancelling any older request for it
+    pub fn enqueue(&self, document_id: &str) -> CompileTicket {
+        let mut documents = self.documents.lock().unwrap_or_else(|e| e.into_inner());
+        let queue = documents.entry(document_id.to_string()).or_default();
+        if let Some(previous) = queue.active.take() {
+            previous.cancel();
+        }
+
+        let cancel = CancelToken::new();
+        queue.generation += 1;
+        queue.active = Some(cancel.clone());
+        CompileTicket {
+            document_id: document_id.to_string(),
+            generation: queue.generation,
+            cancel,
+            running: queue.running.clone(),
+        }
+    }
+
+    /// Returns true if `ticket` is still the newest request for its document
+    pub fn is_current(&self, ticket: &CompileTicket) -> bool {
+        self.documents
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&ticket.document_id)
+            .is_some_and(|queue| queue.generation == ticket.generation)
+    }
+
+    /// Mark `ticket` as finished
+    pub fn finish(&self, ticket: &CompileTicket) {
+        let mut documents = self.documents.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(queue) = documents.get_mut(&ticket.document_id) {
+            if queue.generation == ticket.generation {
+                queue.active = None;
+            }
+        }
+    }
+
+    /// Cancel the active compile for one document, or for all documents
+    ///
+    /// Returns false if nothing was running.
+    pub fn cancel(&self, document_id: Option<&str>) -> bool {
+        self.cancel_where(|id| document_id.is_none_or(|d| d == id))
+    }
+
+    /// Cancel the active compiles for every document whose id starts with `prefix`
+    pub fn cancel_prefix(&self, prefix: &str) -> bool {
+        self.cancel_where(|id| id.starts_with(prefix))
+    }
+
+    fn cancel_where(&self, matches: impl Fn(&str) -> bool) -> bool {
+        let mut documents = self.documents.lock().unwrap_or_else(|e| e.into_inner());
+        let mut cancelled = false;
+        for (id, queue) in documents.iter_mut() {
+            if !matches(id) {
+                continue;
+            }
+            if let Some(token) = queue.active.take() {
+                token.cancel();
+                cancelled = true;
+            }
+        }
+        cancelled
+    }
+}
+
+/// Where a job in the export queue is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportJobStatus {
+    Queued,
+    Running,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+/// A job in the export queue, as listed and sent with `export_job` events
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportJobInfo {
+    pub id: u64,
+    pub format: ExportFormat,
+    pub path: String,
+    pub part: Option<String>,
+    pub status: ExportJobStatus,
+    /// Triangles written, once the job is done
+    pub triangle_count: Option<usize>,
+    pub error: Option<String>,
+}
+
+/// An export waiting in the queue, or being run
+pub struct QueuedExport {
+    pub id: u64,
+    pub job: ExportJob,
+    pub cancel: CancelToken,
+}
+
+impl QueuedExport {
+    pub fn info(&self, status: ExportJobStatus) -> ExportJobInfo {
+        ExportJobInfo {
+            id: self.id,
+            format: self.job.format,
+            path: self.job.path.clone(),
+            part: self.job.part.clone(),
+            status,
+            triangle_count: None,
+            error: None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct ExportJobs {
+    next_id: u64,
+    pending: VecDeque<QueuedExport>,
+    running: Option<(ExportJobInfo, CancelToken)>,
+    /// Whether a worker is taking jobs off the queue
+    worker: bool,
+}
+
+/// Managed state holding exports for the background worker
+///
+/// Jobs run one at a time in the order they were queued.  The worker stops
+/// once the queue is empty, and `push` says when a new one must start.
+#[derive(Default)]
+pub struct ExportQueue {
+    jobs: Mutex<ExportJobs>,
+}
+
+impl ExportQueue {
+    /// Queue `jobs`, returning them as queued and whether a worker must be started to run them
+    pub fn push(&self, jobs: Vec<ExportJob>) -> (Vec<ExportJobInfo>, bool) {
+        let mut queue = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        let mut queued = Vec::with_capacity(jobs.len());
+        for job in jobs {
+            queue.next_id += 1;
+            let export = QueuedExport { id: queue.next_id, job, cancel: CancelToken::new() };
+            queued.push(export.info(ExportJobStatus::Queued));
+            queue.pending.push_back(export);
+        }
+        let start = !queue.worker && !queue.pending.is_empty();
+        queue.worker |= start;
+        (queued, start)
+    }
+
+    /// Take the next job to run, or mark the worker stopped if there's none
+    pub fn next(&self) -> Option<QueuedExport> {
+        let mut queue = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        let next = queue.pending.pop_front();
+        queue.running = next.as_ref().map(|export| (export.info(ExportJobStatus::Running), export.cancel.clone()));
+        queue.worker = next.is_some();
+        next
+    }
+
+    /// Cancel the job `id`, or every job
+    ///
+    /// Queued jobs are taken off the queue and returned, as they'll never
+    /// run; the running job stops at its next cancellation check.  The bool
+    /// is whether the running job was cancelled.
+    pub fn cancel(&self, id: Option<u64>) -> (Vec<ExportJobInfo>, bool) {
+        let mut queue = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        let (removed, kept): (VecDeque<_>, VecDeque<_>) =
+            std::mem::take(&mut queue.pending).into_iter().partition(|export| id.is_none_or(|id| id == export.id));
+        queue.pending = kept;
+        let running = queue.running.as_ref().filter(|(info, _)| id.is_none_or(|id| id == info.id));
+        if let Some((_, cancel)) = running {
+            cancel.cancel();
+        }
+        (removed.iter().map(|export| export.info(ExportJobStatus::Cancelled)).collect(), running.is_some())
+    }
+
+    /// The running job, then the queued ones in the order they'll run
+    pub fn list(&self) -> Vec<ExportJobInfo> {
+        let queue = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        let running = queue.running.iter().map(|(info, _)| info.clone());
+        running.chain(queue.pending.iter().map(|export| export.info(ExportJobStatus::Queued))).collect()
+    }
+}
+
+/// Budget a script may use before it is stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScriptLimits {
+    /// Rhai operations allowed per run, or 0 for no limit
+    pub max_operations: u64,
+    /// Wall-clock time allowed per run, in milliseconds, or 0 for no limit
+    pub timeout_ms: u64,
+}
+
+impl Default for ScriptLimits {
+    fn default() -> Self {
+        // Matches the step limit of `fidget::rhai::engine`
+        ScriptLimits { max_operations: 50_000, timeout_ms: 10_000 }
+    }
+}
+
+/// Managed state holding the current script limits
+#[derive(Default)]
+pub struct ScriptLimitsState {
+    limits: Mutex<ScriptLimits>,
+}
+
+impl ScriptLimitsState {
+    pub fn get(&self) -> ScriptLimits {
+        *self.limits.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn set(&self, limits: ScriptLimits) {
+        *self.limits.lock().unwrap_or_else(|e| e.into_inner()) = limits;
+    }
+}
+
+/// App-wide meshing options, changed from the settings
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MeshOptions {
+    /// Evaluator used unless a compile asks for another
+    pub backend: EvalBackend,
+    /// Threads used to build octrees, or 0 for one per core
+    pub threads: usize,
+    /// Run meshing threads at the lowest OS priority, so the machine stays
+    /// responsive during long compiles
+    pub low_priority: bool,
+    /// Largest mesh a compile may build
+    pub limits: MeshLimits,
+}
+
+/// Managed state holding the current meshing options
+#[derive(Default)]
+pub struct MeshOptionsState {
+    options: Mutex<MeshOptions>,
+    /// Pool matching `options`, built on first use
+    pool: Mutex<Option<Arc<ThreadPool>>>,
+}
+
+impl MeshOptionsState {
+    pub fn get(&self) -> MeshOptions {
+        *self.options.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn set(&self, options: MeshOptions) {
+        let mut current = self.options.lock().unwrap_or_else(|e| e.into_inner());
+        if (current.threads, current.low_priority) != (options.threads, options.low_priority) {
+            *self.pool.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        }
+        *current = options;
+    }
+
+    /// Thread pool for octree construction
+    ///
+    /// The global pool is used unless the thread count or priority was
+    /// changed; a custom pool is kept until they change again.
+    pub fn thread_pool(&self) -> Result<Arc<ThreadPool>, String> {
+        let options = self.get();
+        let mut pool = self.pool.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(pool) = pool.as_ref() {
+            return Ok(pool.clone());
+        }
+        let built = if options.threads == 0 && !options.low_priority {
+            ThreadPool::Global
+        } else {
+            let low_priority = options.low_priority;
+            let custom = rayon::ThreadPoolBuilder::new()
+                .num_threads(options.threads)
+                .thread_name(|i| format!("horsecad-mesh-{}", i))
+                .start_handler(move |_| {
+                    if low_priority {
+                        // Best effort; meshing still works at normal priority
+                        let _ = thread_priority::set_current_thread_priority(thread_priority::ThreadPriority::Min);
+                    }
+                })
+                .build()
+                .map_err(|e| format!("Failed to start meshing threads: {}", e))?;
+            ThreadPool::Custom(custom)
+        };
+        Ok(pool.insert(Arc::new(built)).clone())
+    }
+}
+
+/// How scripts are saved, changed from the settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SaveOptions {
+    /// Earlier versions kept beside a script each time it is saved, as
+    /// `.bak`, `.bak2` and so on, newest first; 0 keeps none
+    pub backups: usize,
+}
+
+impl Default for SaveOptions {
+    fn default() -> Self {
+        SaveOptions { backups: 3 }
+    }
+}
+
+/// Managed state holding the current save options
+#[derive(Default)]
+pub struct SaveOptionsState {
+    options: Mutex<SaveOptions>,
+}
+
+impl SaveOptionsState {
+    pub fn get(&self) -> SaveOptions {
+        *self.options.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn set(&self, options: SaveOptions) {
+        *self.options.lock().unwrap_or_else(|e| e.into_inner()) = options;
+    }
+}
+
+/// Mesh from a document's last successful compile
+pub struct LastMesh {
+    pub mesh: Mesh,
+    /// Per-triangle colors, if any drawn shape has one
+    pub triangle_colors: Option<Vec<Option<Rgb>>>,
+    pub units: MeshUnits,
+    /// What the mesh was compiled from, for export reports
+    pub origin: Option<MeshOrigin>,
+}
+
+/// The script and settings a mesh was compiled from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeshOrigin {
+    /// SHA-256 of the script's code, in hex
+    pub script_hash: String,
+    pub parameters: Vec<ScriptParam>,
+    pub timings: CompileTimings,
+}
+
+/// Managed state keeping each document's last mesh, keyed like the compile queue
+///
+/// Exports read the mesh from here, so it never has to be sent back from
+/// the frontend.  The mesh before it is kept too, for `diff_last_compiles`.
+#[derive(Default)]
+pub struct MeshCache {
+    meshes: Mutex<HashMap<String, Arc<LastMesh>>>,
+    previous: Mutex<HashMap<String, Arc<LastMesh>>>,
+}
+
+impl MeshCache {
+    pub fn get(&self, document_id: &str) -> Option<Arc<LastMesh>> {
+        self.meshes.lock().unwrap_or_else(|e| e.into_inner()).get(document_id).cloned()
+    }
+
+    /// The mesh the document had before its last one
+    pub fn previous(&self, document_id: &str) -> Option<Arc<LastMesh>> {
+        self.previous.lock().unwrap_or_else(|e| e.into_inner()).get(document_id).cloned()
+    }
+
+    /// Make `mesh` the document's last, keeping the one it replaces as the previous
+    ///
+    /// Inserting the mesh that's already last, as a compile cache hit
+    /// does, leaves the previous mesh alone.
+    pub fn insert(&self, document_id: &str, mesh: Arc<LastMesh>) {
+        let replaced =
+            self.meshes.lock().unwrap_or_else(|e| e.into_inner()).insert(document_id.to_string(), mesh.clone());
+        if let Some(replaced) = replaced.filter(|r| !Arc::ptr_eq(r, &mesh)) {
+            self.previous.lock().unwrap_or_else(|e| e.into_inner()).insert(document_id.to_string(), replaced);
+        }
+    }
+
+    /// Drop the meshes of every document whose id starts with `prefix`
+    pub fn remove_prefix(&self, prefix: &str) {
+        self.meshes.lock().unwrap_or_else(|e| e.into_inner()).retain(|id, _| !id.starts_with(prefix));
+        self.previous.lock().unwrap_or_else(|e| e.into_inner()).retain(|id, _| !id.starts_with(prefix));
+    }
+}
+
+/// Snapshots kept for each document
+pub const MAX_SNAPSHOTS: usize = 10;
+
+/// Memory the snapshots of all documents may hold before the oldest are dropped, in bytes
+pub const SNAPSHOT_BYTES: usize = 128 * 1024 * 1024;
+
+/// A successful compile kept so the document can flip back to it
+pub struct Snapshot {
+    pub id: u64,
+    /// When the compile finished, as RFC 3339
+    pub timestamp: String,
+    /// Result header as first sent
+    pub result: MeshResult,
+    pub stl_data: Vec<u8>,
+    pub mesh: Arc<LastMesh>,
+}
+
+impl Snapshot {
+    /// Rough size of the snapshot, counting its mesh and STL data
+    fn size(&self) -> usize {
+        let mesh = &self.mesh.mesh;
+        let colors = self.mesh.triangle_colors.as_deref().map_or(0, std::mem::size_of_val);
+        let geometry = std::mem::size_of_val(&mesh.vertices[..]) + std::mem::size_of_val(&mesh.triangles[..]);
+        self.stl_data.len() + geometry + colors
+    }
+}
+
+/// A snapshot as listed by `list_snapshots`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotInfo {
+    pub id: u64,
+    pub timestamp: String,
+    pub triangle_count: Option<usize>,
+    /// Parameters the compile used
+    pub parameters: Vec<ScriptParam>,
+    /// Memory the snapshot holds, in bytes
+    pub bytes: usize,
+}
+
+#[derive(Default)]
+struct SnapshotEntries {
+    /// Each document's snapshots, oldest first
+    documents: HashMap<String, VecDeque<Arc<Snapshot>>>,
+    bytes: usize,
+    next_id: u64,
+}
+
+/// Managed state keeping each document's last few successful compiles
+///
+/// Up to `MAX_SNAPSHOTS` are kept per document, and the oldest snapshots
+/// of any document are dropped once they hold more than `SNAPSHOT_BYTES`.
+#[derive(Default)]
+pub struct SnapshotStore {
+    inner: Mutex<SnapshotEntries>,
+}
+
+impl SnapshotStore {
+    /// Keep a successful compile of `document_id`, unless its mesh is already the newest snapshot
+    pub fn record(&self, document_id: &str, result: MeshResult, stl_data: Vec<u8>, mesh: Arc<LastMesh>) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let newest = inner.documents.get(document_id).and_then(|snapshots| snapshots.back());
+        if newest.is_some_and(|s| Arc::ptr_eq(&s.mesh, &mesh)) {
+            return;
+        }
+        inner.next_id += 1;
+        let snapshot = Snapshot {
+            id: inner.next_id,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            result,
+            stl_data,
+            mesh,
+        };
+        let size = snapshot.size();
+        if size > SNAPSHOT_BYTES {
+            return;
+        }
+        inner.bytes += size;
+        let snapshots = inner.documents.entry(document_id.to_string()).or_default();
+        snapshots.push_back(Arc::new(snapshot));
+        let dropped = if snapshots.len() > MAX_SNAPSHOTS { snapshots.pop_front() } else { None };
+        if let Some(dropped) = dropped {
+            inner.bytes -= dropped.size();
+        }
+        while inner.bytes > SNAPSHOT_BYTES {
+            let oldest = inner
+                .documents
+                .iter()
+                .filter_map(|(id, snapshots)| Some((snapshots.front()?.id, id.clone())))
+                .min();
+            let Some((_, oldest)) = oldest else {
+                break;
+            };
+            if let Some(dropped) = inner.documents.get_mut(&oldest).and_then(VecDeque::pop_front) {
+                inner.bytes -= dropped.size();
+            }
+        }
+    }
+
+    /// The document's snapshots, newest first
+    pub fn list(&self, document_id: &str) -> Vec<SnapshotInfo> {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(snapshots) = inner.documents.get(document_id) else {
+            return Vec::new();
+        };
+        snapshots
+            .iter()
+            .rev()
+            .map(|s| SnapshotInfo {
+                id: s.id,
+                timestamp: s.timestamp.clone(),
+                triangle_count: s.result.triangle_count,
+                parameters: s.result.parameters.clone(),
+                bytes: s.size(),
+            })
+            .collect()
+    }
+
+    pub fn get(&self, document_id: &str, id: u64) -> Option<Arc<Snapshot>> {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.documents.get(document_id)?.iter().find(|s| s.id == id).cloned()
+    }
+
+    /// Drop the snapshots of every document whose id starts with `prefix`
+    pub fn remove_prefix(&self, prefix: &str) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let removed: usize = inner
+            .documents
+            .iter()
+            .filter(|(id, _)| id.starts_with(prefix))
+            .flat_map(|(_, snapshots)| snapshots.iter().map(|s| s.size()))
+            .sum();
+        inner.bytes -= removed;
+        inner.documents.retain(|id, _| !id.starts_with(prefix));
+    }
+}
+
+/// Memory the compile cache may hold before it evicts, in bytes
+pub const DEFAULT_COMPILE_CACHE_BYTES: usize = 256 * 1024 * 1024;
+
+/// A successful compile kept for reuse when the same inputs come back
+pub struct CachedCompile {
+    /// Result header as first sent, before the generation was stamped
+    pub result: MeshResult,
+    pub stl_data: Vec<u8>,
+    pub mesh: Arc<LastMesh>,
+    /// Files the script read; the entry is stale once any of them changes
+    pub imports: Vec<ImportStamp>,
+}
+
+impl CachedCompile {
+    /// Rough size of the entry, counting its mesh and STL data
+    fn size(&self) -> usize {
+        let mesh = &self.mesh.mesh;
+        let colors = self.mesh.triangle_colors.as_deref().map_or(0, std::mem::size_of_val);
+        let geometry = std::mem::size_of_val(&mesh.vertices[..]) + std::mem::size_of_val(&mesh.triangles[..]);
+        self.stl_data.len() + geometry + colors
+    }
+}
+
+#[derive(Default)]
+struct CompileCacheEntries {
+    /// Entries with the tick they were last used at
+    entries: HashMap<String, (u64, Arc<CachedCompile>)>,
+    bytes: usize,
+    tick: u64,
+}
+
+impl CompileCacheEntries {
+    fn remove(&mut self, key: &str) {
+        if let Some((_, entry)) = self.entries.remove(key) {
+            self.bytes -= entry.size();
+        }
+    }
+}
+
+/// Managed state keeping recent compiles by a hash of their inputs
+///
+/// Compiling the same script with the same parameters and meshing settings
+/// returns the kept result instead of meshing again.  The least recently
+/// used entries are dropped once the cache holds more than its limit.
+/// With a disk cache, entries are also written there and read back when
+/// memory doesn't have them, so they outlast the session.
+pub struct CompileCache {
+    limit_bytes: usize,
+    inner: Mutex<CompileCacheEntries>,
+    disk: Option<DiskCache>,
+}
+
+impl CompileCache {
+    pub fn new(limit_bytes: usize, disk: Option<DiskCache>) -> Self {
+        CompileCache { limit_bytes, inner: Mutex::new(CompileCacheEntries::default()), disk }
+    }
+
+    /// The compile kept under `key`, unless a file it imported has changed since
+    pub fn get(&self, key: &str) -> Option<Arc<CachedCompile>> {
+        let in_memory = {
+            let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+            match inner.entries.get(key).map(|(_, entry)| entry.clone()) {
+                Some(entry) if entry.imports.iter().all(|i| i.is_current()) => {
+                    inner.tick += 1;
+                    let tick = inner.tick;
+                    inner.entries.insert(key.to_string(), (tick, entry.clone()));
+                    Some(entry)
+                }
+                Some(_) => {
+                    inner.remove(key);
+                    None
+                }
+                None => None,
+            }
+        };
+        in_memory.or_else(|| {
+            let entry = Arc::new(self.disk.as_ref()?.load(key)?);
+            self.keep(key, entry.clone());
+            Some(entry)
+        })
+    }
+
+    /// Keep `entry` under `key`, in memory and on disk
+    ///
+    /// An error means only the disk copy couldn't be written.
+    pub fn insert(&self, key: &str, entry: CachedCompile) -> Result<(), String> {
+        let entry = Arc::new(entry);
+        self.keep(key, entry.clone());
+        match &self.disk {
+            Some(disk) => disk.store(key, &entry),
+            None => Ok(()),
+        }
+    }
+
+    /// Keep `entry` in memory, evicting the least recently used entries to stay under the limit
+    fn keep(&self, key: &str, entry: Arc<CachedCompile>) {
+        let size = entry.size();
+        if size > self.limit_bytes {
+            return;
+        }
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.remove(key);
+        inner.tick += 1;
+        let tick = inner.tick;
+        inner.entries.insert(key.to_string(), (tick, entry));
+        inner.bytes += size;
+        while inner.bytes > self.limit_bytes {
+            let oldest = inner.entries.iter().min_by_key(|(_, (used, _))| *used).map(|(key, _)| key.clone());
+            let Some(oldest) = oldest else {
+                break;
+            };
+            inner.remove(&oldest);
+        }
+    }
+}
+
+/// Last script evaluated for a document, kept for parameter-only recompiles
+struct CachedScript {
+    code: String,
+    base_dir: Option<PathBuf>,
+    /// Script output with `param_tree()` values left as variables
+    output: Arc<ScriptOutput>,
+}
+
+/// Managed state caching evaluated scripts per document
+///
+/// When only parameter values change, the cached graph can be re-bound to
+/// new constants instead of running the script again.
+#[derive(Default)]
+pub struct ScriptCache {
+    documents: Mutex<HashMap<String, CachedScript>>,
+}
+
+impl ScriptCache {
+    /// Cached output for `document_id`, if it was evaluated from `source`
+    /// and none of its imported files have changed since
+    pub fn get(&self, document_id: &str, source: &ScriptSource) -> Option<Arc<ScriptOutput>> {
+        let documents = self.documents.lock().unwrap_or_else(|e| e.into_inner());
+        documents
+            .get(document_id)
+            .filter(|cached| cached.code == source.code && cached.base_dir == source.base_dir)
+            .filter(|cached| cached.output.imports.iter().all(|i| i.is_current()))
+            .map(|cached| cached.output.clone())
+    }
+
+    /// Output last evaluated for `document_id`, whatever code it came from
+    pub fn latest(&self, document_id: &str) -> Option<Arc<ScriptOutput>> {
+        self.documents.lock().unwrap_or_else(|e| e.into_inner()).get(document_id).map(|cached| cached.output.clone())
+    }
+
+    /// Replace the cached output for `document_id`
+    pub fn insert(&self, document_id: &str, source: &ScriptSource, output: Arc<ScriptOutput>) {
+        let cached = CachedScript { code: source.code.clone(), base_dir: source.base_dir.clone(), output };
+        self.documents.lock().unwrap_or_else(|e| e.into_inner()).insert(document_id.to_string(), cached);
+    }
+}
+
+/// Compiles kept in the session's history, oldest dropped first
+pub const MAX_COMPILE_HISTORY: usize = 1000;
+
+/// How a compile went, kept for the compile history and bug reports
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompileRecord {
+    /// When the compile finished, as RFC 3339
+    pub timestamp: String,
+    pub document_id: String,
+    /// Hash of the script, parameters and meshing request, as hex;
+    /// equal hashes mean nothing changed between compiles
+    pub hash: String,
+    pub depth: u8,
+    pub triangle_count: Option<usize>,
+    pub success: bool,
+    pub timings: CompileTimings,
+}
+
+/// Summary of the successful compiles in a stretch of history, in milliseconds
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct CompileStats {
+    pub compiles: usize,
+    pub failures: usize,
+    pub mean_ms: f64,
+    pub fastest_ms: f64,
+    pub slowest_ms: f64,
+    /// Time of the latest compile less the mean of the ones before it,
+    /// so a positive value means the last edit made meshing slower
+    pub latest_change_ms: Option<f64>,
+}
+
+impl CompileStats {
+    pub fn of(records: &[CompileRecord]) -> CompileStats {
+        let times: Vec<f64> = records.iter().filter(|r| r.success).map(|r| r.timings.total_ms()).collect();
+        let mean = |times: &[f64]| times.iter().sum::<f64>() / times.len().max(1) as f64;
+        CompileStats {
+            compiles: records.len(),
+            failures: records.len() - times.len(),
+            mean_ms: mean(&times),
+            fastest_ms: times.iter().copied().reduce(f64::min).unwrap_or(0.0),
+            slowest_ms: times.iter().copied().reduce(f64::max).unwrap_or(0.0),
+            latest_change_ms: match times.split_last() {
+                Some((latest, earlier)) if !earlier.is_empty() => Some(latest - mean(earlier)),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// Managed state holding this session's compiles, oldest first
+///
+/// Compiles answered from the compile cache aren't recorded, since they
+/// say nothing about how long meshing takes.
+#[derive(Default)]
+pub struct CompileHistory {
+    records: Mutex<VecDeque<CompileRecord>>,
+}
+
+impl CompileHistory {
+    pub fn record(&self, record: CompileRecord) {
+        let mut records = self.records.lock().unwrap_or_else(|e| e.into_inner());
+        if records.len() == MAX_COMPILE_HISTORY {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+
+    /// The most recent compile of any document
+    pub fn last(&self) -> Option<CompileRecord> {
+        self.records.lock().unwrap_or_else(|e| e.into_inner()).back().cloned()
+    }
+
+    /// Every recorded compile of `document_id`
+    pub fn query(&self, document_id: &str) -> Vec<CompileRecord> {
+        let records = self.records.lock().unwrap_or_else(|e| e.into_inner());
+        records.iter().filter(|r| r.document_id == document_id).cloned().collect()
+    }
+
+    /// Every recorded compile of a document whose id starts with `prefix`
+    pub fn query_prefix(&self, prefix: &str) -> Vec<CompileRecord> {
+        let records = self.records.lock().unwrap_or_else(|e| e.into_inner());
+        records.iter().filter(|r| r.document_id.starts_with(prefix)).cloned().collect()
+    }
+}
@@ -0,0 +1,65 @@
+use serde::{Deserialize, Serialize};
+use tauri::menu::{MenuItemBuilder, Submenu, SubmenuBuilder};
+use tauri::{AppHandle, Wry};
+
+use crate::horsi::HorsiFile;
+
+/// Menu id prefix of the items in the New From Template submenu, followed by the template's id
+pub const TEMPLATE_MENU_PREFIX: &str = "new_from_template_";
+
+/// Id, name, description and contents of each bundled template, in menu order
+const TEMPLATES: &[(&str, &str, &str, &str)] = &[
+    (
+        "primitives",
+        "Primitives",
+        "The basic solids side by side",
+        include_str!("../templates/primitives.horsi"),
+    ),
+    (
+        "parametric_box",
+        "Parametric Box",
+        "An open box sized by parameters",
+        include_str!("../templates/parametric_box.horsi"),
+    ),
+    ("gear", "Gear", "A spur gear with a bore and hub", include_str!("../templates/gear.horsi")),
+    (
+        "lamp_shade",
+        "Lamp Shade",
+        "A twisted, tapering shade with an open top and bottom",
+        include_str!("../templates/lamp_shade.horsi"),
+    ),
+];
+
+/// A bundled example script, as listed by `list_templates`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateInfo {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+}
+
+pub fn templates() -> Vec<TemplateInfo> {
+    TEMPLATES
+        .iter()
+        .map(|&(id, name, description, _)| TemplateInfo {
+            id: id.to_string(),
+            name: name.to_string(),
+            description: description.to_string(),
+        })
+        .collect()
+}
+
+/// The script of the template with `id`, without any metadata section
+pub fn template_script(id: &str) -> Option<String> {
+    let &(.., text) = TEMPLATES.iter().find(|(template, ..)| *template == id)?;
+    Some(HorsiFile::parse(text).script)
+}
+
+/// The New From Template submenu, one item per template
+pub fn template_menu(app_handle: &AppHandle) -> tauri::Result<Submenu<Wry>> {
+    let mut menu = SubmenuBuilder::new(app_handle, "New From Template");
+    for &(id, name, ..) in TEMPLATES {
+        menu = menu.item(&MenuItemBuilder::with_id(format!("{}{}", TEMPLATE_MENU_PREFIX, id), name).build(app_handle)?);
+    }
+    menu.build()
+}
@@ -0,0 +1,98 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+/// Write `data` to `path` so that the file is either the old or the new
+/// contents, never a mix
+///
+/// The data goes to a temporary file next to `path`, is flushed to disk,
+/// and then renamed over it.
+pub fn write_atomically(path: &Path, data: &[u8]) -> io::Result<()> {
+    let file_name = path.file_name().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file path"))?;
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(file_name);
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    let temp_path = path.with_file_name(temp_name);
+
+    let written = (|| {
+        let mut file = File::create(&temp_path)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        fs::rename(&temp_path, path)
+    })();
+    if written.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    written?;
+
+    // Make the rename itself durable; not possible on every platform
+    #[cfg(unix)]
+    if let Some(folder) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        let _ = File::open(folder).and_then(|folder| folder.sync_all());
+    }
+    Ok(())
+}
+
+/// Path of the `index`th backup of `path`, 1 being the newest
+pub fn backup_path(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    if index == 1 {
+        name.push(".bak");
+    } else {
+        name.push(format!(".bak{}", index));
+    }
+    path.with_file_name(name)
+}
+
+/// Copy the current contents of `path` to its newest backup, shifting older
+/// backups along and dropping any beyond `count`
+///
+/// Does nothing if `count` is zero or `path` doesn't exist yet.
+pub fn rotate_backups(path: &Path, count: usize) -> io::Result<()> {
+    if count == 0 || !path.is_file() {
+        return Ok(());
+    }
+    let _ = fs::remove_file(backup_path(path, count));
+    for index in (1..count).rev() {
+        let from = backup_path(path, index);
+        if from.exists() {
+            fs::rename(&from, backup_path(path, index + 1))?;
+        }
+    }
+    fs::copy(path, backup_path(path, 1))?;
+    Ok(())
+}
+
+/// Move a file that couldn't be read to its newest backup, so the next save
+/// doesn't write over what was in it
+///
+/// Returns where the file went.
+pub fn set_aside(path: &Path) -> io::Result<PathBuf> {
+    let backup = backup_path(path, 1);
+    fs::rename(path, &backup)?;
+    Ok(backup)
+}
+
+/// Write `value` to `path` as pretty-printed JSON, creating its folder if needed
+///
+/// The file is written atomically, so a crash mid-save leaves the old one.
+pub fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), String> {
+    let text = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+    if let Some(folder) = path.parent() {
+        fs::create_dir_all(folder).map_err(|e| e.to_string())?;
+    }
+    write_atomically(path, text.as_bytes()).map_err(|e| e.to_string())
+}
+
+/// SHA-256 of `data`, in lowercase hex
+pub fn sha256_hex(data: &[u8]) -> String {
+    to_hex(&Sha256::digest(data))
+}
+
+/// `bytes` in lowercase hex
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
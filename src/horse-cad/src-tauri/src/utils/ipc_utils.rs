@@ -0,0 +1,27 @@
+use serde::{de::DeserializeOwned, Serialize};
+use tauri::http::HeaderMap;
+
+/// Frame a JSON header and a binary payload into a single IPC response body
+///
+/// Layout is `[u32 LE header length][UTF-8 JSON header][payload bytes]`.
+pub fn encode_binary_frame<T: Serialize>(header: &T, payload: &[u8]) -> Result<Vec<u8>, String> {
+    let header = serde_json::to_vec(header).map_err(|e| format!("Failed to encode response header: {}", e))?;
+    let mut frame = Vec::with_capacity(4 + header.len() + payload.len());
+    frame.extend_from_slice(&(header.len() as u32).to_le_bytes());
+    frame.extend_from_slice(&header);
+    frame.extend_from_slice(payload);
+    Ok(frame)
+}
+
+/// Read a URI-encoded JSON value from an IPC request header
+pub fn decode_json_header<T: DeserializeOwned>(headers: &HeaderMap, name: &str) -> Result<T, String> {
+    let value = headers
+        .get(name)
+        .ok_or_else(|| format!("Missing {} header", name))?
+        .to_str()
+        .map_err(|e| format!("Invalid {} header: {}", name, e))?;
+    let json = percent_encoding::percent_decode_str(value)
+        .decode_utf8()
+        .map_err(|e| format!("Invalid {} header: {}", name, e))?;
+    serde_json::from_str(&json).map_err(|e| format!("Invalid {} header: {}", name, e))
+}
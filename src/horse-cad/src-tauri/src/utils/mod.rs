@@ -1 +1,3 @@
+pub mod file_utils;
+pub mod ipc_utils;
 pub mod log_utils;
\ No newline at end of file
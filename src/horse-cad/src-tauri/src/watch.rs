@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+use std::time::Duration;
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use tauri::Manager;
+
+use crate::export::stl::StlFormat;
+use crate::horsi::HorsiFile;
+use crate::host::WindowHost;
+use crate::open::check_external_change;
+use crate::state::CompileState;
+use crate::utils::ipc_utils::encode_binary_frame;
+use crate::{compile_queued, emit_log, MeshRequest, MeshResult, ScriptSource};
+
+/// How long a file has to stay quiet before it is recompiled
+///
+/// Editors often save in several steps (truncate, write, rename), each of
+/// which shows up as its own event.
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// How a watched file is compiled each time it changes
+pub(crate) struct WatchRequest {
+    /// Queue and cache key of the document, as given by `WindowHost::document_key`
+    pub document_key: String,
+    pub params: HashMap<String, f64>,
+    pub request: MeshRequest,
+    pub stl_format: StlFormat,
+}
+
+/// Sent after a watched file has been recompiled
+#[derive(Debug, Serialize)]
+pub struct WatchedFileCompiled {
+    pub path: String,
+    /// The file's new contents, for the editor to show
+    pub code: String,
+    pub result: MeshResult,
+}
+
+struct FileWatch {
+    path: PathBuf,
+    /// Dropping the watcher stops it
+    _watcher: RecommendedWatcher,
+}
+
+/// The script each window is watching, by window label
+#[derive(Default)]
+pub struct WatchState {
+    windows: Mutex<HashMap<String, Arc<WindowWatch>>>,
+}
+
+/// The script one window is watching, if any, and the mesh from its latest compile
+#[derive(Default)]
+struct WindowWatch {
+    current: Mutex<Option<FileWatch>>,
+    /// Bumped on every change, so only the last of a burst of events compiles
+    generation: Arc<AtomicU64>,
+    /// Contents last compiled, to skip events that didn't change anything
+    last_code: Arc<Mutex<Option<String>>>,
+    /// Latest recompile, framed as `compile_script` returns it
+    mesh: Arc<Mutex<Option<Vec<u8>>>>,
+}
+
+impl WatchState {
+    /// Start watching `path` for the window `host` belongs to, replacing any file it watched before
+    ///
+    /// Recompiles are reported to that window.
+    pub(crate) fn watch(&self, host: &WindowHost, path: PathBuf, request: WatchRequest) -> Result<(), String> {
+        let watch =
+            self.windows.lock().unwrap_or_else(|e| e.into_inner()).entry(host.label().to_string()).or_default().clone();
+        watch.watch(host, path, request)
+    }
+
+    /// Stop `window` watching, returning the path it watched
+    pub fn unwatch(&self, window: &str) -> Option<PathBuf> {
+        let watch = self.windows.lock().unwrap_or_else(|e| e.into_inner()).remove(window)?;
+        watch.unwatch()
+    }
+
+    /// Result header and STL bytes of `window`'s latest recompile, framed as `compile_script` returns them
+    pub fn mesh(&self, window: &str) -> Option<Vec<u8>> {
+        self.windows.lock().unwrap_or_else(|e| e.into_inner()).get(window)?.mesh()
+    }
+}
+
+impl WindowWatch {
+    fn watch(&self, host: &WindowHost, path: PathBuf, request: WatchRequest) -> Result<(), String> {
+        // Events carry the full path, so match against the canonical one
+        let path = path.canonicalize().map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        let code = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let code = HorsiFile::parse(&code).script;
+        // Watch the folder rather than the file, since editors that save by
+        // renaming a temporary file over it replace the file being watched
+        let folder = path.parent().unwrap_or(&path);
+
+        let handle = host.clone();
+        let watched = path.clone();
+        let request = Arc::new(request);
+        let generation = self.generation.clone();
+        let last_code = self.last_code.clone();
+        let mesh = self.mesh.clone();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let Ok(event) = event else { return };
+            if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) || !event.paths.contains(&watched) {
+                return;
+            }
+            let this = generation.fetch_add(1, Ordering::SeqCst) + 1;
+            let handle = handle.clone();
+            let watched = watched.clone();
+            let request = request.clone();
+            let generation = generation.clone();
+            let last_code = last_code.clone();
+            let mesh = mesh.clone();
+            tauri::async_runtime::spawn(async move {
+                let _ = tauri::async_runtime::spawn_blocking(|| std::thread::sleep(DEBOUNCE)).await;
+                if generation.load(Ordering::SeqCst) != this {
+                    return;
+                }
+                check_external_change(handle.app(), handle.label());
+                let Ok(text) = std::fs::read_to_string(&watched) else { return };
+                let code = HorsiFile::parse(&text).script;
+                {
+                    let mut last = last_code.lock().unwrap_or_else(|e| e.into_inner());
+                    if last.as_deref() == Some(code.as_str()) {
+                        return;
+                    }
+                    *last = Some(code.clone());
+                }
+                recompile(&handle, &watched, code, &request, &mesh).await;
+            });
+        })
+        .map_err(|e| format!("Failed to start file watcher: {}", e))?;
+        watcher
+            .watch(folder, RecursiveMode::NonRecursive)
+            .map_err(|e| format!("Failed to watch {}: {}", path.display(), e))?;
+
+        *self.last_code.lock().unwrap_or_else(|e| e.into_inner()) = Some(code);
+        *self.mesh.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        emit_log(host, "info", &format!("Watching {} for changes", path.display()), Some("Compiler"));
+        *self.current.lock().unwrap_or_else(|e| e.into_inner()) = Some(FileWatch { path, _watcher: watcher });
+        Ok(())
+    }
+
+    /// Stop watching, returning the path that was watched
+    fn unwatch(&self) -> Option<PathBuf> {
+        // Drop any recompile still waiting out the debounce
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        *self.last_code.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        *self.mesh.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        self.current.lock().unwrap_or_else(|e| e.into_inner()).take().map(|watch| watch.path)
+    }
+
+    fn mesh(&self) -> Option<Vec<u8>> {
+        self.mesh.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+async fn recompile(
+    host: &WindowHost,
+    path: &Path,
+    code: String,
+    request: &WatchRequest,
+    mesh: &Mutex<Option<Vec<u8>>>,
+) {
+    let path_name = path.to_string_lossy().into_owned();
+    emit_log(host, "info", &format!("{} changed on disk, recompiling", path_name), Some("Compiler"));
+    let source = ScriptSource::new(code.clone(), Some(&path_name));
+    let compile_state = host.app().state::<CompileState>();
+    let compiled = compile_queued(
+        host,
+        &compile_state,
+        &request.document_key,
+        source,
+        request.params.clone(),
+        request.request,
+        request.stl_format,
+    )
+    .await;
+    let (result, stl_data) = match compiled {
+        Ok(compiled) => compiled,
+        Err(e) => {
+            emit_log(host, "error", &e, Some("Compiler"));
+            return;
+        }
+    };
+    if result.superseded {
+        return;
+    }
+
+    match encode_binary_frame(&result, &stl_data) {
+        Ok(frame) => *mesh.lock().unwrap_or_else(|e| e.into_inner()) = Some(frame),
+        Err(e) => emit_log(host, "error", &e, Some("Compiler")),
+    }
+    let event = WatchedFileCompiled { path: path_name, code, result };
+    host.emit("watched_file_compiled", &event);
+}
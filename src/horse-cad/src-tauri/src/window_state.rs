@@ -0,0 +1,62 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use tauri::{PhysicalPosition, PhysicalSize, Runtime, Window};
+
+/// Where the main window was and how it was laid out, restored at startup
+///
+/// Position and size are in physical pixels, as the OS reports them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowState {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub maximized: bool,
+    /// Panel sizes and similar hints, keyed by the frontend
+    pub panels: HashMap<String, f64>,
+}
+
+impl WindowState {
+    /// Record `window`'s current geometry, keeping the rest of `self`
+    ///
+    /// A maximized window keeps the size it had before, so it un-maximizes
+    /// back to it next session.
+    pub fn capture<R: Runtime>(&mut self, window: &Window<R>) -> tauri::Result<()> {
+        self.maximized = window.is_maximized()?;
+        if self.maximized || window.is_minimized()? {
+            return Ok(());
+        }
+        let position = window.outer_position()?;
+        let size = window.inner_size()?;
+        self.x = position.x;
+        self.y = position.y;
+        self.width = size.width;
+        self.height = size.height;
+        Ok(())
+    }
+
+    /// Move and resize `window` to match
+    ///
+    /// The position is only used if the window would land on a monitor
+    /// that is still connected, so unplugging a display doesn't leave the
+    /// window off screen.
+    pub fn restore<R: Runtime>(&self, window: &Window<R>) -> tauri::Result<()> {
+        if self.width > 0 && self.height > 0 {
+            window.set_size(PhysicalSize::new(self.width, self.height))?;
+        }
+        let on_screen = window.available_monitors()?.iter().any(|monitor| {
+            let (origin, size) = (monitor.position(), monitor.size());
+            (origin.x..origin.x + size.width as i32).contains(&self.x)
+                && (origin.y..origin.y + size.height as i32).contains(&self.y)
+        });
+        if on_screen {
+            window.set_position(PhysicalPosition::new(self.x, self.y))?;
+        }
+        if self.maximized {
+            window.maximize()?;
+        }
+        Ok(())
+    }
+}
@@ -2,6 +2,7 @@ import { useState, useCallback, useEffect, useRef } from "react";
 import Layout from "./components/Layout";
 import { listen } from '@tauri-apps/api/event';
 import { invoke } from '@tauri-apps/api/core';
+import { decodeBinaryFrame } from './utils/ipcUtils';
 import "./App.css";
 
 // Types moved from contexts
@@ -23,6 +24,16 @@ export interface FileState {
   isLoading: boolean;
 }
 
+// Settings the open file was last compiled with, sent along with the menu's Compile; null where unset
+export interface CompileDefaults {
+  depth?: number | null;
+  quality?: 'draft' | 'normal' | 'fine' | null;
+  scale?: number | null;
+  center?: [number, number, number] | null;
+  auto_bounds?: boolean | null;
+  backend?: 'vm' | 'jit' | null;
+}
+
 const DEFAULT_CONTENT = `// Welcome to HorseCAD Rhai Editor
 // Create 3D shapes using fidget functions
 
@@ -199,35 +210,60 @@ function App() {
     }));
   }, []);
 
+  // Quality preset picked from the View menu; overrides the editor's depth when set
+  const qualityRef = useRef<'draft' | 'normal' | 'fine' | null>(null);
+
+  // Newest compile generation seen, so late results from superseded requests are dropped
+  const latestGenerationRef = useRef(0);
+
+  // Open document's path, so script imports resolve next to it
+  const documentPathRef = useRef<string | null>(null);
+  useEffect(() => {
+    documentPathRef.current = fileState.currentFilePath;
+  }, [fileState.currentFilePath]);
+
   // Mesh operations
   const compileScript = useCallback(async (
     code: string,
     depth: number = 6,
     scale: number = 1.0,
-    center: [number, number, number] = [0, 0, 0]
+    center: [number, number, number] = [0, 0, 0],
+    defaults: CompileDefaults = {}
   ) => {
     try {
-      const result = await invoke<{
-        success: boolean;
-        stl_data?: number[];
-        triangle_count?: number;
-        error?: string;
-      }>('compile_script', {
+      const response = await invoke<ArrayBuffer>('compile_script', {
         code,
         depth,
         scale,
         center,
+        quality: defaults.quality ?? qualityRef.current,
+        autoBounds: defaults.auto_bounds,
+        backend: defaults.backend,
+        documentPath: documentPathRef.current,
       });
+      const { header: result, payload } = decodeBinaryFrame<{
+        success: boolean;
+        cancelled: boolean;
+        superseded: boolean;
+        generation: number;
+        triangle_count?: number;
+        error?: string;
+      }>(response);
+
+      if (result.superseded || result.generation < latestGenerationRef.current) {
+        return;
+      }
+      latestGenerationRef.current = result.generation;
 
       if (result.success) {
-        if (!result.stl_data || !result.triangle_count) {
+        if (payload.length === 0 || result.triangle_count === undefined) {
           throw new Error('Invalid STL data received from compilation');
         }
         if (result.triangle_count === 0) {
           throw new Error('No geometry generated - check your script for valid shapes');
         }
 
-        const stlData = new Uint8Array(result.stl_data);
+        const stlData = payload;
         const newMeshData: MeshData = {
           stlData,
           triangleCount: result.triangle_count,
@@ -239,6 +275,11 @@ function App() {
           status: 'succeeded',
           error: undefined,
         });
+      } else if (result.cancelled) {
+        setCompilationState({
+          status: 'cancelled',
+          error: undefined,
+        });
       } else {
         throw new Error(result.error || 'Compilation failed without a specific error message');
       }
@@ -261,9 +302,11 @@ function App() {
       const filePath = await invoke<string | null>('show_stl_save_dialog');
       if (!filePath) return;
 
-      await invoke<boolean>('export_stl_file', {
+      // The backend keeps the compiled mesh, with its units, so nothing is sent back
+      await invoke<boolean>('export_last_mesh', {
         path: filePath,
-        stlData: Array.from(meshData.stlData),
+        format: 'stl',
+        documentPath: documentPathRef.current,
       });
 
       alert('STL file exported successfully!');
@@ -273,6 +316,85 @@ function App() {
     }
   }, [meshData]);
 
+  // Writes the backend's copy of the compiled mesh out in another format
+  const exportMesh = useCallback(async (
+    label: string,
+    dialogCommand: string,
+    format: string,
+    extraOptions: Record<string, unknown> = {}
+  ) => {
+    try {
+      if (!meshData) {
+        alert('No mesh data available. Please compile your script first.');
+        return;
+      }
+
+      const filePath = await invoke<string | null>(dialogCommand);
+      if (!filePath) return;
+
+      await invoke<boolean>('export_last_mesh', {
+        path: filePath,
+        format,
+        documentPath: documentPathRef.current,
+        ...extraOptions,
+      });
+
+      alert(`${label} file exported successfully!`);
+    } catch (error) {
+      console.error(`Failed to export ${label}:`, error);
+      alert(`Failed to export ${label}: ${error}`);
+    }
+  }, [meshData]);
+
+  const exportOBJ = useCallback(
+    () => exportMesh('OBJ', 'show_obj_save_dialog', 'obj'),
+    [exportMesh]
+  );
+
+  const exportPLY = useCallback(
+    () => exportMesh('PLY', 'show_ply_save_dialog', 'ply'),
+    [exportMesh]
+  );
+
+  const exportGLB = useCallback(
+    () => exportMesh('GLB', 'show_glb_save_dialog', 'glb'),
+    [exportMesh]
+  );
+
+  const export3MF = useCallback(
+    () => exportMesh('3MF', 'show_3mf_save_dialog', '3mf', {
+      scriptName: fileState.currentFilePath?.split(/[\\/]/).pop() ?? null,
+    }),
+    [exportMesh, fileState.currentFilePath]
+  );
+
+  const exportParts = useCallback(async () => {
+    try {
+      const folder = await invoke<string | null>('show_folder_dialog');
+      if (!folder) return;
+
+      const result = await invoke<{
+        success: boolean;
+        parts: { name: string; path: string; triangle_count: number }[];
+        error?: string;
+      }>('export_parts', {
+        code: getEditorContentRef.current(),
+        folder,
+        depth: 6,
+        quality: qualityRef.current,
+        documentPath: documentPathRef.current,
+      });
+      if (!result.success) {
+        throw new Error(result.error || 'Part export failed');
+      }
+
+      alert(`Exported ${result.parts.length} parts: ${result.parts.map(p => p.name).join(', ')}`);
+    } catch (error) {
+      console.error('Failed to export parts:', error);
+      alert(`Failed to export parts: ${error}`);
+    }
+  }, []);
+
   // Menu event listeners
   useEffect(() => {
     const unlistenNew = listen('menu_new', () => newFile());
@@ -280,13 +402,25 @@ function App() {
     const unlistenSave = listen('menu_save', () => saveFile());
     const unlistenSaveAs = listen('menu_save_as', () => saveFileAs());
     const unlistenExportSTL = listen('menu_export_stl', () => exportSTL());
+    const unlistenExportOBJ = listen('menu_export_obj', () => exportOBJ());
+    const unlistenExport3MF = listen('menu_export_3mf', () => export3MF());
+    const unlistenExportPLY = listen('menu_export_ply', () => exportPLY());
+    const unlistenExportGLB = listen('menu_export_glb', () => exportGLB());
+    const unlistenExportParts = listen('menu_export_parts', () => exportParts());
+    const unlistenQuality = listen<'draft' | 'normal' | 'fine'>('menu_quality', (event) => {
+      qualityRef.current = event.payload;
+    });
 
-    const unlisteners = [unlistenNew, unlistenOpen, unlistenSave, unlistenSaveAs, unlistenExportSTL];
+    const unlisteners = [
+      unlistenNew, unlistenOpen, unlistenSave, unlistenSaveAs,
+      unlistenExportSTL, unlistenExportOBJ, unlistenExport3MF, unlistenExportPLY, unlistenExportGLB,
+      unlistenExportParts, unlistenQuality,
+    ];
 
     return () => {
       unlisteners.forEach(unlisten => unlisten.then(f => f()));
     };
-  }, [newFile, openFile, saveFile, saveFileAs, exportSTL]);
+  }, [newFile, openFile, saveFile, saveFileAs, exportSTL, exportOBJ, export3MF, exportPLY, exportGLB, exportParts]);
 
   return (
     <Layout
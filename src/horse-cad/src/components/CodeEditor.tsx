@@ -1,9 +1,10 @@
 import React, { useRef, useEffect, useCallback, useState } from 'react';
 import * as monaco from 'monaco-editor';
 import { initMonacoThemes } from '../utils/monacoThemes';
-import { FileState } from '../App';
+import { CompileDefaults, FileState } from '../App';
 import { Badge } from "@/components/ui/badge";
 import { Button } from "@/components/ui/button";
+import { invoke } from '@tauri-apps/api/core';
 import { listen } from '@tauri-apps/api/event';
 import { Box, Boxes, FastForward, Hourglass } from 'lucide-react';
 
@@ -12,13 +13,77 @@ interface CompilationState {
   error?: string;
 }
 
+// Located script error from a compile result
+interface Diagnostic {
+  line: number | null;
+  column: number | null;
+  length: number;
+  message: string;
+  severity: 'error' | 'warning';
+}
+
 interface CodeEditorProps {
   fileState: FileState;
   onContentUpdate: (content: string) => void;
   registerEditorMethods: (getter: () => string, setter: (content: string) => void) => void;
-  onCompileRequest: (code: string, depth?: number, scale?: number, center?: [number, number, number]) => Promise<void>;
+  onCompileRequest: (
+    code: string,
+    depth?: number,
+    scale?: number,
+    center?: [number, number, number],
+    defaults?: CompileDefaults
+  ) => Promise<void>;
+}
+
+// User-defined name reported by get_script_symbols
+interface ScriptSymbol {
+  name: string;
+  kind: 'function' | 'variable' | 'constant';
+  line: number;
+  column: number;
+  scope: string | null;
+  params: string[];
 }
 
+// Script function reported by get_api_reference
+interface ApiFunction {
+  name: string;
+  arity: number;
+  signature: string | null;
+  doc: string | null;
+  source: 'horsecad' | 'fidget';
+}
+
+// The API doesn't change while the app runs, so it's fetched once
+let apiReference: Promise<ApiFunction[]> | undefined;
+const getApiReference = () => {
+  apiReference ??= invoke<ApiFunction[]>('get_api_reference').catch((error) => {
+    apiReference = undefined;
+    throw error;
+  });
+  return apiReference;
+};
+
+// Delay before syntax-checking after the last keystroke
+const CHECK_DEBOUNCE_MS = 300;
+
+// Replace the editor's script markers with `diagnostics`
+const showDiagnostics = (model: monaco.editor.ITextModel, diagnostics: Diagnostic[]) => {
+  const markers = diagnostics.map((d) => {
+    const line = d.line ?? 1;
+    const column = d.column ?? 1;
+    return {
+      startLineNumber: line,
+      startColumn: column,
+      endLineNumber: line,
+      endColumn: d.column === null ? model.getLineMaxColumn(line) : column + d.length,
+      message: d.message,
+      severity: d.severity === 'warning' ? monaco.MarkerSeverity.Warning : monaco.MarkerSeverity.Error,
+    };
+  });
+  monaco.editor.setModelMarkers(model, 'horsecad', markers);
+};
+
 const getMonacoRefValue = (ref: React.RefObject<monaco.editor.IStandaloneCodeEditor | null>) => {
   const monacoCodeValue = ref.current?.getValue();
   if (monacoCodeValue === undefined) {
@@ -43,10 +108,10 @@ const CodeEditor: React.FC<CodeEditorProps> = ({
   });
 
   // Handle compilation with local state management
-  const handleCompile = useCallback(async (code: string, depth: number ) => {
+  const handleCompile = useCallback(async (code: string, depth: number, defaults?: CompileDefaults) => {
     setCompilationState({ status: 'compiling', error: undefined });
     try {
-      await onCompileRequest(code, depth);
+      await onCompileRequest(code, depth, defaults?.scale ?? undefined, defaults?.center ?? undefined, defaults);
       setCompilationState({ status: 'succeeded', error: undefined });
     } catch (error) {
       setCompilationState({ 
@@ -68,11 +133,12 @@ const CodeEditor: React.FC<CodeEditorProps> = ({
     handleCompile(code, 6);
   }, []);
 
-  // Listen for menu compile events
+  // Listen for menu compile events, compiling as the file was last compiled if it says how
   useEffect(() => {
-    const unlistenCompile = listen('menu_compile', () => {
+    const unlistenCompile = listen<CompileDefaults | null>('menu_compile', (event) => {
       const code = getMonacoRefValue(monacoEditorRef);
-      handleCompile(code, 6);
+      const defaults = event.payload ?? undefined;
+      handleCompile(code, defaults?.depth ?? 6, defaults);
     });
 
     const unlisteners = [unlistenCompile];
@@ -82,6 +148,20 @@ const CodeEditor: React.FC<CodeEditorProps> = ({
     };
   }, []);
 
+  // Show script diagnostics from each compile as editor squiggles
+  useEffect(() => {
+    const unlisten = listen<{ diagnostics?: Diagnostic[] }>('compile_result', (event) => {
+      const model = monacoEditorRef.current?.getModel();
+      if (model) {
+        showDiagnostics(model, event.payload.diagnostics ?? []);
+      }
+    });
+
+    return () => {
+      unlisten.then(f => f());
+    };
+  }, []);
+
   // Register editor methods with parent - stable function
   useEffect(() => {
     if (monacoEditorRef.current) {
@@ -176,7 +256,7 @@ const CodeEditor: React.FC<CodeEditorProps> = ({
 
       // Add basic completion provider for Rhai
       monaco.languages.registerCompletionItemProvider('rhai', {
-        provideCompletionItems: (model, position) => {
+        provideCompletionItems: async (model, position) => {
           const word = model.getWordUntilPosition(position);
           const range = {
             startLineNumber: position.lineNumber,
@@ -212,9 +292,57 @@ const CodeEditor: React.FC<CodeEditorProps> = ({
             }
           ];
 
+          // Script functions, plus top-level names defined above the cursor
+          try {
+            const symbols = await invoke<ScriptSymbol[]>('get_script_symbols', { code: model.getValue() });
+            for (const symbol of symbols) {
+              if (symbol.kind !== 'function' && (symbol.scope !== null || symbol.line > position.lineNumber)) {
+                continue;
+              }
+              suggestions.push(symbol.kind === 'function'
+                ? {
+                    label: symbol.name,
+                    kind: monaco.languages.CompletionItemKind.Function,
+                    insertText: `${symbol.name}(${symbol.params.map((p, i) => `\${${i + 1}:${p}}`).join(', ')})`,
+                    insertTextRules: monaco.languages.CompletionItemInsertTextRule.InsertAsSnippet,
+                    detail: `fn ${symbol.name}(${symbol.params.join(', ')})`,
+                    range: range
+                  }
+                : {
+                    label: symbol.name,
+                    kind: symbol.kind === 'constant'
+                      ? monaco.languages.CompletionItemKind.Constant
+                      : monaco.languages.CompletionItemKind.Variable,
+                    insertText: symbol.name,
+                    detail: `${symbol.kind === 'constant' ? 'const' : 'let'} ${symbol.name} (line ${symbol.line})`,
+                    range: range
+                  });
+            }
+          } catch {
+            // Scripts that don't parse yet only get the built-in suggestions
+          }
+
           return { suggestions };
         }
       });
+
+      // Hover docs for built-in script functions
+      monaco.languages.registerHoverProvider('rhai', {
+        provideHover: async (model, position) => {
+          const word = model.getWordAtPosition(position);
+          if (!word) return null;
+          const overloads = (await getApiReference()).filter((f) => f.name === word.word);
+          if (overloads.length === 0) return null;
+          return {
+            range: new monaco.Range(position.lineNumber, word.startColumn, position.lineNumber, word.endColumn),
+            contents: overloads.map((f) => ({
+              value: f.signature
+                ? `\`${f.signature}\`${f.doc ? ` — ${f.doc}` : ''}`
+                : `\`${f.name}\` (${f.arity} argument${f.arity === 1 ? '' : 's'}, from fidget)`,
+            })),
+          };
+        }
+      });
     }
 
     initMonacoThemes();
@@ -240,12 +368,26 @@ const CodeEditor: React.FC<CodeEditorProps> = ({
       tabCompletion: 'on',
     });
 
-    // Add change listener for file state updates only
+    // Add change listener for file state updates and syntax checking
+    let checkTimer: ReturnType<typeof setTimeout> | undefined;
     const changeListener = monacoEditorRef.current.onDidChangeModelContent(() => {
       const code = monacoEditorRef.current?.getValue() || '';
       
       // Update file state with new content
       onContentUpdate(code);
+
+      clearTimeout(checkTimer);
+      checkTimer = setTimeout(async () => {
+        try {
+          const result = await invoke<{ valid: boolean; diagnostics: Diagnostic[] }>('check_script', { code });
+          const model = monacoEditorRef.current?.getModel();
+          if (model && model.getValue() === code) {
+            showDiagnostics(model, result.diagnostics);
+          }
+        } catch (error) {
+          console.error('Syntax check failed:', error);
+        }
+      }, CHECK_DEBOUNCE_MS);
     });
 
     // Focus the editor
@@ -257,6 +399,7 @@ const CodeEditor: React.FC<CodeEditorProps> = ({
 
     // Cleanup
     return () => {
+      clearTimeout(checkTimer);
       changeListener.dispose();
       if (monacoEditorRef.current) {
         monacoEditorRef.current.dispose();
@@ -2,7 +2,7 @@ import React from 'react';
 import CodeEditor from './CodeEditor';
 import ThreeCanvas from './ThreeCanvas';
 import LogPanel from './LogPanel';
-import { FileState, MeshData } from '../App';
+import { CompileDefaults, FileState, MeshData } from '../App';
 import {
   ResizablePanelGroup,
   ResizablePanel,
@@ -17,7 +17,13 @@ interface LayoutProps {
   
   // Mesh props
   meshData: MeshData | null;
-  onCompileRequest: (code: string, depth?: number, scale?: number, center?: [number, number, number]) => Promise<void>;
+  onCompileRequest: (
+    code: string,
+    depth?: number,
+    scale?: number,
+    center?: [number, number, number],
+    defaults?: CompileDefaults
+  ) => Promise<void>;
 }
 
 const Layout: React.FC<LayoutProps> = ({
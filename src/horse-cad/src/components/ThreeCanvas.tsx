@@ -1,7 +1,7 @@
 import React, { useRef, useEffect, useState } from 'react';
 import * as THREE from 'three';
 import { OrbitControls } from 'three-stdlib';
-import { STLParser } from '../utils/stlParser';
+import { STLParser, DEFAULT_MESH_COLOR } from '../utils/stlParser';
 import { MeshData } from '../App';
 import { Button } from "@/components/ui/button";
 import { Badge } from "@/components/ui/badge";
@@ -214,8 +214,10 @@ const ThreeCanvas: React.FC<ThreeCanvasProps> = ({ meshData }) => {
       const newMesh = STLParser.createMeshFromSTL(meshData.stlData);
       newMesh.castShadow = true;
       newMesh.receiveShadow = true;
+      const hasColors = newMesh.geometry.hasAttribute('color');
       newMesh.material = new THREE.MeshLambertMaterial({
-        color: 0xf97316,
+        color: hasColors ? 0xffffff : DEFAULT_MESH_COLOR,
+        vertexColors: hasColors,
         transparent: true,
         opacity: 0.9
       });
@@ -0,0 +1,14 @@
+// Decodes a framed binary IPC response: [u32 LE header length][JSON header][payload]
+export function decodeBinaryFrame<T>(buffer: ArrayBuffer): { header: T; payload: Uint8Array } {
+  const view = new DataView(buffer);
+  const headerLength = view.getUint32(0, true);
+  const headerBytes = new Uint8Array(buffer, 4, headerLength);
+  const header = JSON.parse(new TextDecoder().decode(headerBytes)) as T;
+  const payload = new Uint8Array(buffer, 4 + headerLength);
+  return { header, payload };
+}
+
+// Builds invoke options for commands that take a raw body and JSON options header
+export function jsonHeaderOptions(name: string, value: unknown) {
+  return { headers: { [name]: encodeURIComponent(JSON.stringify(value)) } };
+}
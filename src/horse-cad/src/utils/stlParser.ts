@@ -1,5 +1,8 @@
 import * as THREE from 'three';
 
+/** Viewport color for triangles without a per-facet STL color */
+export const DEFAULT_MESH_COLOR = new THREE.Color(0xf97316);
+
 export interface STLTriangle {
   normal: THREE.Vector3;
   vertices: [THREE.Vector3, THREE.Vector3, THREE.Vector3];
@@ -19,7 +22,7 @@ export class STLParser {
   }
 
   private static parseBinarySTL(data: Uint8Array): THREE.BufferGeometry {
-    const dataView = new DataView(data.buffer);
+    const dataView = new DataView(data.buffer, data.byteOffset, data.byteLength);
     
     // Skip 80-byte header
     let offset = 80;
@@ -30,6 +33,8 @@ export class STLParser {
 
     const positions: number[] = [];
     const normals: number[] = [];
+    const colors: number[] = [];
+    let hasColors = false;
 
     for (let i = 0; i < triangleCount; i++) {
       // Read normal vector (3 floats, 12 bytes)
@@ -49,13 +54,24 @@ export class STLParser {
         normals.push(nx, ny, nz);
       }
 
-      // Skip attribute byte count (2 bytes)
+      // Attribute word: VisCAM/SolidView RGB555 color when the top bit is set
+      const attribute = dataView.getUint16(offset, true);
       offset += 2;
+      const color = (attribute & 0x8000)
+        ? new THREE.Color(((attribute >> 10) & 0x1f) / 31, ((attribute >> 5) & 0x1f) / 31, (attribute & 0x1f) / 31)
+        : DEFAULT_MESH_COLOR;
+      hasColors ||= (attribute & 0x8000) !== 0;
+      for (let j = 0; j < 3; j++) {
+        colors.push(color.r, color.g, color.b);
+      }
     }
 
     const geometry = new THREE.BufferGeometry();
     geometry.setAttribute('position', new THREE.Float32BufferAttribute(positions, 3));
     geometry.setAttribute('normal', new THREE.Float32BufferAttribute(normals, 3));
+    if (hasColors) {
+      geometry.setAttribute('color', new THREE.Float32BufferAttribute(colors, 3));
+    }
 
     // Compute bounding box and center the geometry
     geometry.computeBoundingBox();